@@ -0,0 +1,91 @@
+//! Benchmarks of the paths every signal and call goes through, run
+//! with `cargo bench`. They need `dbus-daemon`, like the tests.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use pris::{Event, EventManager, EventType, Metadata, Player};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use tokio::{runtime::Runtime, sync::Notify};
+
+const CALLBACKS: usize = 50;
+
+fn decode_metadata(c: &mut Criterion) {
+    let msg = common::signal(":1.1", common::PLAYER_INTERFACE, "Metadata")
+        .append1(common::captured_metadata());
+
+    c.bench_function("decode 30-key metadata", |b| {
+        b.iter(|| msg.read1::<Metadata>().unwrap())
+    });
+}
+
+fn parse_properties_changed(c: &mut Criterion) {
+    let msg = common::captured_track_change(":1.1");
+
+    c.bench_function("parse PropertiesChanged", |b| {
+        b.iter(|| Event::parse(&msg, "bench").unwrap())
+    });
+}
+
+fn get_property(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let _entered = runtime.enter();
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let server = runtime.block_on(bus.connect_as("bench"));
+    common::serve_properties(
+        &server,
+        common::props(vec![("Volume", common::var(0.5f64))]),
+    );
+    let player = runtime.block_on(Player::try_new("bench", &conn)).unwrap();
+
+    let player = &player;
+    c.bench_function("get a property", |b| {
+        b.to_async(&runtime)
+            .iter(|| async move { player.get_property::<f64>("Volume").await.unwrap() })
+    });
+}
+
+fn dispatch_fan_out(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let _entered = runtime.enter();
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = runtime.block_on(bus.connect_as("bench"));
+    let manager = EventManager::new(&conn);
+
+    let hits = Arc::new(AtomicUsize::new(0));
+    let delivered = Arc::new(Notify::new());
+    for _ in 0..CALLBACKS {
+        let hits = hits.clone();
+        let delivered = delivered.clone();
+        let guard = runtime.block_on(manager.add_callback(EventType::Seeked, move |_| {
+            if (hits.fetch_add(1, Ordering::SeqCst) + 1).is_multiple_of(CALLBACKS) {
+                delivered.notify_one();
+            }
+            true
+        }));
+        guard.unwrap().detach();
+    }
+
+    let (emitter, delivered) = (&emitter, &delivered);
+    c.bench_function("dispatch to 50 callbacks", |b| {
+        b.to_async(&runtime).iter(|| async move {
+            common::emit(emitter, common::seeked(":1.1", 0i64));
+            delivered.notified().await;
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    decode_metadata,
+    parse_properties_changed,
+    get_property,
+    dispatch_fan_out
+);
+criterion_main!(benches);
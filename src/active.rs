@@ -0,0 +1,190 @@
+use crate::{util::ConnRef, Event, LifecycleEvent, PlaybackStatus, Player, StampedEvent};
+use futures::{
+    stream::{self, BoxStream},
+    Stream, StreamExt,
+};
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+struct Ranked {
+    name: String,
+    playing: bool,
+    /// The sequence number of the player's latest event, or zero if
+    /// it hasn't sent one since tracking started.
+    activity: u64,
+}
+
+/// Players, from the most to the least relevant.
+#[derive(Default)]
+struct Ranking(Vec<Ranked>);
+
+impl Ranking {
+    fn current(&self) -> Option<String> {
+        self.0.first().map(|ranked| ranked.name.clone())
+    }
+
+    /// Records activity from `name`, and its playback status if known.
+    fn touch(&mut self, name: &str, status: Option<PlaybackStatus>, activity: u64) {
+        match self.0.iter_mut().find(|ranked| ranked.name == name) {
+            Some(ranked) => {
+                ranked.activity = activity;
+                if let Some(status) = status {
+                    ranked.playing = status == PlaybackStatus::Playing;
+                }
+            }
+            None => self.0.push(Ranked {
+                name: name.to_string(),
+                playing: status == Some(PlaybackStatus::Playing),
+                activity,
+            }),
+        }
+        self.sort();
+    }
+
+    fn remove(&mut self, name: &str) {
+        self.0.retain(|ranked| ranked.name != name);
+    }
+
+    /// Playing players come first, then the most recently active,
+    /// then ties are broken by name.
+    fn sort(&mut self) {
+        self.0.sort_by(|a, b| {
+            b.playing
+                .cmp(&a.playing)
+                .then(b.activity.cmp(&a.activity))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+    }
+}
+
+/// Keeps track of which player is the most relevant to control,
+/// for instance with media keys, created with
+/// [`EventManager::active_players`](crate::EventManager::active_players).
+///
+/// Players that are playing rank above those that aren't, and
+/// within each group the one that most recently sent a signal
+/// ranks first. Events are ordered by their arrival on the bus, so
+/// players signalling at the same time are ranked in the order
+/// their signals arrived. Players that haven't sent anything since
+/// tracking started are ranked by name.
+///
+/// The ranking is updated as this is polled as a stream, which
+/// yields the name of the current player every time it changes,
+/// starting with the current one.
+///
+/// # Example
+/// ```no_run
+/// # use futures::StreamExt;
+/// # use pris::EventManager;
+/// # async fn example(manager: &EventManager<'_>) -> Result<(), Box<dyn std::error::Error>> {
+/// let mut tracker = manager.active_players().await?;
+/// while let Some(current) = tracker.next().await {
+///     println!("Media keys now control {:?}", current);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct ActivePlayerTracker<'a> {
+    ranking: Arc<Mutex<Ranking>>,
+    changes: BoxStream<'a, Option<String>>,
+}
+
+impl<'a> ActivePlayerTracker<'a> {
+    /// Ranks the players in `seed`, with their current statuses,
+    /// and follows `events` from then on.
+    pub(crate) fn new<S>(
+        conn: ConnRef<'a>,
+        events: S,
+        seed: Vec<(String, Option<PlaybackStatus>)>,
+    ) -> ActivePlayerTracker<'a>
+    where
+        S: Stream<Item = StampedEvent> + Send + 'a,
+    {
+        let mut ranking = Ranking::default();
+        for (name, status) in seed {
+            ranking.touch(&name, status, 0);
+        }
+        let first = ranking.current();
+        let ranking = Arc::new(Mutex::new(ranking));
+
+        let state = (events.boxed(), ranking.clone(), first.clone());
+        let rest = stream::unfold(state, move |(mut events, ranking, mut current)| {
+            let conn = conn.clone();
+            async move {
+                loop {
+                    let stamped = events.next().await?;
+                    // Leave zero to the players that haven't sent anything
+                    let activity = stamped.sequence + 1;
+                    match stamped.event {
+                        Event::PropertiesChanged(changed)
+                            if changed.properties.is_player_interface() =>
+                        {
+                            let status = changed.properties.playback_status;
+                            touch(&ranking, &changed.player, status, activity);
+                        }
+                        Event::Seeked(seeked) => touch(&ranking, &seeked.player, None, activity),
+                        Event::PlayerLifecycle(LifecycleEvent::Vanished { name }) => {
+                            ranking.lock().unwrap().remove(&name);
+                        }
+                        Event::PlayerLifecycle(lifecycle) => {
+                            let name = lifecycle.name().to_string();
+                            let status = match Player::try_with(name.clone(), conn.clone()).await {
+                                Ok(player) => {
+                                    player.get_property::<String>("PlaybackStatus").await.ok()
+                                }
+                                Err(_) => None,
+                            };
+                            let status = status.and_then(|status| status.parse().ok());
+                            touch(&ranking, &name, status, activity);
+                        }
+                        Event::PropertiesChanged(_) => continue,
+                    }
+
+                    let now = ranking.lock().unwrap().current();
+                    if now != current {
+                        current = now.clone();
+                        return Some((now, (events, ranking, current)));
+                    }
+                }
+            }
+        });
+
+        ActivePlayerTracker {
+            ranking,
+            changes: stream::iter([first]).chain(rest).boxed(),
+        }
+    }
+
+    /// The name of the current player, in the same form that is
+    /// passed to [`Player::try_new`], or `None` if there are no
+    /// players.
+    pub fn current(&self) -> Option<String> {
+        self.ranking.lock().unwrap().current()
+    }
+
+    /// The names of every known player, from the most to the least
+    /// relevant.
+    pub fn players(&self) -> Vec<String> {
+        let ranking = self.ranking.lock().unwrap();
+        ranking.0.iter().map(|ranked| ranked.name.clone()).collect()
+    }
+}
+
+impl Stream for ActivePlayerTracker<'_> {
+    type Item = Option<String>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.changes.poll_next_unpin(cx)
+    }
+}
+
+/// Records activity from `name`, unless it couldn't be resolved to
+/// a player, in which case it is reported by its unique name.
+fn touch(ranking: &Mutex<Ranking>, name: &str, status: Option<PlaybackStatus>, activity: u64) {
+    if !name.starts_with(':') {
+        ranking.lock().unwrap().touch(name, status, activity);
+    }
+}
@@ -0,0 +1,522 @@
+//! A blocking counterpart of the core of this crate, for programs
+//! that don't run an async runtime, such as a small CLI.
+//!
+//! It mirrors [`Player`](crate::Player) and
+//! [`get_all_players`](crate::get_all_players) on a
+//! [`dbus::blocking::Connection`], resolving names and reporting
+//! errors the same way. Each call blocks the current thread until
+//! the player answers or its timeout elapses.
+//!
+//! # Example
+//! ```no_run
+//! use pris::blocking::{get_connection, Player};
+//!
+//! fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let conn = get_connection()?;
+//!     let player = Player::try_new("vlc", &conn)?;
+//!     player.play_pause()?;
+//!     Ok(())
+//! }
+//! ```
+use crate::{
+    methods::{self, PropertyReply},
+    player::DEFAULT_TIMEOUT,
+    spec,
+    util::{self, MPRIS_PREFIX},
+    Bus, Error, IntoPlayerName, Micros, PlayerCandidate, PlayerState, Result,
+};
+use dbus::{
+    arg::{Append, AppendAll, Arg, Get, PropMap, RefArg, Variant},
+    blocking::{stdintf::org_freedesktop_dbus::Properties, Connection, Proxy},
+    strings::{BusName, Path},
+};
+use std::time::Duration;
+
+const INTERFACE: &str = spec::PLAYER.name;
+
+/// Establishes a blocking connection to the session bus.
+///
+/// # Errors
+/// May `Err` if the session bus can't be reached.
+pub fn get_connection() -> Result<Connection> {
+    Ok(Connection::new_session()?)
+}
+
+/// Establishes a blocking connection to `bus`.
+///
+/// # Errors
+/// Returns [`Error::Connection`] if the bus couldn't be reached, or
+/// refused the connection.
+pub fn get_connection_to(bus: Bus) -> Result<Connection> {
+    Ok(bus.open()?.into())
+}
+
+/// Whether a player with the exact name `player_name` is on the
+/// bus. Names that aren't valid bus names belong to no player.
+///
+/// # Errors
+/// May `Err` if the bus can't be asked.
+pub fn validate(player_name: &str, conn: &Connection) -> Result<bool> {
+    let name = match util::player_bus_name(player_name) {
+        Some(name) => name,
+        None => return Ok(false),
+    };
+
+    let (exists,): (bool,) =
+        bus(conn).method_call("org.freedesktop.DBus", "NameHasOwner", (&*name,))?;
+    Ok(exists)
+}
+
+/// Same as [`pris::get_all_players`](crate::get_all_players),
+/// blocking.
+///
+/// # Errors
+/// May return an `Err` variant if there was a failure in
+/// getting a list of names from `DBus`.
+pub fn get_all_players(conn: &Connection) -> Result<Vec<Player<'_>>> {
+    let mut players = Vec::new();
+    for name in list_players(conn)? {
+        if let Ok(owner) = get_name_owner(&format!("{}{}", MPRIS_PREFIX, name), conn) {
+            if let Ok(player) = Player::with_owner(name, Some(owner), conn) {
+                players.push(player);
+            }
+        }
+    }
+
+    Ok(players)
+}
+
+/// A proxy for the bus itself.
+fn bus(conn: &Connection) -> Proxy<'_, &Connection> {
+    Proxy::new("org.freedesktop.DBus", "/", Duration::from_secs(1), conn)
+}
+
+/// Same as [`pris::list_players`](crate::list_players), blocking.
+///
+/// # Errors
+/// May `Err` if the bus can't be asked.
+pub fn list_players(conn: &Connection) -> Result<Vec<String>> {
+    let (services,): (Vec<String>,) =
+        bus(conn).method_call("org.freedesktop.DBus", "ListNames", ())?;
+    let mut names = util::player_names(services);
+    names.sort();
+    Ok(names)
+}
+
+fn get_name_owner(name: &str, conn: &Connection) -> Result<String> {
+    let (owner,): (String,) =
+        bus(conn).method_call("org.freedesktop.DBus", "GetNameOwner", (name,))?;
+    Ok(owner)
+}
+
+/// Resolves `name` to the one player it refers to, as described on
+/// [`Player::try_new`].
+fn resolve_name(name: &str, conn: &Connection) -> Result<String> {
+    if validate(name, conn)? {
+        return Ok(name.to_string());
+    }
+
+    let names = list_players(conn)?;
+    let mut instances = util::instances_of(name, &names);
+    match instances.len() {
+        0 => return Err(Error::unknown_player(name, names)),
+        1 => return Ok(instances.remove(0)),
+        _ => {}
+    }
+
+    // Instances that left since being listed aren't in the way
+    let mut candidates = Vec::with_capacity(instances.len());
+    for name in instances {
+        let unique_name = match get_name_owner(&format!("{}{}", MPRIS_PREFIX, name), conn) {
+            Ok(owner) => owner,
+            Err(_) => continue,
+        };
+
+        let proxy = Proxy::new(
+            unique_name.as_str(),
+            "/org/mpris/MediaPlayer2",
+            util::IDENTITY_TIMEOUT,
+            conn,
+        );
+        let identity = proxy
+            .get::<String>("org.mpris.MediaPlayer2", "Identity")
+            .ok();
+
+        candidates.push(PlayerCandidate {
+            name,
+            unique_name,
+            identity,
+        });
+    }
+
+    util::pick_candidate(name, candidates)
+}
+
+/// A blocking counterpart of [`pris::Player`](crate::Player), used to
+/// control an MPRIS player.
+#[derive(Clone)]
+pub struct Player<'a> {
+    /// The name of the player, without the `org.mpris.MediaPlayer2.`
+    /// prefix. Changing it doesn't change where calls are sent.
+    pub name: String,
+    unique: Option<String>,
+    destination: BusName<'static>,
+    path: Path<'static>,
+    conn: &'a Connection,
+    timeout: Duration,
+    volume_ceiling: f64,
+}
+
+impl<'a> Player<'a> {
+    /// Same as [`pris::Player::try_new`](crate::Player::try_new),
+    /// blocking.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidArgument`] if `name` can't be the name
+    /// of a player, [`Error::UnknownPlayer`] if no player goes by
+    /// `name`, or [`Error::AmbiguousPlayer`] if several instances of
+    /// it are running.
+    pub fn try_new<T>(name: T, conn: &'a Connection) -> Result<Player<'a>>
+    where
+        T: IntoPlayerName,
+    {
+        let name = name.into_player_name()?;
+        let name = resolve_name(name.as_str(), conn)?;
+        Player::with_owner(name, None, conn)
+    }
+
+    /// Same as [`pris::Player::try_pinned`](crate::Player::try_pinned),
+    /// blocking.
+    ///
+    /// # Errors
+    /// Same as `try_new`.
+    pub fn try_pinned<T>(name: T, conn: &'a Connection) -> Result<Player<'a>>
+    where
+        T: IntoPlayerName,
+    {
+        let name = name.into_player_name()?;
+        let name = resolve_name(name.as_str(), conn)?;
+        let owner = get_name_owner(&format!("{}{}", MPRIS_PREFIX, name), conn)
+            .map_err(|_| Error::InvalidPlayer(name.clone()))?;
+
+        Player::with_owner(name, Some(owner), conn)
+    }
+
+    /// Same as [`pris::Player::all`](crate::Player::all), blocking.
+    ///
+    /// # Errors
+    /// May `Err` if the bus can't be asked.
+    pub fn all(conn: &'a Connection) -> Result<Vec<Player<'a>>> {
+        Ok(list_players(conn)?
+            .into_iter()
+            .filter_map(|name| Player::with_owner(name, None, conn).ok())
+            .collect())
+    }
+
+    /// A `Player` for the player `name`, pinned to the connection
+    /// `unique` if given.
+    fn with_owner(name: String, unique: Option<String>, conn: &'a Connection) -> Result<Self> {
+        let destination = match &unique {
+            Some(unique) => unique.clone(),
+            None => format!("{}{}", MPRIS_PREFIX, name),
+        };
+        let destination = match BusName::new(destination) {
+            Ok(destination) => destination,
+            Err(_) => return Err(Error::InvalidPlayer(name)),
+        };
+
+        Ok(Player {
+            name,
+            unique,
+            destination,
+            path: Path::from("/org/mpris/MediaPlayer2"),
+            conn,
+            timeout: DEFAULT_TIMEOUT,
+            volume_ceiling: 1.0,
+        })
+    }
+
+    /// The unique name of the connection this `Player` is pinned to,
+    /// if it is, as by [`try_pinned`](Self::try_pinned).
+    pub fn unique_name(&self) -> Option<&str> {
+        self.unique.as_deref()
+    }
+
+    /// Sets how long the player is given to answer each call made
+    /// through this `Player`, before it fails with [`Error::Timeout`].
+    /// The default is 5 seconds.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// How long the player is given to answer each call.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Same as
+    /// [`pris::Player::set_volume_ceiling`](crate::Player::set_volume_ceiling).
+    pub fn set_volume_ceiling(&mut self, ceiling: f64) {
+        self.volume_ceiling = ceiling.max(0.0);
+    }
+
+    /// The highest volume the volume helpers go to.
+    pub fn volume_ceiling(&self) -> f64 {
+        self.volume_ceiling
+    }
+
+    fn proxy(&self) -> Proxy<'_, &Connection> {
+        Proxy::new(&self.destination, &self.path, self.timeout, self.conn)
+    }
+
+    /// Calls the method `member` of the Player interface with `args`.
+    fn call_method<A>(&self, member: &str, args: A) -> Result<()>
+    where
+        A: AppendAll,
+    {
+        self.proxy()
+            .method_call::<(), _, _, _>(INTERFACE, member, args)
+            .map_err(|e| Error::from_call(&self.name, member, self.timeout, e))
+    }
+
+    /// Skips to the next track
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::PlayerGone`] if the `Player` has closed.
+    pub fn next(&self) -> Result<()> {
+        self.call_method("Next", ())
+    }
+
+    /// Skips to the previous track
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::PlayerGone`] if the `Player` has closed.
+    pub fn previous(&self) -> Result<()> {
+        self.call_method("Previous", ())
+    }
+
+    /// Pauses the current track
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::PlayerGone`] if the `Player` has closed,
+    /// or [`Error::UnsupportedOperation`] if it rejects `Pause` as
+    /// unknown or unsupported.
+    pub fn pause(&self) -> Result<()> {
+        self.call_method("Pause", ()).map_err(|e| match e {
+            Error::DBus(e) if methods::is_unsupported(&e) => {
+                methods::cant_pause(&self.name, Some(e))
+            }
+            e => e,
+        })
+    }
+
+    /// Starts or resumes the current track
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::PlayerGone`] if the `Player` has closed.
+    pub fn play(&self) -> Result<()> {
+        self.call_method("Play", ())
+    }
+
+    /// Same as [`pris::Player::play_pause`](crate::Player::play_pause),
+    /// blocking, falling back to `Play` or `Pause` the same way.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::PlayerGone`] if the `Player` has closed,
+    /// or [`Error::UnsupportedOperation`] if the fallback has to pause
+    /// a player that can't be paused.
+    pub fn play_pause(&self) -> Result<()> {
+        match self.call_method("PlayPause", ()) {
+            Err(Error::DBus(e)) if methods::is_unsupported(&e) => {}
+            result => return result,
+        }
+
+        let state = self.get_state()?;
+        if methods::fallback_pauses(&self.name, &state)? {
+            self.pause()
+        } else {
+            self.play()
+        }
+    }
+
+    /// Stops playback
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::PlayerGone`] if the `Player` has closed.
+    pub fn stop(&self) -> Result<()> {
+        self.call_method("Stop", ())
+    }
+
+    /// Retrieves track metadata from the `Player`.
+    ///
+    /// # Errors
+    /// May `Err` if there is a failure in getting the metadata.
+    pub fn get_metadata(&self) -> Result<PropMap> {
+        self.proxy()
+            .get(INTERFACE, "Metadata")
+            .map_err(|e| Error::from_call(&self.name, "Metadata", self.timeout, e))
+    }
+
+    /// Retrieves the metadata of the active track, or `None` if
+    /// nothing is playing, as decided by [`is_no_track`](crate::is_no_track).
+    ///
+    /// # Errors
+    /// May `Err` if there is a failure in getting the metadata.
+    pub fn metadata(&self) -> Result<Option<PropMap>> {
+        let metadata = self.get_metadata()?;
+        Ok(Some(metadata).filter(|m| !util::is_no_track(m)))
+    }
+
+    /// Retrieves all of the `Player`'s properties at once.
+    ///
+    /// # Errors
+    /// May `Err` if there is a failure in getting the properties.
+    pub fn get_state(&self) -> Result<PlayerState> {
+        let properties = self
+            .proxy()
+            .get_all(INTERFACE)
+            .map_err(|e| Error::from_call(&self.name, "GetAll", self.timeout, e))?;
+        Ok(PlayerState::from_properties(self.name.clone(), properties))
+    }
+
+    /// Same as [`pris::Player::get_property`](crate::Player::get_property),
+    /// blocking.
+    ///
+    /// # Errors
+    /// May return an `Err` variant if:
+    /// * An invalid type was provided for the property, as
+    ///   [`Error::TypeMismatch`]
+    /// * An invalid property was provided, as [`Error::UnknownProperty`]
+    pub fn get_property<T>(&self, property: &str) -> Result<T>
+    where
+        T: for<'c> Get<'c> + 'static,
+    {
+        let PropertyReply(value) = self
+            .proxy()
+            .method_call::<PropertyReply<T>, _, _, _>(
+                "org.freedesktop.DBus.Properties",
+                "Get",
+                (INTERFACE, property),
+            )
+            .map_err(|e| Error::from_property_call(&self.name, property, self.timeout, false, e))?;
+
+        value.map_err(|actual| Error::TypeMismatch {
+            player: self.name.clone(),
+            property: property.to_string(),
+            expected: std::any::type_name::<T>().to_string(),
+            actual,
+        })
+    }
+
+    /// Same as [`pris::Player::set_property`](crate::Player::set_property),
+    /// blocking.
+    ///
+    /// # Errors
+    /// May return an `Err` variant if:
+    /// * An invalid type was provided for the property
+    /// * An invalid property was provided, as [`Error::UnknownProperty`]
+    pub fn set_property<T>(&self, property: &str, value: T) -> Result<()>
+    where
+        T: Arg + Append,
+    {
+        self.proxy()
+            .method_call::<(), _, _, _>(
+                "org.freedesktop.DBus.Properties",
+                "Set",
+                (INTERFACE, property, Variant(value)),
+            )
+            .map_err(|e| Error::from_property_call(&self.name, property, self.timeout, true, e))
+    }
+
+    /// Retrieves the volume as the player reports it, even above
+    /// the [volume ceiling](Self::set_volume_ceiling).
+    ///
+    /// # Errors
+    /// May `Err` if there is a failure in getting the volume.
+    pub fn get_volume(&self) -> Result<f64> {
+        self.get_property("Volume")
+    }
+
+    /// Sets the volume, clamped to between 0.0 and the
+    /// [volume ceiling](Self::set_volume_ceiling).
+    /// Returns the volume that was set.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::InvalidArgument`] if `volume` is NaN.
+    pub fn set_volume(&self, volume: f64) -> Result<f64> {
+        let volume = methods::clamp_volume(volume, self.volume_ceiling)?;
+        self.set_property("Volume", volume)?;
+        Ok(volume)
+    }
+
+    /// Changes the volume by `delta`, clamped like `set_volume`.
+    /// Returns the volume that was set.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::InvalidArgument`] if `delta` is NaN.
+    pub fn adjust_volume(&self, delta: f64) -> Result<f64> {
+        let volume = self.get_volume()?;
+        self.set_volume(volume + delta)
+    }
+
+    /// Retrieves the position of the active track. Positions of any
+    /// integer type are accepted, and negative ones are clamped to
+    /// zero.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::TypeMismatch`] if the position isn't
+    /// an integer.
+    pub fn get_position(&self) -> Result<Duration> {
+        let value: Box<dyn RefArg> = self.get_property("Position")?;
+        methods::read_position(&self.name, &*value)
+    }
+
+    /// Seeks the position of the active track. Offsets too large to
+    /// send are saturated, at some 292 thousand years.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::NoActiveTrack`] if there is no active track.
+    pub fn seek(&self, offset: Duration) -> Result<()> {
+        self.active_track()?;
+        self.call_method("Seek", (Micros::from_duration(offset),))
+    }
+
+    /// Same as `seek`, but in reverse.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::NoActiveTrack`] if there is no active track.
+    pub fn seek_reverse(&self, offset: Duration) -> Result<()> {
+        self.active_track()?;
+        self.call_method("Seek", (-Micros::from_duration(offset),))
+    }
+
+    /// Sets the position of the current track. Positions too large
+    /// to send are saturated, at some 292 thousand years.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::NoActiveTrack`] if there is no active track,
+    /// or [`Error::UnsupportedOperation`] if the player doesn't report the
+    /// id of its track.
+    pub fn set_position(&self, position: Duration) -> Result<()> {
+        let metadata = self.active_track()?;
+        let track_id = methods::track_id(&self.name, &metadata)?;
+        self.call_method("SetPosition", (track_id, Micros::from_duration(position)))
+    }
+
+    /// Opens a track by its URI.
+    ///
+    /// # Errors
+    /// May return an `Err` variant if the provided URI is invalid.
+    pub fn open_uri(&self, uri: &str) -> Result<()> {
+        self.call_method("OpenUri", (uri,))
+    }
+
+    /// Retrieves the metadata of the active track.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::NoActiveTrack`] if nothing is playing.
+    fn active_track(&self) -> Result<PropMap> {
+        self.metadata()?
+            .ok_or_else(|| Error::NoActiveTrack(self.name.clone()))
+    }
+}
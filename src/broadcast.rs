@@ -0,0 +1,152 @@
+use crate::{runtime, trace, util, Error, Guarded, PlaybackStatus, Player, Result};
+use dbus::nonblock::SyncConnection;
+use futures::future;
+use std::{collections::BTreeMap, future::Future, time::Duration};
+
+/// What a broadcast command did to each player, by name: whether it
+/// was sent, or why it failed.
+pub type BroadcastResults = BTreeMap<String, Result<Guarded>>;
+
+/// Runs `command` on every one of `players` at once, giving each
+/// `limit` to answer, so that one hung player doesn't hold up the
+/// others.
+async fn broadcast<'a, T, F, Fut>(
+    players: &[Player<'a>],
+    operation: &str,
+    limit: Duration,
+    command: F,
+) -> Vec<(String, Result<T>)>
+where
+    F: Fn(Player<'a>) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    future::join_all(players.iter().map(|player| {
+        let sent = command(player.clone());
+        async move {
+            let result = match runtime::timeout(limit, sent).await {
+                Ok(result) => result,
+                Err(_) => {
+                    trace::event!(
+                        debug,
+                        player = %player.name,
+                        operation,
+                        limit_ms = limit.as_millis() as u64,
+                        "timed out"
+                    );
+                    Err(Error::Timeout {
+                        player: Some(player.name.clone()),
+                        operation: operation.to_string(),
+                        limit,
+                        source: None,
+                    })
+                }
+            };
+            (player.name.clone(), result)
+        }
+    }))
+    .await
+}
+
+/// Pauses every player that is playing, as with
+/// [`Player::try_pause`], and remembers which were paused so that
+/// [`PausedPlayers::resume`] plays only those again.
+///
+/// Players are paused at once, each given `limit` to answer. One
+/// failing or not answering in time only fails its own result, and
+/// players that aren't playing are left alone, and out of the
+/// results.
+///
+/// # Errors
+/// May `Err` if the players can't be listed.
+///
+/// # Example
+/// ```no_run
+/// # use std::time::Duration;
+/// # async fn example() -> pris::Result<()> {
+/// let conn = pris::get_connection();
+/// let paused = pris::pause_all(&conn, Duration::from_secs(1)).await?;
+/// // ...the meeting...
+/// for (name, result) in paused.resume(Duration::from_secs(1)).await {
+///     if let Err(e) = result {
+///         println!("Couldn't resume {}: {}", name, e);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn pause_all(conn: &SyncConnection, limit: Duration) -> Result<PausedPlayers<'_>> {
+    let players = util::get_all_players(conn).await?;
+    let paused = broadcast(&players, "Pause", limit, |player| async move {
+        let status: String = player.get_property("PlaybackStatus").await?;
+        match status.parse::<PlaybackStatus>() {
+            Ok(PlaybackStatus::Playing) => player.try_pause().await.map(Some),
+            _ => Ok(None),
+        }
+    })
+    .await;
+
+    let results: BroadcastResults = paused
+        .into_iter()
+        .filter_map(|(name, result)| Some((name, result.transpose()?)))
+        .collect();
+    let players = players
+        .into_iter()
+        .filter(|player| matches!(results.get(&player.name), Some(Ok(guarded)) if guarded.sent))
+        .collect();
+
+    Ok(PausedPlayers { players, results })
+}
+
+/// Plays every player, as with [`Player::try_play`], each given
+/// `limit` to answer. One failing or not answering in time only
+/// fails its own result.
+///
+/// # Errors
+/// May `Err` if the players can't be listed.
+pub async fn play_all(conn: &SyncConnection, limit: Duration) -> Result<BroadcastResults> {
+    let players = util::get_all_players(conn).await?;
+    let played = broadcast(&players, "Play", limit, |player| async move {
+        player.try_play().await
+    })
+    .await;
+
+    Ok(played.into_iter().collect())
+}
+
+/// The players paused by [`pause_all`], to be played again with
+/// [`resume`](Self::resume).
+///
+/// Each is pinned to the connection that owned it when it was
+/// paused, so a player that restarted since isn't resumed.
+pub struct PausedPlayers<'a> {
+    players: Vec<Player<'a>>,
+    results: BroadcastResults,
+}
+
+impl PausedPlayers<'_> {
+    /// What pausing did to each player that was playing.
+    pub fn results(&self) -> &BroadcastResults {
+        &self.results
+    }
+
+    /// The names of the players that were paused.
+    pub fn names(&self) -> Vec<&str> {
+        self.players
+            .iter()
+            .map(|player| player.name.as_str())
+            .collect()
+    }
+
+    /// Plays the players that were paused again, as with
+    /// [`Player::try_play`], each given `limit` to answer. Players
+    /// that weren't paused are left alone, even if they were paused
+    /// by something else since.
+    pub async fn resume(self, limit: Duration) -> BroadcastResults {
+        let played = broadcast(&self.players, "Play", limit, |player| async move {
+            player.try_play().await
+        })
+        .await;
+
+        played.into_iter().collect()
+    }
+}
@@ -0,0 +1,325 @@
+use crate::{
+    guard, util, CallbackGuard, Error, EventManager, EventType, Guarded, LifecycleEvent, Player,
+    Result,
+};
+use dbus::{
+    arg::{Get, PropMap, RefArg, Variant},
+    message::{MatchRule, Message},
+    strings::BusName,
+};
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+/// The property that changes without being signalled, and so is
+/// never cached.
+const POSITION: &str = "Position";
+
+/// The values a [`CachedPlayer`] remembers, kept up to date by its
+/// callbacks.
+#[derive(Default)]
+struct PropertyCache {
+    values: PropMap,
+    /// Bumped on every change, so that a value fetched before one
+    /// isn't cached after it.
+    generation: u64,
+    tracking: bool,
+}
+
+impl PropertyCache {
+    /// Applies a `PropertiesChanged` signal: changed values replace
+    /// the cached ones, and invalidated ones are forgotten.
+    fn apply(&mut self, msg: &Message) {
+        let (interface, changed, invalidated): (&str, PropMap, Vec<String>) = match msg.read3() {
+            Ok(args) => args,
+            Err(_) => return,
+        };
+        if interface != PLAYER_INTERFACE {
+            return;
+        }
+
+        self.generation += 1;
+        for (name, value) in changed {
+            if name != POSITION {
+                self.values.insert(name, value);
+            }
+        }
+        for name in invalidated {
+            self.values.remove(&name);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
+        self.generation += 1;
+    }
+}
+
+/// A [`Player`] whose property reads are answered from a local cache
+/// when possible, rather than with a round trip to the player.
+///
+/// Values are cached the first time they are read, and then replaced
+/// or forgotten as the player's `PropertiesChanged` signals report
+/// them changed or invalidated. `Position` is never cached, since
+/// players don't signal it as it advances, and always goes to the
+/// player.
+///
+/// # Staleness
+/// A cached value is only as fresh as the player's signals. It can
+/// be out of date:
+/// * For properties the player changes without signalling them, as
+///   some do for `Volume` or `Metadata`.
+/// * While the [`EventManager`] is [paused](EventManager::pause)
+///   with a policy that drops signals, or after it was
+///   [rebound](EventManager::rebind) to another connection.
+///
+/// [`refresh`](Self::refresh) reads a property from the player
+/// whatever is cached, and [`clear`](Self::clear) forgets every
+/// value. Once the player leaves the bus or its name changes owner,
+/// nothing is cached anymore and every read goes to the player.
+///
+/// # Example
+/// ```no_run
+/// # use pris::{CachedPlayer, EventManager, Player};
+/// # async fn example(manager: &EventManager<'_>, player: &Player<'_>) -> Result<(), Box<dyn std::error::Error>> {
+/// let cached = CachedPlayer::new(manager, player).await?;
+/// // Only the first read is a round trip, until the volume changes
+/// for _ in 0..10 {
+///     let volume: f64 = cached.get_property("Volume").await?;
+///     println!("At {}", volume);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct CachedPlayer<'a> {
+    player: Player<'a>,
+    cache: Arc<Mutex<PropertyCache>>,
+    _callbacks: [CallbackGuard<'a>; 2],
+}
+
+impl<'a> CachedPlayer<'a> {
+    /// Starts caching the properties of `player`, with callbacks on
+    /// `manager` that are removed when the `CachedPlayer` is dropped.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the player isn't on the bus, or if there
+    /// is a failure in adding the callbacks.
+    pub async fn new(manager: &EventManager<'a>, player: &Player<'a>) -> Result<CachedPlayer<'a>> {
+        let conn = player.connection();
+        let bus_name = format!("{}{}", util::MPRIS_PREFIX, player.name);
+        let owner = util::get_name_owner(&bus_name, &conn).await?;
+        let cache = Arc::new(Mutex::new(PropertyCache {
+            tracking: true,
+            ..PropertyCache::default()
+        }));
+
+        let rule = MatchRule::new_signal(PROPERTIES_INTERFACE, "PropertiesChanged")
+            .with_path(MPRIS_PATH)
+            .with_sender(BusName::new(owner.clone()).map_err(Error::Parse)?);
+        let changes = {
+            let cache = cache.clone();
+            manager
+                .add_raw_match(rule, move |msg| {
+                    let mut cache = cache.lock().unwrap();
+                    cache.apply(&msg);
+                    cache.tracking
+                })
+                .await?
+        };
+
+        let lifecycle = {
+            let cache = cache.clone();
+            let name = player.name.clone();
+            manager
+                .add_callback(
+                    EventType::PlayerLifecycle,
+                    move |msg| match LifecycleEvent::parse(&msg) {
+                        Ok(event) if event.name() == name => {
+                            let mut cache = cache.lock().unwrap();
+                            cache.tracking = false;
+                            cache.clear();
+                            false
+                        }
+                        _ => true,
+                    },
+                )
+                .await?
+        };
+
+        // The player may have left before the lifecycle match was added
+        if util::get_name_owner(&bus_name, &conn).await.ok() != Some(owner) {
+            return Err(Error::InvalidPlayer(player.name.clone()));
+        }
+
+        Ok(CachedPlayer {
+            player: player.clone(),
+            cache,
+            _callbacks: [changes, lifecycle],
+        })
+    }
+
+    /// The player the properties are read from.
+    pub fn player(&self) -> &Player<'a> {
+        &self.player
+    }
+
+    /// Whether signals are still being followed, which stops once
+    /// the player leaves the bus or its name changes owner.
+    pub fn is_tracking(&self) -> bool {
+        self.cache.lock().unwrap().tracking
+    }
+
+    /// Retrieves the value of an MPRIS property, from the cache if
+    /// it holds it, or else from the player, caching it.
+    ///
+    /// # Errors
+    /// Same as [`Player::get_property`].
+    pub async fn get_property<T>(&self, property: &str) -> Result<T>
+    where
+        T: for<'c> Get<'c> + 'static,
+    {
+        if property == POSITION {
+            return self.player.get_property(property).await;
+        }
+
+        let (value, _) = self.lookup(property).await?;
+        self.read_as(property, value)
+    }
+
+    /// Retrieves the value of an MPRIS property from the player,
+    /// whatever is cached, and caches it.
+    ///
+    /// # Errors
+    /// Same as [`Player::get_property`].
+    pub async fn refresh<T>(&self, property: &str) -> Result<T>
+    where
+        T: for<'c> Get<'c> + 'static,
+    {
+        let value = self.fetch(property).await?;
+        self.read_as(property, value)
+    }
+
+    /// Forgets every cached value, so that each is read from the
+    /// player again.
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
+    /// Skips to the next track, if the player reports `CanControl`
+    /// and `CanGoNext`, like [`Player::try_next`] but reading them
+    /// through the cache. Once they are cached, this takes a single
+    /// round trip, until a signal reports them changed.
+    ///
+    /// `CanControl` isn't signalled, as players don't change it, so
+    /// it stays cached for as long as signals are followed.
+    ///
+    /// # Errors
+    /// Same as [`Player::try_next`].
+    pub async fn try_next(&self) -> Result<Guarded> {
+        self.guarded("CanGoNext", self.player.next()).await
+    }
+
+    /// Same as [`try_next`](Self::try_next), skipping to the
+    /// previous track if the player reports `CanGoPrevious`.
+    pub async fn try_previous(&self) -> Result<Guarded> {
+        self.guarded("CanGoPrevious", self.player.previous()).await
+    }
+
+    /// Same as [`try_next`](Self::try_next), pausing if the player
+    /// reports `CanPause`.
+    pub async fn try_pause(&self) -> Result<Guarded> {
+        self.guarded("CanPause", self.player.pause()).await
+    }
+
+    /// Same as [`try_next`](Self::try_next), playing if the player
+    /// reports `CanPlay`.
+    pub async fn try_play(&self) -> Result<Guarded> {
+        self.guarded("CanPlay", self.player.play()).await
+    }
+
+    /// Same as [`try_next`](Self::try_next), toggling playback if
+    /// the player reports `CanPause`.
+    pub async fn try_play_pause(&self) -> Result<Guarded> {
+        self.guarded("CanPause", self.player.play_pause()).await
+    }
+
+    /// Same as [`try_next`](Self::try_next), stopping if the player
+    /// reports `CanControl`.
+    pub async fn try_stop(&self) -> Result<Guarded> {
+        self.guarded("CanControl", self.player.stop()).await
+    }
+
+    /// Same as [`try_next`](Self::try_next), seeking if the player
+    /// reports `CanSeek`.
+    pub async fn try_seek(&self, offset: Duration) -> Result<Guarded> {
+        self.guarded("CanSeek", self.player.seek(offset)).await
+    }
+
+    /// Runs `command` if the player reports `capability`, reading it
+    /// through the cache.
+    async fn guarded<C>(&self, capability: &'static str, command: C) -> Result<Guarded>
+    where
+        C: Future<Output = Result<()>>,
+    {
+        let read = |name: &'static str| async move {
+            let (value, cached) = self.lookup(name).await?;
+            Result::Ok((self.read_as::<bool>(name, value)?, cached))
+        };
+        guard::run(capability, read, command).await
+    }
+
+    /// Reads `property` from the cache if it holds it, or else from
+    /// the player, caching it. Also returns whether it was cached.
+    async fn lookup(&self, property: &str) -> Result<(Box<dyn RefArg>, bool)> {
+        let cached = {
+            let cache = self.cache.lock().unwrap();
+            cache.values.get(property).map(|value| value.0.box_clone())
+        };
+        match cached {
+            Some(value) => Ok((value, true)),
+            None => Ok((self.fetch(property).await?, false)),
+        }
+    }
+
+    /// Reads `property` from the player, caching it unless it
+    /// changed in the meantime.
+    async fn fetch(&self, property: &str) -> Result<Box<dyn RefArg>> {
+        let generation = self.cache.lock().unwrap().generation;
+        let value: Box<dyn RefArg> = self.player.get_property(property).await?;
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.tracking && cache.generation == generation && property != POSITION {
+            cache
+                .values
+                .insert(property.to_string(), Variant(value.box_clone()));
+        }
+        Ok(value)
+    }
+
+    /// Reads `value` as a `T`, going through a message as the value
+    /// would have if it came from the player.
+    fn read_as<T>(&self, property: &str, value: Box<dyn RefArg>) -> Result<T>
+    where
+        T: for<'c> Get<'c> + 'static,
+    {
+        let actual = value.signature().to_string();
+        let msg = Message::new_signal(MPRIS_PATH, PLAYER_INTERFACE, "Cached")
+            .map_err(Error::Parse)?
+            .append1(Variant(value));
+
+        msg.read1::<Variant<T>>()
+            .map(|Variant(value)| value)
+            .map_err(|_| Error::TypeMismatch {
+                player: self.player.name.clone(),
+                property: property.to_string(),
+                expected: std::any::type_name::<T>().to_string(),
+                actual,
+            })
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{runtime, Event, LifecycleEvent, PropertiesChangedEvent};
+use futures::{
+    future,
+    stream::{self, BoxStream},
+    Stream, StreamExt,
+};
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// A stream of events in which bursts of `PropertiesChanged`
+/// signals are merged, created with [`coalesce`] or
+/// [`EventStream::coalesce`](crate::EventStream::coalesce).
+pub struct Coalesced<'a>(BoxStream<'a, Event>);
+
+impl Stream for Coalesced<'_> {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Event>> {
+        self.0.poll_next_unpin(cx)
+    }
+}
+
+/// A merged `PropertiesChanged` event waiting for its burst to settle.
+struct Pending {
+    event: PropertiesChangedEvent,
+    deadline: Instant,
+}
+
+struct State<'a> {
+    events: Option<BoxStream<'a, Event>>,
+    quiet: Duration,
+    pending: Vec<Pending>,
+    ready: VecDeque<Event>,
+}
+
+impl State<'_> {
+    fn absorb(&mut self, event: Event) {
+        match event {
+            Event::PropertiesChanged(event) => {
+                let deadline = Instant::now() + self.quiet;
+                let burst = self.pending.iter_mut().find(|p| {
+                    p.event.player == event.player
+                        && p.event.properties.interface == event.properties.interface
+                });
+                match burst {
+                    Some(burst) => {
+                        burst.event.properties.merge(event.properties);
+                        burst.deadline = deadline;
+                    }
+                    None => self.pending.push(Pending { event, deadline }),
+                }
+            }
+            Event::PlayerLifecycle(LifecycleEvent::Vanished { ref name }) => {
+                // Whatever the player changed before leaving comes first
+                let name = name.clone();
+                self.flush(|p| p.event.player == name);
+                self.ready.push_back(event);
+            }
+            event => self.ready.push_back(event),
+        }
+    }
+
+    /// Moves the pending events selected by `settled` to `ready`,
+    /// in the order their bursts started.
+    fn flush<F>(&mut self, mut settled: F)
+    where
+        F: FnMut(&Pending) -> bool,
+    {
+        let mut i = 0;
+        while i < self.pending.len() {
+            if settled(&self.pending[i]) {
+                let pending = self.pending.remove(i);
+                self.ready
+                    .push_back(Event::PropertiesChanged(pending.event));
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    async fn next(&mut self) -> Option<Event> {
+        loop {
+            if let Some(event) = self.ready.pop_front() {
+                return Some(event);
+            }
+
+            let now = Instant::now();
+            self.flush(|p| p.deadline <= now);
+            if !self.ready.is_empty() {
+                continue;
+            }
+
+            let events = match self.events.as_mut() {
+                Some(events) => events,
+                None => {
+                    self.flush(|_| true);
+                    if self.ready.is_empty() {
+                        return None;
+                    }
+                    continue;
+                }
+            };
+
+            let deadline = self.pending.iter().map(|p| p.deadline).min();
+            let settled = async {
+                match deadline {
+                    Some(deadline) => runtime::sleep_until(deadline).await,
+                    None => future::pending().await,
+                }
+            };
+            tokio::select! {
+                event = events.next() => match event {
+                    Some(event) => self.absorb(event),
+                    None => self.events = None,
+                },
+                _ = settled => {}
+            }
+        }
+    }
+}
+
+/// Merges bursts of `PropertiesChanged` events from `events`.
+///
+/// Events from the same player and interface are held back until
+/// none have arrived for `quiet`, then emitted as one event with the
+/// changes merged by [`ChangedProperties::merge`](crate::ChangedProperties::merge).
+/// Other events are passed through straight away, so they may
+/// overtake a burst that hasn't settled yet; a burst is emitted
+/// before its player is reported as vanished, though.
+///
+/// # Example
+/// ```no_run
+/// # use futures::StreamExt;
+/// # use pris::{EventManager, EventType};
+/// # use std::time::Duration;
+/// # async fn example(manager: &EventManager<'_>) -> Result<(), Box<dyn std::error::Error>> {
+/// let mut events = manager
+///     .stream(&[EventType::PropertiesChanged])
+///     .await?
+///     .coalesce(Duration::from_millis(100));
+/// while let Some(event) = events.next().await {
+///     println!("{:?}", event);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn coalesce<'a, S>(events: S, quiet: Duration) -> Coalesced<'a>
+where
+    S: Stream<Item = Event> + Send + 'a,
+{
+    let state = State {
+        events: Some(events.boxed()),
+        quiet,
+        pending: Vec::new(),
+        ready: VecDeque::new(),
+    };
+
+    Coalesced(
+        stream::unfold(state, |mut state| async move {
+            let event = state.next().await?;
+            Some((event, state))
+        })
+        .boxed(),
+    )
+}
@@ -0,0 +1,184 @@
+use crate::{runtime, Error, Result};
+use dbus::{
+    channel::{BusType, Channel},
+    nonblock::SyncConnection,
+};
+use futures::future::{self, AbortHandle};
+use std::{fmt, future::Future, ops::Deref, pin::Pin, sync::Arc};
+
+/// A bus to connect to with [`connect_to`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Bus {
+    /// The bus of the user's session, where players usually are.
+    Session,
+    /// The bus shared by the whole system, which services running
+    /// outside of a session may export their players on.
+    System,
+    /// The bus at an address such as `unix:path=/run/dbus/socket`.
+    Address(String),
+}
+
+impl Bus {
+    /// Opens a private channel to the bus, and registers with it.
+    pub(crate) fn open(&self) -> Result<Channel> {
+        let channel = match self {
+            Bus::Session => Channel::get_private(BusType::Session),
+            Bus::System => Channel::get_private(BusType::System),
+            Bus::Address(address) => Channel::open_private(address).and_then(|mut channel| {
+                channel.register()?;
+                Ok(channel)
+            }),
+        };
+
+        channel.map_err(|source| Error::Connection {
+            bus: self.clone(),
+            source,
+        })
+    }
+}
+
+impl fmt::Display for Bus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bus::Session => f.write_str("the session bus"),
+            Bus::System => f.write_str("the system bus"),
+            Bus::Address(address) => write!(f, "the bus at {}", address),
+        }
+    }
+}
+
+/// A connection to a bus together with the task handling its
+/// traffic, made with [`connect`] or [`connect_to`].
+///
+/// It dereferences to the [`SyncConnection`] it wraps, so it can be
+/// passed to [`Player::try_new`](crate::Player::try_new) and the like
+/// as `&conn`. Dropping it stops the task, after which the clones
+/// handed out by [`shared`](Self::shared) no longer get any traffic
+/// through.
+pub struct Connection {
+    conn: Arc<SyncConnection>,
+    driver: AbortHandle,
+    lost: Option<Pin<Box<dyn Future<Output = Option<dbus::Error>> + Send + Sync>>>,
+}
+
+/// Connects to the session bus, and starts a task handling the
+/// connection's traffic on the current runtime.
+///
+/// Unlike [`get_connection`](crate::get_connection), losing the
+/// connection doesn't panic, but is reported by
+/// [`Connection::closed`].
+///
+/// # Errors
+/// Returns [`Error::Connection`] if the bus couldn't be reached.
+///
+/// # Panics
+/// Panics if called outside of a tokio runtime, unless the `smol`
+/// feature is enabled.
+///
+/// # Examples
+/// ```no_run
+/// use pris::Player;
+///
+/// # async fn example() -> pris::Result<()> {
+/// let conn = pris::connect()?;
+/// let player = Player::try_new("vlc", &conn).await?;
+/// player.play_pause().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn connect() -> Result<Connection> {
+    connect_to(Bus::Session)
+}
+
+/// Connects to `bus`, and starts a task handling the connection's
+/// traffic on the current runtime, like [`connect`].
+///
+/// Looking players up, discovery and
+/// [`EventManager`](crate::EventManager) work the same over any bus.
+///
+/// # Errors
+/// Returns [`Error::Connection`] if the bus couldn't be reached, or
+/// refused the connection.
+///
+/// # Panics
+/// Panics if called outside of a tokio runtime, unless the `smol`
+/// feature is enabled.
+///
+/// # Examples
+/// Reaching a bus inside a container by its address:
+/// ```no_run
+/// use pris::{Bus, Player};
+///
+/// # async fn example() -> pris::Result<()> {
+/// let conn = pris::connect_to(Bus::Address("unix:path=/run/player/bus".to_string()))?;
+/// let player = Player::try_new("vlc", &conn).await?;
+/// player.play_pause().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn connect_to(bus: Bus) -> Result<Connection> {
+    let channel = bus.open()?;
+    let (conn, driver) = runtime::driven(channel)?;
+    let (driver, abort) = future::abortable(driver);
+    let task = runtime::spawn(driver);
+
+    Ok(Connection {
+        conn,
+        driver: abort,
+        lost: Some(Box::pin(
+            async move { task.await.and_then(|lost| lost.ok()) },
+        )),
+    })
+}
+
+impl Connection {
+    /// The underlying connection, for calls pris doesn't wrap.
+    pub fn as_dbus(&self) -> &SyncConnection {
+        &self.conn
+    }
+
+    /// A clone of the underlying connection, for the types that
+    /// own theirs, like [`OwnedEventManager`](crate::OwnedEventManager).
+    pub fn shared(&self) -> Arc<SyncConnection> {
+        self.conn.clone()
+    }
+
+    /// Waits until the connection is lost, returning the error it was
+    /// lost to. Once it has returned, it returns
+    /// [`Error::Disconnected`] straight away.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # async fn example() -> pris::Result<()> {
+    /// let mut conn = pris::connect()?;
+    /// tokio::select! {
+    ///     error = conn.closed() => eprintln!("{}", error),
+    ///     _ = tokio::signal::ctrl_c() => {}
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn closed(&mut self) -> Error {
+        let lost = match &mut self.lost {
+            Some(lost) => lost.await,
+            None => None,
+        };
+        self.lost = None;
+
+        lost.map_or(Error::Disconnected, Error::DBus)
+    }
+}
+
+impl Deref for Connection {
+    type Target = SyncConnection;
+
+    fn deref(&self) -> &SyncConnection {
+        &self.conn
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.driver.abort();
+    }
+}
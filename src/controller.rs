@@ -0,0 +1,620 @@
+use crate::{
+    snapshot_players, status_rank, ActivePlayerTracker, ChangedProperties, Error, Event,
+    EventManager, EventStream, EventType, IntoPlayerName, LifecycleEvent, PlaybackStatus, Player,
+    Result, SnapshotOptions, TrackChange,
+};
+use dbus::nonblock::SyncConnection;
+use futures::{
+    channel::{
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
+    future::{AbortHandle, Abortable},
+    stream::{BoxStream, SelectAll},
+    Future, StreamExt,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// How a [`Controller`] picks its current player.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum CurrentPolicy {
+    /// Players that are playing first, and within each group the one
+    /// that most recently sent a signal, as with
+    /// [`ActivePlayerTracker`](crate::ActivePlayerTracker). Players
+    /// that haven't sent anything since are ranked by name.
+    #[default]
+    LastActive,
+    /// Playing players first, then paused and then stopped ones, as
+    /// ranked by [`status_rank`], and by name among equals.
+    Status,
+    /// The first of these players that is running, such as `mpd`,
+    /// where a name also matches the instances of the player, such
+    /// as `firefox.instance_1_23` for `firefox`. If none is, as with
+    /// `LastActive`.
+    Preferred(Vec<String>),
+}
+
+/// How a [`Controller`] picks its current player.
+#[derive(Clone, Debug, Default)]
+pub struct ControllerOptions {
+    /// How the current player is picked, unless one was
+    /// [selected](Controller::select).
+    pub policy: CurrentPolicy,
+}
+
+/// An item of [`Controller::events`].
+#[derive(Clone, Debug)]
+pub enum ControllerEvent {
+    /// A player started.
+    PlayerAdded(String),
+    /// A player quit.
+    PlayerRemoved(String),
+    /// The current player changed, to the one named, or to none as
+    /// the last one quit.
+    CurrentChanged(Option<String>),
+    /// Properties of the Player interface of `player` changed.
+    StateChanged {
+        player: String,
+        properties: ChangedProperties,
+    },
+    /// `player` moved to another track, or to none. Tracks are told
+    /// apart as by [`EventManager::track_changes`].
+    TrackChanged { player: String, change: TrackChange },
+}
+
+/// A player known to a [`Controller`].
+struct Tracked {
+    name: String,
+    status: PlaybackStatus,
+}
+
+#[derive(Default)]
+struct State {
+    policy: CurrentPolicy,
+    players: Vec<Tracked>,
+    /// The names of the players, from the most to the least relevant,
+    /// as last ranked by the [`ActivePlayerTracker`] of the feed.
+    ranking: Vec<String>,
+    selected: Option<String>,
+    current: Option<String>,
+    subscribers: Vec<UnboundedSender<ControllerEvent>>,
+}
+
+impl State {
+    fn emit(&mut self, event: ControllerEvent) {
+        self.subscribers
+            .retain(|subscriber| subscriber.unbounded_send(event.clone()).is_ok());
+    }
+
+    fn is_known(&self, name: &str) -> bool {
+        self.players.iter().any(|tracked| tracked.name == name)
+    }
+
+    /// Records that `name` runs, reporting it if it is new. Returns
+    /// whether it is.
+    fn add(&mut self, name: &str) -> bool {
+        if self.is_known(name) {
+            return false;
+        }
+        self.players.push(Tracked {
+            name: name.to_string(),
+            status: PlaybackStatus::Stopped,
+        });
+        self.emit(ControllerEvent::PlayerAdded(name.to_string()));
+        true
+    }
+
+    fn set_status(&mut self, name: &str, status: PlaybackStatus) {
+        if let Some(tracked) = self.players.iter_mut().find(|tracked| tracked.name == name) {
+            tracked.status = status;
+        }
+    }
+
+    fn remove(&mut self, name: &str) {
+        let before = self.players.len();
+        self.players.retain(|tracked| tracked.name != name);
+        if self.selected.as_deref() == Some(name) {
+            self.selected = None;
+        }
+        if self.players.len() < before {
+            self.emit(ControllerEvent::PlayerRemoved(name.to_string()));
+        }
+    }
+
+    /// Picks the current player again, reporting it if it changed.
+    fn update_current(&mut self) {
+        let current = self.pick();
+        if current != self.current {
+            self.current = current.clone();
+            self.emit(ControllerEvent::CurrentChanged(current));
+        }
+    }
+
+    fn pick(&self) -> Option<String> {
+        if let Some(selected) = &self.selected {
+            return Some(selected.clone());
+        }
+
+        match &self.policy {
+            CurrentPolicy::Status => self
+                .players
+                .iter()
+                .min_by(|a, b| {
+                    status_rank(a.status)
+                        .cmp(&status_rank(b.status))
+                        .then_with(|| a.name.cmp(&b.name))
+                })
+                .map(|tracked| tracked.name.clone()),
+            CurrentPolicy::Preferred(preferred) => preferred
+                .iter()
+                .find_map(|name| {
+                    let instance = format!("{}.instance", name);
+                    self.players
+                        .iter()
+                        .map(|tracked| &tracked.name)
+                        .filter(|tracked| *tracked == name || tracked.starts_with(&instance))
+                        .min()
+                        .cloned()
+                })
+                .or_else(|| self.last_active()),
+            CurrentPolicy::LastActive => self.last_active(),
+        }
+    }
+
+    /// The highest ranked player, leaving out those the tracker still
+    /// knows of after they quit.
+    fn last_active(&self) -> Option<String> {
+        self.ranking
+            .iter()
+            .find(|name| self.is_known(name))
+            .cloned()
+    }
+}
+
+/// Follows the bus for a [`Controller`], until it is shut down or
+/// its last clone is dropped.
+struct Feed<'a> {
+    conn: &'a SyncConnection,
+    manager: EventManager<'a>,
+    state: Arc<Mutex<State>>,
+    tracker: ActivePlayerTracker<'a>,
+    changes: EventStream<'a>,
+    tracks: SelectAll<BoxStream<'a, (String, TrackChange)>>,
+    /// Ends the track changes of each player.
+    followed: HashMap<String, AbortHandle>,
+    /// Dropped once the feed has stopped, and its matches are gone.
+    _stopped: oneshot::Sender<()>,
+}
+
+impl<'a> Feed<'a> {
+    async fn run(mut self, mut stop: oneshot::Receiver<()>) {
+        loop {
+            tokio::select! {
+                _ = &mut stop => break,
+                current = self.tracker.next() => {
+                    if current.is_none() {
+                        break;
+                    }
+                }
+                event = self.changes.next() => match event {
+                    Some(event) => self.absorb(event).await,
+                    None => break,
+                },
+                Some((player, change)) = self.tracks.next(), if !self.tracks.is_empty() => {
+                    let mut state = self.state.lock().unwrap();
+                    if state.is_known(&player) {
+                        state.emit(ControllerEvent::TrackChanged { player, change });
+                    }
+                }
+            }
+
+            let ranking = self.tracker.players();
+            let mut state = self.state.lock().unwrap();
+            state.ranking = ranking;
+            state.update_current();
+        }
+        self.state.lock().unwrap().subscribers.clear();
+    }
+
+    async fn absorb(&mut self, event: Event) {
+        match event {
+            // Senders that couldn't be resolved are left alone
+            Event::PropertiesChanged(changed) if changed.player.starts_with(':') => {}
+            Event::PropertiesChanged(changed) if changed.properties.is_player_interface() => {
+                let player = changed.player;
+                let properties = changed.properties;
+                let added = {
+                    let mut state = self.state.lock().unwrap();
+                    let added = state.add(&player);
+                    if let Some(status) = properties.playback_status {
+                        state.set_status(&player, status);
+                    }
+                    state.emit(ControllerEvent::StateChanged {
+                        player: player.clone(),
+                        properties,
+                    });
+                    added
+                };
+                if added {
+                    self.follow(&player, true).await;
+                }
+            }
+            Event::PropertiesChanged(_) | Event::Seeked(_) => {}
+            Event::PlayerLifecycle(LifecycleEvent::Vanished { name }) => {
+                if let Some(followed) = self.followed.remove(&name) {
+                    followed.abort();
+                }
+                self.state.lock().unwrap().remove(&name);
+            }
+            Event::PlayerLifecycle(LifecycleEvent::Replaced { name }) => {
+                // A new instance starts out knowing nothing of the old one
+                let added = {
+                    let mut state = self.state.lock().unwrap();
+                    let added = state.add(&name);
+                    state.set_status(&name, PlaybackStatus::Stopped);
+                    added
+                };
+                if added {
+                    self.follow(&name, true).await;
+                }
+            }
+            Event::PlayerLifecycle(LifecycleEvent::Appeared { name }) => {
+                // Players found while seeding may have been announced already
+                let added = self.state.lock().unwrap().add(&name);
+                if added {
+                    self.follow(&name, true).await;
+                }
+            }
+        }
+    }
+
+    /// Follows the tracks of `name` with
+    /// [`track_changes`](EventManager::track_changes), reporting the
+    /// current one if `announce` is set.
+    async fn follow(&mut self, name: &str, announce: bool) {
+        let player = match Player::try_new(name, self.conn).await {
+            Ok(player) => player,
+            Err(_) => return,
+        };
+        let mut changes = match self.manager.track_changes(&player).await {
+            Ok(changes) => changes,
+            Err(_) => return,
+        };
+        if let Some(TrackChange::Track(metadata)) = changes.next().await {
+            if announce {
+                let change = TrackChange::Track(metadata);
+                self.state
+                    .lock()
+                    .unwrap()
+                    .emit(ControllerEvent::TrackChanged {
+                        player: name.to_string(),
+                        change,
+                    });
+            }
+        }
+
+        let (handle, registration) = AbortHandle::new_pair();
+        let owner = name.to_string();
+        let changes =
+            Abortable::new(changes, registration).map(move |change| (owner.clone(), change));
+        self.tracks.push(changes.boxed());
+        if let Some(replaced) = self.followed.insert(name.to_string(), handle) {
+            replaced.abort();
+        }
+    }
+}
+
+/// One object for what a status bar or a media key daemon needs: the
+/// players on the bus, which of them is current, the standard
+/// commands sent to it, and a single stream of what changed.
+///
+/// It is built on the public APIs of pris alone: the players are
+/// first found with [`snapshot_players`], then followed through an
+/// [`EventManager`] of its own, which also ranks them with
+/// [`active_players`](EventManager::active_players) and reports
+/// their tracks with [`track_changes`](EventManager::track_changes).
+/// Commands go through [`Player`]. The current player is picked by
+/// the [`CurrentPolicy`] of its options, unless one was
+/// [selected](Self::select).
+///
+/// The players are followed by a future returned along with the
+/// controller, which must be run for them to be, such as with
+/// `tokio::spawn` for a controller on a `'static` connection. Both
+/// are `Send`, and clones of the controller share the same players,
+/// so they can be handed to other tasks. Players that start
+/// are counted as stopped until they report otherwise. The feed
+/// stops, removing all of its matches, once
+/// [`shutdown`](Self::shutdown) is called or the last clone is
+/// dropped.
+///
+/// # Example
+/// ```no_run
+/// # use futures::StreamExt;
+/// # use pris::{Controller, ControllerEvent};
+/// # async fn example() -> pris::Result<()> {
+/// let conn = pris::get_connection();
+/// let (controller, feed) = Controller::new(&conn).await?;
+/// let printing = async {
+///     let mut events = controller.events();
+///     println!("Controlling {:?}", controller.current());
+///     while let Some(event) = events.next().await {
+///         match event {
+///             ControllerEvent::CurrentChanged(current) => println!("Controlling {:?}", current),
+///             ControllerEvent::TrackChanged { player, .. } => println!("{} changed tracks", player),
+///             _ => {}
+///         }
+///     }
+/// };
+/// futures::join!(feed, printing);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Controller<'a> {
+    conn: &'a SyncConnection,
+    manager: EventManager<'a>,
+    state: Arc<Mutex<State>>,
+    /// Taken by the first shutdown. Dropping it also stops the feed.
+    stop: Arc<Mutex<Option<Stop>>>,
+}
+
+/// Stops the [`Feed`] of a [`Controller`].
+struct Stop {
+    sender: oneshot::Sender<()>,
+    /// Tells once the feed has stopped.
+    stopped: oneshot::Receiver<()>,
+}
+
+impl<'a> Controller<'a> {
+    /// Same as `with_options`, with the default options.
+    ///
+    /// # Errors
+    /// Same as `with_options`.
+    pub async fn new(
+        conn: &'a SyncConnection,
+    ) -> Result<(Controller<'a>, impl Future<Output = ()> + Send + 'a)> {
+        Controller::with_options(conn, ControllerOptions::default()).await
+    }
+
+    /// Finds the players on `conn`, returning the controller along
+    /// with the future following them from then on.
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding the match
+    /// rules, or in listing the players on the bus.
+    pub async fn with_options(
+        conn: &'a SyncConnection,
+        options: ControllerOptions,
+    ) -> Result<(Controller<'a>, impl Future<Output = ()> + Send + 'a)> {
+        let manager = EventManager::new(conn);
+        // Follow the bus before looking, so no change goes unnoticed
+        let changes = manager
+            .stream(&[EventType::PropertiesChanged, EventType::PlayerLifecycle])
+            .await?;
+        let tracker = manager.active_players().await?;
+        let snapshots = snapshot_players(conn, SnapshotOptions::default()).await?;
+
+        let mut state = State {
+            policy: options.policy,
+            ranking: tracker.players(),
+            ..State::default()
+        };
+        for snapshot in &snapshots {
+            let status = match &snapshot.state {
+                Ok(found) => found.playback_status,
+                Err(_) => None,
+            };
+            state.players.push(Tracked {
+                name: snapshot.player.name.clone(),
+                status: status.unwrap_or(PlaybackStatus::Stopped),
+            });
+        }
+        state.current = state.pick();
+        let state = Arc::new(Mutex::new(state));
+
+        let (sender, stopping) = oneshot::channel();
+        let (done, stopped) = oneshot::channel();
+        let mut feed = Feed {
+            conn,
+            manager: manager.clone(),
+            state: state.clone(),
+            tracker,
+            changes,
+            tracks: SelectAll::new(),
+            followed: HashMap::new(),
+            _stopped: done,
+        };
+        for snapshot in &snapshots {
+            feed.follow(&snapshot.player.name, false).await;
+        }
+
+        let controller = Controller {
+            conn,
+            manager,
+            state,
+            stop: Arc::new(Mutex::new(Some(Stop { sender, stopped }))),
+        };
+        Ok((controller, feed.run(stopping)))
+    }
+
+    /// Returns a [`Stream`](futures::Stream) of what changes from now
+    /// on, in the order it changed. It ends once the controller is
+    /// shut down.
+    pub fn events(&self) -> UnboundedReceiver<ControllerEvent> {
+        let (sender, receiver) = mpsc::unbounded();
+        self.state.lock().unwrap().subscribers.push(sender);
+        receiver
+    }
+
+    /// The name of the current player, in the same form that is
+    /// passed to [`Player::try_new`], or `None` if there are no
+    /// players.
+    pub fn current(&self) -> Option<String> {
+        self.state.lock().unwrap().current.clone()
+    }
+
+    /// The names of the players on the bus, ordered by name.
+    pub fn players(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        let mut names: Vec<String> = state
+            .players
+            .iter()
+            .map(|tracked| tracked.name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// The playback status of the player `name`, as last reported.
+    pub fn status(&self, name: &str) -> Option<PlaybackStatus> {
+        let state = self.state.lock().unwrap();
+        state
+            .players
+            .iter()
+            .find(|tracked| tracked.name == name)
+            .map(|tracked| tracked.status)
+    }
+
+    /// Makes `name` the current player, such as when the user picks
+    /// it, until it quits or [`deselect`](Self::deselect) is called.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidArgument`] if `name` can't be the name
+    /// of a player, or [`Error::UnknownPlayer`] if it isn't on the
+    /// bus.
+    pub fn select<T: IntoPlayerName>(&self, name: T) -> Result<()> {
+        let name = name.into_player_name()?.into_string();
+        let mut state = self.state.lock().unwrap();
+        if !state.players.iter().any(|tracked| tracked.name == name) {
+            drop(state);
+            return Err(Error::unknown_player(&name, self.players()));
+        }
+        state.selected = Some(name);
+        state.update_current();
+        Ok(())
+    }
+
+    /// Goes back to picking the current player by the policy.
+    pub fn deselect(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.selected = None;
+        state.update_current();
+    }
+
+    /// Sets how the current player is picked from now on.
+    pub fn set_policy(&self, policy: CurrentPolicy) {
+        let mut state = self.state.lock().unwrap();
+        state.policy = policy;
+        state.update_current();
+    }
+
+    /// A handle to the current player, for what the commands here
+    /// don't cover.
+    ///
+    /// # Errors
+    /// Returns [`Error::NotFound`] if there are no players, or
+    /// [`Error::InvalidPlayer`] if the current one just quit.
+    pub async fn current_player(&self) -> Result<Player<'a>> {
+        match self.current() {
+            Some(name) => Player::try_new(name, self.conn).await,
+            None => Err(Error::NotFound {
+                tried: vec!["any player".to_string()],
+            }),
+        }
+    }
+
+    /// Plays or pauses the current player.
+    ///
+    /// # Errors
+    /// Same as [`current_player`](Self::current_player), or
+    /// [`Player::play_pause`].
+    pub async fn play_pause(&self) -> Result<()> {
+        self.current_player().await?.play_pause().await
+    }
+
+    /// Plays the current player.
+    ///
+    /// # Errors
+    /// Same as [`current_player`](Self::current_player), or
+    /// [`Player::play`].
+    pub async fn play(&self) -> Result<()> {
+        self.current_player().await?.play().await
+    }
+
+    /// Pauses the current player.
+    ///
+    /// # Errors
+    /// Same as [`current_player`](Self::current_player), or
+    /// [`Player::pause`].
+    pub async fn pause(&self) -> Result<()> {
+        self.current_player().await?.pause().await
+    }
+
+    /// Stops the current player.
+    ///
+    /// # Errors
+    /// Same as [`current_player`](Self::current_player), or
+    /// [`Player::stop`].
+    pub async fn stop(&self) -> Result<()> {
+        self.current_player().await?.stop().await
+    }
+
+    /// Skips the current player to the next track.
+    ///
+    /// # Errors
+    /// Same as [`current_player`](Self::current_player), or
+    /// [`Player::next`].
+    pub async fn next(&self) -> Result<()> {
+        self.current_player().await?.next().await
+    }
+
+    /// Skips the current player to the previous track.
+    ///
+    /// # Errors
+    /// Same as [`current_player`](Self::current_player), or
+    /// [`Player::previous`].
+    pub async fn previous(&self) -> Result<()> {
+        self.current_player().await?.previous().await
+    }
+
+    /// Seeks the current player forward by `offset`.
+    ///
+    /// # Errors
+    /// Same as [`current_player`](Self::current_player), or
+    /// [`Player::seek`].
+    pub async fn seek(&self, offset: Duration) -> Result<()> {
+        self.current_player().await?.seek(offset).await
+    }
+
+    /// Seeks the current player back by `offset`.
+    ///
+    /// # Errors
+    /// Same as [`current_player`](Self::current_player), or
+    /// [`Player::seek_reverse`].
+    pub async fn seek_reverse(&self, offset: Duration) -> Result<()> {
+        self.current_player().await?.seek_reverse(offset).await
+    }
+
+    /// Stops the feed, waiting for it to drop its streams and for the
+    /// bus to confirm that every match was removed, and ends the
+    /// streams of [`events`](Self::events). The feed must be running,
+    /// or have been dropped, for this to complete. Commands still
+    /// reach the player that was current. Calling this more than once
+    /// is safe.
+    ///
+    /// # Errors
+    /// Same as [`EventManager::shutdown`].
+    pub async fn shutdown(&self) -> Result<()> {
+        let stop = self.stop.lock().unwrap().take();
+        if let Some(stop) = stop {
+            let _ = stop.sender.send(());
+            let _ = stop.stopped.await;
+        }
+        self.state.lock().unwrap().subscribers.clear();
+        self.manager.shutdown().await
+    }
+}
@@ -0,0 +1,228 @@
+//! Rendering of the messages sent and received by pris, one line
+//! each, for the [`DebugSink`]s set on players and event managers.
+use crate::Value;
+use dbus::{
+    arg::{AppendAll, ArgType, Iter, IterAppend, ReadAll, TypeMismatchError},
+    message::Message,
+    nonblock::{Proxy, SyncConnection},
+    strings::{Interface, Member},
+};
+use std::{
+    fmt::{self, Display},
+    io::Write,
+    sync::{Arc, Mutex},
+};
+
+/// Where the raw bus traffic of a [`Player`](crate::Player) or an
+/// [`EventManager`](crate::EventManager) is written, set with
+/// [`Player::set_debug_sink`](crate::Player::set_debug_sink) or
+/// [`EventManager::set_debug_sink`](crate::EventManager::set_debug_sink).
+///
+/// Each message gets a line of its own, with every argument in full,
+/// such as:
+///
+/// ```text
+/// -> call dest=org.mpris.MediaPlayer2.vlc path=/org/mpris/MediaPlayer2 iface=org.mpris.MediaPlayer2.Player member=SetPosition sig=ox args=["/org/videolan/vlc/playlist/3", 60000000]
+/// <- reply member=SetPosition sig= args=[]
+/// <- signal token=4 sender=:1.42 path=/org/mpris/MediaPlayer2 iface=org.mpris.MediaPlayer2.Player member=Seeked sig=x args=[60000000]
+/// ```
+///
+/// This is meant for diagnosing players and reporting bugs: nothing
+/// is left out, URLs and file paths included. Nothing is rendered
+/// unless a sink is set.
+#[derive(Clone)]
+pub struct DebugSink(Arc<dyn Fn(&str) + Send + Sync>);
+
+impl DebugSink {
+    /// A sink passing each line to `f`, without a trailing newline.
+    pub fn new<F>(f: F) -> DebugSink
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        DebugSink(Arc::new(f))
+    }
+
+    /// A sink writing each line to `writer`, followed by a newline.
+    /// Failures to write are ignored.
+    pub fn writer<W>(writer: W) -> DebugSink
+    where
+        W: Write + Send + 'static,
+    {
+        let writer = Mutex::new(writer);
+        DebugSink::new(move |line| {
+            let _ = writeln!(writer.lock().unwrap(), "{}", line);
+        })
+    }
+
+    /// A sink writing each line to standard error.
+    pub fn stderr() -> DebugSink {
+        DebugSink::new(|line| eprintln!("{}", line))
+    }
+
+    /// A sink emitting each line as a debug event of the `tracing`
+    /// crate, with the target `pris::bus`.
+    #[cfg(feature = "tracing")]
+    pub fn tracing() -> DebugSink {
+        DebugSink::new(|line| tracing::debug!(target: "pris::bus", "{}", line))
+    }
+
+    pub(crate) fn write(&self, line: impl Display) {
+        (self.0)(&line.to_string());
+    }
+
+    /// Writes the signal `msg`, received by the match `token`.
+    #[cfg(feature = "events")]
+    pub(crate) fn signal(&self, token: usize, msg: &Message) {
+        use dbus::message::MessageType;
+
+        let kind = match msg.msg_type() {
+            MessageType::Signal => "signal",
+            MessageType::MethodCall => "call",
+            MessageType::MethodReturn => "reply",
+            MessageType::Error => "error",
+        };
+        self.write(format_args!(
+            "<- {} token={} sender={} path={} iface={} member={} {}",
+            kind,
+            token,
+            msg.sender().as_deref().unwrap_or("-"),
+            msg.path().as_deref().unwrap_or("-"),
+            msg.interface().as_deref().unwrap_or("-"),
+            msg.member().as_deref().unwrap_or("-"),
+            Args::read(msg.iter_init()),
+        ));
+    }
+}
+
+impl fmt::Debug for DebugSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DebugSink")
+    }
+}
+
+/// Calls `member` of `interface` through `proxy`, writing the call
+/// and its reply to `sink` if there is one.
+pub(crate) async fn method_call<R, A>(
+    sink: Option<&DebugSink>,
+    proxy: &Proxy<'_, &SyncConnection>,
+    interface: &str,
+    member: &str,
+    args: A,
+) -> Result<R, dbus::Error>
+where
+    R: ReadAll + 'static,
+    A: AppendAll,
+{
+    let sink = match sink {
+        Some(sink) => sink,
+        None => return proxy.method_call(interface, member, args).await,
+    };
+
+    let mut call = Message::method_call(
+        &proxy.destination,
+        &proxy.path,
+        &Interface::from(interface),
+        &Member::from(member),
+    );
+    args.append(&mut IterAppend::new(&mut call));
+    sink.write(format_args!(
+        "-> call dest={} path={} iface={} member={} {}",
+        proxy.destination,
+        proxy.path,
+        interface,
+        member,
+        Args::read(call.iter_init()),
+    ));
+
+    match proxy.method_call(interface, member, args).await {
+        Ok(Logged(args, reply)) => {
+            sink.write(format_args!("<- reply member={} {}", member, args));
+            reply.map_err(dbus::Error::from)
+        }
+        Err(e) => {
+            sink.write(format_args!(
+                "<- error member={} name={} message={:?}",
+                member,
+                e.name().unwrap_or("-"),
+                e.message().unwrap_or_default(),
+            ));
+            Err(e)
+        }
+    }
+}
+
+/// A reply, rendered before being read as `R`.
+struct Logged<R>(Args, Result<R, TypeMismatchError>);
+
+impl<R: ReadAll> ReadAll for Logged<R> {
+    fn read(i: &mut Iter) -> Result<Self, TypeMismatchError> {
+        Ok(Logged(Args::read(*i), R::read(i)))
+    }
+}
+
+/// The signature and arguments of a message.
+struct Args {
+    signature: String,
+    values: Vec<Value>,
+}
+
+impl Args {
+    fn read(mut i: Iter) -> Args {
+        let mut args = Args {
+            signature: String::new(),
+            values: Vec::new(),
+        };
+        while i.arg_type() != ArgType::Invalid {
+            args.signature.push_str(&i.signature());
+            if let Some(value) = i.get_refarg() {
+                args.values.push(Value::from_dbus(&*value));
+            }
+            i.next();
+        }
+
+        args
+    }
+}
+
+impl Display for Args {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sig={} args=", self.signature)?;
+        write_list(f, '[', &self.values, ']')
+    }
+}
+
+/// Writes `value` on a single line, strings quoted and escaped.
+fn write_value(f: &mut impl fmt::Write, value: &Value) -> fmt::Result {
+    match value {
+        Value::Bool(v) => write!(f, "{}", v),
+        Value::Int(v) => write!(f, "{}", v),
+        Value::UInt(v) => write!(f, "{}", v),
+        Value::Double(v) => write!(f, "{}", v),
+        Value::Str(v) => write!(f, "{:?}", v),
+        Value::Array(values) => write_list(f, '[', values, ']'),
+        Value::Struct(values) => write_list(f, '(', values, ')'),
+        Value::Dict(entries) => {
+            f.write_char('{')?;
+            for (i, (key, value)) in entries.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{:?}: ", key)?;
+                write_value(f, value)?;
+            }
+            f.write_char('}')
+        }
+        Value::Other(signature) => write!(f, "<{}>", signature),
+    }
+}
+
+fn write_list(f: &mut impl fmt::Write, open: char, values: &[Value], close: char) -> fmt::Result {
+    f.write_char(open)?;
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write_value(f, value)?;
+    }
+    f.write_char(close)
+}
@@ -0,0 +1,511 @@
+use crate::{runtime, trace, Event};
+use dbus::{channel::Token, message::Message};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::sync::Notify;
+
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+/// What to do with an event that arrives while a
+/// subscription's buffer is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DeliveryPolicy {
+    /// Wait up to `timeout` for the consumer to make room, then
+    /// discard the oldest buffered event.
+    ///
+    /// This blocks the task dispatching messages for the whole
+    /// connection, along with the worker thread running it, so the
+    /// consumer must run on a different thread (for instance on a
+    /// multi-threaded runtime) and shouldn't rely on timers.
+    /// Method replies are held up as well; the first event from
+    /// each player needs one to resolve the player's name, so a
+    /// burst from a player the subscription hasn't seen before can
+    /// still end up waiting out the timeout.
+    Block { timeout: Duration },
+    /// Discard the oldest buffered event to make room.
+    DropOldest,
+    /// Discard the incoming event.
+    DropNewest,
+    /// Keep only the most recent buffered event from each player,
+    /// discarding the oldest event if the buffer is still full.
+    LatestPerPlayer,
+}
+
+/// Buffering options for [`EventManager::subscribe_with`](crate::EventManager::subscribe_with)
+/// and [`EventManager::stream_with`](crate::EventManager::stream_with).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SubscriptionOptions {
+    /// The number of events to buffer. Must be at least 1.
+    pub capacity: usize,
+    /// What to do when the buffer is full.
+    pub policy: DeliveryPolicy,
+    /// Whether to start with a `PropertiesChanged` event for each
+    /// player on the bus, carrying all of its current properties.
+    ///
+    /// These come before any real signal, and are marked as
+    /// [`initial`](StampedEvent::initial). Signals a player sent
+    /// before its properties were requested are left out, since
+    /// they are already accounted for; signals sent while the
+    /// request was in flight are still delivered afterwards, and
+    /// may repeat values the initial event already carries.
+    pub initial_state: bool,
+}
+
+impl Default for SubscriptionOptions {
+    fn default() -> Self {
+        SubscriptionOptions {
+            capacity: 64,
+            policy: DeliveryPolicy::DropOldest,
+            initial_state: false,
+        }
+    }
+}
+
+/// How an async callback's futures are run when events arrive
+/// faster than they complete.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CallbackOrdering {
+    /// Run one future at a time, in the order events arrived.
+    /// Later events wait (unbounded) until the previous future
+    /// has finished.
+    #[default]
+    Serial,
+    /// Spawn a task for every event straight away. Futures may
+    /// complete in any order.
+    Concurrent,
+}
+
+/// An event along with details of its delivery, from
+/// [`Subscription::recv_stamped`](crate::Subscription::recv_stamped)
+/// or [`EventStream::stamped`](crate::EventStream::stamped).
+#[derive(Clone, Debug)]
+pub struct StampedEvent {
+    pub event: Event,
+    /// The order in which the signal was received by the manager,
+    /// counting every signal received for any of its subscriptions
+    /// and streams. Gaps therefore don't necessarily mean loss; see
+    /// [`dropped_before`](Self::dropped_before) for that.
+    pub sequence: u64,
+    /// When the signal was received from the bus.
+    pub received: Instant,
+    /// When the signal was received from the bus, by the wall clock.
+    pub received_at: SystemTime,
+    /// The number of events discarded since the previous event was
+    /// delivered, because the buffer was full.
+    pub dropped_before: u64,
+    /// Whether the event was made up from a player's current state,
+    /// see [`SubscriptionOptions::initial_state`]. Initial events
+    /// are numbered after the signals received until then.
+    pub initial: bool,
+}
+
+/// A message waiting in an [`EventQueue`].
+pub(crate) struct Queued {
+    pub(crate) msg: Message,
+    sequence: u64,
+    received: Instant,
+    received_at: SystemTime,
+    dropped_before: u64,
+    initial: bool,
+}
+
+impl Queued {
+    pub(crate) fn stamp(self, event: Event) -> StampedEvent {
+        StampedEvent {
+            event,
+            sequence: self.sequence,
+            received: self.received,
+            received_at: self.received_at,
+            dropped_before: self.dropped_before,
+            initial: self.initial,
+        }
+    }
+}
+
+struct QueueState {
+    messages: VecDeque<Queued>,
+    dropped: u64,
+    dropped_since_pop: u64,
+    closed: bool,
+}
+
+/// A bounded queue of messages between a match callback (which
+/// may not await) and an async consumer.
+pub(crate) struct EventQueue {
+    options: SubscriptionOptions,
+    sequence: Arc<AtomicU64>,
+    state: Mutex<QueueState>,
+    space: Condvar,
+    available: Notify,
+}
+
+impl EventQueue {
+    /// Creates a queue, numbering messages from the shared `sequence`.
+    pub(crate) fn new(options: SubscriptionOptions, sequence: Arc<AtomicU64>) -> EventQueue {
+        EventQueue {
+            options: SubscriptionOptions {
+                capacity: options.capacity.max(1),
+                ..options
+            },
+            sequence,
+            state: Mutex::new(QueueState {
+                messages: VecDeque::new(),
+                dropped: 0,
+                dropped_since_pop: 0,
+                closed: false,
+            }),
+            space: Condvar::new(),
+            available: Notify::new(),
+        }
+    }
+
+    fn queued(&self, msg: Message, initial: bool) -> Queued {
+        Queued {
+            msg,
+            sequence: self.sequence.fetch_add(1, Ordering::SeqCst),
+            received: Instant::now(),
+            received_at: SystemTime::now(),
+            dropped_before: 0,
+            initial,
+        }
+    }
+
+    /// Queues a message according to the delivery policy.
+    /// Returns `false` once the queue has been closed.
+    pub(crate) fn push(&self, msg: Message) -> bool {
+        let queued = self.queued(msg, false);
+        let mut state = self.state.lock().unwrap();
+        let capacity = self.options.capacity;
+
+        if state.closed {
+            return false;
+        }
+
+        if state.messages.len() >= capacity {
+            match self.options.policy {
+                DeliveryPolicy::Block { timeout } => {
+                    state = self
+                        .space
+                        .wait_timeout_while(state, timeout, |s| {
+                            s.messages.len() >= capacity && !s.closed
+                        })
+                        .unwrap()
+                        .0;
+                    if state.closed {
+                        return false;
+                    }
+                    if state.messages.len() >= capacity {
+                        state.messages.pop_front();
+                        state.dropped += 1;
+                        state.dropped_since_pop += 1;
+                    }
+                }
+                DeliveryPolicy::DropOldest => {
+                    state.messages.pop_front();
+                    state.dropped += 1;
+                    state.dropped_since_pop += 1;
+                }
+                DeliveryPolicy::DropNewest => {
+                    state.dropped += 1;
+                    state.dropped_since_pop += 1;
+                    return true;
+                }
+                DeliveryPolicy::LatestPerPlayer => {
+                    let sender = queued.msg.sender();
+                    let same_player = state.messages.iter().position(|q| {
+                        q.msg.sender() == sender && q.msg.member() == queued.msg.member()
+                    });
+                    match same_player {
+                        Some(index) => state.messages.remove(index),
+                        None => state.messages.pop_front(),
+                    };
+                    state.dropped += 1;
+                    state.dropped_since_pop += 1;
+                }
+            }
+        }
+
+        state.messages.push_back(queued);
+        drop(state);
+        self.available.notify_one();
+
+        true
+    }
+
+    /// Queues `messages` ahead of everything else, regardless of
+    /// the capacity, marking them as initial.
+    pub(crate) fn push_initial(&self, messages: Vec<Message>) {
+        let queued: Vec<Queued> = messages
+            .into_iter()
+            .map(|msg| self.queued(msg, true))
+            .collect();
+        let mut state = self.state.lock().unwrap();
+        for queued in queued.into_iter().rev() {
+            state.messages.push_front(queued);
+        }
+        drop(state);
+        self.available.notify_one();
+    }
+
+    /// Removes the Player `PropertiesChanged` signals `sender` sent
+    /// before the message numbered `before`.
+    pub(crate) fn supersede(&self, sender: &str, before: u64) {
+        self.state.lock().unwrap().messages.retain(|q| {
+            let superseded = q.sequence < before
+                && q.msg.sender().as_deref() == Some(sender)
+                && q.msg.member().as_deref() == Some("PropertiesChanged")
+                && q.msg.read1::<&str>().ok() == Some(PLAYER_INTERFACE);
+            !superseded
+        });
+    }
+
+    /// Waits for the next queued message.
+    pub(crate) async fn pop(&self) -> Queued {
+        loop {
+            let popped = {
+                let mut state = self.state.lock().unwrap();
+                let popped = state.messages.pop_front();
+                popped.map(|mut queued| {
+                    queued.dropped_before = std::mem::take(&mut state.dropped_since_pop);
+                    queued
+                })
+            };
+            if let Some(queued) = popped {
+                self.space.notify_one();
+                return queued;
+            }
+            self.available.notified().await;
+        }
+    }
+
+    /// Waits for the next queued message, or returns `None` once the
+    /// queue is closed, discarding whatever is still in it.
+    pub(crate) async fn next(&self) -> Option<Queued> {
+        loop {
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.closed {
+                    return None;
+                }
+                if let Some(mut queued) = state.messages.pop_front() {
+                    queued.dropped_before = std::mem::take(&mut state.dropped_since_pop);
+                    drop(state);
+                    self.space.notify_one();
+                    return Some(queued);
+                }
+            }
+            self.available.notified().await;
+        }
+    }
+
+    /// The number of messages discarded because the queue was full.
+    pub(crate) fn dropped(&self) -> u64 {
+        self.state.lock().unwrap().dropped
+    }
+
+    /// Stops accepting messages, and wakes up a blocked producer
+    /// along with a consumer waiting in [`next`](Self::next).
+    pub(crate) fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.space.notify_all();
+        self.available.notify_one();
+    }
+}
+
+/// Closes a queue when dropped.
+struct CloseOnDrop(Arc<EventQueue>);
+
+impl Drop for CloseOnDrop {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+/// What to do with events that arrive while delivery is
+/// [paused](crate::EventManager::pause).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PausePolicy {
+    /// Discard them.
+    #[default]
+    Drop,
+    /// Keep up to `capacity` of them for each callback, subscription
+    /// or stream, discarding the oldest once full, and deliver them
+    /// in order on resume.
+    Buffer { capacity: usize },
+}
+
+pub(crate) type HandlerFn = Box<dyn FnMut(Message) -> bool + Send>;
+
+/// Runs `callback` on a task of its own, fed through a queue bounded
+/// by `options`, so that it can take its time without holding up
+/// the task dispatching messages.
+///
+/// Each message is handled on the runtime's blocking thread pool, one at a
+/// time and in order. Returns the function queueing messages, which
+/// returns `false` once the callback has returned `false` or
+/// panicked. Dropping it discards the messages still queued.
+pub(crate) fn spawn_callback(options: SubscriptionOptions, mut callback: HandlerFn) -> HandlerFn {
+    let queue = Arc::new(EventQueue::new(options, Arc::default()));
+    let feed = CloseOnDrop(queue.clone());
+    runtime::detach(async move {
+        while let Some(queued) = queue.next().await {
+            let call = runtime::spawn_blocking(move || {
+                let keep = callback(queued.msg);
+                (callback, keep)
+            });
+            match call.await {
+                Some((returned, true)) => callback = returned,
+                // A panic has already been reported by the panic hook,
+                // and took the callback with it
+                _ => break,
+            }
+        }
+        queue.close();
+    });
+
+    Box::new(move |msg| feed.0.push(msg))
+}
+
+/// A match callback that can be called either by the connection
+/// or, when replaying, by [`DeliveryGate::resume`].
+pub(crate) struct Handler {
+    token: AtomicUsize,
+    callback: Mutex<Option<HandlerFn>>,
+}
+
+impl Handler {
+    pub(crate) fn new(callback: HandlerFn) -> Arc<Handler> {
+        Arc::new(Handler {
+            token: AtomicUsize::new(0),
+            callback: Mutex::new(Some(callback)),
+        })
+    }
+
+    /// Records the token of the match this handler was registered with.
+    pub(crate) fn set_token(&self, token: Token) {
+        self.token.store(token.0, Ordering::SeqCst);
+    }
+
+    pub(crate) fn token(&self) -> Token {
+        Token(self.token.load(Ordering::SeqCst))
+    }
+
+    /// Calls the callback, which is dropped once it returns `false`.
+    fn call(&self, msg: Message) -> bool {
+        trace::event!(
+            trace,
+            token = self.token().0,
+            member = msg.member().as_deref().unwrap_or_default(),
+            "dispatching callback"
+        );
+        let mut callback = self.callback.lock().unwrap();
+        let keep = callback.as_mut().is_some_and(|f| f(msg));
+        if !keep {
+            *callback = None;
+        }
+        keep
+    }
+
+    fn is_alive(&self) -> bool {
+        self.callback.lock().unwrap().is_some()
+    }
+}
+
+#[derive(Default)]
+struct GateState {
+    paused: Option<PausePolicy>,
+    replaying: bool,
+    buffered: VecDeque<(Arc<Handler>, Message)>,
+}
+
+/// Holds back messages from every handler of a manager while it
+/// is paused.
+#[derive(Default)]
+pub(crate) struct DeliveryGate {
+    state: Mutex<GateState>,
+}
+
+impl DeliveryGate {
+    /// Passes `msg` to `handler`, unless delivery is paused or
+    /// buffered messages are still being replayed. Returns `false`
+    /// once the handler no longer wants messages.
+    pub(crate) fn deliver(&self, handler: &Arc<Handler>, msg: Message) -> bool {
+        {
+            let mut state = self.state.lock().unwrap();
+            match state.paused {
+                Some(PausePolicy::Drop) => return handler.is_alive(),
+                Some(PausePolicy::Buffer { capacity }) => {
+                    let buffered = &mut state.buffered;
+                    let own = |(h, _): &(Arc<Handler>, Message)| Arc::ptr_eq(h, handler);
+                    if buffered.iter().filter(|entry| own(entry)).count() >= capacity.max(1) {
+                        let oldest = buffered.iter().position(own).unwrap();
+                        buffered.remove(oldest);
+                    }
+                    state.buffered.push_back((handler.clone(), msg));
+                    return handler.is_alive();
+                }
+                // Queue behind the messages being replayed to keep the order
+                None if state.replaying => {
+                    state.buffered.push_back((handler.clone(), msg));
+                    return handler.is_alive();
+                }
+                None => {}
+            }
+        }
+
+        handler.call(msg)
+    }
+
+    pub(crate) fn pause(&self, policy: PausePolicy) {
+        let mut state = self.state.lock().unwrap();
+        if policy == PausePolicy::Drop {
+            state.buffered.clear();
+        }
+        state.paused = Some(policy);
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.state.lock().unwrap().paused.is_some()
+    }
+
+    /// Resumes delivery, replaying buffered messages on the calling
+    /// thread. `unregister` is called with the token of each handler
+    /// that stops wanting messages during the replay.
+    pub(crate) fn resume(&self, mut unregister: impl FnMut(Token)) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.paused = None;
+            if state.replaying {
+                // Whoever is already replaying picks up the rest
+                return;
+            }
+            state.replaying = true;
+        }
+
+        loop {
+            let next = {
+                let mut state = self.state.lock().unwrap();
+                let next = match state.paused {
+                    Some(_) => None,
+                    None => state.buffered.pop_front(),
+                };
+                if next.is_none() {
+                    state.replaying = false;
+                }
+                next
+            };
+            let Some((handler, msg)) = next else {
+                return;
+            };
+            if handler.is_alive() && !handler.call(msg) {
+                unregister(handler.token());
+            }
+        }
+    }
+}
@@ -0,0 +1,388 @@
+#[cfg(feature = "events")]
+use crate::Event;
+use crate::{runtime, spec, status, trace, util, Error, Player, PlayerState, Result};
+use dbus::nonblock::{stdintf::org_freedesktop_dbus::Properties, Proxy, SyncConnection};
+use futures::{future, stream, StreamExt};
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// How [`snapshot_players`] fetches the state of each player.
+#[derive(Clone, Copy, Debug)]
+pub struct SnapshotOptions {
+    /// How long each player is given to answer, after which its
+    /// snapshot fails with [`Error::Timeout`].
+    pub timeout: Duration,
+    /// The most players fetched at once. Zero is taken as one.
+    pub concurrency: usize,
+}
+
+impl Default for SnapshotOptions {
+    fn default() -> Self {
+        SnapshotOptions {
+            timeout: Duration::from_secs(1),
+            concurrency: 8,
+        }
+    }
+}
+
+/// A player found by [`snapshot_players`], with what it reported.
+pub struct PlayerSnapshot<'a> {
+    pub player: Player<'a>,
+    /// The `Identity` of the player, such as `Firefox`, if it gave it.
+    pub identity: Option<String>,
+    /// The state of the player, or why it couldn't be fetched.
+    pub state: Result<PlayerState>,
+}
+
+/// Finds every MPRIS player on the bus, and fetches the identity and
+/// state of each concurrently.
+///
+/// A player that fails or doesn't answer within the timeout of
+/// `options` only fails its own snapshot, so one hung player doesn't
+/// hold up or spoil the others. Snapshots are in the order the
+/// players were listed in, like [`get_all_players`](crate::get_all_players).
+///
+/// # Errors
+/// May return an `Err` variant if there was a failure in
+/// getting a list of names from `DBus`.
+///
+/// # Example
+/// ```no_run
+/// # use pris::SnapshotOptions;
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// # let conn = pris::get_connection();
+/// for snapshot in pris::snapshot_players(&conn, SnapshotOptions::default()).await? {
+///     match snapshot.state {
+///         Ok(state) => println!("{:?}: {:?}", snapshot.identity, state.playback_status),
+///         Err(e) => println!("{} failed: {}", snapshot.player.name, e),
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn snapshot_players(
+    conn: &SyncConnection,
+    options: SnapshotOptions,
+) -> Result<Vec<PlayerSnapshot<'_>>> {
+    let players = util::get_all_players(conn).await?;
+
+    // Built up front, as a stream mapping with a closure here would
+    // keep the future from being `Send`
+    let fetches: Vec<_> = players
+        .into_iter()
+        .enumerate()
+        .map(|(index, player)| async move { (index, snapshot(player, options.timeout).await) })
+        .collect();
+    let mut snapshots: Vec<(usize, PlayerSnapshot<'_>)> = stream::iter(fetches)
+        .buffer_unordered(options.concurrency.max(1))
+        .collect()
+        .await;
+    snapshots.sort_by_key(|(index, _)| *index);
+
+    Ok(snapshots
+        .into_iter()
+        .map(|(_, snapshot)| snapshot)
+        .collect())
+}
+
+/// Fetches the identity and state of `player`, giving up after `limit`.
+async fn snapshot(player: Player<'_>, limit: Duration) -> PlayerSnapshot<'_> {
+    let fetch = async {
+        let proxy = player.get_proxy();
+        let identity = proxy.get::<String>("org.mpris.MediaPlayer2", "Identity");
+        let (identity, state) = futures::join!(identity, player.get_state());
+        (identity.ok(), state)
+    };
+
+    let (identity, state) = match runtime::timeout(limit, fetch).await {
+        Ok(fetched) => fetched,
+        Err(_) => {
+            trace::event!(
+                debug,
+                player = %player.name,
+                operation = "the snapshot",
+                limit_ms = limit.as_millis() as u64,
+                "timed out"
+            );
+            (
+                None,
+                Err(Error::Timeout {
+                    player: Some(player.name.clone()),
+                    operation: "the snapshot".to_string(),
+                    limit,
+                    source: None,
+                }),
+            )
+        }
+    };
+
+    PlayerSnapshot {
+        player,
+        identity,
+        state,
+    }
+}
+
+/// Sorts `players` with the playing ones first, then the paused and
+/// then the stopped ones, as ranked by
+/// [`status_rank`](crate::status_rank), and by name among equals.
+///
+/// Players are asked for their status at once, each given a quarter
+/// of a second to answer. Those that fail or don't answer in time
+/// sort last, rather than failing the sort.
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> pris::Result<()> {
+/// let conn = pris::get_connection();
+/// let players = pris::sort_by_status(pris::get_all_players(&conn).await?).await;
+/// if let Some(player) = players.first() {
+///     println!("Controlling {}", player.name);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn sort_by_status(players: Vec<Player<'_>>) -> Vec<Player<'_>> {
+    let ranks = future::join_all(players.iter().map(|player| async move {
+        let status = runtime::timeout(
+            util::STATUS_TIMEOUT,
+            player.get_property::<String>("PlaybackStatus"),
+        )
+        .await;
+        status::rank_of(status.ok().and_then(Result::ok).as_deref())
+    }))
+    .await;
+
+    let mut ranked: Vec<(u8, Player<'_>)> = ranks.into_iter().zip(players).collect();
+    ranked.sort_by(|(a, x), (b, y)| a.cmp(b).then_with(|| x.name.cmp(&y.name)));
+    ranked.into_iter().map(|(_, player)| player).collect()
+}
+
+/// The players of one application, such as the instances a browser
+/// registers for each tab playing media.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlayerGroup {
+    /// The name of the application, without an instance suffix, such
+    /// as `firefox`.
+    pub app: String,
+    /// The `Identity` its players share, such as `Firefox`, if they
+    /// were asked for it.
+    pub identity: Option<String>,
+    /// The names of its players, such as `firefox.instance_1_23`, in
+    /// the order they were listed.
+    pub instances: Vec<String>,
+}
+
+/// Groups the player names `names` by application, stripping
+/// instance suffixes such as `.instance_1_23` or `.instance7389`, so
+/// that `vlc` and `vlc.instance7389` are grouped together.
+///
+/// Groups are in the order of their first player, and leave
+/// [`identity`](PlayerGroup::identity) empty; [`player_groups`] also
+/// confirms them with the `Identity` of each player.
+pub fn group_players<I>(names: I) -> Vec<PlayerGroup>
+where
+    I: IntoIterator,
+    I::Item: Into<String>,
+{
+    let mut groups: Vec<PlayerGroup> = Vec::new();
+    for name in names {
+        let name = name.into();
+        let app = util::app_name(&name);
+        match groups.iter_mut().find(|group| group.app == app) {
+            Some(group) => group.instances.push(name),
+            None => groups.push(PlayerGroup {
+                app: app.to_string(),
+                identity: None,
+                instances: vec![name],
+            }),
+        }
+    }
+
+    groups
+}
+
+/// Groups the players on the bus by application, as with
+/// [`group_players`], confirmed by their `Identity`.
+///
+/// Players are asked for their `Identity` at once. Those named after
+/// one application but reporting different identities are put in
+/// separate groups, one per identity, and those that don't answer
+/// promptly join the first group of their application.
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> pris::Result<()> {
+/// let conn = pris::get_connection();
+/// for group in pris::player_groups(&conn).await? {
+///     let name = group.identity.as_deref().unwrap_or(&group.app);
+///     println!("{} ({})", name, group.instances.len());
+///     for instance in &group.instances {
+///         println!("  {}", instance);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+/// May `Err` if the bus can't be asked.
+pub async fn player_groups(conn: &SyncConnection) -> Result<Vec<PlayerGroup>> {
+    let names = util::list_players(conn).await?;
+    let identities = future::join_all(names.iter().map(|name| async move {
+        let destination = format!("{}{}", util::MPRIS_PREFIX, name);
+        let proxy = Proxy::new(
+            destination,
+            "/org/mpris/MediaPlayer2",
+            util::IDENTITY_TIMEOUT,
+            conn,
+        );
+        proxy.get::<String>(spec::ROOT.name, "Identity").await.ok()
+    }))
+    .await;
+
+    let mut groups: Vec<PlayerGroup> = Vec::new();
+    for (name, identity) in names.into_iter().zip(identities) {
+        let app = util::app_name(&name);
+        let group = groups.iter_mut().find(|group| {
+            group.app == app
+                && (identity.is_none() || group.identity.is_none() || group.identity == identity)
+        });
+        match group {
+            Some(group) => {
+                if group.identity.is_none() {
+                    group.identity = identity;
+                }
+                group.instances.push(name);
+            }
+            None => groups.push(PlayerGroup {
+                app: app.to_string(),
+                identity,
+                instances: vec![name],
+            }),
+        }
+    }
+
+    Ok(groups)
+}
+
+/// Remembers which MPRIS players are on the bus for a while, so that
+/// discovering them repeatedly, such as once a second to notice new
+/// ones, doesn't list every name on the bus each time.
+///
+/// It is `Send + Sync`, so one cache can be shared between tasks in
+/// an `Arc`. Feeding it the events of a stream with
+/// [`observe`](Self::observe) drops what it remembers as soon as a
+/// player appears or vanishes, rather than when it expires.
+#[derive(Debug)]
+pub struct DiscoveryCache {
+    ttl: Duration,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    /// The players listed, by name and owner, and when.
+    players: Option<(Instant, Vec<(String, String)>)>,
+    /// Bumped on every invalidation, so that a listing started
+    /// before one isn't kept after it.
+    generation: u64,
+}
+
+impl DiscoveryCache {
+    /// Creates a cache that lists the players again once what it
+    /// remembers is older than `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        DiscoveryCache {
+            ttl,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// How long the players listed are remembered.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// The names of the MPRIS players on the bus, without the
+    /// `org.mpris.MediaPlayer2.` prefix, listed again only if the
+    /// remembered ones expired or were invalidated.
+    ///
+    /// # Errors
+    /// May return an `Err` variant if there was a failure in
+    /// getting a list of names from `DBus`.
+    pub async fn names(&self, conn: &SyncConnection) -> Result<Vec<String>> {
+        let players = self.cached(conn).await?;
+        Ok(players.into_iter().map(|(name, _)| name).collect())
+    }
+
+    /// Same as [`get_all_players`](crate::get_all_players), from the
+    /// remembered players if they haven't expired.
+    ///
+    /// # Errors
+    /// May return an `Err` variant if there was a failure in
+    /// getting a list of names from `DBus`.
+    pub async fn players<'c>(&self, conn: &'c SyncConnection) -> Result<Vec<Player<'c>>> {
+        let players = self.cached(conn).await?;
+        Ok(util::pin_players(players, conn))
+    }
+
+    /// Same as `players`, but always lists the players on the bus,
+    /// remembering them for later calls.
+    ///
+    /// # Errors
+    /// May return an `Err` variant if there was a failure in
+    /// getting a list of names from `DBus`.
+    pub async fn fresh_players<'c>(&self, conn: &'c SyncConnection) -> Result<Vec<Player<'c>>> {
+        let generation = self.state.lock().unwrap().generation;
+        let players = self.list(conn, generation).await?;
+        Ok(util::pin_players(players, conn))
+    }
+
+    /// Forgets the players listed, so that the next call lists them
+    /// again.
+    pub fn invalidate(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.players = None;
+        state.generation += 1;
+    }
+
+    /// Invalidates the cache if `event` is a player appearing,
+    /// vanishing or changing owner. Other events are ignored.
+    #[cfg(feature = "events")]
+    pub fn observe(&self, event: &Event) {
+        if let Event::PlayerLifecycle(_) = event {
+            self.invalidate();
+        }
+    }
+
+    /// The remembered players, or the ones on the bus if they
+    /// expired or were invalidated.
+    async fn cached(&self, conn: &SyncConnection) -> Result<Vec<(String, String)>> {
+        let generation = {
+            let state = self.state.lock().unwrap();
+            match &state.players {
+                Some((listed, players)) if listed.elapsed() < self.ttl => {
+                    return Ok(players.clone())
+                }
+                _ => state.generation,
+            }
+        };
+
+        self.list(conn, generation).await
+    }
+
+    /// Lists the players on the bus, remembering them unless the
+    /// cache was invalidated since `generation`.
+    async fn list(&self, conn: &SyncConnection, generation: u64) -> Result<Vec<(String, String)>> {
+        let players = util::list_owners(conn).await?;
+
+        let mut state = self.state.lock().unwrap();
+        if state.generation == generation {
+            state.players = Some((Instant::now(), players.clone()));
+        }
+        Ok(players)
+    }
+}
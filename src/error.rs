@@ -0,0 +1,437 @@
+use crate::{spec, util, Bus, PlayerCandidate};
+use std::{fmt, time::Duration};
+
+/// The errors the bus replies with when the player being called
+/// isn't on it anymore.
+const GONE: &[&str] = &[
+    "org.freedesktop.DBus.Error.ServiceUnknown",
+    "org.freedesktop.DBus.Error.NameHasNoOwner",
+];
+
+/// The errors a call fails with when its reply doesn't come in
+/// time, from the bus or from `dbus` itself.
+const TIMED_OUT: &[&str] = &[
+    "org.freedesktop.DBus.Error.Timeout",
+    "org.freedesktop.DBus.Error.TimedOut",
+    "org.freedesktop.DBus.Error.NoReply",
+];
+
+/// The errors a bus refuses a connection with, because of who is
+/// connecting.
+const REFUSED: &[&str] = &[
+    "org.freedesktop.DBus.Error.AuthFailed",
+    "org.freedesktop.DBus.Error.AccessDenied",
+];
+
+/// The errors a player replies with when asked for a property it
+/// doesn't have, as spelled by GDBus and by `dbus-daemon`.
+const UNKNOWN_PROPERTY: &[&str] = &[
+    "org.freedesktop.DBus.Error.InvalidArgs",
+    "org.freedesktop.DBus.Error.UnknownProperty",
+];
+
+/// The most players an [`Error::UnknownPlayer`] lists.
+const MAX_AVAILABLE: usize = 10;
+
+/// The errors returned by this crate.
+///
+/// More variants may be added, so matches on it need a wildcard
+/// arm. It is `Send + Sync`, so it can cross task boundaries, and
+/// converts into `Box<dyn std::error::Error>` and the like with `?`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// There is no player with this name on the bus, or it left
+    /// while it was being used. Players looked up by the name they go
+    /// by, as with [`Player::try_new`](crate::Player::try_new), are
+    /// reported with [`UnknownPlayer`](Error::UnknownPlayer) instead.
+    InvalidPlayer(String),
+    /// No player goes by the name `requested`. `available` lists the
+    /// players on the bus as it was looked up, at most ten of them
+    /// out of `total`, and `suggestion` is the one `requested` is
+    /// likely a misspelling of, if any.
+    UnknownPlayer {
+        requested: String,
+        available: Vec<String>,
+        total: usize,
+        suggestion: Option<String>,
+    },
+    /// Several players go by the name `requested`, such as instances
+    /// of one application, so the one meant has to be picked from
+    /// `candidates` by its full name.
+    AmbiguousPlayer {
+        requested: String,
+        candidates: Vec<PlayerCandidate>,
+    },
+    /// The player `player` left the bus after it was opened, so it
+    /// should be dropped and looked up again. The bus reported it
+    /// with `source`.
+    PlayerGone { player: String, source: dbus::Error },
+    /// The player with this name has no active track to act on.
+    NoActiveTrack(String),
+    /// A call to the bus or a player failed.
+    DBus(dbus::Error),
+    /// Nothing was received within `limit`, while calling the
+    /// method or accessing the property `operation` of `player`, or
+    /// while waiting for the signal `operation` if `player` is `None`.
+    /// `source` is the error the call failed with, if it was a call.
+    Timeout {
+        player: Option<String>,
+        operation: String,
+        limit: Duration,
+        source: Option<dbus::Error>,
+    },
+    /// The player `player` doesn't have the property `property`,
+    /// which it reported with `source`.
+    UnknownProperty {
+        player: String,
+        property: String,
+        source: dbus::Error,
+    },
+    /// The property `property` of the player `player` has the D-Bus
+    /// signature `actual`, so it can't be read as the Rust type
+    /// `expected`.
+    TypeMismatch {
+        player: String,
+        property: String,
+        expected: String,
+        actual: String,
+    },
+    /// The call was given up on after `attempts` attempts, following
+    /// a [`RetryPolicy`](crate::RetryPolicy), the last failing with
+    /// `source`.
+    Retried { attempts: u32, source: Box<Error> },
+    /// The operation can't be done, as described. `source` is the
+    /// error the player refused it with, if it did.
+    UnsupportedOperation {
+        description: String,
+        source: Option<dbus::Error>,
+    },
+    /// A signal or value didn't have the expected form, as described.
+    Parse(String),
+    /// An argument was rejected, as described.
+    InvalidArgument(String),
+    /// The connection stopped delivering messages.
+    Disconnected,
+    /// Connecting to `bus` failed with `source`, either because it
+    /// couldn't be reached or because it refused the connection.
+    Connection { bus: Bus, source: dbus::Error },
+    /// No player matched any of the rules of a
+    /// [`PlayerFinder`](crate::PlayerFinder), described in `tried` in
+    /// the order they were tried.
+    NotFound { tried: Vec<String> },
+    /// Some matches couldn't be removed from the connection, out of
+    /// `total`; the others were still removed.
+    MatchRemoval {
+        total: usize,
+        errors: Vec<dbus::Error>,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidPlayer(name) => write!(f, "The player {} is not on the bus.", name),
+            Error::UnknownPlayer {
+                requested,
+                total: 0,
+                ..
+            } => write!(
+                f,
+                "The player {} is not on the bus, as no players are running.",
+                requested
+            ),
+            Error::UnknownPlayer {
+                requested,
+                available,
+                total,
+                suggestion,
+            } => {
+                write!(f, "The player {} is not on the bus.", requested)?;
+                if let Some(suggestion) = suggestion {
+                    write!(f, " Did you mean {}?", suggestion)?;
+                }
+                write!(f, " Available players: {}", available.join(", "))?;
+                match total.saturating_sub(available.len()) {
+                    0 => f.write_str("."),
+                    more => write!(f, ", and {} more.", more),
+                }
+            }
+            Error::AmbiguousPlayer {
+                requested,
+                candidates,
+            } => {
+                let candidates: Vec<String> = candidates
+                    .iter()
+                    .map(|c| match &c.identity {
+                        Some(identity) => format!("{} ({}, {})", c.name, identity, c.unique_name),
+                        None => format!("{} ({})", c.name, c.unique_name),
+                    })
+                    .collect();
+                write!(
+                    f,
+                    "The name {} matches several players: {}.",
+                    requested,
+                    candidates.join(", ")
+                )
+            }
+            Error::PlayerGone { player, .. } => {
+                write!(f, "The player {} has left the bus.", player)
+            }
+            Error::NoActiveTrack(name) => write!(f, "The player {} has no active track.", name),
+            Error::DBus(e) => write!(f, "D-Bus error: {}", e),
+            Error::Timeout {
+                player: Some(player),
+                operation,
+                limit,
+                ..
+            } => write!(
+                f,
+                "The player {} didn't answer {} within {:?}.",
+                player, operation, limit
+            ),
+            Error::Timeout {
+                player: None,
+                operation,
+                limit,
+                ..
+            } => write!(
+                f,
+                "No {} signal was received within {:?}.",
+                operation, limit
+            ),
+            Error::UnknownProperty {
+                player, property, ..
+            } => {
+                write!(f, "The player {} has no property {}.", player, property)?;
+                match nearest_property(property) {
+                    Some(suggestion) => write!(f, " Did you mean {}?", suggestion),
+                    None => Ok(()),
+                }
+            }
+            Error::TypeMismatch {
+                player,
+                property,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Expected {}, got {} for property {} on {}.",
+                expected, actual, property, player
+            ),
+            Error::Retried { attempts, source } => {
+                write!(f, "{} Gave up after {} attempts.", source, attempts)
+            }
+            Error::UnsupportedOperation { description, .. }
+            | Error::Parse(description)
+            | Error::InvalidArgument(description) => f.write_str(description),
+            Error::Disconnected => f.write_str("The connection stopped delivering messages."),
+            Error::Connection { bus, source } => {
+                if source.name().is_some_and(|name| REFUSED.contains(&name)) {
+                    write!(f, "Not allowed to connect to {}", bus)?;
+                } else {
+                    write!(f, "Couldn't connect to {}", bus)?;
+                }
+                match source.message() {
+                    Some(message) => write!(f, ": {}", message),
+                    None => f.write_str("."),
+                }
+            }
+            Error::NotFound { tried } if tried.is_empty() => {
+                f.write_str("No player was looked for, as no rules were given.")
+            }
+            Error::NotFound { tried } => {
+                write!(f, "No player matched {}.", tried.join(", or "))
+            }
+            Error::MatchRemoval { total, errors } => {
+                let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(
+                    f,
+                    "Failed to remove {} of {} matches: {}",
+                    errors.len(),
+                    total,
+                    errors.join("; ")
+                )
+            }
+        }
+    }
+}
+
+impl Error {
+    /// The D-Bus error this error was classified from, whatever its
+    /// variant, if it comes from a failed call. For
+    /// [`MatchRemoval`](Error::MatchRemoval), this is the first of
+    /// its errors.
+    pub fn dbus_error(&self) -> Option<&dbus::Error> {
+        match self {
+            Error::DBus(e)
+            | Error::PlayerGone { source: e, .. }
+            | Error::UnknownProperty { source: e, .. }
+            | Error::Connection { source: e, .. } => Some(e),
+            Error::Timeout { source, .. } | Error::UnsupportedOperation { source, .. } => {
+                source.as_ref()
+            }
+            Error::Retried { source, .. } => source.dbus_error(),
+            Error::MatchRemoval { errors, .. } => errors.first(),
+            _ => None,
+        }
+    }
+
+    /// The name of the D-Bus error this error was classified from,
+    /// such as `org.freedesktop.DBus.Error.ServiceUnknown` or one
+    /// particular to the player.
+    pub fn dbus_name(&self) -> Option<&str> {
+        self.dbus_error()?.name()
+    }
+
+    /// The message of the D-Bus error this error was classified from.
+    pub fn dbus_message(&self) -> Option<&str> {
+        self.dbus_error()?.message()
+    }
+
+    /// The error for no player going by `requested`, out of the
+    /// players `available` on the bus, in the order they are to be
+    /// listed.
+    pub(crate) fn unknown_player(requested: &str, mut available: Vec<String>) -> Error {
+        let suggestion = nearest_player(requested, &available);
+        let total = available.len();
+        available.truncate(MAX_AVAILABLE);
+
+        Error::UnknownPlayer {
+            requested: requested.to_string(),
+            available,
+            total,
+            suggestion,
+        }
+    }
+
+    /// Classifies the failure of the call `operation` to the player
+    /// `player`, made with a timeout of `limit`, by the name of the
+    /// D-Bus error.
+    pub(crate) fn from_call(
+        player: &str,
+        operation: &str,
+        limit: Duration,
+        e: dbus::Error,
+    ) -> Error {
+        match e.name() {
+            Some(name) if GONE.contains(&name) => Error::PlayerGone {
+                player: player.to_string(),
+                source: e,
+            },
+            Some(name) if TIMED_OUT.contains(&name) => Error::Timeout {
+                player: Some(player.to_string()),
+                operation: operation.to_string(),
+                limit,
+                source: Some(e),
+            },
+            _ => Error::DBus(e),
+        }
+    }
+
+    /// Classifies the failure of accessing the property `property`
+    /// of the player `player` like [`from_call`](Error::from_call),
+    /// also telling apart properties the player doesn't have.
+    ///
+    /// When `writing` a standard property, `InvalidArgs` is taken to
+    /// be about the value instead, and isn't reclassified.
+    pub(crate) fn from_property_call(
+        player: &str,
+        property: &str,
+        limit: Duration,
+        writing: bool,
+        e: dbus::Error,
+    ) -> Error {
+        let unknown = match e.name() {
+            Some(name) if !UNKNOWN_PROPERTY.contains(&name) => false,
+            Some("org.freedesktop.DBus.Error.InvalidArgs") => {
+                !writing || spec::PLAYER.property(property).is_none()
+            }
+            Some(_) => true,
+            None => false,
+        };
+
+        if unknown {
+            Error::UnknownProperty {
+                player: player.to_string(),
+                property: property.to_string(),
+                source: e,
+            }
+        } else {
+            Error::from_call(player, property, limit, e)
+        }
+    }
+}
+
+/// The standard property whose name is closest to `property`, if
+/// any is close enough to be a likely misspelling of it.
+fn nearest_property(property: &str) -> Option<&'static str> {
+    let lowered = property.to_lowercase();
+    spec::PLAYER
+        .properties
+        .iter()
+        .map(|candidate| {
+            (
+                edit_distance(&lowered, &candidate.name.to_lowercase()),
+                candidate.name,
+            )
+        })
+        .filter(|(distance, _)| *distance <= 2.max(lowered.len() / 4))
+        .min_by_key(|(distance, _)| *distance)
+        .filter(|(_, candidate)| *candidate != property)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The player among `names`, or the application one of them belongs
+/// to, whose name is closest to `requested`, if any is close enough
+/// to be a likely misspelling of it.
+fn nearest_player(requested: &str, names: &[String]) -> Option<String> {
+    let lowered = requested.to_lowercase();
+    names
+        .iter()
+        .flat_map(|name| vec![util::app_name(name), name.as_str()])
+        .map(|candidate| {
+            (
+                edit_distance(&lowered, &candidate.to_lowercase()),
+                candidate,
+            )
+        })
+        .filter(|(distance, _)| *distance <= 2.max(lowered.len() / 4))
+        .min_by_key(|(distance, _)| *distance)
+        .filter(|(_, candidate)| *candidate != requested)
+        .map(|(_, candidate)| candidate.to_string())
+}
+
+/// The Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substituted = diagonal + (ca != *cb) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Retried { source, .. } => Some(&**source),
+            _ => self
+                .dbus_error()
+                .map(|e| e as &(dyn std::error::Error + 'static)),
+        }
+    }
+}
+
+impl From<dbus::Error> for Error {
+    fn from(e: dbus::Error) -> Error {
+        Error::DBus(e)
+    }
+}
@@ -0,0 +1,316 @@
+use crate::{util, ChangedProperties, Error, EventType, PropertyValue, Result};
+use dbus::{arg::PropMap, message::Message, nonblock::SyncConnection};
+use std::{future::Future, time::Duration};
+
+/// A parsed MPRIS signal.
+#[derive(Clone, Debug)]
+pub enum Event {
+    /// Properties of a player changed.
+    PropertiesChanged(PropertiesChangedEvent),
+    /// A player's active track was seeked.
+    Seeked(SeekedEvent),
+    /// A player appeared on, vanished from, or changed
+    /// owner on the bus.
+    PlayerLifecycle(LifecycleEvent),
+}
+
+impl Event {
+    /// Parses a signal, resolving the player that sent it via the bus.
+    ///
+    /// If the sender can't be resolved to an MPRIS player,
+    /// the raw sender name is used instead.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the message is not a supported
+    /// signal, or if resolving the sender fails.
+    pub fn from_message<'c>(
+        msg: &Message,
+        conn: &'c SyncConnection,
+    ) -> impl Future<Output = Result<Event>> + Send + 'c {
+        // Parsed up front, as `Message` isn't `Sync` and so can't
+        // be borrowed across the lookup
+        let parsed = Event::parse_unresolved(msg);
+        async move {
+            let (mut event, sender) = parsed?;
+            if let Some(sender) = sender {
+                event.set_player(resolve_player(sender, conn).await?);
+            }
+
+            Ok(event)
+        }
+    }
+
+    /// Same as `from_message`, looking senders up in `senders` first.
+    pub(crate) fn from_message_cached<'c>(
+        msg: &Message,
+        conn: &'c SyncConnection,
+        senders: &'c util::SenderCache,
+    ) -> impl Future<Output = Result<Event>> + Send + 'c {
+        let parsed = Event::parse_unresolved(msg);
+        async move {
+            let (mut event, sender) = parsed?;
+            if let Some(sender) = sender {
+                event.set_player(senders.resolve(&sender, conn).await?);
+            }
+
+            Ok(event)
+        }
+    }
+
+    /// Parses a signal as though its sender were the player that
+    /// emitted it, also returning the sender if it has yet to be
+    /// resolved to that player.
+    fn parse_unresolved(msg: &Message) -> Result<(Event, Option<String>)> {
+        if msg.member().as_deref() == Some("NameOwnerChanged") {
+            return Ok((Event::PlayerLifecycle(LifecycleEvent::parse(msg)?), None));
+        }
+
+        let sender = message_sender(msg)?;
+        Ok((Event::parse(msg, sender.clone())?, Some(sender)))
+    }
+
+    /// Attributes the event to `player`. Lifecycle events carry
+    /// their own player name, so they are left as they are.
+    fn set_player(&mut self, player: String) {
+        match self {
+            Event::PropertiesChanged(e) => e.player = player,
+            Event::Seeked(e) => e.player = player,
+            Event::PlayerLifecycle(_) => {}
+        }
+    }
+
+    /// Parses a signal that is already known to have come
+    /// from `player`. This does not touch the bus.
+    ///
+    /// Lifecycle events carry their own player name, so
+    /// `player` is ignored for them.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the message is not a supported
+    /// signal, or if its arguments are malformed.
+    pub fn parse<T>(msg: &Message, player: T) -> Result<Event>
+    where
+        T: Into<String>,
+    {
+        match msg.member().as_deref() {
+            Some("PropertiesChanged") => Ok(Event::PropertiesChanged(PropertiesChangedEvent {
+                player: player.into(),
+                properties: ChangedProperties::parse(msg)?,
+            })),
+            Some("Seeked") => Ok(Event::Seeked(SeekedEvent::parse(msg, player)?)),
+            Some("NameOwnerChanged") => Ok(Event::PlayerLifecycle(LifecycleEvent::parse(msg)?)),
+            _ => Err(Error::Parse(
+                "The provided message was not an MPRIS signal.".into(),
+            )),
+        }
+    }
+
+    /// The name of the player that emitted the event.
+    pub fn player(&self) -> &str {
+        match self {
+            Event::PropertiesChanged(e) => &e.player,
+            Event::Seeked(e) => &e.player,
+            Event::PlayerLifecycle(e) => e.name(),
+        }
+    }
+
+    /// The type of the event.
+    pub fn event_type(&self) -> EventType {
+        match self {
+            Event::PropertiesChanged(_) => EventType::PropertiesChanged,
+            Event::Seeked(_) => EventType::Seeked,
+            Event::PlayerLifecycle(_) => EventType::PlayerLifecycle,
+        }
+    }
+}
+
+/// A parsed `PropertiesChanged` signal.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PropertiesChangedEvent {
+    /// The name of the player whose properties changed, in the same
+    /// form that is passed to [`Player::try_new`](crate::Player::try_new).
+    pub player: String,
+    /// The properties that changed.
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub properties: ChangedProperties,
+}
+
+/// A change in ownership of an MPRIS player's bus name.
+///
+/// Names are reported in the same form that is passed to
+/// [`Player::try_new`](crate::Player::try_new).
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "change")
+)]
+pub enum LifecycleEvent {
+    /// A player started.
+    Appeared { name: String },
+    /// A player quit.
+    Vanished { name: String },
+    /// A player's name was taken over by a different connection,
+    /// for instance when the player restarted.
+    Replaced { name: String },
+}
+
+impl LifecycleEvent {
+    /// Parses a `NameOwnerChanged` signal for an MPRIS player.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the message is not a `NameOwnerChanged`
+    /// signal for an MPRIS name, or its arguments are malformed.
+    pub fn parse(msg: &Message) -> Result<LifecycleEvent> {
+        if msg.member().as_deref() != Some("NameOwnerChanged") {
+            return Err(Error::Parse(
+                "The provided message was not a NameOwnerChanged signal.".into(),
+            ));
+        }
+
+        let (name, old_owner, new_owner): (&str, &str, &str) = msg.read3().map_err(|e| {
+            Error::Parse(format!("The NameOwnerChanged signal is malformed: {}", e))
+        })?;
+        let name = name
+            .strip_prefix(util::MPRIS_PREFIX)
+            .ok_or_else(|| {
+                Error::Parse("The NameOwnerChanged signal is not for an MPRIS player.".into())
+            })?
+            .to_string();
+
+        match (old_owner.is_empty(), new_owner.is_empty()) {
+            (true, false) => Ok(LifecycleEvent::Appeared { name }),
+            (false, true) => Ok(LifecycleEvent::Vanished { name }),
+            (false, false) => Ok(LifecycleEvent::Replaced { name }),
+            (true, true) => Err(Error::Parse(
+                "The NameOwnerChanged signal has no owners.".into(),
+            )),
+        }
+    }
+
+    /// The name of the player concerned.
+    pub fn name(&self) -> &str {
+        match self {
+            LifecycleEvent::Appeared { name }
+            | LifecycleEvent::Vanished { name }
+            | LifecycleEvent::Replaced { name } => name,
+        }
+    }
+}
+
+/// The unique name of the connection that sent `msg`.
+fn message_sender(msg: &Message) -> Result<String> {
+    Ok(msg
+        .sender()
+        .ok_or_else(|| Error::Parse("The signal has no sender.".into()))?
+        .to_string())
+}
+
+/// Resolves `sender` to the player that owns it, falling back to
+/// `sender` itself if it isn't an MPRIS player.
+async fn resolve_player(sender: String, conn: &SyncConnection) -> Result<String> {
+    Ok(util::resolve_sender(&sender, conn).await?.unwrap_or(sender))
+}
+
+/// A parsed `Seeked` signal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SeekedEvent {
+    /// The name of the player that seeked, in the same
+    /// form that is passed to [`Player::try_new`](crate::Player::try_new).
+    pub player: String,
+    /// The new position of the active track.
+    ///
+    /// Some players momentarily report negative positions
+    /// (mpv does this around track changes); these are
+    /// clamped to zero.
+    #[cfg_attr(feature = "serde", serde(with = "crate::wire::micros"))]
+    pub position: Duration,
+}
+
+impl SeekedEvent {
+    /// Parses a `Seeked` signal, resolving the player
+    /// that sent it via the bus.
+    ///
+    /// If the sender can't be resolved to an MPRIS player,
+    /// the raw sender name is used instead.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the message is not a valid
+    /// `Seeked` signal, or if resolving the sender fails.
+    pub fn from_message<'c>(
+        msg: &Message,
+        conn: &'c SyncConnection,
+    ) -> impl Future<Output = Result<SeekedEvent>> + Send + 'c {
+        let parsed = message_sender(msg)
+            .and_then(|sender| Ok((SeekedEvent::parse(msg, sender.clone())?, sender)));
+        async move {
+            let (mut event, sender) = parsed?;
+            event.player = resolve_player(sender, conn).await?;
+
+            Ok(event)
+        }
+    }
+
+    /// Parses a `Seeked` signal that is already known to have
+    /// come from `player`. This does not touch the bus.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the message is not a `Seeked` signal,
+    /// or if its position argument is missing or mistyped.
+    pub fn parse<T>(msg: &Message, player: T) -> Result<SeekedEvent>
+    where
+        T: Into<String>,
+    {
+        if msg.member().as_deref() != Some("Seeked") {
+            return Err(Error::Parse(
+                "The provided message was not a Seeked signal.".into(),
+            ));
+        }
+
+        let position = msg.iter_init().get_refarg();
+        let position = position
+            .as_deref()
+            .and_then(util::micros)
+            .ok_or_else(|| Error::Parse("The Seeked signal has no valid position.".into()))?;
+
+        Ok(SeekedEvent {
+            player: player.into(),
+            position,
+        })
+    }
+}
+
+/// A change to one property, passed to callbacks added with
+/// [`EventManager::add_property_callback`](crate::EventManager::add_property_callback).
+#[derive(Clone, Debug)]
+pub struct PropertyChange {
+    /// The name of the player whose property changed.
+    pub player: String,
+    /// The name of the property, such as `Volume`.
+    pub name: String,
+    /// The new value of the property, or
+    /// [`Invalidated`](PropertyValue::Invalidated) if it was only
+    /// invalidated.
+    pub value: PropertyValue,
+}
+
+/// An item of [`EventManager::track_changes`](crate::EventManager::track_changes).
+#[derive(Debug)]
+pub enum TrackChange {
+    /// A new track started, with its metadata.
+    Track(PropMap),
+    /// The player no longer has a track, for instance
+    /// because it was stopped or has exited.
+    NoTrack,
+}
+
+impl Clone for TrackChange {
+    fn clone(&self) -> Self {
+        match self {
+            TrackChange::Track(metadata) => TrackChange::Track(util::clone_prop_map(metadata)),
+            TrackChange::NoTrack => TrackChange::NoTrack,
+        }
+    }
+}
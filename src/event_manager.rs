@@ -1,14 +1,48 @@
-use crate::Result as DefaultResult;
+use crate::{
+    delivery::{spawn_callback, DeliveryGate, EventQueue, Handler, HandlerFn},
+    position::resync_changes,
+    runtime, trace,
+    util::{self, ConnRef},
+    ActivePlayerTracker, CallbackOrdering, ChangedProperties, Coalesced, DebugSink, Error, Event,
+    LifecycleEvent, MilestonePolicy, PausePolicy, PendingPlayer, PlaybackClock, PlaybackStatus,
+    Player, PlayerState, PositionChanges, PositionDedupOptions, PositionTicks, ProgressMilestones,
+    PropertiesChangedEvent, PropertyChange, Result as DefaultResult, StampedEvent,
+    SubscriptionOptions, TrackChange,
+};
 use dbus::{
-    channel::Token,
-    message::{MatchRule, Message},
-    nonblock::{MsgMatch, SyncConnection},
-    strings::{Member, Path},
+    arg::PropMap,
+    channel::{MatchingReceiver, Sender, Token},
+    message::{MatchRule, Message, MessageType},
+    nonblock::{MethodReply, Proxy, SyncConnection},
+    strings::{BusName, Interface, Member, Path},
+};
+use futures::{
+    future,
+    stream::{self, BoxStream},
+    Future, Stream, StreamExt,
 };
-use std::error::Error;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+const DBUS_NAME: &str = "org.freedesktop.DBus";
+const DBUS_PATH: &str = "/org/freedesktop/DBus";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const POSITION_RESYNC_INTERVAL: Duration = Duration::from_secs(10);
 
 /// Enum for indicating which type of MPRIS event to listen
 /// for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum EventType {
     /// Emitted whenever properties change.
     /// A list of all properties that will
@@ -19,22 +53,591 @@ pub enum EventType {
     PropertiesChanged,
     /// Emitted whenever the active track is seeked.
     Seeked,
+    /// Emitted whenever an MPRIS player appears on,
+    /// vanishes from, or changes owner on the bus.
+    PlayerLifecycle,
+}
+
+impl EventType {
+    fn member(&self) -> &'static str {
+        match self {
+            EventType::PropertiesChanged => "PropertiesChanged",
+            EventType::Seeked => "Seeked",
+            EventType::PlayerLifecycle => "NameOwnerChanged",
+        }
+    }
+
+    fn interface(&self) -> &'static str {
+        match self {
+            EventType::PropertiesChanged => PROPERTIES_INTERFACE,
+            EventType::Seeked => PLAYER_INTERFACE,
+            EventType::PlayerLifecycle => DBUS_NAME,
+        }
+    }
+
+    fn path(&self) -> &'static str {
+        match self {
+            EventType::PlayerLifecycle => DBUS_PATH,
+            _ => MPRIS_PATH,
+        }
+    }
+
+    /// Returns the rule used to match signals of this type.
+    ///
+    /// The rule matches on the signal's interface, member and
+    /// object path. [`MatchRule`] has no way to express a signal's
+    /// arguments, so the manager adds
+    /// `arg0='org.mpris.MediaPlayer2.Player'` to the rule it sends
+    /// the bus for `PropertiesChanged`, and checks both that and the
+    /// name of `PlayerLifecycle` signals with
+    /// [`matches`](EventType::matches) as they arrive.
+    pub fn match_rule(&self) -> MatchRule<'static> {
+        let mut rule = MatchRule::new_signal(
+            Interface::from(self.interface()),
+            Member::from(self.member()),
+        );
+        rule.path = Some(Path::from(self.path()));
+        if *self == EventType::PlayerLifecycle {
+            rule.sender = Some(DBUS_NAME.into());
+        }
+        rule
+    }
+
+    /// Whether `msg` is a signal of this type, emitted for the
+    /// `org.mpris.MediaPlayer2.Player` interface (or, for
+    /// `PlayerLifecycle`, concerning an MPRIS player's name).
+    pub fn matches(&self, msg: &Message) -> bool {
+        if !self.match_rule().matches(msg) {
+            return false;
+        }
+
+        match self {
+            EventType::PropertiesChanged => msg.read1::<&str>().ok() == Some(PLAYER_INTERFACE),
+            EventType::Seeked => true,
+            EventType::PlayerLifecycle => msg
+                .read1::<&str>()
+                .is_ok_and(|name| name.starts_with(util::MPRIS_PREFIX)),
+        }
+    }
+
+    /// The rule used when interface filtering is turned off, matching
+    /// any signal with the right member on the MPRIS object path.
+    fn unfiltered_match_rule(&self) -> MatchRule<'static> {
+        let mut rule = MatchRule::new();
+        rule.msg_type = Some(MessageType::Signal);
+        rule.member = Some(Member::from(self.member()));
+        rule.path = Some(Path::from(self.path()));
+        rule
+    }
+}
+
+/// A match of a callback, kept so that it can be registered again
+/// on another connection.
+struct Binding {
+    rule: MatchRule<'static>,
+    handler: Arc<Handler>,
+}
+
+/// The matches behind one registered callback.
+struct Registration {
+    /// The tokens of the matches on the current connection, which
+    /// only differ from the key after a [rebind](EventManager::rebind).
+    tokens: Vec<Token>,
+    bindings: Vec<Binding>,
+    event_types: Vec<EventType>,
+    fired: Arc<AtomicU64>,
+    raw_rule: Option<String>,
+    detached: bool,
+}
+
+impl Registration {
+    fn new(
+        tokens: Vec<Token>,
+        bindings: Vec<Binding>,
+        event_types: Vec<EventType>,
+        fired: Arc<AtomicU64>,
+        raw_rule: Option<String>,
+    ) -> Registration {
+        Registration {
+            tokens,
+            bindings,
+            event_types,
+            fired,
+            raw_rule,
+            detached: false,
+        }
+    }
+}
+
+/// A callback registered with an [`EventManager`], as returned by
+/// [`EventManager::callbacks`].
+#[derive(Clone, PartialEq, Eq)]
+pub struct CallbackInfo {
+    /// The token identifying the callback.
+    pub token: Token,
+    /// The types of event the callback receives, which is empty
+    /// for callbacks added with [`add_raw_match`](EventManager::add_raw_match).
+    pub event_types: Vec<EventType>,
+    /// The match rule of a callback added with
+    /// [`add_raw_match`](EventManager::add_raw_match).
+    pub rule: Option<String>,
+    /// The number of times the callback has been called.
+    pub fired: u64,
+    /// Whether the callback's guard has been [detached](CallbackGuard::detach).
+    pub detached: bool,
+}
+
+impl std::fmt::Debug for CallbackInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CallbackInfo")
+            .field("token", &self.token.0)
+            .field("event_types", &self.event_types)
+            .field("rule", &self.rule)
+            .field("fired", &self.fired)
+            .field("detached", &self.detached)
+            .finish()
+    }
+}
+
+/// A match to register for a callback.
+struct CallbackMatch {
+    rule: MatchRule<'static>,
+    event_type: Option<EventType>,
+    /// Whether messages must additionally be checked against `event_type`.
+    filtered: bool,
+}
+
+/// Callbacks registered through a manager, shared with their guards,
+/// keyed by the token of each callback's first match.
+type CallbackRegistry = Arc<Mutex<HashMap<Token, Registration>>>;
+
+/// The connection of a manager, shared with its guards so that they
+/// follow it to a new one.
+#[derive(Clone)]
+struct SharedConn<'a> {
+    initial: ConnRef<'a>,
+    rebound: Arc<Mutex<Option<Arc<SyncConnection>>>>,
+}
+
+impl<'a> SharedConn<'a> {
+    fn current(&self) -> ConnRef<'a> {
+        match &*self.rebound.lock().unwrap() {
+            Some(conn) => ConnRef::Owned(conn.clone()),
+            None => self.initial.clone(),
+        }
+    }
+}
+
+/// A notice that an [`EventManager`] was rebound to another
+/// connection, from [`EventManager::resyncs`].
+///
+/// Signals sent while the old connection was down can't be
+/// recovered, so state derived from events should be fetched again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Resync {
+    /// How many times the manager has been rebound, including this one.
+    pub generation: u64,
+    /// The number of callbacks registered again on the new connection.
+    pub callbacks: usize,
+}
+
+/// An error returned by a callback added with
+/// [`EventManager::add_fallible_callback`].
+#[derive(Clone)]
+pub struct CallbackError {
+    /// The token of the callback that failed.
+    pub token: Token,
+    /// The type of event the callback was registered for.
+    pub event_type: EventType,
+    /// The error, formatted with `Display`.
+    pub message: String,
+    /// Whether the callback was unregistered after this
+    /// error, having reached the failure threshold.
+    pub unregistered: bool,
+}
+
+impl std::fmt::Debug for CallbackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // `Token` itself doesn't implement `Debug`
+        f.debug_struct("CallbackError")
+            .field("token", &self.token.0)
+            .field("event_type", &self.event_type)
+            .field("message", &self.message)
+            .field("unregistered", &self.unregistered)
+            .finish()
+    }
+}
+
+type ErrorHook = Arc<Mutex<dyn FnMut(&CallbackError) + Send>>;
+
+#[derive(Default)]
+struct ErrorState {
+    hook: Option<ErrorHook>,
+    threshold: Option<u32>,
+    count: u64,
+    last: Option<CallbackError>,
+}
+
+impl ErrorState {
+    /// Records `error`, then passes it to the hook once `state` is
+    /// unlocked again, so that the hook may look at the errors or
+    /// change the threshold itself.
+    fn report(state: &Mutex<ErrorState>, error: CallbackError) {
+        let hook = {
+            let mut state = state.lock().unwrap();
+            state.count += 1;
+            state.last = Some(error.clone());
+            state.hook.clone()
+        };
+        if let Some(hook) = hook {
+            (hook.lock().unwrap())(&error);
+        }
+    }
 }
 
 /// A struct that simplifies the process of adding
 /// and removing listeners and callbacks to/from MPRIS
 /// `DBus` signals.
+///
+/// Every method takes `&self`, so a manager can be shared between
+/// tasks. Clones share all of their state: a callback registered
+/// through one clone is listed, removed and cleared by any other,
+/// and settings such as [pausing](Self::pause) apply to all of them.
+/// Callbacks are only detached once the last clone is dropped.
+#[derive(Clone)]
 pub struct EventManager<'a> {
-    conn: &'a SyncConnection,
-    callback_tokens: Vec<Token>,
+    conn: SharedConn<'a>,
+    counts: RuleCounts,
+    callbacks: CallbackRegistry,
+    filter_interfaces: Arc<AtomicBool>,
+    callback_queue: Arc<Mutex<Option<SubscriptionOptions>>>,
+    senders: Arc<util::SenderCache>,
+    name_tracker: Arc<Mutex<Option<Token>>>,
+    errors: Arc<Mutex<ErrorState>>,
+    sequence: Arc<AtomicU64>,
+    gate: Arc<DeliveryGate>,
+    debug: Arc<Mutex<Option<DebugSink>>>,
+    resyncs: Arc<tokio::sync::watch::Sender<Resync>>,
+    _teardown: Arc<Teardown<'a>>,
 }
 
-impl EventManager<'_> {
+/// Detaches the callbacks of an [`EventManager`] once its last
+/// clone is dropped.
+///
+/// Callbacks stop firing right away, but the match rules are only
+/// removed from the bus on a best-effort basis, since the replies
+/// can't be awaited here. Use [`shutdown`](EventManager::shutdown)
+/// to be sure.
+struct Teardown<'a> {
+    conn: SharedConn<'a>,
+    counts: RuleCounts,
+    callbacks: CallbackRegistry,
+    name_tracker: Arc<Mutex<Option<Token>>>,
+}
+
+impl Drop for Teardown<'_> {
+    fn drop(&mut self) {
+        let mut callbacks = self.callbacks.lock().unwrap();
+        let conn = self.conn.current();
+        for (_, registration) in callbacks.drain() {
+            for token in registration.tokens {
+                detach_match(&conn, &self.counts, token);
+            }
+        }
+        if let Some(tracker) = self.name_tracker.lock().unwrap().take() {
+            detach_match(&conn, &self.counts, tracker);
+        }
+    }
+}
+
+/// An [`EventManager`] that shares ownership of its connection,
+/// created with [`EventManager::new_owned`].
+///
+/// It has the same API as a borrowing manager, but can be stored in
+/// `'static` state and moved into spawned tasks, along with the
+/// callback guards and subscriptions it returns. Everything else it
+/// returns is `'static` too, though streams are tied to the task
+/// polling them.
+pub type OwnedEventManager = EventManager<'static>;
+
+impl EventManager<'static> {
+    /// Creates a new event manager that shares ownership of `conn`,
+    /// rather than borrowing it.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::{EventManager, EventType, OwnedEventManager};
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let manager: OwnedEventManager = EventManager::new_owned(pris::get_connection());
+    /// tokio::spawn(async move {
+    ///     let guard = manager.add_callback(EventType::Seeked, |_| true).await;
+    ///     // ...
+    /// #   drop(guard);
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_owned(conn: Arc<SyncConnection>) -> OwnedEventManager {
+        EventManager::with_conn(ConnRef::Owned(conn))
+    }
+}
+
+impl<'a> EventManager<'a> {
     /// Creates a new event manager.
-    pub fn new(conn: &SyncConnection) -> EventManager {
+    ///
+    /// This configures `conn` to deliver each signal to every
+    /// matching callback, rather than only the first one.
+    pub fn new(conn: &'a SyncConnection) -> EventManager<'a> {
+        EventManager::with_conn(ConnRef::Borrowed(conn))
+    }
+
+    fn with_conn(conn: ConnRef<'a>) -> EventManager<'a> {
+        conn.set_signal_match_mode(true);
+        let conn = SharedConn {
+            initial: conn,
+            rebound: Arc::default(),
+        };
+        let counts = RuleCounts::default();
+        let callbacks = CallbackRegistry::default();
+        let name_tracker = Arc::new(Mutex::new(None));
+        let (resyncs, _) = tokio::sync::watch::channel(Resync {
+            generation: 0,
+            callbacks: 0,
+        });
         EventManager {
-            conn,
-            callback_tokens: Vec::new(),
+            conn: conn.clone(),
+            counts: counts.clone(),
+            callbacks: callbacks.clone(),
+            filter_interfaces: Arc::new(AtomicBool::new(true)),
+            callback_queue: Arc::default(),
+            senders: Arc::default(),
+            name_tracker: name_tracker.clone(),
+            errors: Arc::default(),
+            sequence: Arc::default(),
+            gate: Arc::default(),
+            debug: Arc::default(),
+            resyncs: Arc::new(resyncs),
+            _teardown: Arc::new(Teardown {
+                conn,
+                counts,
+                callbacks,
+                name_tracker,
+            }),
+        }
+    }
+
+    /// The connection currently in use.
+    pub(crate) fn conn(&self) -> ConnRef<'a> {
+        self.conn.current()
+    }
+
+    /// How many receivers use each rule the manager added to the bus.
+    pub(crate) fn counts(&self) -> &RuleCounts {
+        &self.counts
+    }
+
+    /// Sets whether callbacks only receive signals for the
+    /// `org.mpris.MediaPlayer2.Player` interface. This is on by default.
+    ///
+    /// When turned off, callbacks registered afterwards receive
+    /// every signal of their type emitted on the MPRIS object path,
+    /// including `PropertiesChanged` for the root and TrackList
+    /// interfaces, and signals from non-MPRIS services that happen
+    /// to export the same path. `PlayerLifecycle` callbacks are
+    /// always filtered to MPRIS names.
+    pub fn set_interface_filtering(&self, enabled: bool) {
+        self.filter_interfaces.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Sets whether callbacks registered afterwards run on tasks of
+    /// their own, each fed through a queue buffered according to
+    /// `options`, rather than on the task dispatching messages.
+    ///
+    /// By default (`None`), a callback that takes its time holds up
+    /// every other callback, subscription and method call on the
+    /// connection. With a queue, it is only its own events that
+    /// wait: they are passed to it one at a time and in order, on
+    /// tokio's blocking thread pool, so it may block. A callback
+    /// that panics is unregistered without affecting the others.
+    /// [`initial_state`](SubscriptionOptions::initial_state) is
+    /// ignored.
+    ///
+    /// This applies to [`add_callback`](Self::add_callback),
+    /// [`add_fallible_callback`](Self::add_fallible_callback) and
+    /// the other methods registering callbacks, but not to
+    /// subscriptions and streams, which are already buffered.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::{DeliveryPolicy, EventManager, EventType, SubscriptionOptions};
+    /// # async fn example(manager: &EventManager<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    /// manager.set_callback_queue(Some(SubscriptionOptions {
+    ///     capacity: 256,
+    ///     policy: DeliveryPolicy::DropOldest,
+    ///     ..Default::default()
+    /// }));
+    /// let _slow = manager
+    ///     .add_callback(EventType::PropertiesChanged, |_msg| {
+    ///         // Writing to a database is fine here
+    ///         true
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_callback_queue(&self, options: Option<SubscriptionOptions>) {
+        *self.callback_queue.lock().unwrap() = options;
+    }
+
+    /// The options callbacks are queued with, if they run on tasks
+    /// of their own. See [`set_callback_queue`](Self::set_callback_queue).
+    pub fn callback_queue(&self) -> Option<SubscriptionOptions> {
+        *self.callback_queue.lock().unwrap()
+    }
+
+    /// Wraps `callback` in a handler, running it on a task of its own
+    /// if a [callback queue](Self::set_callback_queue) is set.
+    fn handler(&self, callback: HandlerFn) -> Arc<Handler> {
+        match self.callback_queue() {
+            Some(options) => Handler::new(spawn_callback(options, callback)),
+            None => Handler::new(callback),
+        }
+    }
+
+    /// Returns the rule to register for `event_type`, and whether
+    /// messages must additionally be checked against it.
+    fn rule_for(&self, event_type: EventType) -> (MatchRule<'static>, bool) {
+        if self.filter_interfaces.load(Ordering::SeqCst) || event_type == EventType::PlayerLifecycle
+        {
+            (event_type.match_rule(), true)
+        } else {
+            (event_type.unfiltered_match_rule(), false)
+        }
+    }
+
+    /// Sets where the signals received by the callbacks, streams and
+    /// subscriptions of this manager and its clones are written,
+    /// message by message, or stops writing them with `None`, which
+    /// is the default.
+    ///
+    /// A signal is written once for every match receiving it, along
+    /// with the token of the match, including while
+    /// [paused](Self::pause).
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::{DebugSink, EventManager};
+    /// # fn example(manager: &EventManager<'_>) -> std::io::Result<()> {
+    /// let log = std::fs::File::create("signals.log")?;
+    /// manager.set_debug_sink(Some(DebugSink::writer(log)));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_debug_sink(&self, sink: Option<DebugSink>) {
+        *self.debug.lock().unwrap() = sink;
+    }
+
+    /// Where the signals received by this manager are written, if
+    /// anywhere.
+    pub fn debug_sink(&self) -> Option<DebugSink> {
+        self.debug.lock().unwrap().clone()
+    }
+
+    /// Wraps `handler` so that it respects [`pause`](Self::pause),
+    /// writing what it receives to the [debug sink](Self::set_debug_sink).
+    fn gated(&self, handler: &Arc<Handler>) -> impl FnMut(Message) -> bool + Send + 'static {
+        let gate = self.gate.clone();
+        let debug = self.debug.clone();
+        let handler = handler.clone();
+        move |msg| {
+            if let Some(sink) = &*debug.lock().unwrap() {
+                sink.signal(handler.token().0, &msg);
+            }
+            gate.deliver(&handler, msg)
+        }
+    }
+
+    /// Passes the messages matching `rule`, which must already be
+    /// added to the bus, to `handler`, until it returns `false`.
+    fn receive(
+        &self,
+        conn: &SyncConnection,
+        rule: &MatchRule<'static>,
+        handler: &Arc<Handler>,
+    ) -> Token {
+        let token = start_receiving(conn, &self.counts, rule.clone(), self.gated(handler));
+        handler.set_token(token);
+
+        token
+    }
+
+    /// Stops passing signals to callbacks, subscriptions and
+    /// streams, without removing their matches. What happens to
+    /// signals arriving in the meantime is set by `policy`.
+    ///
+    /// Pausing again while paused only changes the policy;
+    /// switching to [`PausePolicy::Drop`] discards whatever has
+    /// been buffered so far. Signals only used internally, such as
+    /// the ones keeping player names up to date, are unaffected,
+    /// as is [`wait_for_event`](Self::wait_for_event).
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::{EventManager, PausePolicy};
+    /// # fn example(manager: &EventManager<'_>) {
+    /// manager.pause(PausePolicy::Buffer { capacity: 100 });
+    /// // ... callbacks don't run here
+    /// manager.resume();
+    /// # }
+    /// ```
+    pub fn pause(&self, policy: PausePolicy) {
+        self.gate.pause(policy);
+    }
+
+    /// Resumes delivery after [`pause`](Self::pause).
+    ///
+    /// Buffered signals are passed on in the order they arrived,
+    /// on the calling thread, before any signal arriving later.
+    pub fn resume(&self) {
+        self.gate
+            .resume(|token| detach_match(&self.conn(), &self.counts, token));
+    }
+
+    /// The callbacks currently registered, ordered by token.
+    ///
+    /// This includes detached callbacks, but not the matches
+    /// behind subscriptions and streams.
+    pub fn callbacks(&self) -> Vec<CallbackInfo> {
+        let mut callbacks: Vec<CallbackInfo> = self
+            .callbacks
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&token, registration)| CallbackInfo {
+                token,
+                event_types: registration.event_types.clone(),
+                rule: registration.raw_rule.clone(),
+                fired: registration.fired.load(Ordering::Relaxed),
+                detached: registration.detached,
+            })
+            .collect();
+        callbacks.sort_by_key(|info| info.token.0);
+
+        callbacks
+    }
+
+    /// Whether delivery is currently paused.
+    pub fn is_paused(&self) -> bool {
+        self.gate.is_paused()
+    }
+
+    /// The match to register for `event_type`.
+    fn callback_match(&self, event_type: EventType) -> CallbackMatch {
+        let (rule, filtered) = self.rule_for(event_type);
+        CallbackMatch {
+            rule,
+            event_type: Some(event_type),
+            filtered,
         }
     }
 
@@ -44,53 +647,2142 @@ impl EventManager<'_> {
     /// a function. A callback takes only one parameter, a
     /// [`Message`](crate::Message).
     ///
+    /// The callback stays registered for as long as the returned
+    /// [`CallbackGuard`] is alive, unless it is
+    /// [detached](CallbackGuard::detach).
+    ///
+    /// The returned future is safe to cancel, for instance by losing
+    /// a `tokio::select!`: if it is dropped before it completes, any
+    /// match rule it already added to the bus is removed again, and
+    /// no callback is left behind. The same goes for every other
+    /// method registering callbacks or streams.
+    ///
+    /// Callbacks, subscriptions and streams listening for the same
+    /// events share a single match rule on the bus, which is only
+    /// removed along with the last of them.
+    ///
     /// # Errors
     /// Returns an `Err` if there is a failure in adding
     /// a match rule to the connection.
     ///
     /// # Example
-    /// ```
-    /// let mut manager = EventManager::new(&connection);
-    /// // Be advised that it is important that this is assigned to a variable
+    /// ```no_run
+    /// # use pris::{EventManager, EventType};
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let connection = pris::get_connection();
+    /// let manager = EventManager::new(&connection);
+    /// // The callback is removed once this guard is dropped
     /// let _incoming = manager
     ///     .add_callback(EventType::PropertiesChanged, |msg| {
     ///         println!("Data: {:?}", msg);
     ///         true
     ///     })
     ///     .await?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub async fn add_callback<F>(
-        &mut self,
+        &self,
         event_type: EventType,
         mut callback: F,
-    ) -> Result<MsgMatch, Box<dyn Error>>
+    ) -> DefaultResult<CallbackGuard<'a>>
     where
         F: FnMut(Message) -> bool + Send + 'static,
     {
-        let mut rule = MatchRule::new();
-        rule.member = Some(Member::new(match event_type {
-            EventType::PropertiesChanged => "PropertiesChanged",
-            EventType::Seeked => "Seeked",
-        })?);
-        rule.path = Some(Path::new("/org/mpris/MediaPlayer2")?);
+        self.add_callback_multi(&[event_type], move |_, msg| callback(msg))
+            .await
+    }
 
-        let msg_match = self.conn.add_match(rule).await?;
-        let registered_callback = msg_match.cb(move |msg, _: ()| callback(msg));
-        self.callback_tokens.push(registered_callback.token());
+    /// Adds one callback for several types of event, which is
+    /// also passed the type of each event it receives.
+    ///
+    /// A match is registered for each type, and they are all
+    /// removed together through the returned [`CallbackGuard`].
+    /// Once the callback returns `false`, it stops receiving
+    /// events of every type.
+    ///
+    /// # Errors
+    /// Returns an `Err` if `event_types` is empty, or if there is
+    /// a failure in adding a match rule to the connection.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::{EventManager, EventType};
+    /// # async fn example(manager: &EventManager<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    /// let _incoming = manager
+    ///     .add_callback_multi(
+    ///         &[EventType::PropertiesChanged, EventType::Seeked],
+    ///         |event_type, _| {
+    ///             println!("Cached state is stale after {:?}", event_type);
+    ///             true
+    ///         },
+    ///     )
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn add_callback_multi<F>(
+        &self,
+        event_types: &[EventType],
+        mut callback: F,
+    ) -> DefaultResult<CallbackGuard<'a>>
+    where
+        F: FnMut(EventType, Message) -> bool + Send + 'static,
+    {
+        let mut unique: Vec<EventType> = Vec::new();
+        for &event_type in event_types {
+            if !unique.contains(&event_type) {
+                unique.push(event_type);
+            }
+        }
+        if unique.is_empty() {
+            return Err(Error::InvalidArgument(
+                "At least one event type is required.".into(),
+            ));
+        }
 
-        Ok(registered_callback)
+        let matches = unique.iter().map(|&t| self.callback_match(t)).collect();
+        self.register_callback(matches, unique, None, move |_, event_type, msg| {
+            callback(event_type.unwrap(), msg)
+        })
+        .await
     }
 
-    /// Clears all registered callbacks from the manager.
+    /// Adds a callback for an arbitrary match rule, for signals that
+    /// [`EventType`] doesn't cover. [`mpris_match_rule`] is a good
+    /// starting point. The callback is removed in the same ways as
+    /// one added with [`add_callback`](Self::add_callback).
+    ///
+    /// Every message matching the rule is passed on, so keep it as
+    /// narrow as possible: the bus wakes the client up for each one,
+    /// and a rule without a path, interface or member can match a
+    /// great deal of unrelated traffic. The one exception is the rule
+    /// of [`EventType::PropertiesChanged`], with or without a sender,
+    /// which only ever asks for the player interface's changes.
     ///
     /// # Errors
-    /// Returns an `Err` if there is a failure in removing
-    /// a match from the connection.
-    pub async fn clear_callbacks(&mut self) -> DefaultResult<()> {
-        for token in &self.callback_tokens {
-            self.conn.remove_match(*token).await?;
+    /// Returns an `Err` if there is a failure in adding
+    /// the match rule to the connection.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::EventManager;
+    /// # async fn example(manager: &EventManager<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    /// let rule = pris::mpris_match_rule()
+    ///     .with_interface("org.mpris.MediaPlayer2.Player")
+    ///     .with_member("TrackChanged");
+    /// let _incoming = manager
+    ///     .add_raw_match(rule, |msg| {
+    ///         println!("Track changed: {:?}", msg);
+    ///         true
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn add_raw_match<F>(
+        &self,
+        rule: MatchRule<'static>,
+        mut callback: F,
+    ) -> DefaultResult<CallbackGuard<'a>>
+    where
+        F: FnMut(Message) -> bool + Send + 'static,
+    {
+        let description = rule.match_str();
+        self.register_callback(
+            vec![CallbackMatch {
+                rule,
+                event_type: None,
+                filtered: false,
+            }],
+            Vec::new(),
+            Some(description),
+            move |_, _, msg| callback(msg),
+        )
+        .await
+    }
+
+    /// Registers each of `matches`, all calling `callback` with the
+    /// token of the registration and the event type of the match, if
+    /// any.
+    async fn register_callback<F>(
+        &self,
+        matches: Vec<CallbackMatch>,
+        event_types: Vec<EventType>,
+        raw_rule: Option<String>,
+        callback: F,
+    ) -> DefaultResult<CallbackGuard<'a>>
+    where
+        F: FnMut(Token, Option<EventType>, Message) -> bool + Send + 'static,
+    {
+        let conn = self.conn();
+        // Undoes the rules added so far on failure or cancellation
+        let mut added = RemoveUnlessKept {
+            conn: &conn,
+            counts: &self.counts,
+            rules: Vec::new(),
+        };
+        for callback_match in &matches {
+            add_rule(&conn, &self.counts, &callback_match.rule).await?;
+            added.rules.push(&callback_match.rule);
         }
 
-        Ok(())
+        // Shared by the matches, and emptied once it returns false
+        let callback = Arc::new(Mutex::new(Some(callback)));
+        // Held until the token is known, which a callback receiving
+        // a message before then waits for
+        let own_token = Arc::new(Mutex::new(Token(0)));
+        let mut published = own_token.lock().unwrap();
+        let fired = Arc::new(AtomicU64::new(0));
+        let mut tokens = Vec::new();
+        let mut bindings = Vec::new();
+        for callback_match in &matches {
+            let CallbackMatch {
+                event_type,
+                filtered,
+                ..
+            } = *callback_match;
+            let callback = callback.clone();
+            let own_token = own_token.clone();
+            let fired = fired.clone();
+            let handler = self.handler(Box::new(move |msg| {
+                let mut callback = callback.lock().unwrap();
+                let Some(f) = callback.as_mut() else {
+                    return false;
+                };
+                if filtered && event_type.is_some_and(|t| !t.matches(&msg)) {
+                    return true;
+                }
+                fired.fetch_add(1, Ordering::Relaxed);
+                let token = *own_token.lock().unwrap();
+                let keep = f(token, event_type, msg);
+                if !keep {
+                    *callback = None;
+                }
+                keep
+            }));
+            tokens.push(self.receive(&conn, &callback_match.rule, &handler));
+            bindings.push(Binding {
+                rule: callback_match.rule.clone(),
+                handler,
+            });
+        }
+        added.disarm();
+
+        let token = tokens[0];
+        *published = token;
+        self.callbacks.lock().unwrap().insert(
+            token,
+            Registration::new(tokens, bindings, event_types, fired, raw_rule),
+        );
+        drop(published);
+
+        Ok(CallbackGuard {
+            conn: self.conn.clone(),
+            counts: self.counts.clone(),
+            callbacks: self.callbacks.clone(),
+            token,
+            detached: false,
+        })
+    }
+
+    /// Adds a callback that may fail.
+    ///
+    /// Errors returned by the callback are counted, kept as the
+    /// [last error](Self::last_callback_error), and passed to the
+    /// [error hook](Self::on_callback_error). Once a callback fails
+    /// as many times in a row as the
+    /// [threshold](Self::set_failure_threshold), it is unregistered.
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding
+    /// a match rule to the connection.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::{EventManager, EventType, SeekedEvent};
+    /// # async fn example(manager: &EventManager<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    /// manager.on_callback_error(|error| eprintln!("Callback failed: {}", error.message));
+    /// let _incoming = manager
+    ///     .add_fallible_callback(EventType::Seeked, |msg| {
+    ///         let seeked = SeekedEvent::parse(&msg, "unknown")?;
+    ///         println!("Seeked to {:?}", seeked.position);
+    ///         Ok::<_, Box<dyn std::error::Error>>(())
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn add_fallible_callback<F, E>(
+        &self,
+        event_type: EventType,
+        mut callback: F,
+    ) -> DefaultResult<CallbackGuard<'a>>
+    where
+        F: FnMut(Message) -> std::result::Result<(), E> + Send + 'static,
+        E: Display,
+    {
+        let callbacks = self.callbacks.clone();
+        let errors = self.errors.clone();
+        let mut failures = 0;
+        self.register_callback(
+            vec![self.callback_match(event_type)],
+            vec![event_type],
+            None,
+            move |token, _, msg| {
+                let message = match callback(msg) {
+                    Ok(()) => {
+                        failures = 0;
+                        return true;
+                    }
+                    Err(e) => e.to_string(),
+                };
+
+                failures += 1;
+                let threshold = errors.lock().unwrap().threshold;
+                let unregistered = threshold.is_some_and(|t| failures >= t);
+                if unregistered {
+                    callbacks.lock().unwrap().remove(&token);
+                }
+                ErrorState::report(
+                    &errors,
+                    CallbackError {
+                        token,
+                        event_type,
+                        message,
+                        unregistered,
+                    },
+                );
+                !unregistered
+            },
+        )
+        .await
+    }
+
+    /// Sets a hook to run whenever a fallible callback returns an
+    /// error. It runs wherever the callback does, which is the task
+    /// dispatching messages unless a
+    /// [callback queue](Self::set_callback_queue) is set, so it
+    /// shouldn't block. It may use the manager, such as to look at
+    /// the [last error](Self::last_callback_error), which is already
+    /// the one it is given.
+    pub fn on_callback_error<H>(&self, hook: H)
+    where
+        H: FnMut(&CallbackError) + Send + 'static,
+    {
+        self.errors.lock().unwrap().hook = Some(Arc::new(Mutex::new(hook)));
+    }
+
+    /// Sets how many consecutive errors a fallible callback may
+    /// return before it is unregistered. `None`, the default,
+    /// keeps failing callbacks registered.
+    pub fn set_failure_threshold(&self, threshold: Option<u32>) {
+        self.errors.lock().unwrap().threshold = threshold;
+    }
+
+    /// The number of errors returned by fallible callbacks so far.
+    pub fn callback_error_count(&self) -> u64 {
+        self.errors.lock().unwrap().count
+    }
+
+    /// The most recent error returned by a fallible callback.
+    pub fn last_callback_error(&self) -> Option<CallbackError> {
+        self.errors.lock().unwrap().last.clone()
+    }
+
+    /// Adds a callback that receives parsed [`Event`]s and
+    /// returns a future.
+    ///
+    /// The futures are run on the runtime rather than on the
+    /// task dispatching messages, so they may await freely. With
+    /// [`CallbackOrdering::Serial`] each future finishes before the
+    /// callback sees the next event; with
+    /// [`CallbackOrdering::Concurrent`] every event gets its own
+    /// task, and no ordering is guaranteed. A panic inside a future
+    /// only ends that future.
+    ///
+    /// As with [`add_callback`](Self::add_callback), the callback is
+    /// removed when the returned [`CallbackGuard`] is dropped.
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding a match rule
+    /// to the connection, or in looking up the players on the bus.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::{CallbackOrdering, Event, EventManager, EventType};
+    /// # async fn example(manager: &EventManager<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    /// let _incoming = manager
+    ///     .add_callback_async(EventType::Seeked, CallbackOrdering::Serial, |event| async move {
+    ///         if let Event::Seeked(seeked) = event {
+    ///             println!("{} seeked to {:?}", seeked.player, seeked.position);
+    ///         }
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn add_callback_async<F, Fut>(
+        &self,
+        event_type: EventType,
+        ordering: CallbackOrdering,
+        mut callback: F,
+    ) -> DefaultResult<CallbackGuard<'a>>
+    where
+        F: FnMut(Event) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel::<Event>();
+        runtime::detach(async move {
+            while let Some(event) = receiver.recv().await {
+                let future = runtime::spawn(callback(event));
+                if ordering == CallbackOrdering::Serial {
+                    // A panicking future has already been reported by
+                    // the panic hook
+                    future.await;
+                }
+            }
+        });
+
+        // Once the task is gone, returning false stops the feeding
+        self.add_event_callback(event_type, move |event| sender.send(event).is_ok())
+            .await
+    }
+
+    /// Adds a callback that receives parsed [`Event`]s rather than
+    /// raw messages. Messages that can't be parsed are skipped.
+    ///
+    /// As with [`add_callback`](Self::add_callback), the callback
+    /// stops receiving events once it returns `false`, and is removed
+    /// when the returned [`CallbackGuard`] is dropped.
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding a match rule
+    /// to the connection, or in looking up the players on the bus.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::{Event, EventManager, EventType};
+    /// # async fn example(manager: &EventManager<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    /// let _incoming = manager
+    ///     .add_event_callback(EventType::Seeked, |event| {
+    ///         if let Event::Seeked(seeked) = event {
+    ///             println!("{} seeked to {:?}", seeked.player, seeked.position);
+    ///         }
+    ///         true
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn add_event_callback<F>(
+        &self,
+        event_type: EventType,
+        mut callback: F,
+    ) -> DefaultResult<CallbackGuard<'a>>
+    where
+        F: FnMut(Event) -> bool + Send + 'static,
+    {
+        // Senders have to be resolved without awaiting, from names
+        // that are kept up to date as players come and go
+        self.track_names().await?;
+
+        let senders = self.senders.clone();
+        self.add_callback(event_type, move |msg| {
+            let name = msg.sender().map(|s| s.to_string()).unwrap_or_default();
+            let player = senders.cached(&name).unwrap_or(name);
+            match Event::parse(&msg, player) {
+                Ok(event) => callback(event),
+                Err(_) => true,
+            }
+        })
+        .await
+    }
+
+    /// Adds a callback that only runs when one of the `names`
+    /// Player properties changes.
+    ///
+    /// The callback runs once for every named property present
+    /// in a `PropertiesChanged` signal, including properties that
+    /// were only invalidated. Returning `false` stops the callback,
+    /// as with [`add_callback`](Self::add_callback).
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding a match rule
+    /// to the connection, or in looking up the players on the bus.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::{EventManager, PropertyValue};
+    /// # async fn example(manager: &EventManager<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    /// let _incoming = manager
+    ///     .add_property_callback(&["Volume"], |change| {
+    ///         if let PropertyValue::Double(volume) = change.value {
+    ///             println!("{} is at {}% volume", change.player, volume * 100.0);
+    ///         }
+    ///         true
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn add_property_callback<F>(
+        &self,
+        names: &[&str],
+        mut callback: F,
+    ) -> DefaultResult<CallbackGuard<'a>>
+    where
+        F: FnMut(PropertyChange) -> bool + Send + 'static,
+    {
+        self.track_names().await?;
+
+        let names: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+        let senders = self.senders.clone();
+        self.add_callback(EventType::PropertiesChanged, move |msg| {
+            let properties = match ChangedProperties::parse(&msg) {
+                Ok(properties) if properties.is_player_interface() => properties,
+                _ => return true,
+            };
+            let sender = msg.sender().map(|s| s.to_string()).unwrap_or_default();
+            let player = senders.cached(&sender).unwrap_or(sender);
+
+            for name in &names {
+                if let Some(value) = properties.get(name) {
+                    let change = PropertyChange {
+                        player: player.clone(),
+                        name: name.clone(),
+                        value,
+                    };
+                    if !callback(change) {
+                        return false;
+                    }
+                }
+            }
+            true
+        })
+        .await
+    }
+
+    /// Keeps the sender cache up to date with every player on the
+    /// bus, so that it can be used without awaiting.
+    async fn track_names(&self) -> DefaultResult<()> {
+        if self.name_tracker.lock().unwrap().is_some() {
+            return Ok(());
+        }
+
+        let conn = self.conn();
+        let token = add_name_tracker(&conn, &self.counts, &self.senders).await?;
+        // Names acquired from here on are caught by the tracker
+        {
+            let mut slot = self.name_tracker.lock().unwrap();
+            if slot.is_some() {
+                // Another caller started tracking in the meantime
+                drop(slot);
+                detach_match(&conn, &self.counts, token);
+                return Ok(());
+            }
+            *slot = Some(token);
+        }
+        if let Err(e) = self.senders.seed(&conn).await {
+            self.name_tracker.lock().unwrap().take();
+            detach_match(&conn, &self.counts, token);
+            return Err(e);
+        }
+        self.senders.set_tracking(true);
+
+        Ok(())
+    }
+
+    /// Waits for the next event of `event_type` for which
+    /// `predicate` returns `true`.
+    ///
+    /// A temporary match is registered for the duration of the
+    /// wait, and removed once it finishes, times out, or the
+    /// returned future is dropped.
+    ///
+    /// # Errors
+    /// Returns an `Err` if no matching event arrives within
+    /// `timeout`, or if there is a failure in adding the match
+    /// or parsing an event.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::{Event, EventManager, EventType, Player};
+    /// # use std::time::Duration;
+    /// # async fn example(manager: &EventManager<'_>, player: &Player<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    /// player.seek(Duration::from_secs(10)).await?;
+    /// let seeked = manager
+    ///     .wait_for_event(
+    ///         EventType::Seeked,
+    ///         |event| event.player() == player.name,
+    ///         Duration::from_secs(1),
+    ///     )
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn wait_for_event<P>(
+        &self,
+        event_type: EventType,
+        mut predicate: P,
+        timeout: Duration,
+    ) -> DefaultResult<Event>
+    where
+        P: FnMut(&Event) -> bool,
+    {
+        let (rule, filtered) = self.rule_for(event_type);
+        let (sender, mut messages) = futures::channel::mpsc::unbounded();
+        let token = add_match(&self.conn(), &self.counts, rule, move |msg| {
+            sender.unbounded_send(msg).is_ok()
+        })
+        .await?;
+        let _guard = DetachOnDrop {
+            conn: self.conn(),
+            counts: self.counts.clone(),
+            token,
+        };
+
+        let wait = async {
+            while let Some(msg) = messages.next().await {
+                if filtered && !event_type.matches(&msg) {
+                    continue;
+                }
+
+                let event = Event::from_message_cached(&msg, &self.conn(), &self.senders).await?;
+                if predicate(&event) {
+                    return Ok(event);
+                }
+            }
+
+            Err(Error::Disconnected)
+        };
+
+        runtime::timeout(timeout, wait).await.map_err(|_| {
+            trace::event!(
+                debug,
+                operation = event_type.member(),
+                limit_ms = timeout.as_millis() as u64,
+                "timed out"
+            );
+            Error::Timeout {
+                player: None,
+                operation: event_type.member().to_string(),
+                limit: timeout,
+                source: None,
+            }
+        })?
+    }
+
+    /// Returns a [`Stream`] of events of any of `event_types`,
+    /// buffered with the default [`SubscriptionOptions`].
+    ///
+    /// Matches are registered for each event type, and removed
+    /// again when the stream is dropped. Signals that fail to parse
+    /// are skipped.
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding
+    /// a match rule to the connection.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::{EventManager, EventType};
+    /// # use futures::StreamExt;
+    /// # async fn example(manager: &EventManager<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut events = manager
+    ///     .stream(&[EventType::PropertiesChanged, EventType::Seeked])
+    ///     .await?;
+    /// while let Some(event) = events.next().await {
+    ///     println!("{} sent {:?}", event.player(), event);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn stream(&self, event_types: &[EventType]) -> DefaultResult<EventStream<'a>> {
+        self.stream_with(event_types, SubscriptionOptions::default())
+            .await
+    }
+
+    /// Same as `stream`, but buffered according to `options`.
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding
+    /// a match rule to the connection.
+    pub async fn stream_with(
+        &self,
+        event_types: &[EventType],
+        options: SubscriptionOptions,
+    ) -> DefaultResult<EventStream<'a>> {
+        let conn = self.conn();
+        let senders = self.senders.clone();
+        let (queue, matches) = self.add_queued_matches(event_types, options).await?;
+
+        let events = stream::unfold(queue.clone(), |queue| async move {
+            let queued = queue.pop().await;
+            Some((queued, queue))
+        })
+        .filter_map(move |queued| {
+            let conn = conn.clone();
+            let senders = senders.clone();
+            async move {
+                let event = Event::from_message_cached(&queued.msg, &conn, &senders).await;
+                event.ok().map(|event| queued.stamp(event))
+            }
+        })
+        .boxed();
+
+        let stream = EventStream {
+            conn: self.conn(),
+            counts: self.counts.clone(),
+            matches,
+            queue,
+            events,
+        };
+        if options.initial_state && event_types.contains(&EventType::PropertiesChanged) {
+            self.queue_initial_state(&stream.queue).await?;
+        }
+
+        Ok(stream)
+    }
+
+    /// Subscribes to events of `event_type`, returning a
+    /// [`Subscription`] to receive them from. Events are buffered
+    /// with the default [`SubscriptionOptions`].
+    ///
+    /// Each subscription registers its own match and receives
+    /// every event independently of other subscriptions.
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding
+    /// a match rule to the connection.
+    pub async fn subscribe(&self, event_type: EventType) -> DefaultResult<Subscription<'a>> {
+        self.subscribe_with(event_type, SubscriptionOptions::default())
+            .await
+    }
+
+    /// Subscribes to events of `event_type`, delivering each along
+    /// with a snapshot of the state of the player that sent it.
+    ///
+    /// The snapshot is fetched when the event is received, so it may
+    /// already reflect changes made after the event was sent, and
+    /// each event costs an extra round trip to the player.
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding
+    /// the match rule to the connection.
+    pub async fn subscribe_with_snapshots(
+        &self,
+        event_type: EventType,
+        options: SubscriptionOptions,
+    ) -> DefaultResult<SnapshotSubscription<'a>> {
+        Ok(SnapshotSubscription {
+            subscription: self.subscribe_with(event_type, options).await?,
+        })
+    }
+
+    /// Returns a stream of `(player, status)` pairs, emitted only when
+    /// a player's playback status actually changes.
+    ///
+    /// The stream starts with the current status of every player on
+    /// the bus. Players that show up later are reported as soon as
+    /// they appear, and a player that vanishes is reported as
+    /// [`Stopped`](PlaybackStatus::Stopped) unless it already was.
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding the match
+    /// rules, or in listing the players on the bus.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use futures::StreamExt;
+    /// # use pris::{EventManager, PlaybackStatus};
+    /// # async fn example(manager: &EventManager<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut statuses = manager.playback_statuses().await?;
+    /// while let Some((player, status)) = statuses.next().await {
+    ///     if status != PlaybackStatus::Playing {
+    ///         println!("{} stopped playing", player);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn playback_statuses(
+        &self,
+    ) -> DefaultResult<BoxStream<'a, (String, PlaybackStatus)>> {
+        let conn = self.conn();
+        // Events are parsed lazily, possibly after their sender has
+        // left the bus, so names have to be recorded as they appear
+        self.track_names().await?;
+        // Subscribe before seeding, so no change goes unnoticed
+        let events = self
+            .stream(&[EventType::PropertiesChanged, EventType::PlayerLifecycle])
+            .await?;
+
+        let mut seed = Vec::new();
+        for player in util::get_all_players(&conn).await? {
+            if let Some(status) = current_status(&player).await {
+                seed.push((player.name, status));
+            }
+        }
+        let known: HashMap<String, PlaybackStatus> = seed.iter().cloned().collect();
+
+        let changes = stream::unfold((events, known), move |(mut events, mut known)| {
+            let conn = conn.clone();
+            async move {
+                loop {
+                    let (player, status) = match events.next().await? {
+                        Event::PropertiesChanged(changed) => {
+                            match changed.properties.playback_status {
+                                Some(status) => (changed.player, status),
+                                None => continue,
+                            }
+                        }
+                        Event::PlayerLifecycle(LifecycleEvent::Appeared { name }) => {
+                            let status = match Player::try_with(name.clone(), conn.clone()).await {
+                                Ok(player) => current_status(&player).await,
+                                Err(_) => None,
+                            };
+                            match status {
+                                Some(status) => (name, status),
+                                None => continue,
+                            }
+                        }
+                        Event::PlayerLifecycle(LifecycleEvent::Vanished { name }) => {
+                            match known.remove(&name) {
+                                Some(PlaybackStatus::Stopped) | None => continue,
+                                Some(_) => {
+                                    return Some(((name, PlaybackStatus::Stopped), (events, known)))
+                                }
+                            }
+                        }
+                        _ => continue,
+                    };
+
+                    if known.insert(player.clone(), status) != Some(status) {
+                        return Some(((player, status), (events, known)));
+                    }
+                }
+            }
+        });
+
+        Ok(stream::iter(seed).chain(changes).boxed())
+    }
+
+    /// Returns a stream of the events of every player on the bus,
+    /// paired with the name of the player, in their order of arrival.
+    ///
+    /// Players that show up later are included as soon as they
+    /// appear, and once a player quits, nothing more is reported for
+    /// it. Lifecycle events are included, so that the stream can be
+    /// followed without watching players separately; signals from
+    /// senders that aren't MPRIS players are left out.
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding the match
+    /// rules, or in listing the players on the bus.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use futures::StreamExt;
+    /// # use pris::{Event, EventManager};
+    /// # async fn example(manager: &EventManager<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut events = manager.all_player_events().await?;
+    /// while let Some((player, event)) = events.next().await {
+    ///     if let Event::PropertiesChanged(changed) = event {
+    ///         println!("{} is now {:?}", player, changed.properties.playback_status);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn all_player_events(&self) -> DefaultResult<BoxStream<'a, (String, Event)>> {
+        self.track_names().await?;
+        // Subscribe before listing, so no player goes unnoticed
+        let events = self
+            .stream(&[
+                EventType::PropertiesChanged,
+                EventType::Seeked,
+                EventType::PlayerLifecycle,
+            ])
+            .await?;
+        let mut players: HashSet<String> = util::get_all_players(&self.conn())
+            .await?
+            .into_iter()
+            .map(|player| player.name)
+            .collect();
+
+        Ok(events
+            .filter_map(move |event| {
+                let player = event.player().to_string();
+                let known = match &event {
+                    Event::PlayerLifecycle(LifecycleEvent::Vanished { name }) => {
+                        players.remove(name)
+                    }
+                    Event::PlayerLifecycle(lifecycle) => {
+                        players.insert(lifecycle.name().to_string());
+                        true
+                    }
+                    _ => players.contains(&player),
+                };
+                future::ready(known.then_some((player, event)))
+            })
+            .boxed())
+    }
+
+    /// Tracks which player is the most relevant to control, see
+    /// [`ActivePlayerTracker`] for how players are ranked.
+    ///
+    /// Every player on the bus is ranked from its current status
+    /// first, and players that show up later are ranked as soon as
+    /// they appear.
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding the match
+    /// rules, or in listing the players on the bus.
+    pub async fn active_players(&self) -> DefaultResult<ActivePlayerTracker<'a>> {
+        self.track_names().await?;
+        // Subscribe before seeding, so no change goes unnoticed
+        let events = self
+            .stream(&[
+                EventType::PropertiesChanged,
+                EventType::Seeked,
+                EventType::PlayerLifecycle,
+            ])
+            .await?
+            .stamped();
+
+        let mut seed = Vec::new();
+        for player in util::get_all_players(&self.conn()).await? {
+            let status = current_status(&player).await;
+            seed.push((player.name, status));
+        }
+
+        Ok(ActivePlayerTracker::new(self.conn(), events, seed))
+    }
+
+    /// Returns a stream of position changes for every player,
+    /// merging `Seeked` signals with `Position` property changes
+    /// that describe the same seek, see [`dedup_positions`](crate::dedup_positions).
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding
+    /// the match rules to the connection.
+    pub async fn position_changes(
+        &self,
+        options: PositionDedupOptions,
+    ) -> DefaultResult<PositionChanges<'a>> {
+        let events = self
+            .stream(&[EventType::Seeked, EventType::PropertiesChanged])
+            .await?;
+
+        Ok(crate::dedup_positions(events, options))
+    }
+
+    /// Returns a stream of the tracks played by `player`, starting
+    /// with the current one.
+    ///
+    /// Metadata updates that describe the same track as before are
+    /// suppressed. Tracks are told apart by their `mpris:trackid`,
+    /// or if a player doesn't send one, by their `xesam:url` and
+    /// then their `xesam:title`. When the player stops, or
+    /// vanishes from the bus, [`TrackChange::NoTrack`] is emitted.
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding the match
+    /// rules, or in looking up the players on the bus.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use futures::StreamExt;
+    /// # use pris::{EventManager, Player, TrackChange};
+    /// # async fn example(manager: &EventManager<'_>, player: &Player<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut tracks = manager.track_changes(player).await?;
+    /// while let Some(change) = tracks.next().await {
+    ///     if let TrackChange::Track(metadata) = change {
+    ///         let title = pris::prop_cast::<String>(&metadata, "xesam:title");
+    ///         println!("Now playing {:?}", title);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn track_changes(
+        &self,
+        player: &Player<'_>,
+    ) -> DefaultResult<BoxStream<'a, TrackChange>> {
+        let conn = self.conn();
+        let name = player.name.clone();
+        self.track_names().await?;
+        let events = self
+            .stream(&[EventType::PropertiesChanged, EventType::PlayerLifecycle])
+            .await?;
+
+        let current = match Player::try_with(name.clone(), conn.clone()).await {
+            Ok(player) => player.get_metadata().await.ok(),
+            Err(_) => None,
+        };
+        let identity = current.as_ref().and_then(util::track_identity);
+        let first = match current {
+            Some(metadata) if identity.is_some() => TrackChange::Track(metadata),
+            _ => TrackChange::NoTrack,
+        };
+
+        let changes = stream::unfold((events, identity), move |(mut events, mut identity)| {
+            let name = name.clone();
+            let conn = conn.clone();
+            async move {
+                loop {
+                    let metadata = match events.next().await? {
+                        Event::PropertiesChanged(changed) if changed.player == name => {
+                            let mut properties = changed.properties;
+                            if properties.metadata.is_none()
+                                && properties.invalidated.iter().any(|p| p == "Metadata")
+                            {
+                                if let Ok(player) =
+                                    Player::try_with(name.clone(), conn.clone()).await
+                                {
+                                    properties.metadata = player.get_metadata().await.ok();
+                                }
+                            }
+                            match properties.metadata {
+                                Some(metadata) => Some(metadata),
+                                None => continue,
+                            }
+                        }
+                        Event::PlayerLifecycle(LifecycleEvent::Vanished { name: vanished })
+                            if vanished == name =>
+                        {
+                            None
+                        }
+                        _ => continue,
+                    };
+
+                    let new_identity = metadata.as_ref().and_then(util::track_identity);
+                    if new_identity == identity {
+                        continue;
+                    }
+                    identity = new_identity;
+                    let change = match metadata {
+                        Some(metadata) if identity.is_some() => TrackChange::Track(metadata),
+                        _ => TrackChange::NoTrack,
+                    };
+                    return Some((change, (events, identity)));
+                }
+            }
+        });
+
+        Ok(stream::iter([first]).chain(changes).boxed())
+    }
+
+    /// Same as `subscribe`, but buffered according to `options`.
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding
+    /// a match rule to the connection.
+    pub async fn subscribe_with(
+        &self,
+        event_type: EventType,
+        options: SubscriptionOptions,
+    ) -> DefaultResult<Subscription<'a>> {
+        let (queue, mut matches) = self.add_queued_matches(&[event_type], options).await?;
+        let subscription = Subscription {
+            conn: self.conn(),
+            counts: self.counts.clone(),
+            token: matches.remove(0),
+            queue,
+            senders: self.senders.clone(),
+        };
+        if options.initial_state && event_type == EventType::PropertiesChanged {
+            self.queue_initial_state(&subscription.queue).await?;
+        }
+
+        Ok(subscription)
+    }
+
+    /// Queues a `PropertiesChanged` signal for each player on the
+    /// bus, carrying all of its current properties, ahead of the
+    /// real signals already queued.
+    async fn queue_initial_state(&self, queue: &EventQueue) -> DefaultResult<()> {
+        let conn = self.conn();
+        let mut initial = Vec::new();
+        for name in util::list_players(&conn).await? {
+            let bus_name = format!("{}{}", util::MPRIS_PREFIX, name);
+            let owner = match self.senders.owner(&name, &conn).await {
+                Ok(owner) => owner,
+                Err(_) => continue,
+            };
+
+            // Signals numbered before this were sent before the request
+            let before = self.sequence.load(Ordering::SeqCst);
+            let proxy = Proxy::new(
+                bus_name.as_str(),
+                MPRIS_PATH,
+                Duration::from_millis(5000),
+                &*conn,
+            );
+            let reply: Result<(PropMap,), _> = proxy
+                .method_call(PROPERTIES_INTERFACE, "GetAll", (PLAYER_INTERFACE,))
+                .await;
+            let (properties,) = match reply {
+                Ok(reply) => reply,
+                Err(_) => continue,
+            };
+            queue.supersede(&owner, before);
+
+            let mut msg =
+                Message::new_signal(MPRIS_PATH, PROPERTIES_INTERFACE, "PropertiesChanged")
+                    .map_err(Error::Parse)?
+                    .append3(PLAYER_INTERFACE, properties, Vec::<String>::new());
+            msg.set_sender(Some(BusName::new(bus_name).map_err(Error::Parse)?));
+            initial.push(msg);
+        }
+        queue.push_initial(initial);
+
+        Ok(())
+    }
+
+    /// Declares callbacks for players named `pattern`, whether or not
+    /// they are running yet. A `pattern` ending in `*` matches every
+    /// name starting with the rest of it, for instance
+    /// `"chromium.instance*"`. See [`PendingPlayer`].
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding a match
+    /// rule to the connection, or in listing the players on the bus.
+    pub async fn when_player(&self, pattern: &str) -> DefaultResult<PendingPlayer<'a>> {
+        PendingPlayer::new(self, pattern).await
+    }
+
+    /// Watches the events of a single player.
+    ///
+    /// `PropertiesChanged` and `Seeked` signals are matched by the
+    /// player's unique name, so other players don't wake up the
+    /// returned [`PlayerEvents`]. Once the player vanishes or its
+    /// name changes owner, the lifecycle event is delivered and
+    /// the events end.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the player isn't on the bus, or if there
+    /// is a failure in adding the matches to the connection.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::{EventManager, Player};
+    /// # async fn example(manager: &EventManager<'_>, player: &Player<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut events = manager.watch(player).await?;
+    /// while let Some(event) = events.next_event().await {
+    ///     println!("{:?}", event);
+    /// }
+    /// println!("{} is gone", player.name);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn watch(&self, player: &Player<'_>) -> DefaultResult<PlayerEvents<'a>> {
+        let conn = self.conn();
+        let bus_name = format!("{}{}", util::MPRIS_PREFIX, player.name);
+        let owner = self.senders.owner(&player.name, &conn).await?;
+
+        let mut callback_matches = Vec::new();
+        for event_type in [EventType::PropertiesChanged, EventType::Seeked] {
+            let mut callback_match = self.callback_match(event_type);
+            callback_match.rule.sender = Some(BusName::new(owner.clone()).map_err(Error::Parse)?);
+            callback_matches.push(callback_match);
+        }
+        callback_matches.push(self.callback_match(EventType::PlayerLifecycle));
+        let (queue, matches) = self
+            .add_queued_rules(callback_matches, SubscriptionOptions::default())
+            .await?;
+
+        let mut events = PlayerEvents {
+            conn,
+            counts: self.counts.clone(),
+            matches,
+            queue: queue.clone(),
+            events: stream::empty().boxed(),
+        };
+        // The player may have left before the lifecycle match was added
+        if util::get_name_owner(&bus_name, &events.conn).await.ok() != Some(owner) {
+            return Err(Error::InvalidPlayer(player.name.clone()));
+        }
+
+        let name = player.name.clone();
+        events.events = stream::unfold(Some(queue), move |queue| {
+            let name = name.clone();
+            async move {
+                let queue = queue?;
+                loop {
+                    let queued = queue.pop().await;
+                    let event = match Event::parse(&queued.msg, name.as_str()) {
+                        Ok(event) if event.player() == name => event,
+                        _ => continue,
+                    };
+                    // Anything after the player has gone isn't from it
+                    let next = match event {
+                        Event::PlayerLifecycle(_) => None,
+                        _ => Some(queue),
+                    };
+                    return Some((event, next));
+                }
+            }
+        })
+        .boxed();
+
+        Ok(events)
+    }
+
+    /// Estimates the position of `player` every `interval` while it
+    /// plays, for progress bars. See [`position_ticks`](crate::position_ticks)
+    /// for when positions are emitted.
+    ///
+    /// The estimate starts from a fetch of the player's state and
+    /// follows its signals. Since players don't always signal the
+    /// jump in position that comes with a change of rate, the
+    /// position is fetched again after every rate change, as well
+    /// as every ten seconds.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the player isn't on the bus, or if its
+    /// state can't be fetched.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use futures::StreamExt;
+    /// # use pris::{EventManager, Player};
+    /// # use std::time::Duration;
+    /// # async fn example(manager: &EventManager<'_>, player: &Player<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut ticks = manager
+    ///     .position_ticks(player, Duration::from_millis(500))
+    ///     .await?;
+    /// while let Some(position) = ticks.next().await {
+    ///     println!("At {:?}", position);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn position_ticks(
+        &self,
+        player: &Player<'_>,
+        interval: Duration,
+    ) -> DefaultResult<PositionTicks<'a>> {
+        let (events, state) = self.resynced_events(player).await?;
+
+        let mut clock = PlaybackClock::new(
+            state.position.unwrap_or_default(),
+            state.playback_status.unwrap_or(PlaybackStatus::Stopped),
+            state.rate.unwrap_or(1.0),
+            tokio::time::Instant::now().into_std(),
+        );
+        clock.set_length(state.metadata.as_ref().and_then(util::track_length));
+
+        Ok(crate::position_ticks(events, clock, interval))
+    }
+
+    /// Emits a milestone each time a track on `player` has been
+    /// played for long enough to satisfy `policy`, for scrobbling.
+    /// See [`progress_milestones`](crate::progress_milestones) for how
+    /// playback is accounted for.
+    ///
+    /// Like [`position_ticks`](Self::position_ticks), this starts from a
+    /// fetch of the player's state, which is fetched again after every
+    /// rate change and every ten seconds.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the player isn't on the bus, or if its
+    /// state can't be fetched.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use futures::StreamExt;
+    /// # use pris::{EventManager, MilestonePolicy, Player};
+    /// # async fn example(manager: &EventManager<'_>, player: &Player<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut milestones = manager
+    ///     .progress_milestones(player, MilestonePolicy::default())
+    ///     .await?;
+    /// while let Some(milestone) = milestones.next().await {
+    ///     println!("Scrobbling {:?}", milestone.metadata);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn progress_milestones(
+        &self,
+        player: &Player<'_>,
+        policy: MilestonePolicy,
+    ) -> DefaultResult<ProgressMilestones<'a>> {
+        let (events, state) = self.resynced_events(player).await?;
+
+        Ok(crate::progress_milestones(events, state, policy))
+    }
+
+    /// Watches `player` and fetches its state, returning its events
+    /// along with fresh reports of its position, playback status and
+    /// rate, fetched after every rate change and periodically.
+    async fn resynced_events(
+        &self,
+        player: &Player<'_>,
+    ) -> DefaultResult<(BoxStream<'a, Event>, PlayerState)> {
+        let events = self.watch(player).await?;
+        let player = Player::try_with(&player.name, self.conn()).await?;
+        let state = player.get_state().await?;
+
+        let rate_changed = Arc::new(tokio::sync::Notify::new());
+        let events = {
+            let rate_changed = rate_changed.clone();
+            events.inspect(move |event| {
+                if let Event::PropertiesChanged(changed) = event {
+                    if changed.properties.rate.is_some() {
+                        rate_changed.notify_one();
+                    }
+                }
+            })
+        };
+        let resyncs = stream::unfold(player, move |player| {
+            let rate_changed = rate_changed.clone();
+            async move {
+                loop {
+                    tokio::select! {
+                        _ = runtime::sleep(POSITION_RESYNC_INTERVAL) => {}
+                        _ = rate_changed.notified() => {}
+                    }
+                    if let Ok(state) = player.get_state().await {
+                        let properties =
+                            resync_changes(state.position, state.playback_status, state.rate);
+                        let event = Event::PropertiesChanged(PropertiesChangedEvent {
+                            player: player.name.clone(),
+                            properties,
+                        });
+                        return Some((event, player));
+                    }
+                }
+            }
+        });
+
+        Ok((stream::select(events, resyncs).boxed(), state))
+    }
+
+    /// Registers a match for each of `event_types`, all feeding
+    /// into one queue.
+    async fn add_queued_matches(
+        &self,
+        event_types: &[EventType],
+        options: SubscriptionOptions,
+    ) -> DefaultResult<(Arc<EventQueue>, Vec<Token>)> {
+        let matches = event_types
+            .iter()
+            .map(|&t| self.callback_match(t))
+            .collect();
+        self.add_queued_rules(matches, options).await
+    }
+
+    /// Registers each of `callback_matches`, all feeding into one queue.
+    async fn add_queued_rules(
+        &self,
+        callback_matches: Vec<CallbackMatch>,
+        options: SubscriptionOptions,
+    ) -> DefaultResult<(Arc<EventQueue>, Vec<Token>)> {
+        let conn = self.conn();
+        let queue = Arc::new(EventQueue::new(options, self.sequence.clone()));
+        let mut matches = Vec::new();
+        let mut added = DetachUnlessKept {
+            conn: &conn,
+            counts: &self.counts,
+            tokens: Vec::new(),
+        };
+
+        for CallbackMatch {
+            rule,
+            event_type,
+            filtered,
+        } in callback_matches
+        {
+            let feed = queue.clone();
+            let handler = Handler::new(Box::new(move |msg| {
+                if filtered && event_type.is_some_and(|t| !t.matches(&msg)) {
+                    return true;
+                }
+                // Once the consumer is gone, returning false stops the feeding
+                feed.push(msg)
+            }));
+            let token = add_match(&conn, &self.counts, rule, self.gated(&handler)).await?;
+            handler.set_token(token);
+            added.tokens.push(token);
+            matches.push(token);
+        }
+        added.disarm();
+
+        Ok((queue, matches))
+    }
+
+    /// Removes a single callback, using the token of the
+    /// [`CallbackGuard`] returned by [`add_callback`](Self::add_callback).
+    /// This also works for detached callbacks.
+    ///
+    /// The callback is removed and the bus asked to drop its match
+    /// as soon as the returned future is first polled, so cancelling
+    /// it afterwards only skips waiting for the confirmation.
+    ///
+    /// # Errors
+    /// Returns an `Err` if no callback is registered with `token`
+    /// (including one that was already removed), or if there is
+    /// a failure in removing the match from the connection.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::{EventManager, EventType};
+    /// # async fn example(manager: &EventManager<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    /// let incoming = manager
+    ///     .add_callback(EventType::Seeked, |_| true)
+    ///     .await?;
+    /// manager.remove_callback(incoming.token()).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn remove_callback(&self, token: Token) -> DefaultResult<()> {
+        let registration = self
+            .callbacks
+            .lock()
+            .unwrap()
+            .remove(&token)
+            .ok_or_else(|| {
+                Error::InvalidArgument("No callback is registered with this token.".into())
+            })?;
+        let mut errors = remove_matches(&self.conn(), &self.counts, registration.tokens).await;
+        match errors.pop() {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Removes all registered callbacks, waiting for the bus to
+    /// confirm each removal. Calling this more than once is safe.
+    ///
+    /// Dropping the last clone of the manager also detaches its
+    /// callbacks, but can't wait for the bus to acknowledge it.
+    ///
+    /// # Errors
+    /// Returns an `Err` if one or more matches couldn't be
+    /// removed, as with [`clear_callbacks`](Self::clear_callbacks).
+    pub async fn shutdown(&self) -> DefaultResult<()> {
+        let cleared = self.clear_callbacks().await;
+        let tracker = self.name_tracker.lock().unwrap().take();
+        if let Some(tracker) = tracker {
+            self.senders.set_tracking(false);
+            let mut errors = remove_matches(&self.conn(), &self.counts, vec![tracker]).await;
+            if let Some(e) = errors.pop() {
+                return Err(e.into());
+            }
+        }
+
+        cleared
+    }
+
+    /// Clears all registered callbacks from the manager.
+    ///
+    /// Every callback is forgotten by the manager, even if removing
+    /// its match fails, so clearing again is always safe. As with
+    /// [`remove_callback`](Self::remove_callback), cancelling the
+    /// returned future after it was first polled still removes
+    /// every match.
+    ///
+    /// # Errors
+    /// Returns an `Err` describing every failure if one or more
+    /// matches couldn't be removed from the connection. The
+    /// remaining matches are still removed.
+    pub async fn clear_callbacks(&self) -> DefaultResult<()> {
+        let tokens: Vec<Token> = self
+            .callbacks
+            .lock()
+            .unwrap()
+            .drain()
+            .flat_map(|(_, registration)| registration.tokens)
+            .collect();
+        let total = tokens.len();
+        let errors = remove_matches(&self.conn(), &self.counts, tokens).await;
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::MatchRemoval { total, errors })
+        }
+    }
+
+    /// Moves the manager over to `conn`, for instance after the
+    /// connection to the bus was lost and a new one was made.
+    ///
+    /// Every callback is registered again on `conn`, keeping its
+    /// token and guard, and stops receiving signals from the old
+    /// connection. Subscriptions, streams and everything else
+    /// created from the manager stay on the connection they were
+    /// created on, and have to be created again. Once done, a
+    /// [`Resync`] is sent to [`resyncs`](Self::resyncs), since
+    /// signals sent in the meantime were missed.
+    ///
+    /// The manager shares ownership of `conn` from then on, even if
+    /// it was created with a borrowed connection.
+    ///
+    /// # Errors
+    /// Returns an `Err` if a match rule can't be added to `conn`,
+    /// or if the players on it can't be listed. The manager is then
+    /// left on its old connection.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::{EventManager, EventType};
+    /// # async fn example(manager: &EventManager<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    /// let _incoming = manager.add_callback(EventType::Seeked, |_| true).await?;
+    /// // ... the bus goes away, and comes back
+    /// manager.rebind(pris::get_connection()).await?;
+    /// // `_incoming` now receives signals from the new connection
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn rebind(&self, conn: Arc<SyncConnection>) -> DefaultResult<Resync> {
+        conn.set_signal_match_mode(true);
+        let (snapshot, rules): (HashSet<Token>, Vec<MatchRule<'static>>) = {
+            let callbacks = self.callbacks.lock().unwrap();
+            let rules = callbacks.values().flat_map(|r| &r.bindings);
+            (
+                callbacks.keys().copied().collect(),
+                rules.map(|binding| binding.rule.clone()).collect(),
+            )
+        };
+
+        // Undoes the rules added so far on failure or cancellation
+        let mut added = RemoveUnlessKept {
+            conn: &conn,
+            counts: &self.counts,
+            rules: Vec::new(),
+        };
+        for rule in &rules {
+            add_rule(&conn, &self.counts, rule).await?;
+            added.rules.push(rule);
+        }
+        let tracker = if self.name_tracker.lock().unwrap().is_some() {
+            let tracker = add_name_tracker(&conn, &self.counts, &self.senders).await?;
+            let seeding = DetachUnlessKept {
+                conn: &conn,
+                counts: &self.counts,
+                tokens: vec![tracker],
+            };
+            self.senders.seed(&conn).await?;
+            seeding.disarm();
+            Some(tracker)
+        } else {
+            None
+        };
+        added.disarm();
+
+        // Nothing is awaited from here on, so that callbacks can't
+        // be registered or removed halfway through
+        let mut callbacks = self.callbacks.lock().unwrap();
+        let old = self.conn();
+        *self.conn.rebound.lock().unwrap() = Some(conn.clone());
+        for (key, registration) in callbacks.iter_mut() {
+            for token in registration.tokens.drain(..) {
+                detach_match(&old, &self.counts, token);
+            }
+            for binding in &registration.bindings {
+                if !snapshot.contains(key) {
+                    // Registered in the meantime, without a rule on `conn`
+                    if self.counts.retain(&conn, &binding.rule) {
+                        send_match_call(&conn, "AddMatch", &binding.rule);
+                    }
+                }
+                let token = self.receive(&conn, &binding.rule, &binding.handler);
+                registration.tokens.push(token);
+            }
+        }
+        if let Some(tracker) = tracker {
+            if let Some(old_tracker) = self.name_tracker.lock().unwrap().replace(tracker) {
+                detach_match(&old, &self.counts, old_tracker);
+            }
+        }
+
+        let resync = Resync {
+            generation: self.resyncs.borrow().generation + 1,
+            callbacks: callbacks.len(),
+        };
+        drop(callbacks);
+        self.resyncs.send_replace(resync);
+
+        Ok(resync)
+    }
+
+    /// Returns a stream of the times the manager is
+    /// [rebound](Self::rebind) from now on. If it is rebound more
+    /// than once before the stream is polled, only the latest is
+    /// yielded.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use futures::StreamExt;
+    /// # use pris::EventManager;
+    /// # async fn example(manager: &EventManager<'_>) {
+    /// let mut resyncs = manager.resyncs();
+    /// while let Some(resync) = resyncs.next().await {
+    ///     println!("Restored {} callbacks, refetching state", resync.callbacks);
+    /// }
+    /// # }
+    /// ```
+    pub fn resyncs(&self) -> BoxStream<'a, Resync> {
+        let receiver = self.resyncs.subscribe();
+        stream::unfold(receiver, |mut receiver| async move {
+            receiver.changed().await.ok()?;
+            let resync = *receiver.borrow_and_update();
+            Some((resync, receiver))
+        })
+        .boxed()
+    }
+}
+
+impl std::fmt::Debug for EventManager<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventManager")
+            .field("callbacks", &self.callbacks())
+            .field(
+                "filter_interfaces",
+                &self.filter_interfaces.load(Ordering::SeqCst),
+            )
+            .field("callback_queue", &self.callback_queue())
+            .field("paused", &self.is_paused())
+            .field(
+                "tracking_names",
+                &self.name_tracker.lock().unwrap().is_some(),
+            )
+            .field("callback_errors", &self.callback_error_count())
+            .finish()
+    }
+}
+
+/// A registered callback, created with [`EventManager::add_callback`].
+///
+/// The callback is removed when this is dropped, unless it has been
+/// [detached](Self::detach).
+pub struct CallbackGuard<'a> {
+    conn: SharedConn<'a>,
+    counts: RuleCounts,
+    callbacks: CallbackRegistry,
+    token: Token,
+    detached: bool,
+}
+
+impl CallbackGuard<'_> {
+    /// The token identifying this callback, which can be passed
+    /// to [`EventManager::remove_callback`]. For callbacks with
+    /// several matches, this is the token of the first one.
+    pub fn token(&self) -> Token {
+        self.token
+    }
+
+    /// Keeps the callback registered until it is removed through
+    /// the manager, or the manager itself is dropped.
+    pub fn detach(mut self) {
+        self.detached = true;
+        if let Some(registration) = self.callbacks.lock().unwrap().get_mut(&self.token) {
+            registration.detached = true;
+        }
+    }
+}
+
+impl Drop for CallbackGuard<'_> {
+    fn drop(&mut self) {
+        if self.detached {
+            return;
+        }
+        // Callbacks already removed through the manager are left alone
+        let registration = self.callbacks.lock().unwrap().remove(&self.token);
+        let conn = self.conn.current();
+        for token in registration.into_iter().flat_map(|r| r.tokens) {
+            detach_match(&conn, &self.counts, token);
+        }
+    }
+}
+
+/// A stream of events, created with [`EventManager::stream`].
+///
+/// The underlying matches are removed when this is dropped.
+pub struct EventStream<'a> {
+    conn: ConnRef<'a>,
+    counts: RuleCounts,
+    matches: Vec<Token>,
+    queue: Arc<EventQueue>,
+    events: BoxStream<'a, StampedEvent>,
+}
+
+impl<'a> EventStream<'a> {
+    /// The number of events discarded because the buffer was full.
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped()
+    }
+
+    /// Merges bursts of `PropertiesChanged` events, see [`coalesce`](crate::coalesce).
+    pub fn coalesce(self, quiet: Duration) -> Coalesced<'a> {
+        crate::coalesce(self, quiet)
+    }
+
+    /// Turns this into a stream of [`StampedEvent`]s, carrying
+    /// sequence numbers and reception times.
+    pub fn stamped(self) -> StampedEventStream<'a> {
+        StampedEventStream(self)
+    }
+}
+
+impl Stream for EventStream<'_> {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Event>> {
+        self.events
+            .poll_next_unpin(cx)
+            .map(|stamped| stamped.map(|s| s.event))
+    }
+}
+
+/// A stream of events with delivery details, created with
+/// [`EventStream::stamped`].
+pub struct StampedEventStream<'a>(EventStream<'a>);
+
+impl StampedEventStream<'_> {
+    /// The number of events discarded because the buffer was full.
+    pub fn dropped(&self) -> u64 {
+        self.0.dropped()
+    }
+}
+
+impl Stream for StampedEventStream<'_> {
+    type Item = StampedEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<StampedEvent>> {
+        self.0.events.poll_next_unpin(cx)
+    }
+}
+
+impl Drop for EventStream<'_> {
+    fn drop(&mut self) {
+        self.queue.close();
+        for &token in &self.matches {
+            detach_match(&self.conn, &self.counts, token);
+        }
+    }
+}
+
+/// A subscription to events, created with [`EventManager::subscribe`].
+///
+/// The underlying match is removed when this is dropped.
+pub struct Subscription<'a> {
+    conn: ConnRef<'a>,
+    counts: RuleCounts,
+    token: Token,
+    queue: Arc<EventQueue>,
+    senders: Arc<util::SenderCache>,
+}
+
+impl Subscription<'_> {
+    /// Receives the next event, waiting for one to arrive if
+    /// none are buffered. Signals that fail to parse are skipped.
+    pub async fn recv(&mut self) -> Event {
+        self.recv_stamped().await.event
+    }
+
+    /// Same as `recv`, along with the event's sequence number
+    /// and reception time.
+    pub async fn recv_stamped(&mut self) -> StampedEvent {
+        loop {
+            let queued = self.queue.pop().await;
+            let event = Event::from_message_cached(&queued.msg, &self.conn, &self.senders).await;
+            if let Ok(event) = event {
+                return queued.stamp(event);
+            }
+        }
+    }
+
+    /// The number of events discarded because the buffer was full.
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped()
+    }
+}
+
+impl Drop for Subscription<'_> {
+    fn drop(&mut self) {
+        self.queue.close();
+        detach_match(&self.conn, &self.counts, self.token);
+    }
+}
+
+/// The events of a single player, created with [`EventManager::watch`].
+///
+/// The underlying matches are removed when this is dropped.
+pub struct PlayerEvents<'a> {
+    conn: ConnRef<'a>,
+    counts: RuleCounts,
+    matches: Vec<Token>,
+    queue: Arc<EventQueue>,
+    events: BoxStream<'a, Event>,
+}
+
+impl PlayerEvents<'_> {
+    /// Receives the next event, waiting for one to arrive if none
+    /// are buffered. Returns `None` once the player has gone, after
+    /// delivering the [`LifecycleEvent`] saying so.
+    pub async fn next_event(&mut self) -> Option<Event> {
+        self.events.next().await
+    }
+
+    /// The number of events discarded because the buffer was full.
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped()
+    }
+}
+
+impl Stream for PlayerEvents<'_> {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Event>> {
+        self.events.poll_next_unpin(cx)
+    }
+}
+
+impl Drop for PlayerEvents<'_> {
+    fn drop(&mut self) {
+        self.queue.close();
+        for &token in &self.matches {
+            detach_match(&self.conn, &self.counts, token);
+        }
+    }
+}
+
+/// A subscription delivering events with a snapshot of the player's
+/// state, created with [`EventManager::subscribe_with_snapshots`].
+pub struct SnapshotSubscription<'a> {
+    subscription: Subscription<'a>,
+}
+
+impl SnapshotSubscription<'_> {
+    /// Receives the next event, along with the state of the player
+    /// that sent it. The state is `None` if it couldn't be fetched,
+    /// for instance because the player has since vanished.
+    pub async fn recv(&mut self) -> (Event, Option<PlayerState>) {
+        let event = self.subscription.recv().await;
+        let state = match &event {
+            Event::PlayerLifecycle(LifecycleEvent::Vanished { .. }) => None,
+            _ => match Player::try_with(event.player(), self.subscription.conn.clone()).await {
+                Ok(player) => player.get_state().await.ok(),
+                Err(_) => None,
+            },
+        };
+
+        (event, state)
+    }
+
+    /// The number of events discarded because the buffer was full.
+    pub fn dropped(&self) -> u64 {
+        self.subscription.dropped()
+    }
+}
+
+/// Returns a rule matching every signal on the MPRIS object path,
+/// to be narrowed down for [`EventManager::add_raw_match`].
+pub fn mpris_match_rule() -> MatchRule<'static> {
+    MatchRule::new()
+        .with_type(MessageType::Signal)
+        .with_path(MPRIS_PATH)
+}
+
+/// Fetches the playback status of `player`, if it reports a valid one.
+pub(crate) async fn current_status(player: &Player<'_>) -> Option<PlaybackStatus> {
+    let status: String = player.get_property("PlaybackStatus").await.ok()?;
+    status.parse().ok()
+}
+
+/// Adds a match keeping `senders` up to date as players come and go.
+async fn add_name_tracker(
+    conn: &SyncConnection,
+    counts: &RuleCounts,
+    senders: &Arc<util::SenderCache>,
+) -> std::result::Result<Token, dbus::Error> {
+    let senders = senders.clone();
+    add_match(
+        conn,
+        counts,
+        EventType::PlayerLifecycle.match_rule(),
+        move |msg| {
+            if let Ok((name, old_owner, new_owner)) = msg.read3::<&str, &str, &str>() {
+                senders.owner_changed(name, old_owner, new_owner);
+            }
+            true
+        },
+    )
+    .await
+}
+
+/// Removes a match from the connection when dropped.
+pub(crate) struct DetachOnDrop<'a> {
+    pub(crate) conn: ConnRef<'a>,
+    pub(crate) counts: RuleCounts,
+    pub(crate) token: Token,
+}
+
+impl Drop for DetachOnDrop<'_> {
+    fn drop(&mut self) {
+        detach_match(&self.conn, &self.counts, self.token);
+    }
+}
+
+/// Detaches matches that were added on the way to a registration
+/// when dropped, unless the registration went through and they were
+/// handed over with [`disarm`](Self::disarm).
+struct DetachUnlessKept<'c> {
+    conn: &'c SyncConnection,
+    counts: &'c RuleCounts,
+    tokens: Vec<Token>,
+}
+
+impl DetachUnlessKept<'_> {
+    fn disarm(mut self) {
+        self.tokens.clear();
+    }
+}
+
+impl Drop for DetachUnlessKept<'_> {
+    fn drop(&mut self) {
+        for token in self.tokens.drain(..) {
+            detach_match(self.conn, self.counts, token);
+        }
+    }
+}
+
+/// Releases the rules in it when dropped, unless they were handed
+/// over with [`disarm`](Self::disarm).
+struct RemoveUnlessKept<'c, 'r> {
+    conn: &'c SyncConnection,
+    counts: &'c RuleCounts,
+    rules: Vec<&'r MatchRule<'r>>,
+}
+
+impl RemoveUnlessKept<'_, '_> {
+    fn disarm(mut self) {
+        self.rules.clear();
+    }
+}
+
+impl Drop for RemoveUnlessKept<'_, '_> {
+    fn drop(&mut self) {
+        for rule in self.rules.drain(..) {
+            release_match(self.conn, self.counts, rule);
+        }
+    }
+}
+
+/// A match rule added to the bus, told apart by the address of the
+/// connection it was added on, as a manager moves to another
+/// connection when it is rebound.
+type RuleKey = (usize, String);
+
+/// How many receivers use each rule added to the bus by an
+/// [`EventManager`], its clones, and everything they handed out, so
+/// that identical rules are only added once and removed along with
+/// their last receiver.
+///
+/// The counts go away with the last of them, and the connections
+/// they count rules on are kept alive until then. Rules counted by a
+/// fresh `RuleCounts` are shared with nobody.
+#[derive(Clone, Default)]
+pub(crate) struct RuleCounts(Arc<Mutex<HashMap<RuleKey, usize>>>);
+
+impl RuleCounts {
+    fn key(conn: &SyncConnection, rule: &MatchRule<'_>) -> RuleKey {
+        (conn as *const SyncConnection as usize, rule.match_str())
+    }
+
+    /// Counts one more receiver of `rule`, returning whether it is
+    /// the first, in which case the rule has to be added to the bus.
+    fn retain(&self, conn: &SyncConnection, rule: &MatchRule<'_>) -> bool {
+        let mut counts = self.0.lock().unwrap();
+        let count = counts.entry(RuleCounts::key(conn, rule)).or_insert(0);
+        *count += 1;
+
+        *count == 1
+    }
+
+    /// Counts one less receiver of `rule`, returning whether it was
+    /// the last, in which case the rule has to be removed from the
+    /// bus.
+    fn release(&self, conn: &SyncConnection, rule: &MatchRule<'_>) -> bool {
+        let mut counts = self.0.lock().unwrap();
+        let key = RuleCounts::key(conn, rule);
+        match counts.get_mut(&key) {
+            Some(count) if *count > 1 => {
+                *count -= 1;
+                false
+            }
+            _ => {
+                counts.remove(&key);
+                trace::event!(debug, rule = %rule.match_str(), "match removed");
+                true
+            }
+        }
+    }
+}
+
+/// The match string to send the bus for `rule`.
+///
+/// The rule for `PropertiesChanged`, with or without a sender, also
+/// asks for the player interface as the first argument, so that the
+/// bus doesn't route the changes of players' other interfaces to us.
+fn bus_match_str(rule: &MatchRule<'_>) -> String {
+    let mut general = rule.clone();
+    general.sender = None;
+    let mut match_str = rule.match_str();
+    if general.match_str() == EventType::PropertiesChanged.match_rule().match_str() {
+        match_str.push_str(&format!(",arg0='{}'", PLAYER_INTERFACE));
+    }
+
+    match_str
+}
+
+/// Adds `rule` to the bus for one more receiver, without a callback.
+/// Only the first receiver of a rule actually adds it; the others
+/// share it.
+///
+/// Unlike [`SyncConnection::add_match_no_cb`], this is safe to
+/// cancel: the request goes out when the future is first polled,
+/// and if the future is dropped before the bus answers, the rule is
+/// released again. Nothing is sent if it is dropped before that.
+pub(crate) async fn add_rule(
+    conn: &SyncConnection,
+    counts: &RuleCounts,
+    rule: &MatchRule<'_>,
+) -> std::result::Result<(), dbus::Error> {
+    if !counts.retain(conn, rule) {
+        return Ok(());
+    }
+    let undo = RemoveUnlessKept {
+        conn,
+        counts,
+        rules: vec![rule],
+    };
+    let result = conn.add_match_no_cb(&bus_match_str(rule)).await;
+    undo.disarm();
+    match &result {
+        Ok(()) => trace::event!(debug, rule = %rule.match_str(), "match added"),
+        Err(_e) => {
+            trace::event!(debug, rule = %rule.match_str(), error = %_e, "match refused");
+            counts.release(conn, rule);
+        }
+    }
+
+    result
+}
+
+/// Adds `rule` to the bus and passes the messages matching it to
+/// `callback`, as safe to cancel as [`add_rule`].
+pub(crate) async fn add_match<F>(
+    conn: &SyncConnection,
+    counts: &RuleCounts,
+    rule: MatchRule<'static>,
+    callback: F,
+) -> std::result::Result<Token, dbus::Error>
+where
+    F: FnMut(Message) -> bool + Send + 'static,
+{
+    add_rule(conn, counts, &rule).await?;
+
+    Ok(start_receiving(conn, counts, rule, callback))
+}
+
+/// Passes the messages matching `rule`, which must already be added
+/// to the bus, to `callback`, until it returns `false` and the rule
+/// is released.
+fn start_receiving<F>(
+    conn: &SyncConnection,
+    counts: &RuleCounts,
+    rule: MatchRule<'static>,
+    mut callback: F,
+) -> Token
+where
+    F: FnMut(Message) -> bool + Send + 'static,
+{
+    let counts = counts.clone();
+    let released = rule.clone();
+    conn.start_receive(
+        rule,
+        Box::new(move |msg, conn| {
+            let keep = callback(msg);
+            if !keep {
+                release_match(conn, &counts, &released);
+            }
+            // Returning false drops the callback from the connection
+            keep
+        }),
+    )
+}
+
+/// Removes the matches of `tokens`, waiting for the bus to confirm
+/// the removal of each rule that no other receiver uses, and returns
+/// the failures.
+///
+/// Every callback is dropped and every request sent before anything
+/// is awaited, so dropping the future early only loses the
+/// confirmations, never a removal.
+async fn remove_matches(
+    conn: &SyncConnection,
+    counts: &RuleCounts,
+    tokens: Vec<Token>,
+) -> Vec<dbus::Error> {
+    let proxy = Proxy::new(DBUS_NAME, DBUS_PATH, Duration::from_secs(10), conn);
+    let replies: Vec<std::result::Result<Option<MethodReply<()>>, dbus::Error>> = tokens
+        .into_iter()
+        .map(|token| match conn.stop_receive(token) {
+            Some((rule, _)) if counts.release(conn, &rule) => Ok(Some(proxy.method_call(
+                DBUS_NAME,
+                "RemoveMatch",
+                (bus_match_str(&rule),),
+            ))),
+            Some(_) => Ok(None),
+            None => Err(dbus::Error::new_failed("No match with that id found")),
+        })
+        .collect();
+
+    let mut errors = Vec::new();
+    for reply in replies {
+        let result = match reply {
+            Ok(Some(reply)) => reply.await,
+            Ok(None) => Ok(()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            errors.push(e);
+        }
+    }
+
+    errors
+}
+
+/// Removes a match without waiting on the bus.
+///
+/// The local callback is dropped immediately, and if no other
+/// receiver uses its rule, the request to remove it from the bus is
+/// sent without awaiting its reply, which makes this usable where
+/// async removal isn't possible.
+pub(crate) fn detach_match(conn: &SyncConnection, counts: &RuleCounts, token: Token) {
+    if let Some((rule, _)) = conn.stop_receive(token) {
+        release_match(conn, counts, &rule);
+    }
+}
+
+/// Releases `rule`, asking the bus to remove it without waiting for
+/// the reply if no other receiver uses it.
+fn release_match(conn: &SyncConnection, counts: &RuleCounts, rule: &MatchRule<'_>) {
+    if counts.release(conn, rule) {
+        send_match_call(conn, "RemoveMatch", rule);
+    }
+}
+
+/// Calls `method` of the bus with `rule`, without waiting for the reply.
+fn send_match_call(conn: &SyncConnection, method: &str, rule: &MatchRule<'_>) {
+    let msg = Message::new_method_call(DBUS_NAME, DBUS_PATH, DBUS_NAME, method)
+        .map(|msg| msg.append1(bus_match_str(rule)));
+
+    if let Ok(msg) = msg {
+        let _ = conn.send(msg);
     }
 }
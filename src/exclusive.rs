@@ -0,0 +1,342 @@
+use crate::{
+    event_manager::current_status,
+    runtime,
+    util::{self, ConnRef},
+    Event, EventManager, LifecycleEvent, PlaybackStatus, Player, Result,
+};
+use futures::{
+    future,
+    stream::{self, BoxStream},
+    Stream, StreamExt,
+};
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// Which player gives way when a second one starts playing, for
+/// [`ExclusivePlayback`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExclusivePolicy {
+    /// The players that were already playing are paused.
+    #[default]
+    PauseOthers,
+    /// The player that started last is paused.
+    PauseNew,
+}
+
+/// How an [`ExclusivePlayback`] enforces that one player plays at a
+/// time.
+#[derive(Clone, Debug)]
+pub struct ExclusiveOptions {
+    /// Which player gives way.
+    pub policy: ExclusivePolicy,
+    /// The names of the players left alone, such as `kdeconnect`,
+    /// which are never paused and never cause others to be.
+    pub exempt: Vec<String>,
+    /// How long a player has to keep playing before it counts, so
+    /// that one reporting `Playing` while it buffers isn't taken as
+    /// starting. What the players paused report for as long
+    /// afterwards is put down to being paused.
+    pub settle: Duration,
+}
+
+impl Default for ExclusiveOptions {
+    fn default() -> Self {
+        ExclusiveOptions {
+            policy: ExclusivePolicy::default(),
+            exempt: Vec::new(),
+            settle: Duration::from_millis(500),
+        }
+    }
+}
+
+/// A player paused by an [`ExclusivePlayback`].
+#[derive(Debug)]
+pub struct Enforcement {
+    /// The player that was paused.
+    pub paused: String,
+    /// The player that was left playing.
+    pub playing: String,
+    /// Whether pausing it succeeded.
+    pub result: Result<()>,
+}
+
+/// Suspends and resumes the enforcement of an [`ExclusivePlayback`],
+/// from any task. Clones control the same one.
+#[derive(Clone, Debug, Default)]
+pub struct Suspension(Arc<AtomicBool>);
+
+impl Suspension {
+    /// Stops pausing players, until resumed.
+    pub fn suspend(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Pauses players again. Players that started playing while it
+    /// was suspended are left alone, until another one starts.
+    pub fn resume(&self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+
+    /// Whether enforcement is suspended.
+    pub fn is_suspended(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Keeps a single player playing at a time, by pausing the others
+/// whenever one starts, as decided by an [`ExclusivePolicy`].
+///
+/// A player counts as having started once it has kept playing for
+/// the `settle` time of its [`ExclusiveOptions`]. The players paused
+/// here are expected to report it, so that doesn't count as them
+/// changing, and neither does anything else they report for as long
+/// afterwards.
+///
+/// Players are followed, and paused, as this is polled as a stream,
+/// which yields every player that was paused. Enforcement stops once
+/// it is dropped.
+///
+/// # Example
+/// ```no_run
+/// # use futures::StreamExt;
+/// # use pris::{EventManager, ExclusiveOptions, ExclusivePlayback};
+/// # async fn example(manager: &EventManager<'_>) -> Result<(), Box<dyn std::error::Error>> {
+/// let options = ExclusiveOptions {
+///     exempt: vec!["kdeconnect".to_string()],
+///     ..ExclusiveOptions::default()
+/// };
+/// let mut exclusive = ExclusivePlayback::new(manager, options).await?;
+/// while let Some(enforcement) = exclusive.next().await {
+///     println!("Paused {} for {}", enforcement.paused, enforcement.playing);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct ExclusivePlayback<'a> {
+    suspension: Suspension,
+    enforcements: BoxStream<'a, Enforcement>,
+}
+
+impl<'a> ExclusivePlayback<'a> {
+    /// Follows the players on the bus of `manager`, and enforces
+    /// `options` from then on. The players already playing are left
+    /// alone, until another one starts.
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding the match
+    /// rules, or in listing the players on the bus.
+    pub async fn new(
+        manager: &EventManager<'a>,
+        options: ExclusiveOptions,
+    ) -> Result<ExclusivePlayback<'a>> {
+        let conn = manager.conn();
+        // Subscribe before seeding, so no change goes unnoticed
+        let events = manager.all_player_events().await?;
+
+        let mut playing = Vec::new();
+        for player in util::get_all_players(&conn).await? {
+            if current_status(&player).await == Some(PlaybackStatus::Playing) {
+                playing.push(player.name);
+            }
+        }
+
+        let suspension = Suspension::default();
+        let state = State {
+            conn,
+            events: Some(events),
+            options,
+            suspension: suspension.clone(),
+            playing,
+            pending: Vec::new(),
+            quiet: Vec::new(),
+            ready: VecDeque::new(),
+        };
+
+        Ok(ExclusivePlayback {
+            suspension,
+            enforcements: stream::unfold(state, |mut state| async move {
+                let enforcement = state.next().await?;
+                Some((enforcement, state))
+            })
+            .boxed(),
+        })
+    }
+
+    /// A handle to suspend and resume enforcement with, which can be
+    /// kept while this is polled.
+    pub fn suspension(&self) -> Suspension {
+        self.suspension.clone()
+    }
+
+    /// Same as [`Suspension::suspend`].
+    pub fn suspend(&self) {
+        self.suspension.suspend();
+    }
+
+    /// Same as [`Suspension::resume`].
+    pub fn resume(&self) {
+        self.suspension.resume();
+    }
+
+    /// Same as [`Suspension::is_suspended`].
+    pub fn is_suspended(&self) -> bool {
+        self.suspension.is_suspended()
+    }
+}
+
+impl Stream for ExclusivePlayback<'_> {
+    type Item = Enforcement;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Enforcement>> {
+        self.enforcements.poll_next_unpin(cx)
+    }
+}
+
+struct State<'a> {
+    conn: ConnRef<'a>,
+    events: Option<BoxStream<'a, (String, Event)>>,
+    options: ExclusiveOptions,
+    suspension: Suspension,
+    /// The players playing, from the first to start.
+    playing: Vec<String>,
+    /// Players that started playing, and when they will have kept at
+    /// it for long enough to count.
+    pending: Vec<(String, Instant)>,
+    /// Players paused here, and until when what they report is put
+    /// down to that.
+    quiet: Vec<(String, Instant)>,
+    ready: VecDeque<Enforcement>,
+}
+
+impl State<'_> {
+    fn is_exempt(&self, name: &str) -> bool {
+        self.options.exempt.iter().any(|exempt| exempt == name)
+    }
+
+    /// Records `player` as playing or not, as it reported.
+    fn record(&mut self, player: String, status: PlaybackStatus) {
+        let now = Instant::now();
+        self.quiet.retain(|(_, until)| *until > now);
+
+        if status != PlaybackStatus::Playing {
+            self.playing.retain(|name| *name != player);
+            self.pending.retain(|(name, _)| *name != player);
+            return;
+        }
+        if self.playing.contains(&player) {
+            return;
+        }
+        let quiet = self.quiet.iter().any(|(name, _)| *name == player);
+        if !quiet && !self.is_exempt(&player) {
+            self.pending
+                .push((player.clone(), now + self.options.settle));
+        }
+        self.playing.push(player);
+    }
+
+    async fn absorb(&mut self, player: String, event: Event) {
+        match event {
+            Event::PropertiesChanged(changed) => {
+                if let Some(status) = changed.properties.playback_status {
+                    self.record(player, status);
+                }
+            }
+            Event::PlayerLifecycle(LifecycleEvent::Vanished { .. }) => {
+                self.record(player, PlaybackStatus::Stopped);
+            }
+            Event::PlayerLifecycle(_) => {
+                // A new instance may start out playing
+                self.record(player.clone(), PlaybackStatus::Stopped);
+                let status = match Player::try_with(player.clone(), self.conn.clone()).await {
+                    Ok(found) => current_status(&found).await,
+                    Err(_) => None,
+                };
+                if let Some(status) = status {
+                    self.record(player, status);
+                }
+            }
+            Event::Seeked(_) => {}
+        }
+    }
+
+    /// Pauses whichever players have to give way to `started`, which
+    /// kept playing for long enough to count.
+    async fn enforce(&mut self, started: String) {
+        if self.suspension.is_suspended() || !self.playing.contains(&started) {
+            return;
+        }
+        let others: Vec<String> = self
+            .playing
+            .iter()
+            .filter(|name| **name != started && !self.is_exempt(name))
+            .cloned()
+            .collect();
+        let last = match others.last() {
+            Some(last) => last.clone(),
+            None => return,
+        };
+
+        let paused = match self.options.policy {
+            ExclusivePolicy::PauseOthers => others
+                .into_iter()
+                .map(|name| (name, started.clone()))
+                .collect(),
+            ExclusivePolicy::PauseNew => vec![(started, last)],
+        };
+        for (name, playing) in paused {
+            let result = match Player::try_with(name.clone(), self.conn.clone()).await {
+                Ok(player) => player.pause().await,
+                Err(e) => Err(e),
+            };
+            self.playing.retain(|other| *other != name);
+            self.pending.retain(|(other, _)| *other != name);
+            self.quiet
+                .push((name.clone(), Instant::now() + self.options.settle));
+            self.ready.push_back(Enforcement {
+                paused: name,
+                playing,
+                result,
+            });
+        }
+    }
+
+    async fn next(&mut self) -> Option<Enforcement> {
+        loop {
+            if let Some(enforcement) = self.ready.pop_front() {
+                return Some(enforcement);
+            }
+
+            let now = Instant::now();
+            if let Some(index) = self.pending.iter().position(|(_, at)| *at <= now) {
+                let (started, _) = self.pending.remove(index);
+                self.enforce(started).await;
+                continue;
+            }
+
+            let events = self.events.as_mut()?;
+            let deadline = self.pending.iter().map(|(_, at)| *at).min();
+            let settled = async {
+                match deadline {
+                    Some(deadline) => runtime::sleep_until(deadline).await,
+                    None => future::pending().await,
+                }
+            };
+            tokio::select! {
+                event = events.next() => match event {
+                    Some((player, event)) => self.absorb(player, event).await,
+                    None => self.events = None,
+                },
+                _ = settled => {}
+            }
+        }
+    }
+}
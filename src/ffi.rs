@@ -0,0 +1,478 @@
+//! A C interface to the core controls, for status bars, plugin hosts
+//! and other programs that can't link Rust directly.
+//!
+//! The interface is built on [`blocking`](crate::blocking): every
+//! function blocks until the player answers or its timeout elapses,
+//! with no runtime to set up. The header is generated with
+//! [cbindgen](https://github.com/mozilla/cbindgen), from the root of
+//! the repository:
+//!
+//! ```text
+//! cargo build --release --features ffi
+//! cbindgen --config cbindgen.toml --output pris.h
+//! ```
+//!
+//! which links against the `libpris` shared or static library built
+//! in `target/release`.
+//!
+//! # Errors
+//! Every fallible function returns a [`PrisStatus`], `PRIS_STATUS_OK`
+//! on success. The message of the last failure on a context is kept
+//! until the next call on it, and is read with [`pris_last_error`].
+//! Panics are caught at the boundary and reported as
+//! `PRIS_STATUS_PANICKED`.
+//!
+//! # Ownership
+//! Strings passed in are borrowed for the duration of the call, and
+//! must be valid, NUL-terminated UTF-8. Strings and lists handed out
+//! belong to the caller, and must be freed with the function named
+//! in their documentation, never with `free`. The string returned by
+//! [`pris_last_error`] is the exception: it stays owned by the
+//! context.
+//!
+//! # Threads
+//! A context can be used from any thread, but by one thread at a
+//! time: calls on the same context must not overlap. Use a context
+//! per thread, or a lock around a shared one. [`pris_players_free`]
+//! and [`pris_string_free`] don't take a context, and may be called
+//! from any thread.
+//!
+//! # Example
+//! ```c
+//! #include "pris.h"
+//!
+//! PrisContext *ctx;
+//! if (pris_context_new(&ctx) != PRIS_STATUS_OK)
+//!     return 1;
+//! if (pris_play_pause(ctx, "vlc") != PRIS_STATUS_OK)
+//!     fprintf(stderr, "%s\n", pris_last_error(ctx));
+//! pris_context_free(ctx);
+//! ```
+use crate::{
+    blocking::{self, Player},
+    Error, Micros, PlaybackStatus, Result,
+};
+use dbus::blocking::Connection;
+use std::{
+    ffi::{CStr, CString},
+    os::raw::c_char,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+/// A connection to the session bus, and the last error reported on it.
+pub struct PrisContext {
+    conn: Connection,
+    last_error: CString,
+}
+
+/// How a call went.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrisStatus {
+    Ok = 0,
+    /// A pointer was null, or a string wasn't valid UTF-8.
+    InvalidArgument = 1,
+    /// No player goes by the name given, or several do.
+    InvalidPlayer = 2,
+    /// The player left the bus during the call.
+    PlayerGone = 3,
+    /// The player didn't answer in time.
+    Timeout = 4,
+    /// The player doesn't support the call.
+    Unsupported = 5,
+    /// The call needs a track, and the player has none.
+    NoActiveTrack = 6,
+    /// The bus or the player reported an error.
+    DBus = 7,
+    /// Any other failure.
+    Failed = 8,
+    /// pris panicked, which is a bug.
+    Panicked = 9,
+}
+
+impl PrisStatus {
+    fn of(error: &Error) -> PrisStatus {
+        match error {
+            Error::InvalidPlayer(_)
+            | Error::UnknownPlayer { .. }
+            | Error::AmbiguousPlayer { .. }
+            | Error::NotFound { .. } => PrisStatus::InvalidPlayer,
+            Error::PlayerGone { .. } => PrisStatus::PlayerGone,
+            Error::Timeout { .. } => PrisStatus::Timeout,
+            Error::UnsupportedOperation { .. } => PrisStatus::Unsupported,
+            Error::NoActiveTrack(_) => PrisStatus::NoActiveTrack,
+            Error::InvalidArgument(_) => PrisStatus::InvalidArgument,
+            Error::DBus(_) | Error::Disconnected | Error::Connection { .. } => PrisStatus::DBus,
+            Error::Retried { source, .. } => PrisStatus::of(source),
+            _ => PrisStatus::Failed,
+        }
+    }
+}
+
+/// The playback status of a player.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrisPlaybackStatus {
+    Playing = 0,
+    Paused = 1,
+    Stopped = 2,
+}
+
+impl From<PlaybackStatus> for PrisPlaybackStatus {
+    fn from(status: PlaybackStatus) -> Self {
+        match status {
+            PlaybackStatus::Playing => PrisPlaybackStatus::Playing,
+            PlaybackStatus::Paused => PrisPlaybackStatus::Paused,
+            PlaybackStatus::Stopped => PrisPlaybackStatus::Stopped,
+        }
+    }
+}
+
+impl PrisContext {
+    /// Records how `result` went, returning its status.
+    fn finish(&mut self, result: std::thread::Result<Result<()>>) -> PrisStatus {
+        let (status, message) = match result {
+            Ok(Ok(())) => (PrisStatus::Ok, String::new()),
+            Ok(Err(e)) => (PrisStatus::of(&e), e.to_string()),
+            Err(_) => (PrisStatus::Panicked, "pris panicked.".to_string()),
+        };
+        self.fail(status, message)
+    }
+
+    /// Records `message` as the last error, returning `status`.
+    fn fail(&mut self, status: PrisStatus, message: String) -> PrisStatus {
+        // Messages can't hold NUL bytes, but player names could
+        self.last_error = CString::new(message.replace('\0', "")).unwrap_or_default();
+        status
+    }
+}
+
+/// Reads the string `s`, if it is valid.
+unsafe fn str_arg<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    CStr::from_ptr(s).to_str().ok()
+}
+
+/// Runs `f` with the player `player`, recording how it went on `ctx`.
+unsafe fn with_player<F>(ctx: *mut PrisContext, player: *const c_char, f: F) -> PrisStatus
+where
+    F: FnOnce(&Player<'_>) -> Result<()>,
+{
+    let ctx = match ctx.as_mut() {
+        Some(ctx) => ctx,
+        None => return PrisStatus::InvalidArgument,
+    };
+    let name = match str_arg(player) {
+        Some(name) => name,
+        None => {
+            let message = "The player name is null or not UTF-8.".to_string();
+            return ctx.fail(PrisStatus::InvalidArgument, message);
+        }
+    };
+
+    let conn = &ctx.conn;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| f(&Player::try_new(name, conn)?)));
+    ctx.finish(result)
+}
+
+/// Runs `f` with the player `player`, writing what it returns to `out`.
+unsafe fn get<T, F>(ctx: *mut PrisContext, player: *const c_char, out: *mut T, f: F) -> PrisStatus
+where
+    F: FnOnce(&Player<'_>) -> Result<T>,
+{
+    if out.is_null() {
+        return match ctx.as_mut() {
+            Some(ctx) => ctx.fail(PrisStatus::InvalidArgument, "The output is null.".into()),
+            None => PrisStatus::InvalidArgument,
+        };
+    }
+
+    with_player(ctx, player, |player| {
+        out.write(f(player)?);
+        Ok(())
+    })
+}
+
+/// Connects to the session bus, storing a new context in `*ctx`, or
+/// null if it fails. The context is freed with [`pris_context_free`].
+///
+/// # Safety
+/// `ctx` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pris_context_new(ctx: *mut *mut PrisContext) -> PrisStatus {
+    if ctx.is_null() {
+        return PrisStatus::InvalidArgument;
+    }
+    ctx.write(ptr::null_mut());
+
+    match panic::catch_unwind(blocking::get_connection) {
+        Ok(Ok(conn)) => {
+            let context = PrisContext {
+                conn,
+                last_error: CString::default(),
+            };
+            ctx.write(Box::into_raw(Box::new(context)));
+            PrisStatus::Ok
+        }
+        Ok(Err(e)) => PrisStatus::of(&e),
+        Err(_) => PrisStatus::Panicked,
+    }
+}
+
+/// Closes the connection of `ctx` and frees it. Does nothing if `ctx`
+/// is null.
+///
+/// # Safety
+/// `ctx` must be null or come from [`pris_context_new`], and not be
+/// used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pris_context_free(ctx: *mut PrisContext) {
+    if !ctx.is_null() {
+        drop(Box::from_raw(ctx));
+    }
+}
+
+/// The message of the last failure on `ctx`, or an empty string if
+/// the last call succeeded. It is owned by `ctx`, and valid until
+/// the next call on it.
+///
+/// # Safety
+/// `ctx` must be null or a live context; null gives null.
+#[no_mangle]
+pub unsafe extern "C" fn pris_last_error(ctx: *const PrisContext) -> *const c_char {
+    match ctx.as_ref() {
+        Some(ctx) => ctx.last_error.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Lists the names of the players on the bus, storing them in
+/// `*names` and how many there are in `*count`. The list is freed
+/// with [`pris_players_free`].
+///
+/// # Safety
+/// `ctx` must be a live context, and `names` and `count` valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn pris_list_players(
+    ctx: *mut PrisContext,
+    names: *mut *mut *mut c_char,
+    count: *mut usize,
+) -> PrisStatus {
+    let ctx = match ctx.as_mut() {
+        Some(ctx) => ctx,
+        None => return PrisStatus::InvalidArgument,
+    };
+    if names.is_null() || count.is_null() {
+        return ctx.fail(PrisStatus::InvalidArgument, "The output is null.".into());
+    }
+
+    let conn = &ctx.conn;
+    let mut found = Vec::new();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        found = blocking::list_players(conn)?
+            .into_iter()
+            .filter_map(|name| CString::new(name).ok())
+            .collect();
+        Ok(())
+    }));
+    let status = ctx.finish(result);
+    if status == PrisStatus::Ok {
+        let list: Box<[*mut c_char]> = found.into_iter().map(CString::into_raw).collect();
+        count.write(list.len());
+        names.write(Box::into_raw(list) as *mut *mut c_char);
+    }
+
+    status
+}
+
+/// Frees a list of `count` names from [`pris_list_players`]. Does
+/// nothing if `names` is null.
+///
+/// # Safety
+/// `names` and `count` must be exactly as handed out, and the list
+/// not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pris_players_free(names: *mut *mut c_char, count: usize) {
+    if names.is_null() {
+        return;
+    }
+    let list = Box::from_raw(ptr::slice_from_raw_parts_mut(names, count));
+    for name in list.iter() {
+        drop(CString::from_raw(*name));
+    }
+}
+
+/// Frees a string handed out by pris. Does nothing if `s` is null.
+///
+/// # Safety
+/// `s` must be null or a string handed out by pris, such as by
+/// [`pris_get_title`], and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pris_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Starts or resumes playback on `player`.
+///
+/// # Safety
+/// `ctx` must be a live context, and `player` a valid string.
+#[no_mangle]
+pub unsafe extern "C" fn pris_play(ctx: *mut PrisContext, player: *const c_char) -> PrisStatus {
+    with_player(ctx, player, |player| player.play())
+}
+
+/// Pauses playback on `player`.
+///
+/// # Safety
+/// `ctx` must be a live context, and `player` a valid string.
+#[no_mangle]
+pub unsafe extern "C" fn pris_pause(ctx: *mut PrisContext, player: *const c_char) -> PrisStatus {
+    with_player(ctx, player, |player| player.pause())
+}
+
+/// Toggles playback on `player`.
+///
+/// # Safety
+/// `ctx` must be a live context, and `player` a valid string.
+#[no_mangle]
+pub unsafe extern "C" fn pris_play_pause(
+    ctx: *mut PrisContext,
+    player: *const c_char,
+) -> PrisStatus {
+    with_player(ctx, player, |player| player.play_pause())
+}
+
+/// Stops playback on `player`.
+///
+/// # Safety
+/// `ctx` must be a live context, and `player` a valid string.
+#[no_mangle]
+pub unsafe extern "C" fn pris_stop(ctx: *mut PrisContext, player: *const c_char) -> PrisStatus {
+    with_player(ctx, player, |player| player.stop())
+}
+
+/// Skips to the next track on `player`.
+///
+/// # Safety
+/// `ctx` must be a live context, and `player` a valid string.
+#[no_mangle]
+pub unsafe extern "C" fn pris_next(ctx: *mut PrisContext, player: *const c_char) -> PrisStatus {
+    with_player(ctx, player, |player| player.next())
+}
+
+/// Skips to the previous track on `player`.
+///
+/// # Safety
+/// `ctx` must be a live context, and `player` a valid string.
+#[no_mangle]
+pub unsafe extern "C" fn pris_previous(ctx: *mut PrisContext, player: *const c_char) -> PrisStatus {
+    with_player(ctx, player, |player| player.previous())
+}
+
+/// Stores the playback status of `player` in `*status`.
+///
+/// # Safety
+/// `ctx` must be a live context, `player` a valid string, and
+/// `status` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pris_get_playback_status(
+    ctx: *mut PrisContext,
+    player: *const c_char,
+    status: *mut PrisPlaybackStatus,
+) -> PrisStatus {
+    get(ctx, player, status, |player| {
+        let status: String = player.get_property("PlaybackStatus")?;
+        status
+            .parse::<PlaybackStatus>()
+            .map(PrisPlaybackStatus::from)
+            .map_err(Error::Parse)
+    })
+}
+
+/// Stores the volume of `player`, usually between 0.0 and 1.0, in
+/// `*volume`.
+///
+/// # Safety
+/// `ctx` must be a live context, `player` a valid string, and
+/// `volume` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pris_get_volume(
+    ctx: *mut PrisContext,
+    player: *const c_char,
+    volume: *mut f64,
+) -> PrisStatus {
+    get(ctx, player, volume, |player| player.get_volume())
+}
+
+/// Sets the volume of `player`, clamped between 0.0 and 1.0.
+///
+/// # Safety
+/// `ctx` must be a live context, and `player` a valid string.
+#[no_mangle]
+pub unsafe extern "C" fn pris_set_volume(
+    ctx: *mut PrisContext,
+    player: *const c_char,
+    volume: f64,
+) -> PrisStatus {
+    with_player(ctx, player, |player| player.set_volume(volume).map(drop))
+}
+
+/// Stores the position of `player` in its track, in microseconds,
+/// in `*position`.
+///
+/// # Safety
+/// `ctx` must be a live context, `player` a valid string, and
+/// `position` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pris_get_position(
+    ctx: *mut PrisContext,
+    player: *const c_char,
+    position: *mut i64,
+) -> PrisStatus {
+    get(ctx, player, position, |player| {
+        let position = player.get_position()?;
+        Ok(Micros::from_duration(position).get())
+    })
+}
+
+/// Moves `player` to `position` in its track, in microseconds.
+///
+/// # Safety
+/// `ctx` must be a live context, and `player` a valid string.
+#[no_mangle]
+pub unsafe extern "C" fn pris_set_position(
+    ctx: *mut PrisContext,
+    player: *const c_char,
+    position: i64,
+) -> PrisStatus {
+    let position = Micros::new(position).to_duration();
+    with_player(ctx, player, |player| player.set_position(position))
+}
+
+/// Stores the title of the track of `player` in `*title`, or null if
+/// it has none. The title is freed with [`pris_string_free`].
+///
+/// # Safety
+/// `ctx` must be a live context, `player` a valid string, and
+/// `title` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pris_get_title(
+    ctx: *mut PrisContext,
+    player: *const c_char,
+    title: *mut *mut c_char,
+) -> PrisStatus {
+    get(ctx, player, title, |player| {
+        let title = player
+            .metadata()?
+            .as_ref()
+            .and_then(|metadata| crate::prop_display(metadata, "xesam:title"))
+            .and_then(|title| CString::new(title).ok());
+        Ok(title.map_or(ptr::null_mut(), CString::into_raw))
+    })
+}
@@ -0,0 +1,403 @@
+use crate::{
+    runtime, spec, status_rank, util, Error, IntoPlayerName, PlaybackStatus, Player, Result,
+};
+use dbus::nonblock::{stdintf::org_freedesktop_dbus::Properties, SyncConnection};
+use futures::future;
+use std::{env, fmt, time::Duration};
+
+/// How long each player is given to report its `PlaybackStatus` and
+/// `Identity`, unless changed with [`PlayerFinder::timeout`].
+const DEFAULT_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// The environment variable [`PlayerFinder::by_env_preferences`]
+/// reads the preferred players from.
+const PREFERENCES_VAR: &str = "PRIS_PLAYERS";
+
+/// A way of picking a player, tried by a [`PlayerFinder`].
+#[derive(Clone, Debug)]
+enum Rule {
+    Name(String),
+    Prefix(String),
+    Pattern(String),
+    App(String),
+    Identity(String),
+    Status(PlaybackStatus),
+    Any,
+}
+
+impl Rule {
+    fn needs_status(&self) -> bool {
+        matches!(self, Rule::Status(_) | Rule::App(_))
+    }
+
+    fn needs_identity(&self) -> bool {
+        matches!(self, Rule::Identity(_))
+    }
+
+    /// How well `candidate` matches, the lowest being the best, or
+    /// `None` if it doesn't.
+    fn rank(&self, candidate: &Candidate<'_>) -> Option<u8> {
+        match self {
+            Rule::App(app) if util::app_name(&candidate.player.name) == app => {
+                Some(candidate.status.map_or(2, status_rank))
+            }
+            Rule::App(_) => None,
+            _ => self.matches(candidate).then_some(0),
+        }
+    }
+
+    fn matches(&self, candidate: &Candidate<'_>) -> bool {
+        match self {
+            Rule::Name(name) => candidate.player.name == *name,
+            Rule::Prefix(prefix) => candidate.player.name.starts_with(prefix.as_str()),
+            Rule::Pattern(pattern) => {
+                let name = &candidate.player.name;
+                glob(pattern, name)
+                    || name
+                        .rsplit_once('.')
+                        .is_some_and(|(player, _)| glob(pattern, player))
+            }
+            Rule::Identity(identity) => candidate
+                .identity
+                .as_ref()
+                .is_some_and(|found| found.eq_ignore_ascii_case(identity)),
+            Rule::Status(status) => candidate.status == Some(*status),
+            Rule::App(_) => self.rank(candidate).is_some(),
+            Rule::Any => true,
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rule::Name(name) => write!(f, "the name {}", name),
+            Rule::Prefix(prefix) => write!(f, "a name starting with {}", prefix),
+            Rule::Pattern(pattern) => write!(f, "a name matching {}", pattern),
+            Rule::App(app) => write!(f, "an instance of {}", app),
+            Rule::Identity(identity) => write!(f, "the identity {}", identity),
+            Rule::Status(PlaybackStatus::Playing) => f.write_str("a playing player"),
+            Rule::Status(PlaybackStatus::Paused) => f.write_str("a paused player"),
+            Rule::Status(PlaybackStatus::Stopped) => f.write_str("a stopped player"),
+            Rule::Any => f.write_str("any player"),
+        }
+    }
+}
+
+/// Whether `name` matches the glob `pattern`, where `*` matches any
+/// run of characters and `?` any one character.
+fn glob(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) =
+        (pattern.chars().collect(), name.chars().collect());
+    let (mut p, mut n) = (0, 0);
+    // Where to resume after the last `*`, and how much it matched
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((after, matched)) => {
+                    p = after;
+                    n = matched + 1;
+                    star = Some((after, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// A player on the bus, with what the rules asked of it.
+struct Candidate<'a> {
+    player: Player<'a>,
+    status: Option<PlaybackStatus>,
+    identity: Option<String>,
+}
+
+/// Picks one player out of those on the bus, by trying a list of
+/// rules in order.
+///
+/// The first rule that matches a player decides, and among several
+/// players matching it, the first in the order of
+/// [`list_players`](crate::list_players) is picked. The players are
+/// listed once, and asked for their `PlaybackStatus` and `Identity`
+/// at once, only if a rule needs them; players that fail or don't
+/// answer in time match no rule needing them, rather than failing
+/// the search.
+///
+/// The players are listed again on every call to
+/// [`find`](Self::find), so one finder can be kept and reused as
+/// players come and go.
+///
+/// # Example
+/// ```no_run
+/// # use pris::PlayerFinder;
+/// # async fn example() -> pris::Result<()> {
+/// let conn = pris::get_connection();
+/// let player = PlayerFinder::new(&conn)
+///     .by_name("spotify")
+///     .or_playing()
+///     .or_any()
+///     .find()
+///     .await?;
+/// player.play_pause().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct PlayerFinder<'a> {
+    conn: &'a SyncConnection,
+    rules: Vec<Rule>,
+    timeout: Duration,
+    /// Why the first invalid name given failed, which `find` fails
+    /// with.
+    invalid: Option<String>,
+}
+
+impl<'a> PlayerFinder<'a> {
+    /// Creates a finder with no rules, which finds nothing until
+    /// some are added.
+    pub fn new(conn: &'a SyncConnection) -> Self {
+        PlayerFinder {
+            conn,
+            rules: Vec::new(),
+            timeout: DEFAULT_TIMEOUT,
+            invalid: None,
+        }
+    }
+
+    /// Adds a rule for the player `name`, or records why it can't be
+    /// the name of a player.
+    fn push_name(&mut self, name: impl IntoPlayerName) {
+        match name.into_player_name() {
+            Ok(name) => self.rules.push(Rule::Name(name.into_string())),
+            Err(e) => {
+                if self.invalid.is_none() {
+                    self.invalid = Some(match e {
+                        Error::InvalidArgument(reason) => reason,
+                        other => other.to_string(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Tries the player with the exact name `name`, such as `vlc` or
+    /// `firefox.instance_1234`, or anything else [`IntoPlayerName`]
+    /// is implemented for. If it can't be the name of a player,
+    /// [`find`](Self::find) fails.
+    pub fn by_name(mut self, name: impl IntoPlayerName) -> Self {
+        self.push_name(name);
+        self
+    }
+
+    /// Tries a player whose name starts with `prefix`, such as
+    /// `firefox` for any instance of Firefox.
+    pub fn by_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.rules.push(Rule::Prefix(prefix.into()));
+        self
+    }
+
+    /// Tries a player whose `Identity` is `identity`, such as
+    /// `Mozilla Firefox`, ignoring ASCII case.
+    pub fn by_identity(mut self, identity: impl Into<String>) -> Self {
+        self.rules.push(Rule::Identity(identity.into()));
+        self
+    }
+
+    /// Tries each of `names` in turn, as with `by_name`.
+    pub fn by_priority<I>(mut self, names: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: IntoPlayerName,
+    {
+        for name in names {
+            self.push_name(name);
+        }
+        self
+    }
+
+    /// Tries a player whose name matches the glob `pattern`, where `*`
+    /// matches any run of characters and `?` any one character, such
+    /// as `chrom*`. A pattern matching the name of a player also
+    /// matches its instances, so `firefox` matches
+    /// `firefox.instance_1234`.
+    pub fn by_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.rules.push(Rule::Pattern(pattern.into()));
+        self
+    }
+
+    /// Tries any instance of the application `app`, such as `firefox`
+    /// for `firefox.instance_1_23`, or `firefox` itself, as grouped by
+    /// [`group_players`](crate::group_players). Among several, the
+    /// first playing is picked, or else the first paused, or else the
+    /// first listed.
+    pub fn by_app(mut self, app: impl Into<String>) -> Self {
+        self.rules.push(Rule::App(app.into()));
+        self
+    }
+
+    /// Tries each of `patterns` in turn, as with `by_pattern`, so that
+    /// the first with a player matching it wins. Among several
+    /// players matching one pattern, the first listed is picked.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::PlayerFinder;
+    /// # async fn example() -> pris::Result<()> {
+    /// let conn = pris::get_connection();
+    /// // mpd if it's running, else Spotify, else whatever's playing
+    /// let player = PlayerFinder::new(&conn)
+    ///     .by_preferences(vec!["mpd", "spotify"])
+    ///     .or_playing()
+    ///     .find()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn by_preferences<I>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.rules.extend(
+            patterns
+                .into_iter()
+                .map(|pattern| Rule::Pattern(pattern.into())),
+        );
+        self
+    }
+
+    /// Same as `by_preferences`, with the comma-separated patterns of
+    /// the `PRIS_PLAYERS` environment variable, such as
+    /// `mpd,spotify,chrom*`. Adds nothing if it isn't set.
+    pub fn by_env_preferences(self) -> Self {
+        let patterns = env::var(PREFERENCES_VAR).unwrap_or_default();
+        self.by_preferences(
+            patterns
+                .split(',')
+                .map(str::trim)
+                .filter(|pattern| !pattern.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Tries a player that is playing.
+    pub fn or_playing(mut self) -> Self {
+        self.rules.push(Rule::Status(PlaybackStatus::Playing));
+        self
+    }
+
+    /// Tries a player that is paused.
+    pub fn or_paused(mut self) -> Self {
+        self.rules.push(Rule::Status(PlaybackStatus::Paused));
+        self
+    }
+
+    /// Tries any player at all.
+    pub fn or_any(mut self) -> Self {
+        self.rules.push(Rule::Any);
+        self
+    }
+
+    /// Sets how long each player is given to answer, 250 milliseconds
+    /// unless changed.
+    pub fn timeout(mut self, limit: Duration) -> Self {
+        self.timeout = limit;
+        self
+    }
+
+    /// The connection players are looked for on.
+    pub(crate) fn conn(&self) -> &'a SyncConnection {
+        self.conn
+    }
+
+    /// This finder, trying the rules of `first` before its own.
+    pub(crate) fn after(mut self, first: PlayerFinder<'a>) -> Self {
+        let mut rules = first.rules;
+        rules.append(&mut self.rules);
+        self.rules = rules;
+        self.invalid = first.invalid.or(self.invalid);
+        self
+    }
+
+    /// Finds the player picked by the rules.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidArgument`] if a name given to
+    /// `by_name` or `by_priority` can't be the name of a player,
+    /// before anything is asked of the bus, [`Error::NotFound`] if no
+    /// rule matched a player, or may `Err` if the bus can't be asked.
+    pub async fn find(&self) -> Result<Player<'a>> {
+        if let Some(reason) = &self.invalid {
+            return Err(Error::InvalidArgument(reason.clone()));
+        }
+        let candidates = self.candidates().await?;
+
+        for rule in &self.rules {
+            // min_by_key keeps the first of equals, so ties go by listing order
+            let best = candidates
+                .iter()
+                .enumerate()
+                .filter_map(|(index, candidate)| Some((rule.rank(candidate)?, index)))
+                .min_by_key(|(rank, _)| *rank);
+            if let Some((_, index)) = best {
+                return Ok(candidates.into_iter().nth(index).unwrap().player);
+            }
+        }
+        Err(Error::NotFound {
+            tried: self.rules.iter().map(ToString::to_string).collect(),
+        })
+    }
+
+    /// The players on the bus, with what the rules need of them.
+    async fn candidates(&self) -> Result<Vec<Candidate<'a>>> {
+        let players = Player::all(self.conn).await?;
+        let status = self.rules.iter().any(Rule::needs_status);
+        let identity = self.rules.iter().any(Rule::needs_identity);
+
+        let fetched = future::join_all(players.iter().map(|player| async move {
+            let fetch_status = async {
+                if !status {
+                    return None;
+                }
+                let status = player.get_property::<String>("PlaybackStatus");
+                match runtime::timeout(self.timeout, status).await {
+                    Ok(Ok(status)) => status.parse::<PlaybackStatus>().ok(),
+                    _ => None,
+                }
+            };
+            let fetch_identity = async {
+                if !identity {
+                    return None;
+                }
+                let proxy = player.get_proxy();
+                let identity = proxy.get::<String>(spec::ROOT.name, "Identity");
+                runtime::timeout(self.timeout, identity).await.ok()?.ok()
+            };
+            future::join(fetch_status, fetch_identity).await
+        }))
+        .await;
+
+        Ok(players
+            .into_iter()
+            .zip(fetched)
+            .map(|(player, (status, identity))| Candidate {
+                player,
+                status,
+                identity,
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,72 @@
+use crate::Result;
+use std::future::Future;
+
+/// The capability every command needs. Players don't signal its
+/// changes, as it isn't expected to change.
+const CAN_CONTROL: &str = "CanControl";
+
+/// The outcome of a guarded command, such as
+/// [`Player::try_next`](crate::Player::try_next), which is only sent
+/// if the player reports being capable of it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Guarded {
+    /// Whether the command was sent.
+    pub sent: bool,
+    /// The capability that decided it: the one the command needs,
+    /// such as `CanGoNext`, if it was sent, or else the first one
+    /// found missing, which may be `CanControl`.
+    pub capability: &'static str,
+    /// Whether every capability checked was read from a cache,
+    /// rather than from the player.
+    pub cached: bool,
+}
+
+/// Checks `CanControl`, then `capability`, reading each with `read`,
+/// which also tells whether it came from a cache. Stops at the first
+/// one that is `false`.
+pub(crate) async fn check<F, Fut>(capability: &'static str, mut read: F) -> Result<Guarded>
+where
+    F: FnMut(&'static str) -> Fut,
+    Fut: Future<Output = Result<(bool, bool)>>,
+{
+    let both = [CAN_CONTROL, capability];
+    let names = match capability {
+        CAN_CONTROL => &both[..1],
+        _ => &both[..],
+    };
+
+    let mut cached = true;
+    for &name in names {
+        let (capable, from_cache) = read(name).await?;
+        cached &= from_cache;
+        if !capable {
+            return Ok(Guarded {
+                sent: false,
+                capability: name,
+                cached,
+            });
+        }
+    }
+
+    Ok(Guarded {
+        sent: true,
+        capability,
+        cached,
+    })
+}
+
+/// Checks `capability` with [`check`], and runs `command` if the
+/// player has it.
+pub(crate) async fn run<F, Fut, C>(capability: &'static str, read: F, command: C) -> Result<Guarded>
+where
+    F: FnMut(&'static str) -> Fut,
+    Fut: Future<Output = Result<(bool, bool)>>,
+    C: Future<Output = Result<()>>,
+{
+    let guarded = check(capability, read).await?;
+    if guarded.sent {
+        command.await?;
+    }
+
+    Ok(guarded)
+}
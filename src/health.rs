@@ -0,0 +1,175 @@
+use crate::{runtime, util::ConnRef, Player};
+use dbus::nonblock::Proxy;
+use futures::{
+    stream::{self, BoxStream},
+    Stream, StreamExt,
+};
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+/// The longest a probe waits for an answer, however long the
+/// interval between probes is.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The errors the bus replies with when a player can't answer.
+const UNANSWERED: &[&str] = &[
+    "org.freedesktop.DBus.Error.NoReply",
+    "org.freedesktop.DBus.Error.Timeout",
+    "org.freedesktop.DBus.Error.TimedOut",
+    "org.freedesktop.DBus.Error.ServiceUnknown",
+    "org.freedesktop.DBus.Error.NameHasNoOwner",
+    "org.freedesktop.DBus.Error.Disconnected",
+];
+
+/// A change in the health of a player, reported by [`PlayerHealth`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HealthEvent {
+    /// The player failed `failures` probes in a row.
+    Unhealthy { player: String, failures: u32 },
+    /// The player answered a probe again after being unhealthy.
+    Recovered { player: String },
+}
+
+/// A watchdog for players that stay on the bus but stop answering
+/// calls, created with [`PlayerHealth::monitor`].
+///
+/// The player is probed by reading its `PlaybackStatus`, with a
+/// short timeout of its own, so that the watchdog doesn't hang
+/// along with the player. This is a stream of the changes in its
+/// health, and probing stops once it is dropped.
+///
+/// # Example
+/// ```no_run
+/// # use futures::StreamExt;
+/// # use pris::{HealthEvent, Player, PlayerHealth};
+/// # use std::time::Duration;
+/// # async fn example(player: &Player<'_>) {
+/// let mut health = PlayerHealth::monitor(player, Duration::from_secs(5), 3);
+/// while let Some(event) = health.next().await {
+///     if let HealthEvent::Unhealthy { player, .. } = event {
+///         println!("{} stopped responding", player);
+///     }
+/// }
+/// # }
+/// ```
+pub struct PlayerHealth<'a> {
+    healthy: Arc<AtomicBool>,
+    events: BoxStream<'a, HealthEvent>,
+}
+
+struct Probe<'a> {
+    proxy: Proxy<'static, ConnRef<'a>>,
+    player: String,
+    interval: Duration,
+    next: Instant,
+    timeout: Duration,
+    threshold: u32,
+    failures: u32,
+    healthy: Arc<AtomicBool>,
+}
+
+impl Probe<'_> {
+    /// Whether the player answered in time. Errors from the player
+    /// itself count as answers, but not those from the bus about it.
+    async fn probe(&self) -> bool {
+        let call = self.proxy.method_call::<(), _, _, _>(
+            "org.freedesktop.DBus.Properties",
+            "Get",
+            (PLAYER_INTERFACE, "PlaybackStatus"),
+        );
+        match runtime::timeout(self.timeout, call).await {
+            Ok(Ok(())) => true,
+            Ok(Err(e)) => !e.name().is_some_and(|name| UNANSWERED.contains(&name)),
+            Err(_) => false,
+        }
+    }
+}
+
+impl<'a> PlayerHealth<'a> {
+    /// Starts probing `player` straight away and then every
+    /// `probe_interval`, reporting it as unhealthy after
+    /// `failure_threshold` failures in a row, and as recovered after
+    /// its next successful probe.
+    ///
+    /// A probe times out after a second, or after `probe_interval`
+    /// if that is shorter; probes are only sent once the last one
+    /// is done.
+    pub fn monitor(
+        player: &Player<'a>,
+        probe_interval: Duration,
+        failure_threshold: u32,
+    ) -> PlayerHealth<'a> {
+        let timeout = PROBE_TIMEOUT.min(probe_interval);
+        let healthy = Arc::new(AtomicBool::new(true));
+        let probe = Probe {
+            proxy: Proxy::new(
+                format!("org.mpris.MediaPlayer2.{}", player.name),
+                "/org/mpris/MediaPlayer2",
+                timeout,
+                player.connection(),
+            ),
+            player: player.name.clone(),
+            interval: probe_interval,
+            next: Instant::now(),
+            timeout,
+            threshold: failure_threshold.max(1),
+            failures: 0,
+            healthy: healthy.clone(),
+        };
+
+        let events = stream::unfold(probe, |mut probe| async move {
+            loop {
+                runtime::sleep_until(probe.next).await;
+                // A probe that ran late pushes the ones after it back
+                probe.next = probe.next.max(Instant::now()) + probe.interval;
+                if probe.probe().await {
+                    let recovered = probe.failures >= probe.threshold;
+                    probe.failures = 0;
+                    if recovered {
+                        probe.healthy.store(true, Ordering::SeqCst);
+                        let player = probe.player.clone();
+                        return Some((HealthEvent::Recovered { player }, probe));
+                    }
+                } else {
+                    probe.failures += 1;
+                    if probe.failures == probe.threshold {
+                        probe.healthy.store(false, Ordering::SeqCst);
+                        let event = HealthEvent::Unhealthy {
+                            player: probe.player.clone(),
+                            failures: probe.failures,
+                        };
+                        return Some((event, probe));
+                    }
+                }
+            }
+        });
+
+        PlayerHealth {
+            healthy,
+            events: events.boxed(),
+        }
+    }
+
+    /// Whether the player is considered healthy, as of the last
+    /// event. Players start out healthy.
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::SeqCst)
+    }
+}
+
+impl Stream for PlayerHealth<'_> {
+    type Item = HealthEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<HealthEvent>> {
+        self.events.poll_next_unpin(cx)
+    }
+}
@@ -1,37 +1,265 @@
 //! This library provides a high-level interface to the [MPRIS] `DBus` specification.
 //!
 //! [MPRIS]: https://specifications.freedesktop.org/mpris-spec/latest/
-//! 
+//!
 //! It allows both controlling of a player, as well as listening
 //! for events and executing callbacks.
-//! 
+//!
 //! # A basic player controller
-//! ```
-//! use pris::{self, Player};
-//! 
-//! #[tokio::main]
+//! ```no_run
+//! use pris::Player;
+//!
+//! #[tokio::main(flavor = "current_thread")]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
 //!     // Create a connection to work with
-//!     let conn = pris::get_connection();
+//!     let conn = pris::connect()?;
 //!     // Get a player under the name "vlc"
 //!     let player = Player::try_new("vlc", &conn).await?;
 //!     // Play/pause the player
 //!     player.play_pause().await?;
+//!     Ok(())
 //! }
 //! ```
+//!
+//! # Tasks and threads
+//! The futures of [`Player`]'s methods, of the functions in
+//! [`methods`], of signal parsing, and of adding, removing and
+//! waiting for callbacks on an [`EventManager`] are all `Send`, so
+//! they can be handed to `tokio::spawn`.
+//!
+//! So are the streams of events, such as [`EventStream`],
+//! [`track_changes`](EventManager::track_changes),
+//! [`all_player_events`](EventManager::all_player_events) and
+//! [`resyncs`](EventManager::resyncs), what is built on them, such
+//! as [`ActivePlayerTracker`], [`ExclusivePlayback`] and
+//! [`PlayerHealth`], and the futures creating them, as are
+//! [`Subscription`], [`PlayerPool`], [`Controller`] and the future
+//! feeding a `Controller`. [`PlayerStateWatcher`] is the exception:
+//! its [listeners](PlayerStateWatcher::on_change) needn't be `Send`,
+//! so it must stay on the task that created it. Use a
+//! `tokio::task::LocalSet` to run it on a multi-threaded runtime.
+//!
+//! # Text from players
+//! Strings in metadata and properties are passed on as the player
+//! sent them, control characters included. [`prop_str`] also reads
+//! text sent as a byte array, replacing invalid UTF-8 rather than
+//! failing, [`prop_display`] strips control characters for showing
+//! it directly, and [`prop_bytes`] keeps the raw bytes reachable.
+//! The bus itself rejects strings that aren't valid UTF-8.
+//!
+//! # Without the `dbus` crate
+//! The main workflows don't need types from the `dbus` crate:
+//! [`connect`] returns a [`Connection`] that players and event
+//! managers take as `&conn`, [`EventManager::add_event_callback`] and
+//! the streams hand out parsed [`Event`]s, and [`prop_value`] and
+//! [`PropertyValue::value`] copy values out as [`Value`]s. The raw
+//! types stay reachable for what pris doesn't wrap, through
+//! [`Connection::as_dbus`], [`Value::from_dbus`] and
+//! [`Error::dbus_error`].
+//!
+//! # Features
+//! Everything is enabled by default. The [`Player`] controls, the
+//! functions in [`methods`], player discovery and [`PlayerState`]
+//! are always available; the rest can be left out for slimmer
+//! builds with `default-features = false`:
+//!
+//! - `events`: listening for signals, with [`EventManager`] and
+//!   everything built on it, such as [`CachedPlayer`] and
+//!   [`PlayerStateWatcher`].
+//! - `metadata`: the typed [`Metadata`] decoder.
+//!
+//! The `blocking` feature, off by default, adds the [`blocking`]
+//! module, for controlling players without an async runtime.
+//! The `ffi` feature builds on it with the [`ffi`] module, a C
+//! interface to the core controls.
+//! The `zbus` feature, also off by default, adds the [`zbus`](mod@zbus)
+//! module, for controlling players over a zbus connection.
+//! The `server` feature adds the [`server`] module, for serving an
+//! MPRIS player of your own.
+//! The `testing` feature adds the [`testing`] module, with a mock
+//! player to test code built on pris against, and fixtures recorded
+//! from real players to replay without a bus.
+//! The `serde` feature makes [`Event`] and its payloads serializable,
+//! for forwarding them to other processes, in the format described in
+//! the [`wire`] module.
+//!
+//! pris runs on tokio unless the `smol` feature is enabled, in which
+//! case its timers, background tasks and the connection made by
+//! [`get_connection`] run on smol's reactor and thread pool instead.
+//! These work under any executor, such as async-std's or smol's own,
+//! so that no tokio runtime is needed.
+//!
+//! # Tracing
+//! With the `tracing` feature, pris reports what it does through the
+//! [`tracing`](https://docs.rs/tracing) crate. Without it, none of
+//! this is compiled in. These names are kept stable:
+//!
+//! - A `pris.call` span, at debug level, around each method call
+//!   and property access of a [`Player`], with the fields `player`,
+//!   `operation` (the method or property), `duration_ms`, and
+//!   `error_kind` if it failed.
+//! - Debug events within it: `call failed`, with `error_kind` and
+//!   `error`, and `retrying call`, with `attempt`, `delay_ms`,
+//!   `error_kind` and `error`.
+//! - Debug events `match added`, `match refused` and `match removed`,
+//!   with the `rule`, as match rules are added to and removed from
+//!   the bus.
+//! - A trace event `dispatching callback`, with the `token` of the
+//!   callback and the `member` of the signal, for each signal passed
+//!   to a callback.
+//! - A debug event `timed out`, with `operation`, `limit_ms`, and
+//!   `player` if there is one, when waiting for a signal or a
+//!   snapshot times out.
+//!
+//! `error_kind` is the variant of [`Error`] in snake case, such as
+//! `player_gone` or `timeout`.
+//!
+//! # Bus traffic
+//! To see the messages themselves, such as for reporting a player
+//! that ignores a call, set a [`DebugSink`] with
+//! [`Player::set_debug_sink`] or [`EventManager::set_debug_sink`].
+//! Every call, reply and signal is then written on a line of its
+//! own, with all of its arguments.
+//!
 //! ---
 //! This crate re-exports [`Message`](dbus::message::Message) for use
-//! in typing non-closure callbacks.
+//! in typing non-closure callbacks, [`Token`](dbus::channel::Token)
+//! for removing them, and [`MatchRule`](dbus::message::MatchRule) for
+//! custom matches.
+#[cfg(feature = "events")]
+mod active;
+mod broadcast;
+#[cfg(feature = "events")]
+mod cached;
+#[cfg(feature = "events")]
+mod coalesce;
+mod connection;
+#[cfg(feature = "events")]
+mod controller;
+mod debug;
+#[cfg(feature = "events")]
+mod delivery;
+mod discovery;
+mod error;
+#[cfg(feature = "events")]
+mod event;
+#[cfg(feature = "events")]
 mod event_manager;
+#[cfg(feature = "events")]
+mod exclusive;
+mod finder;
+mod guard;
+#[cfg(feature = "events")]
+mod health;
+#[cfg(feature = "metadata")]
+mod metadata;
+mod micros;
+#[cfg(feature = "events")]
+mod milestone;
+#[cfg(feature = "events")]
+mod multi;
+mod name;
+#[cfg(feature = "events")]
+mod pending;
 mod player;
+#[cfg(feature = "events")]
+mod pool;
+#[cfg(feature = "events")]
+mod position;
+mod properties;
+mod retry;
+mod runtime;
+mod selection;
+mod spec;
+mod state;
+mod status;
+mod support;
+mod trace;
 mod util;
+mod value;
+#[cfg(feature = "events")]
+mod watcher;
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod methods;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "serde")]
+pub mod wire;
+#[cfg(feature = "zbus")]
+pub mod zbus;
 
+#[cfg(feature = "events")]
+pub use active::*;
+pub use broadcast::{pause_all, play_all, BroadcastResults, PausedPlayers};
+#[cfg(feature = "events")]
+pub use cached::CachedPlayer;
+#[cfg(feature = "events")]
+pub use coalesce::{coalesce, Coalesced};
+pub use connection::{connect, connect_to, Bus, Connection};
+#[cfg(feature = "events")]
+pub use controller::{Controller, ControllerEvent, ControllerOptions, CurrentPolicy};
+#[doc(no_inline)]
+pub use dbus::channel::Token;
 #[doc(no_inline)]
-pub use dbus::message::Message;
+pub use dbus::message::{MatchRule, Message};
+pub use debug::DebugSink;
+#[cfg(feature = "events")]
+pub use delivery::{
+    CallbackOrdering, DeliveryPolicy, PausePolicy, StampedEvent, SubscriptionOptions,
+};
+pub use discovery::{
+    group_players, player_groups, snapshot_players, sort_by_status, DiscoveryCache, PlayerGroup,
+    PlayerSnapshot, SnapshotOptions,
+};
+pub use error::Error;
+#[cfg(feature = "events")]
+pub use event::*;
+#[cfg(feature = "events")]
 pub use event_manager::*;
+#[cfg(feature = "events")]
+pub use exclusive::{
+    Enforcement, ExclusiveOptions, ExclusivePlayback, ExclusivePolicy, Suspension,
+};
+pub use finder::PlayerFinder;
+pub use guard::Guarded;
+#[cfg(feature = "events")]
+pub use health::*;
+#[cfg(feature = "metadata")]
+pub use metadata::Metadata;
+pub use methods::PositionStrategy;
+pub use micros::Micros;
+#[cfg(feature = "events")]
+pub use milestone::*;
+#[cfg(feature = "events")]
+pub use multi::*;
+pub use name::{IntoPlayerName, PlayerName};
+#[cfg(feature = "events")]
+pub use pending::*;
 pub use player::*;
-pub use util::{get_all_players, get_connection, prop_cast};
+#[cfg(feature = "events")]
+pub use pool::{PlayerPool, PoolChange};
+#[cfg(feature = "events")]
+pub use position::*;
+pub use properties::*;
+pub use retry::RetryPolicy;
+pub use selection::SelectionMemory;
+pub use state::*;
+pub use status::*;
+pub use support::{InterfaceSupport, SupportReport, SupportSource};
+pub use util::{
+    format_duration, get_all_players, get_connection, is_no_track, list_players,
+    list_players_ordered, parse_duration, prop_bytes, prop_cast, prop_display, prop_str,
+    resolve_sender, sanitize, DurationStyle, Hours, ParsedDuration, PlayerOrder,
+};
+pub use value::{prop_value, Value};
+#[cfg(feature = "events")]
+pub use watcher::*;
 
-type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+/// The result of the fallible operations of this crate.
+pub type Result<T> = std::result::Result<T, Error>;
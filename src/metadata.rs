@@ -0,0 +1,186 @@
+use crate::{util, Error, Micros, Result};
+use dbus::{
+    arg::{Arg, ArgType, Get, Iter, PropMap, RefArg, Variant},
+    strings::{Path, Signature},
+};
+use std::time::Duration;
+
+/// The metadata of a track, decoded straight from the `a{sv}` the
+/// player sent, as an alternative to the `PropMap` returned by
+/// [`Player::get_metadata`](crate::Player::get_metadata).
+///
+/// The common fields are read into their own types without going
+/// through boxed values first. Every other entry, and any common
+/// one sent with an unexpected type, such as a title sent as bytes,
+/// is kept as it is in [`extra`](Self::extra), read in the same pass.
+///
+/// # Example
+/// ```no_run
+/// # use pris::{Metadata, Player};
+/// # async fn example(player: &Player<'_>) -> Result<(), Box<dyn std::error::Error>> {
+/// let metadata: Metadata = player.get_property("Metadata").await?;
+/// println!("{:?} by {:?}", metadata.title, metadata.artists);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct Metadata {
+    /// `mpris:trackid`, sent as an object path or a string.
+    pub track_id: Option<String>,
+    /// `mpris:length`, from any integer type. Negative lengths are
+    /// clamped to zero.
+    pub length: Option<Duration>,
+    /// `xesam:title`.
+    pub title: Option<String>,
+    /// `xesam:artist`.
+    pub artists: Option<Vec<String>>,
+    /// `xesam:album`.
+    pub album: Option<String>,
+    /// `xesam:albumArtist`.
+    pub album_artists: Option<Vec<String>>,
+    /// `mpris:artUrl`.
+    pub art_url: Option<String>,
+    /// `xesam:url`.
+    pub url: Option<String>,
+    /// Every other entry, with nested variants unwrapped.
+    pub extra: PropMap,
+}
+
+impl Clone for Metadata {
+    fn clone(&self) -> Self {
+        Metadata {
+            track_id: self.track_id.clone(),
+            length: self.length,
+            title: self.title.clone(),
+            artists: self.artists.clone(),
+            album: self.album.clone(),
+            album_artists: self.album_artists.clone(),
+            art_url: self.art_url.clone(),
+            url: self.url.clone(),
+            extra: util::clone_prop_map(&self.extra),
+        }
+    }
+}
+
+impl Metadata {
+    /// Encodes the metadata the way players send it, for serving it
+    /// as the `Metadata` property. The track id is sent as an object
+    /// path, and the length in microseconds; entries of
+    /// [`extra`](Self::extra) are sent as they are.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidArgument`] if the track id isn't a
+    /// valid object path.
+    pub fn to_prop_map(&self) -> Result<PropMap> {
+        let mut map = util::clone_prop_map(&self.extra);
+        let mut insert = |key: &str, value: Box<dyn RefArg>| {
+            map.insert(key.to_string(), Variant(value));
+        };
+
+        if let Some(track_id) = &self.track_id {
+            let path = Path::new(track_id.clone()).map_err(Error::InvalidArgument)?;
+            insert("mpris:trackid", Box::new(path));
+        }
+        if let Some(length) = self.length {
+            insert(
+                "mpris:length",
+                Box::new(Micros::from_duration(length).get()),
+            );
+        }
+        let texts = [
+            ("xesam:title", &self.title),
+            ("xesam:album", &self.album),
+            ("mpris:artUrl", &self.art_url),
+            ("xesam:url", &self.url),
+        ];
+        for (key, text) in texts {
+            if let Some(text) = text {
+                insert(key, Box::new(text.clone()));
+            }
+        }
+        let lists = [
+            ("xesam:artist", &self.artists),
+            ("xesam:albumArtist", &self.album_artists),
+        ];
+        for (key, list) in lists {
+            if let Some(list) = list {
+                insert(key, Box::new(list.clone()));
+            }
+        }
+
+        Ok(map)
+    }
+
+    /// Reads the value of the entry `key` into its field, returning
+    /// whether it is a common entry of the expected type.
+    fn set_typed(&mut self, key: &str, value: &mut Iter<'_>) -> bool {
+        fn text(value: &mut Iter<'_>) -> Option<String> {
+            value.get::<&str>().map(str::to_string)
+        }
+        fn set<T>(field: &mut Option<T>, value: Option<T>) -> bool {
+            *field = value;
+            field.is_some()
+        }
+
+        match key {
+            "mpris:trackid" => {
+                let path = value.get::<Path>().map(|path| path.to_string());
+                set(&mut self.track_id, path.or_else(|| text(value)))
+            }
+            "mpris:length" => {
+                let length = match value.arg_type() {
+                    ArgType::Int64 => value.get::<Micros>().map(Micros::to_duration),
+                    ArgType::Int32 => value
+                        .get::<i32>()
+                        .map(|m| Micros::new(m.into()).to_duration()),
+                    ArgType::UInt64 => value.get::<u64>().map(Duration::from_micros),
+                    ArgType::UInt32 => value.get::<u32>().map(|m| Duration::from_micros(m.into())),
+                    _ => None,
+                };
+                set(&mut self.length, length)
+            }
+            "xesam:title" => set(&mut self.title, text(value)),
+            "xesam:artist" => set(&mut self.artists, value.get()),
+            "xesam:album" => set(&mut self.album, text(value)),
+            "xesam:albumArtist" => set(&mut self.album_artists, value.get()),
+            "mpris:artUrl" => set(&mut self.art_url, text(value)),
+            "xesam:url" => set(&mut self.url, text(value)),
+            _ => false,
+        }
+    }
+}
+
+impl Arg for Metadata {
+    const ARG_TYPE: ArgType = ArgType::Array;
+
+    fn signature() -> Signature<'static> {
+        Signature::from("a{sv}")
+    }
+}
+
+impl<'a> Get<'a> for Metadata {
+    fn get(i: &mut Iter<'a>) -> Option<Self> {
+        if &*i.signature() != "a{sv}" {
+            return None;
+        }
+
+        let mut metadata = Metadata::default();
+        let mut entries = i.recurse(ArgType::Array)?;
+        // An empty array has no entry to start at
+        while entries.arg_type() == ArgType::DictEntry {
+            let mut entry = entries.recurse(ArgType::DictEntry)?;
+            let key: String = entry.read().ok()?;
+            let mut value = entry.recurse(ArgType::Variant)?;
+            while value.arg_type() == ArgType::Variant {
+                value = value.recurse(ArgType::Variant)?;
+            }
+
+            if !metadata.set_typed(&key, &mut value) {
+                metadata.extra.insert(key, Variant(value.get_refarg()?));
+            }
+            entries.next();
+        }
+
+        Some(metadata)
+    }
+}
@@ -1,11 +1,10 @@
-use super::INTERFACE;
-use crate::{Player, Result};
-use dbus::nonblock::stdintf::org_freedesktop_dbus::Properties;
+use super::{call_error, call_method, property_error, INTERFACE, PROPERTIES};
+use crate::{debug, retry, runtime, trace, util, Error, Micros, Player, PlayerState, Result};
 use dbus::{
-    arg::{Append, Arg, Get, PropMap},
+    arg::{Append, Arg, Get, Iter, PropMap, ReadAll, RefArg, TypeMismatchError, Variant},
     strings::Path,
 };
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 /// Retrieves track metadata from a `Player`.
 /// The [`prop_cast`](crate::prop_cast) function may be used
@@ -13,12 +12,74 @@ use std::time::Duration;
 ///
 /// # Errors
 /// May `Err` if there is a failure in getting the metadata.
-pub async fn get_metadata(player: &mut Player<'_>) -> Result<PropMap> {
-    let proxy = player.get_proxy()?;
-    let metadata: PropMap = proxy.get(INTERFACE, "Metadata").await?;
+pub async fn get_metadata(player: &Player<'_>) -> Result<PropMap> {
+    let policy = player.retry_policy();
+    let proxy = player.get_proxy();
+    let (Variant(metadata),) = trace::call(
+        &player.name,
+        "Metadata",
+        retry::run(
+            policy,
+            true,
+            || {
+                debug::method_call(
+                    player.debug_sink(),
+                    &proxy,
+                    PROPERTIES,
+                    "Get",
+                    (INTERFACE, "Metadata"),
+                )
+            },
+            call_error(player, &proxy, "Metadata"),
+        ),
+    )
+    .await?;
     Ok(metadata)
 }
 
+/// Retrieves the metadata of the active track of a `Player`, or
+/// `None` if nothing is playing, as decided by
+/// [`is_no_track`](crate::is_no_track).
+///
+/// # Errors
+/// May `Err` if there is a failure in getting the metadata.
+pub async fn metadata(player: &Player<'_>) -> Result<Option<PropMap>> {
+    let metadata = get_metadata(player).await?;
+    Ok(Some(metadata).filter(|m| !util::is_no_track(m)))
+}
+
+/// Retrieves all of a `Player`'s properties at once.
+///
+/// # Errors
+/// May `Err` if there is a failure in getting the properties.
+pub async fn get_state(player: &Player<'_>) -> Result<PlayerState> {
+    let policy = player.retry_policy();
+    let proxy = player.get_proxy();
+    let (properties,) = trace::call(
+        &player.name,
+        "GetAll",
+        retry::run(
+            policy,
+            true,
+            || {
+                debug::method_call(
+                    player.debug_sink(),
+                    &proxy,
+                    PROPERTIES,
+                    "GetAll",
+                    (INTERFACE,),
+                )
+            },
+            call_error(player, &proxy, "GetAll"),
+        ),
+    )
+    .await?;
+    Ok(PlayerState::from_properties(
+        player.name.clone(),
+        properties,
+    ))
+}
+
 /// Retrieves the value of an MPRIS property.
 /// Available properties can be found [here].
 ///
@@ -26,16 +87,59 @@ pub async fn get_metadata(player: &mut Player<'_>) -> Result<PropMap> {
 ///
 /// # Errors
 /// May return an `Err` variant if:
-/// * An invalid type was provided for the property
-/// * An invalid property was provided
-pub async fn get_property<T>(player: &mut Player<'_>, property: &str) -> Result<T>
+/// * An invalid type was provided for the property, as
+///   [`Error::TypeMismatch`]
+/// * An invalid property was provided, as [`Error::UnknownProperty`]
+pub async fn get_property<T>(player: &Player<'_>, property: &str) -> Result<T>
 where
     T: for<'a> Get<'a> + 'static,
 {
-    let proxy = player.get_proxy()?;
-    let value: T = proxy.get(INTERFACE, property).await?;
+    let policy = player.retry_policy();
+    let proxy = player.get_proxy();
+    let PropertyReply(value) = trace::call(
+        &player.name,
+        property,
+        retry::run(
+            policy,
+            true,
+            || {
+                debug::method_call::<PropertyReply<T>, _>(
+                    player.debug_sink(),
+                    &proxy,
+                    PROPERTIES,
+                    "Get",
+                    (INTERFACE, property),
+                )
+            },
+            property_error(player, &proxy, property, false),
+        ),
+    )
+    .await?;
 
-    Ok(value)
+    value.map_err(|actual| Error::TypeMismatch {
+        player: player.name.clone(),
+        property: property.to_string(),
+        expected: std::any::type_name::<T>().to_string(),
+        actual,
+    })
+}
+
+/// The reply to a `Get` call, holding the value read as `T`, or the
+/// signature it had if it couldn't be.
+pub(crate) struct PropertyReply<T>(pub(crate) std::result::Result<T, String>);
+
+impl<T> ReadAll for PropertyReply<T>
+where
+    T: for<'a> Get<'a>,
+{
+    fn read(i: &mut Iter) -> std::result::Result<Self, TypeMismatchError> {
+        let Variant(mut value): Variant<Iter> = i.read()?;
+        let signature = value.signature();
+
+        Ok(PropertyReply(
+            value.get().ok_or_else(|| signature.to_string()),
+        ))
+    }
 }
 
 /// Sets the value of a writable MPRIS property.
@@ -46,57 +150,260 @@ where
 /// # Errors
 /// May return an `Err` variant if:
 /// * An invalid type was provided for the property
-/// * An invalid property was provided
-pub async fn set_property<T>(player: &mut Player<'_>, property: &str, value: T) -> Result<()>
+/// * An invalid property was provided, as [`Error::UnknownProperty`]
+pub async fn set_property<T>(player: &Player<'_>, property: &str, value: T) -> Result<()>
 where
     T: Arg + Append,
 {
-    let proxy = player.get_proxy()?;
-    proxy.set(INTERFACE, property, value).await?;
+    let policy = player.retry_policy();
+    let proxy = player.get_proxy();
+    trace::call(
+        &player.name,
+        property,
+        retry::run(
+            policy,
+            true,
+            || {
+                debug::method_call::<(), _>(
+                    player.debug_sink(),
+                    &proxy,
+                    PROPERTIES,
+                    "Set",
+                    (INTERFACE, property, Variant(&value)),
+                )
+            },
+            property_error(player, &proxy, property, true),
+        ),
+    )
+    .await
+}
 
-    Ok(())
+/// How long each step of a volume fade lasts.
+const FADE_STEP: Duration = Duration::from_millis(50);
+
+/// Retrieves the volume of a `Player` as it reports it, even above
+/// its [volume ceiling](crate::Player::set_volume_ceiling).
+///
+/// # Errors
+/// May `Err` if there is a failure in getting the volume.
+pub async fn get_volume(player: &Player<'_>) -> Result<f64> {
+    get_property(player, "Volume").await
+}
+
+/// Sets the volume of a `Player`, clamped to between 0.0 and its
+/// volume ceiling. Returns the volume that was set.
+///
+/// # Errors
+/// Will `Err` with [`Error::InvalidArgument`] if `volume` is NaN.
+pub async fn set_volume(player: &Player<'_>, volume: f64) -> Result<f64> {
+    let volume = clamp_volume(volume, player.volume_ceiling())?;
+    set_property(player, "Volume", volume).await?;
+    Ok(volume)
+}
+
+/// Changes the volume of a `Player` by `delta`, clamped like
+/// `set_volume`. Returns the volume that was set.
+///
+/// # Errors
+/// Will `Err` with [`Error::InvalidArgument`] if `delta` is NaN.
+pub async fn adjust_volume(player: &Player<'_>, delta: f64) -> Result<f64> {
+    let volume = get_volume(player).await?;
+    set_volume(player, volume + delta).await
 }
 
-/// Seeks the position of the active track.
-pub async fn seek(player: &mut Player<'_>, offset: Duration) -> Result<()> {
-    let proxy = player.get_proxy()?;
-    let offset = offset.as_micros() as i64;
-    proxy.method_call(INTERFACE, "Seek", (offset,)).await?;
+/// Gradually changes the volume of a `Player` to `target` over
+/// `duration`, clamping every step like `set_volume`.
+///
+/// # Errors
+/// Will `Err` with [`Error::InvalidArgument`] if `target` is NaN.
+pub async fn fade_volume(player: &Player<'_>, target: f64, duration: Duration) -> Result<()> {
+    let target = clamp_volume(target, player.volume_ceiling())?;
+    let start = get_volume(player).await?;
+    let steps = (duration.as_millis() / FADE_STEP.as_millis()).clamp(1, u32::MAX as u128) as u32;
+
+    for step in 1..=steps {
+        runtime::sleep(duration / steps).await;
+        let volume = start + (target - start) * f64::from(step) / f64::from(steps);
+        set_volume(player, volume).await?;
+    }
 
     Ok(())
 }
 
+/// Clamps `volume` to between 0.0 and `ceiling`.
+pub(crate) fn clamp_volume(volume: f64, ceiling: f64) -> Result<f64> {
+    if volume.is_nan() {
+        return Err(Error::InvalidArgument("The volume can't be NaN.".into()));
+    }
+
+    Ok(volume.clamp(0.0, ceiling))
+}
+
+/// Retrieves the position of the active track.
+///
+/// Positions of any integer type are accepted, and negative ones
+/// are clamped to zero.
+///
+/// # Errors
+/// Will `Err` with [`Error::TypeMismatch`] if the position isn't
+/// an integer.
+pub async fn get_position(player: &Player<'_>) -> Result<Duration> {
+    let value: Box<dyn RefArg> = get_property(player, "Position").await?;
+    read_position(&player.name, &*value)
+}
+
+/// Reads the `Position` of the player `player` from `value`.
+pub(crate) fn read_position(player: &str, value: &(dyn RefArg + 'static)) -> Result<Duration> {
+    util::micros(value).ok_or_else(|| Error::TypeMismatch {
+        player: player.to_string(),
+        property: "Position".to_string(),
+        expected: "an integer".to_string(),
+        actual: value.signature().to_string(),
+    })
+}
+
+/// Seeks the position of the active track. Offsets too large to
+/// send are saturated, at some 292 thousand years.
+///
+/// # Errors
+/// Will `Err` with [`Error::NoActiveTrack`] if there is no active track.
+pub async fn seek(player: &Player<'_>, offset: Duration) -> Result<()> {
+    active_track(player).await?;
+
+    let offset = Micros::from_duration(offset);
+    call_method(player, "Seek", (offset,), false).await
+}
+
 /// Same as `seek`, but in reverse.
-pub async fn seek_reverse(player: &mut Player<'_>, offset: Duration) -> Result<()> {
-    let proxy = player.get_proxy()?;
-    let offset = offset.as_micros() as i64;
-    proxy.method_call(INTERFACE, "Seek", (-offset,)).await?;
+///
+/// # Errors
+/// Will `Err` with [`Error::NoActiveTrack`] if there is no active track.
+pub async fn seek_reverse(player: &Player<'_>, offset: Duration) -> Result<()> {
+    active_track(player).await?;
 
-    Ok(())
+    let offset = -Micros::from_duration(offset);
+    call_method(player, "Seek", (offset,), false).await
 }
 
-/// Sets the position of the current track, by microseconds.
-pub async fn set_position(player: &mut Player<'_>, position: i64) -> Result<()> {
-    let mut player_clone = player.clone();
+/// Sets the position of the current track. Positions too large to
+/// send are saturated, at some 292 thousand years.
+///
+/// # Errors
+/// Will `Err` with [`Error::NoActiveTrack`] if there is no active track,
+/// or [`Error::UnsupportedOperation`] if the player doesn't report the
+/// id of its track.
+pub async fn set_position(player: &Player<'_>, position: Duration) -> Result<()> {
+    let metadata = active_track(player).await?;
+    let track_id = track_id(&player.name, &metadata)?;
 
-    let proxy = player.get_proxy()?;
-    let metadata = get_metadata(&mut player_clone).await?;
-    let track_id: &Path = crate::prop_cast(&metadata, "mpris:trackid").unwrap();
+    let position = Micros::from_duration(position);
+    call_method(player, "SetPosition", (track_id, position), true).await
+}
 
-    proxy
-        .method_call(INTERFACE, "SetPosition", (track_id, position))
-        .await?;
+/// The `mpris:trackid` of the track described by `metadata`, which
+/// the player `player` is sent its position with.
+///
+/// # Errors
+/// Will `Err` with [`Error::UnsupportedOperation`] if the player
+/// doesn't report it.
+pub(crate) fn track_id<'m>(player: &str, metadata: &'m PropMap) -> Result<&'m Path<'static>> {
+    crate::prop_cast(metadata, "mpris:trackid").ok_or_else(|| Error::UnsupportedOperation {
+        description: format!(
+            "The player {} doesn't report a track id, so its position can't be set.",
+            player
+        ),
+        source: None,
+    })
+}
 
-    Ok(())
+/// How long a player is given to reach the position it was sent with
+/// `SetPosition`, before falling back to `Seek`.
+const POSITION_WINDOW: Duration = Duration::from_millis(500);
+
+/// How often the position is read within `POSITION_WINDOW`.
+const POSITION_POLL: Duration = Duration::from_millis(100);
+
+/// How far from the requested position a player may be, beyond the
+/// time that passed since, and still count as having gone there.
+const POSITION_TOLERANCE: Duration = Duration::from_secs(1);
+
+/// How far from where it was, or playing on from there, a player may
+/// read and still count as not having moved, such as from rounding.
+const POSITION_SLACK: Duration = Duration::from_millis(100);
+
+/// The call that moved a player in
+/// [`set_position_with_fallback`](crate::Player::set_position_with_fallback).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PositionStrategy {
+    /// The player went to the position on `SetPosition`.
+    SetPosition,
+    /// The player ignored `SetPosition`, and was moved with a `Seek`
+    /// relative to its current position instead.
+    Seek,
+}
+
+/// Same as `set_position`, falling back to a relative `Seek` for
+/// players that accept `SetPosition` but don't act on it, such as
+/// Spotify.
+///
+/// The `Position` property is read before `SetPosition`, and for up
+/// to half a second after. If by then the player didn't move from
+/// where it was, or where playing on would have taken it, to within
+/// a second of `position`, it is sent a `Seek` by the difference.
+/// Returns which of the two calls moved it; a `position` the player
+/// was already at counts as reached by `SetPosition`.
+///
+/// # Errors
+/// Same as `set_position`, or if the position can't be read.
+pub async fn set_position_with_fallback(
+    player: &Player<'_>,
+    position: Duration,
+) -> Result<PositionStrategy> {
+    let before = get_position(player).await?;
+    let started = Instant::now();
+    set_position(player, position).await?;
+
+    let current = loop {
+        let current = get_position(player).await?;
+        let elapsed = started.elapsed();
+        // How far `position` is from anywhere the player could be
+        // without having moved, whether it is playing or not
+        let unmoved = if position < before {
+            before - position
+        } else {
+            position.saturating_sub(before + elapsed)
+        };
+        let distance = current.max(position) - current.min(position);
+        if unmoved <= POSITION_SLACK
+            || (distance <= POSITION_TOLERANCE + elapsed && distance + POSITION_SLACK < unmoved)
+        {
+            return Ok(PositionStrategy::SetPosition);
+        }
+        if elapsed >= POSITION_WINDOW {
+            break current;
+        }
+        runtime::sleep(POSITION_POLL).await;
+    };
+
+    let offset = Micros::from_duration(position) - Micros::from_duration(current);
+    call_method(player, "Seek", (offset,), false).await?;
+    Ok(PositionStrategy::Seek)
+}
+
+/// Retrieves the metadata of the active track of `player`.
+///
+/// # Errors
+/// Will `Err` with [`Error::NoActiveTrack`] if nothing is playing.
+async fn active_track(player: &Player<'_>) -> Result<PropMap> {
+    metadata(player)
+        .await?
+        .ok_or_else(|| Error::NoActiveTrack(player.name.clone()))
 }
 
 /// Opens a track by its URI.
 ///
 /// # Errors
 /// May return an `Err` variant if the provided URI is invalid.
-pub async fn open_uri(player: &mut Player<'_>, uri: &str) -> Result<()> {
-    let proxy = player.get_proxy()?;
-    proxy.method_call(INTERFACE, "OpenUri", (uri,)).await?;
-
-    Ok(())
+pub async fn open_uri(player: &Player<'_>, uri: &str) -> Result<()> {
+    call_method(player, "OpenUri", (uri,), false).await
 }
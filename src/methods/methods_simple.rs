@@ -1,50 +1,98 @@
-use super::INTERFACE;
-use crate::{Player, Result};
+use super::{call_method, get_state};
+use crate::{Error, PlaybackStatus, Player, PlayerState, Result};
 
-/// Skips to the next track
-pub async fn next(player: &mut Player<'_>) -> Result<()> {
-    let proxy = player.get_proxy()?;
-    proxy.method_call(INTERFACE, "Next", ()).await?;
+/// The errors a player replies with when it doesn't implement a
+/// method, or refuses it outright.
+const UNSUPPORTED: &[&str] = &[
+    "org.freedesktop.DBus.Error.UnknownMethod",
+    "org.freedesktop.DBus.Error.NotSupported",
+];
 
-    Ok(())
+/// Skips to the next track
+pub async fn next(player: &Player<'_>) -> Result<()> {
+    call_method(player, "Next", (), false).await
 }
 
 /// Skips to the previous track
-pub async fn previous(player: &mut Player<'_>) -> Result<()> {
-    let proxy = player.get_proxy()?;
-    proxy.method_call(INTERFACE, "Previous", ()).await?;
-
-    Ok(())
+pub async fn previous(player: &Player<'_>) -> Result<()> {
+    call_method(player, "Previous", (), false).await
 }
 
 /// Pauses the current track
-pub async fn pause(player: &mut Player<'_>) -> Result<()> {
-    let proxy = player.get_proxy()?;
-    proxy.method_call(INTERFACE, "Pause", ()).await?;
-
-    Ok(())
+///
+/// # Errors
+/// Will `Err` with [`Error::UnsupportedOperation`] if the player
+/// rejects `Pause` as unknown or unsupported.
+pub async fn pause(player: &Player<'_>) -> Result<()> {
+    call_method(player, "Pause", (), true)
+        .await
+        .map_err(|e| match e {
+            Error::DBus(e) if is_unsupported(&e) => cant_pause(&player.name, Some(e)),
+            e => e,
+        })
 }
 
 /// Starts or resumes the current track
-pub async fn play(player: &mut Player<'_>) -> Result<()> {
-    let proxy = player.get_proxy()?;
-    proxy.method_call(INTERFACE, "Play", ()).await?;
-
-    Ok(())
+pub async fn play(player: &Player<'_>) -> Result<()> {
+    call_method(player, "Play", (), true).await
 }
 
 /// Resumes/starts or pauses the current track
-pub async fn play_pause(player: &mut Player<'_>) -> Result<()> {
-    let proxy = player.get_proxy()?;
-    proxy.method_call(INTERFACE, "PlayPause", ()).await?;
+///
+/// Players that reject `PlayPause` as unknown or unsupported are
+/// sent `Play` or `Pause` instead, depending on their
+/// `PlaybackStatus`. This fallback costs an extra round trip to
+/// read the player's state before the second call.
+///
+/// # Errors
+/// Will `Err` with [`Error::UnsupportedOperation`] if the fallback
+/// has to pause a player that can't be paused.
+pub async fn play_pause(player: &Player<'_>) -> Result<()> {
+    match call_method(player, "PlayPause", (), false).await {
+        Err(Error::DBus(e)) if is_unsupported(&e) => {}
+        result => return result,
+    }
 
-    Ok(())
+    let state = get_state(player).await?;
+    if fallback_pauses(&player.name, &state)? {
+        pause(player).await
+    } else {
+        play(player).await
+    }
 }
 
 /// Stops playback
-pub async fn stop(player: &mut Player<'_>) -> Result<()> {
-    let proxy = player.get_proxy()?;
-    proxy.method_call(INTERFACE, "Stop", ()).await?;
+pub async fn stop(player: &Player<'_>) -> Result<()> {
+    call_method(player, "Stop", (), true).await
+}
+
+/// Whether `error` is the player refusing a method it doesn't have.
+pub(crate) fn is_unsupported(error: &dbus::Error) -> bool {
+    error.name().is_some_and(|name| UNSUPPORTED.contains(&name))
+}
+
+/// Whether the player `player`, in `state`, is paused rather than
+/// played when it rejects `PlayPause`.
+///
+/// # Errors
+/// Will `Err` with [`Error::UnsupportedOperation`] if it has to be
+/// paused but can't be.
+pub(crate) fn fallback_pauses(player: &str, state: &PlayerState) -> Result<bool> {
+    if state.playback_status != Some(PlaybackStatus::Playing) {
+        return Ok(false);
+    }
+    if state.can_pause == Some(false) {
+        return Err(cant_pause(player, None));
+    }
+
+    Ok(true)
+}
 
-    Ok(())
+/// The error for pausing `player` when it can't be paused, which it
+/// reported with `source` if it did.
+pub(crate) fn cant_pause(player: &str, source: Option<dbus::Error>) -> Error {
+    Error::UnsupportedOperation {
+        description: format!("The player {} can't be paused.", player),
+        source,
+    }
 }
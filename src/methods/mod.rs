@@ -1,6 +1,6 @@
 //! Provides all of the methods needed to control and
 //! work with a `Player`.
-//! 
+//!
 //! Note that these methods are also all implemented
 //! on the `Player` struct.
 mod methods_complex;
@@ -9,4 +9,78 @@ mod methods_simple;
 pub use methods_complex::*;
 pub use methods_simple::*;
 
-const INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+#[cfg(any(feature = "blocking", feature = "zbus"))]
+pub(crate) use methods_complex::{clamp_volume, read_position, track_id, PropertyReply};
+#[cfg(any(feature = "blocking", feature = "zbus"))]
+pub(crate) use methods_simple::{cant_pause, fallback_pauses, is_unsupported};
+
+use crate::{debug, retry, spec, trace, Error, Player, Result};
+use dbus::{arg::AppendAll, nonblock::Proxy};
+
+const INTERFACE: &str = spec::PLAYER.name;
+const PROPERTIES: &str = "org.freedesktop.DBus.Properties";
+
+/// Calls the method `member` of the Player interface of `player`
+/// with `args`, retrying it as the player's
+/// [`RetryPolicy`](crate::RetryPolicy) allows if it is `idempotent`.
+async fn call_method<A>(player: &Player<'_>, member: &str, args: A, idempotent: bool) -> Result<()>
+where
+    A: AppendAll + Clone,
+{
+    debug_assert!(
+        spec::PLAYER.method(member).is_some(),
+        "no method {}",
+        member
+    );
+    let policy = player.retry_policy();
+    let proxy = player.get_proxy();
+    trace::call(
+        &player.name,
+        member,
+        retry::run(
+            policy,
+            idempotent,
+            || {
+                debug::method_call::<(), _>(
+                    player.debug_sink(),
+                    &proxy,
+                    INTERFACE,
+                    member,
+                    args.clone(),
+                )
+            },
+            call_error(player, &proxy, member),
+        ),
+    )
+    .await
+}
+
+/// Classifies the failures of the call `operation` made to `player`
+/// through `proxy`, so that players that have left the bus or hung
+/// are told apart. `operation` is the method or property being called.
+fn call_error<C>(
+    player: &Player<'_>,
+    proxy: &Proxy<'_, C>,
+    operation: &str,
+) -> impl Fn(dbus::Error) -> Error {
+    let player = player.name.clone();
+    let operation = operation.to_string();
+    let limit = proxy.timeout;
+
+    move |e| Error::from_call(&player, &operation, limit, e)
+}
+
+/// Same as `call_error`, for reading or `writing` the property
+/// `property`, also telling apart properties the player doesn't have.
+fn property_error<C>(
+    player: &Player<'_>,
+    proxy: &Proxy<'_, C>,
+    property: &str,
+    writing: bool,
+) -> impl Fn(dbus::Error) -> Error {
+    let player = player.name.clone();
+    let property = property.to_string();
+    let limit = proxy.timeout;
+
+    move |e| Error::from_property_call(&player, &property, limit, writing, e)
+}
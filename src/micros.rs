@@ -0,0 +1,169 @@
+use crate::{Error, Result};
+use dbus::{
+    arg::{Append, Arg, ArgType, Get, Iter, IterAppend},
+    strings::Signature,
+};
+use std::{
+    convert::TryFrom,
+    fmt,
+    ops::{Add, Neg, Sub},
+    time::Duration,
+};
+
+/// A signed number of microseconds, the unit MPRIS sends positions,
+/// `Seek` offsets and `mpris:length` in.
+///
+/// Conversions from and to [`Duration`] are explicit, and either
+/// saturate, at some 292 thousand years one way and at zero the
+/// other, or fail. Arithmetic saturates too, rather than
+/// overflowing.
+///
+/// # Example
+/// ```
+/// # use pris::Micros;
+/// # use std::time::Duration;
+/// let position = Micros::from_duration(Duration::from_secs(90));
+/// assert_eq!(position.get(), 90_000_000);
+/// let rewound = position - Micros::from_duration(Duration::from_secs(100));
+/// assert_eq!(rewound.to_duration(), Duration::ZERO);
+/// assert!(rewound.try_to_duration().is_err());
+/// assert_eq!(rewound.to_string(), "-10000000µs");
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Micros(i64);
+
+impl Micros {
+    /// No time at all.
+    pub const ZERO: Micros = Micros(0);
+    /// The longest time that can be sent.
+    pub const MAX: Micros = Micros(i64::MAX);
+    /// The most negative offset that can be sent.
+    pub const MIN: Micros = Micros(i64::MIN);
+
+    /// `micros` microseconds, as sent on the bus.
+    pub const fn new(micros: i64) -> Micros {
+        Micros(micros)
+    }
+
+    /// The number of microseconds, as sent on the bus.
+    pub const fn get(self) -> i64 {
+        self.0
+    }
+
+    /// Whether this is below zero, such as a backwards offset.
+    pub const fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+
+    /// The distance from zero, saturating at [`MAX`](Self::MAX).
+    pub const fn abs(self) -> Micros {
+        Micros(self.0.saturating_abs())
+    }
+
+    /// `duration`, saturating at [`MAX`](Self::MAX).
+    pub fn from_duration(duration: Duration) -> Micros {
+        Micros(i64::try_from(duration.as_micros()).unwrap_or(i64::MAX))
+    }
+
+    /// `duration`, or an `Err` if it is longer than
+    /// [`MAX`](Self::MAX).
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidArgument`] if `duration` is too long.
+    pub fn try_from_duration(duration: Duration) -> Result<Micros> {
+        i64::try_from(duration.as_micros())
+            .map(Micros)
+            .map_err(|_| {
+                Error::InvalidArgument(format!(
+                    "{:?} is too long to be sent in microseconds.",
+                    duration
+                ))
+            })
+    }
+
+    /// This as a `Duration`, clamping negative values, such as a
+    /// length of -1 or positions around track changes, to zero.
+    pub fn to_duration(self) -> Duration {
+        Duration::from_micros(self.0.max(0) as u64)
+    }
+
+    /// This as a `Duration`, or an `Err` if it is negative.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidArgument`] if this is negative.
+    pub fn try_to_duration(self) -> Result<Duration> {
+        match u64::try_from(self.0) {
+            Ok(micros) => Ok(Duration::from_micros(micros)),
+            Err(_) => Err(Error::InvalidArgument(format!(
+                "{} is negative, so it isn't a duration.",
+                self
+            ))),
+        }
+    }
+}
+
+impl From<Duration> for Micros {
+    /// Same as [`Micros::from_duration`].
+    fn from(duration: Duration) -> Micros {
+        Micros::from_duration(duration)
+    }
+}
+
+impl TryFrom<Micros> for Duration {
+    type Error = Error;
+
+    /// Same as [`Micros::try_to_duration`].
+    fn try_from(micros: Micros) -> Result<Duration> {
+        micros.try_to_duration()
+    }
+}
+
+impl Add for Micros {
+    type Output = Micros;
+
+    fn add(self, other: Micros) -> Micros {
+        Micros(self.0.saturating_add(other.0))
+    }
+}
+
+impl Sub for Micros {
+    type Output = Micros;
+
+    fn sub(self, other: Micros) -> Micros {
+        Micros(self.0.saturating_sub(other.0))
+    }
+}
+
+impl Neg for Micros {
+    type Output = Micros;
+
+    fn neg(self) -> Micros {
+        Micros(self.0.saturating_neg())
+    }
+}
+
+impl fmt::Display for Micros {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}µs", self.0)
+    }
+}
+
+impl Arg for Micros {
+    const ARG_TYPE: ArgType = ArgType::Int64;
+
+    fn signature() -> Signature<'static> {
+        i64::signature()
+    }
+}
+
+impl Append for Micros {
+    fn append_by_ref(&self, iter: &mut IterAppend) {
+        self.0.append_by_ref(iter);
+    }
+}
+
+impl<'a> Get<'a> for Micros {
+    fn get(iter: &mut Iter<'a>) -> Option<Micros> {
+        i64::get(iter).map(Micros)
+    }
+}
@@ -0,0 +1,315 @@
+use crate::{
+    position::EventClock, runtime, util, Event, LifecycleEvent, PlaybackClock, PlaybackStatus,
+    PlayerState,
+};
+use dbus::arg::PropMap;
+use futures::{
+    stream::{self, BoxStream},
+    Stream, StreamExt,
+};
+use std::{
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// How close to a threshold counts as having crossed it, to
+/// absorb float rounding in the clock.
+const THRESHOLD_TOLERANCE: Duration = Duration::from_millis(1);
+
+/// When a track counts as played, for [`progress_milestones`].
+///
+/// With both thresholds set, whichever is reached first counts.
+/// A `fraction` can only be reached once the length of the track
+/// is known. The default is the usual scrobbling rule: half of the
+/// track, or four minutes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MilestonePolicy {
+    /// The fraction of the track that has to be played, between
+    /// 0 and 1.
+    pub fraction: Option<f64>,
+    /// How much of the track has to be played.
+    pub duration: Option<Duration>,
+}
+
+impl Default for MilestonePolicy {
+    fn default() -> Self {
+        MilestonePolicy {
+            fraction: Some(0.5),
+            duration: Some(Duration::from_secs(240)),
+        }
+    }
+}
+
+impl MilestonePolicy {
+    /// The amount of a track of `length` that has to be played, if
+    /// it can be reached at all.
+    fn threshold(&self, length: Option<Duration>) -> Option<Duration> {
+        let fraction = self.fraction.zip(length).map(|(fraction, length)| {
+            Duration::from_secs_f64(length.as_secs_f64() * fraction.clamp(0.0, 1.0))
+        });
+        fraction.into_iter().chain(self.duration).min()
+    }
+}
+
+/// A track having been played for long enough to satisfy a
+/// [`MilestonePolicy`].
+pub struct ProgressMilestone {
+    /// The name of the player, in the same form that is
+    /// passed to [`Player::try_new`](crate::Player::try_new).
+    pub player: String,
+    /// The metadata of the track, as last reported.
+    pub metadata: Option<PropMap>,
+    /// The length of the track, if known.
+    pub length: Option<Duration>,
+    /// How much of the track has been played, not counting any
+    /// part more than once.
+    pub listened: Duration,
+    /// The position in the track when the milestone was reached.
+    pub position: Duration,
+}
+
+impl fmt::Debug for ProgressMilestone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressMilestone")
+            .field("player", &self.player)
+            .field("metadata", &self.metadata)
+            .field("length", &self.length)
+            .field("listened", &self.listened)
+            .field("position", &self.position)
+            .finish()
+    }
+}
+
+impl Clone for ProgressMilestone {
+    fn clone(&self) -> Self {
+        ProgressMilestone {
+            player: self.player.clone(),
+            metadata: self.metadata.as_ref().map(util::clone_prop_map),
+            ..*self
+        }
+    }
+}
+
+/// A stream of play-progress milestones, created with
+/// [`progress_milestones`] or
+/// [`EventManager::progress_milestones`](crate::EventManager::progress_milestones).
+pub struct ProgressMilestones<'a>(BoxStream<'a, ProgressMilestone>);
+
+impl Stream for ProgressMilestones<'_> {
+    type Item = ProgressMilestone;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<ProgressMilestone>> {
+        self.0.poll_next_unpin(cx)
+    }
+}
+
+/// The bookkeeping for the playback of a single track.
+struct Progress<'a> {
+    events: BoxStream<'a, Event>,
+    policy: MilestonePolicy,
+    player: String,
+    tracked: EventClock,
+    track: Option<String>,
+    metadata: Option<PropMap>,
+    /// The parts of the track played so far, sorted and disjoint.
+    listened: Vec<(Duration, Duration)>,
+    /// The position as of the last update.
+    from: Duration,
+    reached: bool,
+    next: Option<Instant>,
+}
+
+impl Progress<'_> {
+    fn clock(&self) -> &PlaybackClock {
+        &self.tracked.clock
+    }
+
+    fn listened(&self) -> Duration {
+        self.listened.iter().map(|(start, end)| *end - *start).sum()
+    }
+
+    /// Records the part of the track played since the last update.
+    fn settle(&mut self, now: Instant) {
+        let position = self.clock().position_at(now.into_std());
+        let (start, end) = (self.from.min(position), self.from.max(position));
+        if start < end {
+            self.listened.push((start, end));
+            self.listened.sort_by_key(|(start, _)| *start);
+            let mut merged: Vec<(Duration, Duration)> = Vec::with_capacity(self.listened.len());
+            for (start, end) in self.listened.drain(..) {
+                match merged.last_mut() {
+                    Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                    _ => merged.push((start, end)),
+                }
+            }
+            self.listened = merged;
+        }
+        self.from = position;
+    }
+
+    /// Starts a new playback of the current track.
+    fn restart(&mut self) {
+        self.listened.clear();
+        self.reached = false;
+    }
+
+    /// Updates the bookkeeping from `event`, received at `now`.
+    fn apply(&mut self, event: &Event, now: Instant) {
+        self.settle(now);
+        if let Event::PropertiesChanged(changed) = event {
+            let changes = &changed.properties;
+            if changes.is_player_interface() {
+                if let Some(metadata) = &changes.metadata {
+                    let track = util::track_identity(metadata);
+                    if track != self.track {
+                        self.track = track;
+                        self.restart();
+                    }
+                    self.metadata = Some(util::clone_prop_map(metadata));
+                }
+                if changes.playback_status == Some(PlaybackStatus::Stopped) {
+                    self.restart();
+                }
+            }
+        }
+        self.tracked.apply(event, now.into_std());
+        self.from = self.clock().position_at(now.into_std());
+    }
+
+    /// Returns a milestone if the threshold has just been crossed,
+    /// otherwise schedules a check for when it would be, if playing
+    /// continues uninterrupted.
+    fn check(&mut self, now: Instant) -> Option<ProgressMilestone> {
+        self.next = None;
+        if self.reached || self.track.is_none() {
+            return None;
+        }
+        let length = self.clock().length();
+        let threshold = self.policy.threshold(length)?;
+        let listened = self.listened();
+        if listened + THRESHOLD_TOLERANCE >= threshold {
+            self.reached = true;
+            return Some(ProgressMilestone {
+                player: self.player.clone(),
+                metadata: self.metadata.as_ref().map(util::clone_prop_map),
+                length,
+                listened,
+                position: self.from,
+            });
+        }
+
+        let clock = self.clock();
+        if clock.status() == PlaybackStatus::Playing && clock.rate() > 0.0 {
+            let target = self.position_reaching(threshold - listened);
+            if length.is_none_or(|length| target <= length) {
+                let wait = (target - self.from).as_secs_f64() / clock.rate();
+                self.next = Some(now + Duration::from_secs_f64(wait));
+            }
+        }
+
+        None
+    }
+
+    /// The position playing forward has to reach for `needed` more
+    /// of the track to have been played, skipping parts already
+    /// played.
+    fn position_reaching(&self, mut needed: Duration) -> Duration {
+        let mut cursor = self.from;
+        for &(start, end) in &self.listened {
+            if end <= cursor {
+                continue;
+            }
+            if start > cursor {
+                let gap = start - cursor;
+                if gap >= needed {
+                    return cursor + needed;
+                }
+                needed -= gap;
+            }
+            cursor = end;
+        }
+
+        cursor + needed
+    }
+}
+
+/// Turns the events of a single player into a stream of
+/// milestones, starting from its `state`.
+///
+/// The parts of each track that are played are accumulated, in
+/// track time, and a milestone is emitted once they add up to the
+/// threshold of `policy`. Seeking forward skips the part in between,
+/// and parts played more than once, for instance after seeking
+/// backwards, only count once. A milestone is emitted at most once
+/// per playback of a track: a new track, or stopping, starts a new
+/// one. The stream ends with `events`, or once the player vanishes.
+///
+/// Time is measured with tokio's clock, so that the accounting
+/// follows [paused time](tokio::time::pause) in tests.
+pub fn progress_milestones<'a, S>(
+    events: S,
+    state: PlayerState,
+    policy: MilestonePolicy,
+) -> ProgressMilestones<'a>
+where
+    S: Stream<Item = Event> + Send + 'a,
+{
+    let now = Instant::now();
+    let position = state.position.unwrap_or_default();
+    let mut clock = PlaybackClock::new(
+        position,
+        state.playback_status.unwrap_or(PlaybackStatus::Stopped),
+        state.rate.unwrap_or(1.0),
+        now.into_std(),
+    );
+    let metadata = state.metadata;
+    clock.set_length(metadata.as_ref().and_then(util::track_length));
+    let track = metadata.as_ref().and_then(util::track_identity);
+    let mut progress = Progress {
+        events: events.boxed(),
+        policy,
+        player: state.player,
+        tracked: EventClock::new(clock, track.clone()),
+        track,
+        metadata,
+        listened: Vec::new(),
+        from: position,
+        reached: false,
+        next: None,
+    };
+
+    let first = progress.check(now);
+
+    let rest = stream::unfold(progress, |mut progress| async move {
+        loop {
+            let next = progress.next;
+            let now = tokio::select! {
+                event = progress.events.next() => {
+                    let event = event?;
+                    if let Event::PlayerLifecycle(LifecycleEvent::Vanished { .. }) = event {
+                        return None;
+                    }
+                    let now = Instant::now();
+                    progress.apply(&event, now);
+                    now
+                }
+                _ = runtime::sleep_until(next.unwrap_or_else(Instant::now)), if next.is_some() => {
+                    let now = Instant::now();
+                    progress.settle(now);
+                    now
+                }
+            };
+            if let Some(milestone) = progress.check(now) {
+                return Some((milestone, progress));
+            }
+        }
+    });
+
+    ProgressMilestones(stream::iter(first).chain(rest).boxed())
+}
@@ -0,0 +1,243 @@
+use crate::{
+    util, CallbackGuard, Error, Event, EventManager, EventType, IntoPlayerName, Message, Player,
+    Result as DefaultResult, Token,
+};
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use std::sync::{Arc, Mutex};
+
+/// An event received by a [`MultiBusManager`], along with the
+/// label of the bus it came from.
+#[derive(Clone, Debug)]
+pub struct BusEvent {
+    /// The label the bus was added with.
+    pub bus: String,
+    /// The event itself.
+    pub event: Event,
+}
+
+/// Watches players on several buses at once, such as the session
+/// bus along with the system bus, or the bus of a sandbox.
+///
+/// Each bus is handled by its own [`EventManager`], added under a
+/// label that identifies the bus in everything this returns.
+/// Callbacks and streams cover every bus added so far, and
+/// [`player`](Self::player) opens a player on the bus an event
+/// came from.
+///
+/// # Example
+/// ```no_run
+/// # use futures::StreamExt;
+/// # use pris::{EventManager, EventType, MultiBusManager};
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// # let (session, system) = (pris::get_connection(), pris::get_connection());
+/// let mut buses = MultiBusManager::new();
+/// buses.add_bus("session", EventManager::new(&session))?;
+/// buses.add_bus("system", EventManager::new(&system))?;
+///
+/// let mut events = buses.stream(&[EventType::PropertiesChanged]).await?;
+/// while let Some(received) = events.next().await {
+///     let player = buses.player(&received.bus, received.event.player()).await?;
+///     println!("{} changed on the {} bus", player.name, received.bus);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct MultiBusManager<'a> {
+    buses: Vec<(String, EventManager<'a>)>,
+}
+
+impl<'a> MultiBusManager<'a> {
+    /// Creates a multiplexer without any buses.
+    pub fn new() -> MultiBusManager<'a> {
+        MultiBusManager::default()
+    }
+
+    /// Adds the bus of `manager`, under the label `bus`.
+    ///
+    /// Callbacks and streams only cover the buses added before
+    /// they were created.
+    ///
+    /// # Errors
+    /// Returns an `Err` if a bus is already labelled `bus`.
+    pub fn add_bus<T>(&mut self, bus: T, manager: EventManager<'a>) -> DefaultResult<()>
+    where
+        T: Into<String>,
+    {
+        let bus = bus.into();
+        if self.manager(&bus).is_some() {
+            return Err(Error::InvalidArgument(format!(
+                "A bus is already labelled {}.",
+                bus
+            )));
+        }
+        self.buses.push((bus, manager));
+
+        Ok(())
+    }
+
+    /// The labels of the buses, in the order they were added.
+    pub fn buses(&self) -> Vec<&str> {
+        self.buses.iter().map(|(bus, _)| bus.as_str()).collect()
+    }
+
+    /// The manager of the bus labelled `bus`.
+    pub fn manager(&self, bus: &str) -> Option<&EventManager<'a>> {
+        self.buses
+            .iter()
+            .find(|(label, _)| label == bus)
+            .map(|(_, manager)| manager)
+    }
+
+    /// Adds a callback for `event_type` on every bus, which is also
+    /// passed the label of the bus each message came from.
+    ///
+    /// Once the callback returns `false`, it stops receiving
+    /// messages from every bus. It is removed from all of them
+    /// when the returned [`MultiCallbackGuard`] is dropped.
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding a match
+    /// rule to any bus, in which case it is added to none of them.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::{EventType, MultiBusManager};
+    /// # async fn example(buses: &MultiBusManager<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    /// let _incoming = buses
+    ///     .add_callback(EventType::Seeked, |bus, msg| {
+    ///         println!("Seeked on the {} bus: {:?}", bus, msg);
+    ///         true
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn add_callback<F>(
+        &self,
+        event_type: EventType,
+        callback: F,
+    ) -> DefaultResult<MultiCallbackGuard<'a>>
+    where
+        F: FnMut(&str, Message) -> bool + Send + 'static,
+    {
+        // Shared by the buses, and emptied once it returns false
+        let callback = Arc::new(Mutex::new(Some(callback)));
+        let mut guards = Vec::new();
+        for (bus, manager) in &self.buses {
+            let label = bus.clone();
+            let callback = callback.clone();
+            let guard = manager
+                .add_callback(event_type, move |msg| {
+                    let mut callback = callback.lock().unwrap();
+                    let keep = callback.as_mut().is_some_and(|f| f(&label, msg));
+                    if !keep {
+                        *callback = None;
+                    }
+                    keep
+                })
+                .await?;
+            guards.push((bus.clone(), guard));
+        }
+
+        Ok(MultiCallbackGuard { guards })
+    }
+
+    /// Returns a [`Stream`](futures::Stream) of events of any of
+    /// `event_types` from every bus, in their order of arrival, as
+    /// [`EventManager::stream`] does for a single one.
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding a match
+    /// rule to any bus.
+    pub async fn stream(
+        &self,
+        event_types: &[EventType],
+    ) -> DefaultResult<BoxStream<'a, BusEvent>> {
+        let mut streams = Vec::new();
+        for (bus, manager) in &self.buses {
+            let bus = bus.clone();
+            let events = manager.stream(event_types).await?;
+            streams.push(
+                events
+                    .map(move |event| BusEvent {
+                        bus: bus.clone(),
+                        event,
+                    })
+                    .boxed(),
+            );
+        }
+
+        Ok(stream::select_all(streams).boxed())
+    }
+
+    /// Gets every player on every bus, paired with the label of its
+    /// bus. The same player name may show up on more than one bus.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the players on any bus can't be listed.
+    pub async fn players(&self) -> DefaultResult<Vec<(String, Player<'a>)>> {
+        let mut players = Vec::new();
+        for (bus, manager) in &self.buses {
+            let conn = manager.conn();
+            for name in util::list_players(&conn).await? {
+                // Players may quit while they're being listed
+                if let Ok(player) = Player::try_with(name, conn.clone()).await {
+                    players.push((bus.clone(), player));
+                }
+            }
+        }
+
+        Ok(players)
+    }
+
+    /// Opens the player `name` on the bus labelled `bus`, as
+    /// reported in a [`BusEvent`].
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is no bus labelled `bus`, if `name`
+    /// can't be the name of a player, or if the player isn't on it.
+    pub async fn player<T>(&self, bus: &str, name: T) -> DefaultResult<Player<'a>>
+    where
+        T: IntoPlayerName,
+    {
+        let manager = self
+            .manager(bus)
+            .ok_or_else(|| Error::InvalidArgument(format!("No bus is labelled {}.", bus)))?;
+
+        Player::try_with(name, manager.conn()).await
+    }
+}
+
+/// A callback registered on several buses, created with
+/// [`MultiBusManager::add_callback`].
+///
+/// The callback is removed from every bus when this is dropped.
+pub struct MultiCallbackGuard<'a> {
+    guards: Vec<(String, CallbackGuard<'a>)>,
+}
+
+impl MultiCallbackGuard<'_> {
+    /// The token of the callback on each bus, paired with the label
+    /// of the bus. Each can be passed to the
+    /// [`remove_callback`](EventManager::remove_callback) of the
+    /// manager of its bus.
+    pub fn tokens(&self) -> Vec<(String, Token)> {
+        self.guards
+            .iter()
+            .map(|(bus, guard)| (bus.clone(), guard.token()))
+            .collect()
+    }
+
+    /// Keeps the callback registered on every bus until it is
+    /// removed through their managers, as with
+    /// [`CallbackGuard::detach`].
+    pub fn detach(self) {
+        for (_, guard) in self.guards {
+            guard.detach();
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use crate::{util::MPRIS_PREFIX, Error, Player, PlayerCandidate, PlayerSnapshot, Result};
+use dbus::strings::BusName;
+use std::{
+    ffi::{OsStr, OsString},
+    fmt,
+    str::FromStr,
+};
+
+/// The longest a bus name can be, prefix included.
+const MAX_BUS_NAME: usize = 255;
+
+/// The name of a player, without the `org.mpris.MediaPlayer2.`
+/// prefix, such as `vlc` or `firefox.instance_1234`, checked to make
+/// a valid bus name with it.
+///
+/// Anything [`IntoPlayerName`] is implemented for can be given where
+/// a player is named, and is checked before anything is asked of the
+/// bus.
+///
+/// # Example
+/// ```
+/// # use pris::PlayerName;
+/// let name: PlayerName = "org.mpris.MediaPlayer2.vlc".parse().unwrap();
+/// assert_eq!(name.as_str(), "vlc");
+/// assert!("".parse::<PlayerName>().is_err());
+/// assert!("not a name".parse::<PlayerName>().is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PlayerName(String);
+
+impl PlayerName {
+    /// The name, without the `org.mpris.MediaPlayer2.` prefix.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The well-known bus name of the player, with the
+    /// `org.mpris.MediaPlayer2.` prefix.
+    pub fn bus_name(&self) -> String {
+        format!("{}{}", MPRIS_PREFIX, self.0)
+    }
+
+    /// The name, without the `org.mpris.MediaPlayer2.` prefix.
+    pub fn into_string(self) -> String {
+        self.0
+    }
+
+    /// Checks `name`, given with or without the
+    /// `org.mpris.MediaPlayer2.` prefix.
+    fn check(name: &str) -> Result<PlayerName> {
+        let invalid = |reason: &str| {
+            Err(Error::InvalidArgument(format!(
+                "{:?} isn't a player name: {}.",
+                name, reason
+            )))
+        };
+
+        let bare = name.strip_prefix(MPRIS_PREFIX).unwrap_or(name);
+        if bare.is_empty() {
+            return invalid("it's empty");
+        }
+        if bare.contains('\0') {
+            return invalid("it contains a NUL");
+        }
+        if MPRIS_PREFIX.len() + bare.len() > MAX_BUS_NAME {
+            return invalid("it's too long to be a bus name");
+        }
+        for element in bare.split('.') {
+            if element.is_empty() {
+                return invalid("it has an empty element");
+            }
+            if element.starts_with(|c: char| c.is_ascii_digit()) {
+                return invalid(&format!("{:?} starts with a digit", element));
+            }
+            if let Some(c) = element
+                .chars()
+                .find(|c| !c.is_ascii_alphanumeric() && *c != '_' && *c != '-')
+            {
+                return invalid(&format!("{:?} can't be in a bus name", c));
+            }
+        }
+
+        Ok(PlayerName(bare.to_string()))
+    }
+}
+
+impl fmt::Display for PlayerName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for PlayerName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for PlayerName {
+    type Err = Error;
+
+    /// Same as [`IntoPlayerName::into_player_name`].
+    fn from_str(name: &str) -> Result<PlayerName> {
+        PlayerName::check(name)
+    }
+}
+
+/// Something that names a player, such as `"vlc"`, the bus name
+/// `"org.mpris.MediaPlayer2.vlc"`, or a [`PlayerCandidate`], taken by
+/// [`Player::try_new`] and the other functions that look a player up
+/// by its name.
+///
+/// Strings may be given with or without the `org.mpris.MediaPlayer2.`
+/// prefix.
+pub trait IntoPlayerName {
+    /// Checks the name, before anything is asked of the bus.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidArgument`] if it is empty, contains a
+    /// NUL, or can't be part of a bus name.
+    fn into_player_name(self) -> Result<PlayerName>;
+}
+
+impl IntoPlayerName for PlayerName {
+    fn into_player_name(self) -> Result<PlayerName> {
+        Ok(self)
+    }
+}
+
+impl IntoPlayerName for &PlayerName {
+    fn into_player_name(self) -> Result<PlayerName> {
+        Ok(self.clone())
+    }
+}
+
+impl IntoPlayerName for &str {
+    fn into_player_name(self) -> Result<PlayerName> {
+        PlayerName::check(self)
+    }
+}
+
+impl IntoPlayerName for String {
+    fn into_player_name(self) -> Result<PlayerName> {
+        PlayerName::check(&self)
+    }
+}
+
+impl IntoPlayerName for &String {
+    fn into_player_name(self) -> Result<PlayerName> {
+        PlayerName::check(self)
+    }
+}
+
+impl IntoPlayerName for &OsStr {
+    /// Same as for `&str`, for names such as those taken from the
+    /// command line.
+    ///
+    /// # Errors
+    /// Also returns [`Error::InvalidArgument`] if it isn't valid
+    /// UTF-8.
+    fn into_player_name(self) -> Result<PlayerName> {
+        match self.to_str() {
+            Some(name) => PlayerName::check(name),
+            None => Err(Error::InvalidArgument(format!(
+                "{:?} isn't a player name: it isn't valid UTF-8.",
+                self
+            ))),
+        }
+    }
+}
+
+impl IntoPlayerName for OsString {
+    fn into_player_name(self) -> Result<PlayerName> {
+        self.as_os_str().into_player_name()
+    }
+}
+
+impl IntoPlayerName for BusName<'_> {
+    /// The player owning the bus name.
+    ///
+    /// # Errors
+    /// Also returns [`Error::InvalidArgument`] if it doesn't start
+    /// with `org.mpris.MediaPlayer2.`.
+    fn into_player_name(self) -> Result<PlayerName> {
+        (&self).into_player_name()
+    }
+}
+
+impl IntoPlayerName for &BusName<'_> {
+    fn into_player_name(self) -> Result<PlayerName> {
+        if !self.starts_with(MPRIS_PREFIX) {
+            return Err(Error::InvalidArgument(format!(
+                "{:?} isn't the bus name of a player.",
+                &**self
+            )));
+        }
+        PlayerName::check(self)
+    }
+}
+
+impl IntoPlayerName for PlayerCandidate {
+    fn into_player_name(self) -> Result<PlayerName> {
+        PlayerName::check(&self.name)
+    }
+}
+
+impl IntoPlayerName for &PlayerCandidate {
+    fn into_player_name(self) -> Result<PlayerName> {
+        PlayerName::check(&self.name)
+    }
+}
+
+impl IntoPlayerName for &PlayerSnapshot<'_> {
+    fn into_player_name(self) -> Result<PlayerName> {
+        (&self.player).into_player_name()
+    }
+}
+
+impl IntoPlayerName for &Player<'_> {
+    fn into_player_name(self) -> Result<PlayerName> {
+        PlayerName::check(&self.name)
+    }
+}
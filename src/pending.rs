@@ -0,0 +1,342 @@
+use crate::{
+    event_manager,
+    util::{self, ConnRef},
+    CallbackGuard, ChangedProperties, Event, EventManager, EventType, LifecycleEvent, Message,
+    PropertiesChangedEvent, Result as DefaultResult, Token,
+};
+use dbus::{
+    arg::PropMap,
+    channel::MatchingReceiver,
+    message::MessageType,
+    nonblock::{NonblockReply, SyncConnection},
+};
+use futures::stream::{self, BoxStream, StreamExt};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+type PendingCallback = Box<dyn FnMut(Event) -> bool + Send>;
+
+/// A player matching the pattern that is currently on the bus.
+struct Active {
+    name: String,
+    /// Events held back until the player's snapshot arrives.
+    held: Option<Vec<Event>>,
+}
+
+struct PendingState {
+    pattern: String,
+    /// Keyed by the unique name of each player.
+    active: HashMap<String, Active>,
+    callbacks: Vec<(EventType, PendingCallback)>,
+}
+
+impl PendingState {
+    fn matches(&self, name: &str) -> bool {
+        match self.pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == self.pattern,
+        }
+    }
+
+    /// Passes `event` to the callbacks of its type, unless it comes
+    /// from a player whose snapshot is still on its way.
+    fn deliver(&mut self, owner: &str, event: Event) {
+        if let Some(held) = self.active.get_mut(owner).and_then(|a| a.held.as_mut()) {
+            held.push(event);
+            return;
+        }
+        self.dispatch(event);
+    }
+
+    fn dispatch(&mut self, event: Event) {
+        let event_type = event_type(&event);
+        self.callbacks.retain_mut(|(wanted, callback)| {
+            // Returning false drops the callback
+            *wanted != event_type || callback(event.clone())
+        });
+    }
+
+    /// Starts following the player `name`, owned by `owner`, and
+    /// asks it for a snapshot of its state.
+    fn activate(state: &Arc<Mutex<PendingState>>, conn: &SyncConnection, owner: &str, name: &str) {
+        let active = Active {
+            name: name.to_string(),
+            held: Some(Vec::new()),
+        };
+        state
+            .lock()
+            .unwrap()
+            .active
+            .insert(owner.to_string(), active);
+
+        let owner = owner.to_string();
+        let snapshot = {
+            let state = state.clone();
+            let owner = owner.clone();
+            move |reply: Message, _: &SyncConnection| {
+                let properties = match reply.msg_type() {
+                    MessageType::MethodReturn => reply.read1::<PropMap>().ok(),
+                    _ => None,
+                };
+                state.lock().unwrap().snapshot(&owner, properties);
+            }
+        };
+        let call = Message::new_method_call(&owner, MPRIS_PATH, PROPERTIES_INTERFACE, "GetAll")
+            .map(|msg| msg.append1(PLAYER_INTERFACE));
+        let sent = call.map(|msg| conn.send_with_reply(msg, Box::new(snapshot)).is_ok());
+        if sent != Ok(true) {
+            state.lock().unwrap().snapshot(&owner, None);
+        }
+    }
+
+    /// Delivers the snapshot of `owner`, if it could be fetched,
+    /// followed by the events held back while waiting for it.
+    fn snapshot(&mut self, owner: &str, properties: Option<PropMap>) {
+        let Some(active) = self.active.get_mut(owner) else {
+            // The player quit in the meantime
+            return;
+        };
+        let name = active.name.clone();
+        let held = active.held.take().unwrap_or_default();
+        if let Some(properties) = properties {
+            self.dispatch(Event::PropertiesChanged(PropertiesChangedEvent {
+                player: name,
+                properties: ChangedProperties::from_parts(
+                    PLAYER_INTERFACE.to_string(),
+                    properties,
+                    Vec::new(),
+                ),
+            }));
+        }
+        for event in held {
+            self.dispatch(event);
+        }
+    }
+}
+
+fn event_type(event: &Event) -> EventType {
+    match event {
+        Event::PropertiesChanged(_) => EventType::PropertiesChanged,
+        Event::Seeked(_) => EventType::Seeked,
+        Event::PlayerLifecycle(_) => EventType::PlayerLifecycle,
+    }
+}
+
+/// Callbacks for a player that may not be running yet, created with
+/// [`EventManager::when_player`].
+///
+/// Callbacks are added once, and receive the events of every player
+/// matching the pattern for as long as this is alive: they are
+/// activated when a matching player appears, and kept, inactive,
+/// after it quits, until it appears again. On activation,
+/// `PropertiesChanged` callbacks first receive a snapshot of the
+/// player's current properties, and events sent in the meantime
+/// are only passed on after it.
+///
+/// Callbacks run on the task dispatching messages, while the other
+/// callbacks of the handle wait, so they shouldn't block or use the
+/// handle themselves.
+///
+/// # Example
+/// ```no_run
+/// # use pris::{Event, EventManager, EventType};
+/// # async fn example(manager: &EventManager<'_>) -> Result<(), Box<dyn std::error::Error>> {
+/// let spotify = manager.when_player("spotify").await?;
+/// spotify
+///     .add_callback(EventType::PropertiesChanged, |event| {
+///         if let Event::PropertiesChanged(changed) = event {
+///             println!("Spotify is {:?}", changed.properties.playback_status);
+///         }
+///         true
+///     })
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct PendingPlayer<'a> {
+    manager: EventManager<'a>,
+    conn: ConnRef<'a>,
+    /// The match following players as they come and go.
+    tracker: Token,
+    state: Arc<Mutex<PendingState>>,
+    /// The match feeding the callbacks of each type.
+    guards: Mutex<HashMap<EventType, CallbackGuard<'a>>>,
+}
+
+impl<'a> PendingPlayer<'a> {
+    pub(crate) async fn new(
+        manager: &EventManager<'a>,
+        pattern: &str,
+    ) -> DefaultResult<PendingPlayer<'a>> {
+        let state = Arc::new(Mutex::new(PendingState {
+            pattern: pattern.to_string(),
+            active: HashMap::new(),
+            callbacks: Vec::new(),
+        }));
+
+        // Like the manager's own name tracking, this isn't paused
+        let conn = manager.conn();
+        let rule = EventType::PlayerLifecycle.match_rule();
+        event_manager::add_rule(&conn, manager.counts(), &rule).await?;
+        let tracker = {
+            let state = state.clone();
+            conn.start_receive(
+                rule,
+                Box::new(move |msg, conn| {
+                    following(&state, conn, &msg);
+                    true
+                }),
+            )
+        };
+        let pending = PendingPlayer {
+            manager: manager.clone(),
+            conn: conn.clone(),
+            tracker,
+            state,
+            guards: Mutex::default(),
+        };
+
+        // Players that appear from here on are caught by the callback
+        for name in util::list_players(&conn).await? {
+            if !pending.state.lock().unwrap().matches(&name) {
+                continue;
+            }
+            let bus_name = format!("{}{}", util::MPRIS_PREFIX, name);
+            if let Ok(owner) = util::get_name_owner(&bus_name, &conn).await {
+                let known = pending.state.lock().unwrap().active.contains_key(&owner);
+                if !known {
+                    PendingState::activate(&pending.state, &conn, &owner, &name);
+                }
+            }
+        }
+
+        Ok(pending)
+    }
+
+    /// Adds a callback for the events of `event_type` from matching
+    /// players. It is kept until it returns `false`, or this is
+    /// dropped.
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding a match
+    /// rule to the connection.
+    pub async fn add_callback<F>(&self, event_type: EventType, callback: F) -> DefaultResult<()>
+    where
+        F: FnMut(Event) -> bool + Send + 'static,
+    {
+        self.add_match(event_type).await?;
+        self.state
+            .lock()
+            .unwrap()
+            .callbacks
+            .push((event_type, Box::new(callback)));
+
+        Ok(())
+    }
+
+    /// Returns a stream of the events of any of `event_types` from
+    /// matching players, which stops receiving them once dropped.
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding a match
+    /// rule to the connection.
+    pub async fn stream(&self, event_types: &[EventType]) -> DefaultResult<BoxStream<'a, Event>> {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        for &event_type in event_types {
+            let sender = sender.clone();
+            // Once the stream is gone, returning false drops the callback
+            self.add_callback(event_type, move |event| sender.send(event).is_ok())
+                .await?;
+        }
+
+        let events = stream::unfold(receiver, |mut receiver| async move {
+            let event = receiver.recv().await?;
+            Some((event, receiver))
+        });
+        Ok(events.boxed())
+    }
+
+    /// The names of the matching players currently on the bus.
+    pub fn players(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        let mut players: Vec<String> = state.active.values().map(|a| a.name.clone()).collect();
+        players.sort();
+
+        players
+    }
+
+    /// Registers the match feeding callbacks of `event_type`, unless
+    /// it already is.
+    async fn add_match(&self, event_type: EventType) -> DefaultResult<()> {
+        if self.guards.lock().unwrap().contains_key(&event_type) {
+            return Ok(());
+        }
+
+        let state = self.state.clone();
+        let guard = self
+            .manager
+            .add_callback(event_type, move |msg| {
+                let mut state = state.lock().unwrap();
+                if event_type == EventType::PlayerLifecycle {
+                    match LifecycleEvent::parse(&msg) {
+                        Ok(lifecycle) if state.matches(lifecycle.name()) => {
+                            state.dispatch(Event::PlayerLifecycle(lifecycle));
+                        }
+                        _ => {}
+                    }
+                    return true;
+                }
+
+                let owner = msg.sender().map(|s| s.to_string()).unwrap_or_default();
+                let Some(active) = state.active.get(&owner) else {
+                    return true;
+                };
+                if let Ok(event) = Event::parse(&msg, active.name.as_str()) {
+                    state.deliver(&owner, event);
+                }
+                true
+            })
+            .await?;
+        // A match added concurrently for the same type is dropped here
+        self.guards
+            .lock()
+            .unwrap()
+            .entry(event_type)
+            .or_insert(guard);
+
+        Ok(())
+    }
+}
+
+/// Follows matching players as they come and go, from a
+/// `NameOwnerChanged` signal.
+fn following(state: &Arc<Mutex<PendingState>>, conn: &SyncConnection, msg: &Message) {
+    let Ok((name, old_owner, new_owner)) = msg.read3::<&str, &str, &str>() else {
+        return;
+    };
+    let Some(name) = name.strip_prefix(util::MPRIS_PREFIX) else {
+        return;
+    };
+    {
+        let mut state = state.lock().unwrap();
+        if !state.matches(name) {
+            return;
+        }
+        state.active.remove(old_owner);
+    }
+    if !new_owner.is_empty() {
+        PendingState::activate(state, conn, new_owner, name);
+    }
+}
+
+impl Drop for PendingPlayer<'_> {
+    fn drop(&mut self) {
+        event_manager::detach_match(&self.conn, self.manager.counts(), self.tracker);
+    }
+}
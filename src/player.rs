@@ -1,95 +1,517 @@
-use crate::{methods, util, Result};
+#[cfg(feature = "events")]
+use crate::{event_manager, runtime, trace, EventType};
+use crate::{
+    guard, methods, spec, support, util, util::ConnRef, DebugSink, Error, Guarded, IntoPlayerName,
+    PlayerFinder, PlayerState, PositionStrategy, Result, RetryPolicy, SupportReport,
+};
 use dbus::{
     arg::{Append, Arg, Get, PropMap},
-    nonblock::{Proxy, SyncConnection},
+    nonblock::{stdintf::org_freedesktop_dbus::Properties, Proxy, SyncConnection},
+    strings::{BusName, Path},
 };
-use std::{fmt::Display, time::Duration};
+#[cfg(feature = "events")]
+use futures::StreamExt;
+use std::{fmt, future::Future, time::Duration};
+
+/// How long players are given to answer a call, unless changed with
+/// [`Player::set_timeout`].
+pub(crate) const DEFAULT_TIMEOUT: Duration = Duration::from_millis(5000);
+
+/// One of the players a name given to [`Player::try_new`] could
+/// refer to, listed in [`Error::AmbiguousPlayer`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlayerCandidate {
+    /// The name of the player, without the `org.mpris.MediaPlayer2.`
+    /// prefix, such as `firefox.instance_1234`.
+    pub name: String,
+    /// The unique name of the connection owning it, such as `:1.42`.
+    pub unique_name: String,
+    /// The `Identity` of the player, such as `Firefox`, if it gave it
+    /// promptly.
+    pub identity: Option<String>,
+}
 
 /// A struct used to control an MPRIS player.
 #[derive(Clone)]
 pub struct Player<'a> {
+    /// The name of the player, without the `org.mpris.MediaPlayer2.`
+    /// prefix. Changing it doesn't change where calls are sent.
     pub name: String,
-    conn: &'a SyncConnection,
+    unique: Option<String>,
+    /// Where calls are sent, built once rather than for every call.
+    destination: BusName<'static>,
+    path: Path<'static>,
+    conn: ConnRef<'a>,
+    timeout: Duration,
+    retry: Option<RetryPolicy>,
+    volume_ceiling: f64,
+    debug: Option<DebugSink>,
+    /// Whether the player was looked up on the bus by name as this
+    /// handle was made, rather than taken from a listing or a cache.
+    validated: bool,
 }
 
 impl<'a> Player<'a> {
     /// Tries to create a new `Player` instance from a given name.
     ///
+    /// Players that allow several instances, such as Firefox or VLC,
+    /// add an instance suffix to their name, like
+    /// `firefox.instance_1234`. If no player has the exact name
+    /// `name`, the one player named after it with such a suffix is
+    /// used instead.
+    ///
+    /// `name` can be anything [`IntoPlayerName`] is implemented for,
+    /// such as `"vlc"` or `"org.mpris.MediaPlayer2.vlc"`.
+    ///
     /// # Errors
-    /// Returns an `Err` if the provided player is invalid.
+    /// Returns [`Error::InvalidArgument`] if `name` can't be the name
+    /// of a player, before anything is asked of the bus,
+    /// [`Error::UnknownPlayer`] if no player goes by `name`, listing
+    /// those that are on the bus, or
+    /// [`Error::AmbiguousPlayer`] if several instances of it are
+    /// running, listing them so that one can be picked by its full
+    /// name.
     pub async fn try_new<T>(name: T, conn: &'a SyncConnection) -> Result<Player<'a>>
     where
-        T: AsRef<str> + Display,
+        T: IntoPlayerName,
     {
-        if !util::validate(name.as_ref(), conn).await? {
-            return Err(Box::from("The provided player was invalid."));
+        let name = name.into_player_name()?;
+        let name = util::resolve_name(name.as_str(), conn).await?;
+        Player::with_owner(name, None, ConnRef::Borrowed(conn)).map(Player::validated)
+    }
+
+    /// Same as `try_new`, but the returned `Player` is pinned to the
+    /// connection owning the player now, by its unique name.
+    ///
+    /// Calls made through it keep going to that connection even if
+    /// another process takes over the name, such as a second instance
+    /// of the same application, and fail with [`Error::PlayerGone`]
+    /// once it leaves the bus.
+    ///
+    /// # Errors
+    /// Same as `try_new`.
+    pub async fn try_pinned<T>(name: T, conn: &'a SyncConnection) -> Result<Player<'a>>
+    where
+        T: IntoPlayerName,
+    {
+        let name = name.into_player_name()?;
+        let name = util::resolve_name(name.as_str(), conn).await?;
+        let owner = util::get_name_owner(&format!("{}{}", util::MPRIS_PREFIX, name), conn)
+            .await
+            .map_err(|_| Error::InvalidPlayer(name.clone()))?;
+
+        Player::with_owner(name, Some(owner), ConnRef::Borrowed(conn)).map(Player::validated)
+    }
+
+    /// A `Player` for every MPRIS player on the bus, in the order of
+    /// [`list_players`](crate::list_players).
+    ///
+    /// The names are listed with a single call, and the players
+    /// aren't asked anything further, so the handles are ready to use
+    /// right away. They are a snapshot: players that start later
+    /// aren't included, and calls to ones that have since quit fail
+    /// with [`Error::InvalidPlayer`] or [`Error::PlayerGone`]. Unlike
+    /// with [`get_all_players`](crate::get_all_players), the handles
+    /// aren't pinned to the connection owning each player.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::Player;
+    /// # async fn example() -> pris::Result<()> {
+    /// let conn = pris::get_connection();
+    /// for player in Player::all(&conn).await? {
+    ///     player.pause().await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// May `Err` if the bus can't be asked.
+    pub async fn all(conn: &'a SyncConnection) -> Result<Vec<Player<'a>>> {
+        Ok(util::list_players(conn)
+            .await?
+            .into_iter()
+            .filter_map(|name| Player::with_owner(name, None, ConnRef::Borrowed(conn)).ok())
+            .collect())
+    }
+
+    /// The player that's most likely the one to control, such as for
+    /// a media key: the first one playing, or if none is, the first
+    /// one paused, in the order of [`list_players`](crate::list_players).
+    /// `None` if every player is stopped, or there are none.
+    ///
+    /// Every player is asked for its `PlaybackStatus` at once, and
+    /// given 250 milliseconds to answer. Players that fail or don't
+    /// answer in time are skipped rather than failing the search.
+    /// This is a [`PlayerFinder`] with `or_playing().or_paused()`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::Player;
+    /// # async fn example() -> pris::Result<()> {
+    /// let conn = pris::get_connection();
+    /// if let Some(player) = Player::find_active(&conn).await? {
+    ///     player.play_pause().await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// May `Err` if the bus can't be asked.
+    pub async fn find_active(conn: &'a SyncConnection) -> Result<Option<Player<'a>>> {
+        let finder = PlayerFinder::new(conn).or_playing().or_paused();
+        match finder.find().await {
+            Ok(player) => Ok(Some(player)),
+            Err(Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(e),
         }
+    }
 
-        let player = Player {
-            name: name.to_string(),
-            conn,
+    /// Same as `try_new`, but if no player goes by `name` yet, waits
+    /// up to `timeout` for one to take the name, such as after
+    /// launching it.
+    ///
+    /// The bus is watched for the name before looking for the
+    /// player, so that one appearing in between isn't missed. Once
+    /// it appears, only the exact name `name` is waited for, rather
+    /// than its instances. The watch is removed once the wait ends
+    /// or the future is dropped.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::Player;
+    /// # use std::time::Duration;
+    /// # async fn example() -> pris::Result<()> {
+    /// let conn = pris::get_connection();
+    /// std::process::Command::new("vlc").spawn().unwrap();
+    /// let player = Player::wait_for("vlc", &conn, Duration::from_secs(10)).await?;
+    /// player.open_uri("file:///music/track.flac").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns [`Error::Timeout`] if no player took the name within
+    /// `timeout`, [`Error::InvalidArgument`] or
+    /// [`Error::AmbiguousPlayer`] as `try_new` does, or may `Err` if
+    /// the bus can't be asked.
+    #[cfg(feature = "events")]
+    pub async fn wait_for<T>(
+        name: T,
+        conn: &'a SyncConnection,
+        timeout: Duration,
+    ) -> Result<Player<'a>>
+    where
+        T: IntoPlayerName,
+    {
+        let name = name.into_player_name()?;
+        let bus_name = name.bus_name();
+        let (sender, mut acquired) = futures::channel::mpsc::unbounded();
+        let rule = EventType::PlayerLifecycle.match_rule();
+        // Not shared with anything else
+        let counts = event_manager::RuleCounts::default();
+        let token = event_manager::add_match(conn, &counts, rule, move |msg| {
+            match msg.read3::<&str, &str, &str>() {
+                Ok((changed, _, new_owner)) if changed == bus_name && !new_owner.is_empty() => {
+                    sender.unbounded_send(()).is_ok()
+                }
+                _ => true,
+            }
+        })
+        .await?;
+        let _guard = event_manager::DetachOnDrop {
+            conn: ConnRef::Borrowed(conn),
+            counts,
+            token,
+        };
+
+        let wait = async {
+            match Player::try_new(&name, conn).await {
+                Err(Error::UnknownPlayer { .. }) | Err(Error::InvalidPlayer(_)) => {}
+                found => return found,
+            }
+            match acquired.next().await {
+                Some(()) => Player::with_owner(name.to_string(), None, ConnRef::Borrowed(conn))
+                    .map(Player::validated),
+                None => Err(Error::Disconnected),
+            }
         };
-        Ok(player)
+
+        runtime::timeout(timeout, wait).await.map_err(|_| {
+            trace::event!(
+                debug,
+                player = %name,
+                operation = "NameOwnerChanged",
+                limit_ms = timeout.as_millis() as u64,
+                "timed out"
+            );
+            Error::Timeout {
+                player: None,
+                operation: "NameOwnerChanged".to_string(),
+                limit: timeout,
+                source: None,
+            }
+        })?
     }
 
-    #[doc(hidden)]
-    pub fn get_proxy(&mut self) -> Result<Proxy<&'a SyncConnection>> {
-        let proxy = Proxy::new(
-            format!("org.mpris.MediaPlayer2.{}", self.name),
-            "/org/mpris/MediaPlayer2",
-            Duration::from_millis(5000),
-            self.conn,
-        );
+    /// Same as `try_new`, on a borrowed or shared connection, but
+    /// only for the player with the exact name `name`.
+    #[cfg(feature = "events")]
+    pub(crate) async fn try_with<T>(name: T, conn: ConnRef<'a>) -> Result<Player<'a>>
+    where
+        T: IntoPlayerName,
+    {
+        let name = name.into_player_name()?;
+        if !util::validate(name.as_str(), &conn).await? {
+            return Err(Error::InvalidPlayer(name.into_string()));
+        }
+
+        Player::with_owner(name.into_string(), None, conn).map(Player::validated)
+    }
+
+    /// A `Player` for the player `name`, pinned to the connection
+    /// `unique` if given.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidPlayer`] if `name` or `unique` can't
+    /// be a bus name.
+    pub(crate) fn with_owner(
+        name: String,
+        unique: Option<String>,
+        conn: ConnRef<'a>,
+    ) -> Result<Self> {
+        let destination = match &unique {
+            Some(unique) => unique.clone(),
+            None => format!("{}{}", util::MPRIS_PREFIX, name),
+        };
+        let destination = match BusName::new(destination) {
+            Ok(destination) => destination,
+            Err(_) => return Err(Error::InvalidPlayer(name)),
+        };
+
+        Ok(Player {
+            name,
+            unique,
+            destination,
+            path: Path::from("/org/mpris/MediaPlayer2"),
+            conn,
+            timeout: DEFAULT_TIMEOUT,
+            retry: None,
+            volume_ceiling: 1.0,
+            debug: None,
+            validated: false,
+        })
+    }
+
+    /// Marks the player as looked up on the bus by name.
+    fn validated(mut self) -> Self {
+        self.validated = true;
+        self
+    }
+
+    /// The unique name of the connection this `Player` is pinned to,
+    /// if it is, as by [`try_pinned`](Self::try_pinned).
+    pub fn unique_name(&self) -> Option<&str> {
+        self.unique.as_deref()
+    }
+
+    #[cfg(feature = "events")]
+    pub(crate) fn connection(&self) -> ConnRef<'a> {
+        self.conn.clone()
+    }
+
+    /// Sets how long the player is given to answer each call made
+    /// through this `Player`, before it fails with [`Error::Timeout`].
+    /// The default is 5 seconds.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
 
-        Ok(proxy)
+    /// How long the player is given to answer each call.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Sets how the calls made through this `Player` are retried
+    /// after failing, or stops retrying them with `None`, which is
+    /// the default.
+    pub fn set_retry_policy(&mut self, policy: Option<RetryPolicy>) {
+        self.retry = policy;
+    }
+
+    /// The policy the calls made through this `Player` are retried
+    /// with, if any.
+    pub fn retry_policy(&self) -> Option<RetryPolicy> {
+        self.retry
+    }
+
+    /// Sets the highest volume the volume helpers go to, such as
+    /// [`set_volume`](Self::set_volume). The default is 1.0; negative
+    /// and NaN ceilings are taken as 0.0.
+    ///
+    /// Raising it only helps with players that boost the volume above
+    /// 1.0 themselves, such as mpv. Most players clamp the volume on
+    /// their own whatever the ceiling, so the volume they end up at
+    /// is best read back with [`get_volume`](Self::get_volume).
+    pub fn set_volume_ceiling(&mut self, ceiling: f64) {
+        self.volume_ceiling = ceiling.max(0.0);
+    }
+
+    /// The highest volume the volume helpers go to.
+    pub fn volume_ceiling(&self) -> f64 {
+        self.volume_ceiling
+    }
+
+    /// Sets where the calls made through this `Player` and the
+    /// replies to them are written, message by message, or stops
+    /// writing them with `None`, which is the default.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::{DebugSink, Player};
+    /// # async fn example(player: &mut Player<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    /// player.set_debug_sink(Some(DebugSink::stderr()));
+    /// // -> call dest=org.mpris.MediaPlayer2.vlc path=/org/mpris/MediaPlayer2 ... member=Pause sig= args=[]
+    /// // <- reply member=Pause sig= args=[]
+    /// player.pause().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_debug_sink(&mut self, sink: Option<DebugSink>) {
+        self.debug = sink;
+    }
+
+    /// Where the calls made through this `Player` are written, if
+    /// anywhere.
+    pub fn debug_sink(&self) -> Option<&DebugSink> {
+        self.debug.as_ref()
+    }
+
+    #[doc(hidden)]
+    pub fn get_proxy(&self) -> Proxy<'_, &SyncConnection> {
+        Proxy::new(&self.destination, &self.path, self.timeout, &*self.conn)
     }
 
     /// Skips to the next track
     ///
     /// # Errors
-    /// Will `Err` if the `Player` has closed.
-    pub async fn next(&mut self) -> Result<()> {
-        Ok(methods::next(self).await?)
+    /// Will `Err` with [`Error::PlayerGone`] if the `Player` has closed.
+    pub async fn next(&self) -> Result<()> {
+        methods::next(self).await
     }
 
     /// Skips to the previous track
     ///
     /// # Errors
-    /// Will `Err` if the `Player` has closed.
-    pub async fn previous(&mut self) -> Result<()> {
-        Ok(methods::previous(self).await?)
+    /// Will `Err` with [`Error::PlayerGone`] if the `Player` has closed.
+    pub async fn previous(&self) -> Result<()> {
+        methods::previous(self).await
     }
 
     /// Pauses the current track
     ///
     /// # Errors
-    /// Will `Err` if the `Player` has closed.
-    pub async fn pause(&mut self) -> Result<()> {
-        Ok(methods::pause(self).await?)
+    /// Will `Err` with [`Error::PlayerGone`] if the `Player` has closed,
+    /// or [`Error::UnsupportedOperation`] if it rejects `Pause` as
+    /// unknown or unsupported.
+    pub async fn pause(&self) -> Result<()> {
+        methods::pause(self).await
     }
 
     /// Starts or resumes the current track
     ///
     /// # Errors
-    /// Will `Err` if the `Player` has closed.
-    pub async fn play(&mut self) -> Result<()> {
-        Ok(methods::play(self).await?)
+    /// Will `Err` with [`Error::PlayerGone`] if the `Player` has closed.
+    pub async fn play(&self) -> Result<()> {
+        methods::play(self).await
     }
 
     /// Resumes/starts or pauses the current track
     ///
+    /// If the player rejects `PlayPause` as unknown or unsupported,
+    /// `Play` or `Pause` is called instead, depending on its
+    /// `PlaybackStatus`. This costs an extra round trip to read
+    /// its state first.
+    ///
     /// # Errors
-    /// Will `Err` if the `Player` has closed.
-    pub async fn play_pause(&mut self) -> Result<()> {
-        Ok(methods::play_pause(self).await?)
+    /// Will `Err` with [`Error::PlayerGone`] if the `Player` has closed,
+    /// or [`Error::UnsupportedOperation`] if the fallback has to pause
+    /// a player that can't be paused.
+    pub async fn play_pause(&self) -> Result<()> {
+        methods::play_pause(self).await
     }
 
     /// Stops playback
     ///
     /// # Errors
-    /// Will `Err` if the `Player` has closed.
-    pub async fn stop(&mut self) -> Result<()> {
-        Ok(methods::stop(self).await?)
+    /// Will `Err` with [`Error::PlayerGone`] if the `Player` has closed.
+    pub async fn stop(&self) -> Result<()> {
+        methods::stop(self).await
+    }
+
+    /// Skips to the next track, if the player reports `CanControl`
+    /// and `CanGoNext`. Both are read from the player first, so this
+    /// takes three round trips; [`CachedPlayer::try_next`] usually
+    /// takes one.
+    ///
+    /// [`CachedPlayer::try_next`]: crate::CachedPlayer::try_next
+    ///
+    /// # Errors
+    /// Same as [`next`](Self::next), and may also `Err` if the
+    /// capabilities can't be read.
+    pub async fn try_next(&self) -> Result<Guarded> {
+        self.guarded("CanGoNext", self.next()).await
+    }
+
+    /// Same as [`try_next`](Self::try_next), skipping to the
+    /// previous track if the player reports `CanGoPrevious`.
+    pub async fn try_previous(&self) -> Result<Guarded> {
+        self.guarded("CanGoPrevious", self.previous()).await
+    }
+
+    /// Same as [`try_next`](Self::try_next), pausing if the player
+    /// reports `CanPause`.
+    pub async fn try_pause(&self) -> Result<Guarded> {
+        self.guarded("CanPause", self.pause()).await
+    }
+
+    /// Same as [`try_next`](Self::try_next), playing if the player
+    /// reports `CanPlay`.
+    pub async fn try_play(&self) -> Result<Guarded> {
+        self.guarded("CanPlay", self.play()).await
+    }
+
+    /// Same as [`try_next`](Self::try_next), toggling playback if
+    /// the player reports `CanPause`.
+    pub async fn try_play_pause(&self) -> Result<Guarded> {
+        self.guarded("CanPause", self.play_pause()).await
+    }
+
+    /// Same as [`try_next`](Self::try_next), stopping if the player
+    /// reports `CanControl`.
+    pub async fn try_stop(&self) -> Result<Guarded> {
+        self.guarded("CanControl", self.stop()).await
+    }
+
+    /// Same as [`try_next`](Self::try_next), seeking if the player
+    /// reports `CanSeek`.
+    pub async fn try_seek(&self, offset: Duration) -> Result<Guarded> {
+        self.guarded("CanSeek", self.seek(offset)).await
+    }
+
+    /// Runs `command` if the player reports `capability`, reading it
+    /// from the player.
+    async fn guarded<C>(&self, capability: &'static str, command: C) -> Result<Guarded>
+    where
+        C: Future<Output = Result<()>>,
+    {
+        let read = |name: &'static str| async move {
+            let capable: bool = self.get_property(name).await?;
+            Result::Ok((capable, false))
+        };
+        guard::run(capability, read, command).await
     }
 
     /// Retrieves track metadata from the `Player`.
@@ -98,8 +520,25 @@ impl<'a> Player<'a> {
     ///
     /// # Errors
     /// May `Err` if there is a failure in getting the metadata.
-    pub async fn get_metadata(&mut self) -> Result<PropMap> {
-        Ok(methods::get_metadata(self).await?)
+    pub async fn get_metadata(&self) -> Result<PropMap> {
+        methods::get_metadata(self).await
+    }
+
+    /// Retrieves the metadata of the active track, or `None` if
+    /// nothing is playing, as decided by [`is_no_track`](crate::is_no_track).
+    ///
+    /// # Errors
+    /// May `Err` if there is a failure in getting the metadata.
+    pub async fn metadata(&self) -> Result<Option<PropMap>> {
+        methods::metadata(self).await
+    }
+
+    /// Retrieves all of the `Player`'s properties at once.
+    ///
+    /// # Errors
+    /// May `Err` if there is a failure in getting the properties.
+    pub async fn get_state(&self) -> Result<PlayerState> {
+        methods::get_state(self).await
     }
 
     /// Retrieves the value of an MPRIS property.
@@ -109,13 +548,14 @@ impl<'a> Player<'a> {
     ///
     /// # Errors
     /// May return an `Err` variant if:
-    /// * An invalid type was provided for the property
-    /// * An invalid property was provided
-    pub async fn get_property<T>(&mut self, property: &str) -> Result<T>
+    /// * An invalid type was provided for the property, as
+    ///   [`Error::TypeMismatch`]
+    /// * An invalid property was provided, as [`Error::UnknownProperty`]
+    pub async fn get_property<T>(&self, property: &str) -> Result<T>
     where
         T: for<'c> Get<'c> + 'static,
     {
-        Ok(methods::get_property(self, property).await?)
+        methods::get_property(self, property).await
     }
 
     /// Sets the value of a writable MPRIS property.
@@ -126,34 +566,184 @@ impl<'a> Player<'a> {
     /// # Errors
     /// May return an `Err` variant if:
     /// * An invalid type was provided for the property
-    /// * An invalid property was provided
-    pub async fn set_property<T>(&mut self, property: &str, value: T) -> Result<()>
+    /// * An invalid property was provided, as [`Error::UnknownProperty`]
+    pub async fn set_property<T>(&self, property: &str, value: T) -> Result<()>
     where
         T: Arg + Append,
     {
-        Ok(methods::set_property(self, property, value).await?)
+        methods::set_property(self, property, value).await
+    }
+
+    /// Retrieves the volume as the player reports it, even above
+    /// the [volume ceiling](Self::set_volume_ceiling).
+    ///
+    /// # Errors
+    /// May `Err` if there is a failure in getting the volume.
+    pub async fn get_volume(&self) -> Result<f64> {
+        methods::get_volume(self).await
+    }
+
+    /// Sets the volume, clamped to between 0.0 and the
+    /// [volume ceiling](Self::set_volume_ceiling).
+    /// Returns the volume that was set.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::InvalidArgument`] if `volume` is NaN.
+    pub async fn set_volume(&self, volume: f64) -> Result<f64> {
+        methods::set_volume(self, volume).await
     }
 
-    /// Seeks the position of the active track.
-    pub async fn seek(&mut self, offset: Duration) -> Result<()> {
-        Ok(methods::seek(self, offset).await?)
+    /// Changes the volume by `delta`, clamped like `set_volume`.
+    /// Returns the volume that was set.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::InvalidArgument`] if `delta` is NaN.
+    pub async fn adjust_volume(&self, delta: f64) -> Result<f64> {
+        methods::adjust_volume(self, delta).await
+    }
+
+    /// Gradually changes the volume to `target` over `duration`,
+    /// clamping every step like `set_volume`.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::InvalidArgument`] if `target` is NaN.
+    pub async fn fade_volume(&self, target: f64, duration: Duration) -> Result<()> {
+        methods::fade_volume(self, target, duration).await
+    }
+
+    /// Retrieves the position of the active track. Positions of any
+    /// integer type are accepted, and negative ones are clamped to
+    /// zero.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::TypeMismatch`] if the position isn't
+    /// an integer.
+    pub async fn get_position(&self) -> Result<Duration> {
+        methods::get_position(self).await
+    }
+
+    /// Seeks the position of the active track. Offsets too large to
+    /// send are saturated, at some 292 thousand years.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::NoActiveTrack`] if there is no active track.
+    pub async fn seek(&self, offset: Duration) -> Result<()> {
+        methods::seek(self, offset).await
     }
 
     /// Same as `seek`, but in reverse.
-    pub async fn seek_reverse(&mut self, offset: Duration) -> Result<()> {
-        Ok(methods::seek_reverse(self, offset).await?)
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::NoActiveTrack`] if there is no active track.
+    pub async fn seek_reverse(&self, offset: Duration) -> Result<()> {
+        methods::seek_reverse(self, offset).await
+    }
+
+    /// Sets the position of the current track. Positions too large
+    /// to send are saturated, at some 292 thousand years.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::NoActiveTrack`] if there is no active track.
+    pub async fn set_position(&self, position: Duration) -> Result<()> {
+        methods::set_position(self, position).await
     }
 
-    /// Sets the position of the current track, by microseconds.
-    pub async fn set_position(&mut self, position: i64) -> Result<()> {
-        Ok(methods::set_position(self, position).await?)
+    /// Same as `set_position`, for players that accept `SetPosition`
+    /// but don't act on it, such as Spotify and some versions of
+    /// Chromium.
+    ///
+    /// The `Position` property is read before `SetPosition`, and for
+    /// up to half a second after. If the player didn't move close to
+    /// `position` by then, it is sent a `Seek` by the difference from
+    /// its current position instead. The returned
+    /// [`PositionStrategy`] tells which of the two calls moved it.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::NoActiveTrack`] if there is no active track.
+    /// May also `Err` if the position can't be read.
+    pub async fn set_position_with_fallback(&self, position: Duration) -> Result<PositionStrategy> {
+        methods::set_position_with_fallback(self, position).await
     }
 
     /// Opens a track by its URI.
     ///
     /// # Errors
     /// May return an `Err` variant if the provided URI is invalid.
-    pub async fn open_uri(&mut self, uri: &str) -> Result<()> {
-        Ok(methods::open_uri(self, uri).await?)
+    pub async fn open_uri(&self, uri: &str) -> Result<()> {
+        methods::open_uri(self, uri).await
+    }
+
+    /// A one-line summary of the player for logs and pickers, such as
+    /// `spotify — Spotify — Playing`, with its name, `Identity` and
+    /// `PlaybackStatus`.
+    ///
+    /// Both are asked for at once, and given a quarter of a second
+    /// rather than the timeout of this `Player`. Whichever isn't
+    /// reported in time is left out, down to the name alone.
+    pub async fn describe(&self) -> String {
+        let proxy = Proxy::new(
+            &self.destination,
+            &self.path,
+            util::IDENTITY_TIMEOUT,
+            &*self.conn,
+        );
+        let (identity, status) = futures::join!(
+            proxy.get::<String>(spec::ROOT.name, "Identity"),
+            proxy.get::<String>(spec::PLAYER.name, "PlaybackStatus"),
+        );
+
+        let mut description = self.name.clone();
+        for part in [identity, status].iter().flatten() {
+            description.push_str(" — ");
+            description.push_str(part);
+        }
+        description
+    }
+
+    /// Reports what the player implements of the MPRIS
+    /// specification: which interfaces, the methods and properties
+    /// of each, and what the specification requires that it lacks.
+    /// Its [`Display`](fmt::Display) rendering is meant for bug
+    /// reports.
+    ///
+    /// The player is introspected, or if it can't be, asked for the
+    /// properties of each interface instead, in which case its
+    /// methods aren't known.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::Player;
+    /// # async fn example() -> pris::Result<()> {
+    /// let conn = pris::get_connection();
+    /// let player = Player::try_new("vlc", &conn).await?;
+    /// println!("{}", player.support_report().await?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::PlayerGone`] if the `Player` has
+    /// closed, or [`Error::Timeout`] if it doesn't answer.
+    pub async fn support_report(&self) -> Result<SupportReport> {
+        support::support_report(self).await
+    }
+}
+
+impl fmt::Debug for Player<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Player")
+            .field("name", &self.name)
+            .field("destination", &&*self.destination)
+            .field("unique_name", &self.unique)
+            .field("timeout", &self.timeout)
+            .field("validated", &self.validated)
+            .finish()
+    }
+}
+
+/// Writes the name of the player, such as `vlc`.
+impl fmt::Display for Player<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
     }
 }
@@ -0,0 +1,195 @@
+use crate::{
+    util::{self, ConnRef},
+    CallbackGuard, EventManager, EventType, Message, Player, Result,
+};
+use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+/// A change to the players in a [`PlayerPool`], reported by the
+/// player's name, in the same form that is passed to
+/// [`Player::try_new`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PoolChange {
+    /// A player started, and was added to the pool.
+    Added(String),
+    /// A player quit, and was removed from the pool.
+    Removed(String),
+    /// A player's name was taken over by a different connection, for
+    /// instance when the player restarted, so handles taken from the
+    /// pool before this reach the old connection.
+    Replaced(String),
+}
+
+#[derive(Default)]
+struct PoolState {
+    /// The players in the pool, by name, with their owner.
+    players: BTreeMap<String, String>,
+    /// The names that changed while the pool was being seeded, which
+    /// the players listed then would be staler than, or `None` once
+    /// it was seeded.
+    seeding: Option<HashSet<String>>,
+    subscribers: Vec<UnboundedSender<PoolChange>>,
+}
+
+impl PoolState {
+    /// Applies a `NameOwnerChanged` signal for the player `name`.
+    fn owner_changed(&mut self, name: String, new_owner: &str) {
+        if let Some(touched) = &mut self.seeding {
+            touched.insert(name.clone());
+        }
+
+        let change = if new_owner.is_empty() {
+            match self.players.remove(&name) {
+                Some(_) => PoolChange::Removed(name),
+                None => return,
+            }
+        } else {
+            match self.players.insert(name.clone(), new_owner.to_string()) {
+                Some(old_owner) if old_owner == new_owner => return,
+                Some(_) => PoolChange::Replaced(name),
+                None => PoolChange::Added(name),
+            }
+        };
+        self.subscribers
+            .retain(|subscriber| subscriber.unbounded_send(change.clone()).is_ok());
+    }
+}
+
+/// The players on the bus, kept current as they come and go by
+/// following `NameOwnerChanged` signals.
+///
+/// Each player is reported by a handle pinned to the connection
+/// owning it, as with [`Player::try_pinned`], so a handle to a
+/// player that restarted doesn't silently reach the new instance.
+/// Players are removed as soon as they quit, and
+/// [`changes`](Self::changes) reports every player added, removed or
+/// replaced, so that state kept for each can be rebuilt.
+///
+/// Clones share the same players, and can be handed to other tasks.
+/// The pool stops following the bus once the last of them is
+/// dropped.
+///
+/// # Example
+/// ```no_run
+/// # use futures::StreamExt;
+/// # use pris::{EventManager, PlayerPool, PoolChange};
+/// # async fn example(manager: &EventManager<'_>) -> Result<(), Box<dyn std::error::Error>> {
+/// let pool = PlayerPool::new(manager).await?;
+/// let mut changes = pool.changes();
+/// for player in pool.iter() {
+///     println!("Showing {}", player.name);
+/// }
+/// while let Some(change) = changes.next().await {
+///     match change {
+///         PoolChange::Added(name) => println!("Showing {}", name),
+///         PoolChange::Removed(name) => println!("Hiding {}", name),
+///         PoolChange::Replaced(name) => println!("Resetting {}", name),
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct PlayerPool<'a> {
+    conn: ConnRef<'a>,
+    state: Arc<Mutex<PoolState>>,
+    _lifecycle: Arc<CallbackGuard<'a>>,
+}
+
+impl<'a> PlayerPool<'a> {
+    /// Creates a pool of the players on the bus of `manager`, which
+    /// follows them from then on.
+    ///
+    /// # Errors
+    /// Returns an `Err` if there is a failure in adding the match
+    /// rule, or in listing the players on the bus.
+    pub async fn new(manager: &EventManager<'a>) -> Result<PlayerPool<'a>> {
+        let state = Arc::new(Mutex::new(PoolState {
+            seeding: Some(HashSet::new()),
+            ..PoolState::default()
+        }));
+
+        // Follow the bus before listing, so no player goes unnoticed
+        let followed = state.clone();
+        let lifecycle = manager
+            .add_callback(EventType::PlayerLifecycle, move |msg: Message| {
+                if let Ok((name, _, new_owner)) = msg.read3::<&str, &str, &str>() {
+                    if let Some(name) = name.strip_prefix(util::MPRIS_PREFIX) {
+                        let mut state = followed.lock().unwrap();
+                        state.owner_changed(name.to_string(), new_owner);
+                    }
+                }
+                true
+            })
+            .await?;
+
+        let conn = manager.conn();
+        let listed = util::list_owners(&conn).await?;
+        {
+            let mut state = state.lock().unwrap();
+            let touched = state.seeding.take().unwrap_or_default();
+            for (name, owner) in listed {
+                if !touched.contains(&name) {
+                    state.players.insert(name, owner);
+                }
+            }
+        }
+
+        Ok(PlayerPool {
+            conn,
+            state,
+            _lifecycle: Arc::new(lifecycle),
+        })
+    }
+
+    /// Handles to the players in the pool, ordered by name.
+    pub fn iter(&self) -> std::vec::IntoIter<Player<'a>> {
+        self.players().into_iter()
+    }
+
+    /// Same as `iter`, collected.
+    pub fn players(&self) -> Vec<Player<'a>> {
+        let state = self.state.lock().unwrap();
+        state
+            .players
+            .iter()
+            .filter_map(|(name, owner)| {
+                Player::with_owner(name.clone(), Some(owner.clone()), self.conn.clone()).ok()
+            })
+            .collect()
+    }
+
+    /// A handle to the player `name`, if it is in the pool.
+    pub fn get(&self, name: &str) -> Option<Player<'a>> {
+        let owner = self.state.lock().unwrap().players.get(name).cloned()?;
+        Player::with_owner(name.to_string(), Some(owner), self.conn.clone()).ok()
+    }
+
+    /// The names of the players in the pool, ordered by name.
+    pub fn names(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state.players.keys().cloned().collect()
+    }
+
+    /// How many players are in the pool.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().players.len()
+    }
+
+    /// Whether the pool has no players.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a [`Stream`](futures::Stream) of the changes made to
+    /// the pool from now on, in the order they were made. The
+    /// players already in it are left to [`iter`](Self::iter).
+    pub fn changes(&self) -> UnboundedReceiver<PoolChange> {
+        let (sender, receiver) = mpsc::unbounded();
+        self.state.lock().unwrap().subscribers.push(sender);
+        receiver
+    }
+}
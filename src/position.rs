@@ -0,0 +1,298 @@
+use crate::{
+    runtime, util, ChangedProperties, Event, LifecycleEvent, Micros, PlaybackClock, PlaybackStatus,
+};
+use dbus::arg::{PropMap, RefArg, Variant};
+use futures::{
+    future,
+    stream::{self, BoxStream},
+    Stream, StreamExt,
+};
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+/// How [`dedup_positions`] recognizes two reports of the same seek.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PositionDedupOptions {
+    /// How far apart two positions may be while still
+    /// describing the same seek.
+    pub tolerance: Duration,
+    /// How long after a report a matching one is considered
+    /// a duplicate.
+    pub window: Duration,
+}
+
+impl Default for PositionDedupOptions {
+    fn default() -> Self {
+        PositionDedupOptions {
+            tolerance: Duration::from_millis(250),
+            window: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Where a [`PositionChange`] was reported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PositionSource {
+    /// A `Seeked` signal.
+    Seeked,
+    /// A `PropertiesChanged` signal carrying `Position`.
+    PropertiesChanged,
+}
+
+/// A change in a player's playback position.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PositionChange {
+    /// The name of the player, in the same form that is
+    /// passed to [`Player::try_new`](crate::Player::try_new).
+    pub player: String,
+    pub position: Duration,
+    pub source: PositionSource,
+}
+
+impl PositionChange {
+    /// Extracts a position change from `event`, if it carries one.
+    pub fn from_event(event: &Event) -> Option<PositionChange> {
+        match event {
+            Event::Seeked(seeked) => Some(PositionChange {
+                player: seeked.player.clone(),
+                position: seeked.position,
+                source: PositionSource::Seeked,
+            }),
+            Event::PropertiesChanged(changed) if changed.properties.is_player_interface() => {
+                let position = changed.properties.other.get("Position")?;
+                Some(PositionChange {
+                    player: changed.player.clone(),
+                    position: util::micros(&*position.0)?,
+                    source: PositionSource::PropertiesChanged,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A stream of de-duplicated position changes, created with
+/// [`dedup_positions`] or
+/// [`EventManager::position_changes`](crate::EventManager::position_changes).
+pub struct PositionChanges<'a>(BoxStream<'a, PositionChange>);
+
+impl Stream for PositionChanges<'_> {
+    type Item = PositionChange;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<PositionChange>> {
+        self.0.poll_next_unpin(cx)
+    }
+}
+
+/// Turns `events` into a stream of position changes, merging
+/// `Seeked` signals with `Position` property changes.
+///
+/// A change is dropped if the same player reported a position
+/// within `options.tolerance` of it less than `options.window`
+/// earlier, since players often report a single seek both ways.
+/// Events without a position are ignored.
+pub fn dedup_positions<'a, S>(events: S, options: PositionDedupOptions) -> PositionChanges<'a>
+where
+    S: Stream<Item = Event> + Send + 'a,
+{
+    let mut last: HashMap<String, (Duration, Instant)> = HashMap::new();
+
+    PositionChanges(
+        events
+            .filter_map(move |event| {
+                let change = PositionChange::from_event(&event).filter(|change| {
+                    let now = Instant::now();
+                    let duplicate = last.get(&change.player).is_some_and(|(position, at)| {
+                        position.abs_diff(change.position) <= options.tolerance
+                            && now - *at <= options.window
+                    });
+                    last.insert(change.player.clone(), (change.position, now));
+                    !duplicate
+                });
+                future::ready(change)
+            })
+            .boxed(),
+    )
+}
+
+/// A stream of estimated playback positions, created with
+/// [`position_ticks`] or
+/// [`EventManager::position_ticks`](crate::EventManager::position_ticks).
+pub struct PositionTicks<'a>(BoxStream<'a, Duration>);
+
+impl Stream for PositionTicks<'_> {
+    type Item = Duration;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Duration>> {
+        self.0.poll_next_unpin(cx)
+    }
+}
+
+/// A clock kept up to date from a single player's events.
+pub(crate) struct EventClock {
+    pub(crate) clock: PlaybackClock,
+    track: Option<String>,
+}
+
+impl EventClock {
+    /// Wraps `clock`, which is playing `track` if known.
+    pub(crate) fn new(clock: PlaybackClock, track: Option<String>) -> EventClock {
+        EventClock { clock, track }
+    }
+
+    /// Updates the clock from `event`, returning whether it moved.
+    pub(crate) fn apply(&mut self, event: &Event, at: std::time::Instant) -> bool {
+        let changes = match event {
+            Event::Seeked(seeked) => {
+                self.clock.set_position(seeked.position, at);
+                return true;
+            }
+            Event::PropertiesChanged(changed) if changed.properties.is_player_interface() => {
+                &changed.properties
+            }
+            _ => return false,
+        };
+
+        let mut moved = false;
+        if let Some(status) = changes
+            .playback_status
+            .filter(|s| *s != self.clock.status())
+        {
+            self.clock.set_status(status, at);
+            moved = true;
+        }
+        if let Some(rate) = changes.rate.filter(|r| *r != self.clock.rate()) {
+            self.clock.set_rate(rate, at);
+            moved = true;
+        }
+        let position = changes
+            .other
+            .get("Position")
+            .and_then(|v| util::micros(&*v.0));
+        if let Some(metadata) = &changes.metadata {
+            self.clock.set_length(util::track_length(metadata));
+            let track = util::track_identity(metadata);
+            // Without a previous track, this may well be the same one
+            if track != self.track && self.track.is_some() && position.is_none() {
+                self.clock.set_position(Duration::ZERO, at);
+                moved = true;
+            }
+            self.track = track;
+        }
+        if let Some(position) = position {
+            self.clock.set_position(position, at);
+            moved = true;
+        }
+
+        moved
+    }
+}
+
+struct Ticker<'a> {
+    events: BoxStream<'a, Event>,
+    tracked: EventClock,
+    interval: Duration,
+    next: Option<Instant>,
+}
+
+impl Ticker<'_> {
+    /// Schedules the next tick after one emitted at `now`, if playing.
+    fn schedule(&mut self, now: Instant) {
+        self.next = match self.tracked.clock.status() {
+            PlaybackStatus::Playing => Some(now + self.interval),
+            _ => None,
+        };
+    }
+
+    fn position_at(&self, now: Instant) -> Duration {
+        self.tracked.clock.position_at(now.into_std())
+    }
+}
+
+/// Turns the events of a single player into a stream of its
+/// estimated position, starting from `clock`.
+///
+/// The current position is emitted straight away, then every
+/// `interval` while playing. Seeks, changes of playback status,
+/// rate or track, and reported `Position` values move the clock
+/// and are emitted immediately; otherwise nothing is emitted while
+/// paused or stopped. The stream ends with `events`, or once the
+/// player vanishes.
+///
+/// Time is measured with tokio's clock, so that the estimate follows
+/// [paused time](tokio::time::pause) in tests.
+pub fn position_ticks<'a, S>(
+    events: S,
+    clock: PlaybackClock,
+    interval: Duration,
+) -> PositionTicks<'a>
+where
+    S: Stream<Item = Event> + Send + 'a,
+{
+    let mut ticker = Ticker {
+        events: events.boxed(),
+        tracked: EventClock::new(clock, None),
+        interval,
+        next: None,
+    };
+    let now = Instant::now();
+    ticker.schedule(now);
+    let first = ticker.position_at(now);
+
+    let rest = stream::unfold(ticker, |mut ticker| async move {
+        loop {
+            let next = ticker.next;
+            tokio::select! {
+                event = ticker.events.next() => {
+                    let event = event?;
+                    if let Event::PlayerLifecycle(LifecycleEvent::Vanished { .. }) = event {
+                        return None;
+                    }
+                    let now = Instant::now();
+                    if ticker.tracked.apply(&event, now.into_std()) {
+                        ticker.schedule(now);
+                        return Some((ticker.position_at(now), ticker));
+                    }
+                }
+                _ = runtime::sleep_until(next.unwrap_or_else(Instant::now)), if next.is_some() => {
+                    let now = Instant::now();
+                    ticker.next = next.map(|next| next + ticker.interval);
+                    return Some((ticker.position_at(now), ticker));
+                }
+            }
+        }
+    });
+
+    PositionTicks(stream::iter([first]).chain(rest).boxed())
+}
+
+/// Builds the changes a player reported in a fresh fetch of its
+/// position, playback status and rate.
+pub(crate) fn resync_changes(
+    position: Option<Duration>,
+    status: Option<PlaybackStatus>,
+    rate: Option<f64>,
+) -> ChangedProperties {
+    let mut properties = PropMap::new();
+    let mut insert = |name: &str, value: Box<dyn RefArg>| {
+        properties.insert(name.to_string(), Variant(value));
+    };
+    if let Some(position) = position {
+        insert("Position", Box::new(Micros::from_duration(position).get()));
+    }
+    if let Some(status) = status {
+        insert("PlaybackStatus", Box::new(status.as_str().to_string()));
+    }
+    if let Some(rate) = rate {
+        insert("Rate", Box::new(rate));
+    }
+
+    ChangedProperties::from_parts(PLAYER_INTERFACE.to_string(), properties, Vec::new())
+}
@@ -0,0 +1,356 @@
+use crate::{debug, util, Error, LoopStatus, PlaybackStatus, Player, Result, Value};
+use dbus::{
+    arg::{cast, PropMap, RefArg, Variant},
+    message::Message,
+};
+
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+/// The parsed payload of a `PropertiesChanged` signal.
+///
+/// Properties of the Player interface that are commonly
+/// listened for are extracted into typed fields; anything
+/// else (or anything of an unexpected type) is kept in
+/// [`other`](ChangedProperties::other).
+#[derive(Debug, Default)]
+pub struct ChangedProperties {
+    /// The interface whose properties changed. Players also emit
+    /// this signal for the root and TrackList interfaces, see
+    /// [`is_player_interface`](ChangedProperties::is_player_interface).
+    pub interface: String,
+    pub playback_status: Option<PlaybackStatus>,
+    pub metadata: Option<PropMap>,
+    pub volume: Option<f64>,
+    pub loop_status: Option<LoopStatus>,
+    pub shuffle: Option<bool>,
+    pub rate: Option<f64>,
+    pub can_go_next: Option<bool>,
+    pub can_go_previous: Option<bool>,
+    pub can_play: Option<bool>,
+    pub can_pause: Option<bool>,
+    pub can_seek: Option<bool>,
+    pub can_control: Option<bool>,
+    /// Every other changed property, keyed by name.
+    pub other: PropMap,
+    /// Properties that changed without their new value being sent.
+    /// Consumers should re-fetch these, for instance with
+    /// [`fill_invalidated`](ChangedProperties::fill_invalidated).
+    pub invalidated: Vec<String>,
+}
+
+impl Clone for ChangedProperties {
+    fn clone(&self) -> Self {
+        ChangedProperties {
+            interface: self.interface.clone(),
+            metadata: self.metadata.as_ref().map(util::clone_prop_map),
+            other: util::clone_prop_map(&self.other),
+            invalidated: self.invalidated.clone(),
+            ..*self
+        }
+    }
+}
+
+impl ChangedProperties {
+    /// Parses the payload of a `PropertiesChanged` signal.
+    ///
+    /// Values wrapped in more than one variant are unwrapped.
+    /// Signals for interfaces other than the Player interface
+    /// are parsed as well; everything then ends up in `other`.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the message is not a
+    /// `PropertiesChanged` signal, or its arguments are malformed.
+    pub fn parse(msg: &Message) -> Result<ChangedProperties> {
+        if msg.member().as_deref() != Some("PropertiesChanged") {
+            return Err(Error::Parse(
+                "The provided message was not a PropertiesChanged signal.".into(),
+            ));
+        }
+
+        let malformed =
+            |e| Error::Parse(format!("The PropertiesChanged signal is malformed: {}", e));
+        let mut args = msg.iter_init();
+        let interface: String = args.read().map_err(malformed)?;
+        let changed: PropMap = args.read().map_err(malformed)?;
+        // Should always be present, but an absent list means the same as an empty one
+        let invalidated: Vec<String> = args.read().unwrap_or_default();
+
+        Ok(ChangedProperties::from_parts(
+            interface,
+            changed,
+            invalidated,
+        ))
+    }
+
+    /// Sorts `changed` into typed fields, the same way `parse` does.
+    pub(crate) fn from_parts(
+        interface: String,
+        changed: PropMap,
+        invalidated: Vec<String>,
+    ) -> ChangedProperties {
+        let mut properties = ChangedProperties {
+            interface,
+            invalidated,
+            ..Default::default()
+        };
+
+        for (name, value) in changed {
+            if !properties.is_player_interface() || !properties.set_typed(&name, &*value.0) {
+                properties.other.insert(name, value);
+            }
+        }
+
+        properties
+    }
+
+    /// Re-fetches every invalidated property from `player`, filling
+    /// in its value as though it had been sent with the signal.
+    ///
+    /// Properties that were fetched successfully are removed
+    /// from [`invalidated`](ChangedProperties::invalidated).
+    ///
+    /// # Errors
+    /// Returns the first error encountered while fetching; properties
+    /// that had not been fetched yet remain listed as invalidated.
+    pub async fn fill_invalidated(&mut self, player: &Player<'_>) -> Result<()> {
+        let proxy = player.get_proxy();
+
+        while let Some(name) = self.invalidated.first().cloned() {
+            let (value,): (Variant<Box<dyn RefArg>>,) = debug::method_call(
+                player.debug_sink(),
+                &proxy,
+                "org.freedesktop.DBus.Properties",
+                "Get",
+                (self.interface.as_str(), name.as_str()),
+            )
+            .await?;
+            if !self.is_player_interface() || !self.set_typed(&name, &*value.0) {
+                self.other.insert(name, value);
+            }
+            self.invalidated.remove(0);
+        }
+
+        Ok(())
+    }
+
+    /// Merges `later` changes into these, as though both had been
+    /// sent in a single signal. Values from `later` win.
+    pub fn merge(&mut self, later: ChangedProperties) {
+        for name in later.invalidated {
+            self.clear(&name);
+            if !self.invalidated.contains(&name) {
+                self.invalidated.push(name);
+            }
+        }
+
+        let mut sent: Vec<&str> = Vec::new();
+        macro_rules! take_later {
+            ($($field:ident: $name:literal),* $(,)?) => {
+                $(
+                    if later.$field.is_some() {
+                        self.$field = later.$field;
+                        sent.push($name);
+                    }
+                )*
+            };
+        }
+        take_later!(
+            playback_status: "PlaybackStatus",
+            metadata: "Metadata",
+            volume: "Volume",
+            loop_status: "LoopStatus",
+            shuffle: "Shuffle",
+            rate: "Rate",
+            can_go_next: "CanGoNext",
+            can_go_previous: "CanGoPrevious",
+            can_play: "CanPlay",
+            can_pause: "CanPause",
+            can_seek: "CanSeek",
+            can_control: "CanControl",
+        );
+        for (name, value) in later.other {
+            self.invalidated.retain(|n| *n != name);
+            self.other.insert(name, value);
+        }
+        self.invalidated.retain(|n| !sent.contains(&n.as_str()));
+    }
+
+    /// Forgets any value sent for the property `name`.
+    fn clear(&mut self, name: &str) {
+        self.other.remove(name);
+        match name {
+            "PlaybackStatus" => self.playback_status = None,
+            "Metadata" => self.metadata = None,
+            "Volume" => self.volume = None,
+            "LoopStatus" => self.loop_status = None,
+            "Shuffle" => self.shuffle = None,
+            "Rate" => self.rate = None,
+            "CanGoNext" => self.can_go_next = None,
+            "CanGoPrevious" => self.can_go_previous = None,
+            "CanPlay" => self.can_play = None,
+            "CanPause" => self.can_pause = None,
+            "CanSeek" => self.can_seek = None,
+            "CanControl" => self.can_control = None,
+            _ => {}
+        }
+    }
+
+    /// The new value of the property `name`, if it changed.
+    ///
+    /// Properties that were only invalidated come back as
+    /// [`PropertyValue::Invalidated`].
+    pub fn get(&self, name: &str) -> Option<PropertyValue> {
+        let typed = match name {
+            "PlaybackStatus" => self.playback_status.map(PropertyValue::PlaybackStatus),
+            "LoopStatus" => self.loop_status.map(PropertyValue::LoopStatus),
+            "Metadata" => self
+                .metadata
+                .as_ref()
+                .map(|m| PropertyValue::Metadata(util::clone_prop_map(m))),
+            "Volume" => self.volume.map(PropertyValue::Double),
+            "Rate" => self.rate.map(PropertyValue::Double),
+            "Shuffle" => self.shuffle.map(PropertyValue::Bool),
+            "CanGoNext" => self.can_go_next.map(PropertyValue::Bool),
+            "CanGoPrevious" => self.can_go_previous.map(PropertyValue::Bool),
+            "CanPlay" => self.can_play.map(PropertyValue::Bool),
+            "CanPause" => self.can_pause.map(PropertyValue::Bool),
+            "CanSeek" => self.can_seek.map(PropertyValue::Bool),
+            "CanControl" => self.can_control.map(PropertyValue::Bool),
+            _ => None,
+        };
+
+        typed
+            .or_else(|| {
+                self.other
+                    .get(name)
+                    .map(|v| PropertyValue::Other(Variant(v.0.box_clone())))
+            })
+            .or_else(|| {
+                self.invalidated
+                    .iter()
+                    .any(|n| n == name)
+                    .then_some(PropertyValue::Invalidated)
+            })
+    }
+
+    /// Whether the player has a track after these changes, or `None`
+    /// if its `Metadata` didn't change. Metadata that is empty or only
+    /// holds the `NoTrack` id counts as no track, see
+    /// [`is_no_track`](crate::is_no_track).
+    pub fn has_track(&self) -> Option<bool> {
+        self.metadata.as_ref().map(|m| !util::is_no_track(m))
+    }
+
+    /// Whether these changes are for the `org.mpris.MediaPlayer2.Player`
+    /// interface.
+    pub fn is_player_interface(&self) -> bool {
+        self.interface == PLAYER_INTERFACE
+    }
+
+    /// Stores a value into its typed field, returning whether
+    /// the property was recognized and of the expected type.
+    fn set_typed(&mut self, name: &str, value: &(dyn RefArg + 'static)) -> bool {
+        let value = util::unwrap_variant(value);
+        let flag = || cast::<bool>(value).copied();
+
+        match name {
+            "PlaybackStatus" => {
+                self.playback_status = value.as_str().and_then(|s| s.parse().ok());
+                self.playback_status.is_some()
+            }
+            "LoopStatus" => {
+                self.loop_status = value.as_str().and_then(|s| s.parse().ok());
+                self.loop_status.is_some()
+            }
+            "Metadata" => {
+                self.metadata = util::as_prop_map(value);
+                self.metadata.is_some()
+            }
+            "Volume" => {
+                self.volume = value.as_f64();
+                self.volume.is_some()
+            }
+            "Rate" => {
+                self.rate = value.as_f64();
+                self.rate.is_some()
+            }
+            "Shuffle" => {
+                self.shuffle = flag();
+                self.shuffle.is_some()
+            }
+            "CanGoNext" => {
+                self.can_go_next = flag();
+                self.can_go_next.is_some()
+            }
+            "CanGoPrevious" => {
+                self.can_go_previous = flag();
+                self.can_go_previous.is_some()
+            }
+            "CanPlay" => {
+                self.can_play = flag();
+                self.can_play.is_some()
+            }
+            "CanPause" => {
+                self.can_pause = flag();
+                self.can_pause.is_some()
+            }
+            "CanSeek" => {
+                self.can_seek = flag();
+                self.can_seek.is_some()
+            }
+            "CanControl" => {
+                self.can_control = flag();
+                self.can_control.is_some()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The new value of a single changed property.
+#[derive(Debug)]
+pub enum PropertyValue {
+    PlaybackStatus(PlaybackStatus),
+    LoopStatus(LoopStatus),
+    Metadata(PropMap),
+    /// `Volume` or `Rate`.
+    Double(f64),
+    /// `Shuffle`, or one of the `Can*` properties.
+    Bool(bool),
+    /// A property without a typed representation, or one of an
+    /// unexpected type.
+    Other(Variant<Box<dyn RefArg>>),
+    /// The property changed, but its new value wasn't sent
+    /// and has to be re-fetched.
+    Invalidated,
+}
+
+impl PropertyValue {
+    /// The new value, copied out of its D-Bus form, or `None` if it
+    /// was [`Invalidated`](PropertyValue::Invalidated).
+    pub fn value(&self) -> Option<Value> {
+        Some(match self {
+            PropertyValue::PlaybackStatus(s) => Value::Str(s.to_string()),
+            PropertyValue::LoopStatus(s) => Value::Str(s.to_string()),
+            PropertyValue::Metadata(m) => Value::Dict(Value::from_dbus_map(m)),
+            PropertyValue::Double(d) => Value::Double(*d),
+            PropertyValue::Bool(b) => Value::Bool(*b),
+            PropertyValue::Other(v) => Value::from_dbus(&*v.0),
+            PropertyValue::Invalidated => return None,
+        })
+    }
+}
+
+impl Clone for PropertyValue {
+    fn clone(&self) -> Self {
+        match self {
+            PropertyValue::PlaybackStatus(s) => PropertyValue::PlaybackStatus(*s),
+            PropertyValue::LoopStatus(s) => PropertyValue::LoopStatus(*s),
+            PropertyValue::Metadata(m) => PropertyValue::Metadata(util::clone_prop_map(m)),
+            PropertyValue::Double(d) => PropertyValue::Double(*d),
+            PropertyValue::Bool(b) => PropertyValue::Bool(*b),
+            PropertyValue::Other(v) => PropertyValue::Other(Variant(v.0.box_clone())),
+            PropertyValue::Invalidated => PropertyValue::Invalidated,
+        }
+    }
+}
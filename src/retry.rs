@@ -0,0 +1,111 @@
+use crate::{runtime, trace, Error, Result};
+use std::{future::Future, time::Duration};
+
+/// The D-Bus errors that usually go away when the call is made
+/// again shortly after.
+const TRANSIENT: &[&str] = &["org.freedesktop.DBus.Error.LimitsExceeded"];
+
+/// When and how often the calls of a [`Player`](crate::Player) are
+/// retried after failing, set with
+/// [`Player::set_retry_policy`](crate::Player::set_retry_policy).
+///
+/// Calls that aren't safe to repeat, such as `Next` or `PlayPause`,
+/// are only retried if [`non_idempotent`](Self::non_idempotent) is
+/// set, since a call that timed out may still have been carried out.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// The most times a call is made, including the first.
+    pub max_attempts: u32,
+    /// The delay before the first retry.
+    pub base_delay: Duration,
+    /// What the delay is multiplied by for each retry after the first.
+    pub backoff: f64,
+    /// Whether a failure is worth retrying. Defaults to
+    /// [`RetryPolicy::transient`].
+    pub retryable: fn(&Error) -> bool,
+    /// Whether calls that aren't safe to repeat are retried too.
+    pub non_idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            backoff: 2.0,
+            retryable: RetryPolicy::transient,
+            non_idempotent: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether `error` is one that often goes away by itself: the
+    /// player not answering in time, having briefly left the bus
+    /// while its name changes hands, or the bus being overloaded.
+    pub fn transient(error: &Error) -> bool {
+        match error {
+            Error::Timeout { .. } | Error::PlayerGone { .. } => true,
+            Error::DBus(e) => e.name().is_some_and(|name| TRANSIENT.contains(&name)),
+            _ => false,
+        }
+    }
+
+    /// The delay before the retry following the failed attempt
+    /// numbered `attempt`, counting from 1.
+    fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let factor = self.backoff.max(0.0).powi(exponent);
+        Duration::try_from_secs_f64(self.base_delay.as_secs_f64() * factor).unwrap_or(Duration::MAX)
+    }
+}
+
+/// Starts the call with `call` and classifies its failures with
+/// `classify`, retrying it as `policy` allows if it is `idempotent`.
+///
+/// If the call was made more than once, the last failure is
+/// wrapped in [`Error::Retried`].
+pub(crate) async fn run<T, F, Fut>(
+    policy: Option<RetryPolicy>,
+    idempotent: bool,
+    mut call: F,
+    classify: impl Fn(dbus::Error) -> Error,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = std::result::Result<T, dbus::Error>>,
+{
+    let policy = match policy {
+        Some(policy) if idempotent || policy.non_idempotent => policy,
+        _ => return call().await.map_err(classify),
+    };
+
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let error = match call().await {
+            Ok(value) => return Ok(value),
+            Err(e) => classify(e),
+        };
+
+        if attempts >= policy.max_attempts || !(policy.retryable)(&error) {
+            return Err(match attempts {
+                1 => error,
+                _ => Error::Retried {
+                    attempts,
+                    source: Box::new(error),
+                },
+            });
+        }
+        let delay = policy.delay(attempts);
+        trace::event!(
+            debug,
+            attempt = attempts,
+            delay_ms = delay.as_millis() as u64,
+            error_kind = trace::error_kind(&error),
+            error = %error,
+            "retrying call"
+        );
+        runtime::sleep(delay).await;
+    }
+}
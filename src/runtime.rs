@@ -0,0 +1,238 @@
+//! The few things pris needs from an async runtime: timers, tasks
+//! and a task driving the connection. These are tokio's, unless the
+//! `smol` feature is enabled, in which case they are smol's, which
+//! run on any executor, async-std's included.
+use dbus::{
+    channel::{BusType, Channel},
+    nonblock::SyncConnection,
+};
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
+#[cfg(feature = "events")]
+use tokio::time::Instant;
+
+/// A [`timeout`] that ran out before its future completed.
+#[derive(Debug)]
+pub(crate) struct Elapsed;
+
+/// Waits until `duration` has passed.
+pub(crate) async fn sleep(duration: Duration) {
+    #[cfg(not(feature = "smol"))]
+    tokio::time::sleep(duration).await;
+    #[cfg(feature = "smol")]
+    smol::Timer::after(duration).await;
+}
+
+/// Waits until `deadline`.
+#[cfg(feature = "events")]
+pub(crate) async fn sleep_until(deadline: Instant) {
+    #[cfg(not(feature = "smol"))]
+    tokio::time::sleep_until(deadline).await;
+    #[cfg(feature = "smol")]
+    smol::Timer::at(deadline.into_std()).await;
+}
+
+/// Runs `future`, giving up on it if it takes longer than `limit`.
+#[cfg(not(feature = "smol"))]
+pub(crate) async fn timeout<F: Future>(limit: Duration, future: F) -> Result<F::Output, Elapsed> {
+    tokio::time::timeout(limit, future)
+        .await
+        .map_err(|_| Elapsed)
+}
+
+/// Runs `future`, giving up on it if it takes longer than `limit`.
+#[cfg(feature = "smol")]
+pub(crate) async fn timeout<F: Future>(limit: Duration, future: F) -> Result<F::Output, Elapsed> {
+    use futures::future::{select, Either};
+
+    futures::pin_mut!(future);
+    match select(future, smol::Timer::after(limit)).await {
+        Either::Left((output, _)) => Ok(output),
+        Either::Right(_) => Err(Elapsed),
+    }
+}
+
+/// Starts running `future` on a task of its own straight away.
+///
+/// The returned future resolves to its output, or to `None` if it
+/// panicked. Dropping it leaves the task running.
+#[cfg(not(feature = "smol"))]
+pub(crate) fn spawn<F>(future: F) -> impl Future<Output = Option<F::Output>>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let task = tokio::spawn(future);
+    async move { task.await.ok() }
+}
+
+/// Starts running `future` on a task of its own straight away.
+///
+/// The returned future resolves to its output, or to `None` if it
+/// panicked. Dropping it leaves the task running.
+#[cfg(feature = "smol")]
+pub(crate) fn spawn<F>(future: F) -> impl Future<Output = Option<F::Output>>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    use futures::{channel::oneshot, FutureExt};
+    use std::panic::AssertUnwindSafe;
+
+    // smol cancels tasks whose handle is dropped, so the output is
+    // handed over separately
+    let (sender, receiver) = oneshot::channel();
+    smol::spawn(async move {
+        if let Ok(output) = AssertUnwindSafe(future).catch_unwind().await {
+            let _ = sender.send(output);
+        }
+    })
+    .detach();
+    async move { receiver.await.ok() }
+}
+
+/// Starts running `future` on a task of its own, which is left to
+/// run to completion.
+pub(crate) fn detach<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    #[cfg(not(feature = "smol"))]
+    tokio::spawn(future);
+    #[cfg(feature = "smol")]
+    smol::spawn(future).detach();
+}
+
+/// Runs `f` on a thread where it may block, resolving to what it
+/// returns, or to `None` if it panicked.
+#[cfg(all(feature = "events", not(feature = "smol")))]
+pub(crate) async fn spawn_blocking<F, R>(f: F) -> Option<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await.ok()
+}
+
+/// Runs `f` on a thread where it may block, resolving to what it
+/// returns, or to `None` if it panicked.
+#[cfg(all(feature = "events", feature = "smol"))]
+pub(crate) async fn spawn_blocking<F, R>(f: F) -> Option<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    smol::unblock(move || catch_unwind(AssertUnwindSafe(f)).ok()).await
+}
+
+/// The future handling a connection's traffic, which resolves to
+/// the error the connection was lost to.
+pub(crate) type Driver = Pin<Box<dyn Future<Output = dbus::Error> + Send>>;
+
+/// Sets up a connection on the registered `channel`, returning it
+/// along with the future that has to be run for it to work.
+#[cfg(not(feature = "smol"))]
+pub(crate) fn driven(channel: Channel) -> Result<(Arc<SyncConnection>, Driver), dbus::Error> {
+    let (resource, conn) = dbus_tokio::connection::from_channel(channel)?;
+    let driver: Driver = Box::pin(async move {
+        let err = resource.await;
+        dbus::Error::new_failed(&err.to_string())
+    });
+
+    Ok((conn, driver))
+}
+
+/// Sets up a connection on the registered `channel`, returning it
+/// along with the future that has to be run for it to work.
+#[cfg(feature = "smol")]
+pub(crate) fn driven(mut channel: Channel) -> Result<(Arc<SyncConnection>, Driver), dbus::Error> {
+    use dbus::nonblock::NonblockReply;
+    use tokio::sync::Notify;
+
+    channel.set_watch_enabled(true);
+
+    // Messages queued from other tasks have to wake the driving task
+    let queued = Arc::new(Notify::new());
+    let mut conn = SyncConnection::from(channel);
+    conn.set_timeout_maker(Some(timer));
+    let waker = queued.clone();
+    conn.set_waker(Some(Box::new(move || {
+        waker.notify_one();
+        Ok(())
+    })));
+
+    let conn = Arc::new(conn);
+    let driven = conn.clone();
+    let driver: Driver = Box::pin(async move { drive(&driven, &queued).await });
+
+    Ok((conn, driver))
+}
+
+/// Connects to the session bus, and starts a task handling the
+/// connection's traffic.
+///
+/// # Panics
+/// Panics if the connection can't be established, and from the task
+/// handling it if it is lost.
+pub(crate) fn connect_session() -> Arc<SyncConnection> {
+    let channel = Channel::get_private(BusType::Session).unwrap();
+    let (conn, driver) = driven(channel).unwrap();
+    detach(async move {
+        let err = driver.await;
+        panic!("Lost connection to D-Bus: {}", err);
+    });
+
+    conn
+}
+
+/// Resolves at `deadline`, for method calls to time out with.
+#[cfg(feature = "smol")]
+fn timer(deadline: std::time::Instant) -> Pin<Box<dyn Future<Output = ()> + Send + Sync>> {
+    Box::pin(async move {
+        smol::Timer::at(deadline).await;
+    })
+}
+
+/// The connection's file descriptor, which stays owned by libdbus.
+#[cfg(feature = "smol")]
+struct WatchFd(std::os::unix::io::RawFd);
+
+#[cfg(feature = "smol")]
+impl std::os::unix::io::AsFd for WatchFd {
+    fn as_fd(&self) -> std::os::unix::io::BorrowedFd<'_> {
+        // The descriptor lives as long as the connection, which
+        // outlives the task driving it
+        unsafe { std::os::unix::io::BorrowedFd::borrow_raw(self.0) }
+    }
+}
+
+/// Reads, writes and dispatches the traffic of `conn` until it
+/// fails, waiting in between for its socket to be ready or for
+/// `queued` to be notified of an outgoing message.
+#[cfg(feature = "smol")]
+async fn drive(conn: &SyncConnection, queued: &tokio::sync::Notify) -> dbus::Error {
+    use dbus::nonblock::Process;
+
+    let channel: &Channel = conn.as_ref();
+    let socket = match smol::Async::new(WatchFd(channel.watch().fd)) {
+        Ok(socket) => socket,
+        Err(e) => return dbus::Error::new_failed(&e.to_string()),
+    };
+
+    loop {
+        if channel.read_write(Some(Duration::ZERO)).is_err() {
+            return dbus::Error::new_failed("Read/write failed");
+        }
+        conn.process_all();
+
+        let result = tokio::select! {
+            result = socket.readable() => result,
+            result = socket.writable(), if channel.has_messages_to_send() => result,
+            _ = queued.notified() => Ok(()),
+        };
+        if let Err(e) = result {
+            return dbus::Error::new_failed(&e.to_string());
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use crate::{runtime, spec, util, IntoPlayerName, Player, PlayerFinder, Result};
+use dbus::nonblock::stdintf::org_freedesktop_dbus::Properties;
+
+/// The player a user last chose, to keep preferring it across runs.
+///
+/// It records the player's well-known bus name and `Identity`, and
+/// can be serialized with the `serde` feature, to be stored wherever
+/// the application keeps its state. [`resolve`](Self::resolve) then
+/// looks for the player it records before trying the rules of a
+/// [`PlayerFinder`], and [`remember`](Self::remember) records a new
+/// choice.
+///
+/// # Example
+/// ```no_run
+/// # use pris::{PlayerFinder, SelectionMemory};
+/// # async fn example(memory: &mut SelectionMemory) -> pris::Result<()> {
+/// let conn = pris::get_connection();
+/// let fallback = PlayerFinder::new(&conn).or_playing().or_any();
+/// let player = memory.resolve(&fallback).await?;
+/// // ...the user picks another player...
+/// let other = pris::Player::try_new("mpd", &conn).await?;
+/// memory.remember(&other).await;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SelectionMemory {
+    /// The well-known bus name of the player, such as
+    /// `org.mpris.MediaPlayer2.mpd`, or `None` if none was chosen.
+    pub bus_name: Option<String>,
+    /// The `Identity` of the player, such as `Music Player Daemon`,
+    /// if it gave it.
+    pub identity: Option<String>,
+}
+
+impl SelectionMemory {
+    /// A memory of no player.
+    pub fn new() -> Self {
+        SelectionMemory::default()
+    }
+
+    /// Records `player` as the one chosen, asking it for its
+    /// `Identity`. A player that doesn't answer within a quarter of
+    /// a second is recorded without one.
+    pub async fn remember(&mut self, player: &Player<'_>) {
+        let proxy = player.get_proxy();
+        let identity = proxy.get::<String>(spec::ROOT.name, "Identity");
+        self.bus_name = Some(format!("{}{}", util::MPRIS_PREFIX, player.name));
+        self.identity = match runtime::timeout(util::IDENTITY_TIMEOUT, identity).await {
+            Ok(Ok(identity)) => Some(identity),
+            _ => None,
+        };
+    }
+
+    /// Forgets the player chosen.
+    pub fn forget(&mut self) {
+        *self = SelectionMemory::default();
+    }
+
+    /// Finds the player chosen, or else the one `fallback` picks.
+    ///
+    /// The player chosen is looked for by its exact name, then as
+    /// another instance of the same application, such as
+    /// `firefox.instance_2` for `firefox.instance_1`, and then by its
+    /// `Identity`, in case another application took its place. An
+    /// instance found in its stead is remembered instead of it.
+    /// Falling back leaves the memory alone, so the player chosen is
+    /// preferred again once it's back.
+    ///
+    /// # Errors
+    /// Same as [`PlayerFinder::find`].
+    pub async fn resolve<'a>(&mut self, fallback: &PlayerFinder<'a>) -> Result<Player<'a>> {
+        let remembered = self
+            .bus_name
+            .as_deref()
+            .and_then(|bus_name| bus_name.into_player_name().ok());
+
+        let mut first = PlayerFinder::new(fallback.conn());
+        if let Some(name) = &remembered {
+            first = first.by_name(name).by_app(util::app_name(name.as_str()));
+        }
+        if let Some(identity) = &self.identity {
+            first = first.by_identity(identity.as_str());
+        }
+
+        let player = fallback.clone().after(first).find().await?;
+        if let Some(name) = remembered {
+            let app = util::app_name(name.as_str());
+            if player.name != name.as_str() && util::app_name(&player.name) == app {
+                self.bus_name = Some(format!("{}{}", util::MPRIS_PREFIX, player.name));
+            }
+        }
+        Ok(player)
+    }
+}
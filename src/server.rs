@@ -0,0 +1,810 @@
+//! Serving an MPRIS player of your own, with the same types used to
+//! control players.
+//!
+//! An application implements [`MediaPlayer`], reporting its state
+//! and handling the methods it supports, and [`Server::register`]
+//! takes the player's name on the bus and serves the root and Player
+//! interfaces on `/org/mpris/MediaPlayer2` from it. Properties are
+//! sent with the signatures the specification gives them, and
+//! [`Metadata`] is encoded the way [`Player`](crate::Player) reads it
+//! back.
+//!
+//! Changes a method handler makes are signalled by the server on its
+//! own. Changes the application makes otherwise, such as moving on
+//! to the next track by itself, are made through
+//! [`Server::update`], which signals them the same way.
+//!
+//! # Example
+//! ```no_run
+//! use pris::{server::{MediaPlayer, Server}, Metadata, PlaybackStatus};
+//!
+//! struct Jukebox {
+//!     playing: bool,
+//!     title: String,
+//! }
+//!
+//! impl MediaPlayer for Jukebox {
+//!     fn identity(&self) -> String {
+//!         "Jukebox".to_string()
+//!     }
+//!
+//!     fn playback_status(&self) -> PlaybackStatus {
+//!         if self.playing {
+//!             PlaybackStatus::Playing
+//!         } else {
+//!             PlaybackStatus::Paused
+//!         }
+//!     }
+//!
+//!     fn metadata(&self) -> Metadata {
+//!         Metadata {
+//!             title: Some(self.title.clone()),
+//!             ..Metadata::default()
+//!         }
+//!     }
+//!
+//!     fn play(&mut self) -> pris::Result<()> {
+//!         self.playing = true;
+//!         Ok(())
+//!     }
+//!
+//!     fn pause(&mut self) -> pris::Result<()> {
+//!         self.playing = false;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! # async fn example() -> pris::Result<()> {
+//! let conn = pris::connect()?;
+//! let jukebox = Jukebox { playing: false, title: "Windowlicker".to_string() };
+//! let server = Server::register("jukebox", jukebox, &conn).await?;
+//! // Signals PropertiesChanged with the new Metadata
+//! server.update(|jukebox| jukebox.title = "Flim".to_string())?;
+//! # Ok(())
+//! # }
+//! ```
+use crate::{
+    util::{self, MPRIS_PREFIX},
+    Error, LoopStatus, Metadata, Micros, PlaybackStatus, Result, Value,
+};
+use dbus::{
+    arg::{ArgType, PropMap, RefArg, Variant},
+    channel::{MatchingReceiver, Sender, Token},
+    message::{MatchRule, Message},
+    nonblock::{stdintf::org_freedesktop_dbus::RequestNameReply, SyncConnection},
+    strings::{ErrorName, Path},
+};
+use std::{
+    ffi::CString,
+    str::FromStr,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+const ROOT_INTERFACE: &str = "org.mpris.MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const INTROSPECTABLE_INTERFACE: &str = "org.freedesktop.DBus.Introspectable";
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+/// The interfaces served, for `Introspect`.
+const INTROSPECTION: &str = r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
+<node>
+  <interface name="org.freedesktop.DBus.Introspectable">
+    <method name="Introspect"><arg name="xml" type="s" direction="out"/></method>
+  </interface>
+  <interface name="org.freedesktop.DBus.Properties">
+    <method name="Get">
+      <arg name="interface" type="s" direction="in"/>
+      <arg name="property" type="s" direction="in"/>
+      <arg name="value" type="v" direction="out"/>
+    </method>
+    <method name="GetAll">
+      <arg name="interface" type="s" direction="in"/>
+      <arg name="properties" type="a{sv}" direction="out"/>
+    </method>
+    <method name="Set">
+      <arg name="interface" type="s" direction="in"/>
+      <arg name="property" type="s" direction="in"/>
+      <arg name="value" type="v" direction="in"/>
+    </method>
+    <signal name="PropertiesChanged">
+      <arg name="interface" type="s"/>
+      <arg name="changed" type="a{sv}"/>
+      <arg name="invalidated" type="as"/>
+    </signal>
+  </interface>
+  <interface name="org.mpris.MediaPlayer2">
+    <method name="Raise"/>
+    <method name="Quit"/>
+    <property name="CanQuit" type="b" access="read"/>
+    <property name="CanRaise" type="b" access="read"/>
+    <property name="HasTrackList" type="b" access="read"/>
+    <property name="Identity" type="s" access="read"/>
+    <property name="DesktopEntry" type="s" access="read"/>
+    <property name="SupportedUriSchemes" type="as" access="read"/>
+    <property name="SupportedMimeTypes" type="as" access="read"/>
+  </interface>
+  <interface name="org.mpris.MediaPlayer2.Player">
+    <method name="Next"/>
+    <method name="Previous"/>
+    <method name="Pause"/>
+    <method name="PlayPause"/>
+    <method name="Stop"/>
+    <method name="Play"/>
+    <method name="Seek"><arg name="Offset" type="x" direction="in"/></method>
+    <method name="SetPosition">
+      <arg name="TrackId" type="o" direction="in"/>
+      <arg name="Position" type="x" direction="in"/>
+    </method>
+    <method name="OpenUri"><arg name="Uri" type="s" direction="in"/></method>
+    <signal name="Seeked"><arg name="Position" type="x"/></signal>
+    <property name="PlaybackStatus" type="s" access="read"/>
+    <property name="LoopStatus" type="s" access="readwrite"/>
+    <property name="Rate" type="d" access="readwrite"/>
+    <property name="Shuffle" type="b" access="readwrite"/>
+    <property name="Metadata" type="a{sv}" access="read"/>
+    <property name="Volume" type="d" access="readwrite"/>
+    <property name="Position" type="x" access="read"/>
+    <property name="MinimumRate" type="d" access="read"/>
+    <property name="MaximumRate" type="d" access="read"/>
+    <property name="CanGoNext" type="b" access="read"/>
+    <property name="CanGoPrevious" type="b" access="read"/>
+    <property name="CanPlay" type="b" access="read"/>
+    <property name="CanPause" type="b" access="read"/>
+    <property name="CanSeek" type="b" access="read"/>
+    <property name="CanControl" type="b" access="read"/>
+  </interface>
+</node>
+"#;
+
+/// A player served by a [`Server`].
+///
+/// Only [`identity`](Self::identity) and
+/// [`playback_status`](Self::playback_status) have to be
+/// implemented. The other properties default to a player that can
+/// play and pause, but not skip or seek, and the optional
+/// `LoopStatus` and `Shuffle` properties are left out unless
+/// reported. Methods default to failing with
+/// `org.freedesktop.DBus.Error.NotSupported`.
+///
+/// An `Err` returned by a handler is sent to the caller as a D-Bus
+/// error: [`Error::UnsupportedOperation`] as `NotSupported`,
+/// [`Error::InvalidArgument`] as `InvalidArgs`, [`Error::DBus`] as
+/// the error it holds, and anything else as `Failed`.
+pub trait MediaPlayer: Send + 'static {
+    /// `Identity`, the name of the player as shown to users.
+    fn identity(&self) -> String;
+
+    /// `DesktopEntry`, the name of the player's desktop file without
+    /// its extension.
+    fn desktop_entry(&self) -> Option<String> {
+        None
+    }
+
+    /// `CanQuit`.
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    /// `CanRaise`.
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    /// `SupportedUriSchemes`, such as `file` and `http`.
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// `SupportedMimeTypes`, such as `audio/mpeg`.
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// `PlaybackStatus`.
+    fn playback_status(&self) -> PlaybackStatus;
+
+    /// `LoopStatus`, left out if `None`.
+    fn loop_status(&self) -> Option<LoopStatus> {
+        None
+    }
+
+    /// `Rate`.
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    /// `MinimumRate`.
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    /// `MaximumRate`.
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    /// `Shuffle`, left out if `None`.
+    fn shuffle(&self) -> Option<bool> {
+        None
+    }
+
+    /// `Metadata`, of the current track. Without a track id, the
+    /// track is sent as having none, with the `NoTrack` path.
+    fn metadata(&self) -> Metadata {
+        Metadata::default()
+    }
+
+    /// `Volume`.
+    fn volume(&self) -> f64 {
+        1.0
+    }
+
+    /// `Position`, in the current track.
+    fn position(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    /// `CanGoNext`.
+    fn can_go_next(&self) -> bool {
+        false
+    }
+
+    /// `CanGoPrevious`.
+    fn can_go_previous(&self) -> bool {
+        false
+    }
+
+    /// `CanPlay`.
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    /// `CanPause`.
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    /// `CanSeek`.
+    fn can_seek(&self) -> bool {
+        false
+    }
+
+    /// `CanControl`.
+    fn can_control(&self) -> bool {
+        true
+    }
+
+    /// Handles `Raise`.
+    fn raise(&mut self) -> Result<()> {
+        Err(unsupported("Raise"))
+    }
+
+    /// Handles `Quit`.
+    fn quit(&mut self) -> Result<()> {
+        Err(unsupported("Quit"))
+    }
+
+    /// Handles `Next`.
+    fn next(&mut self) -> Result<()> {
+        Err(unsupported("Next"))
+    }
+
+    /// Handles `Previous`.
+    fn previous(&mut self) -> Result<()> {
+        Err(unsupported("Previous"))
+    }
+
+    /// Handles `Pause`.
+    fn pause(&mut self) -> Result<()> {
+        Err(unsupported("Pause"))
+    }
+
+    /// Handles `PlayPause`. Defaults to [`pause`](Self::pause) while
+    /// playing, and [`play`](Self::play) otherwise.
+    fn play_pause(&mut self) -> Result<()> {
+        match self.playback_status() {
+            PlaybackStatus::Playing => self.pause(),
+            _ => self.play(),
+        }
+    }
+
+    /// Handles `Stop`.
+    fn stop(&mut self) -> Result<()> {
+        Err(unsupported("Stop"))
+    }
+
+    /// Handles `Play`.
+    fn play(&mut self) -> Result<()> {
+        Err(unsupported("Play"))
+    }
+
+    /// Handles `Seek`, by `offset`, backwards if negative. A
+    /// `Seeked` signal is sent for it if it succeeds.
+    fn seek(&mut self, offset: Micros) -> Result<()> {
+        let _ = offset;
+        Err(unsupported("Seek"))
+    }
+
+    /// Handles `SetPosition`, to `position` into the track
+    /// `track_id`. The specification has players ignore it
+    /// if `track_id` isn't that of the current track. A `Seeked`
+    /// signal is sent for it if it succeeds.
+    fn set_position(&mut self, track_id: &str, position: Micros) -> Result<()> {
+        let _ = (track_id, position);
+        Err(unsupported("SetPosition"))
+    }
+
+    /// Handles `OpenUri`.
+    fn open_uri(&mut self, uri: &str) -> Result<()> {
+        let _ = uri;
+        Err(unsupported("OpenUri"))
+    }
+
+    /// Handles setting `LoopStatus`.
+    fn set_loop_status(&mut self, status: LoopStatus) -> Result<()> {
+        let _ = status;
+        Err(unsupported("LoopStatus"))
+    }
+
+    /// Handles setting `Rate`.
+    fn set_rate(&mut self, rate: f64) -> Result<()> {
+        let _ = rate;
+        Err(unsupported("Rate"))
+    }
+
+    /// Handles setting `Shuffle`.
+    fn set_shuffle(&mut self, shuffle: bool) -> Result<()> {
+        let _ = shuffle;
+        Err(unsupported("Shuffle"))
+    }
+
+    /// Handles setting `Volume`.
+    fn set_volume(&mut self, volume: f64) -> Result<()> {
+        let _ = volume;
+        Err(unsupported("Volume"))
+    }
+}
+
+/// A [`MediaPlayer`] served on a connection.
+///
+/// Each `Server` needs a connection of its own. It leaves the bus
+/// when dropped.
+pub struct Server<'a, P: MediaPlayer> {
+    name: String,
+    conn: &'a SyncConnection,
+    player: Arc<Mutex<P>>,
+    token: Token,
+}
+
+impl<'a, P: MediaPlayer> Server<'a, P> {
+    /// Registers `org.mpris.MediaPlayer2.<name>` on `conn`, and starts
+    /// serving `player` there.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidArgument`] if `name` isn't valid in a
+    /// bus name, or is already taken, or an `Err` if it couldn't be
+    /// requested.
+    pub async fn register(
+        name: &str,
+        player: P,
+        conn: &'a SyncConnection,
+    ) -> Result<Server<'a, P>> {
+        let bus_name = util::player_bus_name(name).ok_or_else(|| {
+            Error::InvalidArgument(format!("{} isn't a valid player name.", name))
+        })?;
+        let player = Arc::new(Mutex::new(player));
+        let served = player.clone();
+        // Served before the name is taken, so that no call made as
+        // soon as the player shows up goes unanswered
+        let token = conn.start_receive(
+            MatchRule::new_method_call().with_path(MPRIS_PATH),
+            Box::new(move |msg, conn| {
+                let messages = answer(&mut *served.lock().unwrap(), &msg);
+                for message in messages {
+                    let _ = conn.send(message);
+                }
+                true
+            }),
+        );
+
+        let taken = match conn
+            .request_name(bus_name.to_string(), false, false, true)
+            .await
+        {
+            Ok(RequestNameReply::PrimaryOwner) => Ok(()),
+            Ok(_) => Err(Error::InvalidArgument(format!(
+                "The name {} is already taken.",
+                bus_name
+            ))),
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = taken {
+            conn.stop_receive(token);
+            return Err(e);
+        }
+
+        Ok(Server {
+            name: name.to_string(),
+            conn,
+            player,
+            token,
+        })
+    }
+
+    /// The name the player was registered under, without the MPRIS
+    /// prefix.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The player, locked, for reading its state. Changes made
+    /// through it aren't signalled; use [`update`](Self::update).
+    pub fn player(&self) -> MutexGuard<'_, P> {
+        self.player.lock().unwrap()
+    }
+
+    /// Changes the player with `change`, and emits
+    /// `PropertiesChanged` for the properties that changed.
+    ///
+    /// # Errors
+    /// Returns [`Error::Disconnected`] if the signal couldn't be sent.
+    pub fn update<F, R>(&self, change: F) -> Result<R>
+    where
+        F: FnOnce(&mut P) -> R,
+    {
+        let (result, signals) = {
+            let mut player = self.player.lock().unwrap();
+            let before = Snapshot::of(&*player);
+            let result = change(&mut player);
+            (result, Snapshot::of(&*player).changes_since(&before))
+        };
+
+        for signal in signals {
+            self.send(signal)?;
+        }
+        Ok(result)
+    }
+
+    /// Emits `Seeked` with the player's current position, for when
+    /// it jumped other than by playing on, such as on a new track.
+    ///
+    /// # Errors
+    /// Returns [`Error::Disconnected`] if the signal couldn't be sent.
+    pub fn seeked(&self) -> Result<()> {
+        let position = self.player.lock().unwrap().position();
+        self.send(seeked(position))
+    }
+
+    fn send(&self, msg: Message) -> Result<()> {
+        self.conn
+            .send(msg)
+            .map(|_| ())
+            .map_err(|_| Error::Disconnected)
+    }
+}
+
+impl<P: MediaPlayer> Drop for Server<'_, P> {
+    fn drop(&mut self) {
+        self.conn.stop_receive(self.token);
+        let release = Message::new_method_call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "ReleaseName",
+        )
+        .unwrap()
+        .append1(format!("{}{}", MPRIS_PREFIX, self.name));
+        let _ = self.conn.send(release);
+    }
+}
+
+/// The properties of both interfaces at one point.
+struct Snapshot {
+    root: PropMap,
+    player: PropMap,
+}
+
+impl Snapshot {
+    fn of<P: MediaPlayer>(player: &P) -> Snapshot {
+        Snapshot {
+            root: root_properties(player),
+            player: player_properties(player),
+        }
+    }
+
+    /// The `PropertiesChanged` signals for what changed since
+    /// `before`. `Position` is left out, as the specification has it.
+    fn changes_since(&self, before: &Snapshot) -> Vec<Message> {
+        let interfaces = [
+            (ROOT_INTERFACE, &before.root, &self.root),
+            (PLAYER_INTERFACE, &before.player, &self.player),
+        ];
+
+        let mut signals = Vec::new();
+        for (interface, before, after) in interfaces {
+            let mut changed = PropMap::new();
+            for (name, value) in after {
+                let previous = before.get(name).map(|v| Value::from_dbus(&*v.0));
+                if name != "Position" && previous != Some(Value::from_dbus(&*value.0)) {
+                    changed.insert(name.clone(), Variant(value.0.box_clone()));
+                }
+            }
+            let invalidated: Vec<String> = before
+                .keys()
+                .filter(|name| !after.contains_key(*name))
+                .cloned()
+                .collect();
+
+            if !changed.is_empty() || !invalidated.is_empty() {
+                signals.push(properties_changed(interface, changed, invalidated));
+            }
+        }
+
+        signals
+    }
+}
+
+fn insert<T: RefArg + 'static>(properties: &mut PropMap, name: &str, value: T) {
+    properties.insert(name.to_string(), Variant(Box::new(value)));
+}
+
+fn root_properties<P: MediaPlayer>(player: &P) -> PropMap {
+    let mut properties = PropMap::new();
+    insert(&mut properties, "CanQuit", player.can_quit());
+    insert(&mut properties, "CanRaise", player.can_raise());
+    insert(&mut properties, "HasTrackList", false);
+    insert(&mut properties, "Identity", player.identity());
+    if let Some(entry) = player.desktop_entry() {
+        insert(&mut properties, "DesktopEntry", entry);
+    }
+    insert(
+        &mut properties,
+        "SupportedUriSchemes",
+        player.supported_uri_schemes(),
+    );
+    insert(
+        &mut properties,
+        "SupportedMimeTypes",
+        player.supported_mime_types(),
+    );
+    properties
+}
+
+fn player_properties<P: MediaPlayer>(player: &P) -> PropMap {
+    let mut properties = PropMap::new();
+    insert(
+        &mut properties,
+        "PlaybackStatus",
+        player.playback_status().as_str().to_string(),
+    );
+    if let Some(status) = player.loop_status() {
+        insert(&mut properties, "LoopStatus", status.as_str().to_string());
+    }
+    insert(&mut properties, "Rate", player.rate());
+    insert(&mut properties, "MinimumRate", player.minimum_rate());
+    insert(&mut properties, "MaximumRate", player.maximum_rate());
+    if let Some(shuffle) = player.shuffle() {
+        insert(&mut properties, "Shuffle", shuffle);
+    }
+    insert(
+        &mut properties,
+        "Metadata",
+        encode_metadata(&player.metadata()),
+    );
+    insert(&mut properties, "Volume", player.volume());
+    insert(
+        &mut properties,
+        "Position",
+        Micros::from_duration(player.position()).get(),
+    );
+    insert(&mut properties, "CanGoNext", player.can_go_next());
+    insert(&mut properties, "CanGoPrevious", player.can_go_previous());
+    insert(&mut properties, "CanPlay", player.can_play());
+    insert(&mut properties, "CanPause", player.can_pause());
+    insert(&mut properties, "CanSeek", player.can_seek());
+    insert(&mut properties, "CanControl", player.can_control());
+    properties
+}
+
+/// `metadata` as sent, with the `NoTrack` path standing in for a
+/// missing or invalid track id.
+fn encode_metadata(metadata: &Metadata) -> PropMap {
+    let mut encoded = metadata.to_prop_map().unwrap_or_else(|_| {
+        Metadata {
+            track_id: None,
+            ..metadata.clone()
+        }
+        .to_prop_map()
+        .unwrap_or_default()
+    });
+    if !encoded.contains_key("mpris:trackid") {
+        insert(&mut encoded, "mpris:trackid", Path::from(NO_TRACK));
+    }
+    encoded
+}
+
+/// The properties of `interface`.
+fn properties<P: MediaPlayer>(player: &P, interface: &str) -> Option<PropMap> {
+    match interface {
+        ROOT_INTERFACE => Some(root_properties(player)),
+        PLAYER_INTERFACE => Some(player_properties(player)),
+        _ => None,
+    }
+}
+
+/// Handles `msg`, returning the reply to it followed by the signals
+/// it caused.
+fn answer<P: MediaPlayer>(player: &mut P, msg: &Message) -> Vec<Message> {
+    let interface = msg.interface().map(|i| i.to_string()).unwrap_or_default();
+    let member = msg.member().map(|m| m.to_string()).unwrap_or_default();
+
+    let before = Snapshot::of(player);
+    let result = match (interface.as_str(), member.as_str()) {
+        (INTROSPECTABLE_INTERFACE, "Introspect") => {
+            return vec![msg.method_return().append1(INTROSPECTION)]
+        }
+        (PROPERTIES_INTERFACE, "Get") => return vec![get(player, msg)],
+        (PROPERTIES_INTERFACE, "GetAll") => return vec![get_all(player, msg)],
+        (PROPERTIES_INTERFACE, "Set") => set(player, msg),
+        (ROOT_INTERFACE, "Raise") => player.raise(),
+        (ROOT_INTERFACE, "Quit") => player.quit(),
+        (PLAYER_INTERFACE, "Next") => player.next(),
+        (PLAYER_INTERFACE, "Previous") => player.previous(),
+        (PLAYER_INTERFACE, "Pause") => player.pause(),
+        (PLAYER_INTERFACE, "PlayPause") => player.play_pause(),
+        (PLAYER_INTERFACE, "Stop") => player.stop(),
+        (PLAYER_INTERFACE, "Play") => player.play(),
+        (PLAYER_INTERFACE, "Seek") => msg
+            .read1::<Micros>()
+            .map_err(|e| Error::InvalidArgument(e.to_string()))
+            .and_then(|offset| player.seek(offset)),
+        (PLAYER_INTERFACE, "SetPosition") => msg
+            .read2::<Path, Micros>()
+            .map_err(|e| Error::InvalidArgument(e.to_string()))
+            .and_then(|(track_id, position)| player.set_position(&track_id, position)),
+        (PLAYER_INTERFACE, "OpenUri") => msg
+            .read1::<&str>()
+            .map_err(|e| Error::InvalidArgument(e.to_string()))
+            .and_then(|uri| player.open_uri(uri)),
+        _ => {
+            let error = standard_error("UnknownMethod");
+            return vec![error_reply(msg, &error, "No such method")];
+        }
+    };
+
+    let moved = result.is_ok() && (member == "Seek" || member == "SetPosition");
+    let mut messages = match result {
+        Ok(()) => vec![msg.method_return()],
+        Err(e) => vec![handler_error(msg, &e)],
+    };
+    messages.extend(Snapshot::of(player).changes_since(&before));
+    if moved {
+        messages.push(seeked(player.position()));
+    }
+
+    messages
+}
+
+fn get<P: MediaPlayer>(player: &P, msg: &Message) -> Message {
+    let (interface, name): (&str, &str) = match msg.read2() {
+        Ok(args) => args,
+        Err(e) => return error_reply(msg, &standard_error("InvalidArgs"), &e.to_string()),
+    };
+    match properties(player, interface) {
+        Some(mut properties) => match properties.remove(name) {
+            Some(value) => msg.method_return().append1(value),
+            None => error_reply(msg, &standard_error("UnknownProperty"), name),
+        },
+        None => error_reply(msg, &standard_error("UnknownInterface"), interface),
+    }
+}
+
+fn get_all<P: MediaPlayer>(player: &P, msg: &Message) -> Message {
+    let interface: &str = match msg.read1() {
+        Ok(interface) => interface,
+        Err(e) => return error_reply(msg, &standard_error("InvalidArgs"), &e.to_string()),
+    };
+    match properties(player, interface) {
+        Some(properties) => msg.method_return().append1(properties),
+        None => error_reply(msg, &standard_error("UnknownInterface"), interface),
+    }
+}
+
+fn set<P: MediaPlayer>(player: &mut P, msg: &Message) -> Result<()> {
+    let (interface, name, value): (&str, &str, Variant<Box<dyn RefArg>>) = msg
+        .read3()
+        .map_err(|e| Error::InvalidArgument(e.to_string()))?;
+    let value = &*value.0;
+    let wrong_type = || {
+        Error::InvalidArgument(format!(
+            "{} can't be set to a value of signature {}.",
+            name,
+            value.signature()
+        ))
+    };
+
+    match (interface, name) {
+        (PLAYER_INTERFACE, "Volume") => player.set_volume(value.as_f64().ok_or_else(wrong_type)?),
+        (PLAYER_INTERFACE, "Rate") => player.set_rate(value.as_f64().ok_or_else(wrong_type)?),
+        (PLAYER_INTERFACE, "Shuffle") if value.arg_type() == ArgType::Boolean => {
+            player.set_shuffle(value.as_u64() == Some(1))
+        }
+        (PLAYER_INTERFACE, "LoopStatus") => {
+            let status = value.as_str().ok_or_else(wrong_type)?;
+            player.set_loop_status(LoopStatus::from_str(status).map_err(|_| wrong_type())?)
+        }
+        (PLAYER_INTERFACE, "Shuffle") => Err(wrong_type()),
+        _ => match properties(player, interface) {
+            Some(properties) if properties.contains_key(name) => {
+                Err(Error::DBus(dbus::Error::new_custom(
+                    "org.freedesktop.DBus.Error.PropertyReadOnly",
+                    &format!("{} is read-only.", name),
+                )))
+            }
+            Some(_) => Err(Error::DBus(dbus::Error::new_custom(
+                "org.freedesktop.DBus.Error.UnknownProperty",
+                name,
+            ))),
+            None => Err(Error::DBus(dbus::Error::new_custom(
+                "org.freedesktop.DBus.Error.UnknownInterface",
+                interface,
+            ))),
+        },
+    }
+}
+
+/// The error of a method the player doesn't implement.
+fn unsupported(member: &str) -> Error {
+    Error::UnsupportedOperation {
+        description: format!("The player doesn't support {}.", member),
+        source: None,
+    }
+}
+
+/// The D-Bus error a handler's `error` is sent as.
+fn handler_error(msg: &Message, error: &Error) -> Message {
+    let name = match error {
+        Error::DBus(e) => e
+            .name()
+            .and_then(|name| ErrorName::new(name.to_string()).ok()),
+        Error::UnsupportedOperation { .. } => Some(standard_error("NotSupported")),
+        Error::InvalidArgument(_) => Some(standard_error("InvalidArgs")),
+        _ => None,
+    };
+    let text = match error {
+        Error::DBus(e) => e.message().unwrap_or_default().to_string(),
+        e => e.to_string(),
+    };
+
+    error_reply(
+        msg,
+        &name.unwrap_or_else(|| standard_error("Failed")),
+        &text,
+    )
+}
+
+/// The standard D-Bus error `name`.
+fn standard_error(name: &str) -> ErrorName<'static> {
+    ErrorName::new(format!("org.freedesktop.DBus.Error.{}", name)).unwrap()
+}
+
+fn error_reply(msg: &Message, error: &ErrorName<'_>, message: &str) -> Message {
+    let message = CString::new(message.replace('\0', "")).unwrap();
+    msg.error(error, &message)
+}
+
+fn properties_changed(interface: &str, changed: PropMap, invalidated: Vec<String>) -> Message {
+    Message::new_signal(MPRIS_PATH, PROPERTIES_INTERFACE, "PropertiesChanged")
+        .unwrap()
+        .append3(interface, changed, invalidated)
+}
+
+fn seeked(position: Duration) -> Message {
+    Message::new_signal(MPRIS_PATH, PLAYER_INTERFACE, "Seeked")
+        .unwrap()
+        .append1(Micros::from_duration(position))
+}
@@ -0,0 +1,209 @@
+// Generated from the XML in spec/ by tests/test_spec.rs; don't edit it by hand.
+
+use super::{Access, Arg, Direction, EmitsChanged, Interface, Method, Property, Signal};
+
+/// `org.mpris.MediaPlayer2.Player`, from `spec/org.mpris.MediaPlayer2.Player.xml`.
+pub(crate) const PLAYER: Interface = Interface {
+    name: "org.mpris.MediaPlayer2.Player",
+    methods: &[
+        Method {
+            name: "Next",
+            args: &[],
+        },
+        Method {
+            name: "Previous",
+            args: &[],
+        },
+        Method {
+            name: "Pause",
+            args: &[],
+        },
+        Method {
+            name: "PlayPause",
+            args: &[],
+        },
+        Method {
+            name: "Stop",
+            args: &[],
+        },
+        Method {
+            name: "Play",
+            args: &[],
+        },
+        Method {
+            name: "Seek",
+            args: &[
+                Arg { name: "Offset", signature: "x", direction: Direction::In },
+            ],
+        },
+        Method {
+            name: "SetPosition",
+            args: &[
+                Arg { name: "TrackId", signature: "o", direction: Direction::In },
+                Arg { name: "Position", signature: "x", direction: Direction::In },
+            ],
+        },
+        Method {
+            name: "OpenUri",
+            args: &[
+                Arg { name: "Uri", signature: "s", direction: Direction::In },
+            ],
+        },
+    ],
+    properties: &[
+        Property { name: "PlaybackStatus", signature: "s", access: Access::Read, emits_changed: EmitsChanged::True, optional: false },
+        Property { name: "LoopStatus", signature: "s", access: Access::ReadWrite, emits_changed: EmitsChanged::True, optional: true },
+        Property { name: "Rate", signature: "d", access: Access::ReadWrite, emits_changed: EmitsChanged::True, optional: false },
+        Property { name: "Shuffle", signature: "b", access: Access::ReadWrite, emits_changed: EmitsChanged::True, optional: true },
+        Property { name: "Metadata", signature: "a{sv}", access: Access::Read, emits_changed: EmitsChanged::True, optional: false },
+        Property { name: "Volume", signature: "d", access: Access::ReadWrite, emits_changed: EmitsChanged::True, optional: false },
+        Property { name: "Position", signature: "x", access: Access::Read, emits_changed: EmitsChanged::False, optional: false },
+        Property { name: "MinimumRate", signature: "d", access: Access::Read, emits_changed: EmitsChanged::True, optional: false },
+        Property { name: "MaximumRate", signature: "d", access: Access::Read, emits_changed: EmitsChanged::True, optional: false },
+        Property { name: "CanGoNext", signature: "b", access: Access::Read, emits_changed: EmitsChanged::True, optional: false },
+        Property { name: "CanGoPrevious", signature: "b", access: Access::Read, emits_changed: EmitsChanged::True, optional: false },
+        Property { name: "CanPlay", signature: "b", access: Access::Read, emits_changed: EmitsChanged::True, optional: false },
+        Property { name: "CanPause", signature: "b", access: Access::Read, emits_changed: EmitsChanged::True, optional: false },
+        Property { name: "CanSeek", signature: "b", access: Access::Read, emits_changed: EmitsChanged::True, optional: false },
+        Property { name: "CanControl", signature: "b", access: Access::Read, emits_changed: EmitsChanged::False, optional: false },
+    ],
+    signals: &[
+        Signal {
+            name: "Seeked",
+            args: &[
+                Arg { name: "Position", signature: "x", direction: Direction::Out },
+            ],
+        },
+    ],
+};
+
+/// `org.mpris.MediaPlayer2.Playlists`, from `spec/org.mpris.MediaPlayer2.Playlists.xml`.
+pub(crate) const PLAYLISTS: Interface = Interface {
+    name: "org.mpris.MediaPlayer2.Playlists",
+    methods: &[
+        Method {
+            name: "ActivatePlaylist",
+            args: &[
+                Arg { name: "PlaylistId", signature: "o", direction: Direction::In },
+            ],
+        },
+        Method {
+            name: "GetPlaylists",
+            args: &[
+                Arg { name: "Index", signature: "u", direction: Direction::In },
+                Arg { name: "MaxCount", signature: "u", direction: Direction::In },
+                Arg { name: "Order", signature: "s", direction: Direction::In },
+                Arg { name: "ReverseOrder", signature: "b", direction: Direction::In },
+                Arg { name: "Playlists", signature: "a(oss)", direction: Direction::Out },
+            ],
+        },
+    ],
+    properties: &[
+        Property { name: "PlaylistCount", signature: "u", access: Access::Read, emits_changed: EmitsChanged::True, optional: false },
+        Property { name: "Orderings", signature: "as", access: Access::Read, emits_changed: EmitsChanged::True, optional: false },
+        Property { name: "ActivePlaylist", signature: "(b(oss))", access: Access::Read, emits_changed: EmitsChanged::True, optional: false },
+    ],
+    signals: &[
+        Signal {
+            name: "PlaylistChanged",
+            args: &[
+                Arg { name: "Playlist", signature: "(oss)", direction: Direction::Out },
+            ],
+        },
+    ],
+};
+
+/// `org.mpris.MediaPlayer2.TrackList`, from `spec/org.mpris.MediaPlayer2.TrackList.xml`.
+pub(crate) const TRACK_LIST: Interface = Interface {
+    name: "org.mpris.MediaPlayer2.TrackList",
+    methods: &[
+        Method {
+            name: "GetTracksMetadata",
+            args: &[
+                Arg { name: "TrackIds", signature: "ao", direction: Direction::In },
+                Arg { name: "Metadata", signature: "aa{sv}", direction: Direction::Out },
+            ],
+        },
+        Method {
+            name: "AddTrack",
+            args: &[
+                Arg { name: "Uri", signature: "s", direction: Direction::In },
+                Arg { name: "AfterTrack", signature: "o", direction: Direction::In },
+                Arg { name: "SetAsCurrent", signature: "b", direction: Direction::In },
+            ],
+        },
+        Method {
+            name: "RemoveTrack",
+            args: &[
+                Arg { name: "TrackId", signature: "o", direction: Direction::In },
+            ],
+        },
+        Method {
+            name: "GoTo",
+            args: &[
+                Arg { name: "TrackId", signature: "o", direction: Direction::In },
+            ],
+        },
+    ],
+    properties: &[
+        Property { name: "Tracks", signature: "ao", access: Access::Read, emits_changed: EmitsChanged::Invalidates, optional: false },
+        Property { name: "CanEditTracks", signature: "b", access: Access::Read, emits_changed: EmitsChanged::True, optional: false },
+    ],
+    signals: &[
+        Signal {
+            name: "TrackListReplaced",
+            args: &[
+                Arg { name: "Tracks", signature: "ao", direction: Direction::Out },
+                Arg { name: "CurrentTrack", signature: "o", direction: Direction::Out },
+            ],
+        },
+        Signal {
+            name: "TrackAdded",
+            args: &[
+                Arg { name: "Metadata", signature: "a{sv}", direction: Direction::Out },
+                Arg { name: "AfterTrack", signature: "o", direction: Direction::Out },
+            ],
+        },
+        Signal {
+            name: "TrackRemoved",
+            args: &[
+                Arg { name: "TrackId", signature: "o", direction: Direction::Out },
+            ],
+        },
+        Signal {
+            name: "TrackMetadataChanged",
+            args: &[
+                Arg { name: "TrackId", signature: "o", direction: Direction::Out },
+                Arg { name: "Metadata", signature: "a{sv}", direction: Direction::Out },
+            ],
+        },
+    ],
+};
+
+/// `org.mpris.MediaPlayer2`, from `spec/org.mpris.MediaPlayer2.xml`.
+pub(crate) const ROOT: Interface = Interface {
+    name: "org.mpris.MediaPlayer2",
+    methods: &[
+        Method {
+            name: "Raise",
+            args: &[],
+        },
+        Method {
+            name: "Quit",
+            args: &[],
+        },
+    ],
+    properties: &[
+        Property { name: "CanQuit", signature: "b", access: Access::Read, emits_changed: EmitsChanged::True, optional: false },
+        Property { name: "Fullscreen", signature: "b", access: Access::ReadWrite, emits_changed: EmitsChanged::True, optional: true },
+        Property { name: "CanSetFullscreen", signature: "b", access: Access::Read, emits_changed: EmitsChanged::True, optional: true },
+        Property { name: "CanRaise", signature: "b", access: Access::Read, emits_changed: EmitsChanged::True, optional: false },
+        Property { name: "HasTrackList", signature: "b", access: Access::Read, emits_changed: EmitsChanged::True, optional: false },
+        Property { name: "Identity", signature: "s", access: Access::Read, emits_changed: EmitsChanged::True, optional: false },
+        Property { name: "DesktopEntry", signature: "s", access: Access::Read, emits_changed: EmitsChanged::True, optional: true },
+        Property { name: "SupportedUriSchemes", signature: "as", access: Access::Read, emits_changed: EmitsChanged::True, optional: false },
+        Property { name: "SupportedMimeTypes", signature: "as", access: Access::Read, emits_changed: EmitsChanged::True, optional: false },
+    ],
+    signals: &[
+    ],
+};
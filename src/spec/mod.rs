@@ -0,0 +1,115 @@
+//! The MPRIS interfaces as the specification defines them, so that
+//! method, property and signal names are looked up in one place
+//! rather than spelled out wherever they're used.
+//!
+//! The tables themselves, in `generated.rs`, are generated from the
+//! introspection XML vendored in `spec/` by `tests/test_spec.rs`,
+//! which fails when they fall behind it. After changing the XML,
+//! regenerate them with
+//!
+//! ```text
+//! PRIS_REGENERATE=1 cargo test --test test_spec
+//! ```
+// The tables cover all four interfaces, not only what's used so far
+#![allow(dead_code)]
+
+#[rustfmt::skip]
+mod generated;
+
+pub(crate) use generated::*;
+
+/// A D-Bus interface of the specification.
+pub(crate) struct Interface {
+    pub(crate) name: &'static str,
+    pub(crate) methods: &'static [Method],
+    pub(crate) properties: &'static [Property],
+    pub(crate) signals: &'static [Signal],
+}
+
+/// A method of an [`Interface`].
+pub(crate) struct Method {
+    pub(crate) name: &'static str,
+    pub(crate) args: &'static [Arg],
+}
+
+/// A property of an [`Interface`].
+pub(crate) struct Property {
+    pub(crate) name: &'static str,
+    pub(crate) signature: &'static str,
+    pub(crate) access: Access,
+    pub(crate) emits_changed: EmitsChanged,
+    /// Whether players may leave it out, as its
+    /// `org.mpris.MediaPlayer2.property.optional` annotation says.
+    pub(crate) optional: bool,
+}
+
+/// A signal of an [`Interface`].
+pub(crate) struct Signal {
+    pub(crate) name: &'static str,
+    pub(crate) args: &'static [Arg],
+}
+
+/// An argument of a [`Method`] or [`Signal`]. The arguments of
+/// signals are all [`Direction::Out`].
+pub(crate) struct Arg {
+    pub(crate) name: &'static str,
+    pub(crate) signature: &'static str,
+    pub(crate) direction: Direction,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Direction {
+    In,
+    Out,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+/// Whether changes to a [`Property`] are announced with
+/// `PropertiesChanged`, as its `EmitsChangedSignal` annotation says.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum EmitsChanged {
+    /// With the new value.
+    True,
+    /// Without the new value, which has to be fetched.
+    Invalidates,
+    /// It never changes.
+    Const,
+    /// Not at all; `Position` is the notable one.
+    False,
+}
+
+impl Interface {
+    /// The method `name`, if the interface has one.
+    pub(crate) fn method(&self, name: &str) -> Option<&'static Method> {
+        self.methods.iter().find(|method| method.name == name)
+    }
+
+    /// The property `name`, if the interface has one.
+    pub(crate) fn property(&self, name: &str) -> Option<&'static Property> {
+        self.properties
+            .iter()
+            .find(|property| property.name == name)
+    }
+
+    /// The signal `name`, if the interface has one.
+    pub(crate) fn signal(&self, name: &str) -> Option<&'static Signal> {
+        self.signals.iter().find(|signal| signal.name == name)
+    }
+}
+
+impl Method {
+    /// The signature of the arguments the method is called with.
+    pub(crate) fn input_signature(&self) -> String {
+        self.args
+            .iter()
+            .filter(|arg| arg.direction == Direction::In)
+            .map(|arg| arg.signature)
+            .collect()
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{util, ChangedProperties, LoopStatus, PlaybackStatus};
+use dbus::arg::PropMap;
+use std::time::Duration;
+
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+/// A snapshot of the properties of a player's
+/// `org.mpris.MediaPlayer2.Player` interface.
+///
+/// Properties a player doesn't report are `None`; anything
+/// without a typed field ends up in [`other`](PlayerState::other).
+#[derive(Debug, Default)]
+pub struct PlayerState {
+    /// The name of the player, in the same form that is
+    /// passed to [`Player::try_new`](crate::Player::try_new).
+    pub player: String,
+    pub playback_status: Option<PlaybackStatus>,
+    pub metadata: Option<PropMap>,
+    pub position: Option<Duration>,
+    pub volume: Option<f64>,
+    pub loop_status: Option<LoopStatus>,
+    pub shuffle: Option<bool>,
+    pub rate: Option<f64>,
+    pub can_go_next: Option<bool>,
+    pub can_go_previous: Option<bool>,
+    pub can_play: Option<bool>,
+    pub can_pause: Option<bool>,
+    pub can_seek: Option<bool>,
+    pub can_control: Option<bool>,
+    /// Every other property, keyed by name.
+    pub other: PropMap,
+}
+
+impl Clone for PlayerState {
+    fn clone(&self) -> Self {
+        PlayerState {
+            player: self.player.clone(),
+            metadata: self.metadata.as_ref().map(util::clone_prop_map),
+            other: util::clone_prop_map(&self.other),
+            ..*self
+        }
+    }
+}
+
+impl PlayerState {
+    /// Builds a snapshot of `player` from the result of a
+    /// `GetAll` call for the Player interface.
+    pub fn from_properties<T>(player: T, properties: PropMap) -> PlayerState
+    where
+        T: Into<String>,
+    {
+        let mut properties =
+            ChangedProperties::from_parts(PLAYER_INTERFACE.to_string(), properties, Vec::new());
+        let position = take_position(&mut properties.other);
+
+        PlayerState {
+            player: player.into(),
+            playback_status: properties.playback_status,
+            metadata: properties.metadata,
+            position,
+            volume: properties.volume,
+            loop_status: properties.loop_status,
+            shuffle: properties.shuffle,
+            rate: properties.rate,
+            can_go_next: properties.can_go_next,
+            can_go_previous: properties.can_go_previous,
+            can_play: properties.can_play,
+            can_pause: properties.can_pause,
+            can_seek: properties.can_seek,
+            can_control: properties.can_control,
+            other: properties.other,
+        }
+    }
+
+    /// Updates the snapshot with the values sent in `changes`,
+    /// returning the names of the properties that were set.
+    ///
+    /// Invalidated properties keep their previous value, since
+    /// their new one isn't known.
+    pub fn apply(&mut self, mut changes: ChangedProperties) -> Vec<String> {
+        let mut changed = Vec::new();
+        if let Some(position) = take_position(&mut changes.other) {
+            self.position = Some(position);
+            changed.push("Position".to_string());
+        }
+
+        macro_rules! take_changed {
+            ($($field:ident: $name:literal),* $(,)?) => {
+                $(
+                    if changes.$field.is_some() {
+                        self.$field = changes.$field;
+                        changed.push($name.to_string());
+                    }
+                )*
+            };
+        }
+        take_changed!(
+            playback_status: "PlaybackStatus",
+            metadata: "Metadata",
+            volume: "Volume",
+            loop_status: "LoopStatus",
+            shuffle: "Shuffle",
+            rate: "Rate",
+            can_go_next: "CanGoNext",
+            can_go_previous: "CanGoPrevious",
+            can_play: "CanPlay",
+            can_pause: "CanPause",
+            can_seek: "CanSeek",
+            can_control: "CanControl",
+        );
+        for (name, value) in changes.other {
+            changed.push(name.clone());
+            self.other.insert(name, value);
+        }
+
+        changed
+    }
+}
+
+/// Removes the `Position` property from `properties`, if it holds
+/// a number of microseconds, clamping negative ones to zero.
+fn take_position(properties: &mut PropMap) -> Option<Duration> {
+    let position = properties.get("Position").and_then(|v| util::micros(&*v.0));
+    if position.is_some() {
+        properties.remove("Position");
+    }
+
+    position
+}
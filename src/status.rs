@@ -0,0 +1,116 @@
+use std::{fmt, str::FromStr};
+
+/// The playback status of a player, as reported by the
+/// `PlaybackStatus` property.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PlaybackStatus {
+    /// A track is currently playing.
+    Playing,
+    /// A track is currently paused.
+    Paused,
+    /// There is no track currently playing.
+    Stopped,
+}
+
+impl PlaybackStatus {
+    /// Returns the string used for this status on the bus.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlaybackStatus::Playing => "Playing",
+            PlaybackStatus::Paused => "Paused",
+            PlaybackStatus::Stopped => "Stopped",
+        }
+    }
+}
+
+/// The rank of players that didn't report a valid status, below
+/// every [`status_rank`].
+pub(crate) const UNKNOWN_RANK: u8 = 3;
+
+/// How highly players with `status` sort, the lowest first: 0 when
+/// playing, 1 when paused and 2 when stopped. Players whose status
+/// isn't known sort after all of these, as by
+/// [`sort_by_status`](crate::sort_by_status).
+///
+/// # Example
+/// ```
+/// # use pris::{status_rank, PlaybackStatus};
+/// let mut statuses = vec![PlaybackStatus::Stopped, PlaybackStatus::Playing];
+/// statuses.sort_by_key(|status| status_rank(*status));
+/// assert_eq!(statuses[0], PlaybackStatus::Playing);
+/// ```
+pub fn status_rank(status: PlaybackStatus) -> u8 {
+    match status {
+        PlaybackStatus::Playing => 0,
+        PlaybackStatus::Paused => 1,
+        PlaybackStatus::Stopped => 2,
+    }
+}
+
+/// The rank of a player that reported `status`, if it did.
+pub(crate) fn rank_of(status: Option<&str>) -> u8 {
+    status
+        .and_then(|status| status.parse().ok())
+        .map_or(UNKNOWN_RANK, status_rank)
+}
+
+impl FromStr for PlaybackStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Playing" => Ok(PlaybackStatus::Playing),
+            "Paused" => Ok(PlaybackStatus::Paused),
+            "Stopped" => Ok(PlaybackStatus::Stopped),
+            other => Err(format!("Unknown playback status: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for PlaybackStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The loop status of a player, as reported by the
+/// `LoopStatus` property.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LoopStatus {
+    /// Playback stops when there are no more tracks.
+    None,
+    /// The current track loops.
+    Track,
+    /// The current playlist loops.
+    Playlist,
+}
+
+impl LoopStatus {
+    /// Returns the string used for this status on the bus.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LoopStatus::None => "None",
+            LoopStatus::Track => "Track",
+            LoopStatus::Playlist => "Playlist",
+        }
+    }
+}
+
+impl FromStr for LoopStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "None" => Ok(LoopStatus::None),
+            "Track" => Ok(LoopStatus::Track),
+            "Playlist" => Ok(LoopStatus::Playlist),
+            other => Err(format!("Unknown loop status: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for LoopStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,291 @@
+use crate::{spec, Error, Player, Result};
+use dbus::nonblock::stdintf::org_freedesktop_dbus::Properties;
+use futures::future;
+use std::fmt;
+
+const INTROSPECTABLE_INTERFACE: &str = "org.freedesktop.DBus.Introspectable";
+
+/// The interfaces of the specification, in its order, and whether
+/// every player has to implement them.
+const INTERFACES: [(&spec::Interface, bool); 4] = [
+    (&spec::ROOT, true),
+    (&spec::PLAYER, true),
+    (&spec::TRACK_LIST, false),
+    (&spec::PLAYLISTS, false),
+];
+
+/// How a [`SupportReport`] found out what a player implements.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SupportSource {
+    /// From its introspection data, which lists its methods and
+    /// properties.
+    Introspection,
+    /// By asking for the properties of each interface, as it can't
+    /// be introspected. Its methods aren't known.
+    Probing,
+}
+
+/// What a player implements of one MPRIS interface, in a
+/// [`SupportReport`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InterfaceSupport {
+    /// The name of the interface, such as
+    /// `org.mpris.MediaPlayer2.Player`.
+    pub name: String,
+    /// The methods it exposes, or `None` if they aren't known.
+    pub methods: Option<Vec<String>>,
+    /// The properties it exposes.
+    pub properties: Vec<String>,
+    /// The methods and properties the specification requires of the
+    /// interface that it lacks. Properties the specification marks
+    /// as optional aren't counted, nor are methods if they aren't
+    /// known.
+    pub missing: Vec<String>,
+}
+
+/// What a player implements of the MPRIS specification, made by
+/// [`Player::support_report`].
+///
+/// It is displayed as a plain-text summary, suitable for pasting
+/// into a bug report.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SupportReport {
+    /// The name of the player, without the `org.mpris.MediaPlayer2.`
+    /// prefix.
+    pub player: String,
+    /// How it was found out.
+    pub source: SupportSource,
+    /// The MPRIS interfaces the player implements, in the order of
+    /// the specification.
+    pub interfaces: Vec<InterfaceSupport>,
+    /// The names of the MPRIS interfaces it doesn't implement.
+    pub absent: Vec<String>,
+}
+
+impl SupportReport {
+    /// What the player implements of the interface `name`, if it
+    /// implements it at all.
+    pub fn interface(&self, name: &str) -> Option<&InterfaceSupport> {
+        self.interfaces
+            .iter()
+            .find(|interface| interface.name == name)
+    }
+
+    /// Whether the player implements the interfaces every player has
+    /// to, with everything they require, as far as is known.
+    pub fn is_complete(&self) -> bool {
+        INTERFACES
+            .iter()
+            .filter(|(_, required)| *required)
+            .all(|(spec, _)| {
+                matches!(self.interface(spec.name), Some(found) if found.missing.is_empty())
+            })
+    }
+}
+
+impl fmt::Display for SupportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let source = match self.source {
+            SupportSource::Introspection => "introspection",
+            SupportSource::Probing => "probing its properties",
+        };
+        writeln!(f, "MPRIS support of {}, by {}:", self.player, source)?;
+
+        for interface in &self.interfaces {
+            writeln!(f, "{}", interface.name)?;
+            match &interface.methods {
+                Some(methods) => writeln!(f, "  Methods: {}", list(methods))?,
+                None => writeln!(f, "  Methods: unknown")?,
+            }
+            writeln!(f, "  Properties: {}", list(&interface.properties))?;
+            if !interface.missing.is_empty() {
+                writeln!(f, "  Missing: {}", interface.missing.join(", "))?;
+            }
+        }
+        for name in &self.absent {
+            let required = INTERFACES
+                .iter()
+                .any(|(spec, required)| *required && spec.name == name);
+            if required {
+                writeln!(f, "{} is absent, but required", name)?;
+            } else {
+                writeln!(f, "{} is absent", name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `names` joined with commas, or `none`.
+fn list(names: &[String]) -> String {
+    if names.is_empty() {
+        "none".to_string()
+    } else {
+        names.join(", ")
+    }
+}
+
+/// Makes the report described on [`Player::support_report`].
+pub(crate) async fn support_report(player: &Player<'_>) -> Result<SupportReport> {
+    let proxy = player.get_proxy();
+    let reply: std::result::Result<(String,), _> = proxy
+        .method_call(INTROSPECTABLE_INTERFACE, "Introspect", ())
+        .await;
+
+    let introspected = match reply {
+        Ok((xml,)) => parse_introspection(&xml),
+        Err(e) => match Error::from_call(&player.name, "Introspect", proxy.timeout, e) {
+            e @ Error::PlayerGone { .. } | e @ Error::Timeout { .. } => return Err(e),
+            // Not every player can be introspected
+            _ => None,
+        },
+    };
+
+    let (source, found) = match introspected {
+        Some(found) => (SupportSource::Introspection, found),
+        None => (SupportSource::Probing, probe(player).await),
+    };
+
+    let mut report = SupportReport {
+        player: player.name.clone(),
+        source,
+        interfaces: Vec::new(),
+        absent: Vec::new(),
+    };
+    for (spec, _) in INTERFACES.iter() {
+        let (methods, properties) = match found.iter().find(|(name, _, _)| name == spec.name) {
+            Some((_, methods, properties)) => (methods.clone(), properties.clone()),
+            None => {
+                report.absent.push(spec.name.to_string());
+                continue;
+            }
+        };
+
+        let mut missing = Vec::new();
+        if let Some(methods) = &methods {
+            for method in spec.methods {
+                if !methods.iter().any(|name| name == method.name) {
+                    missing.push(method.name.to_string());
+                }
+            }
+        }
+        for property in spec.properties {
+            if !property.optional && !properties.iter().any(|name| name == property.name) {
+                missing.push(property.name.to_string());
+            }
+        }
+
+        report.interfaces.push(InterfaceSupport {
+            name: spec.name.to_string(),
+            methods,
+            properties,
+            missing,
+        });
+    }
+
+    Ok(report)
+}
+
+/// An interface found on a player: its name, its methods if known,
+/// and its properties.
+type Found = (String, Option<Vec<String>>, Vec<String>);
+
+/// Asks for the properties of each MPRIS interface at once, taking
+/// those that can't be asked for as absent.
+async fn probe(player: &Player<'_>) -> Vec<Found> {
+    let proxy = player.get_proxy();
+    let replies =
+        future::join_all(INTERFACES.iter().map(|(spec, _)| proxy.get_all(spec.name))).await;
+
+    INTERFACES
+        .iter()
+        .zip(replies)
+        .filter_map(|((spec, _), reply)| {
+            let mut properties: Vec<String> = reply.ok()?.into_keys().collect();
+            properties.sort();
+            Some((spec.name.to_string(), None, properties))
+        })
+        .collect()
+}
+
+/// The interfaces described by the introspection data `xml`, or
+/// `None` if it is too malformed to tell.
+///
+/// Only the elements that matter are read: `interface`s directly in
+/// the root `node`, and their `method`s and `property`s. Child nodes
+/// are skipped.
+fn parse_introspection(xml: &str) -> Option<Vec<Found>> {
+    let mut found: Vec<Found> = Vec::new();
+    let mut depth = 0usize;
+    let mut in_interface = false;
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        if rest.starts_with("<!--") {
+            rest = &rest[rest.find("-->")? + 3..];
+            continue;
+        }
+        let end = rest.find('>')?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+
+        if let Some(closing) = tag.strip_prefix('/') {
+            match closing.trim() {
+                "node" => depth = depth.checked_sub(1)?,
+                "interface" if depth == 1 => in_interface = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        let closed = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let element = tag.split_whitespace().next().unwrap_or("");
+        match element {
+            "node" if !closed => depth += 1,
+            "interface" if depth == 1 => {
+                found.push((attr(tag, "name")?.to_string(), Some(Vec::new()), Vec::new()));
+                in_interface = !closed;
+            }
+            "method" if depth == 1 && in_interface => {
+                let (_, methods, _) = found.last_mut()?;
+                methods.as_mut()?.push(attr(tag, "name")?.to_string());
+            }
+            "property" if depth == 1 && in_interface => {
+                let (_, _, properties) = found.last_mut()?;
+                properties.push(attr(tag, "name")?.to_string());
+            }
+            _ => {}
+        }
+    }
+
+    if depth == 0 {
+        Some(found)
+    } else {
+        None
+    }
+}
+
+/// The value of the attribute `key` of the start tag `tag`.
+fn attr<'x>(tag: &'x str, key: &str) -> Option<&'x str> {
+    let mut rest = tag;
+    loop {
+        let at = rest.find(key)?;
+        let before = rest[..at].chars().next_back();
+        let after = rest[at + key.len()..].trim_start();
+        rest = &rest[at + key.len()..];
+        if !before.is_some_and(char::is_whitespace) {
+            continue;
+        }
+        if let Some(value) = after.strip_prefix('=') {
+            let value = value.trim_start();
+            let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+            let value = &value[1..];
+            return value.find(quote).map(|end| &value[..end]);
+        }
+    }
+}
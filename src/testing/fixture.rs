@@ -0,0 +1,522 @@
+use crate::{
+    event_manager::{self, RuleCounts},
+    runtime,
+    util::{self, MPRIS_PREFIX},
+    Error, Event, EventType, Player, PlayerState, Result,
+};
+use dbus::{
+    arg::PropMap,
+    channel::Token,
+    message::Message,
+    nonblock::{stdintf::org_freedesktop_dbus::Properties, SyncConnection},
+    strings::BusName,
+};
+use futures::stream::{self, BoxStream, StreamExt};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+const DBUS_NAME: &str = "org.freedesktop.DBus";
+const DBUS_PATH: &str = "/org/freedesktop/DBus";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+/// The first line of every fixture, naming the format's version.
+const HEADER: &str = "pris-fixture 1";
+
+/// What a recorded message is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Kind {
+    /// A signal, as it was received.
+    Signal,
+    /// The reply to `GetAll` for the Player interface of the player.
+    Properties(String),
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    /// When the message came in, from the start of the recording.
+    at: Duration,
+    kind: Kind,
+    /// The marshaled message.
+    message: Vec<u8>,
+}
+
+/// Something replayed from a fixture.
+enum Replayed {
+    Event(Event),
+    State(PlayerState),
+}
+
+/// Records what players send over a connection into a [`Fixture`].
+///
+/// Every MPRIS signal on the bus is recorded as it is received, byte
+/// for byte, along with replies to the property reads made through
+/// [`snapshot`](Self::snapshot). Nothing is recorded of calls made
+/// otherwise.
+///
+/// Unique names are replaced with `:fixture.1`, `:fixture.2` and so
+/// on in order of appearance, and serials with the position of the
+/// message in the recording, so that recording the same session
+/// twice gives the same fixture.
+///
+/// # Example
+/// ```no_run
+/// use pris::{testing::Recorder, Player};
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let conn = pris::connect()?;
+/// let recorder = Recorder::start(&conn).await?;
+/// let player = Player::try_new("chromium", &conn).await?;
+/// recorder.snapshot(&player).await?;
+/// player.play_pause().await?;
+/// tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+///
+/// std::fs::write("chromium.fixture", recorder.finish().to_string())?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Recorder<'a> {
+    conn: &'a SyncConnection,
+    recording: Arc<Mutex<Recording>>,
+    counts: RuleCounts,
+    tokens: Vec<Token>,
+}
+
+struct Recording {
+    started: Instant,
+    /// The unique names seen so far, and what they were replaced with.
+    aliases: HashMap<String, String>,
+    owners: Vec<(String, String)>,
+    entries: Vec<Entry>,
+}
+
+impl<'a> Recorder<'a> {
+    /// Starts recording the MPRIS signals received by `conn`, and
+    /// notes which player owns which name at the start.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the signals couldn't be subscribed to, or
+    /// the players couldn't be listed.
+    pub async fn start(conn: &'a SyncConnection) -> Result<Recorder<'a>> {
+        let mut recorder = Recorder {
+            conn,
+            recording: Arc::new(Mutex::new(Recording::new())),
+            counts: RuleCounts::default(),
+            tokens: Vec::new(),
+        };
+        for event_type in [
+            EventType::PropertiesChanged,
+            EventType::Seeked,
+            EventType::PlayerLifecycle,
+        ] {
+            let recording = recorder.recording.clone();
+            let token = event_manager::add_match(
+                conn,
+                &recorder.counts,
+                event_type.match_rule(),
+                move |msg| {
+                    if event_type.matches(&msg) {
+                        recording.lock().unwrap().record_signal(&msg);
+                    }
+                    true
+                },
+            )
+            .await?;
+            recorder.tokens.push(token);
+        }
+
+        // Listed once the signals are subscribed to, so that a player
+        // changing hands in between isn't missed
+        let players = util::list_owners(conn).await?;
+        let mut recording = recorder.recording.lock().unwrap();
+        for (name, owner) in players {
+            let alias = recording.alias(&owner);
+            recording.owners.push((name, alias));
+        }
+        drop(recording);
+
+        Ok(recorder)
+    }
+
+    /// Reads every property of the Player interface of `player`,
+    /// recording the reply, and returns them.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the properties couldn't be read.
+    pub async fn snapshot(&self, player: &Player<'_>) -> Result<PlayerState> {
+        let properties: PropMap = player.get_proxy().get_all(PLAYER_INTERFACE).await?;
+        let destination = format!("{}{}", MPRIS_PREFIX, player.name);
+        let call =
+            Message::new_method_call(destination, MPRIS_PATH, PROPERTIES_INTERFACE, "GetAll")
+                .map_err(Error::InvalidArgument)?
+                .append1(PLAYER_INTERFACE);
+        self.recording.lock().unwrap().record_reply(
+            call,
+            player.name.clone(),
+            util::clone_prop_map(&properties),
+        );
+
+        Ok(PlayerState::from_properties(
+            player.name.clone(),
+            properties,
+        ))
+    }
+
+    /// How many messages were recorded so far.
+    pub fn len(&self) -> usize {
+        self.recording.lock().unwrap().entries.len()
+    }
+
+    /// Whether nothing was recorded yet.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Stops recording, and returns what was recorded.
+    pub fn finish(self) -> Fixture {
+        let recording = self.recording.lock().unwrap();
+        Fixture {
+            owners: recording.owners.clone(),
+            entries: recording.entries.clone(),
+        }
+    }
+}
+
+impl Drop for Recorder<'_> {
+    fn drop(&mut self) {
+        for token in self.tokens.drain(..) {
+            event_manager::detach_match(self.conn, &self.counts, token);
+        }
+    }
+}
+
+impl Recording {
+    fn new() -> Recording {
+        Recording {
+            started: Instant::now(),
+            aliases: HashMap::new(),
+            owners: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// The name `name` is recorded as. Unique names are replaced,
+    /// while well-known and empty ones are kept.
+    fn alias(&mut self, name: &str) -> String {
+        if !name.starts_with(':') {
+            return name.to_string();
+        }
+        let next = self.aliases.len() + 1;
+        self.aliases
+            .entry(name.to_string())
+            .or_insert_with(|| format!(":fixture.{}", next))
+            .clone()
+    }
+
+    fn record_signal(&mut self, msg: &Message) {
+        let scrubbed = if msg.member().as_deref() == Some("NameOwnerChanged") {
+            self.scrub_owner_change(msg)
+        } else {
+            self.scrub_sender(msg)
+        };
+        if let Some(scrubbed) = scrubbed {
+            self.push(Kind::Signal, scrubbed);
+        }
+    }
+
+    /// Records the reply to `call` with `properties`.
+    fn record_reply(&mut self, mut call: Message, player: String, properties: PropMap) {
+        // The reply refers to the serial of the call, which is
+        // replaced the same way as the reply's own
+        call.set_serial(self.serial());
+        let reply = call.method_return().append1(properties);
+        self.push(Kind::Properties(player), reply);
+    }
+
+    /// A copy of `msg`, the arguments untouched, from the sender's
+    /// alias.
+    fn scrub_sender(&mut self, msg: &Message) -> Option<Message> {
+        let mut scrubbed = msg.duplicate().ok()?;
+        let sender = self.alias(&msg.sender()?);
+        scrubbed.set_sender(Some(BusName::new(sender).ok()?));
+        scrubbed.set_destination(None);
+        Some(scrubbed)
+    }
+
+    /// `NameOwnerChanged` names the owners among its arguments, so it
+    /// is sent again with their aliases.
+    fn scrub_owner_change(&mut self, msg: &Message) -> Option<Message> {
+        let (name, old_owner, new_owner): (&str, &str, &str) = msg.read3().ok()?;
+        let mut scrubbed = Message::new_signal(DBUS_PATH, DBUS_NAME, "NameOwnerChanged")
+            .ok()?
+            .append3(name, self.alias(old_owner), self.alias(new_owner));
+        scrubbed.set_sender(Some(BusName::from(DBUS_NAME)));
+        Some(scrubbed)
+    }
+
+    fn serial(&self) -> u32 {
+        self.entries.len() as u32 + 1
+    }
+
+    fn push(&mut self, kind: Kind, mut msg: Message) {
+        msg.set_serial(self.serial());
+        let mut message = Vec::new();
+        let marshaled: std::result::Result<(), ()> = msg.marshal(|bytes| {
+            message.extend_from_slice(bytes);
+            Ok(())
+        });
+        if marshaled.is_ok() {
+            self.entries.push(Entry {
+                at: self.started.elapsed(),
+                kind,
+                message,
+            });
+        }
+    }
+}
+
+/// A recording made by a [`Recorder`], replayed through the same
+/// parsing as the [`EventManager`](crate::EventManager), with no bus.
+///
+/// It is saved as text with [`to_string`](ToString::to_string), one
+/// message per line, and read back with [`parse`](str::parse), so a
+/// fixture can be attached to a bug report, then checked in next to
+/// the test reproducing it.
+///
+/// Senders are resolved to players from the owners noted at the
+/// start of the recording and the `NameOwnerChanged` signals
+/// recorded since, the way the bus would resolve them.
+///
+/// # Example
+/// ```no_run
+/// use pris::{testing::Fixture, Event};
+///
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let fixture: Fixture = std::fs::read_to_string("tests/fixtures/chromium.fixture")?.parse()?;
+/// for event in fixture.events() {
+///     if let Event::PropertiesChanged(changed) = event {
+///         assert!(changed.properties.metadata.is_some());
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Fixture {
+    /// Each player at the start, by name, and its owner's alias.
+    owners: Vec<(String, String)>,
+    entries: Vec<Entry>,
+}
+
+impl Fixture {
+    /// How many messages were recorded.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether nothing was recorded.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The recorded messages, signals and replies alike, in order.
+    pub fn messages(&self) -> Vec<Message> {
+        self.entries
+            .iter()
+            .filter_map(|entry| Message::demarshal(&entry.message).ok())
+            .collect()
+    }
+
+    /// The recorded signals, parsed into events. Signals that don't
+    /// parse are left out, as the [`EventManager`](crate::EventManager)
+    /// would.
+    pub fn events(&self) -> Vec<Event> {
+        self.replay_entries()
+            .into_iter()
+            .filter_map(|(_, replayed)| match replayed {
+                Replayed::Event(event) => Some(event),
+                Replayed::State(_) => None,
+            })
+            .collect()
+    }
+
+    /// Like [`events`](Self::events), but as a stream yielding each
+    /// event as long after the previous one as it was recorded, for
+    /// code that reacts to timing such as
+    /// [`coalesce`](crate::coalesce).
+    pub fn replay(&self) -> BoxStream<'static, Event> {
+        let mut previous = Duration::ZERO;
+        let events: Vec<(Duration, Event)> = self
+            .replay_entries()
+            .into_iter()
+            .filter_map(|(at, replayed)| match replayed {
+                Replayed::Event(event) => {
+                    let gap = at.saturating_sub(previous);
+                    previous = at;
+                    Some((gap, event))
+                }
+                Replayed::State(_) => None,
+            })
+            .collect();
+
+        stream::iter(events)
+            .then(|(gap, event)| async move {
+                runtime::sleep(gap).await;
+                event
+            })
+            .boxed()
+    }
+
+    /// The state of each player snapshotted during the recording, as
+    /// of its end, by name: the snapshot, with the changes signalled
+    /// after it applied.
+    pub fn states(&self) -> Vec<PlayerState> {
+        let mut states = BTreeMap::new();
+        for (_, replayed) in self.replay_entries() {
+            match replayed {
+                Replayed::State(state) => {
+                    states.insert(state.player.clone(), state);
+                }
+                Replayed::Event(Event::PropertiesChanged(changed)) => {
+                    if let Some(state) = states.get_mut(&changed.player) {
+                        state.apply(changed.properties);
+                    }
+                }
+                Replayed::Event(Event::Seeked(seeked)) => {
+                    if let Some(state) = states.get_mut(&seeked.player) {
+                        state.position = Some(seeked.position);
+                    }
+                }
+                Replayed::Event(Event::PlayerLifecycle(_)) => {}
+            }
+        }
+
+        states.into_values().collect()
+    }
+
+    /// Parses every entry, resolving senders along the way.
+    fn replay_entries(&self) -> Vec<(Duration, Replayed)> {
+        let mut players: HashMap<String, String> = self
+            .owners
+            .iter()
+            .map(|(name, owner)| (owner.clone(), name.clone()))
+            .collect();
+
+        let mut replayed = Vec::new();
+        for entry in &self.entries {
+            let msg = match Message::demarshal(&entry.message) {
+                Ok(msg) => msg,
+                Err(_) => continue,
+            };
+            match &entry.kind {
+                Kind::Properties(player) => {
+                    if let Ok(properties) = msg.read1::<PropMap>() {
+                        let state = PlayerState::from_properties(player.clone(), properties);
+                        replayed.push((entry.at, Replayed::State(state)));
+                    }
+                }
+                Kind::Signal => {
+                    if let Ok((name, _, new_owner)) = msg.read3::<&str, &str, &str>() {
+                        if let Some(name) = name.strip_prefix(MPRIS_PREFIX) {
+                            players.retain(|_, player| player != name);
+                            if !new_owner.is_empty() {
+                                players.insert(new_owner.to_string(), name.to_string());
+                            }
+                        }
+                    }
+
+                    let sender = msg.sender().map(|s| s.to_string()).unwrap_or_default();
+                    let player = players.get(&sender).cloned().unwrap_or(sender);
+                    if let Ok(event) = Event::parse(&msg, player) {
+                        replayed.push((entry.at, Replayed::Event(event)));
+                    }
+                }
+            }
+        }
+
+        replayed
+    }
+}
+
+impl fmt::Display for Fixture {
+    /// Writes the fixture in its text form.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        for (name, owner) in &self.owners {
+            writeln!(f, "owner {} {}", name, owner)?;
+        }
+        for entry in &self.entries {
+            let at = entry.at.as_millis();
+            match &entry.kind {
+                Kind::Signal => write!(f, "signal {}", at)?,
+                Kind::Properties(player) => write!(f, "properties {} {}", at, player)?,
+            }
+            write!(f, " ")?;
+            for byte in &entry.message {
+                write!(f, "{:02x}", byte)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Fixture {
+    type Err = Error;
+
+    /// Reads a fixture back from its text form.
+    ///
+    /// # Errors
+    /// Returns [`Error::Parse`] if `text` isn't a fixture, or a
+    /// message in it is malformed.
+    fn from_str(text: &str) -> Result<Fixture> {
+        let mut lines = text.lines().enumerate();
+        if lines.next().map(|(_, line)| line.trim()) != Some(HEADER) {
+            return Err(Error::Parse("The text is not a pris fixture.".into()));
+        }
+
+        let mut fixture = Fixture::default();
+        for (number, line) in lines {
+            let malformed =
+                || Error::Parse(format!("Line {} of the fixture is malformed.", number + 1));
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (kind, at, message) = match fields.as_slice() {
+                [] => continue,
+                ["owner", name, owner] => {
+                    fixture.owners.push((name.to_string(), owner.to_string()));
+                    continue;
+                }
+                ["signal", at, message] => (Kind::Signal, at, message),
+                ["properties", at, player, message] => {
+                    (Kind::Properties(player.to_string()), at, message)
+                }
+                _ => return Err(malformed()),
+            };
+
+            let at = Duration::from_millis(at.parse().map_err(|_| malformed())?);
+            let message = parse_hex(message).ok_or_else(malformed)?;
+            Message::demarshal(&message).map_err(|_| malformed())?;
+            fixture.entries.push(Entry { at, kind, message });
+        }
+
+        Ok(fixture)
+    }
+}
+
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
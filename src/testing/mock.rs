@@ -0,0 +1,440 @@
+use crate::{
+    spec,
+    util::{self, MPRIS_PREFIX},
+    Error, Micros, Result, Value,
+};
+use dbus::{
+    arg::{PropMap, RefArg, Variant},
+    channel::{MatchingReceiver, Sender, Token},
+    message::{MatchRule, Message},
+    nonblock::{stdintf::org_freedesktop_dbus::RequestNameReply, SyncConnection},
+    strings::ErrorName,
+};
+use std::{
+    collections::HashMap,
+    ffi::CString,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+const ROOT_INTERFACE: &str = spec::ROOT.name;
+const PLAYER_INTERFACE: &str = spec::PLAYER.name;
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+/// A method call received by a [`MockPlayer`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct MockCall {
+    /// The interface the method belongs to.
+    pub interface: String,
+    /// The name of the method, such as `Pause`.
+    pub member: String,
+    /// The arguments it was called with.
+    pub args: Vec<Value>,
+}
+
+struct MockState {
+    root: PropMap,
+    player: PropMap,
+    calls: Vec<MockCall>,
+    errors: HashMap<String, ErrorName<'static>>,
+}
+
+/// An MPRIS player served from the test itself.
+///
+/// It starts out stopped, with no track, and able to do everything
+/// but raise itself; [`set_property`](Self::set_property) changes
+/// that. Method calls get an empty reply, unless an error was set
+/// for them with [`set_error`](Self::set_error), and don't change any
+/// properties, except for `Set`, which also emits `PropertiesChanged`
+/// like a real player would.
+///
+/// Each `MockPlayer` needs a connection of its own. It leaves the bus
+/// when dropped.
+pub struct MockPlayer<'a> {
+    name: String,
+    conn: &'a SyncConnection,
+    state: Arc<Mutex<MockState>>,
+    token: Token,
+}
+
+impl<'a> MockPlayer<'a> {
+    /// Registers `org.mpris.MediaPlayer2.<name>` on `conn`, and starts
+    /// serving the player there.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the name is already taken, or couldn't be
+    /// requested.
+    pub async fn register(name: &str, conn: &'a SyncConnection) -> Result<MockPlayer<'a>> {
+        let state = Arc::new(Mutex::new(MockState::new(name)));
+        let served = state.clone();
+        // Served before the name is taken, so that no call made as
+        // soon as the player shows up goes unanswered
+        let token = conn.start_receive(
+            MatchRule::new_method_call().with_path(MPRIS_PATH),
+            Box::new(move |msg, conn| {
+                let (reply, signal) = served.lock().unwrap().answer(&msg);
+                let _ = conn.send(reply);
+                if let Some(signal) = signal {
+                    let _ = conn.send(signal);
+                }
+                true
+            }),
+        );
+
+        let bus_name = format!("{}{}", MPRIS_PREFIX, name);
+        let taken = match conn
+            .request_name(bus_name.clone(), false, false, true)
+            .await
+        {
+            Ok(RequestNameReply::PrimaryOwner) => Ok(()),
+            Ok(_) => Err(Error::InvalidArgument(format!(
+                "The name {} is already taken.",
+                bus_name
+            ))),
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = taken {
+            conn.stop_receive(token);
+            return Err(e);
+        }
+
+        Ok(MockPlayer {
+            name: name.to_string(),
+            conn,
+            state,
+            token,
+        })
+    }
+
+    /// The name the player was registered under, without the MPRIS
+    /// prefix.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Sets the property `name` of the root or the Player interface,
+    /// without emitting `PropertiesChanged`; see
+    /// [`emit_properties_changed`](Self::emit_properties_changed).
+    /// Properties neither interface has are added to the Player one.
+    pub fn set_property<T: RefArg + 'static>(&self, name: &str, value: T) {
+        let mut state = self.state.lock().unwrap();
+        let properties = if state.root.contains_key(name) {
+            &mut state.root
+        } else {
+            &mut state.player
+        };
+        properties.insert(name.to_string(), Variant(Box::new(value)));
+    }
+
+    /// The current value of the property `name`.
+    pub fn property(&self, name: &str) -> Option<Value> {
+        let state = self.state.lock().unwrap();
+        let value = state.root.get(name).or_else(|| state.player.get(name))?;
+        Some(Value::from_dbus(&*value.0))
+    }
+
+    /// Replaces the `Metadata` property.
+    pub fn set_metadata(&self, metadata: PropMap) {
+        self.set_property("Metadata", metadata);
+    }
+
+    /// Sets the entry `key` of the `Metadata` property, such as
+    /// `xesam:title`.
+    pub fn insert_metadata<T: RefArg + 'static>(&self, key: &str, value: T) {
+        let mut state = self.state.lock().unwrap();
+        let mut metadata = state
+            .player
+            .get("Metadata")
+            .and_then(|value| util::as_prop_map(&*value.0))
+            .unwrap_or_default();
+        metadata.insert(key.to_string(), Variant(Box::new(value)));
+        state
+            .player
+            .insert("Metadata".to_string(), Variant(Box::new(metadata)));
+    }
+
+    /// Makes calls of the method `member` fail with the D-Bus error
+    /// `error`, such as `org.freedesktop.DBus.Error.NotSupported`,
+    /// or answer normally again if it is `None`.
+    ///
+    /// # Errors
+    /// Returns an `Err` if `error` isn't a valid error name.
+    pub fn set_error(&self, member: &str, error: Option<&str>) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        match error {
+            Some(error) => {
+                let error = ErrorName::new(error.to_string()).map_err(Error::InvalidArgument)?;
+                state.errors.insert(member.to_string(), error);
+            }
+            None => {
+                state.errors.remove(member);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Every method call received so far, property accesses
+    /// included, in order.
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    /// How many times the method `member` was called.
+    pub fn calls_to(&self, member: &str) -> usize {
+        let state = self.state.lock().unwrap();
+        state
+            .calls
+            .iter()
+            .filter(|call| call.member == member)
+            .count()
+    }
+
+    /// Forgets the calls received so far.
+    pub fn clear_calls(&self) {
+        self.state.lock().unwrap().calls.clear();
+    }
+
+    /// Emits `PropertiesChanged` with the current values of the
+    /// properties `names`, one signal per interface they belong to.
+    /// Names of properties the player doesn't have are sent as
+    /// invalidated.
+    ///
+    /// # Errors
+    /// Returns [`Error::Disconnected`] if the signal couldn't be sent.
+    pub fn emit_properties_changed(&self, names: &[&str]) -> Result<()> {
+        let signals = {
+            let state = self.state.lock().unwrap();
+            let mut root = PropMap::new();
+            let mut player = PropMap::new();
+            let mut invalidated = Vec::new();
+            for &name in names {
+                if let Some(value) = state.root.get(name) {
+                    root.insert(name.to_string(), Variant(value.0.box_clone()));
+                } else if let Some(value) = state.player.get(name) {
+                    player.insert(name.to_string(), Variant(value.0.box_clone()));
+                } else {
+                    invalidated.push(name.to_string());
+                }
+            }
+
+            let mut signals = Vec::new();
+            if !root.is_empty() {
+                signals.push(properties_changed(ROOT_INTERFACE, root, Vec::new()));
+            }
+            if !player.is_empty() || !invalidated.is_empty() {
+                signals.push(properties_changed(PLAYER_INTERFACE, player, invalidated));
+            }
+            signals
+        };
+
+        for signal in signals {
+            self.send(signal)?;
+        }
+        Ok(())
+    }
+
+    /// Emits `Seeked` with `position`, also setting the `Position`
+    /// property to it.
+    ///
+    /// # Errors
+    /// Returns [`Error::Disconnected`] if the signal couldn't be sent.
+    pub fn emit_seeked(&self, position: Duration) -> Result<()> {
+        let micros = Micros::from_duration(position);
+        self.set_property("Position", micros.get());
+        let signal = Message::new_signal(MPRIS_PATH, PLAYER_INTERFACE, "Seeked")
+            .unwrap()
+            .append1(micros);
+        self.send(signal)
+    }
+
+    fn send(&self, msg: Message) -> Result<()> {
+        self.conn
+            .send(msg)
+            .map(|_| ())
+            .map_err(|_| Error::Disconnected)
+    }
+}
+
+impl Drop for MockPlayer<'_> {
+    fn drop(&mut self) {
+        self.conn.stop_receive(self.token);
+        let release = Message::new_method_call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "ReleaseName",
+        )
+        .unwrap()
+        .append1(format!("{}{}", MPRIS_PREFIX, self.name));
+        let _ = self.conn.send(release);
+    }
+}
+
+impl MockState {
+    fn new(name: &str) -> MockState {
+        let mut root = PropMap::new();
+        let mut insert = |name: &str, value: Box<dyn RefArg>| {
+            root.insert(name.to_string(), Variant(value));
+        };
+        insert("CanQuit", Box::new(true));
+        insert("CanRaise", Box::new(false));
+        insert("HasTrackList", Box::new(false));
+        insert("Identity", Box::new(name.to_string()));
+        insert("SupportedUriSchemes", Box::<Vec<String>>::default());
+        insert("SupportedMimeTypes", Box::<Vec<String>>::default());
+
+        let mut player = PropMap::new();
+        let mut insert = |name: &str, value: Box<dyn RefArg>| {
+            player.insert(name.to_string(), Variant(value));
+        };
+        insert("PlaybackStatus", Box::new("Stopped".to_string()));
+        insert("LoopStatus", Box::new("None".to_string()));
+        insert("Rate", Box::new(1.0));
+        insert("Shuffle", Box::new(false));
+        insert("Metadata", Box::<PropMap>::default());
+        insert("Volume", Box::new(1.0));
+        insert("Position", Box::new(0i64));
+        insert("MinimumRate", Box::new(1.0));
+        insert("MaximumRate", Box::new(1.0));
+        for &flag in &[
+            "CanGoNext",
+            "CanGoPrevious",
+            "CanPlay",
+            "CanPause",
+            "CanSeek",
+            "CanControl",
+        ] {
+            insert(flag, Box::new(true));
+        }
+
+        MockState {
+            root,
+            player,
+            calls: Vec::new(),
+            errors: HashMap::new(),
+        }
+    }
+
+    /// The properties of `interface`.
+    fn properties(&mut self, interface: &str) -> Option<&mut PropMap> {
+        match interface {
+            ROOT_INTERFACE => Some(&mut self.root),
+            PLAYER_INTERFACE => Some(&mut self.player),
+            _ => None,
+        }
+    }
+
+    /// Records `msg` and builds the reply to it, along with the
+    /// signal it causes, if any.
+    fn answer(&mut self, msg: &Message) -> (Message, Option<Message>) {
+        let interface = msg.interface().map(|i| i.to_string()).unwrap_or_default();
+        let member = msg.member().map(|m| m.to_string()).unwrap_or_default();
+
+        let mut args = Vec::new();
+        let mut iter = msg.iter_init();
+        while let Some(arg) = iter.get_refarg() {
+            args.push(Value::from_dbus(&*arg));
+            iter.next();
+        }
+        self.calls.push(MockCall {
+            interface: interface.clone(),
+            member: member.clone(),
+            args,
+        });
+
+        if let Some(error) = self.errors.get(&member) {
+            return (error_reply(msg, error, "Injected by the mock player"), None);
+        }
+
+        match (interface.as_str(), member.as_str()) {
+            (PROPERTIES_INTERFACE, "Get") => (self.get(msg), None),
+            (PROPERTIES_INTERFACE, "GetAll") => (self.get_all(msg), None),
+            (PROPERTIES_INTERFACE, "Set") => self.set(msg),
+            (ROOT_INTERFACE, member) if spec::ROOT.method(member).is_some() => {
+                (msg.method_return(), None)
+            }
+            (PLAYER_INTERFACE, member) if spec::PLAYER.method(member).is_some() => {
+                (msg.method_return(), None)
+            }
+            _ => (
+                error_reply(msg, &standard_error("UnknownMethod"), "No such method"),
+                None,
+            ),
+        }
+    }
+
+    fn get(&mut self, msg: &Message) -> Message {
+        let (interface, name): (String, String) = match msg.read2() {
+            Ok(args) => args,
+            Err(e) => return error_reply(msg, &standard_error("InvalidArgs"), &e.to_string()),
+        };
+        match self.properties(&interface) {
+            Some(properties) => match properties.get(&name) {
+                Some(value) => msg.method_return().append1(Variant(value.0.box_clone())),
+                None => error_reply(msg, &standard_error("UnknownProperty"), &name),
+            },
+            None => error_reply(msg, &standard_error("InvalidArgs"), "No such interface"),
+        }
+    }
+
+    fn get_all(&mut self, msg: &Message) -> Message {
+        let interface: String = match msg.read1() {
+            Ok(interface) => interface,
+            Err(e) => return error_reply(msg, &standard_error("InvalidArgs"), &e.to_string()),
+        };
+        match self.properties(&interface) {
+            Some(properties) => msg
+                .method_return()
+                .append1(util::clone_prop_map(properties)),
+            None => error_reply(msg, &standard_error("InvalidArgs"), "No such interface"),
+        }
+    }
+
+    fn set(&mut self, msg: &Message) -> (Message, Option<Message>) {
+        let (interface, name, value): (String, String, Variant<Box<dyn RefArg>>) = match msg.read3()
+        {
+            Ok(args) => args,
+            Err(e) => {
+                let reply = error_reply(msg, &standard_error("InvalidArgs"), &e.to_string());
+                return (reply, None);
+            }
+        };
+        let properties = match self.properties(&interface) {
+            Some(properties) if properties.contains_key(&name) => properties,
+            Some(_) => {
+                return (
+                    error_reply(msg, &standard_error("UnknownProperty"), &name),
+                    None,
+                )
+            }
+            None => {
+                let reply = error_reply(msg, &standard_error("InvalidArgs"), "No such interface");
+                return (reply, None);
+            }
+        };
+
+        let mut changed = PropMap::new();
+        changed.insert(name.clone(), Variant(value.0.box_clone()));
+        properties.insert(name, value);
+        let signal = properties_changed(&interface, changed, Vec::new());
+        (msg.method_return(), Some(signal))
+    }
+}
+
+/// The standard D-Bus error `name`.
+fn standard_error(name: &str) -> ErrorName<'static> {
+    ErrorName::new(format!("org.freedesktop.DBus.Error.{}", name)).unwrap()
+}
+
+fn error_reply(msg: &Message, error: &ErrorName<'_>, message: &str) -> Message {
+    let message = CString::new(message.replace('\0', "")).unwrap();
+    msg.error(error, &message)
+}
+
+fn properties_changed(interface: &str, changed: PropMap, invalidated: Vec<String>) -> Message {
+    Message::new_signal(MPRIS_PATH, PROPERTIES_INTERFACE, "PropertiesChanged")
+        .unwrap()
+        .append3(interface, changed, invalidated)
+}
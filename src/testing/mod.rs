@@ -0,0 +1,39 @@
+//! Stand-ins for real players, for testing code built on pris
+//! without one on the bus.
+//!
+//! A [`MockPlayer`] serves the root and Player interfaces on a
+//! connection of its own, answering property reads from values the
+//! test sets, recording every method call it receives, and emitting
+//! signals on demand. Paired with a private `dbus-daemon` and
+//! [`connect_to`](crate::connect_to), nothing outside the test is
+//! involved.
+//!
+//! Some players can't be run where the tests are, and misbehave in
+//! ways a mock wouldn't think to. For those, a [`Recorder`] captures
+//! what a real player sends into a [`Fixture`], which replays it
+//! through the same parsing as the [`EventManager`](crate::EventManager)
+//! with no bus at all.
+//!
+//! # Example
+//! ```no_run
+//! use pris::{testing::MockPlayer, Player};
+//!
+//! # async fn example() -> pris::Result<()> {
+//! let mock_conn = pris::connect()?;
+//! let mock = MockPlayer::register("vlc", &mock_conn).await?;
+//! mock.set_property("PlaybackStatus", "Playing".to_string());
+//!
+//! let conn = pris::connect()?;
+//! let player = Player::try_new("vlc", &conn).await?;
+//! player.pause().await?;
+//! assert_eq!(mock.calls_to("Pause"), 1);
+//! # Ok(())
+//! # }
+//! ```
+#[cfg(feature = "events")]
+mod fixture;
+mod mock;
+
+#[cfg(feature = "events")]
+pub use fixture::{Fixture, Recorder};
+pub use mock::{MockCall, MockPlayer};
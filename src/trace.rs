@@ -0,0 +1,80 @@
+//! Instrumentation with `tracing`, compiled out entirely unless the
+//! `tracing` feature is enabled. The names of spans and fields here
+//! are documented at the crate root, and kept stable.
+#[cfg(feature = "tracing")]
+use crate::Error;
+use crate::Result;
+use std::future::Future;
+
+/// Emits a `tracing` event, such as `event!(debug, rule = %rule,
+/// "match added")`. Without the feature, nothing is emitted and the
+/// fields aren't evaluated.
+macro_rules! event {
+    ($level:ident, $($field:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($field)+);
+    }};
+}
+pub(crate) use event;
+
+/// Runs `call`, the call `operation` made to `player`, in a
+/// `pris.call` span recording how long it took and how it failed.
+#[cfg(feature = "tracing")]
+pub(crate) async fn call<T>(
+    player: &str,
+    operation: &str,
+    call: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    use std::time::Instant;
+    use tracing::{field, Instrument};
+
+    let span = tracing::debug_span!(
+        "pris.call",
+        player,
+        operation,
+        duration_ms = field::Empty,
+        error_kind = field::Empty,
+    );
+    let started = Instant::now();
+    let result = call.instrument(span.clone()).await;
+    span.record("duration_ms", started.elapsed().as_millis() as u64);
+    if let Err(e) = &result {
+        span.record("error_kind", error_kind(e));
+        tracing::debug!(parent: &span, error_kind = error_kind(e), error = %e, "call failed");
+    }
+
+    result
+}
+
+/// Without the feature, `call` is passed through as it is.
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn call<T, F>(_player: &str, _operation: &str, call: F) -> F
+where
+    F: Future<Output = Result<T>>,
+{
+    call
+}
+
+/// The `error_kind` field of `error`: its variant, in snake case.
+#[cfg(feature = "tracing")]
+pub(crate) fn error_kind(error: &Error) -> &'static str {
+    match error {
+        Error::InvalidPlayer(_) => "invalid_player",
+        Error::UnknownPlayer { .. } => "unknown_player",
+        Error::AmbiguousPlayer { .. } => "ambiguous_player",
+        Error::PlayerGone { .. } => "player_gone",
+        Error::NoActiveTrack(_) => "no_active_track",
+        Error::DBus(_) => "dbus",
+        Error::Timeout { .. } => "timeout",
+        Error::UnknownProperty { .. } => "unknown_property",
+        Error::TypeMismatch { .. } => "type_mismatch",
+        Error::Retried { .. } => "retried",
+        Error::UnsupportedOperation { .. } => "unsupported_operation",
+        Error::Parse(_) => "parse",
+        Error::InvalidArgument(_) => "invalid_argument",
+        Error::Disconnected => "disconnected",
+        Error::Connection { .. } => "connection",
+        Error::NotFound { .. } => "not_found",
+        Error::MatchRemoval { .. } => "match_removal",
+    }
+}
@@ -1,77 +1,523 @@
-use crate::{Player, Result};
+use crate::{runtime, spec, status, Error, Micros, Player, PlayerCandidate, Result};
 use dbus::{
-    arg::PropMap,
-    nonblock::{Proxy, SyncConnection},
+    arg::{cast, PropMap, RefArg, Variant},
+    nonblock::{stdintf::org_freedesktop_dbus::Properties, Proxy, SyncConnection},
+    strings::BusName,
 };
-use dbus_tokio::connection;
-use std::{sync::Arc, time::Duration};
+use futures::future;
+use std::{borrow::Cow, ops::Deref, sync::Arc, time::Duration};
+#[cfg(feature = "events")]
+use std::{collections::HashMap, sync::Mutex};
 
-const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+pub(crate) const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
 
+/// How long players are given to report their `PlaybackStatus` for
+/// sorting.
+pub(crate) const STATUS_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// How long the players an ambiguous name matches are given to
+/// report their `Identity`.
+pub(crate) const IDENTITY_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// A connection that is either borrowed or shared, so that the
+/// borrowed and owned forms of a type can share one implementation.
+#[derive(Clone)]
+pub(crate) enum ConnRef<'a> {
+    Borrowed(&'a SyncConnection),
+    #[cfg(feature = "events")]
+    Owned(Arc<SyncConnection>),
+}
+
+impl Deref for ConnRef<'_> {
+    type Target = SyncConnection;
+
+    fn deref(&self) -> &SyncConnection {
+        match self {
+            ConnRef::Borrowed(conn) => conn,
+            #[cfg(feature = "events")]
+            ConnRef::Owned(conn) => conn,
+        }
+    }
+}
+
+/// Whether a player with the exact name `player_name` is on the
+/// bus, asked with a single `NameHasOwner` call rather than by
+/// listing every name on it. Names that aren't valid bus names
+/// belong to no player.
 pub async fn validate(player_name: &str, conn: &SyncConnection) -> Result<bool> {
-    Ok(get_all_names(&conn)
-        .await?
-        .contains(&player_name.to_string()))
+    let name = match player_bus_name(player_name) {
+        Some(name) => name,
+        None => return Ok(false),
+    };
+
+    let proxy = Proxy::new("org.freedesktop.DBus", "/", Duration::from_secs(1), conn);
+    let (exists,): (bool,) = proxy
+        .method_call("org.freedesktop.DBus", "NameHasOwner", (&*name,))
+        .await?;
+
+    Ok(exists)
+}
+
+/// The names of the MPRIS players on the bus, without the
+/// `org.mpris.MediaPlayer2.` prefix, such as `vlc` or
+/// `firefox.instance_1234`.
+///
+/// Only well-known names are listed: the unique names of players,
+/// such as `:1.42`, and services other than MPRIS players are left
+/// out. With no player running, the list is empty.
+///
+/// The names are sorted, so that the players come in the same order
+/// from one call to the next; `ListNames` lists them in no particular
+/// order. Everything built on this list keeps its order, such as
+/// [`Player::all`], [`get_all_players`] and
+/// [`PlayerFinder`](crate::PlayerFinder). [`list_players_ordered`]
+/// keeps the order of the bus instead.
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> pris::Result<()> {
+/// let conn = pris::get_connection();
+/// for name in pris::list_players(&conn).await? {
+///     println!("{}", name);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+/// May `Err` if the bus can't be asked.
+pub async fn list_players(conn: &SyncConnection) -> Result<Vec<String>> {
+    list_players_ordered(conn, PlayerOrder::Name).await
+}
+
+/// The order players are listed in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PlayerOrder {
+    /// Sorted by name, such as `firefox.instance_1_23` before `vlc`.
+    #[default]
+    Name,
+    /// As `ListNames` lists them, which can change from one call to
+    /// the next.
+    Bus,
+    /// Playing players first, then paused and then stopped ones, as
+    /// ranked by [`status_rank`](crate::status_rank), and by name
+    /// among equals. Players are asked for their status at once, and
+    /// those that don't answer within a quarter of a second come
+    /// last.
+    Status,
 }
 
-async fn get_all_names(conn: &SyncConnection) -> Result<Vec<String>> {
+/// Same as [`list_players`], in the order `order`.
+///
+/// # Errors
+/// May `Err` if the bus can't be asked.
+pub async fn list_players_ordered(
+    conn: &SyncConnection,
+    order: PlayerOrder,
+) -> Result<Vec<String>> {
     let proxy = Proxy::new("org.freedesktop.DBus", "/", Duration::from_secs(1), conn);
     let (services,): (Vec<String>,) = proxy
         .method_call("org.freedesktop.DBus", "ListNames", ())
         .await?;
 
-    let active_players: Vec<String> = services
+    let mut names = player_names(services);
+    if order != PlayerOrder::Bus {
+        names.sort();
+    }
+    if order == PlayerOrder::Status {
+        let ranks = future::join_all(names.iter().map(|name| async move {
+            let destination = format!("{}{}", MPRIS_PREFIX, name);
+            let proxy = Proxy::new(destination, "/org/mpris/MediaPlayer2", STATUS_TIMEOUT, conn);
+            let status = proxy.get::<String>(spec::PLAYER.name, "PlaybackStatus");
+            status::rank_of(status.await.ok().as_deref())
+        }))
+        .await;
+        // The sort is stable, so equals stay sorted by name
+        let mut ranked: Vec<(u8, String)> = ranks.into_iter().zip(names).collect();
+        ranked.sort_by_key(|(rank, _)| *rank);
+        names = ranked.into_iter().map(|(_, name)| name).collect();
+    }
+    Ok(names)
+}
+
+/// The well-known name of the player `player_name`, if it can be a
+/// bus name.
+pub(crate) fn player_bus_name(player_name: &str) -> Option<BusName<'static>> {
+    BusName::new(format!("{}{}", MPRIS_PREFIX, player_name)).ok()
+}
+
+/// The names of the MPRIS players among the names on the bus,
+/// without the `org.mpris.MediaPlayer2.` prefix, in the order given.
+pub(crate) fn player_names(services: Vec<String>) -> Vec<String> {
+    services
         .into_iter()
         .filter_map(|name| {
-            name.strip_prefix(&MPRIS_PREFIX)
-                .map_or_else(|| None, |s| Some(s.to_string()))
+            name.strip_prefix(MPRIS_PREFIX)
+                .filter(|s| !s.is_empty())
+                .map(ToString::to_string)
         })
-        .collect();
-    Ok(active_players)
+        .collect()
+}
+
+/// The application the player `name` belongs to: `name` without its
+/// instance suffix, such as `firefox` for `firefox.instance_1_23` or
+/// `vlc.instance7389`, or `name` itself if it has none.
+pub(crate) fn app_name(name: &str) -> &str {
+    match name.rsplit_once('.') {
+        Some((app, suffix)) if suffix.starts_with("instance") => app,
+        _ => name,
+    }
+}
+
+/// The players among `names` named after `name` with an instance
+/// suffix, such as `firefox.instance_1234`.
+pub(crate) fn instances_of(name: &str, names: &[String]) -> Vec<String> {
+    let prefix = format!("{}.", name);
+    names
+        .iter()
+        .filter(|n| n.starts_with(&prefix))
+        .cloned()
+        .collect()
+}
+
+/// Resolves `name` to the one player it refers to, as described on
+/// [`Player::try_new`].
+///
+/// The names on the bus are only listed if no player has the exact
+/// name `name`, and are then also those reported if none is named
+/// after it.
+pub(crate) async fn resolve_name(name: &str, conn: &SyncConnection) -> Result<String> {
+    if validate(name, conn).await? {
+        return Ok(name.to_string());
+    }
+
+    let names = list_players(conn).await?;
+    let mut instances = instances_of(name, &names);
+    match instances.len() {
+        0 => return Err(Error::unknown_player(name, names)),
+        1 => return Ok(instances.remove(0)),
+        _ => {}
+    }
+
+    // Instances that left since being listed aren't in the way
+    let candidates = describe_players(instances, conn).await;
+    pick_candidate(name, candidates)
+}
+
+/// The one player `name` refers to among the `candidates` still on
+/// the bus.
+pub(crate) fn pick_candidate(name: &str, mut candidates: Vec<PlayerCandidate>) -> Result<String> {
+    match candidates.len() {
+        0 => Err(Error::InvalidPlayer(name.to_string())),
+        1 => Ok(candidates.remove(0).name),
+        _ => Err(Error::AmbiguousPlayer {
+            requested: name.to_string(),
+            candidates,
+        }),
+    }
+}
+
+/// Looks up the owner and identity of each of the players `names`,
+/// skipping those that aren't on the bus anymore.
+async fn describe_players(names: Vec<String>, conn: &SyncConnection) -> Vec<PlayerCandidate> {
+    let mut candidates = Vec::with_capacity(names.len());
+    for name in names {
+        let unique_name = match get_name_owner(&format!("{}{}", MPRIS_PREFIX, name), conn).await {
+            Ok(owner) => owner,
+            Err(_) => continue,
+        };
+
+        let proxy = Proxy::new(
+            unique_name.as_str(),
+            "/org/mpris/MediaPlayer2",
+            IDENTITY_TIMEOUT,
+            conn,
+        );
+        let identity = proxy
+            .get::<String>("org.mpris.MediaPlayer2", "Identity")
+            .await
+            .ok();
+
+        candidates.push(PlayerCandidate {
+            name,
+            unique_name,
+            identity,
+        });
+    }
+
+    candidates
+}
+
+pub(crate) async fn get_name_owner(name: &str, conn: &SyncConnection) -> Result<String> {
+    let proxy = Proxy::new("org.freedesktop.DBus", "/", Duration::from_secs(1), conn);
+    let (owner,): (String,) = proxy
+        .method_call("org.freedesktop.DBus", "GetNameOwner", (name,))
+        .await?;
+
+    Ok(owner)
+}
+
+/// Resolves the sender of a message (usually a unique name such
+/// as `:1.42`) to the name of the MPRIS player that owns it.
+pub async fn resolve_sender(sender: &str, conn: &SyncConnection) -> Result<Option<String>> {
+    if !sender.starts_with(':') {
+        return Ok(sender.strip_prefix(MPRIS_PREFIX).map(str::to_string));
+    }
+
+    for name in list_players(conn).await? {
+        let full_name = format!("{}{}", MPRIS_PREFIX, name);
+        if let Ok(owner) = get_name_owner(&full_name, conn).await {
+            if owner == sender {
+                return Ok(Some(name));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// The players each unique name owns, and the other way around.
+#[cfg(feature = "events")]
+#[derive(Default)]
+struct SenderState {
+    /// Unique names to the players they own or owned.
+    players: HashMap<String, String>,
+    /// Players to the unique names currently owning them.
+    owners: HashMap<String, String>,
+    /// Whether `NameOwnerChanged` signals are being recorded, so
+    /// that the maps are complete and up to date.
+    tracking: bool,
+}
+
+/// Remembers which player each unique name resolved to, and which
+/// unique name owns each player, so that attributing and filtering
+/// signals doesn't take a round trip to the bus for each of them.
+///
+/// Entries are filled in lazily, and kept up to date from
+/// `NameOwnerChanged` signals once an [`EventManager`] tracks names.
+/// Until then, owners are looked up again every time, since a
+/// player may have restarted under a new unique name.
+///
+/// [`EventManager`]: crate::EventManager
+#[cfg(feature = "events")]
+#[derive(Default)]
+pub(crate) struct SenderCache(Mutex<SenderState>);
+
+#[cfg(feature = "events")]
+impl SenderCache {
+    /// Resolves `sender` like [`resolve_sender`], falling back to
+    /// the raw sender name if it isn't an MPRIS player.
+    ///
+    /// While names are tracked, every player is already known, so
+    /// this never touches the bus. Otherwise, a sender that isn't
+    /// known yet has the owners of every player looked up at once,
+    /// which saves lookups for the senders that follow.
+    pub(crate) async fn resolve(&self, sender: &str, conn: &SyncConnection) -> Result<String> {
+        if !sender.starts_with(':') {
+            let player = sender.strip_prefix(MPRIS_PREFIX).unwrap_or(sender);
+            return Ok(player.to_string());
+        }
+        {
+            let state = self.0.lock().unwrap();
+            if let Some(player) = state.players.get(sender) {
+                return Ok(player.clone());
+            }
+            if state.tracking {
+                return Ok(sender.to_string());
+            }
+        }
+
+        self.look_up(conn, true).await?;
+        Ok(self.cached(sender).unwrap_or_else(|| sender.to_string()))
+    }
+
+    /// The unique name owning `player`, taken from the cache while
+    /// names are tracked, and otherwise looked up and recorded.
+    pub(crate) async fn owner(&self, player: &str, conn: &SyncConnection) -> Result<String> {
+        {
+            let state = self.0.lock().unwrap();
+            if let Some(owner) = state.owners.get(player).filter(|_| state.tracking) {
+                return Ok(owner.clone());
+            }
+        }
+
+        let owner = get_name_owner(&format!("{}{}", MPRIS_PREFIX, player), conn).await?;
+        self.record(player, &owner);
+        Ok(owner)
+    }
+}
+
+#[cfg(feature = "events")]
+impl SenderCache {
+    /// Looks `sender` up without touching the bus.
+    pub(crate) fn cached(&self, sender: &str) -> Option<String> {
+        self.0.lock().unwrap().players.get(sender).cloned()
+    }
+
+    /// Records that `owner` owns `player`.
+    fn record(&self, player: &str, owner: &str) {
+        let mut state = self.0.lock().unwrap();
+        state.owners.insert(player.to_string(), owner.to_string());
+        state.players.insert(owner.to_string(), player.to_string());
+    }
+
+    /// Records a `NameOwnerChanged` signal from the bus.
+    pub(crate) fn owner_changed(&self, name: &str, old_owner: &str, new_owner: &str) {
+        let player = match name.strip_prefix(MPRIS_PREFIX) {
+            Some(player) => player,
+            None => return,
+        };
+
+        // Unique names are never reused, so the old owner is kept
+        // around for signals it sent that haven't been parsed yet,
+        // unless it takes on another player
+        if new_owner.is_empty() {
+            let mut state = self.0.lock().unwrap();
+            if state.owners.get(player).map(String::as_str) == Some(old_owner) {
+                state.owners.remove(player);
+            }
+        } else {
+            self.record(player, new_owner);
+        }
+    }
+
+    /// Records the owner of every player currently on the bus,
+    /// without overwriting what was recorded in the meantime.
+    pub(crate) async fn seed(&self, conn: &SyncConnection) -> Result<()> {
+        self.look_up(conn, false).await
+    }
+
+    /// Looks up the owner of every player on the bus, recording it
+    /// over what is already there if `overwrite` is set, and
+    /// otherwise only where nothing is.
+    async fn look_up(&self, conn: &SyncConnection, overwrite: bool) -> Result<()> {
+        for name in list_players(conn).await? {
+            let full_name = format!("{}{}", MPRIS_PREFIX, name);
+            let Ok(owner) = get_name_owner(&full_name, conn).await else {
+                continue;
+            };
+            if overwrite {
+                self.record(&name, &owner);
+            } else {
+                let mut state = self.0.lock().unwrap();
+                state
+                    .owners
+                    .entry(name.clone())
+                    .or_insert_with(|| owner.clone());
+                state.players.entry(owner).or_insert(name);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets whether `NameOwnerChanged` signals are being recorded.
+    pub(crate) fn set_tracking(&self, tracking: bool) {
+        self.0.lock().unwrap().tracking = tracking;
+    }
+}
+
+/// The object path players report as `mpris:trackid` when
+/// there is no current track.
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+/// Whether `metadata` describes no track: it is empty, as players
+/// commonly report while stopped, or its `mpris:trackid` is the
+/// `NoTrack` path.
+pub fn is_no_track(metadata: &PropMap) -> bool {
+    let track_id = metadata
+        .get("mpris:trackid")
+        .and_then(|v| unwrap_variant(&*v.0).as_str());
+
+    metadata.is_empty() || track_id == Some(NO_TRACK)
+}
+
+/// Identifies the track described by `metadata`, by its
+/// `mpris:trackid`, falling back to its `xesam:url` and
+/// then its `xesam:title`. Returns `None` if there is no track.
+#[cfg(feature = "events")]
+pub(crate) fn track_identity(metadata: &PropMap) -> Option<String> {
+    let field = |key: &str| {
+        prop_str(metadata, key)
+            .filter(|s| !s.is_empty())
+            .map(Cow::into_owned)
+    };
+
+    match field("mpris:trackid") {
+        Some(id) if id == NO_TRACK => None,
+        Some(id) => Some(format!("trackid:{}", id)),
+        None => field("xesam:url")
+            .map(|url| format!("url:{}", url))
+            .or_else(|| field("xesam:title").map(|title| format!("title:{}", title))),
+    }
 }
 
 /// Establishes a connection to the `DBus`.
 /// Use this to create a connection to pass into `Player`.
+///
+/// The connection is handled by a task spawned on tokio's runtime,
+/// which has to be running, or on smol's with the `smol` feature.
 pub fn get_connection() -> Arc<SyncConnection> {
-    let (resource, conn) = connection::new_session_sync().unwrap();
-
-    tokio::spawn(async {
-        let err = resource.await;
-        panic!("Lost connection to D-Bus: {}", err);
-    });
-
-    conn
+    runtime::connect_session()
 }
 
 /// Gets a `Vec` of `Player`s from all active
 /// MPRIS players found on the `DBus`.
 ///
+/// Each player is pinned to the connection owning it, like with
+/// [`Player::try_pinned`], so that several instances of one
+/// application are told apart even if they trade names.
+///
 /// # Errors
 /// May return an `Err` variant if there was a failure in
 /// getting a list of names from `DBus`.
 pub async fn get_all_players(conn: &SyncConnection) -> Result<Vec<Player<'_>>> {
-    let mut players: Vec<Player<'_>> = Vec::new();
+    let players = list_owners(conn).await?;
+    Ok(pin_players(players, conn))
+}
 
-    for name in get_all_names(&conn).await? {
-        match Player::try_new(name, &conn).await {
-            Ok(player) => players.push(player),
-            Err(_) => continue,
-        };
+/// The name and owner of every MPRIS player on the bus. Players
+/// that leave while being listed are skipped.
+pub(crate) async fn list_owners(conn: &SyncConnection) -> Result<Vec<(String, String)>> {
+    let mut players = Vec::new();
+
+    for name in list_players(conn).await? {
+        if let Ok(owner) = get_name_owner(&format!("{}{}", MPRIS_PREFIX, name), conn).await {
+            players.push((name, owner));
+        }
     }
 
     Ok(players)
 }
 
+/// A `Player` for each of `players`, by name and owner, pinned to
+/// its owner.
+pub(crate) fn pin_players(
+    players: Vec<(String, String)>,
+    conn: &SyncConnection,
+) -> Vec<Player<'_>> {
+    players
+        .into_iter()
+        .filter_map(|(name, owner)| {
+            Player::with_owner(name, Some(owner), ConnRef::Borrowed(conn)).ok()
+        })
+        .collect()
+}
+
 /// Gets a value from a HashMap, and casts it to the
 /// type provided.
 ///
 /// # Example
-/// ```
+/// ```no_run
+/// # use pris::{prop_cast, Player};
+/// # async fn example(player: &Player<'_>) -> Result<(), Box<dyn std::error::Error>> {
 /// let metadata = player.get_metadata().await?;
 /// let title = match prop_cast::<String>(&metadata, "xesam:title") {
 ///     Some(t) => t.to_string(),
 ///     None => "Unknown title".to_string()
 /// };
+/// # Ok(())
+/// # }
 /// ```
 pub fn prop_cast<'a, T>(map: &'a PropMap, key: &str) -> Option<&'a T>
 where
@@ -79,3 +525,298 @@ where
 {
     map.get(key).and_then(|v| v.0.as_any().downcast_ref())
 }
+
+/// Reads the text `key` of `map`, such as the `xesam:title` of some
+/// metadata, unwrapping any variants around it.
+///
+/// Besides strings and object paths, byte arrays are read as text,
+/// since some players send it that way. Invalid UTF-8 in them is
+/// replaced with U+FFFD instead of failing; the raw bytes stay
+/// reachable with [`prop_bytes`]. Control characters are kept, and
+/// [`prop_display`] strips them.
+///
+/// # Example
+/// ```no_run
+/// # use pris::{prop_str, Player};
+/// # async fn example(player: &Player<'_>) -> Result<(), Box<dyn std::error::Error>> {
+/// let metadata = player.get_metadata().await?;
+/// let title = prop_str(&metadata, "xesam:title").unwrap_or("Unknown title".into());
+/// # Ok(())
+/// # }
+/// ```
+pub fn prop_str<'a>(map: &'a PropMap, key: &str) -> Option<Cow<'a, str>> {
+    let value = unwrap_variant(&*map.get(key)?.0);
+    match value.as_str() {
+        Some(text) => Some(Cow::Borrowed(text)),
+        None => cast::<Vec<u8>>(value).map(|bytes| String::from_utf8_lossy(bytes)),
+    }
+}
+
+/// Same as [`prop_str`], with the C0 control characters stripped,
+/// so that the text can be shown as is in a terminal or status bar.
+pub fn prop_display(map: &PropMap, key: &str) -> Option<String> {
+    prop_str(map, key).map(|text| sanitize(&text).into_owned())
+}
+
+/// The bytes of the text `key` of `map`, exactly as the player sent
+/// them, for a string, an object path or a byte array.
+pub fn prop_bytes<'a>(map: &'a PropMap, key: &str) -> Option<&'a [u8]> {
+    let value = unwrap_variant(&*map.get(key)?.0);
+    match value.as_str() {
+        Some(text) => Some(text.as_bytes()),
+        None => cast::<Vec<u8>>(value).map(Vec::as_slice),
+    }
+}
+
+/// Strips the C0 control characters, such as escape sequences and
+/// line breaks, from `text`.
+pub fn sanitize(text: &str) -> Cow<'_, str> {
+    let is_control = |c: char| c <= '\u{1f}';
+    if text.contains(is_control) {
+        Cow::Owned(text.replace(is_control, ""))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Whether [`format_duration`] writes hours.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Hours {
+    /// Only when there are any, as `3:07` or `1:02:45`.
+    #[default]
+    Auto,
+    /// Always, as `0:03:07`.
+    Always,
+    /// Never, counting minutes past the hour, as `62:45`.
+    Never,
+}
+
+/// How [`format_duration`] writes a duration.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DurationStyle {
+    /// Whether hours are written.
+    pub hours: Hours,
+    /// Whether the first field is padded to two digits, as `03:07`.
+    pub padded: bool,
+}
+
+/// Writes `duration` as a clock, such as `3:07` or `1:02:45`, as
+/// decided by `style`. Fractions of a second are dropped, and hours
+/// go past 24 rather than into days, as `26:00:00`.
+///
+/// # Example
+/// ```
+/// # use pris::{format_duration, DurationStyle, Hours};
+/// # use std::time::Duration;
+/// let length = Duration::from_secs(187);
+/// assert_eq!(format_duration(length, DurationStyle::default()), "3:07");
+/// let style = DurationStyle {
+///     hours: Hours::Always,
+///     padded: true,
+/// };
+/// assert_eq!(format_duration(length, style), "00:03:07");
+/// ```
+pub fn format_duration(duration: Duration, style: DurationStyle) -> String {
+    let total = duration.as_secs();
+    let (hours, minutes, seconds) = (total / 3600, total / 60 % 60, total % 60);
+    let width = if style.padded { 2 } else { 1 };
+
+    let hours = match style.hours {
+        Hours::Auto => hours > 0,
+        Hours::Always => true,
+        Hours::Never => false,
+    }
+    .then_some(hours);
+    match hours {
+        Some(hours) => format!("{:0w$}:{:02}:{:02}", hours, minutes, seconds, w = width),
+        None => format!("{:0w$}:{:02}", total / 60, seconds, w = width),
+    }
+}
+
+/// A duration read by [`parse_duration`], which may be relative.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParsedDuration {
+    /// A duration without a sign, such as a position to seek to.
+    Absolute(Duration),
+    /// A duration with a `+`, such as an offset to seek forward by.
+    Forward(Duration),
+    /// A duration with a `-`, such as an offset to seek back by.
+    Backward(Duration),
+}
+
+impl ParsedDuration {
+    /// The duration itself, whatever its sign.
+    pub fn duration(self) -> Duration {
+        match self {
+            ParsedDuration::Absolute(duration)
+            | ParsedDuration::Forward(duration)
+            | ParsedDuration::Backward(duration) => duration,
+        }
+    }
+}
+
+/// Reads a duration typed by a user, such as a position or an
+/// offset to seek by, with an optional `+` or `-` sign, in any of
+/// the forms:
+/// - seconds, as `90` or `1.5`, or with a unit of `s`, `m` or `h`,
+///   as `15s` or `2m`
+/// - minutes and seconds, as `1:30`
+/// - hours, minutes and seconds, as `1:02:45`
+///
+/// The first field of a clock may be as large as needed, as `90:00`,
+/// and the others must be below 60. Unless `strict`, they may also
+/// have a single digit, as `1:5` for `1:05`.
+///
+/// # Errors
+/// Returns [`Error::InvalidArgument`] describing what is wrong with
+/// `input` if it isn't in one of these forms.
+///
+/// # Example
+/// ```
+/// # use pris::{parse_duration, ParsedDuration};
+/// # use std::time::Duration;
+/// # fn example() -> pris::Result<()> {
+/// assert_eq!(
+///     parse_duration("+15s", true)?,
+///     ParsedDuration::Forward(Duration::from_secs(15))
+/// );
+/// assert_eq!(parse_duration("1:02:45", true)?.duration().as_secs(), 3765);
+/// assert!(parse_duration("1:5", true).is_err());
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_duration(input: &str, strict: bool) -> Result<ParsedDuration> {
+    let invalid = |reason: String| {
+        Error::InvalidArgument(format!("{:?} isn't a duration: {}", input, reason))
+    };
+
+    let trimmed = input.trim();
+    let (sign, unsigned) = match trimmed.as_bytes().first() {
+        Some(b'+') => (Some(true), &trimmed[1..]),
+        Some(b'-') => (Some(false), &trimmed[1..]),
+        _ => (None, trimmed),
+    };
+    if unsigned.is_empty() {
+        return Err(invalid("it is empty.".into()));
+    }
+
+    let duration = if unsigned.contains(':') {
+        let fields: Vec<&str> = unsigned.split(':').collect();
+        if fields.len() > 3 {
+            return Err(invalid(
+                "it has more than hours, minutes and seconds.".into(),
+            ));
+        }
+        let mut seconds: u64 = 0;
+        for (index, field) in fields.iter().enumerate() {
+            if field.is_empty() || !field.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid(format!("{:?} isn't a whole number.", field)));
+            }
+            let value: u64 = field
+                .parse()
+                .map_err(|_| invalid(format!("{} is too large.", field)))?;
+            if index > 0 {
+                if strict && field.len() != 2 {
+                    return Err(invalid(format!("{:?} isn't two digits.", field)));
+                }
+                if value >= 60 {
+                    return Err(invalid(format!("{} is 60 or more.", field)));
+                }
+            }
+            seconds = seconds
+                .checked_mul(60)
+                .and_then(|seconds| seconds.checked_add(value))
+                .ok_or_else(|| invalid("it is too long.".into()))?;
+        }
+        Duration::from_secs(seconds)
+    } else {
+        let (number, scale) = match unsigned.as_bytes()[unsigned.len() - 1] {
+            b's' => (&unsigned[..unsigned.len() - 1], 1.0),
+            b'm' => (&unsigned[..unsigned.len() - 1], 60.0),
+            b'h' => (&unsigned[..unsigned.len() - 1], 3600.0),
+            _ => (unsigned, 1.0),
+        };
+        if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit() || b == b'.') {
+            return Err(invalid(format!("{:?} isn't a number.", number)));
+        }
+        let seconds: f64 = number
+            .parse()
+            .map_err(|_| invalid(format!("{:?} isn't a number.", number)))?;
+        Duration::try_from_secs_f64(seconds * scale)
+            .map_err(|_| invalid("it is too long.".into()))?
+    };
+
+    Ok(match sign {
+        None => ParsedDuration::Absolute(duration),
+        Some(true) => ParsedDuration::Forward(duration),
+        Some(false) => ParsedDuration::Backward(duration),
+    })
+}
+
+/// Strips any number of variant wrappers from a value.
+pub(crate) fn unwrap_variant<'a>(
+    mut value: &'a (dyn RefArg + 'static),
+) -> &'a (dyn RefArg + 'static) {
+    while let Some(inner) = cast::<Variant<Box<dyn RefArg>>>(value) {
+        value = &*inner.0;
+    }
+
+    value
+}
+
+/// Deep-clones a `PropMap`, which does not implement `Clone` itself.
+pub(crate) fn clone_prop_map(map: &PropMap) -> PropMap {
+    map.iter()
+        .map(|(k, v)| (k.clone(), Variant(v.0.box_clone())))
+        .collect()
+}
+
+/// Copies a dictionary value (such as `Metadata`) into a `PropMap`,
+/// unwrapping any nested variants in its values.
+pub(crate) fn as_prop_map(value: &(dyn RefArg + 'static)) -> Option<PropMap> {
+    if let Some(map) = cast::<PropMap>(value) {
+        return Some(
+            map.iter()
+                .map(|(k, v)| (k.clone(), Variant(unwrap_variant(&*v.0).box_clone())))
+                .collect(),
+        );
+    }
+
+    if !value.signature().starts_with("a{s") {
+        return None;
+    }
+
+    let mut map = PropMap::new();
+    let mut index = 0;
+    while let (Some(key), Some(value)) = (
+        value.as_static_inner(index),
+        value.as_static_inner(index + 1),
+    ) {
+        map.insert(
+            key.as_str()?.to_string(),
+            Variant(unwrap_variant(value).box_clone()),
+        );
+        index += 2;
+    }
+
+    Some(map)
+}
+
+/// The length of a track, from the `mpris:length` metadata entry,
+/// clamped like [`micros`].
+#[cfg(feature = "events")]
+pub(crate) fn track_length(metadata: &PropMap) -> Option<Duration> {
+    micros(&*metadata.get("mpris:length")?.0)
+}
+
+/// Reads a number of microseconds sent by a player, of any integer
+/// type. Negative values, such as a length of -1 or positions
+/// around track changes, are clamped to zero; unsigned ones too
+/// large for an `i64` are kept, since a `Duration` holds them.
+pub(crate) fn micros(value: &(dyn RefArg + 'static)) -> Option<Duration> {
+    let value = unwrap_variant(value);
+    match value.as_i64() {
+        Some(micros) => Some(Micros::new(micros).to_duration()),
+        None => value.as_u64().map(Duration::from_micros),
+    }
+}
@@ -0,0 +1,137 @@
+use dbus::arg::{ArgType, PropMap, RefArg};
+use std::{collections::BTreeMap, convert::TryFrom};
+
+/// A property or metadata value, copied out of its D-Bus form so it
+/// can be used without the `dbus` crate.
+///
+/// Variants are unwrapped, and the several integer types are
+/// widened to [`Int`](Value::Int) or [`UInt`](Value::UInt).
+///
+/// # Example
+/// ```no_run
+/// # use pris::{prop_value, Player, Value};
+/// # async fn example(player: &Player<'_>) -> Result<(), Box<dyn std::error::Error>> {
+/// let metadata = player.get_metadata().await?;
+/// if let Some(Value::Array(artists)) = prop_value(&metadata, "xesam:artist") {
+///     let artists: Vec<&str> = artists.iter().filter_map(Value::as_str).collect();
+///     println!("By {}", artists.join(", "));
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    /// Any signed integer.
+    Int(i64),
+    /// Any unsigned integer, bytes included.
+    UInt(u64),
+    Double(f64),
+    /// A string, an object path or a signature.
+    Str(String),
+    /// An array other than a dictionary.
+    Array(Vec<Value>),
+    /// A dictionary, such as nested metadata. Keys that aren't
+    /// strings are written out as text.
+    Dict(BTreeMap<String, Value>),
+    /// The fields of a struct, in order.
+    Struct(Vec<Value>),
+    /// A value with no representation here, such as a file
+    /// descriptor, by its D-Bus signature.
+    Other(String),
+}
+
+impl Value {
+    /// Copies `value` out of its D-Bus form.
+    pub fn from_dbus(value: &dyn RefArg) -> Value {
+        let items = || value.as_iter().into_iter().flatten().map(Value::from_dbus);
+        match value.arg_type() {
+            ArgType::Variant => items().next().unwrap_or_else(|| Value::Other("v".into())),
+            ArgType::Boolean => Value::Bool(value.as_u64() == Some(1)),
+            ArgType::Int16 | ArgType::Int32 | ArgType::Int64 => {
+                Value::Int(value.as_i64().unwrap_or_default())
+            }
+            ArgType::Byte | ArgType::UInt16 | ArgType::UInt32 | ArgType::UInt64 => {
+                Value::UInt(value.as_u64().unwrap_or_default())
+            }
+            ArgType::Double => Value::Double(value.as_f64().unwrap_or_default()),
+            ArgType::String | ArgType::ObjectPath | ArgType::Signature => {
+                Value::Str(value.as_str().unwrap_or_default().to_string())
+            }
+            // Dictionaries iterate over their keys and values in turn
+            ArgType::Array if value.signature().starts_with("a{") => {
+                let mut entries = BTreeMap::new();
+                let mut items = items();
+                while let (Some(key), Some(item)) = (items.next(), items.next()) {
+                    entries.insert(key.to_key(), item);
+                }
+                Value::Dict(entries)
+            }
+            ArgType::Array => Value::Array(items().collect()),
+            ArgType::Struct => Value::Struct(items().collect()),
+            _ => Value::Other(value.signature().to_string()),
+        }
+    }
+
+    /// Copies every value of `map` out of its D-Bus form.
+    pub fn from_dbus_map(map: &PropMap) -> BTreeMap<String, Value> {
+        map.iter()
+            .map(|(key, value)| (key.clone(), Value::from_dbus(&*value.0)))
+            .collect()
+    }
+
+    /// The text of a [`Str`](Value::Str).
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// The number of an [`Int`](Value::Int), a [`UInt`](Value::UInt)
+    /// that fits, or a whole [`Double`](Value::Double).
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::Int(n) => Some(n),
+            Value::UInt(n) => i64::try_from(n).ok(),
+            Value::Double(n) if n.fract() == 0.0 => Some(n as i64),
+            _ => None,
+        }
+    }
+
+    /// The number of a [`Double`](Value::Double), or of an integer.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Double(n) => Some(n),
+            Value::Int(n) => Some(n as f64),
+            Value::UInt(n) => Some(n as f64),
+            _ => None,
+        }
+    }
+
+    /// The flag of a [`Bool`](Value::Bool).
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Value::Bool(flag) => Some(flag),
+            _ => None,
+        }
+    }
+
+    /// The value as a dictionary key.
+    fn to_key(&self) -> String {
+        match self {
+            Value::Str(text) => text.clone(),
+            Value::Int(n) => n.to_string(),
+            Value::UInt(n) => n.to_string(),
+            Value::Double(n) => n.to_string(),
+            Value::Bool(flag) => flag.to_string(),
+            other => format!("{:?}", other),
+        }
+    }
+}
+
+/// Reads the value `key` of `map`, such as the `xesam:artist` of
+/// some metadata, as a [`Value`].
+pub fn prop_value(map: &PropMap, key: &str) -> Option<Value> {
+    map.get(key).map(|value| Value::from_dbus(&*value.0))
+}
@@ -0,0 +1,515 @@
+use crate::{
+    util, ChangedProperties, Event, EventManager, PlaybackStatus, Player, PlayerEvents,
+    PlayerState, Result,
+};
+use dbus::arg::{PropMap, RefArg, Variant};
+use futures::future::{self, Either};
+use std::{
+    future::Future,
+    mem,
+    time::{Duration, Instant},
+};
+use tokio::sync::watch;
+
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+/// A snapshot of a player's position: where it was, when, and how
+/// fast it was moving, from which the playhead can be computed at any
+/// other time.
+///
+/// This is plain arithmetic, with no I/O, so a render loop can ask
+/// for the position at exactly its frame time.
+///
+/// # Example
+/// ```
+/// # use pris::{PlaybackStatus, PositionEstimate};
+/// # use std::time::{Duration, Instant};
+/// let now = Instant::now();
+/// let estimate = PositionEstimate::new(Duration::from_secs(10), now, 2.0, PlaybackStatus::Playing)
+///     .with_length(Some(Duration::from_secs(60)));
+/// assert_eq!(estimate.at(now + Duration::from_secs(5)), Duration::from_secs(20));
+/// assert_eq!(estimate.at(now + Duration::from_secs(100)), Duration::from_secs(60));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PositionEstimate {
+    /// The position that was known.
+    pub position: Duration,
+    /// When `position` was known.
+    pub captured_at: Instant,
+    pub rate: f64,
+    pub status: PlaybackStatus,
+    /// The length of the track, past which the position isn't
+    /// extrapolated.
+    pub length: Option<Duration>,
+}
+
+impl PositionEstimate {
+    /// Creates an estimate from `position` as of `captured_at`, for a
+    /// track of unknown length.
+    pub fn new(
+        position: Duration,
+        captured_at: Instant,
+        rate: f64,
+        status: PlaybackStatus,
+    ) -> PositionEstimate {
+        PositionEstimate {
+            position,
+            captured_at,
+            rate,
+            status,
+            length: None,
+        }
+    }
+
+    /// Sets the length of the track.
+    pub fn with_length(mut self, length: Option<Duration>) -> PositionEstimate {
+        self.length = length;
+        self
+    }
+
+    /// The estimated position at `now`. It only advances while
+    /// playing, never before `captured_at`, and stays between zero
+    /// and the length of the track, if known.
+    pub fn at(&self, now: Instant) -> Duration {
+        if self.status != PlaybackStatus::Playing {
+            return self.position;
+        }
+
+        let elapsed = now
+            .saturating_duration_since(self.captured_at)
+            .as_secs_f64()
+            * self.rate;
+        let position = Duration::from_secs_f64((self.position.as_secs_f64() + elapsed).max(0.0));
+        match self.length {
+            Some(length) => position.min(length),
+            None => position,
+        }
+    }
+}
+
+/// Estimates a player's position between reports, from the last
+/// known position, playback status and rate.
+///
+/// Players don't signal the passing of time, only jumps such as
+/// seeks, so the position has to be extrapolated while playing.
+/// Every change takes the time it happened at, so that the
+/// estimate can be rebased before the new value takes effect.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlaybackClock {
+    estimate: PositionEstimate,
+}
+
+impl PlaybackClock {
+    /// Creates a clock at `position` as of `at`.
+    pub fn new(
+        position: Duration,
+        status: PlaybackStatus,
+        rate: f64,
+        at: Instant,
+    ) -> PlaybackClock {
+        PlaybackClock {
+            estimate: PositionEstimate::new(position, at, rate, status),
+        }
+    }
+
+    /// The estimated position at `now`, which never goes below
+    /// zero or past the length of the track, if known.
+    pub fn position_at(&self, now: Instant) -> Duration {
+        self.estimate.at(now)
+    }
+
+    /// The estimated position right now.
+    pub fn position(&self) -> Duration {
+        self.position_at(Instant::now())
+    }
+
+    /// A snapshot of the clock, which keeps estimating from the
+    /// current reference point regardless of later changes.
+    pub fn estimate(&self) -> PositionEstimate {
+        self.estimate
+    }
+
+    pub fn status(&self) -> PlaybackStatus {
+        self.estimate.status
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.estimate.rate
+    }
+
+    pub fn length(&self) -> Option<Duration> {
+        self.estimate.length
+    }
+
+    /// Records that the player was at `position` at `at`, for
+    /// instance after a seek.
+    pub fn set_position(&mut self, position: Duration, at: Instant) {
+        self.estimate.position = position;
+        self.estimate.captured_at = at;
+    }
+
+    /// Records a change of playback status at `at`. Stopping
+    /// resets the position to zero.
+    pub fn set_status(&mut self, status: PlaybackStatus, at: Instant) {
+        self.rebase(at);
+        self.estimate.status = status;
+        if status == PlaybackStatus::Stopped {
+            self.estimate.position = Duration::ZERO;
+        }
+    }
+
+    /// Records a change of playback rate at `at`.
+    pub fn set_rate(&mut self, rate: f64, at: Instant) {
+        self.rebase(at);
+        self.estimate.rate = rate;
+    }
+
+    /// Sets the length of the current track, past which the
+    /// position isn't extrapolated.
+    pub fn set_length(&mut self, length: Option<Duration>) {
+        self.estimate.length = length;
+    }
+
+    /// Moves the reference point to `at`, keeping the estimate.
+    fn rebase(&mut self, at: Instant) {
+        self.set_position(self.position_at(at), at);
+    }
+}
+
+type ChangeListener<'a> = Box<dyn FnMut(&PlayerStateWatcher<'a>, &[String]) + 'a>;
+
+/// A cache of a player's state, seeded with a `GetAll` call and
+/// kept up to date from its signals.
+///
+/// The getters only read the cache. It is updated by awaiting
+/// [`update`](Self::update), typically in a loop.
+///
+/// # Example
+/// ```no_run
+/// # use pris::{EventManager, Player, PlayerStateWatcher};
+/// # async fn example(manager: &EventManager<'_>, player: &Player<'_>) -> Result<(), Box<dyn std::error::Error>> {
+/// let mut watcher = PlayerStateWatcher::new(manager, player).await?;
+/// watcher.on_change(|watcher, changed| {
+///     println!("{:?} changed, now at {:?}", changed, watcher.position());
+/// });
+/// while watcher.update().await.is_some() {}
+/// # Ok(())
+/// # }
+/// ```
+pub struct PlayerStateWatcher<'a> {
+    player: Player<'a>,
+    events: PlayerEvents<'a>,
+    state: PlayerState,
+    clock: PlaybackClock,
+    listeners: Vec<ChangeListener<'a>>,
+    dropped: u64,
+    gone: bool,
+}
+
+impl<'a> PlayerStateWatcher<'a> {
+    /// Starts watching `player`.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the player isn't on the bus, or if its
+    /// properties can't be fetched.
+    pub async fn new(
+        manager: &EventManager<'a>,
+        player: &Player<'a>,
+    ) -> Result<PlayerStateWatcher<'a>> {
+        // Watch first, so that nothing is missed after fetching
+        let events = manager.watch(player).await?;
+        let player = player.clone();
+        let state = player.get_state().await?;
+        let mut watcher = PlayerStateWatcher {
+            player,
+            events,
+            clock: PlaybackClock::new(Duration::ZERO, PlaybackStatus::Stopped, 1.0, Instant::now()),
+            state: PlayerState::default(),
+            listeners: Vec::new(),
+            dropped: 0,
+            gone: false,
+        };
+        watcher.reset(state);
+
+        Ok(watcher)
+    }
+
+    /// The cached state. Its [`position`](PlayerState::position) is
+    /// the last one reported; see [`position`](Self::position) for
+    /// an up to date estimate.
+    pub fn state(&self) -> &PlayerState {
+        &self.state
+    }
+
+    pub fn status(&self) -> Option<PlaybackStatus> {
+        self.state.playback_status
+    }
+
+    pub fn volume(&self) -> Option<f64> {
+        self.state.volume
+    }
+
+    pub fn metadata(&self) -> Option<&PropMap> {
+        self.state.metadata.as_ref()
+    }
+
+    /// The position, extrapolated from the last one reported.
+    pub fn position(&self) -> Duration {
+        self.clock.position()
+    }
+
+    /// The clock used to estimate the position.
+    pub fn clock(&self) -> &PlaybackClock {
+        &self.clock
+    }
+
+    /// A snapshot of the position, for computing it at a given time
+    /// without going through the watcher.
+    pub fn estimate(&self) -> PositionEstimate {
+        self.clock.estimate()
+    }
+
+    /// Whether the player has left the bus. The cache isn't
+    /// updated anymore once it has.
+    pub fn is_gone(&self) -> bool {
+        self.gone
+    }
+
+    /// Registers a listener, called after every change to the
+    /// cache with the names of the properties that changed.
+    pub fn on_change<F>(&mut self, listener: F)
+    where
+        F: FnMut(&PlayerStateWatcher<'a>, &[String]) + 'a,
+    {
+        self.listeners.push(Box::new(listener));
+    }
+
+    /// Turns the watcher into a `watch` channel of the cached state,
+    /// for code that only wants the latest state rather than every
+    /// change, such as the update loop of a GUI.
+    ///
+    /// The returned future keeps the channel up to date, and must be
+    /// run for it to be, such as with `tokio::task::spawn_local`. It
+    /// finishes once every receiver is dropped, or once the player
+    /// has gone, after sending a last state with nothing but the
+    /// [`player`](PlayerState::player) set. Receivers can tell it has
+    /// finished by [`changed`](watch::Receiver::changed) failing.
+    ///
+    /// The [`position`](PlayerState::position) sent is the last one
+    /// reported, like with [`state`](Self::state).
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use pris::{EventManager, Player, PlayerStateWatcher};
+    /// # async fn example(manager: &EventManager<'static>, player: &Player<'static>) -> Result<(), Box<dyn std::error::Error>> {
+    /// let watcher = PlayerStateWatcher::new(manager, player).await?;
+    /// let (mut states, feed) = watcher.watch_channel();
+    /// tokio::task::spawn_local(feed);
+    /// while states.changed().await.is_ok() {
+    ///     println!("Now {:?}", states.borrow().playback_status);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn watch_channel(
+        mut self,
+    ) -> (watch::Receiver<PlayerState>, impl Future<Output = ()> + 'a) {
+        let (sender, receiver) = watch::channel(self.state.clone());
+        let feed = async move {
+            loop {
+                let changed = {
+                    let update = self.update();
+                    let closed = sender.closed();
+                    futures::pin_mut!(update, closed);
+                    match future::select(update, closed).await {
+                        Either::Left((changed, _)) => changed,
+                        Either::Right(_) => return,
+                    }
+                };
+
+                let state = match changed {
+                    Some(_) => self.state.clone(),
+                    None => PlayerState {
+                        player: self.state.player.clone(),
+                        ..PlayerState::default()
+                    },
+                };
+                if sender.send(state).is_err() || self.gone {
+                    return;
+                }
+            }
+        };
+
+        (receiver, feed)
+    }
+
+    /// Waits for the next change to the cache, returning the names
+    /// of the properties that changed, or `None` once the player
+    /// has gone.
+    ///
+    /// Invalidated properties are fetched again. If signals were
+    /// missed because they arrived faster than they were handled,
+    /// the whole state is fetched again with [`resync`](Self::resync).
+    pub async fn update(&mut self) -> Option<Vec<String>> {
+        while !self.gone {
+            let changed = match self.events.next_event().await {
+                Some(Event::PropertiesChanged(event)) if event.properties.is_player_interface() => {
+                    let at = Instant::now();
+                    let invalidated = event.properties.invalidated.clone();
+                    let mut changed = self.apply(event.properties, at);
+                    if !invalidated.is_empty() {
+                        changed.extend(self.refetch(&invalidated).await);
+                    }
+                    changed
+                }
+                Some(Event::Seeked(seeked)) => {
+                    self.state.position = Some(seeked.position);
+                    self.clock.set_position(seeked.position, Instant::now());
+                    vec!["Position".to_string()]
+                }
+                Some(Event::PropertiesChanged(_)) => Vec::new(),
+                Some(Event::PlayerLifecycle(_)) | None => {
+                    self.gone = true;
+                    return None;
+                }
+            };
+
+            if self.events.dropped() != self.dropped {
+                self.dropped = self.events.dropped();
+                if let Ok(resynced) = self.fetch().await {
+                    self.notify(&resynced);
+                    return Some(resynced);
+                }
+            }
+            if !changed.is_empty() {
+                self.notify(&changed);
+                return Some(changed);
+            }
+        }
+
+        None
+    }
+
+    /// Replaces the cache with a fresh `GetAll` call, for instance
+    /// after reconnecting. Listeners are told about every property
+    /// the player reported.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the properties can't be fetched.
+    pub async fn resync(&mut self) -> Result<()> {
+        let changed = self.fetch().await?;
+        self.notify(&changed);
+
+        Ok(())
+    }
+
+    /// Fetches and caches the whole state, returning the names of
+    /// the properties reported.
+    async fn fetch(&mut self) -> Result<Vec<String>> {
+        let state = self.player.get_state().await?;
+        Ok(self.reset(state))
+    }
+
+    fn reset(&mut self, state: PlayerState) -> Vec<String> {
+        let at = Instant::now();
+        let mut clock = PlaybackClock::new(
+            state.position.unwrap_or_default(),
+            state.playback_status.unwrap_or(PlaybackStatus::Stopped),
+            state.rate.unwrap_or(1.0),
+            at,
+        );
+        clock.set_length(state.metadata.as_ref().and_then(util::track_length));
+        self.clock = clock;
+        let reported = reported_properties(&state);
+        self.state = state;
+
+        reported
+    }
+
+    /// Fetches the current values of `names`, returning the names of
+    /// those that could be fetched.
+    async fn refetch(&mut self, names: &[String]) -> Vec<String> {
+        let mut properties = PropMap::new();
+        for name in names {
+            let value = self.player.get_property::<Box<dyn RefArg>>(name).await;
+            if let Ok(value) = value {
+                properties.insert(name.clone(), Variant(value));
+            }
+        }
+        let changes =
+            ChangedProperties::from_parts(PLAYER_INTERFACE.to_string(), properties, Vec::new());
+
+        self.apply(changes, Instant::now())
+    }
+
+    /// Applies `changes` to the cache and the clock, as of `at`.
+    fn apply(&mut self, changes: ChangedProperties, at: Instant) -> Vec<String> {
+        let previous_track = self.state.metadata.as_ref().and_then(util::track_identity);
+        let changed = self.state.apply(changes);
+        let was_changed = |name: &str| changed.iter().any(|c| c == name);
+
+        if was_changed("PlaybackStatus") {
+            if let Some(status) = self.state.playback_status {
+                self.clock.set_status(status, at);
+            }
+        }
+        if was_changed("Rate") {
+            if let Some(rate) = self.state.rate {
+                self.clock.set_rate(rate, at);
+            }
+        }
+        if was_changed("Metadata") {
+            let metadata = self.state.metadata.as_ref();
+            self.clock.set_length(metadata.and_then(util::track_length));
+            // A new track starts from the beginning, unless told otherwise
+            if metadata.and_then(util::track_identity) != previous_track && !was_changed("Position")
+            {
+                self.clock.set_position(Duration::ZERO, at);
+                self.state.position = Some(Duration::ZERO);
+            }
+        }
+        if was_changed("Position") {
+            if let Some(position) = self.state.position {
+                self.clock.set_position(position, at);
+            }
+        }
+
+        changed
+    }
+
+    fn notify(&mut self, changed: &[String]) {
+        let mut listeners = mem::take(&mut self.listeners);
+        for listener in &mut listeners {
+            listener(self, changed);
+        }
+        // Keep any listeners registered in the meantime
+        listeners.append(&mut self.listeners);
+        self.listeners = listeners;
+    }
+}
+
+/// The names of the properties present in `state`.
+fn reported_properties(state: &PlayerState) -> Vec<String> {
+    let typed = [
+        ("PlaybackStatus", state.playback_status.is_some()),
+        ("Metadata", state.metadata.is_some()),
+        ("Position", state.position.is_some()),
+        ("Volume", state.volume.is_some()),
+        ("LoopStatus", state.loop_status.is_some()),
+        ("Shuffle", state.shuffle.is_some()),
+        ("Rate", state.rate.is_some()),
+        ("CanGoNext", state.can_go_next.is_some()),
+        ("CanGoPrevious", state.can_go_previous.is_some()),
+        ("CanPlay", state.can_play.is_some()),
+        ("CanPause", state.can_pause.is_some()),
+        ("CanSeek", state.can_seek.is_some()),
+        ("CanControl", state.can_control.is_some()),
+    ];
+
+    typed
+        .iter()
+        .filter(|(_, present)| *present)
+        .map(|(name, _)| name.to_string())
+        .chain(state.other.keys().cloned())
+        .collect()
+}
@@ -0,0 +1,652 @@
+//! The serialized form of events, for forwarding them to other
+//! processes, such as over a socket as JSON lines.
+//!
+//! With the `serde` feature, [`Event`] and its payloads implement
+//! `Serialize` and `Deserialize`, and come back out of a round trip
+//! as they went in, with the D-Bus types of their values intact. The
+//! format below is kept stable within a [`VERSION`]; fields may be
+//! added to it, which consumers should ignore. It relies on
+//! self-describing formats, such as JSON, CBOR or MessagePack.
+//!
+//! # Events
+//! An [`Event`] is an object with a `version`, the [`VERSION`] it was
+//! written with, a `type`, the name of its variant, and the fields of
+//! its payload:
+//!
+//! ```json
+//! {"version": 1, "type": "Seeked", "player": "vlc", "position": 30000000}
+//! {"version": 1, "type": "PlayerLifecycle", "change": "Appeared", "name": "vlc"}
+//! {"version": 1, "type": "PropertiesChanged", "player": "vlc",
+//!  "interface": "org.mpris.MediaPlayer2.Player",
+//!  "changed": {"PlaybackStatus": ["s", "Playing"], "Volume": ["d", 0.5]},
+//!  "invalidated": []}
+//! ```
+//!
+//! Events written with a newer version are refused, rather than
+//! misread.
+//!
+//! The payloads on their own, [`SeekedEvent`], [`LifecycleEvent`],
+//! [`PropertiesChangedEvent`] and [`ChangedProperties`], are written
+//! as the same objects, without `version` and `type`:
+//!
+//! - Durations, such as the `position` of a [`SeekedEvent`], are
+//!   whole microseconds, as in MPRIS.
+//! - The `change` of a [`LifecycleEvent`] is `Appeared`, `Vanished`
+//!   or `Replaced`.
+//! - [`ChangedProperties`] are written as the signal they were
+//!   parsed from: the `interface`, every `changed` property with its
+//!   value, typed fields included, and the names of the
+//!   `invalidated` ones.
+//!
+//! # Values
+//! Property and metadata values are written as a pair of their D-Bus
+//! signature and their contents, so that their exact type is kept:
+//!
+//! - Booleans, numbers and strings as themselves, such as
+//!   `["x", 360000000]` for a track length. Object paths, such as
+//!   track ids, and signatures are strings: `["o", "/org/videolan/vlc/3"]`.
+//! - Arrays and structs as lists: `["as", ["Aphex Twin"]]`.
+//! - Dictionaries as objects, their keys written out as text:
+//!   `["a{sv}", {"xesam:title": ["s", "Flim"]}]`, as for metadata.
+//! - Variants as the pair of what they hold, as in the dictionary
+//!   above.
+//!
+//! File descriptors can't be written.
+use crate::{ChangedProperties, Event, LifecycleEvent, PropertiesChangedEvent, SeekedEvent};
+use dbus::{
+    arg::{
+        messageitem::{MessageItem, MessageItemArray, MessageItemDict},
+        ArgType, PropMap, RefArg, Variant,
+    },
+    strings::{Path, Signature},
+};
+use serde::{
+    de::{self, value::Error as ValueError, IntoDeserializer, MapAccess, SeqAccess, Visitor},
+    ser::{self, SerializeMap, SerializeSeq},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{collections::BTreeMap, convert::TryFrom, fmt};
+
+/// The version of the format events are written with.
+pub const VERSION: u32 = 1;
+
+/// An event, with the version and the type it is tagged with.
+#[derive(Serialize)]
+struct Tagged<'a> {
+    version: u32,
+    #[serde(flatten)]
+    event: Kind<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type")]
+enum Kind<'a> {
+    PropertiesChanged(&'a PropertiesChangedEvent),
+    Seeked(&'a SeekedEvent),
+    PlayerLifecycle(&'a LifecycleEvent),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum OwnedKind {
+    PropertiesChanged(PropertiesChangedEvent),
+    Seeked(SeekedEvent),
+    PlayerLifecycle(LifecycleEvent),
+}
+
+impl Serialize for Event {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let event = match self {
+            Event::PropertiesChanged(event) => Kind::PropertiesChanged(event),
+            Event::Seeked(event) => Kind::Seeked(event),
+            Event::PlayerLifecycle(event) => Kind::PlayerLifecycle(event),
+        };
+
+        Tagged {
+            version: VERSION,
+            event,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Event {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // The version is checked before anything else, which may have
+        // changed meaning since
+        let mut fields = BTreeMap::<String, Content>::deserialize(deserializer)?;
+        let version = match fields.remove("version") {
+            Some(Content::UInt(version)) => version,
+            Some(_) => return Err(de::Error::custom("the version isn't a positive integer")),
+            None => return Err(de::Error::missing_field("version")),
+        };
+        if version > u64::from(VERSION) {
+            return Err(de::Error::custom(format!(
+                "the event was written with version {} of the format, newer than {}",
+                version, VERSION
+            )));
+        }
+
+        Ok(
+            match OwnedKind::deserialize(Content::Map(fields)).map_err(de::Error::custom)? {
+                OwnedKind::PropertiesChanged(event) => Event::PropertiesChanged(event),
+                OwnedKind::Seeked(event) => Event::Seeked(event),
+                OwnedKind::PlayerLifecycle(event) => Event::PlayerLifecycle(event),
+            },
+        )
+    }
+}
+
+#[derive(Serialize)]
+struct ChangesRef<'a> {
+    interface: &'a str,
+    changed: Content,
+    invalidated: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct Changes {
+    interface: String,
+    changed: BTreeMap<String, Content>,
+    #[serde(default)]
+    invalidated: Vec<String>,
+}
+
+impl Serialize for ChangedProperties {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let changed = encode_map(&changed_map(self)).map_err(ser::Error::custom)?;
+        ChangesRef {
+            interface: &self.interface,
+            changed,
+            invalidated: &self.invalidated,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ChangedProperties {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let changes = Changes::deserialize(deserializer)?;
+        let changed = changes
+            .changed
+            .iter()
+            .map(|(name, value)| Ok((name.clone(), Variant(decode_variant(value)?))))
+            .collect::<Result<PropMap, String>>()
+            .map_err(de::Error::custom)?;
+
+        Ok(ChangedProperties::from_parts(
+            changes.interface,
+            changed,
+            changes.invalidated,
+        ))
+    }
+}
+
+/// The properties in `properties`, typed fields included, as they
+/// were sent in the signal.
+fn changed_map(properties: &ChangedProperties) -> PropMap {
+    let mut changed = crate::util::clone_prop_map(&properties.other);
+    let mut insert = |name: &str, value: Box<dyn RefArg>| {
+        changed.insert(name.to_string(), Variant(value));
+    };
+
+    if let Some(status) = properties.playback_status {
+        insert("PlaybackStatus", Box::new(status.to_string()));
+    }
+    if let Some(status) = properties.loop_status {
+        insert("LoopStatus", Box::new(status.to_string()));
+    }
+    if let Some(metadata) = &properties.metadata {
+        insert("Metadata", Box::new(crate::util::clone_prop_map(metadata)));
+    }
+    let numbers = [("Volume", properties.volume), ("Rate", properties.rate)];
+    for (name, value) in numbers.iter() {
+        if let Some(value) = value {
+            insert(name, Box::new(*value));
+        }
+    }
+    let flags = [
+        ("Shuffle", properties.shuffle),
+        ("CanGoNext", properties.can_go_next),
+        ("CanGoPrevious", properties.can_go_previous),
+        ("CanPlay", properties.can_play),
+        ("CanPause", properties.can_pause),
+        ("CanSeek", properties.can_seek),
+        ("CanControl", properties.can_control),
+    ];
+    for (name, value) in flags.iter() {
+        if let Some(value) = value {
+            insert(name, Box::new(*value));
+        }
+    }
+
+    changed
+}
+
+/// Durations as whole microseconds.
+pub(crate) mod micros {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub(crate) fn serialize<S: Serializer>(
+        duration: &Duration,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_micros() as u64)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_micros)
+    }
+}
+
+/// The contents of a value, as read from or written to a
+/// self-describing format.
+#[derive(Clone, Debug)]
+enum Content {
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Double(f64),
+    Str(String),
+    List(Vec<Content>),
+    Map(BTreeMap<String, Content>),
+}
+
+impl Serialize for Content {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Content::Bool(v) => serializer.serialize_bool(*v),
+            Content::Int(v) => serializer.serialize_i64(*v),
+            Content::UInt(v) => serializer.serialize_u64(*v),
+            Content::Double(v) => serializer.serialize_f64(*v),
+            Content::Str(v) => serializer.serialize_str(v),
+            Content::List(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            }
+            Content::Map(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Content {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ContentVisitor)
+    }
+}
+
+struct ContentVisitor;
+
+impl<'de> Visitor<'de> for ContentVisitor {
+    type Value = Content;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a boolean, number, string, list or map")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Content, E> {
+        Ok(Content::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Content, E> {
+        // Positive numbers are kept unsigned whichever way they came
+        Ok(u64::try_from(v).map_or(Content::Int(v), Content::UInt))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Content, E> {
+        Ok(Content::UInt(v))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Content, E> {
+        Ok(Content::Double(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Content, E> {
+        Ok(Content::Str(v.to_string()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Content, E> {
+        Ok(Content::Str(v))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Content, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Content::List(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Content, A::Error> {
+        let mut entries = BTreeMap::new();
+        while let Some((key, value)) = map.next_entry()? {
+            entries.insert(key, value);
+        }
+        Ok(Content::Map(entries))
+    }
+}
+
+impl<'de> Deserializer<'de> for Content {
+    type Error = ValueError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        match self {
+            Content::Bool(v) => visitor.visit_bool(v),
+            Content::Int(v) => visitor.visit_i64(v),
+            Content::UInt(v) => visitor.visit_u64(v),
+            Content::Double(v) => visitor.visit_f64(v),
+            Content::Str(v) => visitor.visit_string(v),
+            Content::List(items) => {
+                visitor.visit_seq(de::value::SeqDeserializer::new(items.into_iter()))
+            }
+            Content::Map(entries) => {
+                visitor.visit_map(de::value::MapDeserializer::new(entries.into_iter()))
+            }
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, ValueError> for Content {
+    type Deserializer = Content;
+
+    fn into_deserializer(self) -> Content {
+        self
+    }
+}
+
+/// Writes the values of `map` as the pairs of their variants.
+fn encode_map(map: &PropMap) -> Result<Content, String> {
+    map.iter()
+        .map(|(name, value)| Ok((name.clone(), encode_variant(&*value.0)?)))
+        .collect::<Result<_, String>>()
+        .map(Content::Map)
+}
+
+/// Writes `value` as the pair of its signature and its contents.
+fn encode_variant(value: &dyn RefArg) -> Result<Content, String> {
+    Ok(Content::List(vec![
+        Content::Str(value.signature().to_string()),
+        encode(value)?,
+    ]))
+}
+
+/// The contents of `value`.
+fn encode(value: &dyn RefArg) -> Result<Content, String> {
+    let items = || value.as_iter().into_iter().flatten();
+    Ok(match value.arg_type() {
+        ArgType::Variant => match items().next() {
+            Some(inner) => encode_variant(inner)?,
+            None => return Err("A variant is empty.".to_string()),
+        },
+        ArgType::Boolean => Content::Bool(value.as_u64() == Some(1)),
+        ArgType::Int16 | ArgType::Int32 | ArgType::Int64 => {
+            Content::Int(value.as_i64().unwrap_or_default())
+        }
+        ArgType::Byte | ArgType::UInt16 | ArgType::UInt32 | ArgType::UInt64 => {
+            Content::UInt(value.as_u64().unwrap_or_default())
+        }
+        ArgType::Double => Content::Double(value.as_f64().unwrap_or_default()),
+        ArgType::String | ArgType::ObjectPath | ArgType::Signature => {
+            Content::Str(value.as_str().unwrap_or_default().to_string())
+        }
+        // Dictionaries iterate over their keys and values in turn
+        ArgType::Array if value.signature().starts_with("a{") => {
+            let mut entries = BTreeMap::new();
+            let mut items = items();
+            while let (Some(key), Some(item)) = (items.next(), items.next()) {
+                entries.insert(key_text(key), encode(item)?);
+            }
+            Content::Map(entries)
+        }
+        ArgType::Array | ArgType::Struct => {
+            Content::List(items().map(encode).collect::<Result<_, _>>()?)
+        }
+        _ => {
+            return Err(format!(
+                "Values of type {} can't be serialized.",
+                value.signature()
+            ))
+        }
+    })
+}
+
+/// A dictionary key written out as text.
+fn key_text(key: &dyn RefArg) -> String {
+    match key.arg_type() {
+        ArgType::Boolean => (key.as_u64() == Some(1)).to_string(),
+        ArgType::Int16 | ArgType::Int32 | ArgType::Int64 => {
+            key.as_i64().unwrap_or_default().to_string()
+        }
+        ArgType::Byte | ArgType::UInt16 | ArgType::UInt32 | ArgType::UInt64 => {
+            key.as_u64().unwrap_or_default().to_string()
+        }
+        ArgType::Double => key.as_f64().unwrap_or_default().to_string(),
+        _ => key.as_str().unwrap_or_default().to_string(),
+    }
+}
+
+/// Reads the pair of a variant, as a value of the type it names.
+fn decode_variant(pair: &Content) -> Result<Box<dyn RefArg>, String> {
+    let (signature, contents) = match pair {
+        Content::List(pair) => match pair.as_slice() {
+            [Content::Str(signature), contents] => (signature, contents),
+            _ => return Err("A value isn't a pair of a signature and contents.".to_string()),
+        },
+        _ => return Err("A value isn't a pair of a signature and contents.".to_string()),
+    };
+    if Signature::new(signature.as_str()).is_err() || type_len(signature) != Some(signature.len()) {
+        return Err(format!(
+            "{:?} isn't the signature of a single type.",
+            signature
+        ));
+    }
+
+    decode(signature, contents).map(into_ref_arg)
+}
+
+/// Reads `contents` as a value of the type `signature`, which must
+/// be a single complete type.
+fn decode(signature: &str, contents: &Content) -> Result<MessageItem, String> {
+    let mismatch = || format!("{:?} isn't a value of type {}.", contents, signature);
+    let integer = || match contents {
+        Content::Int(v) => Some(i128::from(*v)),
+        Content::UInt(v) => Some(i128::from(*v)),
+        _ => None,
+    };
+    macro_rules! int {
+        ($variant:ident) => {
+            integer()
+                .and_then(|v| TryFrom::try_from(v).ok())
+                .map(MessageItem::$variant)
+                .ok_or_else(mismatch)
+        };
+    }
+    let text = || match contents {
+        Content::Str(v) => Ok(v.clone()),
+        _ => Err(mismatch()),
+    };
+
+    match signature.as_bytes()[0] {
+        b'b' => match contents {
+            Content::Bool(v) => Ok(MessageItem::Bool(*v)),
+            _ => Err(mismatch()),
+        },
+        b'y' => int!(Byte),
+        b'n' => int!(Int16),
+        b'q' => int!(UInt16),
+        b'i' => int!(Int32),
+        b'u' => int!(UInt32),
+        b'x' => int!(Int64),
+        b't' => int!(UInt64),
+        b'd' => match contents {
+            Content::Double(v) => Ok(MessageItem::Double(*v)),
+            _ => integer()
+                .map(|v| MessageItem::Double(v as f64))
+                .ok_or_else(mismatch),
+        },
+        b's' => text().map(MessageItem::Str),
+        b'o' => Path::new(text()?)
+            .map(MessageItem::ObjectPath)
+            .map_err(|_| mismatch()),
+        b'g' => Signature::new(text()?)
+            .map(MessageItem::Signature)
+            .map_err(|_| mismatch()),
+        b'v' => {
+            let value = match contents {
+                Content::List(pair) => match pair.as_slice() {
+                    [Content::Str(inner), inner_contents] => decode(inner, inner_contents)?,
+                    _ => return Err(mismatch()),
+                },
+                _ => return Err(mismatch()),
+            };
+            Ok(MessageItem::Variant(Box::new(value)))
+        }
+        b'a' if signature.as_bytes()[1] == b'{' => {
+            let key_signature = &signature[2..3];
+            let value_signature = &signature[3..signature.len() - 1];
+            let entries = match contents {
+                Content::Map(entries) => entries,
+                _ => return Err(mismatch()),
+            };
+            let entries = entries
+                .iter()
+                .map(|(key, value)| {
+                    let key = decode(key_signature, &key_contents(key_signature, key))?;
+                    Ok((key, decode(value_signature, value)?))
+                })
+                .collect::<Result<_, String>>()?;
+            MessageItemDict::new(
+                entries,
+                Signature::from(key_signature.to_string()),
+                Signature::from(value_signature.to_string()),
+            )
+            .map(MessageItem::Dict)
+            .map_err(|_| mismatch())
+        }
+        b'a' => {
+            let items = match contents {
+                Content::List(items) => items,
+                _ => return Err(mismatch()),
+            };
+            let items = items
+                .iter()
+                .map(|item| decode(&signature[1..], item))
+                .collect::<Result<_, String>>()?;
+            MessageItemArray::new(items, Signature::from(signature.to_string()))
+                .map(MessageItem::Array)
+                .map_err(|_| mismatch())
+        }
+        b'(' => {
+            let fields = match contents {
+                Content::List(fields) => fields,
+                _ => return Err(mismatch()),
+            };
+            let mut types = &signature[1..signature.len() - 1];
+            let mut decoded = Vec::new();
+            for field in fields {
+                let len = type_len(types).ok_or_else(mismatch)?;
+                decoded.push(decode(&types[..len], field)?);
+                types = &types[len..];
+            }
+            if !types.is_empty() {
+                return Err(mismatch());
+            }
+            Ok(MessageItem::Struct(decoded))
+        }
+        _ => Err(format!(
+            "Values of type {} can't be deserialized.",
+            signature
+        )),
+    }
+}
+
+/// The contents of a dictionary key written out as `text`.
+fn key_contents(signature: &str, text: &str) -> Content {
+    let parsed = match signature {
+        "b" => text.parse().ok().map(Content::Bool),
+        "d" => text.parse().ok().map(Content::Double),
+        "y" | "q" | "u" | "t" => text.parse().ok().map(Content::UInt),
+        "n" | "i" | "x" => text.parse().ok().map(Content::Int),
+        _ => None,
+    };
+
+    parsed.unwrap_or_else(|| Content::Str(text.to_string()))
+}
+
+/// The length of the first complete type in `signature`.
+fn type_len(signature: &str) -> Option<usize> {
+    match *signature.as_bytes().first()? {
+        b'a' => type_len(&signature[1..]).map(|len| len + 1),
+        open @ (b'(' | b'{') => {
+            let close = if open == b'(' { b')' } else { b'}' };
+            let mut len = 1;
+            loop {
+                match *signature.as_bytes().get(len)? {
+                    c if c == close => return Some(len + 1),
+                    _ => len += type_len(&signature[len..])?,
+                }
+            }
+        }
+        _ => Some(1),
+    }
+}
+
+/// Turns `item` into the types values are read as from the bus, so
+/// that helpers such as [`prop_cast`](crate::prop_cast) work on it.
+fn into_ref_arg(item: MessageItem) -> Box<dyn RefArg> {
+    match item {
+        MessageItem::Bool(v) => Box::new(v),
+        MessageItem::Byte(v) => Box::new(v),
+        MessageItem::Int16(v) => Box::new(v),
+        MessageItem::UInt16(v) => Box::new(v),
+        MessageItem::Int32(v) => Box::new(v),
+        MessageItem::UInt32(v) => Box::new(v),
+        MessageItem::Int64(v) => Box::new(v),
+        MessageItem::UInt64(v) => Box::new(v),
+        MessageItem::Double(v) => Box::new(v),
+        MessageItem::Str(v) => Box::new(v),
+        MessageItem::ObjectPath(v) => Box::new(v),
+        MessageItem::Signature(v) => Box::new(v),
+        MessageItem::Variant(inner) => Box::new(Variant(into_ref_arg(*inner))),
+        MessageItem::Array(array) if &**array.signature() == "as" => Box::new(
+            array
+                .into_vec()
+                .into_iter()
+                .filter_map(|item| match item {
+                    MessageItem::Str(v) => Some(v),
+                    _ => None,
+                })
+                .collect::<Vec<String>>(),
+        ),
+        MessageItem::Dict(dict) if &**dict.signature() == "a{sv}" => Box::new(
+            dict.into_vec()
+                .into_iter()
+                .filter_map(|(key, value)| match (key, value) {
+                    (MessageItem::Str(key), MessageItem::Variant(value)) => {
+                        Some((key, Variant(into_ref_arg(*value))))
+                    }
+                    _ => None,
+                })
+                .collect::<PropMap>(),
+        ),
+        item => Box::new(item),
+    }
+}
@@ -0,0 +1,729 @@
+//! The core of this crate on a [`zbus`](::zbus) connection, for
+//! programs already built on zbus.
+//!
+//! It mirrors [`Player`](crate::Player) and
+//! [`get_all_players`](crate::get_all_players), resolving names,
+//! reporting errors and parsing state the same way. Values read from
+//! players are converted into the same `PropMap`s and
+//! [`PlayerState`]s as on the `dbus` side, so that helpers such as
+//! [`prop_str`](crate::prop_str) work on them unchanged, while
+//! [`get_property`](Player::get_property) and
+//! [`set_property`](Player::set_property) take zbus values.
+//!
+//! Events aren't available on this backend yet.
+//!
+//! Failures are reported as the D-Bus errors they carry, so that
+//! they are told apart like those of the `dbus` backend. Failures
+//! that didn't come from the bus, such as an I/O error, are reported
+//! as [`Error::DBus`] with the name [`ZBUS_ERROR`].
+//!
+//! # Example
+//! ```no_run
+//! use pris::zbus::{get_connection, Player};
+//!
+//! #[tokio::main(flavor = "current_thread")]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let conn = get_connection().await?;
+//!     let player = Player::try_new("vlc", &conn).await?;
+//!     player.play_pause().await?;
+//!     Ok(())
+//! }
+//! ```
+use crate::{
+    methods, player::DEFAULT_TIMEOUT, runtime, spec, util, Error, IntoPlayerName, Micros,
+    PlayerCandidate, PlayerState, Result,
+};
+use ::zbus::{
+    zvariant::{DynamicType, ObjectPath, OwnedValue, Type, Value},
+    Connection,
+};
+use dbus::arg::{PropMap, RefArg, Variant};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::TryFrom,
+    future::Future,
+    time::Duration,
+};
+
+const INTERFACE: &str = spec::PLAYER.name;
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+
+/// The D-Bus error name given to zbus failures that didn't come from
+/// the bus, such as an I/O error.
+pub const ZBUS_ERROR: &str = "org.freedesktop.zbus.Error";
+
+/// Establishes a connection to the session bus.
+///
+/// # Errors
+/// May `Err` if the session bus can't be reached.
+pub async fn get_connection() -> Result<Connection> {
+    Connection::session()
+        .await
+        .map_err(|e| Error::DBus(dbus_error(e)))
+}
+
+/// Whether a player with the exact name `player_name` is on the
+/// bus. Names that aren't valid bus names belong to no player.
+///
+/// # Errors
+/// May `Err` if the bus can't be asked.
+pub async fn validate(player_name: &str, conn: &Connection) -> Result<bool> {
+    let name = match util::player_bus_name(player_name) {
+        Some(name) => name,
+        None => return Ok(false),
+    };
+
+    bus_call(conn, "NameHasOwner", &(name.to_string(),)).await
+}
+
+/// Same as [`pris::get_all_players`](crate::get_all_players), on a
+/// zbus connection.
+///
+/// # Errors
+/// May return an `Err` variant if there was a failure in
+/// getting a list of names from `DBus`.
+pub async fn get_all_players(conn: &Connection) -> Result<Vec<Player<'_>>> {
+    let mut players = Vec::new();
+    for name in get_all_names(conn).await? {
+        if let Ok(owner) = get_name_owner(&format!("{}{}", util::MPRIS_PREFIX, name), conn).await {
+            if let Ok(player) = Player::with_owner(name, Some(owner), conn) {
+                players.push(player);
+            }
+        }
+    }
+
+    Ok(players)
+}
+
+/// Calls the method `member` of `interface` of `destination`, reading
+/// its reply as `R`.
+async fn call<B, R>(
+    conn: &Connection,
+    destination: &str,
+    path: &str,
+    interface: &str,
+    member: &str,
+    body: &B,
+) -> std::result::Result<R, dbus::Error>
+where
+    B: Serialize + DynamicType,
+    R: DeserializeOwned + Type,
+{
+    let reply = conn
+        .call_method(Some(destination), path, Some(interface), member, body)
+        .await
+        .map_err(dbus_error)?;
+
+    reply.body().deserialize().map_err(dbus_error)
+}
+
+/// Calls the method `member` of the bus itself.
+async fn bus_call<B, R>(conn: &Connection, member: &str, body: &B) -> Result<R>
+where
+    B: Serialize + DynamicType,
+    R: DeserializeOwned + Type,
+{
+    let result = call(
+        conn,
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus",
+        member,
+        body,
+    );
+    match runtime::timeout(Duration::from_secs(1), result).await {
+        Ok(result) => result.map_err(Error::from),
+        Err(_) => Err(Error::Timeout {
+            player: None,
+            operation: member.to_string(),
+            limit: Duration::from_secs(1),
+            source: None,
+        }),
+    }
+}
+
+/// The D-Bus error a zbus error carries, so that it can be classified
+/// like those of the `dbus` backend.
+fn dbus_error(e: ::zbus::Error) -> dbus::Error {
+    match e {
+        ::zbus::Error::MethodError(name, message, _) => {
+            dbus::Error::new_custom(name.as_str(), message.as_deref().unwrap_or_default())
+        }
+        e => dbus::Error::new_custom(ZBUS_ERROR, &e.to_string()),
+    }
+}
+
+async fn get_all_names(conn: &Connection) -> Result<Vec<String>> {
+    let services: Vec<String> = bus_call(conn, "ListNames", &()).await?;
+    let mut names = util::player_names(services);
+    names.sort();
+    Ok(names)
+}
+
+async fn get_name_owner(name: &str, conn: &Connection) -> Result<String> {
+    bus_call(conn, "GetNameOwner", &(name,)).await
+}
+
+/// Resolves `name` to the one player it refers to, as described on
+/// [`Player::try_new`].
+async fn resolve_name(name: &str, conn: &Connection) -> Result<String> {
+    if validate(name, conn).await? {
+        return Ok(name.to_string());
+    }
+
+    let names = get_all_names(conn).await?;
+    let mut instances = util::instances_of(name, &names);
+    match instances.len() {
+        0 => return Err(Error::unknown_player(name, names)),
+        1 => return Ok(instances.remove(0)),
+        _ => {}
+    }
+
+    // Instances that left since being listed aren't in the way
+    let mut candidates = Vec::with_capacity(instances.len());
+    for name in instances {
+        let unique_name =
+            match get_name_owner(&format!("{}{}", util::MPRIS_PREFIX, name), conn).await {
+                Ok(owner) => owner,
+                Err(_) => continue,
+            };
+
+        let identity = call::<_, OwnedValue>(
+            conn,
+            &unique_name,
+            MPRIS_PATH,
+            PROPERTIES_INTERFACE,
+            "Get",
+            &("org.mpris.MediaPlayer2", "Identity"),
+        );
+        let identity = match runtime::timeout(util::IDENTITY_TIMEOUT, identity).await {
+            Ok(Ok(identity)) => String::try_from(identity).ok(),
+            _ => None,
+        };
+
+        candidates.push(PlayerCandidate {
+            name,
+            unique_name,
+            identity,
+        });
+    }
+
+    util::pick_candidate(name, candidates)
+}
+
+/// Converts a zbus value into the boxed value the `dbus` backend
+/// would have read, or `None` for file descriptors.
+fn ref_arg(value: &Value<'_>) -> Option<Box<dyn RefArg>> {
+    let value: Box<dyn RefArg> = match value {
+        Value::U8(v) => Box::new(*v),
+        Value::Bool(v) => Box::new(*v),
+        Value::I16(v) => Box::new(*v),
+        Value::U16(v) => Box::new(*v),
+        Value::I32(v) => Box::new(*v),
+        Value::U32(v) => Box::new(*v),
+        Value::I64(v) => Box::new(*v),
+        Value::U64(v) => Box::new(*v),
+        Value::F64(v) => Box::new(*v),
+        Value::Str(v) => Box::new(v.to_string()),
+        Value::Signature(v) => Box::new(v.to_string()),
+        Value::ObjectPath(v) => Box::new(dbus::Path::from(v.to_string())),
+        Value::Value(v) => Box::new(Variant(ref_arg(v)?)),
+        Value::Array(array) => {
+            let strings: Option<Vec<String>> = array
+                .iter()
+                .map(|item| match item {
+                    Value::Str(s) => Some(s.to_string()),
+                    _ => None,
+                })
+                .collect();
+            match strings {
+                Some(strings) => Box::new(strings),
+                None => Box::new(
+                    array
+                        .iter()
+                        .filter_map(|item| ref_arg(item).map(Variant))
+                        .collect::<Vec<_>>(),
+                ),
+            }
+        }
+        Value::Dict(_) => Box::new(prop_map(value)?),
+        Value::Structure(structure) => Box::new(
+            structure
+                .fields()
+                .iter()
+                .filter_map(ref_arg)
+                .collect::<VecDeque<_>>(),
+        ),
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+/// Converts a dictionary with string keys, such as `Metadata`, into
+/// a `PropMap`, unwrapping the variants around its values.
+fn prop_map(value: &Value<'_>) -> Option<PropMap> {
+    let dict = match value {
+        Value::Dict(dict) => dict,
+        Value::Value(inner) => return prop_map(inner),
+        _ => return None,
+    };
+
+    let mut map = PropMap::new();
+    for (key, value) in dict.iter() {
+        let key = match key {
+            Value::Str(key) => key.to_string(),
+            _ => return None,
+        };
+        let value = match value {
+            Value::Value(inner) => &**inner,
+            value => value,
+        };
+        if let Some(value) = ref_arg(value) {
+            map.insert(key, Variant(value));
+        }
+    }
+
+    Some(map)
+}
+
+/// The counterpart of [`pris::Player`](crate::Player) on a zbus
+/// connection, used to control an MPRIS player.
+#[derive(Clone)]
+pub struct Player<'a> {
+    /// The name of the player, without the `org.mpris.MediaPlayer2.`
+    /// prefix. Changing it doesn't change where calls are sent.
+    pub name: String,
+    unique: Option<String>,
+    destination: String,
+    conn: &'a Connection,
+    timeout: Duration,
+    volume_ceiling: f64,
+}
+
+impl<'a> Player<'a> {
+    /// Same as [`pris::Player::try_new`](crate::Player::try_new).
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidArgument`] if `name` can't be the name
+    /// of a player, [`Error::UnknownPlayer`] if no player goes by
+    /// `name`, or [`Error::AmbiguousPlayer`] if several instances of
+    /// it are running.
+    pub async fn try_new<T>(name: T, conn: &'a Connection) -> Result<Player<'a>>
+    where
+        T: IntoPlayerName,
+    {
+        let name = name.into_player_name()?;
+        let name = resolve_name(name.as_str(), conn).await?;
+        Player::with_owner(name, None, conn)
+    }
+
+    /// Same as [`pris::Player::try_pinned`](crate::Player::try_pinned).
+    ///
+    /// # Errors
+    /// Same as `try_new`.
+    pub async fn try_pinned<T>(name: T, conn: &'a Connection) -> Result<Player<'a>>
+    where
+        T: IntoPlayerName,
+    {
+        let name = name.into_player_name()?;
+        let name = resolve_name(name.as_str(), conn).await?;
+        let owner = get_name_owner(&format!("{}{}", util::MPRIS_PREFIX, name), conn)
+            .await
+            .map_err(|_| Error::InvalidPlayer(name.clone()))?;
+
+        Player::with_owner(name, Some(owner), conn)
+    }
+
+    /// A `Player` for the player `name`, pinned to the connection
+    /// `unique` if given.
+    fn with_owner(name: String, unique: Option<String>, conn: &'a Connection) -> Result<Self> {
+        let destination = match &unique {
+            Some(unique) => unique.clone(),
+            None => match util::player_bus_name(&name) {
+                Some(destination) => destination.to_string(),
+                None => return Err(Error::InvalidPlayer(name)),
+            },
+        };
+
+        Ok(Player {
+            name,
+            unique,
+            destination,
+            conn,
+            timeout: DEFAULT_TIMEOUT,
+            volume_ceiling: 1.0,
+        })
+    }
+
+    /// The unique name of the connection this `Player` is pinned to,
+    /// if it is, as by [`try_pinned`](Self::try_pinned).
+    pub fn unique_name(&self) -> Option<&str> {
+        self.unique.as_deref()
+    }
+
+    /// Sets how long the player is given to answer each call made
+    /// through this `Player`, before it fails with [`Error::Timeout`].
+    /// The default is 5 seconds.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// How long the player is given to answer each call.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Same as
+    /// [`pris::Player::set_volume_ceiling`](crate::Player::set_volume_ceiling).
+    pub fn set_volume_ceiling(&mut self, ceiling: f64) {
+        self.volume_ceiling = ceiling.max(0.0);
+    }
+
+    /// The highest volume the volume helpers go to.
+    pub fn volume_ceiling(&self) -> f64 {
+        self.volume_ceiling
+    }
+
+    /// Runs the call `operation`, giving up on it after the timeout.
+    async fn timed<T, F>(&self, operation: &str, call: F) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        match runtime::timeout(self.timeout, call).await {
+            Ok(result) => result,
+            Err(_) => Err(Error::Timeout {
+                player: Some(self.name.clone()),
+                operation: operation.to_string(),
+                limit: self.timeout,
+                source: None,
+            }),
+        }
+    }
+
+    /// Calls the method `member` of the Player interface with `body`.
+    async fn call_method<B>(&self, member: &str, body: &B) -> Result<()>
+    where
+        B: Serialize + DynamicType,
+    {
+        let result = call::<_, ()>(
+            self.conn,
+            &self.destination,
+            MPRIS_PATH,
+            INTERFACE,
+            member,
+            body,
+        );
+        self.timed(member, async {
+            result
+                .await
+                .map_err(|e| Error::from_call(&self.name, member, self.timeout, e))
+        })
+        .await
+    }
+
+    /// Calls the method `member` of the Properties interface with
+    /// `body`, to read or be `writing` the property `property`.
+    async fn property_call<B, R>(
+        &self,
+        member: &str,
+        property: &str,
+        writing: bool,
+        body: &B,
+    ) -> Result<R>
+    where
+        B: Serialize + DynamicType,
+        R: DeserializeOwned + Type,
+    {
+        let result = call(
+            self.conn,
+            &self.destination,
+            MPRIS_PATH,
+            PROPERTIES_INTERFACE,
+            member,
+            body,
+        );
+        self.timed(property, async {
+            result.await.map_err(|e| {
+                Error::from_property_call(&self.name, property, self.timeout, writing, e)
+            })
+        })
+        .await
+    }
+
+    /// Reads the property `property` as the player sent it.
+    async fn get(&self, property: &str) -> Result<OwnedValue> {
+        self.property_call("Get", property, false, &(INTERFACE, property))
+            .await
+    }
+
+    /// Skips to the next track
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::PlayerGone`] if the `Player` has closed.
+    pub async fn next(&self) -> Result<()> {
+        self.call_method("Next", &()).await
+    }
+
+    /// Skips to the previous track
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::PlayerGone`] if the `Player` has closed.
+    pub async fn previous(&self) -> Result<()> {
+        self.call_method("Previous", &()).await
+    }
+
+    /// Pauses the current track
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::PlayerGone`] if the `Player` has closed,
+    /// or [`Error::UnsupportedOperation`] if it rejects `Pause` as
+    /// unknown or unsupported.
+    pub async fn pause(&self) -> Result<()> {
+        self.call_method("Pause", &()).await.map_err(|e| match e {
+            Error::DBus(e) if methods::is_unsupported(&e) => {
+                methods::cant_pause(&self.name, Some(e))
+            }
+            e => e,
+        })
+    }
+
+    /// Starts or resumes the current track
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::PlayerGone`] if the `Player` has closed.
+    pub async fn play(&self) -> Result<()> {
+        self.call_method("Play", &()).await
+    }
+
+    /// Same as [`pris::Player::play_pause`](crate::Player::play_pause),
+    /// falling back to `Play` or `Pause` the same way.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::PlayerGone`] if the `Player` has closed,
+    /// or [`Error::UnsupportedOperation`] if the fallback has to pause
+    /// a player that can't be paused.
+    pub async fn play_pause(&self) -> Result<()> {
+        match self.call_method("PlayPause", &()).await {
+            Err(Error::DBus(e)) if methods::is_unsupported(&e) => {}
+            result => return result,
+        }
+
+        let state = self.get_state().await?;
+        if methods::fallback_pauses(&self.name, &state)? {
+            self.pause().await
+        } else {
+            self.play().await
+        }
+    }
+
+    /// Stops playback
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::PlayerGone`] if the `Player` has closed.
+    pub async fn stop(&self) -> Result<()> {
+        self.call_method("Stop", &()).await
+    }
+
+    /// Retrieves track metadata from the `Player`, converted into the
+    /// same `PropMap` the `dbus` backend returns.
+    ///
+    /// # Errors
+    /// May `Err` if there is a failure in getting the metadata.
+    pub async fn get_metadata(&self) -> Result<PropMap> {
+        let value = self.get("Metadata").await?;
+        prop_map(&value).ok_or_else(|| Error::TypeMismatch {
+            player: self.name.clone(),
+            property: "Metadata".to_string(),
+            expected: "a{sv}".to_string(),
+            actual: value.value_signature().to_string(),
+        })
+    }
+
+    /// Retrieves the metadata of the active track, or `None` if
+    /// nothing is playing, as decided by [`is_no_track`](crate::is_no_track).
+    ///
+    /// # Errors
+    /// May `Err` if there is a failure in getting the metadata.
+    pub async fn metadata(&self) -> Result<Option<PropMap>> {
+        let metadata = self.get_metadata().await?;
+        Ok(Some(metadata).filter(|m| !util::is_no_track(m)))
+    }
+
+    /// Retrieves all of the `Player`'s properties at once.
+    ///
+    /// # Errors
+    /// May `Err` if there is a failure in getting the properties.
+    pub async fn get_state(&self) -> Result<PlayerState> {
+        let result = call::<_, HashMap<String, OwnedValue>>(
+            self.conn,
+            &self.destination,
+            MPRIS_PATH,
+            PROPERTIES_INTERFACE,
+            "GetAll",
+            &(INTERFACE,),
+        );
+        let properties = self
+            .timed("GetAll", async {
+                result
+                    .await
+                    .map_err(|e| Error::from_call(&self.name, "GetAll", self.timeout, e))
+            })
+            .await?;
+
+        let properties = properties
+            .iter()
+            .filter_map(|(name, value)| Some((name.clone(), Variant(ref_arg(value)?))))
+            .collect();
+        Ok(PlayerState::from_properties(self.name.clone(), properties))
+    }
+
+    /// Retrieves the value of an MPRIS property, as any type a zbus
+    /// value converts into.
+    ///
+    /// # Errors
+    /// May return an `Err` variant if:
+    /// * An invalid type was provided for the property, as
+    ///   [`Error::TypeMismatch`]
+    /// * An invalid property was provided, as [`Error::UnknownProperty`]
+    pub async fn get_property<T>(&self, property: &str) -> Result<T>
+    where
+        T: TryFrom<OwnedValue>,
+    {
+        let value = self.get(property).await?;
+        let actual = value.value_signature().to_string();
+
+        T::try_from(value).map_err(|_| Error::TypeMismatch {
+            player: self.name.clone(),
+            property: property.to_string(),
+            expected: std::any::type_name::<T>().to_string(),
+            actual,
+        })
+    }
+
+    /// Sets the value of a writable MPRIS property.
+    ///
+    /// # Errors
+    /// May return an `Err` variant if:
+    /// * An invalid type was provided for the property
+    /// * An invalid property was provided, as [`Error::UnknownProperty`]
+    pub async fn set_property<'v, T>(&self, property: &str, value: T) -> Result<()>
+    where
+        T: Into<Value<'v>>,
+    {
+        let body = (INTERFACE, property, value.into());
+        self.property_call("Set", property, true, &body).await
+    }
+
+    /// Retrieves the volume as the player reports it, even above
+    /// the [volume ceiling](Self::set_volume_ceiling).
+    ///
+    /// # Errors
+    /// May `Err` if there is a failure in getting the volume.
+    pub async fn get_volume(&self) -> Result<f64> {
+        self.get_property("Volume").await
+    }
+
+    /// Sets the volume, clamped to between 0.0 and the
+    /// [volume ceiling](Self::set_volume_ceiling).
+    /// Returns the volume that was set.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::InvalidArgument`] if `volume` is NaN.
+    pub async fn set_volume(&self, volume: f64) -> Result<f64> {
+        let volume = methods::clamp_volume(volume, self.volume_ceiling)?;
+        self.set_property("Volume", volume).await?;
+        Ok(volume)
+    }
+
+    /// Changes the volume by `delta`, clamped like `set_volume`.
+    /// Returns the volume that was set.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::InvalidArgument`] if `delta` is NaN.
+    pub async fn adjust_volume(&self, delta: f64) -> Result<f64> {
+        let volume = self.get_volume().await?;
+        self.set_volume(volume + delta).await
+    }
+
+    /// Retrieves the position of the active track. Positions of any
+    /// integer type are accepted, and negative ones are clamped to
+    /// zero.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::TypeMismatch`] if the position isn't
+    /// an integer.
+    pub async fn get_position(&self) -> Result<Duration> {
+        let value = self.get("Position").await?;
+        match ref_arg(&value) {
+            Some(position) => methods::read_position(&self.name, &*position),
+            None => Err(Error::TypeMismatch {
+                player: self.name.clone(),
+                property: "Position".to_string(),
+                expected: "an integer".to_string(),
+                actual: value.value_signature().to_string(),
+            }),
+        }
+    }
+
+    /// Seeks the position of the active track. Offsets too large to
+    /// send are saturated, at some 292 thousand years.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::NoActiveTrack`] if there is no active track.
+    pub async fn seek(&self, offset: Duration) -> Result<()> {
+        self.active_track().await?;
+        self.call_method("Seek", &(Micros::from_duration(offset).get(),))
+            .await
+    }
+
+    /// Same as `seek`, but in reverse.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::NoActiveTrack`] if there is no active track.
+    pub async fn seek_reverse(&self, offset: Duration) -> Result<()> {
+        self.active_track().await?;
+        self.call_method("Seek", &((-Micros::from_duration(offset)).get(),))
+            .await
+    }
+
+    /// Sets the position of the current track. Positions too large
+    /// to send are saturated, at some 292 thousand years.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::NoActiveTrack`] if there is no active track,
+    /// or [`Error::UnsupportedOperation`] if the player doesn't report the
+    /// id of its track.
+    pub async fn set_position(&self, position: Duration) -> Result<()> {
+        let metadata = self.active_track().await?;
+        let track_id = methods::track_id(&self.name, &metadata)?;
+        let track_id = ObjectPath::try_from(&**track_id)
+            .map_err(|e| Error::DBus(dbus::Error::new_custom(ZBUS_ERROR, &e.to_string())))?;
+
+        self.call_method(
+            "SetPosition",
+            &(track_id, Micros::from_duration(position).get()),
+        )
+        .await
+    }
+
+    /// Opens a track by its URI.
+    ///
+    /// # Errors
+    /// May return an `Err` variant if the provided URI is invalid.
+    pub async fn open_uri(&self, uri: &str) -> Result<()> {
+        self.call_method("OpenUri", &(uri,)).await
+    }
+
+    /// Retrieves the metadata of the active track.
+    ///
+    /// # Errors
+    /// Will `Err` with [`Error::NoActiveTrack`] if nothing is playing.
+    async fn active_track(&self) -> Result<PropMap> {
+        self.metadata()
+            .await?
+            .ok_or_else(|| Error::NoActiveTrack(self.name.clone()))
+    }
+}
@@ -0,0 +1,431 @@
+//! Shared helpers for the integration tests.
+#![allow(dead_code)]
+
+use dbus::{
+    arg::{Append, PropMap, RefArg, Variant},
+    channel::{Channel, MatchingReceiver, Sender},
+    message::{MatchRule, Message},
+    nonblock::{Proxy, SyncConnection},
+    strings::{BusName, ErrorName},
+};
+use std::{
+    ffi::CString,
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// A private `dbus-daemon`, killed when dropped.
+pub struct TestBus {
+    daemon: Child,
+    pub address: String,
+}
+
+impl TestBus {
+    pub fn new() -> TestBus {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("dbus-daemon is required to run the bus tests");
+
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+
+        TestBus {
+            daemon,
+            address: address.trim().to_string(),
+        }
+    }
+
+    /// Opens a new connection to the bus, driven on the current runtime.
+    pub fn connect(&self) -> Arc<SyncConnection> {
+        let mut channel = Channel::open_private(&self.address).unwrap();
+        channel.register().unwrap();
+        let (resource, conn) = dbus_tokio::connection::from_channel(channel).unwrap();
+        tokio::spawn(resource);
+
+        conn
+    }
+
+    /// Opens a new blocking connection to the bus.
+    pub fn connect_blocking(&self) -> dbus::blocking::Connection {
+        let mut channel = Channel::open_private(&self.address).unwrap();
+        channel.register().unwrap();
+
+        channel.into()
+    }
+
+    /// Opens a connection owning `org.mpris.MediaPlayer2.<name>`.
+    pub async fn connect_as(&self, name: &str) -> Arc<SyncConnection> {
+        let conn = self.connect();
+        conn.request_name(
+            format!("org.mpris.MediaPlayer2.{}", name),
+            false,
+            true,
+            true,
+        )
+        .await
+        .unwrap();
+
+        conn
+    }
+}
+
+impl Drop for TestBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+/// Polls `check` for up to a second, for signals to go through the bus.
+pub async fn eventually<F, Fut>(mut check: F) -> bool
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    for _ in 0..100 {
+        if check().await {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    false
+}
+
+/// The number of match rules the bus holds for `conn`.
+///
+/// The query is sent over `conn` itself, so any rule removals
+/// it sent beforehand have been processed by the time it returns.
+pub async fn match_rules(conn: &SyncConnection) -> u32 {
+    let proxy = Proxy::new(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        Duration::from_secs(1),
+        conn,
+    );
+    let (stats,): (PropMap,) = proxy
+        .method_call(
+            "org.freedesktop.DBus.Debug.Stats",
+            "GetConnectionStats",
+            (conn.unique_name().to_string(),),
+        )
+        .await
+        .unwrap();
+
+    stats["MatchRules"].0.as_u64().unwrap() as u32
+}
+
+/// Records the members of the calls `sender` makes to the bus
+/// itself, such as `ListNames`, seen through a monitor connection.
+pub async fn bus_calls(bus: &TestBus, sender: &str) -> Arc<Mutex<Vec<String>>> {
+    monitor_calls(
+        bus,
+        format!(
+            "type='method_call',sender='{}',destination='org.freedesktop.DBus'",
+            sender
+        ),
+    )
+    .await
+}
+
+/// Records the members of every call `sender` makes, to the bus or
+/// to players, seen through a monitor connection.
+pub async fn calls_from(bus: &TestBus, sender: &str) -> Arc<Mutex<Vec<String>>> {
+    monitor_calls(bus, format!("type='method_call',sender='{}'", sender)).await
+}
+
+/// Records the match rules `sender` adds to the bus, as sent, seen
+/// through a monitor connection.
+pub async fn added_rules(bus: &TestBus, sender: &str) -> Arc<Mutex<Vec<String>>> {
+    monitor(
+        bus,
+        format!(
+            "type='method_call',sender='{}',destination='org.freedesktop.DBus',member='AddMatch'",
+            sender
+        ),
+        |msg| msg.read1::<&str>().ok().map(str::to_string),
+    )
+    .await
+}
+
+/// Records the members of the calls matching `rule`, seen through a
+/// monitor connection.
+async fn monitor_calls(bus: &TestBus, rule: String) -> Arc<Mutex<Vec<String>>> {
+    monitor(bus, rule, |msg| {
+        msg.member().map(|member| member.to_string())
+    })
+    .await
+}
+
+/// Records what `record` takes from the calls matching `rule`, seen
+/// through a monitor connection.
+async fn monitor<F>(bus: &TestBus, rule: String, record: F) -> Arc<Mutex<Vec<String>>>
+where
+    F: Fn(&Message) -> Option<String> + Send + 'static,
+{
+    let monitor = bus.connect();
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let recorded = calls.clone();
+    monitor.start_receive(
+        MatchRule::new_method_call(),
+        Box::new(move |msg, _| {
+            if let Some(value) = record(&msg) {
+                recorded.lock().unwrap().push(value);
+            }
+            true
+        }),
+    );
+
+    let proxy = Proxy::new(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        Duration::from_secs(1),
+        monitor,
+    );
+    proxy
+        .method_call::<(), _, _, _>(
+            "org.freedesktop.DBus.Monitoring",
+            "BecomeMonitor",
+            (vec![rule], 0u32),
+        )
+        .await
+        .unwrap();
+
+    calls
+}
+
+/// Answers `Get` and `GetAll` calls for the Player interface on
+/// `conn` from the returned map, which tests may update.
+pub fn serve_properties(conn: &SyncConnection, properties: PropMap) -> Arc<Mutex<PropMap>> {
+    serve_player(conn, properties, |_, _| None)
+}
+
+/// Same as `serve_properties`, answering other method calls with
+/// `call`, which may update the properties. Calls it returns `None`
+/// for fail with `UnknownMethod`.
+pub fn serve_player<F>(
+    conn: &SyncConnection,
+    properties: PropMap,
+    mut call: F,
+) -> Arc<Mutex<PropMap>>
+where
+    F: FnMut(&Message, &mut PropMap) -> Option<Message> + Send + 'static,
+{
+    let properties = Arc::new(Mutex::new(properties));
+    let served = properties.clone();
+    conn.start_receive(
+        MatchRule::new_method_call(),
+        Box::new(move |msg, conn| {
+            let mut properties = served.lock().unwrap();
+            let reply = match msg.member().as_deref() {
+                Some("Get") => {
+                    let (_, name): (String, String) = msg.read2().unwrap();
+                    match properties.get(&name) {
+                        Some(value) => msg.method_return().append1(Variant(value.0.box_clone())),
+                        None => msg.error(
+                            &ErrorName::new("org.freedesktop.DBus.Error.UnknownProperty").unwrap(),
+                            &CString::new(name).unwrap(),
+                        ),
+                    }
+                }
+                Some("GetAll") => msg.method_return().append1(clone_props(&properties)),
+                _ => call(&msg, &mut properties).unwrap_or_else(|| {
+                    msg.error(
+                        &ErrorName::new("org.freedesktop.DBus.Error.UnknownMethod").unwrap(),
+                        &CString::new("Not implemented by the test player").unwrap(),
+                    )
+                }),
+            };
+            let _ = conn.send(reply);
+            true
+        }),
+    );
+
+    properties
+}
+
+/// Answers calls of `members` on `conn` with an empty reply, and
+/// records them in the returned list. Set up before
+/// `serve_properties` to take precedence over it.
+pub fn serve_methods(conn: &SyncConnection, members: &[&'static str]) -> Arc<Mutex<Vec<String>>> {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    for &member in members {
+        let calls = calls.clone();
+        conn.start_receive(
+            MatchRule::new_method_call().with_member(member),
+            Box::new(move |msg, conn| {
+                calls.lock().unwrap().push(member.to_string());
+                let _ = conn.send(msg.method_return());
+                true
+            }),
+        );
+    }
+
+    calls
+}
+
+/// Answers every method call on `conn` with the D-Bus error `name`.
+pub fn serve_error(conn: &SyncConnection, name: &'static str) {
+    conn.start_receive(
+        MatchRule::new_method_call(),
+        Box::new(move |msg, conn| {
+            let reply = msg.error(
+                &ErrorName::new(name).unwrap(),
+                &CString::new("Injected by the test player").unwrap(),
+            );
+            let _ = conn.send(reply);
+            true
+        }),
+    );
+}
+
+pub fn clone_props(properties: &PropMap) -> PropMap {
+    properties
+        .iter()
+        .map(|(k, v)| (k.clone(), Variant(v.0.box_clone())))
+        .collect()
+}
+
+/// Emits `msg` from `conn`, clearing any fixture sender first.
+pub fn emit(conn: &SyncConnection, mut msg: Message) {
+    msg.set_sender(None);
+    conn.send(msg).unwrap();
+}
+
+pub const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+pub const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+/// Builds a signal resembling one captured from a player
+/// whose unique name is `sender`.
+pub fn signal(sender: &str, interface: &str, member: &str) -> Message {
+    let mut msg = Message::new_signal(MPRIS_PATH, interface, member).unwrap();
+    msg.set_sender(Some(BusName::new(sender).unwrap()));
+    msg
+}
+
+/// A `Seeked` signal carrying `position` microseconds.
+pub fn seeked<T: Append>(sender: &str, position: T) -> Message {
+    signal(sender, PLAYER_INTERFACE, "Seeked").append1(position)
+}
+
+/// Wraps a value the way it appears inside an `a{sv}`.
+pub fn var<T: RefArg + 'static>(value: T) -> Variant<Box<dyn RefArg>> {
+    Variant(Box::new(value))
+}
+
+/// Builds a `PropMap` from `(name, value)` pairs.
+pub fn props(entries: Vec<(&str, Variant<Box<dyn RefArg>>)>) -> PropMap {
+    entries
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect()
+}
+
+/// A `PropertiesChanged` signal for `interface`.
+pub fn properties_changed(
+    sender: &str,
+    interface: &str,
+    changed: PropMap,
+    invalidated: Vec<&str>,
+) -> Message {
+    let invalidated: Vec<String> = invalidated.into_iter().map(String::from).collect();
+    signal(
+        sender,
+        "org.freedesktop.DBus.Properties",
+        "PropertiesChanged",
+    )
+    .append3(interface, changed, invalidated)
+}
+
+/// A `NameOwnerChanged` signal as sent by the bus.
+pub fn name_owner_changed(name: &str, old_owner: &str, new_owner: &str) -> Message {
+    let mut msg = Message::new_signal(
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus",
+        "NameOwnerChanged",
+    )
+    .unwrap()
+    .append3(name, old_owner, new_owner);
+    msg.set_sender(Some(BusName::new("org.freedesktop.DBus").unwrap()));
+    msg
+}
+
+/// The metadata of a track as captured from a music player: 30
+/// entries covering the common fields, the less common xesam ones,
+/// and a few player-specific extras.
+pub fn captured_metadata() -> PropMap {
+    let list = |items: &[&str]| var(items.iter().map(|s| s.to_string()).collect::<Vec<_>>());
+    props(vec![
+        (
+            "mpris:trackid",
+            var(dbus::Path::new("/org/mpris/MediaPlayer2/Track/42").unwrap()),
+        ),
+        ("mpris:length", var(254_000_000i64)),
+        (
+            "mpris:artUrl",
+            var("file:///home/user/.cache/art/42.jpg".to_string()),
+        ),
+        ("xesam:title", var("Windowlicker".to_string())),
+        ("xesam:album", var("Windowlicker".to_string())),
+        ("xesam:artist", list(&["Aphex Twin"])),
+        ("xesam:albumArtist", list(&["Aphex Twin"])),
+        (
+            "xesam:url",
+            var("file:///home/user/Music/Windowlicker.flac".to_string()),
+        ),
+        ("xesam:genre", list(&["Electronic", "IDM"])),
+        ("xesam:trackNumber", var(1i32)),
+        ("xesam:discNumber", var(1i32)),
+        ("xesam:composer", list(&["Richard D. James"])),
+        ("xesam:lyricist", list(&["Richard D. James"])),
+        ("xesam:comment", list(&["Ripped from the 1999 single"])),
+        (
+            "xesam:contentCreated",
+            var("1999-03-22T00:00:00Z".to_string()),
+        ),
+        ("xesam:firstUsed", var("2021-06-01T18:04:11Z".to_string())),
+        ("xesam:lastUsed", var("2024-02-10T21:37:02Z".to_string())),
+        ("xesam:useCount", var(73i32)),
+        ("xesam:userRating", var(0.8f64)),
+        ("xesam:autoRating", var(0.65f64)),
+        ("xesam:audioBPM", var(127i32)),
+        ("xesam:asText", var(String::new())),
+        (
+            "xesam:musicBrainzTrackID",
+            list(&["8d9e9f5c-0b3f-4a3e-9ad4-5b1c1c2c4e6a"]),
+        ),
+        (
+            "xesam:musicBrainzAlbumID",
+            list(&["1b7c3d21-2f49-4e5b-8a8d-9c1e0f3a7b52"]),
+        ),
+        (
+            "xesam:musicBrainzArtistID",
+            list(&["f22942a1-6f70-4f48-866e-238cb2308fbd"]),
+        ),
+        (
+            "xesam:musicBrainzAlbumArtistID",
+            list(&["f22942a1-6f70-4f48-866e-238cb2308fbd"]),
+        ),
+        ("vlc:length", var(254_000i64)),
+        ("vlc:publisher", var("Warp Records".to_string())),
+        ("vlc:encodedby", var("FLAC 1.3.2".to_string())),
+        ("vlc:copyright", var("1999 Warp Records".to_string())),
+    ])
+}
+
+/// A Player `PropertiesChanged` signal as captured from a music
+/// player changing tracks, carrying `captured_metadata` along with
+/// the playback status.
+pub fn captured_track_change(sender: &str) -> Message {
+    let mut changed = PropMap::new();
+    changed.insert("Metadata".to_string(), var(captured_metadata()));
+    changed.insert("PlaybackStatus".to_string(), var("Playing".to_string()));
+    properties_changed(sender, PLAYER_INTERFACE, changed, vec![])
+}
@@ -0,0 +1,39 @@
+/* Links against libpris built with the ffi feature, and runs through
+ * the lifecycle of a context on a bus without players. */
+#include <stdio.h>
+#include <string.h>
+
+#include "pris.h"
+
+#define CHECK(cond)                                                   \
+    do {                                                              \
+        if (!(cond)) {                                                \
+            fprintf(stderr, "%s:%d: %s\n", __FILE__, __LINE__, #cond); \
+            return 1;                                                 \
+        }                                                             \
+    } while (0)
+
+int main(void) {
+    PrisContext *ctx = NULL;
+    CHECK(pris_context_new(&ctx) == PRIS_STATUS_OK);
+    CHECK(ctx != NULL);
+
+    char **names = NULL;
+    size_t count = 0;
+    CHECK(pris_list_players(ctx, &names, &count) == PRIS_STATUS_OK);
+    for (size_t i = 0; i < count; i++)
+        printf("%s\n", names[i]);
+    pris_players_free(names, count);
+    CHECK(strcmp(pris_last_error(ctx), "") == 0);
+
+    double volume = 0.0;
+    CHECK(pris_play_pause(ctx, "pris.nonexistent") == PRIS_STATUS_INVALID_PLAYER);
+    CHECK(strlen(pris_last_error(ctx)) > 0);
+    CHECK(pris_get_volume(ctx, "pris.nonexistent", &volume) == PRIS_STATUS_INVALID_PLAYER);
+    CHECK(pris_get_volume(ctx, NULL, &volume) == PRIS_STATUS_INVALID_ARGUMENT);
+    CHECK(pris_play(NULL, "pris.nonexistent") == PRIS_STATUS_INVALID_ARGUMENT);
+
+    pris_context_free(ctx);
+    pris_context_free(NULL);
+    return 0;
+}
@@ -0,0 +1,71 @@
+//! Coarse allocation budgets for the hot paths, so that egregious
+//! regressions fail rather than drift. Timings are covered by the
+//! benchmarks in `benches/`.
+
+mod common;
+
+use pris::{Event, Metadata};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+/// Counts the allocations made on each thread, so that tests running
+/// in parallel don't skew each other's counts.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// The number of allocations `f` makes on the current thread, not
+/// counting what libdbus allocates itself.
+fn allocations<T>(f: impl FnOnce() -> T) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    let value = f();
+    let count = ALLOCATIONS.with(Cell::get) - before;
+    drop(value);
+
+    count
+}
+
+#[test]
+fn test_metadata_allocations() {
+    let msg = common::signal(":1.1", common::PLAYER_INTERFACE, "Metadata")
+        .append1(common::captured_metadata());
+    msg.read1::<Metadata>().unwrap();
+
+    let count = allocations(|| msg.read1::<Metadata>().unwrap());
+    assert!(
+        count <= 500,
+        "Decoding 30 entries took {} allocations",
+        count
+    );
+}
+
+#[test]
+fn test_event_allocations() {
+    let msg = common::captured_track_change(":1.1");
+    Event::parse(&msg, "test").unwrap();
+
+    let count = allocations(|| Event::parse(&msg, "test").unwrap());
+    assert!(
+        count <= 1000,
+        "Parsing a track change took {} allocations",
+        count
+    );
+}
@@ -0,0 +1,32 @@
+#![cfg(feature = "smol")]
+
+mod common;
+
+use pris::{EventManager, EventType, Player};
+use std::time::Duration;
+
+// Runs without a tokio runtime, the connections being driven by
+// smol's reactor underneath async-std's executor
+#[async_std::test]
+async fn test_async_std_smoke() {
+    let bus = common::TestBus::new();
+    std::env::set_var("DBUS_SESSION_BUS_ADDRESS", &bus.address);
+
+    let vlc = pris::get_connection();
+    vlc.request_name("org.mpris.MediaPlayer2.vlc", false, true, true)
+        .await
+        .unwrap();
+    let calls = common::serve_methods(&vlc, &["Pause"]);
+
+    let conn = pris::get_connection();
+    let player = Player::try_new("vlc", &conn).await.unwrap();
+    player.pause().await.unwrap();
+    assert_eq!(*calls.lock().unwrap(), vec!["Pause"]);
+
+    // Timeouts are kept by smol's timers
+    let events = EventManager::new(&conn);
+    let waited = events
+        .wait_for_event(EventType::Seeked, |_| true, Duration::from_millis(50))
+        .await;
+    assert!(matches!(waited, Err(pris::Error::Timeout { .. })));
+}
@@ -0,0 +1,81 @@
+#![cfg(feature = "blocking")]
+
+mod common;
+
+use dbus::arg::{RefArg, Variant};
+use pris::blocking::{self, Player};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_blocking_player() {
+    let bus = common::TestBus::new();
+    let vlc = bus.connect_as("vlc").await;
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let recorded = calls.clone();
+    let served = common::serve_player(
+        &vlc,
+        common::props(vec![
+            ("PlaybackStatus", common::var("Playing".to_string())),
+            ("CanPause", common::var(true)),
+            ("Volume", common::var(0.5f64)),
+        ]),
+        move |msg, properties| {
+            let member = msg.member()?.to_string();
+            recorded.lock().unwrap().push(member.clone());
+            match member.as_str() {
+                "Pause" => Some(msg.method_return()),
+                "Set" => {
+                    let (_, name, value): (String, String, Variant<Box<dyn RefArg>>) =
+                        msg.read3().unwrap();
+                    properties.insert(name, value);
+                    Some(msg.method_return())
+                }
+                _ => None,
+            }
+        },
+    );
+    let mpv = bus.connect_as("mpv").await;
+    common::serve_error(&mpv, "org.freedesktop.DBus.Error.NoReply");
+
+    let conn = bus.connect_blocking();
+    tokio::task::spawn_blocking(move || {
+        let mut names: Vec<String> = blocking::get_all_players(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|player| player.name)
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["mpv", "vlc"]);
+        assert!(matches!(
+            Player::try_new("nope", &conn),
+            Err(pris::Error::UnknownPlayer { requested, .. }) if requested == "nope"
+        ));
+
+        // PlayPause falls back to Pause like the async API does
+        let player = Player::try_new("vlc", &conn).unwrap();
+        player.play_pause().unwrap();
+        assert_eq!(*calls.lock().unwrap(), vec!["PlayPause", "Pause"]);
+
+        // Properties are read and written, clamped the same way
+        assert_eq!(player.get_volume().unwrap(), 0.5);
+        assert_eq!(player.set_volume(1.5).unwrap(), 1.0);
+        assert_eq!(served.lock().unwrap()["Volume"].0.as_f64(), Some(1.0));
+        assert!(matches!(
+            player.get_property::<String>("Volume"),
+            Err(pris::Error::TypeMismatch { .. })
+        ));
+
+        // And errors are classified with the timeout that was set
+        let mut player = Player::try_new("mpv", &conn).unwrap();
+        player.set_timeout(Duration::from_secs(2));
+        assert!(matches!(
+            player.next(),
+            Err(pris::Error::Timeout { limit, .. }) if limit == Duration::from_secs(2)
+        ));
+    })
+    .await
+    .unwrap();
+}
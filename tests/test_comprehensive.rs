@@ -3,8 +3,8 @@ use pris::{self, EventManager, EventType, Player};
 #[tokio::test]
 async fn test_comprehensive() -> Result<(), Box<dyn std::error::Error>> {
     let conn = pris::get_connection();
-    let mut player = Player::try_new("cmus", &conn).await?;
-    let mut manager = EventManager::new(&conn);
+    let player = Player::try_new("cmus", &conn).await?;
+    let manager = EventManager::new(&conn);
 
     let _incoming = manager
         .add_callback(EventType::PropertiesChanged, |msg| {
@@ -13,7 +13,9 @@ async fn test_comprehensive() -> Result<(), Box<dyn std::error::Error>> {
         })
         .await?;
 
-    player.set_position(456).await?;
+    player
+        .set_position(std::time::Duration::from_micros(456))
+        .await?;
     let metadata = player.get_metadata().await?;
 
     println!(
@@ -0,0 +1,78 @@
+mod common;
+
+use pris::{testing::MockPlayer, Bus, Event, EventManager, EventType, Player, Value};
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_connect() {
+    let bus = common::TestBus::new();
+    std::env::set_var("DBUS_SESSION_BUS_ADDRESS", &bus.address);
+
+    let mock_conn = bus.connect();
+    let mock = MockPlayer::register("vlc", &mock_conn).await.unwrap();
+
+    let mut conn = pris::connect().unwrap();
+    let player = Player::try_new("vlc", &conn).await.unwrap();
+    player.pause().await.unwrap();
+    assert_eq!(mock.calls_to("Pause"), 1);
+
+    // Losing the bus is reported rather than panicking
+    drop(bus);
+    let lost = tokio::time::timeout(Duration::from_secs(5), conn.closed())
+        .await
+        .unwrap();
+    assert!(matches!(lost, pris::Error::DBus(_)));
+    assert!(matches!(conn.closed().await, pris::Error::Disconnected));
+}
+
+#[tokio::test]
+async fn test_connect_to_address() {
+    let bus = common::TestBus::new();
+    let mock_conn = bus.connect();
+    let mock = MockPlayer::register("vlc", &mock_conn).await.unwrap();
+
+    let conn = pris::connect_to(Bus::Address(bus.address.clone())).unwrap();
+    let players = pris::get_all_players(&conn).await.unwrap();
+    assert_eq!(players.len(), 1);
+    players[0].play().await.unwrap();
+    assert_eq!(mock.calls_to("Play"), 1);
+
+    let unreachable = Bus::Address("unix:path=/nonexistent/pris-test".to_string());
+    let error = pris::connect_to(unreachable.clone()).err().unwrap();
+    assert!(matches!(&error, pris::Error::Connection { bus, .. } if *bus == unreachable));
+    assert!(error
+        .to_string()
+        .starts_with("Couldn't connect to the bus at unix:path=/nonexistent/pris-test"));
+}
+
+// The whole way from connecting to reading a changed value, without
+// naming a type from the dbus crate
+#[tokio::test]
+async fn test_event_callback() {
+    let bus = common::TestBus::new();
+    let conn = pris::connect_to(Bus::Address(bus.address.clone())).unwrap();
+    let manager = EventManager::new(&conn);
+    let (sender, mut received) = tokio::sync::mpsc::unbounded_channel();
+    let _guard = manager
+        .add_event_callback(EventType::PropertiesChanged, move |event| {
+            sender.send(event).is_ok()
+        })
+        .await
+        .unwrap();
+
+    let mock_conn = pris::connect_to(Bus::Address(bus.address.clone())).unwrap();
+    let mock = MockPlayer::register("vlc", &mock_conn).await.unwrap();
+    mock.set_property("Volume", 0.25);
+    mock.emit_properties_changed(&["Volume"]).unwrap();
+
+    let event = tokio::time::timeout(Duration::from_secs(5), received.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(event.player(), "vlc");
+    let volume = match event {
+        Event::PropertiesChanged(changed) => changed.properties.get("Volume"),
+        other => panic!("expected a PropertiesChanged event, got {:?}", other),
+    };
+    assert_eq!(volume.and_then(|v| v.value()), Some(Value::Double(0.25)));
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use pris::{testing::MockPlayer, DebugSink, EventManager, EventType, Player};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+fn collect() -> (DebugSink, Arc<Mutex<Vec<String>>>) {
+    let lines = Arc::new(Mutex::new(Vec::new()));
+    let sink_lines = lines.clone();
+    let sink = DebugSink::new(move |line| sink_lines.lock().unwrap().push(line.to_string()));
+    (sink, lines)
+}
+
+#[tokio::test]
+async fn test_debug_sink() {
+    let bus = common::TestBus::new();
+    let mock_conn = bus.connect();
+    let mock = MockPlayer::register("vlc", &mock_conn).await.unwrap();
+    let conn = bus.connect();
+    let mut player = Player::try_new("vlc", &conn).await.unwrap();
+
+    // Nothing is written until a sink is set
+    let (sink, lines) = collect();
+    player.pause().await.unwrap();
+    player.set_debug_sink(Some(sink));
+
+    player.set_property("Rate", 1.5).await.unwrap();
+    let status: String = player.get_property("PlaybackStatus").await.unwrap();
+    mock.set_error("Next", Some("org.freedesktop.DBus.Error.NotSupported"))
+        .unwrap();
+    player.next().await.unwrap_err();
+    let lines = lines.lock().unwrap().clone();
+    assert_eq!(
+        lines[..5],
+        [
+            "-> call dest=org.mpris.MediaPlayer2.vlc path=/org/mpris/MediaPlayer2 \
+             iface=org.freedesktop.DBus.Properties member=Set sig=ssv \
+             args=[\"org.mpris.MediaPlayer2.Player\", \"Rate\", 1.5]"
+                .to_string(),
+            "<- reply member=Set sig= args=[]".to_string(),
+            "-> call dest=org.mpris.MediaPlayer2.vlc path=/org/mpris/MediaPlayer2 \
+             iface=org.freedesktop.DBus.Properties member=Get sig=ss \
+             args=[\"org.mpris.MediaPlayer2.Player\", \"PlaybackStatus\"]"
+                .to_string(),
+            format!("<- reply member=Get sig=v args=[{:?}]", status),
+            "-> call dest=org.mpris.MediaPlayer2.vlc path=/org/mpris/MediaPlayer2 \
+             iface=org.mpris.MediaPlayer2.Player member=Next sig= args=[]"
+                .to_string(),
+        ]
+    );
+    assert!(
+        lines[5].starts_with("<- error member=Next name=org.freedesktop.DBus.Error.NotSupported")
+    );
+
+    let manager = EventManager::new(&conn);
+    let (sink, lines) = collect();
+    manager.set_debug_sink(Some(sink));
+    let (sender, mut received) = tokio::sync::mpsc::unbounded_channel();
+    let guard = manager
+        .add_callback(EventType::Seeked, move |_| sender.send(()).is_ok())
+        .await
+        .unwrap();
+    mock.emit_seeked(Duration::from_secs(1)).unwrap();
+    tokio::time::timeout(Duration::from_secs(5), received.recv())
+        .await
+        .unwrap()
+        .unwrap();
+
+    let line = lines.lock().unwrap()[0].clone();
+    assert!(line.starts_with(&format!("<- signal token={} sender=:", guard.token().0)));
+    assert!(line.ends_with(
+        "path=/org/mpris/MediaPlayer2 iface=org.mpris.MediaPlayer2.Player \
+         member=Seeked sig=x args=[1000000]"
+    ));
+}
@@ -0,0 +1,756 @@
+mod common;
+
+use futures::{stream, StreamExt};
+use pris::{
+    ChangedProperties, Event, EventType, LifecycleEvent, LoopStatus, MilestonePolicy,
+    PlaybackClock, PlaybackStatus, PlayerState, PositionChange, PositionDedupOptions,
+    PositionSource, PropertyValue, SeekedEvent, Value,
+};
+use std::time::Duration;
+use tokio::{sync::mpsc, time::Instant};
+
+#[test]
+fn test_seeked_parse() {
+    let msg = common::seeked(":1.42", 83_000_000i64);
+    let event = SeekedEvent::parse(&msg, "vlc").unwrap();
+
+    assert_eq!(event.player, "vlc");
+    assert_eq!(event.position, Duration::from_secs(83));
+}
+
+#[test]
+fn test_seeked_negative_position() {
+    // Captured from mpv during a track change
+    let msg = common::seeked(":1.42", -1_250i64);
+    let event = SeekedEvent::parse(&msg, "mpv").unwrap();
+
+    assert_eq!(event.position, Duration::ZERO);
+}
+
+#[test]
+fn test_seeked_boundaries() {
+    let parse = |msg| SeekedEvent::parse(&msg, "mpv").unwrap().position;
+
+    for (micros, expected) in [
+        (i64::MIN, Duration::ZERO),
+        (-1, Duration::ZERO),
+        (0, Duration::ZERO),
+        (1, Duration::from_micros(1)),
+        (i64::MAX, Duration::from_micros(i64::MAX as u64)),
+    ] {
+        assert_eq!(parse(common::seeked(":1.42", micros)), expected);
+    }
+    for micros in [0, i64::MAX as u64 + 1, u64::MAX] {
+        assert_eq!(
+            parse(common::seeked(":1.42", micros)),
+            Duration::from_micros(micros)
+        );
+    }
+}
+
+#[test]
+fn test_seeked_malformed() {
+    let missing = common::signal(":1.42", common::PLAYER_INTERFACE, "Seeked");
+    assert!(SeekedEvent::parse(&missing, "vlc").is_err());
+
+    let mistyped = common::seeked(":1.42", "83000000");
+    assert!(SeekedEvent::parse(&mistyped, "vlc").is_err());
+
+    let wrong_member = common::signal(":1.42", common::PLAYER_INTERFACE, "TrackChanged");
+    assert!(SeekedEvent::parse(&wrong_member, "vlc").is_err());
+}
+
+#[test]
+fn test_parse_error() {
+    fn assert_thread_safe<E: std::error::Error + Send + Sync + 'static>(_: &E) {}
+
+    let mistyped = common::seeked(":1.42", "83000000");
+    let error = SeekedEvent::parse(&mistyped, "vlc").unwrap_err();
+    assert_thread_safe(&error);
+
+    assert!(matches!(error, pris::Error::Parse(_)));
+}
+
+#[test]
+fn test_changed_properties_parse() {
+    let metadata = common::props(vec![
+        ("mpris:trackid", common::var(dbus::Path::from("/track/1"))),
+        ("xesam:title", common::var("Stay".to_string())),
+    ]);
+    let msg = common::properties_changed(
+        ":1.42",
+        common::PLAYER_INTERFACE,
+        common::props(vec![
+            ("PlaybackStatus", common::var("Playing".to_string())),
+            ("LoopStatus", common::var("Playlist".to_string())),
+            ("Metadata", common::var(metadata)),
+            ("CanGoNext", common::var(true)),
+            ("Shuffle", common::var(false)),
+            ("X-Custom", common::var(7i32)),
+        ]),
+        vec![],
+    );
+    let changed = ChangedProperties::parse(&msg).unwrap();
+
+    assert!(changed.is_player_interface());
+    assert_eq!(changed.playback_status, Some(PlaybackStatus::Playing));
+    assert_eq!(changed.loop_status, Some(LoopStatus::Playlist));
+    assert_eq!(changed.can_go_next, Some(true));
+    assert_eq!(changed.shuffle, Some(false));
+    assert_eq!(changed.volume, None);
+    assert!(changed.other.contains_key("X-Custom"));
+
+    let metadata = changed.metadata.unwrap();
+    assert_eq!(
+        pris::prop_cast::<String>(&metadata, "xesam:title").unwrap(),
+        "Stay"
+    );
+}
+
+#[test]
+fn test_changed_properties_messy_text() {
+    // A title from a broken tag, sent as bytes, and an escape sequence
+    let title = b"Caf\xe9 \x1b[31mNoir\x1b[0m".to_vec();
+    let metadata = common::props(vec![
+        ("xesam:title", common::var(title.clone())),
+        ("xesam:album", common::var("Live\n\tat Home".to_string())),
+    ]);
+    let msg = common::properties_changed(
+        ":1.42",
+        common::PLAYER_INTERFACE,
+        common::props(vec![("Metadata", common::var(metadata))]),
+        vec![],
+    );
+    let metadata = ChangedProperties::parse(&msg).unwrap().metadata.unwrap();
+
+    assert_eq!(
+        pris::prop_str(&metadata, "xesam:title").unwrap(),
+        "Caf\u{fffd} \u{1b}[31mNoir\u{1b}[0m"
+    );
+    assert_eq!(
+        pris::prop_display(&metadata, "xesam:title").unwrap(),
+        "Caf\u{fffd} [31mNoir[0m"
+    );
+    assert_eq!(pris::prop_bytes(&metadata, "xesam:title").unwrap(), title);
+
+    assert_eq!(
+        pris::prop_str(&metadata, "xesam:album").unwrap(),
+        "Live\n\tat Home"
+    );
+    assert_eq!(
+        pris::prop_display(&metadata, "xesam:album").unwrap(),
+        "Liveat Home"
+    );
+    assert_eq!(
+        pris::prop_bytes(&metadata, "xesam:album").unwrap(),
+        b"Live\n\tat Home"
+    );
+    assert!(pris::prop_str(&metadata, "xesam:artist").is_none());
+    assert!(matches!(
+        pris::sanitize("Already clean"),
+        std::borrow::Cow::Borrowed(_)
+    ));
+}
+
+#[test]
+fn test_changed_properties_no_track() {
+    let parse = |metadata| {
+        let msg = common::properties_changed(
+            ":1.42",
+            common::PLAYER_INTERFACE,
+            common::props(vec![("Metadata", common::var(metadata))]),
+            vec![],
+        );
+        ChangedProperties::parse(&msg).unwrap()
+    };
+
+    // Sent by stopped players
+    let empty = parse(common::props(vec![]));
+    assert!(empty.metadata.as_ref().unwrap().is_empty());
+    assert_eq!(empty.has_track(), Some(false));
+
+    let no_track = parse(common::props(vec![(
+        "mpris:trackid",
+        common::var(dbus::Path::from(
+            "/org/mpris/MediaPlayer2/TrackList/NoTrack",
+        )),
+    )]));
+    assert_eq!(no_track.has_track(), Some(false));
+
+    let track = parse(common::props(vec![(
+        "mpris:trackid",
+        common::var(dbus::Path::from("/track/1")),
+    )]));
+    assert_eq!(track.has_track(), Some(true));
+
+    let unchanged = ChangedProperties::default();
+    assert_eq!(unchanged.has_track(), None);
+}
+
+#[test]
+fn test_changed_properties_nested_variants() {
+    let msg = common::properties_changed(
+        ":1.42",
+        common::PLAYER_INTERFACE,
+        common::props(vec![("Volume", common::var(common::var(0.5f64)))]),
+        vec![],
+    );
+    let changed = ChangedProperties::parse(&msg).unwrap();
+
+    assert_eq!(changed.volume, Some(0.5));
+}
+
+#[test]
+fn test_values() {
+    let metadata = common::captured_metadata();
+    assert_eq!(
+        pris::prop_value(&metadata, "mpris:trackid"),
+        Some(Value::Str("/org/mpris/MediaPlayer2/Track/42".into()))
+    );
+    assert_eq!(
+        pris::prop_value(&metadata, "xesam:genre"),
+        Some(Value::Array(vec![
+            Value::Str("Electronic".into()),
+            Value::Str("IDM".into())
+        ]))
+    );
+    assert_eq!(
+        pris::prop_value(&metadata, "mpris:length").and_then(|v| v.as_i64()),
+        Some(254_000_000)
+    );
+    assert_eq!(
+        pris::prop_value(&metadata, "xesam:trackNumber"),
+        Some(Value::Int(1))
+    );
+    assert_eq!(pris::prop_value(&metadata, "nope"), None);
+
+    // Nested maps and variants are copied out too
+    let nested = common::props(vec![("Metadata", common::var(common::var(metadata)))]);
+    let copied = match pris::prop_value(&nested, "Metadata") {
+        Some(Value::Dict(copied)) => copied,
+        other => panic!("expected a dictionary, got {:?}", other),
+    };
+    assert_eq!(copied.len(), 30);
+    assert_eq!(copied["xesam:userRating"], Value::Double(0.8));
+
+    let msg = common::properties_changed(
+        ":1.42",
+        common::PLAYER_INTERFACE,
+        common::props(vec![
+            ("PlaybackStatus", common::var("Paused".to_string())),
+            ("Fullscreen", common::var(true)),
+        ]),
+        vec!["Volume"],
+    );
+    let changed = ChangedProperties::parse(&msg).unwrap();
+    let value = |name: &str| changed.get(name).map(|value| value.value());
+    assert_eq!(
+        value("PlaybackStatus"),
+        Some(Some(Value::Str("Paused".into())))
+    );
+    assert_eq!(value("Fullscreen"), Some(Some(Value::Bool(true))));
+    assert_eq!(value("Volume"), Some(None));
+}
+
+#[test]
+fn test_changed_properties_other_interface() {
+    let msg = common::properties_changed(
+        ":1.42",
+        "org.mpris.MediaPlayer2.TrackList",
+        common::props(vec![("CanEditTracks", common::var(true))]),
+        vec![],
+    );
+    let changed = ChangedProperties::parse(&msg).unwrap();
+
+    assert!(!changed.is_player_interface());
+    assert!(changed.other.contains_key("CanEditTracks"));
+    assert!(ChangedProperties::parse(&common::seeked(":1.42", 0i64)).is_err());
+}
+
+#[test]
+fn test_changed_properties_invalidated() {
+    let msg = common::properties_changed(
+        ":1.42",
+        common::PLAYER_INTERFACE,
+        common::props(vec![]),
+        vec!["Metadata", "Volume"],
+    );
+    let changed = ChangedProperties::parse(&msg).unwrap();
+
+    assert!(changed.metadata.is_none());
+    assert!(changed.other.is_empty());
+    assert_eq!(changed.invalidated, vec!["Metadata", "Volume"]);
+}
+
+#[test]
+fn test_lifecycle_parse() {
+    let name = "org.mpris.MediaPlayer2.spotify";
+    let appeared = common::name_owner_changed(name, "", ":1.7");
+    let vanished = common::name_owner_changed(name, ":1.7", "");
+    let replaced = common::name_owner_changed(name, ":1.7", ":1.9");
+
+    assert_eq!(
+        LifecycleEvent::parse(&appeared).unwrap(),
+        LifecycleEvent::Appeared {
+            name: "spotify".to_string()
+        }
+    );
+    assert_eq!(
+        LifecycleEvent::parse(&vanished).unwrap(),
+        LifecycleEvent::Vanished {
+            name: "spotify".to_string()
+        }
+    );
+    assert_eq!(
+        LifecycleEvent::parse(&replaced).unwrap(),
+        LifecycleEvent::Replaced {
+            name: "spotify".to_string()
+        }
+    );
+    assert!(EventType::PlayerLifecycle.matches(&appeared));
+}
+
+#[test]
+fn test_lifecycle_non_mpris() {
+    let msg = common::name_owner_changed("org.gnome.Shell", "", ":1.3");
+
+    assert!(!EventType::PlayerLifecycle.matches(&msg));
+    assert!(LifecycleEvent::parse(&msg).is_err());
+}
+
+#[test]
+fn test_changed_properties_get() {
+    let msg = common::properties_changed(
+        ":1.42",
+        common::PLAYER_INTERFACE,
+        common::props(vec![
+            ("PlaybackStatus", common::var("Paused".to_string())),
+            ("Volume", common::var(0.25f64)),
+            ("X-Custom", common::var(7i32)),
+        ]),
+        vec!["Metadata"],
+    );
+    let changed = ChangedProperties::parse(&msg).unwrap();
+
+    assert!(matches!(
+        changed.get("PlaybackStatus"),
+        Some(PropertyValue::PlaybackStatus(PlaybackStatus::Paused))
+    ));
+    assert!(matches!(changed.get("Volume"), Some(PropertyValue::Double(v)) if v == 0.25));
+    assert!(
+        matches!(changed.get("X-Custom"), Some(PropertyValue::Other(v)) if v.0.as_i64() == Some(7))
+    );
+    assert!(matches!(
+        changed.get("Metadata"),
+        Some(PropertyValue::Invalidated)
+    ));
+    assert!(changed.get("Shuffle").is_none());
+}
+
+#[test]
+fn test_player_state_from_properties() {
+    let state = PlayerState::from_properties(
+        "vlc",
+        common::props(vec![
+            ("PlaybackStatus", common::var("Playing".to_string())),
+            ("Position", common::var(common::var(90_500_000i64))),
+            ("Volume", common::var(0.75f64)),
+            ("CanSeek", common::var(true)),
+            ("MinimumRate", common::var(0.5f64)),
+        ]),
+    );
+
+    assert_eq!(state.player, "vlc");
+    assert_eq!(state.playback_status, Some(PlaybackStatus::Playing));
+    assert_eq!(state.position, Some(Duration::from_micros(90_500_000)));
+    assert_eq!(state.volume, Some(0.75));
+    assert_eq!(state.can_seek, Some(true));
+    assert_eq!(state.shuffle, None);
+    assert!(!state.other.contains_key("Position"));
+    assert!(state.other.contains_key("MinimumRate"));
+}
+
+#[test]
+fn test_player_state_position_boundaries() {
+    let position = |value| {
+        let properties = common::props(vec![("Position", value)]);
+        PlayerState::from_properties("vlc", properties).position
+    };
+
+    assert_eq!(position(common::var(-1i64)), Some(Duration::ZERO));
+    assert_eq!(position(common::var(i64::MIN)), Some(Duration::ZERO));
+    assert_eq!(
+        position(common::var(u64::MAX)),
+        Some(Duration::from_micros(u64::MAX))
+    );
+    assert_eq!(position(common::var(7u32)), Some(Duration::from_micros(7)));
+    assert_eq!(position(common::var("7".to_string())), None);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_coalesce() {
+    let changed = |props, invalidated| {
+        let msg = common::properties_changed(":1.42", common::PLAYER_INTERFACE, props, invalidated);
+        Event::parse(&msg, "spotify").unwrap()
+    };
+    let burst = vec![
+        (
+            0,
+            changed(
+                common::props(vec![("PlaybackStatus", common::var("Playing".to_string()))]),
+                vec![],
+            ),
+        ),
+        (
+            10,
+            changed(
+                common::props(vec![("Volume", common::var(0.5f64))]),
+                vec!["Metadata"],
+            ),
+        ),
+        (
+            20,
+            Event::parse(&common::seeked(":1.42", 0i64), "spotify").unwrap(),
+        ),
+        (
+            30,
+            changed(
+                common::props(vec![("Volume", common::var(0.7f64))]),
+                vec!["Shuffle"],
+            ),
+        ),
+        (
+            200,
+            changed(common::props(vec![("CanPlay", common::var(true))]), vec![]),
+        ),
+    ];
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let start = Instant::now();
+    tokio::spawn(async move {
+        for (at, event) in burst {
+            tokio::time::sleep_until(start + Duration::from_millis(at)).await;
+            sender.send(event).unwrap();
+        }
+    });
+    let events = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|event| (event, receiver))
+    });
+    let mut coalesced = pris::coalesce(events, Duration::from_millis(50));
+
+    let mut received = Vec::new();
+    while let Some(event) = coalesced.next().await {
+        received.push((start.elapsed().as_millis(), event));
+    }
+
+    assert_eq!(received.len(), 3);
+    assert!(matches!(received[0], (20, Event::Seeked(_))));
+    match &received[1] {
+        (80, Event::PropertiesChanged(event)) => {
+            let properties = &event.properties;
+            assert_eq!(properties.playback_status, Some(PlaybackStatus::Playing));
+            assert_eq!(properties.volume, Some(0.7));
+            assert_eq!(properties.invalidated, vec!["Metadata", "Shuffle"]);
+        }
+        other => panic!("Unexpected event: {:?}", other),
+    }
+    // The source ends right after, which flushes the last burst
+    match &received[2] {
+        (200, Event::PropertiesChanged(event)) => {
+            assert_eq!(event.properties.can_play, Some(true));
+            assert_eq!(event.properties.volume, None);
+        }
+        other => panic!("Unexpected event: {:?}", other),
+    }
+}
+
+#[test]
+fn test_changed_properties_merge() {
+    let parse = |props, invalidated| {
+        let msg = common::properties_changed(":1.42", common::PLAYER_INTERFACE, props, invalidated);
+        ChangedProperties::parse(&msg).unwrap()
+    };
+    let mut merged = parse(
+        common::props(vec![
+            ("Volume", common::var(0.5f64)),
+            ("X-Custom", common::var(1i32)),
+        ]),
+        vec!["Shuffle"],
+    );
+    merged.merge(parse(
+        common::props(vec![("Shuffle", common::var(true))]),
+        vec!["Volume"],
+    ));
+
+    assert_eq!(merged.shuffle, Some(true));
+    assert_eq!(merged.volume, None);
+    assert_eq!(merged.invalidated, vec!["Volume"]);
+    assert!(merged.other.contains_key("X-Custom"));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_dedup_positions() {
+    let seeked = |player, secs: i64| {
+        Event::parse(&common::seeked(":1.42", secs * 1_000_000), player).unwrap()
+    };
+    let position_changed = |micros: i64| {
+        let props = common::props(vec![("Position", common::var(micros))]);
+        let msg = common::properties_changed(":1.42", common::PLAYER_INTERFACE, props, vec![]);
+        Event::parse(&msg, "vlc").unwrap()
+    };
+    let volume_changed = {
+        let props = common::props(vec![("Volume", common::var(0.5f64))]);
+        let msg = common::properties_changed(":1.42", common::PLAYER_INTERFACE, props, vec![]);
+        Event::parse(&msg, "vlc").unwrap()
+    };
+    let reports = vec![
+        (0, seeked("vlc", 10)),
+        // The same seek, reported again as a property change
+        (50, position_changed(10_100_000)),
+        (60, volume_changed),
+        (1000, seeked("vlc", 10)),
+        (1010, position_changed(30_000_000)),
+        (1020, seeked("mpv", 30)),
+    ];
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let start = Instant::now();
+    tokio::spawn(async move {
+        for (at, event) in reports {
+            tokio::time::sleep_until(start + Duration::from_millis(at)).await;
+            sender.send(event).unwrap();
+        }
+    });
+    let events = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|event| (event, receiver))
+    });
+    let changes: Vec<PositionChange> =
+        pris::dedup_positions(events, PositionDedupOptions::default())
+            .collect()
+            .await;
+
+    let change = |player: &str, secs, source| PositionChange {
+        player: player.to_string(),
+        position: Duration::from_secs(secs),
+        source,
+    };
+    assert_eq!(
+        changes,
+        vec![
+            change("vlc", 10, PositionSource::Seeked),
+            change("vlc", 10, PositionSource::Seeked),
+            change("vlc", 30, PositionSource::PropertiesChanged),
+            change("mpv", 30, PositionSource::Seeked),
+        ]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_position_ticks() {
+    let changed = |name, value| {
+        let msg = common::properties_changed(
+            ":1.42",
+            common::PLAYER_INTERFACE,
+            common::props(vec![(name, value)]),
+            vec![],
+        );
+        Event::parse(&msg, "vlc").unwrap()
+    };
+    let status = |status: &str| changed("PlaybackStatus", common::var(status.to_string()));
+    let script = vec![
+        (2500, status("Paused")),
+        // Seeking while paused moves the position without ticking
+        (
+            5000,
+            Event::parse(&common::seeked(":1.42", 60_000_000i64), "vlc").unwrap(),
+        ),
+        (5500, changed("Volume", common::var(0.5f64))),
+        (6000, status("Playing")),
+        (7500, changed("Rate", common::var(2.0f64))),
+        (9000, status("Stopped")),
+    ];
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let start = Instant::now();
+    tokio::spawn(async move {
+        for (at, event) in script {
+            tokio::time::sleep_until(start + Duration::from_millis(at)).await;
+            sender.send(event).unwrap();
+        }
+    });
+    let events = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|event| (event, receiver))
+    });
+    let clock = PlaybackClock::new(
+        Duration::from_secs(10),
+        PlaybackStatus::Playing,
+        1.0,
+        start.into_std(),
+    );
+    let mut ticks = pris::position_ticks(events, clock, Duration::from_secs(1));
+
+    let mut received = Vec::new();
+    while let Some(position) = ticks.next().await {
+        let millis = (position.as_secs_f64() * 1000.0).round() as u64;
+        received.push((start.elapsed().as_millis() as u64, millis));
+    }
+
+    assert_eq!(
+        received,
+        vec![
+            (0, 10_000),
+            (1000, 11_000),
+            (2000, 12_000),
+            (2500, 12_500),
+            (5000, 60_000),
+            (6000, 60_000),
+            (7000, 61_000),
+            (7500, 61_500),
+            (8500, 63_500),
+            (9000, 0),
+        ]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_progress_milestones() {
+    let track = |id: &str, secs: i64| {
+        common::props(vec![
+            (
+                "mpris:trackid",
+                common::var(dbus::Path::from(id.to_string())),
+            ),
+            ("mpris:length", common::var(secs * 1_000_000)),
+        ])
+    };
+    let changed = |name, value| {
+        let msg = common::properties_changed(
+            ":1.42",
+            common::PLAYER_INTERFACE,
+            common::props(vec![(name, value)]),
+            vec![],
+        );
+        Event::parse(&msg, "vlc").unwrap()
+    };
+    let seek = |secs: i64| Event::parse(&common::seeked(":1.42", secs * 1_000_000), "vlc").unwrap();
+    let status = |status: &str| changed("PlaybackStatus", common::var(status.to_string()));
+    let script = vec![
+        // Parts played twice after seeking back only count once
+        (10, seek(5)),
+        (20, seek(60)),
+        (30, status("Paused")),
+        (40, status("Playing")),
+        (70, changed("Metadata", common::var(track("/track/2", 600)))),
+        (80, seek(0)),
+        (100, changed("Rate", common::var(2.0f64))),
+        (400, changed("Volume", common::var(0.5f64))),
+    ];
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let start = Instant::now();
+    tokio::spawn(async move {
+        for (at, event) in script {
+            tokio::time::sleep_until(start + Duration::from_secs(at)).await;
+            sender.send(event).unwrap();
+        }
+    });
+    let events = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|event| (event, receiver))
+    });
+    let state = PlayerState {
+        player: "vlc".to_string(),
+        playback_status: Some(PlaybackStatus::Playing),
+        metadata: Some(track("/track/1", 100)),
+        position: Some(Duration::ZERO),
+        rate: Some(1.0),
+        ..PlayerState::default()
+    };
+    let mut milestones = pris::progress_milestones(events, state, MilestonePolicy::default());
+
+    let mut received = Vec::new();
+    while let Some(milestone) = milestones.next().await {
+        let secs = |d: Duration| d.as_secs_f64().round() as u64;
+        let trackid = milestone.metadata.as_ref().unwrap()["mpris:trackid"]
+            .0
+            .as_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(milestone.player, "vlc");
+        received.push((
+            start.elapsed().as_secs(),
+            trackid,
+            secs(milestone.listened),
+            secs(milestone.position),
+        ));
+    }
+
+    assert_eq!(
+        received,
+        vec![
+            // Half of the first track
+            (65, "/track/1".to_string(), 50, 95),
+            // Four minutes of the second, played at double speed
+            (210, "/track/2".to_string(), 240, 240),
+        ]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_progress_milestones_replay() {
+    let metadata = |length: Option<i64>| {
+        let mut metadata = vec![("mpris:trackid", common::var(dbus::Path::from("/track/1")))];
+        if let Some(secs) = length {
+            metadata.push(("mpris:length", common::var(secs * 1_000_000)));
+        }
+        common::props(metadata)
+    };
+    let changed = |name, value| {
+        let msg = common::properties_changed(
+            ":1.42",
+            common::PLAYER_INTERFACE,
+            common::props(vec![(name, value)]),
+            vec![],
+        );
+        Event::parse(&msg, "vlc").unwrap()
+    };
+    let status = |status: &str| changed("PlaybackStatus", common::var(status.to_string()));
+    let script = vec![
+        // A fraction can only be reached once the length is known
+        (30, changed("Metadata", common::var(metadata(Some(40))))),
+        // Stopping starts a new playback of the same track
+        (35, status("Stopped")),
+        (40, status("Playing")),
+        (100, status("Paused")),
+    ];
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let start = Instant::now();
+    tokio::spawn(async move {
+        for (at, event) in script {
+            tokio::time::sleep_until(start + Duration::from_secs(at)).await;
+            sender.send(event).unwrap();
+        }
+    });
+    let events = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|event| (event, receiver))
+    });
+    let state = PlayerState {
+        player: "vlc".to_string(),
+        playback_status: Some(PlaybackStatus::Playing),
+        metadata: Some(metadata(None)),
+        ..PlayerState::default()
+    };
+    let policy = MilestonePolicy {
+        fraction: Some(0.5),
+        duration: None,
+    };
+    let mut milestones = pris::progress_milestones(events, state, policy);
+
+    let mut received = Vec::new();
+    while let Some(milestone) = milestones.next().await {
+        let secs = milestone.listened.as_secs_f64().round() as u64;
+        received.push((start.elapsed().as_secs(), secs));
+    }
+
+    assert_eq!(received, vec![(30, 30), (60, 20)]);
+}
@@ -1,4 +1,25 @@
-use pris::{self, EventManager, EventType, Message};
+mod common;
+
+use dbus::{
+    channel::{MatchingReceiver, Sender},
+    message::MatchRule,
+    nonblock::SyncConnection,
+};
+use futures::StreamExt;
+use pris::{
+    self, testing::MockPlayer, CallbackGuard, CallbackOrdering, Controller, ControllerEvent,
+    DeliveryPolicy, Event, EventManager, EventType, ExclusiveOptions, ExclusivePlayback,
+    LifecycleEvent, Message, MultiBusManager, PausePolicy, PlaybackStatus, PlayerPool, PoolChange,
+    PropertyValue, SubscriptionOptions, TrackChange,
+};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::mpsc;
 
 // A callback can be a detached function...
 fn callback(msg: Message) -> bool {
@@ -9,7 +30,7 @@ fn callback(msg: Message) -> bool {
 #[tokio::test]
 async fn test_evt_mgr() -> Result<(), Box<dyn std::error::Error>> {
     let conn = pris::get_connection();
-    let mut manager = EventManager::new(&conn);
+    let manager = EventManager::new(&conn);
 
     let _incoming_props = manager
         .add_callback(EventType::PropertiesChanged, |msg| {
@@ -25,3 +46,2170 @@ async fn test_evt_mgr() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn test_match_filtering() {
+    let player_changed = common::properties_changed(
+        ":1.42",
+        common::PLAYER_INTERFACE,
+        common::props(vec![("Volume", common::var(0.5f64))]),
+        vec![],
+    );
+    let tracklist_changed = common::properties_changed(
+        ":1.42",
+        "org.mpris.MediaPlayer2.TrackList",
+        common::props(vec![("CanEditTracks", common::var(false))]),
+        vec![],
+    );
+    let root_changed = common::properties_changed(
+        ":1.42",
+        "org.mpris.MediaPlayer2",
+        common::props(vec![("Fullscreen", common::var(true))]),
+        vec![],
+    );
+    let foreign_seeked = common::signal(":1.42", "com.example.Player", "Seeked").append1(0i64);
+
+    assert!(EventType::PropertiesChanged.matches(&player_changed));
+    assert!(!EventType::PropertiesChanged.matches(&tracklist_changed));
+    assert!(!EventType::PropertiesChanged.matches(&root_changed));
+    assert!(!EventType::Seeked.matches(&player_changed));
+
+    assert!(EventType::Seeked.matches(&common::seeked(":1.42", 0i64)));
+    assert!(!EventType::Seeked.matches(&foreign_seeked));
+}
+
+#[tokio::test]
+async fn test_wait_for_event() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new(&conn);
+
+    let wait = manager.wait_for_event(
+        EventType::Seeked,
+        |event| matches!(event, Event::Seeked(s) if s.position >= Duration::from_secs(10)),
+        Duration::from_secs(5),
+    );
+    let emit = async {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        common::emit(&emitter, common::seeked(":1.1", 5_000_000i64));
+        common::emit(&emitter, common::seeked(":1.1", 12_000_000i64));
+    };
+    let (event, _) = tokio::join!(wait, emit);
+
+    match event.unwrap() {
+        Event::Seeked(seeked) => {
+            assert_eq!(seeked.player, "test");
+            assert_eq!(seeked.position, Duration::from_secs(12));
+        }
+        other => panic!("Unexpected event: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_wait_for_event_timeout() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let manager = EventManager::new(&conn);
+
+    let result = manager
+        .wait_for_event(EventType::Seeked, |_| true, Duration::from_millis(100))
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_wait_for_lifecycle() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let manager = EventManager::new(&conn);
+
+    let wait = manager.wait_for_event(EventType::PlayerLifecycle, |_| true, Duration::from_secs(5));
+    let appear = async {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        bus.connect_as("test").await
+    };
+    let (event, _player) = tokio::join!(wait, appear);
+
+    match event.unwrap() {
+        Event::PlayerLifecycle(LifecycleEvent::Appeared { name }) => assert_eq!(name, "test"),
+        other => panic!("Unexpected event: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_stream() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new(&conn);
+    let baseline = common::match_rules(&conn).await;
+
+    let mut events = manager
+        .stream(&[EventType::PropertiesChanged, EventType::Seeked])
+        .await
+        .unwrap();
+    assert_eq!(common::match_rules(&conn).await, baseline + 2);
+
+    common::emit(&emitter, common::seeked(":1.1", 1_000_000i64));
+    common::emit(
+        &emitter,
+        common::properties_changed(
+            ":1.1",
+            common::PLAYER_INTERFACE,
+            common::props(vec![("Volume", common::var(0.25f64))]),
+            vec![],
+        ),
+    );
+
+    // Events from separate matches aren't ordered relative to each other
+    let mut received = [events.next().await.unwrap(), events.next().await.unwrap()];
+    received.sort_by_key(|e| e.event_type() == EventType::Seeked);
+    match &received[0] {
+        Event::PropertiesChanged(changed) => {
+            assert_eq!(changed.player, "test");
+            assert_eq!(changed.properties.volume, Some(0.25));
+        }
+        other => panic!("Unexpected event: {:?}", other),
+    }
+    assert_eq!(received[1].event_type(), EventType::Seeked);
+
+    drop(events);
+    assert_eq!(common::match_rules(&conn).await, baseline);
+}
+
+#[tokio::test]
+async fn test_subscribe() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new(&conn);
+    let baseline = common::match_rules(&conn).await;
+
+    let mut first = manager.subscribe(EventType::Seeked).await.unwrap();
+    let mut second = manager.subscribe(EventType::Seeked).await.unwrap();
+    common::emit(&emitter, common::seeked(":1.1", 3_000_000i64));
+
+    // Both subscriptions see the same event independently
+    for subscription in [&mut first, &mut second] {
+        match subscription.recv().await {
+            Event::Seeked(seeked) => assert_eq!(seeked.position, Duration::from_secs(3)),
+            other => panic!("Unexpected event: {:?}", other),
+        }
+    }
+
+    drop(first);
+    drop(second);
+    assert_eq!(common::match_rules(&conn).await, baseline);
+}
+
+/// Emits seeks to 1..=count seconds from `emitter` into a subscription
+/// with a stalled consumer, then returns what the subscription kept.
+async fn stalled_positions(policy: DeliveryPolicy, capacity: usize, count: u64) -> (Vec<u64>, u64) {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new(&conn);
+
+    let options = SubscriptionOptions {
+        capacity,
+        policy,
+        ..SubscriptionOptions::default()
+    };
+    let mut subscription = manager
+        .subscribe_with(EventType::Seeked, options)
+        .await
+        .unwrap();
+    for secs in 1..=count {
+        common::emit(&emitter, common::seeked(":1.1", secs as i64 * 1_000_000));
+    }
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let dropped = subscription.dropped();
+    let mut positions = Vec::new();
+    for _ in 0..count - dropped {
+        match subscription.recv().await {
+            Event::Seeked(seeked) => positions.push(seeked.position.as_secs()),
+            other => panic!("Unexpected event: {:?}", other),
+        }
+    }
+
+    (positions, dropped)
+}
+
+#[tokio::test]
+async fn test_policy_drop_oldest() {
+    let (positions, dropped) = stalled_positions(DeliveryPolicy::DropOldest, 2, 5).await;
+
+    assert_eq!(positions, vec![4, 5]);
+    assert_eq!(dropped, 3);
+}
+
+#[tokio::test]
+async fn test_policy_drop_newest() {
+    let (positions, dropped) = stalled_positions(DeliveryPolicy::DropNewest, 2, 5).await;
+
+    assert_eq!(positions, vec![1, 2]);
+    assert_eq!(dropped, 3);
+}
+
+#[tokio::test]
+async fn test_policy_latest_per_player() {
+    // All seeks come from one player, so only the latest survives
+    let (positions, dropped) = stalled_positions(DeliveryPolicy::LatestPerPlayer, 1, 4).await;
+
+    assert_eq!(positions, vec![4]);
+    assert_eq!(dropped, 3);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_policy_block() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new(&conn);
+
+    let options = SubscriptionOptions {
+        capacity: 1,
+        policy: DeliveryPolicy::Block {
+            timeout: Duration::from_secs(5),
+        },
+        ..SubscriptionOptions::default()
+    };
+    let mut subscription = manager
+        .subscribe_with(EventType::Seeked, options)
+        .await
+        .unwrap();
+
+    // Let the subscription learn the player's name before the burst
+    common::emit(&emitter, common::seeked(":1.1", 0i64));
+    subscription.recv().await;
+
+    for secs in 1..=3 {
+        common::emit(&emitter, common::seeked(":1.1", secs * 1_000_000i64));
+    }
+
+    for secs in 1..=3 {
+        match subscription.recv().await {
+            Event::Seeked(seeked) => assert_eq!(seeked.position.as_secs(), secs),
+            other => panic!("Unexpected event: {:?}", other),
+        }
+    }
+    assert_eq!(subscription.dropped(), 0);
+}
+
+#[tokio::test]
+async fn test_remove_callback() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new(&conn);
+    let baseline = common::match_rules(&conn).await;
+
+    let removed_hits = Arc::new(AtomicUsize::new(0));
+    let kept_hits = Arc::new(AtomicUsize::new(0));
+    let removed = {
+        let hits = removed_hits.clone();
+        manager
+            .add_callback(EventType::Seeked, move |_| {
+                hits.fetch_add(1, Ordering::SeqCst);
+                true
+            })
+            .await
+            .unwrap()
+    };
+    let _kept = {
+        let hits = kept_hits.clone();
+        manager
+            .add_callback(EventType::Seeked, move |_| {
+                hits.fetch_add(1, Ordering::SeqCst);
+                true
+            })
+            .await
+            .unwrap()
+    };
+    assert_eq!(common::match_rules(&conn).await, baseline + 1);
+
+    manager.remove_callback(removed.token()).await.unwrap();
+    assert!(manager.remove_callback(removed.token()).await.is_err());
+    assert_eq!(common::match_rules(&conn).await, baseline + 1);
+
+    common::emit(&emitter, common::seeked(":1.1", 0i64));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(removed_hits.load(Ordering::SeqCst), 0);
+    assert_eq!(kept_hits.load(Ordering::SeqCst), 1);
+
+    manager.clear_callbacks().await.unwrap();
+    assert_eq!(common::match_rules(&conn).await, baseline);
+}
+
+#[tokio::test]
+async fn test_clear_callbacks_twice() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let manager = EventManager::new(&conn);
+    let baseline = common::match_rules(&conn).await;
+
+    let _seeked = manager
+        .add_callback(EventType::Seeked, |_| true)
+        .await
+        .unwrap();
+    let _props = manager
+        .add_callback(EventType::PropertiesChanged, |_| true)
+        .await
+        .unwrap();
+
+    manager.clear_callbacks().await.unwrap();
+    manager.clear_callbacks().await.unwrap();
+    assert_eq!(common::match_rules(&conn).await, baseline);
+}
+
+#[tokio::test]
+async fn test_clear_callbacks_then_add() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new(&conn);
+    let baseline = common::match_rules(&conn).await;
+
+    let _old = manager
+        .add_callback(EventType::Seeked, |_| true)
+        .await
+        .unwrap();
+    manager.clear_callbacks().await.unwrap();
+
+    let hits = Arc::new(AtomicUsize::new(0));
+    let fresh = {
+        let hits = hits.clone();
+        manager
+            .add_callback(EventType::Seeked, move |_| {
+                hits.fetch_add(1, Ordering::SeqCst);
+                true
+            })
+            .await
+            .unwrap()
+    };
+    assert_eq!(common::match_rules(&conn).await, baseline + 1);
+
+    common::emit(&emitter, common::seeked(":1.1", 0i64));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    manager.clear_callbacks().await.unwrap();
+    assert_eq!(common::match_rules(&conn).await, baseline);
+    // The fresh callback was cleared along with its token
+    assert!(manager.remove_callback(fresh.token()).await.is_err());
+}
+
+#[tokio::test]
+async fn test_shutdown() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let manager = EventManager::new(&conn);
+    let baseline = common::match_rules(&conn).await;
+
+    let _seeked = manager
+        .add_callback(EventType::Seeked, |_| true)
+        .await
+        .unwrap();
+    manager.shutdown().await.unwrap();
+    manager.shutdown().await.unwrap();
+    assert_eq!(common::match_rules(&conn).await, baseline);
+}
+
+#[tokio::test]
+async fn test_drop_manager() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let baseline = common::match_rules(&conn).await;
+
+    let hits = Arc::new(AtomicUsize::new(0));
+    let manager = EventManager::new(&conn);
+    let _incoming = {
+        let hits = hits.clone();
+        manager
+            .add_callback(EventType::Seeked, move |_| {
+                hits.fetch_add(1, Ordering::SeqCst);
+                true
+            })
+            .await
+            .unwrap()
+    };
+    drop(manager);
+
+    common::emit(&emitter, common::seeked(":1.1", 0i64));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+    assert_eq!(common::match_rules(&conn).await, baseline);
+}
+
+#[tokio::test]
+async fn test_callback_guard() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new(&conn);
+    let baseline = common::match_rules(&conn).await;
+
+    let scoped_hits = Arc::new(AtomicUsize::new(0));
+    let detached_hits = Arc::new(AtomicUsize::new(0));
+    let scoped = {
+        let hits = scoped_hits.clone();
+        manager
+            .add_callback(EventType::Seeked, move |_| {
+                hits.fetch_add(1, Ordering::SeqCst);
+                true
+            })
+            .await
+            .unwrap()
+    };
+    let detached = {
+        let hits = detached_hits.clone();
+        manager
+            .add_callback(EventType::Seeked, move |_| {
+                hits.fetch_add(1, Ordering::SeqCst);
+                true
+            })
+            .await
+            .unwrap()
+    };
+    let detached_token = detached.token();
+    detached.detach();
+    drop(scoped);
+
+    common::emit(&emitter, common::seeked(":1.1", 0i64));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(scoped_hits.load(Ordering::SeqCst), 0);
+    assert_eq!(detached_hits.load(Ordering::SeqCst), 1);
+    assert_eq!(common::match_rules(&conn).await, baseline + 1);
+
+    manager.remove_callback(detached_token).await.unwrap();
+    assert_eq!(common::match_rules(&conn).await, baseline);
+}
+
+#[test]
+fn test_callback_guard_is_send() {
+    fn assert_send<T: Send>() {}
+    assert_send::<CallbackGuard<'static>>();
+}
+
+#[test]
+fn test_event_futures_are_send() {
+    fn assert_send<T: Send>(_: T) {}
+
+    // Only needs to compile, so that futures stay spawnable
+    fn check(
+        manager: &EventManager<'_>,
+        subscription: &mut pris::Subscription<'_>,
+        changed: &mut pris::ChangedProperties,
+        player: &pris::Player<'_>,
+        msg: &Message,
+        conn: &SyncConnection,
+    ) {
+        assert_send(Event::from_message(msg, conn));
+        assert_send(pris::SeekedEvent::from_message(msg, conn));
+        assert_send(changed.fill_invalidated(player));
+        assert_send(manager.add_callback(EventType::Seeked, |_| true));
+        assert_send(manager.wait_for_event(EventType::Seeked, |_| true, Duration::from_secs(1)));
+        assert_send(manager.remove_callback(dbus::channel::Token(0)));
+        assert_send(manager.clear_callbacks());
+        assert_send(manager.shutdown());
+        assert_send(subscription.recv());
+    }
+    let _ = check;
+}
+
+#[test]
+fn test_event_streams_are_send() {
+    fn assert_send<T: Send>(_: T) {}
+
+    // Only needs to compile, so that streams, and the futures creating
+    // and feeding them, can be moved to another task
+    fn check(manager: &EventManager<'_>, player: &pris::Player<'_>, conn: &SyncConnection) {
+        assert_send(manager.stream(&[EventType::Seeked]));
+        assert_send(manager.all_player_events());
+        assert_send(manager.track_changes(player));
+        assert_send(manager.active_players());
+        assert_send(manager.watch(player));
+        assert_send(manager.resyncs());
+        assert_send(pris::PlayerHealth::monitor(
+            player,
+            Duration::from_secs(1),
+            3,
+        ));
+        assert_send(ExclusivePlayback::new(manager, ExclusiveOptions::default()));
+        assert_send(Controller::new(conn));
+    }
+    async fn created(manager: &EventManager<'_>, conn: &SyncConnection) {
+        assert_send(manager.stream(&[EventType::Seeked]).await.unwrap());
+        assert_send(manager.active_players().await.unwrap());
+        let exclusive = ExclusivePlayback::new(manager, ExclusiveOptions::default());
+        assert_send(exclusive.await.unwrap());
+        let (controller, feed) = Controller::new(conn).await.unwrap();
+        assert_send(controller);
+        assert_send(feed);
+    }
+    let _ = (check, created);
+}
+
+/// Registers an async callback reporting the positions it was called
+/// with, stalling on the first event and panicking on zero.
+async fn position_reporter<'a>(
+    manager: &EventManager<'a>,
+    ordering: CallbackOrdering,
+) -> (CallbackGuard<'a>, mpsc::UnboundedReceiver<(String, u64)>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let guard = manager
+        .add_callback_async(EventType::Seeked, ordering, move |event| {
+            let sender = sender.clone();
+            async move {
+                if let Event::Seeked(seeked) = event {
+                    let secs = seeked.position.as_secs();
+                    match secs {
+                        0 => panic!("Seeked to the start"),
+                        1 => tokio::time::sleep(Duration::from_millis(300)).await,
+                        _ => {}
+                    }
+                    sender.send((seeked.player, secs)).unwrap();
+                }
+            }
+        })
+        .await
+        .unwrap();
+
+    (guard, receiver)
+}
+
+#[tokio::test]
+async fn test_async_callback_serial() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let manager = EventManager::new(&conn);
+    let (_guard, mut reports) = position_reporter(&manager, CallbackOrdering::Serial).await;
+
+    // The player shows up after the callback was added
+    let emitter = bus.connect_as("test").await;
+    for secs in 0..=3 {
+        common::emit(&emitter, common::seeked(":1.1", secs * 1_000_000i64));
+    }
+
+    for secs in 1..=3 {
+        let report = tokio::time::timeout(Duration::from_secs(5), reports.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(report, ("test".to_string(), secs));
+    }
+}
+
+#[tokio::test]
+async fn test_async_callback_concurrent() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new(&conn);
+    let (_guard, mut reports) = position_reporter(&manager, CallbackOrdering::Concurrent).await;
+
+    for secs in 1..=2 {
+        common::emit(&emitter, common::seeked(":1.1", secs * 1_000_000i64));
+    }
+
+    // The first future stalls, so the second one overtakes it
+    for secs in [2, 1] {
+        let report = tokio::time::timeout(Duration::from_secs(5), reports.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(report, ("test".to_string(), secs));
+    }
+}
+
+#[tokio::test]
+async fn test_fallible_callback() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new(&conn);
+    let baseline = common::match_rules(&conn).await;
+
+    let (errors_sender, mut errors) = mpsc::unbounded_channel();
+    manager.on_callback_error(move |error| errors_sender.send(error.clone()).unwrap());
+    manager.set_failure_threshold(Some(2));
+
+    let healthy_hits = Arc::new(AtomicUsize::new(0));
+    let _healthy = {
+        let hits = healthy_hits.clone();
+        manager
+            .add_fallible_callback(EventType::Seeked, move |_| {
+                hits.fetch_add(1, Ordering::SeqCst);
+                Ok::<_, String>(())
+            })
+            .await
+            .unwrap()
+    };
+    let broken = manager
+        .add_fallible_callback(EventType::Seeked, |_| Err("Broken callback"))
+        .await
+        .unwrap();
+    assert_eq!(common::match_rules(&conn).await, baseline + 1);
+
+    for _ in 0..3 {
+        common::emit(&emitter, common::seeked(":1.1", 0i64));
+    }
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let first = errors.recv().await.unwrap();
+    assert!(first.token == broken.token());
+    assert_eq!(first.message, "Broken callback");
+    assert!(!first.unregistered);
+    assert!(errors.recv().await.unwrap().unregistered);
+    assert!(errors.try_recv().is_err());
+
+    assert_eq!(healthy_hits.load(Ordering::SeqCst), 3);
+    assert_eq!(manager.callback_error_count(), 2);
+    assert!(manager.last_callback_error().unwrap().unregistered);
+    assert_eq!(common::match_rules(&conn).await, baseline + 1);
+
+    drop(broken);
+    manager.clear_callbacks().await.unwrap();
+    assert_eq!(common::match_rules(&conn).await, baseline);
+}
+
+#[tokio::test]
+async fn test_error_hook_uses_manager() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new_owned(conn.clone());
+
+    // The hook may look at the errors, and change the threshold
+    let (seen_sender, mut seen) = mpsc::unbounded_channel();
+    let hooked = manager.clone();
+    manager.on_callback_error(move |error| {
+        let last = hooked.last_callback_error().unwrap();
+        seen_sender
+            .send((last.message == error.message, hooked.callback_error_count()))
+            .unwrap();
+        hooked.set_failure_threshold(Some(1));
+    });
+    let _broken = manager
+        .add_fallible_callback(EventType::Seeked, |_| Err("Broken callback"))
+        .await
+        .unwrap();
+
+    for _ in 0..2 {
+        common::emit(&emitter, common::seeked(":1.1", 0i64));
+    }
+    for count in 1..=2 {
+        let next = tokio::time::timeout(Duration::from_secs(5), seen.recv());
+        assert_eq!(next.await.unwrap(), Some((true, count)));
+    }
+    assert!(manager.last_callback_error().unwrap().unregistered);
+
+    // Replacing the hook lets go of the manager it holds
+    manager.on_callback_error(|_| {});
+    manager.clear_callbacks().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_callback_queue() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new(&conn);
+    let baseline = common::match_rules(&conn).await;
+    manager.set_callback_queue(Some(SubscriptionOptions::default()));
+
+    let slow_hits = Arc::new(AtomicUsize::new(0));
+    let slow = {
+        let hits = slow_hits.clone();
+        manager
+            .add_callback(EventType::Seeked, move |_| {
+                std::thread::sleep(Duration::from_secs(2));
+                hits.fetch_add(1, Ordering::SeqCst);
+                true
+            })
+            .await
+            .unwrap()
+    };
+    let panicking = manager
+        .add_callback(EventType::Seeked, |_| panic!("Broken callback"))
+        .await
+        .unwrap();
+    let (sender, mut positions) = mpsc::unbounded_channel();
+    let fast = manager
+        .add_callback(EventType::Seeked, move |msg| {
+            if let Ok(Event::Seeked(seeked)) = Event::parse(&msg, "test") {
+                let _ = sender.send(seeked.position);
+            }
+            true
+        })
+        .await
+        .unwrap();
+
+    // The fast callback gets every event in order, long before the
+    // slow one is done with the first
+    for position in 1..=3 {
+        common::emit(&emitter, common::seeked(":1.1", position as i64));
+    }
+    for position in 1..=3 {
+        let received = tokio::time::timeout(Duration::from_secs(1), positions.recv());
+        assert_eq!(
+            received.await.unwrap(),
+            Some(Duration::from_micros(position))
+        );
+    }
+    assert_eq!(slow_hits.load(Ordering::SeqCst), 0);
+
+    // The panic only took the panicking callback down
+    common::emit(&emitter, common::seeked(":1.1", 4i64));
+    let received = tokio::time::timeout(Duration::from_secs(1), positions.recv());
+    assert_eq!(received.await.unwrap(), Some(Duration::from_micros(4)));
+
+    drop((slow, panicking, fast));
+    assert_eq!(common::match_rules(&conn).await, baseline);
+}
+
+#[tokio::test]
+async fn test_property_callback() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new(&conn);
+
+    let (sender, mut changes) = mpsc::unbounded_channel();
+    let _guard = manager
+        .add_property_callback(&["Volume"], move |change| {
+            sender.send(change).unwrap();
+            true
+        })
+        .await
+        .unwrap();
+
+    let changed = |props, invalidated| {
+        common::properties_changed(":1.1", common::PLAYER_INTERFACE, props, invalidated)
+    };
+    common::emit(
+        &emitter,
+        changed(common::props(vec![("Volume", common::var(0.5f64))]), vec![]),
+    );
+    common::emit(
+        &emitter,
+        changed(
+            common::props(vec![("Shuffle", common::var(true))]),
+            vec!["Metadata"],
+        ),
+    );
+    common::emit(&emitter, changed(common::props(vec![]), vec!["Volume"]));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let first = changes.try_recv().unwrap();
+    assert_eq!(
+        (first.player.as_str(), first.name.as_str()),
+        ("test", "Volume")
+    );
+    assert!(matches!(first.value, PropertyValue::Double(v) if v == 0.5));
+    assert!(matches!(
+        changes.try_recv().unwrap().value,
+        PropertyValue::Invalidated
+    ));
+    assert!(changes.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_playback_statuses() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    common::serve_properties(
+        &emitter,
+        common::props(vec![("PlaybackStatus", common::var("Paused".to_string()))]),
+    );
+    let manager = EventManager::new(&conn);
+    let mut statuses = manager.playback_statuses().await.unwrap();
+
+    let status_changed = |status: &str| {
+        common::properties_changed(
+            ":1.1",
+            common::PLAYER_INTERFACE,
+            common::props(vec![
+                ("PlaybackStatus", common::var(status.to_string())),
+                ("Volume", common::var(0.5f64)),
+            ]),
+            vec![],
+        )
+    };
+    for status in ["Paused", "Playing", "Playing", "Playing", "Paused"] {
+        common::emit(&emitter, status_changed(status));
+    }
+    emitter
+        .release_name("org.mpris.MediaPlayer2.test")
+        .await
+        .unwrap();
+
+    let mut received = Vec::new();
+    for _ in 0..4 {
+        let next = tokio::time::timeout(Duration::from_secs(5), statuses.next());
+        received.push(next.await.unwrap().unwrap());
+    }
+    let test = |status| ("test".to_string(), status);
+    assert_eq!(
+        received,
+        vec![
+            test(PlaybackStatus::Paused),
+            test(PlaybackStatus::Playing),
+            test(PlaybackStatus::Paused),
+            test(PlaybackStatus::Stopped),
+        ]
+    );
+
+    let quiet = tokio::time::timeout(Duration::from_millis(300), statuses.next());
+    assert!(quiet.await.is_err());
+}
+
+#[tokio::test]
+async fn test_track_changes() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let track = |id: &str, title: &str| {
+        common::props(vec![
+            (
+                "mpris:trackid",
+                common::var(dbus::Path::from(id.to_string())),
+            ),
+            ("xesam:title", common::var(title.to_string())),
+        ])
+    };
+    common::serve_properties(
+        &emitter,
+        common::props(vec![("Metadata", common::var(track("/track/1", "One")))]),
+    );
+    let manager = EventManager::new(&conn);
+    let player = pris::Player::try_new("test", &conn).await.unwrap();
+    let mut tracks = manager.track_changes(&player).await.unwrap();
+
+    let metadata_changed = |metadata| {
+        common::properties_changed(
+            ":1.1",
+            common::PLAYER_INTERFACE,
+            common::props(vec![("Metadata", common::var(metadata))]),
+            vec![],
+        )
+    };
+    let streamed = common::props(vec![(
+        "xesam:url",
+        common::var("https://example.com/3".to_string()),
+    )]);
+    let mut streamed_retitled = common::clone_props(&streamed);
+    streamed_retitled.insert("xesam:title".to_string(), common::var("Three".to_string()));
+
+    // Spotify-style burst of updates for the same track
+    common::emit(
+        &emitter,
+        metadata_changed(track("/track/1", "One (Remastered)")),
+    );
+    common::emit(&emitter, metadata_changed(track("/track/2", "Two")));
+    common::emit(&emitter, metadata_changed(track("/track/2", "Two")));
+    common::emit(&emitter, metadata_changed(common::props(vec![])));
+    common::emit(&emitter, metadata_changed(streamed));
+    common::emit(&emitter, metadata_changed(streamed_retitled));
+    emitter
+        .release_name("org.mpris.MediaPlayer2.test")
+        .await
+        .unwrap();
+
+    let mut received = Vec::new();
+    for _ in 0..5 {
+        let next = tokio::time::timeout(Duration::from_secs(5), tracks.next());
+        received.push(match next.await.unwrap().unwrap() {
+            TrackChange::Track(metadata) => metadata
+                .get("mpris:trackid")
+                .or_else(|| metadata.get("xesam:url"))
+                .and_then(|v| v.0.as_str().map(str::to_string)),
+            TrackChange::NoTrack => None,
+        });
+    }
+    assert_eq!(
+        received,
+        vec![
+            Some("/track/1".to_string()),
+            Some("/track/2".to_string()),
+            None,
+            Some("https://example.com/3".to_string()),
+            None,
+        ]
+    );
+
+    let quiet = tokio::time::timeout(Duration::from_millis(300), tracks.next());
+    assert!(quiet.await.is_err());
+}
+
+#[tokio::test]
+async fn test_stamped_events() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new(&conn);
+
+    let options = SubscriptionOptions {
+        capacity: 2,
+        policy: DeliveryPolicy::DropOldest,
+        ..SubscriptionOptions::default()
+    };
+    let mut subscription = manager
+        .subscribe_with(EventType::Seeked, options)
+        .await
+        .unwrap();
+    let mut stream = manager
+        .stream(&[EventType::Seeked])
+        .await
+        .unwrap()
+        .stamped();
+    let before = std::time::Instant::now();
+    for secs in 1..=5 {
+        common::emit(&emitter, common::seeked(":1.1", secs * 1_000_000i64));
+    }
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let first = subscription.recv_stamped().await;
+    let second = subscription.recv_stamped().await;
+    assert_eq!(first.dropped_before, 3);
+    assert_eq!(second.dropped_before, 0);
+    assert!(first.sequence < second.sequence);
+    assert!(before <= first.received && first.received <= second.received);
+
+    let mut sequences = Vec::new();
+    for _ in 0..5 {
+        let stamped = stream.next().await.unwrap();
+        assert_eq!(stamped.dropped_before, 0);
+        sequences.push(stamped.sequence);
+    }
+    assert!(sequences.windows(2).all(|w| w[0] < w[1]));
+}
+
+#[tokio::test]
+async fn test_snapshot_subscription() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let anonymous = bus.connect();
+    common::serve_properties(
+        &emitter,
+        common::props(vec![
+            ("PlaybackStatus", common::var("Playing".to_string())),
+            ("Volume", common::var(0.5f64)),
+        ]),
+    );
+    let manager = EventManager::new(&conn);
+    let mut subscription = manager
+        .subscribe_with_snapshots(EventType::Seeked, SubscriptionOptions::default())
+        .await
+        .unwrap();
+
+    common::emit(&emitter, common::seeked(":1.1", 1_000_000i64));
+    let (event, state) = subscription.recv().await;
+    assert_eq!(event.player(), "test");
+    let state = state.unwrap();
+    assert_eq!(state.player, "test");
+    assert_eq!(state.playback_status, Some(PlaybackStatus::Playing));
+    assert_eq!(state.volume, Some(0.5));
+
+    // Snapshots that can't be fetched still deliver the event
+    common::emit(&anonymous, common::seeked(":1.1", 2_000_000i64));
+    let (event, state) = subscription.recv().await;
+    assert!(matches!(event, Event::Seeked(s) if s.position == Duration::from_secs(2)));
+    assert!(state.is_none());
+}
+
+/// Registers a callback reporting the position of every `Seeked` signal.
+async fn seeked_positions<'a>(
+    manager: &EventManager<'a>,
+) -> (CallbackGuard<'a>, mpsc::UnboundedReceiver<i64>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let guard = manager
+        .add_callback(EventType::Seeked, move |msg| {
+            sender.send(msg.read1::<i64>().unwrap()).is_ok()
+        })
+        .await
+        .unwrap();
+
+    (guard, receiver)
+}
+
+/// Emits a `Seeked` signal for each of `positions`, returning once
+/// the last one has reached the manager.
+async fn emit_positions(manager: &EventManager<'_>, emitter: &SyncConnection, positions: &[i64]) {
+    let last = Duration::from_micros(*positions.last().unwrap() as u64);
+    let wait = manager.wait_for_event(
+        EventType::Seeked,
+        |event| matches!(event, Event::Seeked(s) if s.position == last),
+        Duration::from_secs(5),
+    );
+    let emit = async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        for &position in positions {
+            common::emit(emitter, common::seeked(":1.1", position));
+        }
+    };
+    let (event, _) = tokio::join!(wait, emit);
+    event.unwrap();
+}
+
+#[tokio::test]
+async fn test_pause_drop() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new(&conn);
+    let (_guard, mut positions) = seeked_positions(&manager).await;
+    let rules = common::match_rules(&conn).await;
+
+    manager.pause(PausePolicy::Drop);
+    assert!(manager.is_paused());
+    emit_positions(&manager, &emitter, &[1, 2]).await;
+    assert!(positions.try_recv().is_err());
+    assert_eq!(common::match_rules(&conn).await, rules);
+
+    manager.resume();
+    assert!(!manager.is_paused());
+    assert!(positions.try_recv().is_err());
+    emit_positions(&manager, &emitter, &[3]).await;
+    assert_eq!(positions.recv().await, Some(3));
+}
+
+#[tokio::test]
+async fn test_pause_buffer() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new(&conn);
+    let (_guard, mut positions) = seeked_positions(&manager).await;
+    let mut subscription = manager.subscribe(EventType::Seeked).await.unwrap();
+
+    manager.pause(PausePolicy::Buffer { capacity: 3 });
+    emit_positions(&manager, &emitter, &[1, 2, 3, 4]).await;
+    assert!(positions.try_recv().is_err());
+
+    manager.resume();
+    emit_positions(&manager, &emitter, &[5]).await;
+    // The oldest signal didn't fit in the buffer
+    for expected in 2..=5 {
+        assert_eq!(positions.recv().await, Some(expected));
+        match subscription.recv().await {
+            Event::Seeked(seeked) => {
+                assert_eq!(seeked.position, Duration::from_micros(expected as u64))
+            }
+            other => panic!("Unexpected event: {:?}", other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_callback_multi() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new(&conn);
+    let baseline = common::match_rules(&conn).await;
+
+    let (sender, mut received) = mpsc::unbounded_channel();
+    let guard = manager
+        .add_callback_multi(
+            &[
+                EventType::PropertiesChanged,
+                EventType::Seeked,
+                EventType::Seeked,
+            ],
+            move |event_type, _| sender.send(event_type).is_ok(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(common::match_rules(&conn).await, baseline + 2);
+    assert!(manager.add_callback_multi(&[], |_, _| true).await.is_err());
+
+    common::emit(&emitter, common::seeked(":1.1", 0i64));
+    common::emit(
+        &emitter,
+        common::properties_changed(
+            ":1.1",
+            "org.mpris.MediaPlayer2.Player",
+            common::props(vec![("Volume", common::var(0.5))]),
+            vec![],
+        ),
+    );
+    assert_eq!(received.recv().await, Some(EventType::Seeked));
+    assert_eq!(received.recv().await, Some(EventType::PropertiesChanged));
+
+    drop(guard);
+    assert_eq!(common::match_rules(&conn).await, baseline);
+}
+
+#[tokio::test]
+async fn test_callback_introspection() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new(&conn);
+    assert!(manager.callbacks().is_empty());
+
+    let (_seeked, mut positions) = seeked_positions(&manager).await;
+    let multi = manager
+        .add_callback_multi(
+            &[EventType::PropertiesChanged, EventType::PlayerLifecycle],
+            |_, _| true,
+        )
+        .await
+        .unwrap();
+    let multi_token = multi.token();
+    multi.detach();
+
+    common::emit(&emitter, common::seeked(":1.1", 1i64));
+    common::emit(&emitter, common::seeked(":1.1", 2i64));
+    positions.recv().await.unwrap();
+    positions.recv().await.unwrap();
+
+    let callbacks = manager.callbacks();
+    assert_eq!(callbacks.len(), 2);
+    assert_eq!(callbacks[0].event_types, vec![EventType::Seeked]);
+    assert_eq!(callbacks[0].fired, 2);
+    assert!(!callbacks[0].detached);
+
+    assert!(callbacks[1].token == multi_token);
+    assert_eq!(
+        callbacks[1].event_types,
+        vec![EventType::PropertiesChanged, EventType::PlayerLifecycle]
+    );
+    assert_eq!(callbacks[1].fired, 0);
+    assert!(callbacks[1].detached);
+
+    let debug = format!("{:?}", manager);
+    assert!(debug.contains("fired: 2"), "{}", debug);
+}
+
+#[tokio::test]
+async fn test_raw_match() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new(&conn);
+    let baseline = common::match_rules(&conn).await;
+
+    let rule = pris::mpris_match_rule()
+        .with_interface("org.mpris.MediaPlayer2.Player")
+        .with_member("TrackChanged");
+    let (sender, mut received) = mpsc::unbounded_channel();
+    manager
+        .add_raw_match(rule, move |msg| {
+            sender
+                .send(msg.read1::<&str>().unwrap().to_string())
+                .is_ok()
+        })
+        .await
+        .unwrap()
+        .detach();
+    assert_eq!(common::match_rules(&conn).await, baseline + 1);
+
+    let info = &manager.callbacks()[0];
+    assert!(info.event_types.is_empty());
+    assert!(info
+        .rule
+        .as_ref()
+        .unwrap()
+        .contains("member='TrackChanged'"));
+
+    common::emit(&emitter, common::seeked(":1.1", 0i64));
+    common::emit(
+        &emitter,
+        common::signal(":1.1", "org.mpris.MediaPlayer2.Player", "TrackChanged").append1("one"),
+    );
+    assert_eq!(received.recv().await.as_deref(), Some("one"));
+
+    manager.clear_callbacks().await.unwrap();
+    assert_eq!(common::match_rules(&conn).await, baseline);
+}
+
+#[tokio::test]
+async fn test_watch() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let other = bus.connect_as("other").await;
+    let manager = EventManager::new(&conn);
+    let baseline = common::match_rules(&conn).await;
+
+    let player = pris::Player::try_new("test", &conn).await.unwrap();
+    let mut events = manager.watch(&player).await.unwrap();
+    assert_eq!(common::match_rules(&conn).await, baseline + 3);
+
+    common::emit(&other, common::seeked(":1.1", 1i64));
+    common::emit(&emitter, common::seeked(":1.1", 2i64));
+    emitter
+        .release_name("org.mpris.MediaPlayer2.test")
+        .await
+        .unwrap();
+
+    let mut received = Vec::new();
+    loop {
+        let next = tokio::time::timeout(Duration::from_secs(5), events.next_event());
+        match next.await.unwrap() {
+            Some(event) => received.push(event),
+            None => break,
+        }
+    }
+    assert_eq!(received.len(), 2, "{:?}", received);
+    match &received[0] {
+        Event::Seeked(seeked) => {
+            assert_eq!(seeked.player, "test");
+            assert_eq!(seeked.position, Duration::from_micros(2));
+        }
+        other => panic!("Unexpected event: {:?}", other),
+    }
+    match &received[1] {
+        Event::PlayerLifecycle(LifecycleEvent::Vanished { name }) => assert_eq!(name, "test"),
+        other => panic!("Unexpected event: {:?}", other),
+    }
+
+    drop(events);
+    assert_eq!(common::match_rules(&conn).await, baseline);
+    assert!(manager.watch(&player).await.is_err());
+}
+
+#[tokio::test]
+async fn test_properties_rule() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let _emitter = bus.connect_as("test").await;
+    let manager = EventManager::new(&conn);
+    let baseline = common::match_rules(&conn).await;
+    let added = common::added_rules(&bus, &conn.unique_name()).await;
+
+    // The bus is only asked for the changes of the player interface,
+    // with or without a sender
+    let player = pris::Player::try_new("test", &conn).await.unwrap();
+    let guard = manager
+        .add_callback(EventType::PropertiesChanged, |_| true)
+        .await
+        .unwrap();
+    let events = manager.watch(&player).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let rules: Vec<String> = added
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|rule| rule.contains("member='PropertiesChanged'"))
+        .cloned()
+        .collect();
+    assert_eq!(rules.len(), 2, "{:?}", rules);
+    assert!(rules
+        .iter()
+        .all(|rule| rule.ends_with(",arg0='org.mpris.MediaPlayer2.Player'")));
+
+    // The rules are removed as they were added
+    manager.remove_callback(guard.token()).await.unwrap();
+    drop(events);
+    assert_eq!(common::match_rules(&conn).await, baseline);
+}
+
+#[tokio::test]
+async fn test_initial_state() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    // Change the volume while the initial state is being fetched
+    emitter.start_receive(
+        MatchRule::new_method_call(),
+        Box::new(|msg, conn| {
+            let volume = common::props(vec![("Volume", common::var(0.7f64))]);
+            let mut changed = common::properties_changed(
+                ":1.1",
+                common::PLAYER_INTERFACE,
+                common::clone_props(&volume),
+                vec![],
+            );
+            changed.set_sender(None);
+            let _ = conn.send(changed);
+            let _ = conn.send(msg.method_return().append1(volume));
+            true
+        }),
+    );
+    let manager = EventManager::new(&conn);
+
+    let options = SubscriptionOptions {
+        initial_state: true,
+        ..SubscriptionOptions::default()
+    };
+    let mut subscription = manager
+        .subscribe_with(EventType::PropertiesChanged, options)
+        .await
+        .unwrap();
+    common::emit(
+        &emitter,
+        common::properties_changed(
+            ":1.1",
+            common::PLAYER_INTERFACE,
+            common::props(vec![("Volume", common::var(0.9f64))]),
+            vec![],
+        ),
+    );
+
+    let mut received = Vec::new();
+    for _ in 0..3 {
+        let next = tokio::time::timeout(Duration::from_secs(5), subscription.recv_stamped());
+        let stamped = next.await.unwrap();
+        match stamped.event {
+            Event::PropertiesChanged(event) => {
+                assert_eq!(event.player, "test");
+                received.push((event.properties.volume, stamped.initial));
+            }
+            other => panic!("Unexpected event: {:?}", other),
+        }
+    }
+    // The signal sent during the fetch follows the initial event
+    assert_eq!(
+        received,
+        vec![(Some(0.7), true), (Some(0.7), false), (Some(0.9), false)]
+    );
+}
+
+async fn next_active(tracker: &mut pris::ActivePlayerTracker<'_>) -> Option<String> {
+    tokio::time::timeout(Duration::from_secs(5), tracker.next())
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_active_players() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let status =
+        |status: &str| common::props(vec![("PlaybackStatus", common::var(status.to_string()))]);
+    let alpha = bus.connect_as("alpha").await;
+    let _alpha_served = common::serve_properties(&alpha, status("Paused"));
+    let beta = bus.connect_as("beta").await;
+    let _beta_served = common::serve_properties(&beta, status("Paused"));
+    let manager = EventManager::new(&conn);
+
+    // Players that haven't done anything yet are ranked by name
+    let mut tracker = manager.active_players().await.unwrap();
+    assert_eq!(next_active(&mut tracker).await.as_deref(), Some("alpha"));
+
+    common::emit(&beta, common::seeked(":1.1", 1i64));
+    assert_eq!(next_active(&mut tracker).await.as_deref(), Some("beta"));
+
+    let playing =
+        common::properties_changed(":1.1", common::PLAYER_INTERFACE, status("Playing"), vec![]);
+    common::emit(&alpha, playing);
+    assert_eq!(next_active(&mut tracker).await.as_deref(), Some("alpha"));
+
+    // Playing players outrank more recent ones that aren't
+    common::emit(&beta, common::seeked(":1.1", 2i64));
+    let gamma = bus.connect();
+    let _gamma_served = common::serve_properties(&gamma, status("Playing"));
+    gamma
+        .request_name("org.mpris.MediaPlayer2.gamma", false, true, true)
+        .await
+        .unwrap();
+    assert_eq!(next_active(&mut tracker).await.as_deref(), Some("gamma"));
+    assert_eq!(tracker.players(), vec!["gamma", "alpha", "beta"]);
+
+    gamma
+        .release_name("org.mpris.MediaPlayer2.gamma")
+        .await
+        .unwrap();
+    assert_eq!(next_active(&mut tracker).await.as_deref(), Some("alpha"));
+    alpha
+        .release_name("org.mpris.MediaPlayer2.alpha")
+        .await
+        .unwrap();
+    assert_eq!(next_active(&mut tracker).await.as_deref(), Some("beta"));
+    beta.release_name("org.mpris.MediaPlayer2.beta")
+        .await
+        .unwrap();
+    assert_eq!(next_active(&mut tracker).await, None);
+    assert_eq!(tracker.current(), None);
+}
+
+#[tokio::test]
+async fn test_all_player_events() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let manager = EventManager::new(&conn);
+    let mut events = manager.all_player_events().await.unwrap();
+
+    // Players that appear later are picked up without subscribing again
+    let emitter = bus.connect_as("test").await;
+    let outsider = bus.connect();
+    common::emit(&outsider, common::seeked(":1.1", 1i64));
+    common::emit(&emitter, common::seeked(":1.1", 2i64));
+    emitter
+        .release_name("org.mpris.MediaPlayer2.test")
+        .await
+        .unwrap();
+    // Nothing more is reported once the player has quit
+    common::emit(&emitter, common::seeked(":1.1", 3i64));
+    let late = bus.connect_as("late").await;
+
+    let mut received = Vec::new();
+    while received.len() < 4 {
+        let next = tokio::time::timeout(Duration::from_secs(5), events.next());
+        received.push(next.await.unwrap().unwrap());
+    }
+    drop(late);
+
+    let players: Vec<&str> = received.iter().map(|(player, _)| player.as_str()).collect();
+    assert_eq!(players, vec!["test", "test", "test", "late"]);
+    assert!(matches!(
+        received[0].1,
+        Event::PlayerLifecycle(LifecycleEvent::Appeared { .. })
+    ));
+    match &received[1].1 {
+        Event::Seeked(seeked) => assert_eq!(seeked.position, Duration::from_micros(2)),
+        other => panic!("Unexpected event: {:?}", other),
+    }
+    assert!(matches!(
+        received[2].1,
+        Event::PlayerLifecycle(LifecycleEvent::Vanished { .. })
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_shared_manager() {
+    let bus = common::TestBus::new();
+    let conn: &'static SyncConnection = Box::leak(Box::new(bus.connect()));
+    let manager = EventManager::new(conn);
+    let baseline = common::match_rules(conn).await;
+
+    // Callbacks registered concurrently through clones all end up shared
+    let tasks: Vec<_> = (0..8)
+        .map(|_| {
+            let manager = manager.clone();
+            tokio::spawn(async move {
+                for _ in 0..5 {
+                    manager
+                        .add_callback(EventType::Seeked, |_| true)
+                        .await
+                        .unwrap()
+                        .detach();
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+    assert_eq!(manager.callbacks().len(), 40);
+
+    // Removals through one clone and additions through another
+    let tokens: Vec<_> = manager.callbacks().iter().map(|c| c.token).collect();
+    let remover = {
+        let manager = manager.clone();
+        tokio::spawn(async move {
+            for token in tokens.into_iter().take(20) {
+                manager.remove_callback(token).await.unwrap();
+            }
+        })
+    };
+    let adder = {
+        let manager = manager.clone();
+        tokio::spawn(async move {
+            for _ in 0..10 {
+                manager
+                    .add_callback(EventType::Seeked, |_| true)
+                    .await
+                    .unwrap()
+                    .detach();
+            }
+        })
+    };
+    remover.await.unwrap();
+    adder.await.unwrap();
+    assert_eq!(manager.callbacks().len(), 30);
+
+    // Dropping a clone leaves the shared callbacks in place
+    let clone = manager.clone();
+    drop(manager);
+    assert_eq!(clone.callbacks().len(), 30);
+    assert_eq!(common::match_rules(conn).await, baseline + 1);
+
+    clone.clone().clear_callbacks().await.unwrap();
+    assert!(clone.callbacks().is_empty());
+    assert_eq!(common::match_rules(conn).await, baseline);
+}
+
+fn assert_send_static<T: Send + 'static>(_: &T) {}
+
+struct App {
+    events: pris::OwnedEventManager,
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_owned_manager() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let app = App {
+        events: EventManager::new_owned(conn.clone()),
+    };
+    assert_send_static(&app.events);
+    let baseline = common::match_rules(&conn).await;
+
+    let (sender, mut positions) = tokio::sync::mpsc::unbounded_channel();
+    let manager = app.events.clone();
+    let guard = tokio::spawn(async move {
+        manager
+            .add_callback(EventType::Seeked, move |msg| {
+                let event = Event::parse(&msg, "test").unwrap();
+                if let Event::Seeked(seeked) = event {
+                    let _ = sender.send(seeked.position);
+                }
+                true
+            })
+            .await
+            .unwrap()
+    })
+    .await
+    .unwrap();
+    assert_send_static(&guard);
+    let subscription = app.events.subscribe(EventType::Seeked).await.unwrap();
+    assert_send_static(&subscription);
+    assert_eq!(common::match_rules(&conn).await, baseline + 1);
+
+    common::emit(&emitter, common::seeked(":1.1", 5i64));
+    let position = tokio::time::timeout(Duration::from_secs(5), positions.recv());
+    assert_eq!(position.await.unwrap(), Some(Duration::from_micros(5)));
+
+    // Guards can be dropped on another task
+    tokio::spawn(async move { drop((guard, subscription)) })
+        .await
+        .unwrap();
+    drop(app);
+    assert_eq!(common::match_rules(&conn).await, baseline);
+}
+
+#[tokio::test]
+async fn test_rebind() {
+    let bus = common::TestBus::new();
+    let (old, new) = (bus.connect(), bus.connect());
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new_owned(old.clone());
+    let baselines = (
+        common::match_rules(&old).await,
+        common::match_rules(&new).await,
+    );
+
+    let (sender, mut positions) = mpsc::unbounded_channel();
+    let guard = manager
+        .add_callback(EventType::Seeked, move |msg| {
+            if let Ok(Event::Seeked(seeked)) = Event::parse(&msg, "test") {
+                let _ = sender.send(seeked.position);
+            }
+            true
+        })
+        .await
+        .unwrap();
+    let mut resyncs = manager.resyncs();
+
+    let resync = manager.rebind(new.clone()).await.unwrap();
+    assert_eq!((resync.generation, resync.callbacks), (1, 1));
+    let notified = tokio::time::timeout(Duration::from_secs(5), resyncs.next());
+    assert_eq!(notified.await.unwrap(), Some(resync));
+    assert!(manager.callbacks()[0].token == guard.token());
+    assert_eq!(common::match_rules(&old).await, baselines.0);
+    assert_eq!(common::match_rules(&new).await, baselines.1 + 1);
+
+    // The callback only hears from the new connection
+    common::emit(&emitter, common::seeked(":1.1", 5i64));
+    let position = tokio::time::timeout(Duration::from_secs(5), positions.recv());
+    assert_eq!(position.await.unwrap(), Some(Duration::from_micros(5)));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(positions.try_recv().is_err());
+
+    drop(guard);
+    assert_eq!(common::match_rules(&new).await, baselines.1);
+}
+
+#[tokio::test]
+async fn test_multiple_buses() {
+    let (session_bus, system_bus) = (common::TestBus::new(), common::TestBus::new());
+    let (session, system) = (session_bus.connect(), system_bus.connect());
+    let emitters = (
+        session_bus.connect_as("test").await,
+        system_bus.connect_as("test").await,
+    );
+    let mut buses = MultiBusManager::new();
+    buses
+        .add_bus("session", EventManager::new_owned(session.clone()))
+        .unwrap();
+    buses
+        .add_bus("system", EventManager::new_owned(system.clone()))
+        .unwrap();
+    assert!(buses
+        .add_bus("system", EventManager::new_owned(system.clone()))
+        .is_err());
+    assert_eq!(buses.buses(), ["session", "system"]);
+    let baselines = (
+        common::match_rules(&session).await,
+        common::match_rules(&system).await,
+    );
+
+    let players = buses.players().await.unwrap();
+    let found: Vec<(&str, &str)> = players
+        .iter()
+        .map(|(bus, player)| (bus.as_str(), player.name.as_str()))
+        .collect();
+    assert_eq!(found, [("session", "test"), ("system", "test")]);
+    assert!(buses.player("system", "test").await.is_ok());
+    assert!(buses.player("other", "test").await.is_err());
+
+    // Events are labelled with the bus they came from
+    let mut events = buses.stream(&[EventType::Seeked]).await.unwrap();
+    common::emit(&emitters.1, common::seeked(":1.1", 5i64));
+    let received = tokio::time::timeout(Duration::from_secs(5), events.next());
+    let received = received.await.unwrap().unwrap();
+    assert_eq!(received.bus, "system");
+    assert_eq!(received.event.player(), "test");
+    drop(events);
+
+    let (sender, mut labels) = mpsc::unbounded_channel();
+    let guard = buses
+        .add_callback(EventType::Seeked, move |bus, _| {
+            let _ = sender.send(bus.to_string());
+            true
+        })
+        .await
+        .unwrap();
+    let tokens = guard.tokens();
+    assert_eq!(tokens.len(), 2);
+    assert_eq!(common::match_rules(&session).await, baselines.0 + 1);
+    assert_eq!(common::match_rules(&system).await, baselines.1 + 1);
+
+    common::emit(&emitters.0, common::seeked(":1.1", 5i64));
+    let label = tokio::time::timeout(Duration::from_secs(5), labels.recv());
+    assert_eq!(label.await.unwrap().as_deref(), Some("session"));
+
+    drop(guard);
+    assert_eq!(common::match_rules(&session).await, baselines.0);
+    assert_eq!(common::match_rules(&system).await, baselines.1);
+}
+
+async fn next_pending(events: &mut futures::stream::BoxStream<'_, Event>) -> Event {
+    let event = tokio::time::timeout(Duration::from_secs(5), events.next());
+    event.await.unwrap().unwrap()
+}
+
+#[tokio::test]
+async fn test_when_player() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let manager = EventManager::new(&conn);
+    let pending = manager.when_player("test*").await.unwrap();
+    assert!(pending.players().is_empty());
+    let mut events = pending
+        .stream(&[EventType::PropertiesChanged, EventType::PlayerLifecycle])
+        .await
+        .unwrap();
+
+    let player = bus.connect();
+    let served = common::serve_properties(
+        &player,
+        common::props(vec![("PlaybackStatus", common::var("Playing".to_string()))]),
+    );
+    let other = bus.connect_as("other").await;
+    common::emit(&other, common::seeked(":1.1", 5i64));
+
+    // The callbacks keep working over several runs of the player
+    for status in ["Playing", "Paused"] {
+        served.lock().unwrap().insert(
+            "PlaybackStatus".to_string(),
+            common::var(status.to_string()),
+        );
+        player
+            .request_name("org.mpris.MediaPlayer2.test.instance1", false, true, true)
+            .await
+            .unwrap();
+        match next_pending(&mut events).await {
+            Event::PlayerLifecycle(LifecycleEvent::Appeared { name }) => {
+                assert_eq!(name, "test.instance1")
+            }
+            event => panic!("Expected the player to appear, got {:?}", event),
+        }
+        // The snapshot comes first
+        match next_pending(&mut events).await {
+            Event::PropertiesChanged(changed) => {
+                assert_eq!(changed.player, "test.instance1");
+                assert_eq!(
+                    changed.properties.playback_status,
+                    Some(status.parse().unwrap())
+                );
+            }
+            event => panic!("Expected a snapshot, got {:?}", event),
+        }
+        assert_eq!(pending.players(), ["test.instance1"]);
+
+        let changed = common::props(vec![("PlaybackStatus", common::var("Stopped".to_string()))]);
+        common::emit(
+            &player,
+            common::properties_changed(":1.1", common::PLAYER_INTERFACE, changed, vec![]),
+        );
+        match next_pending(&mut events).await {
+            Event::PropertiesChanged(changed) => {
+                assert_eq!(
+                    changed.properties.playback_status,
+                    Some(PlaybackStatus::Stopped)
+                );
+            }
+            event => panic!("Expected a change, got {:?}", event),
+        }
+
+        player
+            .release_name("org.mpris.MediaPlayer2.test.instance1")
+            .await
+            .unwrap();
+        match next_pending(&mut events).await {
+            Event::PlayerLifecycle(LifecycleEvent::Vanished { name }) => {
+                assert_eq!(name, "test.instance1")
+            }
+            event => panic!("Expected the player to vanish, got {:?}", event),
+        }
+        assert!(pending.players().is_empty());
+    }
+}
+
+#[tokio::test]
+async fn test_cancelled_registration() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new(&conn);
+    let baseline = common::match_rules(&conn).await;
+
+    // The registration loses the race after asking the bus for its match
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counted = hits.clone();
+    tokio::select! {
+        biased;
+        _ = manager.add_callback(EventType::Seeked, move |_| {
+            counted.fetch_add(1, Ordering::SeqCst);
+            true
+        }) => panic!("Registered without waiting for the bus"),
+        _ = futures::future::ready(()) => {}
+    }
+    assert_eq!(common::match_rules(&conn).await, baseline);
+
+    // Dropped after the first of two matches was added
+    let event_types = [EventType::PropertiesChanged, EventType::Seeked];
+    let mut events = Box::pin(manager.stream(&event_types));
+    assert!(futures::poll!(events.as_mut()).is_pending());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(futures::poll!(events.as_mut()).is_pending());
+    drop(events);
+    assert_eq!(common::match_rules(&conn).await, baseline);
+
+    common::emit(&emitter, common::seeked(":1.1", 1_000_000i64));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+    assert!(manager.callbacks().is_empty());
+}
+
+#[tokio::test]
+async fn test_cancelled_removal() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = EventManager::new(&conn);
+    let baseline = common::match_rules(&conn).await;
+
+    let hits = Arc::new(AtomicUsize::new(0));
+    let mut guards = Vec::new();
+    for event_type in [
+        EventType::Seeked,
+        EventType::PropertiesChanged,
+        EventType::PlayerLifecycle,
+    ] {
+        let hits = hits.clone();
+        let guard = manager
+            .add_callback(event_type, move |_| {
+                hits.fetch_add(1, Ordering::SeqCst);
+                true
+            })
+            .await
+            .unwrap();
+        guards.push(guard);
+    }
+    assert_eq!(common::match_rules(&conn).await, baseline + 3);
+
+    // Cancelling removals once they started still removes every match
+    let mut removal = Box::pin(manager.remove_callback(guards[0].token()));
+    assert!(futures::poll!(removal.as_mut()).is_pending());
+    drop(removal);
+    assert_eq!(common::match_rules(&conn).await, baseline + 2);
+    let mut clearing = Box::pin(manager.clear_callbacks());
+    assert!(futures::poll!(clearing.as_mut()).is_pending());
+    drop(clearing);
+    assert_eq!(common::match_rules(&conn).await, baseline);
+
+    common::emit(&emitter, common::seeked(":1.1", 1_000_000i64));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_shared_rules() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let manager = &EventManager::new(&conn);
+    let baseline = common::match_rules(&conn).await;
+
+    let hits = &Arc::new(AtomicUsize::new(0));
+    let add = || async move {
+        let hits = hits.clone();
+        manager
+            .add_callback(EventType::Seeked, move |_| {
+                hits.fetch_add(1, Ordering::SeqCst);
+                true
+            })
+            .await
+            .unwrap()
+    };
+
+    // Identical rules are only added to the bus once
+    let first = add().await;
+    let second = add().await;
+    let mut subscription = manager.subscribe(EventType::Seeked).await.unwrap();
+    assert_eq!(common::match_rules(&conn).await, baseline + 1);
+
+    drop(first);
+    assert_eq!(common::match_rules(&conn).await, baseline + 1);
+    common::emit(&emitter, common::seeked(":1.1", 0i64));
+    let event = tokio::time::timeout(Duration::from_secs(5), subscription.recv());
+    assert_eq!(event.await.unwrap().event_type(), EventType::Seeked);
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    // A removal racing an addition leaves the rule in place
+    let (removed, third) = tokio::join!(manager.remove_callback(second.token()), add());
+    removed.unwrap();
+    assert_eq!(common::match_rules(&conn).await, baseline + 1);
+    common::emit(&emitter, common::seeked(":1.1", 0i64));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    // The rule goes with its last user, and comes back with the next
+    manager.clear_callbacks().await.unwrap();
+    assert_eq!(common::match_rules(&conn).await, baseline + 1);
+    drop(subscription);
+    assert_eq!(common::match_rules(&conn).await, baseline);
+    drop((second, third));
+    let fourth = add().await;
+    assert_eq!(common::match_rules(&conn).await, baseline + 1);
+    drop(fourth);
+    assert_eq!(common::match_rules(&conn).await, baseline);
+
+    // Each manager counts the rules it added, so one dropping a rule
+    // never takes away another's
+    let other = EventManager::new(&conn);
+    let fifth = add().await;
+    let sixth = other
+        .add_callback(EventType::Seeked, |_| true)
+        .await
+        .unwrap();
+    assert_eq!(common::match_rules(&conn).await, baseline + 2);
+    drop(fifth);
+    assert_eq!(common::match_rules(&conn).await, baseline + 1);
+    drop((sixth, other));
+    assert_eq!(common::match_rules(&conn).await, baseline);
+}
+
+#[tokio::test]
+async fn test_sender_cache() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let first = bus.connect_as("test").await;
+    let stranger = bus.connect();
+    let manager = EventManager::new(&conn);
+    let mut events = manager.all_player_events().await.unwrap();
+    let calls = common::bus_calls(&bus, &conn.unique_name()).await;
+
+    // Once names are tracked, senders are resolved without the bus,
+    // whether or not they are players
+    common::emit(&stranger, common::seeked(":1.1", 1i64));
+    common::emit(&first, common::seeked(":1.1", 2i64));
+    let next = tokio::time::timeout(Duration::from_secs(5), events.next());
+    let (player, event) = next.await.unwrap().unwrap();
+    assert_eq!(player, "test");
+    assert!(matches!(event, Event::Seeked(seeked) if seeked.position == Duration::from_micros(2)));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!calls
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|member| member == "GetNameOwner" || member == "ListNames"));
+
+    // After a restart, both the new owner and the old one, which
+    // took on another name, are attributed correctly
+    first
+        .release_name("org.mpris.MediaPlayer2.test")
+        .await
+        .unwrap();
+    first
+        .request_name("org.mpris.MediaPlayer2.other", false, true, true)
+        .await
+        .unwrap();
+    let second = bus.connect_as("test").await;
+    common::emit(&first, common::seeked(":1.1", 10i64));
+    common::emit(&second, common::seeked(":1.1", 20i64));
+
+    let mut seeks = Vec::new();
+    while seeks.len() < 2 {
+        let next = tokio::time::timeout(Duration::from_secs(5), events.next());
+        if let (player, Event::Seeked(seeked)) = next.await.unwrap().unwrap() {
+            seeks.push((seeked.position.as_micros(), player));
+        }
+    }
+    seeks.sort();
+    assert_eq!(seeks, [(10, "other".to_string()), (20, "test".to_string())]);
+}
+
+#[tokio::test]
+async fn test_player_pool() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let vlc = bus.connect_as("vlc").await;
+    let manager = EventManager::new(&conn);
+    let baseline = common::match_rules(&conn).await;
+    let pool = PlayerPool::new(&manager).await.unwrap();
+    let mut changes = pool.changes();
+    assert_eq!(pool.names(), vec!["vlc"]);
+
+    async fn next_change(
+        changes: &mut (impl futures::Stream<Item = PoolChange> + Unpin),
+    ) -> PoolChange {
+        let next = tokio::time::timeout(Duration::from_secs(5), changes.next());
+        next.await.unwrap().unwrap()
+    }
+
+    // Players are followed without being polled, from any clone
+    let shared = pool.clone();
+    let mpv = bus.connect();
+    mpv.request_name("org.mpris.MediaPlayer2.mpv", true, true, true)
+        .await
+        .unwrap();
+    assert_eq!(
+        next_change(&mut changes).await,
+        PoolChange::Added("mpv".into())
+    );
+    assert_eq!(shared.names(), vec!["mpv", "vlc"]);
+    let handle = shared.get("mpv").unwrap();
+    assert_eq!(handle.unique_name(), Some(&*mpv.unique_name()));
+
+    // A player taken over by another connection is reported, and its
+    // handles are pinned to the new owner
+    let restarted = bus.connect_as("mpv").await;
+    assert_eq!(
+        next_change(&mut changes).await,
+        PoolChange::Replaced("mpv".into())
+    );
+    let handle = pool.get("mpv").unwrap();
+    assert_eq!(handle.unique_name(), Some(&*restarted.unique_name()));
+
+    vlc.release_name("org.mpris.MediaPlayer2.vlc")
+        .await
+        .unwrap();
+    assert_eq!(
+        next_change(&mut changes).await,
+        PoolChange::Removed("vlc".into())
+    );
+    let names: Vec<_> = pool.iter().map(|player| player.name).collect();
+    assert_eq!(names, vec!["mpv"]);
+
+    // The pool stops following the bus once every clone is dropped
+    drop((pool, shared));
+    assert_eq!(common::match_rules(&conn).await, baseline);
+    assert!(changes.next().await.is_none());
+}
+
+#[tokio::test]
+async fn test_exclusive_playback() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let (vlc_conn, mpv_conn, cmus_conn) = (bus.connect(), bus.connect(), bus.connect());
+    let vlc = MockPlayer::register("vlc", &vlc_conn).await.unwrap();
+    let mpv = MockPlayer::register("mpv", &mpv_conn).await.unwrap();
+    let cmus = MockPlayer::register("cmus", &cmus_conn).await.unwrap();
+    let report = |player: &MockPlayer<'_>, status: &str| {
+        player.set_property("PlaybackStatus", status.to_string());
+        player.emit_properties_changed(&["PlaybackStatus"]).unwrap();
+    };
+
+    let manager = EventManager::new(&conn);
+    let options = ExclusiveOptions {
+        settle: Duration::from_millis(100),
+        ..ExclusiveOptions::default()
+    };
+    let mut exclusive = ExclusivePlayback::new(&manager, options).await.unwrap();
+
+    // The players start one after the other, and cmus only blips
+    // while buffering, which doesn't count
+    let start = async {
+        report(&vlc, "Playing");
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        report(&cmus, "Playing");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        report(&cmus, "Paused");
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        report(&mpv, "Playing");
+    };
+    let next = tokio::time::timeout(Duration::from_secs(5), exclusive.next());
+    let (enforcement, ()) = tokio::join!(next, start);
+    let enforcement = enforcement.unwrap().unwrap();
+    assert_eq!(enforcement.paused, "vlc");
+    assert_eq!(enforcement.playing, "mpv");
+    assert!(enforcement.result.is_ok());
+
+    // Its own pause being reported doesn't set off anything else
+    report(&vlc, "Paused");
+    let next = tokio::time::timeout(Duration::from_millis(400), exclusive.next());
+    assert!(next.await.is_err());
+    assert_eq!(vlc.calls_to("Pause"), 1);
+    assert_eq!(mpv.calls_to("Pause") + cmus.calls_to("Pause"), 0);
+    let playing: Vec<_> = [&vlc, &mpv, &cmus]
+        .iter()
+        .filter(|player| {
+            player.property("PlaybackStatus") == Some(pris::Value::Str("Playing".into()))
+        })
+        .map(|player| player.name().to_string())
+        .collect();
+    assert_eq!(playing, ["mpv"]);
+
+    // Nothing is paused while suspended
+    exclusive.suspend();
+    report(&cmus, "Playing");
+    let next = tokio::time::timeout(Duration::from_millis(400), exclusive.next());
+    assert!(next.await.is_err());
+    assert_eq!(mpv.calls_to("Pause"), 0);
+}
+
+#[tokio::test]
+async fn test_controller() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let (vlc_conn, mpv_conn) = (bus.connect(), bus.connect());
+    let report = |player: &MockPlayer<'_>, status: &str| {
+        player.set_property("PlaybackStatus", status.to_string());
+        player.emit_properties_changed(&["PlaybackStatus"]).unwrap();
+    };
+
+    // Skips whatever else changed in between
+    async fn wait_for(
+        events: &mut (impl futures::Stream<Item = ControllerEvent> + Unpin),
+        wanted: impl Fn(&ControllerEvent) -> bool,
+    ) {
+        let found = async {
+            while let Some(event) = events.next().await {
+                if wanted(&event) {
+                    return;
+                }
+            }
+            panic!("The events ended");
+        };
+        tokio::time::timeout(Duration::from_secs(5), found)
+            .await
+            .unwrap();
+    }
+
+    let baseline = common::match_rules(&conn).await;
+    let (controller, feed) = Controller::new(&conn).await.unwrap();
+    let controlling = async {
+        let mut events = controller.events();
+        assert!(controller.players().is_empty());
+        assert_eq!(controller.current(), None);
+        assert!(matches!(
+            controller.play().await,
+            Err(pris::Error::NotFound { .. })
+        ));
+
+        // The first player to start becomes the current one
+        let vlc = MockPlayer::register("vlc", &vlc_conn).await.unwrap();
+        wait_for(
+            &mut events,
+            |event| matches!(event, ControllerEvent::PlayerAdded(name) if name == "vlc"),
+        )
+        .await;
+        wait_for(
+            &mut events,
+            |event| matches!(event, ControllerEvent::CurrentChanged(Some(name)) if name == "vlc"),
+        )
+        .await;
+        report(&vlc, "Playing");
+        wait_for(&mut events, |event| {
+            matches!(
+                event,
+                ControllerEvent::StateChanged { player, properties }
+                    if player == "vlc" && properties.playback_status == Some(PlaybackStatus::Playing)
+            )
+        })
+        .await;
+        assert_eq!(controller.status("vlc"), Some(PlaybackStatus::Playing));
+        controller.pause().await.unwrap();
+        assert_eq!(vlc.calls_to("Pause"), 1);
+
+        // A player that starts playing afterwards takes over, and clones
+        // command the same one
+        let shared = controller.clone();
+        let mpv = MockPlayer::register("mpv", &mpv_conn).await.unwrap();
+        report(&mpv, "Playing");
+        wait_for(
+            &mut events,
+            |event| matches!(event, ControllerEvent::CurrentChanged(Some(name)) if name == "mpv"),
+        )
+        .await;
+        assert_eq!(shared.players(), vec!["mpv", "vlc"]);
+        shared.next().await.unwrap();
+        assert_eq!(mpv.calls_to("Next"), 1);
+        assert_eq!(vlc.calls_to("Next"), 0);
+
+        mpv.insert_metadata("xesam:title", "Second".to_string());
+        mpv.emit_properties_changed(&["Metadata"]).unwrap();
+        wait_for(&mut events, |event| {
+            matches!(
+                event,
+                ControllerEvent::TrackChanged { player, change: TrackChange::Track(_) }
+                    if player == "mpv"
+            )
+        })
+        .await;
+
+        // A selected player stays current, until it quits
+        assert!(matches!(
+            controller.select("nothing"),
+            Err(pris::Error::UnknownPlayer { .. })
+        ));
+        controller.select("vlc").unwrap();
+        assert_eq!(controller.current().as_deref(), Some("vlc"));
+        controller.play_pause().await.unwrap();
+        assert_eq!(vlc.calls_to("PlayPause"), 1);
+        drop(vlc);
+        wait_for(
+            &mut events,
+            |event| matches!(event, ControllerEvent::PlayerRemoved(name) if name == "vlc"),
+        )
+        .await;
+        wait_for(
+            &mut events,
+            |event| matches!(event, ControllerEvent::CurrentChanged(Some(name)) if name == "mpv"),
+        )
+        .await;
+        assert_eq!(controller.players(), vec!["mpv"]);
+
+        // Shutting down removes every match, and ends the events
+        controller.shutdown().await.unwrap();
+        assert_eq!(common::match_rules(&conn).await, baseline);
+        let rest = tokio::time::timeout(Duration::from_secs(5), events.collect::<Vec<_>>());
+        assert!(rest.await.is_ok());
+    };
+    futures::future::join(feed, controlling).await;
+}
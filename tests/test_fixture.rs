@@ -0,0 +1,94 @@
+mod common;
+
+use futures::StreamExt;
+use pris::{
+    testing::{Fixture, MockPlayer, Recorder},
+    Event, LifecycleEvent, PlaybackStatus, Player,
+};
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_record_and_replay() {
+    let bus = common::TestBus::new();
+    let vlc_conn = bus.connect();
+    let vlc = MockPlayer::register("vlc", &vlc_conn).await.unwrap();
+
+    let conn = bus.connect();
+    let recorder = Recorder::start(&conn).await.unwrap();
+    let player = Player::try_new("vlc", &conn).await.unwrap();
+    let state = recorder.snapshot(&player).await.unwrap();
+    assert_eq!(state.playback_status, Some(PlaybackStatus::Stopped));
+
+    vlc.set_property("PlaybackStatus", "Playing".to_string());
+    vlc.emit_properties_changed(&["PlaybackStatus"]).unwrap();
+    vlc.emit_seeked(Duration::from_secs(42)).unwrap();
+    let spotify_conn = bus.connect();
+    let spotify = MockPlayer::register("spotify", &spotify_conn)
+        .await
+        .unwrap();
+    spotify.set_property("Volume", 0.5);
+    spotify.emit_properties_changed(&["Volume"]).unwrap();
+    assert!(common::eventually(|| async { recorder.len() == 5 }).await);
+
+    // Unique names are scrubbed, and the text reads back the same
+    let text = recorder.finish().to_string();
+    assert!(text.starts_with("pris-fixture 1\nowner vlc :fixture.1\n"));
+    assert!(!text.contains(":1."));
+    let fixture: Fixture = text.parse().unwrap();
+    assert_eq!(fixture.len(), 5);
+    assert_eq!(fixture.to_string(), text);
+
+    let events = fixture.events();
+    assert_eq!(events.len(), 4);
+    match &events[0] {
+        Event::PropertiesChanged(changed) => {
+            assert_eq!(changed.player, "vlc");
+            assert_eq!(
+                changed.properties.playback_status,
+                Some(PlaybackStatus::Playing)
+            );
+        }
+        other => panic!("expected a PropertiesChanged event, got {:?}", other),
+    }
+    match &events[1] {
+        Event::Seeked(seeked) => assert_eq!(seeked.position, Duration::from_secs(42)),
+        other => panic!("expected a Seeked event, got {:?}", other),
+    }
+    match &events[2] {
+        Event::PlayerLifecycle(lifecycle) => assert_eq!(
+            lifecycle,
+            &LifecycleEvent::Appeared {
+                name: "spotify".to_string()
+            }
+        ),
+        other => panic!("expected a PlayerLifecycle event, got {:?}", other),
+    }
+    // Resolved through the recorded NameOwnerChanged
+    match &events[3] {
+        Event::PropertiesChanged(changed) => {
+            assert_eq!(changed.player, "spotify");
+            assert_eq!(changed.properties.volume, Some(0.5));
+        }
+        other => panic!("expected a PropertiesChanged event, got {:?}", other),
+    }
+
+    let states = fixture.states();
+    assert_eq!(states.len(), 1);
+    assert_eq!(states[0].player, "vlc");
+    assert_eq!(states[0].playback_status, Some(PlaybackStatus::Playing));
+    assert_eq!(states[0].position, Some(Duration::from_secs(42)));
+
+    let replayed =
+        tokio::time::timeout(Duration::from_secs(5), fixture.replay().collect::<Vec<_>>())
+            .await
+            .unwrap();
+    assert_eq!(replayed.len(), 4);
+}
+
+#[test]
+fn test_malformed_fixture() {
+    assert!("owner vlc :fixture.1".parse::<Fixture>().is_err());
+    assert!("pris-fixture 1\nsignal 0 zz".parse::<Fixture>().is_err());
+    assert!("pris-fixture 1\nsignal 0 00".parse::<Fixture>().is_err());
+    assert!("pris-fixture 1\n".parse::<Fixture>().unwrap().is_empty());
+}
@@ -1,9 +1,17 @@
-use pris::{self, Player};
+mod common;
+
+use dbus::{
+    arg::{RefArg, Variant},
+    channel::MatchingReceiver,
+    nonblock::SyncConnection,
+};
+use pris::{self, testing::MockPlayer, Player, PlayerFinder};
+use std::sync::{Arc, Mutex};
 
 #[tokio::test]
 async fn test_methods() -> Result<(), Box<dyn std::error::Error>> {
     let conn = pris::get_connection();
-    let mut player = Player::try_new("cmus", &conn).await?;
+    let player = Player::try_new("cmus", &conn).await?;
     // player
     //     .seek_reverse(std::time::Duration::from_secs(15))
     //     .await?;
@@ -17,3 +25,1615 @@ async fn test_methods() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_player_gone() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let vlc = bus.connect_as("vlc").await;
+    common::serve_error(&vlc, "org.freedesktop.DBus.Error.ServiceUnknown");
+    let mpv = bus.connect_as("mpv").await;
+    common::serve_properties(&mpv, common::props(vec![]));
+
+    let injected = Player::try_new("vlc", &conn).await.unwrap();
+    let result = injected.next().await;
+    assert!(matches!(result, Err(pris::Error::PlayerGone { player, .. }) if player == "vlc"));
+
+    // Other errors from the player are passed on as they are
+    let player = Player::try_new("mpv", &conn).await.unwrap();
+    assert!(matches!(player.next().await, Err(pris::Error::DBus(_))));
+
+    mpv.release_name("org.mpris.MediaPlayer2.mpv")
+        .await
+        .unwrap();
+    let result = player.get_metadata().await;
+    assert!(matches!(result, Err(pris::Error::PlayerGone { player, .. }) if player == "mpv"));
+}
+
+#[tokio::test]
+async fn test_player_timeout() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let vlc = bus.connect_as("vlc").await;
+    common::serve_error(&vlc, "org.freedesktop.DBus.Error.NoReply");
+
+    let mut player = Player::try_new("vlc", &conn).await.unwrap();
+    match player.play_pause().await {
+        Err(pris::Error::Timeout {
+            player,
+            operation,
+            limit,
+            source,
+        }) => {
+            assert_eq!(player.as_deref(), Some("vlc"));
+            assert_eq!(operation, "PlayPause");
+            assert_eq!(limit, std::time::Duration::from_secs(5));
+            assert_eq!(
+                source.unwrap().name(),
+                Some("org.freedesktop.DBus.Error.NoReply")
+            );
+        }
+        other => panic!("Expected a timeout, got {:?}", other),
+    }
+
+    let result = player.get_property::<String>("LoopStatus").await;
+    assert!(matches!(
+        result,
+        Err(pris::Error::Timeout { operation, .. }) if operation == "LoopStatus"
+    ));
+
+    // The timeout can be changed per player
+    player.set_timeout(std::time::Duration::from_secs(2));
+    assert!(matches!(
+        player.next().await,
+        Err(pris::Error::Timeout { limit, .. }) if limit == std::time::Duration::from_secs(2)
+    ));
+}
+
+#[tokio::test]
+async fn test_dbus_error_name() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let spotify = bus.connect_as("spotify").await;
+    common::serve_error(&spotify, "org.mpris.MediaPlayer2.Player.Error.Restricted");
+    let mpv = bus.connect_as("mpv").await;
+    common::serve_error(&mpv, "org.freedesktop.DBus.Error.NotSupported");
+
+    let mut player = Player::try_new("spotify", &conn).await.unwrap();
+    for error in [
+        pris::methods::next(&player).await.unwrap_err(),
+        player.next().await.unwrap_err(),
+    ] {
+        assert_eq!(
+            error.dbus_name(),
+            Some("org.mpris.MediaPlayer2.Player.Error.Restricted")
+        );
+        assert_eq!(error.dbus_message(), Some("Injected by the test player"));
+    }
+
+    // Whatever the error was classified into
+    player.set_retry_policy(Some(pris::RetryPolicy {
+        base_delay: std::time::Duration::from_millis(1),
+        retryable: |_| true,
+        non_idempotent: true,
+        ..Default::default()
+    }));
+    let error = player.next().await.unwrap_err();
+    assert!(matches!(error, pris::Error::Retried { .. }));
+    assert_eq!(
+        error.dbus_name(),
+        Some("org.mpris.MediaPlayer2.Player.Error.Restricted")
+    );
+
+    let player = Player::try_new("mpv", &conn).await.unwrap();
+    let error = player.pause().await.unwrap_err();
+    assert!(matches!(error, pris::Error::UnsupportedOperation { .. }));
+    assert_eq!(
+        error.dbus_name(),
+        Some("org.freedesktop.DBus.Error.NotSupported")
+    );
+    assert!(std::error::Error::source(&error).is_some());
+
+    mpv.release_name("org.mpris.MediaPlayer2.mpv")
+        .await
+        .unwrap();
+    let error = player.get_metadata().await.unwrap_err();
+    assert!(matches!(error, pris::Error::PlayerGone { .. }));
+    assert_eq!(
+        error.dbus_name(),
+        Some("org.freedesktop.DBus.Error.ServiceUnknown")
+    );
+    assert!(pris::Error::Disconnected.dbus_name().is_none());
+}
+
+#[tokio::test]
+async fn test_unknown_property() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    // As spelled by GDBus
+    let vlc = bus.connect_as("vlc").await;
+    common::serve_error(&vlc, "org.freedesktop.DBus.Error.InvalidArgs");
+    // As spelled by dbus-daemon
+    let mpv = bus.connect_as("mpv").await;
+    common::serve_error(&mpv, "org.freedesktop.DBus.Error.UnknownProperty");
+
+    let player = Player::try_new("vlc", &conn).await.unwrap();
+    let error = player.get_property::<f64>("Volme").await.unwrap_err();
+    assert!(matches!(
+        &error,
+        pris::Error::UnknownProperty { player, property, .. }
+            if player == "vlc" && property == "Volme"
+    ));
+    assert!(std::error::Error::source(&error).is_some());
+    assert_eq!(
+        error.to_string(),
+        "The player vlc has no property Volme. Did you mean Volume?"
+    );
+
+    // A standard property rejected on writing is about the value
+    let result = player.set_property("Volume", 0.5).await;
+    assert!(matches!(result, Err(pris::Error::DBus(_))));
+
+    let player = Player::try_new("mpv", &conn).await.unwrap();
+    let error = player
+        .set_property("xesam:whatever", true)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        &error,
+        pris::Error::UnknownProperty { player, .. } if player == "mpv"
+    ));
+    assert_eq!(
+        error.to_string(),
+        "The player mpv has no property xesam:whatever."
+    );
+}
+
+#[tokio::test]
+async fn test_type_mismatch() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let spotify = bus.connect_as("spotify").await;
+    common::serve_properties(
+        &spotify,
+        common::props(vec![("Volume", Variant(Box::new(0.5f64)))]),
+    );
+
+    let player = Player::try_new("spotify", &conn).await.unwrap();
+    let error = player.get_property::<String>("Volume").await.unwrap_err();
+    assert!(matches!(
+        &error,
+        pris::Error::TypeMismatch { player, property, actual, .. }
+            if player == "spotify" && property == "Volume" && actual == "d"
+    ));
+    assert_eq!(
+        error.to_string(),
+        "Expected alloc::string::String, got d for property Volume on spotify."
+    );
+
+    // The right type still reads fine
+    assert_eq!(player.get_property::<f64>("Volume").await.unwrap(), 0.5);
+}
+
+#[tokio::test]
+async fn test_no_active_track() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let vlc = bus.connect_as("vlc").await;
+    let served = common::serve_properties(
+        &vlc,
+        common::props(vec![("Metadata", common::var(common::props(vec![])))]),
+    );
+
+    let player = Player::try_new("vlc", &conn).await.unwrap();
+    let result = player.seek(std::time::Duration::from_secs(5)).await;
+    assert!(matches!(result, Err(pris::Error::NoActiveTrack(name)) if name == "vlc"));
+
+    served.lock().unwrap().insert(
+        "Metadata".to_string(),
+        common::var(common::props(vec![(
+            "mpris:trackid",
+            common::var(dbus::Path::from(
+                "/org/mpris/MediaPlayer2/TrackList/NoTrack",
+            )),
+        )])),
+    );
+    let result = player.set_position(std::time::Duration::ZERO).await;
+    assert!(matches!(result, Err(pris::Error::NoActiveTrack(name)) if name == "vlc"));
+}
+
+#[tokio::test]
+async fn test_retry_policy() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let vlc = bus.connect_as("vlc").await;
+    common::serve_error(&vlc, "org.freedesktop.DBus.Error.NoReply");
+
+    let mut player = Player::try_new("vlc", &conn).await.unwrap();
+    let policy = pris::RetryPolicy {
+        base_delay: std::time::Duration::from_millis(1),
+        ..Default::default()
+    };
+    player.set_retry_policy(Some(policy));
+    assert_eq!(player.retry_policy().unwrap().max_attempts, 3);
+
+    let result = player.get_property::<String>("LoopStatus").await;
+    match result {
+        Err(pris::Error::Retried { attempts, source }) => {
+            assert_eq!(attempts, 3);
+            assert!(matches!(*source, pris::Error::Timeout { .. }));
+        }
+        other => panic!("Expected retries, got {:?}", other),
+    }
+
+    // Calls that aren't safe to repeat are made once
+    let result = player.play_pause().await;
+    assert!(matches!(result, Err(pris::Error::Timeout { .. })));
+
+    // As are calls failing for good
+    let mpv = bus.connect_as("mpv").await;
+    common::serve_error(&mpv, "org.freedesktop.DBus.Error.UnknownMethod");
+    let mut player = Player::try_new("mpv", &conn).await.unwrap();
+    player.set_retry_policy(Some(pris::RetryPolicy {
+        non_idempotent: true,
+        ..policy
+    }));
+    assert!(matches!(player.next().await, Err(pris::Error::DBus(_))));
+}
+
+#[tokio::test]
+async fn test_metadata_nothing_playing() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let vlc = bus.connect_as("vlc").await;
+    let served = common::serve_properties(
+        &vlc,
+        common::props(vec![("Metadata", common::var(common::props(vec![])))]),
+    );
+
+    let player = Player::try_new("vlc", &conn).await.unwrap();
+    assert!(player.metadata().await.unwrap().is_none());
+
+    let no_track = common::props(vec![(
+        "mpris:trackid",
+        common::var(dbus::Path::from(
+            "/org/mpris/MediaPlayer2/TrackList/NoTrack",
+        )),
+    )]);
+    served
+        .lock()
+        .unwrap()
+        .insert("Metadata".to_string(), common::var(no_track));
+    assert!(player.metadata().await.unwrap().is_none());
+
+    let track = common::props(vec![(
+        "mpris:trackid",
+        common::var(dbus::Path::from("/track/1")),
+    )]);
+    served
+        .lock()
+        .unwrap()
+        .insert("Metadata".to_string(), common::var(track));
+    let metadata = player.metadata().await.unwrap().unwrap();
+    assert!(!pris::is_no_track(&metadata));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_concurrent_gets() {
+    let bus = common::TestBus::new();
+    let conn: &'static _ = Box::leak(Box::new(bus.connect()));
+    let vlc = bus.connect_as("vlc").await;
+    common::serve_properties(
+        &vlc,
+        common::props(vec![
+            ("Volume", common::var(0.5f64)),
+            ("LoopStatus", common::var("Track".to_string())),
+        ]),
+    );
+
+    let player = std::sync::Arc::new(Player::try_new("vlc", conn).await.unwrap());
+    let volume = tokio::spawn({
+        let player = player.clone();
+        async move { player.get_property::<f64>("Volume").await }
+    });
+    let loop_status = tokio::spawn({
+        let player = player.clone();
+        async move { player.get_property::<String>("LoopStatus").await }
+    });
+
+    assert_eq!(volume.await.unwrap().unwrap(), 0.5);
+    assert_eq!(loop_status.await.unwrap().unwrap(), "Track");
+}
+
+#[tokio::test]
+async fn test_play_pause_fallback() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    // Rejects PlayPause, like the player serving properties only
+    let vlc = bus.connect_as("vlc").await;
+    let calls = common::serve_methods(&vlc, &["Play", "Pause"]);
+    let served = common::serve_properties(
+        &vlc,
+        common::props(vec![
+            ("PlaybackStatus", common::var("Paused".to_string())),
+            ("CanPause", common::var(true)),
+        ]),
+    );
+
+    let player = Player::try_new("vlc", &conn).await.unwrap();
+    player.play_pause().await.unwrap();
+    served.lock().unwrap().insert(
+        "PlaybackStatus".to_string(),
+        common::var("Playing".to_string()),
+    );
+    player.play_pause().await.unwrap();
+    assert_eq!(*calls.lock().unwrap(), ["Play", "Pause"]);
+
+    // Pausing is refused up front when the player says it can't
+    served
+        .lock()
+        .unwrap()
+        .insert("CanPause".to_string(), common::var(false));
+    let result = player.play_pause().await;
+    assert!(matches!(
+        result,
+        Err(pris::Error::UnsupportedOperation { .. })
+    ));
+    assert_eq!(calls.lock().unwrap().len(), 2);
+
+    // Players implementing neither are told apart from other failures
+    let mpv = bus.connect_as("mpv").await;
+    common::serve_error(&mpv, "org.freedesktop.DBus.Error.NotSupported");
+    let player = Player::try_new("mpv", &conn).await.unwrap();
+    let result = player.pause().await;
+    assert!(matches!(
+        result,
+        Err(pris::Error::UnsupportedOperation { .. })
+    ));
+    assert!(matches!(
+        player.play_pause().await,
+        Err(pris::Error::DBus(_))
+    ));
+}
+
+/// Serves a player at 10 seconds into a track, moving on `Seek`, and
+/// on `SetPosition` unless it `ignores` it. Returns the calls made.
+fn serve_positioned(conn: &SyncConnection, ignores: bool) -> Arc<Mutex<Vec<String>>> {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let recorded = calls.clone();
+    let metadata = common::props(vec![(
+        "mpris:trackid",
+        common::var(dbus::Path::from("/track/1")),
+    )]);
+    common::serve_player(
+        conn,
+        common::props(vec![
+            ("Metadata", common::var(metadata)),
+            ("Position", common::var(10_000_000i64)),
+        ]),
+        move |msg, properties| {
+            let member = msg.member()?.to_string();
+            let position = match member.as_str() {
+                "SetPosition" if ignores => None,
+                "SetPosition" => Some(msg.read2::<dbus::Path, i64>().unwrap().1),
+                "Seek" => {
+                    let current = properties["Position"].0.as_i64().unwrap();
+                    Some(current + msg.read1::<i64>().unwrap())
+                }
+                _ => return None,
+            };
+            if let Some(position) = position {
+                properties.insert("Position".to_string(), common::var(position));
+            }
+            recorded.lock().unwrap().push(member);
+            Some(msg.method_return())
+        },
+    );
+
+    calls
+}
+
+#[tokio::test]
+async fn test_set_position_with_fallback() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let vlc = bus.connect_as("vlc").await;
+    let vlc_calls = serve_positioned(&vlc, false);
+    let spotify = bus.connect_as("spotify").await;
+    let spotify_calls = serve_positioned(&spotify, true);
+
+    let player = Player::try_new("vlc", &conn).await.unwrap();
+    let minute = std::time::Duration::from_secs(60);
+    let strategy = player.set_position_with_fallback(minute).await.unwrap();
+    assert_eq!(strategy, pris::PositionStrategy::SetPosition);
+    assert_eq!(*vlc_calls.lock().unwrap(), ["SetPosition"]);
+
+    let player = Player::try_new("spotify", &conn).await.unwrap();
+    let strategy = player.set_position_with_fallback(minute).await.unwrap();
+    assert_eq!(strategy, pris::PositionStrategy::Seek);
+    assert_eq!(*spotify_calls.lock().unwrap(), ["SetPosition", "Seek"]);
+    let position: i64 = player.get_property("Position").await.unwrap();
+    assert_eq!(position, 60_000_000);
+}
+
+#[tokio::test]
+async fn test_set_position_nearby() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let vlc = bus.connect_as("vlc").await;
+    let vlc_calls = serve_positioned(&vlc, false);
+    let spotify = bus.connect_as("spotify").await;
+    let spotify_calls = serve_positioned(&spotify, true);
+    let ahead = std::time::Duration::from_secs(11);
+
+    // A second ahead is within the tolerance, but only counts as
+    // reached if the player moved there
+    let player = Player::try_new("vlc", &conn).await.unwrap();
+    let strategy = player.set_position_with_fallback(ahead).await.unwrap();
+    assert_eq!(strategy, pris::PositionStrategy::SetPosition);
+    assert_eq!(*vlc_calls.lock().unwrap(), ["SetPosition"]);
+
+    let player = Player::try_new("spotify", &conn).await.unwrap();
+    let strategy = player.set_position_with_fallback(ahead).await.unwrap();
+    assert_eq!(strategy, pris::PositionStrategy::Seek);
+    assert_eq!(*spotify_calls.lock().unwrap(), ["SetPosition", "Seek"]);
+    let position: i64 = player.get_property("Position").await.unwrap();
+    assert_eq!(position, 11_000_000);
+
+    // Where the player already is needs no moving
+    spotify_calls.lock().unwrap().clear();
+    let strategy = player.set_position_with_fallback(ahead).await.unwrap();
+    assert_eq!(strategy, pris::PositionStrategy::SetPosition);
+    assert_eq!(*spotify_calls.lock().unwrap(), ["SetPosition"]);
+}
+
+#[tokio::test]
+async fn test_time_boundaries() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let vlc = bus.connect_as("vlc").await;
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let recorded = sent.clone();
+    let metadata = common::props(vec![
+        ("mpris:trackid", common::var(dbus::Path::from("/track/1"))),
+        ("mpris:length", common::var(-1i64)),
+    ]);
+    let served = common::serve_player(
+        &vlc,
+        common::props(vec![
+            ("Metadata", common::var(metadata)),
+            ("Position", common::var(u64::MAX)),
+        ]),
+        move |msg, _| {
+            let micros = match msg.member()?.to_string().as_str() {
+                "Seek" => msg.read1::<i64>().unwrap(),
+                "SetPosition" => msg.read2::<dbus::Path, i64>().unwrap().1,
+                _ => return None,
+            };
+            recorded.lock().unwrap().push(micros);
+            Some(msg.method_return())
+        },
+    );
+
+    let player = Player::try_new("vlc", &conn).await.unwrap();
+    assert_eq!(
+        player.get_position().await.unwrap(),
+        std::time::Duration::from_micros(u64::MAX)
+    );
+    served
+        .lock()
+        .unwrap()
+        .insert("Position".to_string(), common::var(-250i64));
+    assert_eq!(
+        player.get_position().await.unwrap(),
+        std::time::Duration::ZERO
+    );
+
+    // Sending saturates rather than wrapping around
+    player.seek(std::time::Duration::MAX).await.unwrap();
+    player.seek_reverse(std::time::Duration::MAX).await.unwrap();
+    player
+        .set_position(std::time::Duration::ZERO)
+        .await
+        .unwrap();
+    player.set_position(std::time::Duration::MAX).await.unwrap();
+    assert_eq!(*sent.lock().unwrap(), [i64::MAX, -i64::MAX, 0, i64::MAX]);
+
+    served
+        .lock()
+        .unwrap()
+        .insert("Position".to_string(), common::var("0:00".to_string()));
+    assert!(matches!(
+        player.get_position().await,
+        Err(pris::Error::TypeMismatch { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_volume_ceiling() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let mpv = bus.connect_as("mpv").await;
+    let set = Arc::new(Mutex::new(Vec::new()));
+    let recorded = set.clone();
+    let served = common::serve_player(
+        &mpv,
+        common::props(vec![("Volume", common::var(0.5f64))]),
+        move |msg, properties| {
+            if &*msg.member()? != "Set" {
+                return None;
+            }
+            let (_, name, value): (String, String, Variant<Box<dyn RefArg>>) = msg.read3().unwrap();
+            recorded.lock().unwrap().push(value.0.as_f64().unwrap());
+            properties.insert(name, value);
+            Some(msg.method_return())
+        },
+    );
+
+    let mut player = Player::try_new("mpv", &conn).await.unwrap();
+    assert_eq!(player.volume_ceiling(), 1.0);
+    assert_eq!(player.set_volume(1.5).await.unwrap(), 1.0);
+    assert_eq!(player.adjust_volume(-2.0).await.unwrap(), 0.0);
+    assert!(matches!(
+        player.set_volume(f64::NAN).await,
+        Err(pris::Error::InvalidArgument(_))
+    ));
+
+    player.set_volume_ceiling(1.5);
+    assert_eq!(player.set_volume(1.3).await.unwrap(), 1.3);
+    assert_eq!(player.adjust_volume(0.5).await.unwrap(), 1.5);
+
+    // The getter reports what the player has, even above the ceiling
+    served
+        .lock()
+        .unwrap()
+        .insert("Volume".to_string(), common::var(2.0f64));
+    assert_eq!(player.get_volume().await.unwrap(), 2.0);
+
+    set.lock().unwrap().clear();
+    player
+        .fade_volume(0.0, std::time::Duration::from_millis(200))
+        .await
+        .unwrap();
+    let steps = set.lock().unwrap().clone();
+    assert!(steps.len() > 1);
+    assert!(steps.windows(2).all(|pair| pair[0] > pair[1]));
+    assert!(steps.iter().all(|&volume| volume <= 1.5));
+    assert_eq!(steps.last(), Some(&0.0));
+}
+
+fn assert_send<T: Send>(_: T) {}
+
+#[test]
+fn test_futures_are_send() {
+    // Only needs to compile, so that futures stay spawnable
+    fn check(player: &Player<'_>, conn: &SyncConnection) {
+        assert_send(Player::try_new("vlc", conn));
+        assert_send(player.next());
+        assert_send(player.previous());
+        assert_send(player.pause());
+        assert_send(player.play());
+        assert_send(player.play_pause());
+        assert_send(player.stop());
+        assert_send(player.get_metadata());
+        assert_send(player.metadata());
+        assert_send(player.get_state());
+        assert_send(player.get_property::<String>("LoopStatus"));
+        assert_send(player.set_property("Volume", 0.5));
+        assert_send(player.get_position());
+        assert_send(player.seek(std::time::Duration::from_secs(5)));
+        assert_send(player.seek_reverse(std::time::Duration::from_secs(5)));
+        assert_send(player.set_position(std::time::Duration::ZERO));
+        assert_send(player.set_position_with_fallback(std::time::Duration::ZERO));
+        assert_send(player.get_volume());
+        assert_send(player.set_volume(0.5));
+        assert_send(player.adjust_volume(0.1));
+        assert_send(player.fade_volume(0.0, std::time::Duration::from_secs(1)));
+        assert_send(player.open_uri("file:///track.flac"));
+        assert_send(pris::methods::get_property::<f64>(player, "Volume"));
+        assert_send(pris::get_all_players(conn));
+        assert_send(pris::resolve_sender(":1.42", conn));
+    }
+    let _ = check;
+}
+
+#[tokio::test]
+async fn test_unknown_player() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+
+    // With nothing running, there is nothing to suggest
+    let e = Player::try_new("spotfy", &conn).await.err().unwrap();
+    match &e {
+        pris::Error::UnknownPlayer {
+            requested,
+            available,
+            total,
+            suggestion,
+        } => {
+            assert_eq!(requested, "spotfy");
+            assert!(available.is_empty());
+            assert_eq!(*total, 0);
+            assert_eq!(*suggestion, None);
+        }
+        other => panic!("expected an unknown player, got {:?}", other),
+    }
+    assert_eq!(
+        e.to_string(),
+        "The player spotfy is not on the bus, as no players are running."
+    );
+
+    let players = bus.connect();
+    for name in &["spotify", "mpd", "firefox.instance_1_23"] {
+        players
+            .request_name(
+                format!("org.mpris.MediaPlayer2.{}", name),
+                false,
+                false,
+                true,
+            )
+            .await
+            .unwrap();
+    }
+    let e = Player::try_new("spotfy", &conn).await.err().unwrap();
+    assert_eq!(
+        e.to_string(),
+        "The player spotfy is not on the bus. Did you mean spotify? \
+         Available players: firefox.instance_1_23, mpd, spotify."
+    );
+    // Applications are suggested by the name they are looked up by
+    assert!(matches!(
+        Player::try_new("Firefx", &conn).await,
+        Err(pris::Error::UnknownPlayer { suggestion: Some(name), .. }) if name == "firefox"
+    ));
+    assert!(matches!(
+        Player::try_new("rhythmbox", &conn).await,
+        Err(pris::Error::UnknownPlayer {
+            suggestion: None,
+            total: 3,
+            ..
+        })
+    ));
+
+    // Only so many players are listed
+    for index in 0..10 {
+        players
+            .request_name(
+                format!("org.mpris.MediaPlayer2.vlc.instance_{}", index),
+                false,
+                false,
+                true,
+            )
+            .await
+            .unwrap();
+    }
+    let e = Player::try_new("nope", &conn).await.err().unwrap();
+    match &e {
+        pris::Error::UnknownPlayer {
+            available, total, ..
+        } => {
+            assert_eq!(available.len(), 10);
+            assert_eq!(*total, 13);
+        }
+        other => panic!("expected an unknown player, got {:?}", other),
+    }
+    assert!(e.to_string().ends_with(", and 3 more."));
+}
+
+#[tokio::test]
+async fn test_ambiguous_player() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let first = bus.connect_as("firefox.instance_1").await;
+    common::serve_properties(
+        &first,
+        common::props(vec![("Identity", common::var("Firefox".to_string()))]),
+    );
+    let second = bus.connect_as("firefox.instance_2").await;
+    common::serve_properties(&second, common::props(vec![]));
+    let vlc = bus.connect_as("vlc.instance_3").await;
+    common::serve_properties(&vlc, common::props(vec![]));
+
+    match Player::try_new("firefox", &conn).await {
+        Err(pris::Error::AmbiguousPlayer {
+            requested,
+            mut candidates,
+        }) => {
+            assert_eq!(requested, "firefox");
+            candidates.sort_by(|a, b| a.name.cmp(&b.name));
+            assert_eq!(
+                candidates,
+                vec![
+                    pris::PlayerCandidate {
+                        name: "firefox.instance_1".to_string(),
+                        unique_name: first.unique_name().to_string(),
+                        identity: Some("Firefox".to_string()),
+                    },
+                    pris::PlayerCandidate {
+                        name: "firefox.instance_2".to_string(),
+                        unique_name: second.unique_name().to_string(),
+                        identity: None,
+                    },
+                ]
+            );
+        }
+        other => panic!("expected an ambiguous player, got {:?}", other.err()),
+    }
+
+    // Full names, and prefixes with a single instance, resolve
+    let player = Player::try_new("firefox.instance_2", &conn).await.unwrap();
+    assert_eq!(player.name, "firefox.instance_2");
+    assert_eq!(player.unique_name(), None);
+    let player = Player::try_new("vlc", &conn).await.unwrap();
+    assert_eq!(player.name, "vlc.instance_3");
+    assert!(matches!(
+        Player::try_new("fire", &conn).await,
+        Err(pris::Error::UnknownPlayer { .. })
+    ));
+
+    // A pinned player keeps calling its owner after the name moves
+    let pinned = Player::try_pinned("firefox.instance_1", &conn)
+        .await
+        .unwrap();
+    assert_eq!(pinned.unique_name(), Some(&*first.unique_name()));
+    first
+        .release_name("org.mpris.MediaPlayer2.firefox.instance_1")
+        .await
+        .unwrap();
+    second
+        .request_name(
+            "org.mpris.MediaPlayer2.firefox.instance_1",
+            false,
+            true,
+            true,
+        )
+        .await
+        .unwrap();
+    // The mock players answer for any interface
+    let identity: String = pinned.get_property("Identity").await.unwrap();
+    assert_eq!(identity, "Firefox");
+
+    for player in pris::get_all_players(&conn).await.unwrap() {
+        assert!(player.unique_name().is_some());
+    }
+}
+
+#[tokio::test]
+async fn test_validate_single_call() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let vlc = bus.connect_as("vlc").await;
+    common::serve_properties(&vlc, common::props(vec![]));
+    let calls = common::bus_calls(&bus, &conn.unique_name()).await;
+
+    Player::try_new("vlc", &conn).await.unwrap();
+    // Gives the monitor time to receive the calls
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert_eq!(*calls.lock().unwrap(), vec!["NameHasOwner".to_string()]);
+
+    // Invalid names fail before anything is asked of the bus
+    calls.lock().unwrap().clear();
+    assert!(matches!(
+        Player::try_new("not a name", &conn).await,
+        Err(pris::Error::InvalidArgument(_))
+    ));
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert!(calls.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_player_names() {
+    use dbus::strings::BusName;
+    use pris::{IntoPlayerName, PlayerFinder, PlayerName};
+    use std::ffi::OsStr;
+
+    for name in ["vlc", "firefox.instance_1_23", "org.mpris.MediaPlayer2.vlc"] {
+        let parsed = name.into_player_name().unwrap();
+        assert_eq!(
+            parsed.bus_name(),
+            format!("org.mpris.MediaPlayer2.{}", parsed)
+        );
+    }
+    let vlc: PlayerName = "vlc".parse().unwrap();
+    assert_eq!(OsStr::new("vlc").into_player_name().unwrap(), vlc);
+    let bus_name = BusName::new("org.mpris.MediaPlayer2.vlc").unwrap();
+    assert_eq!(bus_name.into_player_name().unwrap(), vlc);
+    let not_a_player = BusName::new("org.freedesktop.DBus").unwrap();
+    assert!(not_a_player.into_player_name().is_err());
+
+    for name in [
+        "",
+        "org.mpris.MediaPlayer2.",
+        "vlc\0",
+        "vlc..2",
+        "vlc.2",
+        "vlc/1",
+        "not a name",
+    ] {
+        match name.into_player_name() {
+            Err(pris::Error::InvalidArgument(reason)) => {
+                assert!(reason.starts_with(&format!("{:?} isn't a player name: ", name)))
+            }
+            other => panic!("expected {:?} to be invalid, got {:?}", name, other),
+        }
+    }
+
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let vlc_conn = bus.connect();
+    let _vlc = MockPlayer::register("vlc", &vlc_conn).await.unwrap();
+
+    let player = Player::try_new("org.mpris.MediaPlayer2.vlc", &conn)
+        .await
+        .unwrap();
+    assert_eq!(player.name, "vlc");
+    let candidate = pris::PlayerCandidate {
+        name: "vlc".to_string(),
+        unique_name: vlc_conn.unique_name().to_string(),
+        identity: None,
+    };
+    let player = Player::try_new(&candidate, &conn).await.unwrap();
+    assert_eq!(player.name, "vlc");
+    assert_eq!(Player::try_new(&player, &conn).await.unwrap().name, "vlc");
+
+    // The finder fails on invalid names, rather than matching nothing
+    let error = PlayerFinder::new(&conn)
+        .by_priority(vec!["mpv", ""])
+        .or_any()
+        .find()
+        .await
+        .unwrap_err();
+    assert!(matches!(error, pris::Error::InvalidArgument(_)));
+    let player = PlayerFinder::new(&conn).by_name(vlc).find().await.unwrap();
+    assert_eq!(player.name, "vlc");
+}
+
+#[tokio::test]
+async fn test_list_players() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    assert!(pris::list_players(&conn).await.unwrap().is_empty());
+
+    let _vlc = bus.connect_as("vlc").await;
+    let _firefox = bus.connect_as("firefox.instance_1234").await;
+    let other = bus.connect();
+    other
+        .request_name("org.example.NotAPlayer", false, true, true)
+        .await
+        .unwrap();
+
+    // Unique names and other services are left out
+    let mut names = pris::list_players(&conn).await.unwrap();
+    names.sort();
+    assert_eq!(names, vec!["firefox.instance_1234", "vlc"]);
+}
+
+#[tokio::test]
+async fn test_list_players_order() {
+    let names = [
+        "vlc",
+        "firefox.instance_1_57",
+        "mpv",
+        "firefox.instance_1_23",
+    ];
+    let mut sorted = names.to_vec();
+    sorted.sort_unstable();
+
+    // However the bus lists them, the players come sorted by name
+    for rotation in 0..names.len() {
+        let bus = common::TestBus::new();
+        let mut owners = Vec::new();
+        let mut shuffled = names.to_vec();
+        shuffled.rotate_left(rotation);
+        shuffled.swap(0, rotation % 2 + 1);
+        for name in &shuffled {
+            owners.push(bus.connect_as(name).await);
+        }
+
+        let conn = bus.connect();
+        assert_eq!(pris::list_players(&conn).await.unwrap(), sorted);
+        let players = Player::all(&conn).await.unwrap();
+        let all: Vec<_> = players.iter().map(|player| player.name.as_str()).collect();
+        assert_eq!(all, sorted);
+        let first = PlayerFinder::new(&conn).or_any().find().await.unwrap();
+        assert_eq!(first.name, sorted[0]);
+
+        let mut raw = pris::list_players_ordered(&conn, pris::PlayerOrder::Bus)
+            .await
+            .unwrap();
+        raw.sort();
+        assert_eq!(raw, sorted);
+    }
+}
+
+#[tokio::test]
+async fn test_wait_for_player() {
+    use std::time::Duration;
+
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let rules = common::match_rules(&conn).await;
+
+    // A player registering while being waited for is picked up
+    let mock_conn = bus.connect();
+    let register = async {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        MockPlayer::register("vlc", &mock_conn).await.unwrap()
+    };
+    let (player, vlc) = tokio::join!(
+        Player::wait_for("vlc", &conn, Duration::from_secs(5)),
+        register
+    );
+    player.unwrap().pause().await.unwrap();
+    assert_eq!(vlc.calls_to("Pause"), 1);
+    assert_eq!(common::match_rules(&conn).await, rules);
+
+    // One already there is returned right away
+    let player = Player::wait_for("vlc", &conn, Duration::from_millis(10)).await;
+    assert_eq!(player.unwrap().name, "vlc");
+
+    let error = Player::wait_for("mpv", &conn, Duration::from_millis(100))
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(error, pris::Error::Timeout { .. }));
+    assert_eq!(common::match_rules(&conn).await, rules);
+
+    // Dropping the wait removes its match
+    let wait = Player::wait_for("mpv", &conn, Duration::from_secs(5));
+    assert!(tokio::time::timeout(Duration::from_millis(100), wait)
+        .await
+        .is_err());
+    assert_eq!(common::match_rules(&conn).await, rules);
+}
+
+#[tokio::test]
+async fn test_player_all() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    assert!(Player::all(&conn).await.unwrap().is_empty());
+
+    let (vlc_conn, mpv_conn) = (bus.connect(), bus.connect());
+    let vlc = MockPlayer::register("vlc", &vlc_conn).await.unwrap();
+    let mpv = MockPlayer::register("mpv", &mpv_conn).await.unwrap();
+
+    // The handles are usable as they are, in the order of list_players
+    let players = Player::all(&conn).await.unwrap();
+    let names: Vec<_> = players.iter().map(|player| player.name.clone()).collect();
+    assert_eq!(names, pris::list_players(&conn).await.unwrap());
+    for player in &players {
+        player.pause().await.unwrap();
+    }
+    assert_eq!(vlc.calls_to("Pause"), 1);
+    assert_eq!(mpv.calls_to("Pause"), 1);
+}
+
+#[tokio::test]
+async fn test_find_active() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    assert!(Player::find_active(&conn).await.unwrap().is_none());
+
+    // A player that never answers is skipped
+    let hung = bus.connect_blocking();
+    hung.request_name("org.mpris.MediaPlayer2.hung", false, true, true)
+        .unwrap();
+    let (vlc_conn, mpv_conn) = (bus.connect(), bus.connect());
+    let vlc = MockPlayer::register("vlc", &vlc_conn).await.unwrap();
+    let mpv = MockPlayer::register("mpv", &mpv_conn).await.unwrap();
+    assert!(Player::find_active(&conn).await.unwrap().is_none());
+
+    vlc.set_property("PlaybackStatus", "Paused".to_string());
+    let started = std::time::Instant::now();
+    let active = Player::find_active(&conn).await.unwrap().unwrap();
+    assert_eq!(active.name, "vlc");
+    assert!(started.elapsed() < std::time::Duration::from_secs(1));
+
+    // Playing wins over paused
+    mpv.set_property("PlaybackStatus", "Playing".to_string());
+    let active = Player::find_active(&conn).await.unwrap().unwrap();
+    assert_eq!(active.name, "mpv");
+}
+
+#[tokio::test]
+async fn test_player_finder() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let (vlc_conn, mpv_conn, firefox_conn) = (bus.connect(), bus.connect(), bus.connect());
+    let vlc = MockPlayer::register("vlc", &vlc_conn).await.unwrap();
+    let mpv = MockPlayer::register("mpv", &mpv_conn).await.unwrap();
+    let firefox = MockPlayer::register("firefox.instance_1", &firefox_conn)
+        .await
+        .unwrap();
+    vlc.set_property("PlaybackStatus", "Paused".to_string());
+    mpv.set_property("PlaybackStatus", "Playing".to_string());
+
+    async fn find(finder: PlayerFinder<'_>) -> String {
+        finder.find().await.unwrap().name
+    }
+
+    // Rules are tried in order, the first to match deciding
+    let finder = PlayerFinder::new(&conn).by_name("spotify");
+    assert_eq!(find(finder.clone().or_playing().or_any()).await, "mpv");
+    assert_eq!(find(finder.clone().or_paused().or_playing()).await, "vlc");
+    let finder = PlayerFinder::new(&conn).by_priority(vec!["spotify", "vlc", "mpv"]);
+    assert_eq!(find(finder).await, "vlc");
+    let finder = PlayerFinder::new(&conn).by_prefix("firefox");
+    assert_eq!(find(finder).await, "firefox.instance_1");
+    let finder = PlayerFinder::new(&conn).by_identity("MPV");
+    assert_eq!(find(finder).await, "mpv");
+
+    // Preferences are patterns, which match instances too
+    let finder = PlayerFinder::new(&conn).by_preferences(vec!["mpd", "fire*", "vlc"]);
+    assert_eq!(find(finder).await, "firefox.instance_1");
+    let finder = PlayerFinder::new(&conn).by_preferences(vec!["mpd", "firefox", "vlc"]);
+    assert_eq!(find(finder.clone()).await, "firefox.instance_1");
+    drop(firefox);
+    assert!(common::eventually(|| async { finder.find().await.unwrap().name == "vlc" }).await);
+    std::env::set_var("PRIS_PLAYERS", "mpd, m?v ,vlc");
+    let finder = PlayerFinder::new(&conn).by_env_preferences();
+    assert_eq!(find(finder).await, "mpv");
+
+    // What was tried is reported
+    let error = PlayerFinder::new(&conn)
+        .by_name("spotify")
+        .by_identity("Spotify")
+        .find()
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(&error, pris::Error::NotFound { tried } if tried.len() == 2));
+    assert_eq!(
+        error.to_string(),
+        "No player matched the name spotify, or the identity Spotify."
+    );
+}
+
+#[test]
+fn test_group_players() {
+    let groups = pris::group_players(vec![
+        "firefox.instance_1_23",
+        "vlc",
+        "firefox.instance_1_57",
+        "vlc.instance7389",
+        "mpv",
+        "chromium.instance4821",
+    ]);
+    let grouped: Vec<(&str, Vec<&str>)> = groups
+        .iter()
+        .map(|group| {
+            let instances = group.instances.iter().map(String::as_str).collect();
+            (group.app.as_str(), instances)
+        })
+        .collect();
+    assert_eq!(
+        grouped,
+        vec![
+            (
+                "firefox",
+                vec!["firefox.instance_1_23", "firefox.instance_1_57"]
+            ),
+            ("vlc", vec!["vlc", "vlc.instance7389"]),
+            ("mpv", vec!["mpv"]),
+            ("chromium", vec!["chromium.instance4821"]),
+        ]
+    );
+    assert!(groups.iter().all(|group| group.identity.is_none()));
+}
+
+#[tokio::test]
+async fn test_player_groups() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let conns: Vec<_> = (0..5).map(|_| bus.connect()).collect();
+    let names = [
+        ("firefox.instance_1_23", "Firefox"),
+        ("firefox.instance_1_57", "Firefox"),
+        ("vlc", "VLC media player"),
+        ("vlc.instance7389", "VLC media player"),
+        // Named like Firefox, but something else entirely
+        ("firefox.instance_2_1", "Impostor"),
+    ];
+    let mut mocks = Vec::new();
+    for ((name, identity), conn) in names.iter().zip(&conns) {
+        let mock = MockPlayer::register(name, conn).await.unwrap();
+        mock.set_property("Identity", identity.to_string());
+        mocks.push(mock);
+    }
+
+    let mut groups = pris::player_groups(&conn).await.unwrap();
+    for group in &mut groups {
+        group.instances.sort();
+    }
+    groups.sort_by(|a, b| a.instances.cmp(&b.instances));
+    let grouped: Vec<(&str, Option<&str>, usize)> = groups
+        .iter()
+        .map(|g| (g.app.as_str(), g.identity.as_deref(), g.instances.len()))
+        .collect();
+    assert_eq!(
+        grouped,
+        vec![
+            ("firefox", Some("Firefox"), 2),
+            ("firefox", Some("Impostor"), 1),
+            ("vlc", Some("VLC media player"), 2),
+        ]
+    );
+
+    // Any instance will do, preferring the one playing
+    let finder = PlayerFinder::new(&conn).by_app("firefox");
+    mocks[1].set_property("PlaybackStatus", "Playing".to_string());
+    assert_eq!(finder.find().await.unwrap().name, "firefox.instance_1_57");
+    let finder = PlayerFinder::new(&conn).by_app("vlc");
+    assert!(finder.find().await.unwrap().name.starts_with("vlc"));
+    let error = PlayerFinder::new(&conn).by_app("mpv").find().await;
+    assert!(matches!(error, Err(pris::Error::NotFound { .. })));
+}
+
+#[tokio::test]
+async fn test_discovery_cache() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let vlc = bus.connect_as("vlc").await;
+    common::serve_properties(&vlc, common::props(vec![]));
+    let calls = common::bus_calls(&bus, &conn.unique_name()).await;
+    let calls = &calls;
+    let listings = || async move {
+        // Gives the monitor time to receive the calls
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|member| *member == "ListNames")
+            .count()
+    };
+
+    let cache = Arc::new(pris::DiscoveryCache::new(std::time::Duration::from_secs(
+        60,
+    )));
+    let players = cache.players(&conn).await.unwrap();
+    assert_eq!(players.len(), 1);
+    assert_eq!(players[0].unique_name(), Some(&*vlc.unique_name()));
+    assert_eq!(listings().await, 1);
+
+    // Repeated discovery within the window is free, from any task
+    let mpv = bus.connect_as("mpv").await;
+    common::serve_properties(&mpv, common::props(vec![]));
+    let (shared, shared_conn) = (cache.clone(), conn.clone());
+    let names = tokio::spawn(async move { shared.names(&shared_conn).await.unwrap() })
+        .await
+        .unwrap();
+    assert_eq!(names, vec!["vlc".to_string()]);
+    assert_eq!(listings().await, 1);
+
+    // Fresh listings bypass the cache and refill it
+    let mut names: Vec<String> = cache
+        .fresh_players(&conn)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|player| player.name)
+        .collect();
+    names.sort();
+    assert_eq!(names, vec!["mpv".to_string(), "vlc".to_string()]);
+    assert_eq!(cache.names(&conn).await.unwrap().len(), 2);
+    assert_eq!(listings().await, 2);
+
+    // Lifecycle events invalidate it early
+    let vanished = common::name_owner_changed("org.mpris.MediaPlayer2.mpv", &mpv.unique_name(), "");
+    let event = pris::Event::from_message(&vanished, &conn).await.unwrap();
+    mpv.release_name("org.mpris.MediaPlayer2.mpv")
+        .await
+        .unwrap();
+    cache.observe(&event);
+    assert_eq!(cache.names(&conn).await.unwrap(), vec!["vlc".to_string()]);
+    assert_eq!(listings().await, 3);
+
+    // And a zero TTL caches nothing
+    let uncached = pris::DiscoveryCache::new(std::time::Duration::ZERO);
+    uncached.names(&conn).await.unwrap();
+    uncached.names(&conn).await.unwrap();
+    assert_eq!(listings().await, 5);
+}
+
+#[tokio::test]
+async fn test_typed_metadata() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let vlc = bus.connect_as("vlc").await;
+    let metadata = common::props(vec![
+        (
+            "mpris:trackid",
+            common::var(dbus::Path::from("/org/vlc/track/1")),
+        ),
+        ("mpris:length", common::var(60_000_000u64)),
+        ("xesam:artist", common::var(vec!["Artist".to_string()])),
+        ("xesam:album", common::var("Album".to_string())),
+        ("xesam:title", common::var(b"Title\xff".to_vec())),
+        ("xesam:trackNumber", common::var(3i32)),
+    ]);
+    let served = common::serve_properties(
+        &vlc,
+        common::props(vec![("Metadata", common::var(metadata))]),
+    );
+
+    let player = Player::try_new("vlc", &conn).await.unwrap();
+    let metadata: pris::Metadata = player.get_property("Metadata").await.unwrap();
+    assert_eq!(metadata.track_id.as_deref(), Some("/org/vlc/track/1"));
+    assert_eq!(metadata.length, Some(std::time::Duration::from_secs(60)));
+    assert_eq!(metadata.artists, Some(vec!["Artist".to_string()]));
+    assert_eq!(metadata.album.as_deref(), Some("Album"));
+    assert_eq!(metadata.url, None);
+
+    // Other entries, and common ones of another type, are kept raw
+    assert_eq!(metadata.title, None);
+    assert_eq!(
+        pris::prop_str(&metadata.extra, "xesam:title").as_deref(),
+        Some("Title\u{fffd}")
+    );
+    assert_eq!(metadata.extra["xesam:trackNumber"].0.as_i64(), Some(3));
+    assert_eq!(metadata.extra.len(), 2);
+
+    served
+        .lock()
+        .unwrap()
+        .insert("Metadata".to_string(), common::var(common::props(vec![])));
+    let metadata: pris::Metadata = player.get_property("Metadata").await.unwrap();
+    assert!(metadata.track_id.is_none() && metadata.extra.is_empty());
+
+    // Anything but a dictionary of variants is a type mismatch
+    served
+        .lock()
+        .unwrap()
+        .insert("Metadata".to_string(), common::var(3i32));
+    assert!(matches!(
+        player.get_property::<pris::Metadata>("Metadata").await,
+        Err(pris::Error::TypeMismatch { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_snapshot_players() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let vlc = bus.connect_as("vlc").await;
+    common::serve_properties(
+        &vlc,
+        common::props(vec![
+            ("Identity", common::var("VLC".to_string())),
+            ("PlaybackStatus", common::var("Playing".to_string())),
+        ]),
+    );
+    let mpv = bus.connect_as("mpv").await;
+    common::serve_properties(
+        &mpv,
+        common::props(vec![("PlaybackStatus", common::var("Paused".to_string()))]),
+    );
+    // A player that never answers
+    let firefox = bus.connect_as("firefox").await;
+    firefox.start_receive(
+        dbus::message::MatchRule::new_method_call(),
+        Box::new(|_, _| true),
+    );
+
+    let options = pris::SnapshotOptions {
+        timeout: std::time::Duration::from_millis(200),
+        concurrency: 0,
+    };
+    let started = std::time::Instant::now();
+    let mut snapshots = pris::snapshot_players(&conn, options).await.unwrap();
+    assert!(started.elapsed() < std::time::Duration::from_secs(2));
+    snapshots.sort_by(|a, b| a.player.name.cmp(&b.player.name));
+
+    let names: Vec<&str> = snapshots.iter().map(|s| s.player.name.as_str()).collect();
+    assert_eq!(names, vec!["firefox", "mpv", "vlc"]);
+    assert!(matches!(
+        &snapshots[0].state,
+        Err(pris::Error::Timeout { player: Some(player), .. }) if player == "firefox"
+    ));
+    assert_eq!(snapshots[1].identity, None);
+    assert_eq!(
+        snapshots[1].state.as_ref().unwrap().playback_status,
+        Some(pris::PlaybackStatus::Paused)
+    );
+    // The mock players answer for any interface
+    assert_eq!(snapshots[2].identity.as_deref(), Some("VLC"));
+    assert_eq!(
+        snapshots[2].state.as_ref().unwrap().playback_status,
+        Some(pris::PlaybackStatus::Playing)
+    );
+}
+
+#[tokio::test]
+async fn test_broadcast() {
+    use std::time::Duration;
+
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let hung = bus.connect_blocking();
+    hung.request_name("org.mpris.MediaPlayer2.hung", false, true, true)
+        .unwrap();
+    let (vlc_conn, mpv_conn, cmus_conn) = (bus.connect(), bus.connect(), bus.connect());
+    let vlc = MockPlayer::register("vlc", &vlc_conn).await.unwrap();
+    let mpv = MockPlayer::register("mpv", &mpv_conn).await.unwrap();
+    let cmus = MockPlayer::register("cmus", &cmus_conn).await.unwrap();
+    vlc.set_property("PlaybackStatus", "Playing".to_string());
+    mpv.set_property("PlaybackStatus", "Playing".to_string());
+    mpv.set_property("CanPause", false);
+
+    // Only playing players are paused, and one that hangs or can't
+    // pause doesn't spoil the others
+    let started = std::time::Instant::now();
+    let paused = pris::pause_all(&conn, Duration::from_millis(200))
+        .await
+        .unwrap();
+    assert!(started.elapsed() < Duration::from_secs(1));
+    let results = paused.results();
+    assert_eq!(results.keys().collect::<Vec<_>>(), ["hung", "mpv", "vlc"]);
+    assert!(matches!(results["hung"], Err(pris::Error::Timeout { .. })));
+    assert!(!results["mpv"].as_ref().unwrap().sent);
+    assert!(results["vlc"].as_ref().unwrap().sent);
+    assert_eq!(paused.names(), ["vlc"]);
+    assert_eq!(vlc.calls_to("Pause"), 1);
+    assert_eq!(mpv.calls_to("Pause") + cmus.calls_to("Pause"), 0);
+
+    // Only what was paused is resumed
+    let resumed = paused.resume(Duration::from_millis(200)).await;
+    assert_eq!(resumed.keys().collect::<Vec<_>>(), ["vlc"]);
+    assert_eq!(vlc.calls_to("Play"), 1);
+    assert_eq!(cmus.calls_to("Play"), 0);
+
+    let played = pris::play_all(&conn, Duration::from_millis(200))
+        .await
+        .unwrap();
+    assert_eq!(played.len(), 4);
+    assert!(played["cmus"].as_ref().unwrap().sent);
+    assert_eq!(cmus.calls_to("Play"), 1);
+}
+
+#[test]
+fn test_duration_format() {
+    use pris::{format_duration, parse_duration, DurationStyle, Hours, ParsedDuration};
+    use std::time::Duration;
+
+    let style = |hours, padded| DurationStyle { hours, padded };
+    let length = Duration::from_millis(187_900);
+    assert_eq!(format_duration(length, DurationStyle::default()), "3:07");
+    assert_eq!(format_duration(length, style(Hours::Auto, true)), "03:07");
+    assert_eq!(
+        format_duration(length, style(Hours::Always, false)),
+        "0:03:07"
+    );
+    let long = Duration::from_secs(3765);
+    assert_eq!(format_duration(long, DurationStyle::default()), "1:02:45");
+    assert_eq!(format_duration(long, style(Hours::Never, false)), "62:45");
+    // Hours don't roll over into days
+    let marathon = Duration::from_secs(26 * 3600 + 5);
+    assert_eq!(
+        format_duration(marathon, DurationStyle::default()),
+        "26:00:05"
+    );
+
+    let parse = |input| parse_duration(input, true).unwrap();
+    assert_eq!(
+        parse("90"),
+        ParsedDuration::Absolute(Duration::from_secs(90))
+    );
+    assert_eq!(
+        parse("1:30"),
+        ParsedDuration::Absolute(Duration::from_secs(90))
+    );
+    assert_eq!(parse("1:02:45").duration(), long);
+    assert_eq!(parse("90:00").duration(), Duration::from_secs(5400));
+    assert_eq!(
+        parse("+15s"),
+        ParsedDuration::Forward(Duration::from_secs(15))
+    );
+    assert_eq!(
+        parse("-2m"),
+        ParsedDuration::Backward(Duration::from_secs(120))
+    );
+    assert_eq!(parse("0.5").duration(), Duration::from_millis(500));
+
+    // Single digits are only taken leniently
+    assert!(parse_duration("1:5", true).is_err());
+    assert_eq!(
+        parse_duration("1:5", false).unwrap().duration(),
+        Duration::from_secs(65)
+    );
+    for malformed in &["", "+", "1:60", "1::2", "1:2:3:4", "abc", "1e3", "s"] {
+        let error = parse_duration(malformed, false).unwrap_err();
+        assert!(
+            matches!(error, pris::Error::InvalidArgument(_)),
+            "{}",
+            malformed
+        );
+    }
+    let error = parse_duration("1:x", false).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "\"1:x\" isn't a duration: \"x\" isn't a whole number."
+    );
+}
+
+#[test]
+fn test_micros() {
+    use pris::Micros;
+    use std::{convert::TryFrom, time::Duration};
+
+    let second = Micros::from_duration(Duration::from_secs(1));
+    assert_eq!(second.get(), 1_000_000);
+    assert_eq!(Micros::from(Duration::from_millis(1)), Micros::new(1000));
+    assert!(Micros::new(-1) < Micros::ZERO && Micros::ZERO < second);
+    assert_eq!(second.to_string(), "1000000µs");
+
+    // Arithmetic and conversions saturate, unless checked
+    assert_eq!(Micros::MAX + second, Micros::MAX);
+    assert_eq!(Micros::MIN - second, Micros::MIN);
+    assert_eq!(-Micros::MIN, Micros::MAX);
+    assert_eq!(Micros::from_duration(Duration::MAX), Micros::MAX);
+    assert!(Micros::try_from_duration(Duration::MAX).is_err());
+    assert_eq!(Micros::new(-250).to_duration(), Duration::ZERO);
+    assert!(matches!(
+        Duration::try_from(Micros::new(-250)),
+        Err(pris::Error::InvalidArgument(_))
+    ));
+    assert_eq!(
+        Duration::try_from(second - Micros::new(1)).unwrap(),
+        Duration::from_micros(999_999)
+    );
+}
+
+#[tokio::test]
+async fn test_describe_player() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let vlc_conn = bus.connect();
+    let vlc = MockPlayer::register("vlc", &vlc_conn).await.unwrap();
+    vlc.set_property("Identity", "VLC media player".to_string());
+    vlc.set_property("PlaybackStatus", "Playing".to_string());
+
+    // Printing the player doesn't ask it for anything
+    let player = Player::try_pinned("vlc", &conn).await.unwrap();
+    vlc.clear_calls();
+    assert_eq!(player.to_string(), "vlc");
+    let debug = format!("{:?}", player);
+    assert!(debug.contains("\"vlc\""), "{}", debug);
+    assert!(debug.contains(&*vlc_conn.unique_name()), "{}", debug);
+    assert!(debug.contains("validated: true"), "{}", debug);
+    assert!(vlc.calls().is_empty());
+
+    // Handles taken from a listing weren't looked up by name
+    let listed = Player::all(&conn).await.unwrap();
+    let debug = format!("{:?}", listed[0]);
+    assert!(debug.contains("validated: false"), "{}", debug);
+
+    assert_eq!(player.describe().await, "vlc — VLC media player — Playing");
+
+    // A player that doesn't answer is described by its name alone
+    let hung = bus.connect_blocking();
+    hung.request_name("org.mpris.MediaPlayer2.hung", false, true, true)
+        .unwrap();
+    let player = Player::try_new("hung", &conn).await.unwrap();
+    let started = std::time::Instant::now();
+    assert_eq!(player.describe().await, "hung");
+    assert!(started.elapsed() < std::time::Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn test_sort_by_status() {
+    use pris::{status_rank, PlaybackStatus, PlayerOrder};
+    use std::time::{Duration, Instant};
+
+    assert!(status_rank(PlaybackStatus::Playing) < status_rank(PlaybackStatus::Paused));
+    assert!(status_rank(PlaybackStatus::Paused) < status_rank(PlaybackStatus::Stopped));
+
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let hung = bus.connect_blocking();
+    hung.request_name("org.mpris.MediaPlayer2.hung", false, true, true)
+        .unwrap();
+    let (vlc_conn, mpv_conn, cmus_conn, audacious_conn) =
+        (bus.connect(), bus.connect(), bus.connect(), bus.connect());
+    let vlc = MockPlayer::register("vlc", &vlc_conn).await.unwrap();
+    let mpv = MockPlayer::register("mpv", &mpv_conn).await.unwrap();
+    let _cmus = MockPlayer::register("cmus", &cmus_conn).await.unwrap();
+    let audacious = MockPlayer::register("audacious", &audacious_conn)
+        .await
+        .unwrap();
+    vlc.set_property("PlaybackStatus", "Playing".to_string());
+    mpv.set_property("PlaybackStatus", "Paused".to_string());
+    audacious.set_property("PlaybackStatus", "Paused".to_string());
+
+    // A player that doesn't answer sorts last, without holding up the
+    // others for long
+    let expected = ["vlc", "audacious", "mpv", "cmus", "hung"];
+    let started = Instant::now();
+    let players = pris::sort_by_status(Player::all(&conn).await.unwrap()).await;
+    assert!(started.elapsed() < Duration::from_secs(1));
+    let names: Vec<_> = players.iter().map(|player| player.name.as_str()).collect();
+    assert_eq!(names, expected);
+
+    let names = pris::list_players_ordered(&conn, PlayerOrder::Status)
+        .await
+        .unwrap();
+    assert_eq!(names, expected);
+}
+
+#[tokio::test]
+async fn test_selection_memory() {
+    use pris::{PlayerFinder, SelectionMemory};
+
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let (mpd_conn, vlc_conn) = (bus.connect(), bus.connect());
+    let mpd = MockPlayer::register("mpd", &mpd_conn).await.unwrap();
+    mpd.set_property("Identity", "Music Player Daemon".to_string());
+    let _vlc = MockPlayer::register("vlc", &vlc_conn).await.unwrap();
+    let fallback = PlayerFinder::new(&conn).by_name("vlc").or_any();
+
+    let mut memory = SelectionMemory::new();
+    assert_eq!(memory.resolve(&fallback).await.unwrap().name, "vlc");
+    memory
+        .remember(&Player::try_new("mpd", &conn).await.unwrap())
+        .await;
+    assert_eq!(
+        memory.bus_name.as_deref(),
+        Some("org.mpris.MediaPlayer2.mpd")
+    );
+    assert_eq!(memory.identity.as_deref(), Some("Music Player Daemon"));
+    assert_eq!(memory.resolve(&fallback).await.unwrap().name, "mpd");
+
+    // An absent player is fallen back from, and still remembered
+    drop(mpd);
+    assert!(
+        common::eventually(|| async {
+            !pris::list_players(&conn)
+                .await
+                .unwrap()
+                .contains(&"mpd".to_string())
+        })
+        .await
+    );
+    let remembered = memory.clone();
+    assert_eq!(memory.resolve(&fallback).await.unwrap().name, "vlc");
+    assert_eq!(memory, remembered);
+
+    // A different application with the same identity takes its place
+    let mpdris_conn = bus.connect();
+    let mpdris = MockPlayer::register("mpdris2", &mpdris_conn).await.unwrap();
+    mpdris.set_property("Identity", "music player daemon".to_string());
+    assert_eq!(memory.resolve(&fallback).await.unwrap().name, "mpdris2");
+    assert_eq!(memory, remembered);
+
+    // Another instance of it is found, and remembered instead
+    let (first_conn, second_conn) = (bus.connect(), bus.connect());
+    let first = MockPlayer::register("firefox.instance_1", &first_conn)
+        .await
+        .unwrap();
+    memory
+        .remember(&Player::try_new("firefox.instance_1", &conn).await.unwrap())
+        .await;
+    drop(first);
+    let _second = MockPlayer::register("firefox.instance_2", &second_conn)
+        .await
+        .unwrap();
+    assert!(
+        common::eventually(|| async {
+            !pris::list_players(&conn)
+                .await
+                .unwrap()
+                .contains(&"firefox.instance_1".to_string())
+        })
+        .await
+    );
+    let player = memory.resolve(&fallback).await.unwrap();
+    assert_eq!(player.name, "firefox.instance_2");
+    assert_eq!(
+        memory.bus_name.as_deref(),
+        Some("org.mpris.MediaPlayer2.firefox.instance_2")
+    );
+
+    memory.forget();
+    assert_eq!(memory, SelectionMemory::default());
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use futures::StreamExt;
+use pris::{
+    testing::{MockCall, MockPlayer},
+    Event, EventManager, EventType, PlaybackStatus, Player, Value,
+};
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_mock_player() {
+    let bus = common::TestBus::new();
+    let mock_conn = bus.connect();
+    let mock = MockPlayer::register("vlc", &mock_conn).await.unwrap();
+    mock.set_property("PlaybackStatus", "Playing".to_string());
+    mock.insert_metadata("xesam:title", "Windowlicker".to_string());
+    assert!(MockPlayer::register("vlc", &bus.connect()).await.is_err());
+
+    let conn = bus.connect();
+    let player = Player::try_new("vlc", &conn).await.unwrap();
+    let state = player.get_state().await.unwrap();
+    assert_eq!(state.playback_status, Some(PlaybackStatus::Playing));
+    assert_eq!(
+        pris::prop_str(state.metadata.as_ref().unwrap(), "xesam:title").as_deref(),
+        Some("Windowlicker")
+    );
+    assert_eq!(mock.property("Identity"), Some(Value::Str("vlc".into())));
+
+    mock.clear_calls();
+    player.pause().await.unwrap();
+    player.seek(Duration::from_secs(5)).await.unwrap();
+    assert_eq!(mock.calls_to("Pause"), 1);
+    assert_eq!(
+        mock.calls().last(),
+        Some(&MockCall {
+            interface: common::PLAYER_INTERFACE.to_string(),
+            member: "Seek".to_string(),
+            args: vec![Value::Int(5_000_000)],
+        })
+    );
+
+    // Setting a property sticks, as with a real player
+    player.set_volume(0.25).await.unwrap();
+    assert_eq!(mock.property("Volume"), Some(Value::Double(0.25)));
+    assert_eq!(player.get_volume().await.unwrap(), 0.25);
+
+    mock.set_error("Next", Some("org.freedesktop.DBus.Error.NotSupported"))
+        .unwrap();
+    let error = player.next().await.unwrap_err();
+    assert_eq!(
+        error.dbus_name(),
+        Some("org.freedesktop.DBus.Error.NotSupported")
+    );
+    mock.set_error("Next", None).unwrap();
+    player.next().await.unwrap();
+    assert_eq!(mock.calls_to("Next"), 2);
+
+    // The player leaves the bus once dropped
+    drop(mock);
+    assert!(
+        common::eventually(|| async { pris::get_all_players(&conn).await.unwrap().is_empty() })
+            .await
+    );
+}
+
+#[tokio::test]
+async fn test_mock_player_signals() {
+    let bus = common::TestBus::new();
+    let mock_conn = bus.connect();
+    let mock = MockPlayer::register("vlc", &mock_conn).await.unwrap();
+
+    let conn = bus.connect();
+    let manager = EventManager::new(&conn);
+    let mut events = manager
+        .stream(&[EventType::PropertiesChanged, EventType::Seeked])
+        .await
+        .unwrap();
+
+    mock.set_property("PlaybackStatus", "Paused".to_string());
+    mock.emit_properties_changed(&["PlaybackStatus", "Nope"])
+        .unwrap();
+    mock.emit_seeked(Duration::from_secs(42)).unwrap();
+
+    let timeout = Duration::from_secs(5);
+    match tokio::time::timeout(timeout, events.next()).await.unwrap() {
+        Some(Event::PropertiesChanged(changed)) => {
+            assert_eq!(changed.player, "vlc");
+            assert_eq!(
+                changed.properties.playback_status,
+                Some(PlaybackStatus::Paused)
+            );
+            assert_eq!(changed.properties.invalidated, vec!["Nope"]);
+        }
+        other => panic!("expected a PropertiesChanged event, got {:?}", other),
+    }
+    match tokio::time::timeout(timeout, events.next()).await.unwrap() {
+        Some(Event::Seeked(seeked)) => assert_eq!(seeked.position, Duration::from_secs(42)),
+        other => panic!("expected a Seeked event, got {:?}", other),
+    }
+    assert_eq!(mock.property("Position"), Some(Value::Int(42_000_000)));
+}
@@ -0,0 +1,272 @@
+mod common;
+
+use futures::StreamExt;
+use pris::{
+    server::{MediaPlayer, Server},
+    Error, Event, EventManager, EventStream, EventType, Metadata, Micros, PlaybackStatus, Player,
+};
+use std::time::Duration;
+
+const TRACKS: &[&str] = &["Windowlicker", "Flim"];
+
+struct Jukebox {
+    playing: bool,
+    track: usize,
+    position: Duration,
+    volume: f64,
+}
+
+impl Jukebox {
+    fn track_id(&self) -> String {
+        format!("/jukebox/track/{}", self.track)
+    }
+}
+
+impl MediaPlayer for Jukebox {
+    fn identity(&self) -> String {
+        "Jukebox".to_string()
+    }
+
+    fn playback_status(&self) -> PlaybackStatus {
+        if self.playing {
+            PlaybackStatus::Playing
+        } else {
+            PlaybackStatus::Paused
+        }
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            track_id: Some(self.track_id()),
+            length: Some(Duration::from_secs(360)),
+            title: Some(TRACKS[self.track].to_string()),
+            artists: Some(vec!["Aphex Twin".to_string()]),
+            ..Metadata::default()
+        }
+    }
+
+    fn volume(&self) -> f64 {
+        self.volume
+    }
+
+    fn position(&self) -> Duration {
+        self.position
+    }
+
+    fn can_go_next(&self) -> bool {
+        self.track + 1 < TRACKS.len()
+    }
+
+    fn can_seek(&self) -> bool {
+        true
+    }
+
+    fn play(&mut self) -> pris::Result<()> {
+        self.playing = true;
+        Ok(())
+    }
+
+    fn pause(&mut self) -> pris::Result<()> {
+        self.playing = false;
+        Ok(())
+    }
+
+    fn next(&mut self) -> pris::Result<()> {
+        if self.can_go_next() {
+            self.track += 1;
+            self.position = Duration::ZERO;
+        }
+        Ok(())
+    }
+
+    fn seek(&mut self, offset: Micros) -> pris::Result<()> {
+        self.position = (Micros::from_duration(self.position) + offset).to_duration();
+        Ok(())
+    }
+
+    fn set_position(&mut self, track_id: &str, position: Micros) -> pris::Result<()> {
+        if track_id == self.track_id() {
+            self.position = position.to_duration();
+        }
+        Ok(())
+    }
+
+    fn set_volume(&mut self, volume: f64) -> pris::Result<()> {
+        if !(0.0..=1.0).contains(&volume) {
+            return Err(Error::InvalidArgument("Out of range".to_string()));
+        }
+        self.volume = volume;
+        Ok(())
+    }
+}
+
+async fn next_event(events: &mut EventStream<'_>) -> Event {
+    tokio::time::timeout(Duration::from_secs(5), events.next())
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+// The crate's client controlling the crate's server
+#[tokio::test]
+async fn test_loopback() {
+    let bus = common::TestBus::new();
+    let server_conn = bus.connect();
+    let jukebox = Jukebox {
+        playing: false,
+        track: 0,
+        position: Duration::ZERO,
+        volume: 1.0,
+    };
+    let server = Server::register("jukebox", jukebox, &server_conn)
+        .await
+        .unwrap();
+
+    let conn = bus.connect();
+    let manager = EventManager::new(&conn);
+    let mut events = manager
+        .stream(&[EventType::PropertiesChanged, EventType::Seeked])
+        .await
+        .unwrap();
+    let player = Player::try_new("jukebox", &conn).await.unwrap();
+    let state = player.get_state().await.unwrap();
+    assert_eq!(state.playback_status, Some(PlaybackStatus::Paused));
+    assert_eq!(state.can_go_next, Some(true));
+    let metadata: Metadata = player.get_property("Metadata").await.unwrap();
+    assert_eq!(metadata.title.as_deref(), Some("Windowlicker"));
+    assert_eq!(metadata.track_id.as_deref(), Some("/jukebox/track/0"));
+    assert_eq!(metadata.length, Some(Duration::from_secs(360)));
+    assert_eq!(metadata.artists, Some(vec!["Aphex Twin".to_string()]));
+
+    player.play().await.unwrap();
+    assert!(server.player().playing);
+    match next_event(&mut events).await {
+        Event::PropertiesChanged(changed) => {
+            assert_eq!(changed.player, "jukebox");
+            assert_eq!(
+                changed.properties.playback_status,
+                Some(PlaybackStatus::Playing)
+            );
+            assert!(changed.properties.metadata.is_none());
+        }
+        other => panic!("expected a PropertiesChanged event, got {:?}", other),
+    }
+
+    player.seek(Duration::from_secs(30)).await.unwrap();
+    match next_event(&mut events).await {
+        Event::Seeked(seeked) => assert_eq!(seeked.position, Duration::from_secs(30)),
+        other => panic!("expected a Seeked event, got {:?}", other),
+    }
+    player.set_position(Duration::from_secs(60)).await.unwrap();
+    match next_event(&mut events).await {
+        Event::Seeked(seeked) => assert_eq!(seeked.position, Duration::from_secs(60)),
+        other => panic!("expected a Seeked event, got {:?}", other),
+    }
+
+    player.next().await.unwrap();
+    match next_event(&mut events).await {
+        Event::PropertiesChanged(changed) => {
+            let metadata = changed.properties.metadata.unwrap();
+            assert_eq!(
+                pris::prop_str(&metadata, "xesam:title").as_deref(),
+                Some("Flim")
+            );
+            assert_eq!(changed.properties.can_go_next, Some(false));
+        }
+        other => panic!("expected a PropertiesChanged event, got {:?}", other),
+    }
+
+    // Setting a property goes through its handler, and is signalled
+    player.set_volume(0.5).await.unwrap();
+    assert_eq!(server.player().volume, 0.5);
+    match next_event(&mut events).await {
+        Event::PropertiesChanged(changed) => assert_eq!(changed.properties.volume, Some(0.5)),
+        other => panic!("expected a PropertiesChanged event, got {:?}", other),
+    }
+
+    // Methods the player doesn't handle are refused as unsupported
+    let error = player.previous().await.unwrap_err();
+    assert_eq!(
+        error.dbus_name(),
+        Some("org.freedesktop.DBus.Error.NotSupported")
+    );
+
+    // Changes made by the application itself are signalled too
+    server.update(|jukebox| jukebox.playing = false).unwrap();
+    match next_event(&mut events).await {
+        Event::PropertiesChanged(changed) => assert_eq!(
+            changed.properties.playback_status,
+            Some(PlaybackStatus::Paused)
+        ),
+        other => panic!("expected a PropertiesChanged event, got {:?}", other),
+    }
+
+    drop(server);
+    assert!(
+        common::eventually(|| async { pris::get_all_players(&conn).await.unwrap().is_empty() })
+            .await
+    );
+}
+
+#[tokio::test]
+async fn test_support_report() {
+    use pris::{testing::MockPlayer, SupportSource};
+
+    let bus = common::TestBus::new();
+    let server_conn = bus.connect();
+    let jukebox = Jukebox {
+        playing: false,
+        track: 0,
+        position: Duration::ZERO,
+        volume: 1.0,
+    };
+    let _server = Server::register("jukebox", jukebox, &server_conn)
+        .await
+        .unwrap();
+    let conn = bus.connect();
+
+    // Served players are introspected, and lack only what's optional
+    let player = Player::try_new("jukebox", &conn).await.unwrap();
+    let report = player.support_report().await.unwrap();
+    assert_eq!(report.source, SupportSource::Introspection);
+    assert!(report.is_complete());
+    let root = report.interface("org.mpris.MediaPlayer2").unwrap();
+    assert_eq!(
+        root.methods.as_deref(),
+        Some(&["Raise".to_string(), "Quit".to_string()][..])
+    );
+    assert!(root.properties.contains(&"Identity".to_string()));
+    let interface = report.interface("org.mpris.MediaPlayer2.Player").unwrap();
+    assert!(interface.missing.is_empty());
+    assert_eq!(
+        report.absent,
+        [
+            "org.mpris.MediaPlayer2.TrackList",
+            "org.mpris.MediaPlayer2.Playlists"
+        ]
+    );
+    let rendered = report.to_string();
+    assert!(rendered.starts_with("MPRIS support of jukebox, by introspection:\n"));
+    assert!(rendered.contains("org.mpris.MediaPlayer2.TrackList is absent\n"));
+
+    // The mock player can't be introspected, so its properties are
+    // probed instead
+    let mock_conn = bus.connect();
+    let vlc = MockPlayer::register("vlc", &mock_conn).await.unwrap();
+    let player = Player::try_new("vlc", &conn).await.unwrap();
+    let report = player.support_report().await.unwrap();
+    assert_eq!(report.source, SupportSource::Probing);
+    let interface = report.interface("org.mpris.MediaPlayer2.Player").unwrap();
+    assert_eq!(interface.methods, None);
+    assert!(interface.properties.contains(&"PlaybackStatus".to_string()));
+    assert!(report.to_string().contains("  Methods: unknown\n"));
+
+    // Players that quit fail the report, rather than showing nothing
+    drop(vlc);
+    assert!(
+        common::eventually(|| async {
+            matches!(player.support_report().await, Err(Error::PlayerGone { .. }))
+        })
+        .await
+    );
+}
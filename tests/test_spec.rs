@@ -0,0 +1,283 @@
+//! Generates `src/spec/generated.rs` from the introspection XML in
+//! `spec/`, and checks that the committed tables match it. Set
+//! `PRIS_REGENERATE` to write them out instead.
+use std::{env, fmt::Write, fs, path::Path};
+
+const GENERATED: &str = "src/spec/generated.rs";
+const EMITS_CHANGED: &str = "org.freedesktop.DBus.Property.EmitsChangedSignal";
+const OPTIONAL: &str = "org.mpris.MediaPlayer2.property.optional";
+
+/// An element of an XML document, without its text.
+struct Element {
+    name: String,
+    attrs: Vec<(String, String)>,
+    children: Vec<Element>,
+}
+
+impl Element {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    /// The value of the annotation `name` on this element.
+    fn annotation(&self, name: &str) -> Option<&str> {
+        self.children("annotation")
+            .find(|annotation| annotation.attr("name") == Some(name))
+            .and_then(|annotation| annotation.attr("value"))
+    }
+}
+
+/// Parses the subset of XML that introspection data is written in: no
+/// CDATA, and quoted attributes.
+fn parse(xml: &str) -> Element {
+    let mut stack = vec![Element {
+        name: String::new(),
+        attrs: Vec::new(),
+        children: Vec::new(),
+    }];
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        let skip_to = |rest: &str, end: &str| {
+            let at = rest.find(end).expect("unterminated markup");
+            at + end.len()
+        };
+        if rest.starts_with("<!--") {
+            rest = &rest[skip_to(rest, "-->")..];
+            continue;
+        }
+        if rest.starts_with("<?") || rest.starts_with("<!") {
+            rest = &rest[skip_to(rest, ">")..];
+            continue;
+        }
+
+        let end = skip_to(rest, ">");
+        let tag = &rest[1..end - 1];
+        rest = &rest[end..];
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let element = stack.pop().unwrap();
+            assert_eq!(element.name, name.trim(), "mismatched closing tag");
+            stack.last_mut().unwrap().children.push(element);
+            continue;
+        }
+
+        let closed = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let (name, mut attrs) = tag.split_at(tag.find(char::is_whitespace).unwrap_or(tag.len()));
+        let mut element = Element {
+            name: name.to_string(),
+            attrs: Vec::new(),
+            children: Vec::new(),
+        };
+        while let Some(eq) = attrs.find('=') {
+            let key = attrs[..eq].trim().to_string();
+            let value = &attrs[eq + 1..].trim_start()[1..];
+            let close = value.find('"').expect("unterminated attribute");
+            element.attrs.push((key, unescape(&value[..close])));
+            attrs = &value[close + 1..];
+        }
+
+        if closed {
+            stack.last_mut().unwrap().children.push(element);
+        } else {
+            stack.push(element);
+        }
+    }
+
+    assert_eq!(stack.len(), 1, "unclosed element");
+    stack.pop().unwrap().children.pop().expect("empty document")
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// The name of the constant for `interface`: `ROOT` for the root
+/// interface, and its last component in screaming snake case for
+/// the others.
+fn const_name(interface: &str) -> String {
+    match interface.strip_prefix("org.mpris.MediaPlayer2.") {
+        None => "ROOT".to_string(),
+        Some(name) => {
+            let mut out = String::new();
+            for (i, c) in name.chars().enumerate() {
+                if c.is_uppercase() && i > 0 {
+                    out.push('_');
+                }
+                out.push(c.to_ascii_uppercase());
+            }
+            out
+        }
+    }
+}
+
+fn emits_changed(value: &str) -> &'static str {
+    match value {
+        "true" => "True",
+        "invalidates" => "Invalidates",
+        "const" => "Const",
+        "false" => "False",
+        other => panic!("unknown EmitsChangedSignal value {:?}", other),
+    }
+}
+
+fn write_args(out: &mut String, args: &Element, signal: bool) {
+    let args: Vec<_> = args.children("arg").collect();
+    if args.is_empty() {
+        writeln!(out, "            args: &[],").unwrap();
+        return;
+    }
+    writeln!(out, "            args: &[").unwrap();
+    for arg in args {
+        let direction = match arg.attr("direction") {
+            _ if signal => "Out",
+            Some("in") | None => "In",
+            Some("out") => "Out",
+            Some(other) => panic!("unknown direction {:?}", other),
+        };
+        writeln!(
+            out,
+            "                Arg {{ name: {:?}, signature: {:?}, direction: Direction::{} }},",
+            arg.attr("name").expect("arg without a name"),
+            arg.attr("type").expect("arg without a type"),
+            direction,
+        )
+        .unwrap();
+    }
+    writeln!(out, "            ],").unwrap();
+}
+
+fn write_interface(out: &mut String, file: &str, interface: &Element) {
+    let name = interface.attr("name").expect("interface without a name");
+    let default_emits = interface.annotation(EMITS_CHANGED).unwrap_or("true");
+
+    writeln!(out).unwrap();
+    writeln!(out, "/// `{}`, from `spec/{}`.", name, file).unwrap();
+    writeln!(
+        out,
+        "pub(crate) const {}: Interface = Interface {{",
+        const_name(name)
+    )
+    .unwrap();
+    writeln!(out, "    name: {:?},", name).unwrap();
+
+    writeln!(out, "    methods: &[").unwrap();
+    for method in interface.children("method") {
+        writeln!(out, "        Method {{").unwrap();
+        writeln!(out, "            name: {:?},", method.attr("name").unwrap()).unwrap();
+        write_args(out, method, false);
+        writeln!(out, "        }},").unwrap();
+    }
+    writeln!(out, "    ],").unwrap();
+
+    writeln!(out, "    properties: &[").unwrap();
+    for property in interface.children("property") {
+        let access = match property.attr("access") {
+            Some("read") => "Read",
+            Some("write") => "Write",
+            Some("readwrite") => "ReadWrite",
+            other => panic!("unknown access {:?}", other),
+        };
+        let emits = property.annotation(EMITS_CHANGED).unwrap_or(default_emits);
+        let optional = property.annotation(OPTIONAL) == Some("true");
+        writeln!(
+            out,
+            "        Property {{ name: {:?}, signature: {:?}, access: Access::{}, emits_changed: EmitsChanged::{}, optional: {} }},",
+            property.attr("name").unwrap(),
+            property.attr("type").unwrap(),
+            access,
+            emits_changed(emits),
+            optional,
+        )
+        .unwrap();
+    }
+    writeln!(out, "    ],").unwrap();
+
+    writeln!(out, "    signals: &[").unwrap();
+    for signal in interface.children("signal") {
+        writeln!(out, "        Signal {{").unwrap();
+        writeln!(out, "            name: {:?},", signal.attr("name").unwrap()).unwrap();
+        write_args(out, signal, true);
+        writeln!(out, "        }},").unwrap();
+    }
+    writeln!(out, "    ],").unwrap();
+    writeln!(out, "}};").unwrap();
+}
+
+fn generate(root: &Path) -> String {
+    let mut files: Vec<_> = fs::read_dir(root.join("spec"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|file| file.ends_with(".xml"))
+        .collect();
+    files.sort();
+
+    let mut out = String::from(
+        "// Generated from the XML in spec/ by tests/test_spec.rs; don't edit it by hand.\n\n\
+         use super::{Access, Arg, Direction, EmitsChanged, Interface, Method, Property, Signal};\n",
+    );
+    for file in &files {
+        let xml = fs::read_to_string(root.join("spec").join(file)).unwrap();
+        let node = parse(&xml);
+        assert_eq!(node.name, "node");
+        for interface in node.children("interface") {
+            write_interface(&mut out, file, interface);
+        }
+    }
+    out
+}
+
+#[test]
+fn test_spec_up_to_date() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let generated = generate(root);
+
+    if env::var_os("PRIS_REGENERATE").is_some() {
+        fs::write(root.join(GENERATED), &generated).unwrap();
+        return;
+    }
+    let committed = fs::read_to_string(root.join(GENERATED)).unwrap();
+    assert!(
+        committed == generated,
+        "{} is out of date with spec/; regenerate it with \
+         `PRIS_REGENERATE=1 cargo test --test test_spec`",
+        GENERATED
+    );
+}
+
+#[test]
+fn test_spec_parse() {
+    let node = parse(
+        r#"<?xml version="1.0" ?>
+        <!-- <interface name="commented.Out"/> -->
+        <node><interface name="a.b"><method name="M"><arg type="a{sv}" name="x&amp;y"/></method></interface></node>"#,
+    );
+    let interface = node.children("interface").next().unwrap();
+    assert_eq!(node.children("interface").count(), 1);
+    assert_eq!(interface.attr("name"), Some("a.b"));
+    let arg = interface
+        .children("method")
+        .next()
+        .unwrap()
+        .children("arg")
+        .next()
+        .unwrap();
+    assert_eq!(arg.attr("type"), Some("a{sv}"));
+    assert_eq!(arg.attr("name"), Some("x&y"));
+
+    assert_eq!(const_name("org.mpris.MediaPlayer2"), "ROOT");
+    assert_eq!(const_name("org.mpris.MediaPlayer2.TrackList"), "TRACK_LIST");
+}
@@ -0,0 +1,136 @@
+#![cfg(feature = "tracing")]
+mod common;
+
+use pris::{testing::MockPlayer, EventManager, EventType, Player};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Subscriber,
+};
+
+type Fields = BTreeMap<String, String>;
+
+/// Every span and event, with their fields as text.
+#[derive(Default)]
+struct Captured {
+    spans: Vec<(String, Fields)>,
+    events: Vec<Fields>,
+}
+
+impl Captured {
+    fn span(&self, name: &str, operation: &str) -> Option<&Fields> {
+        self.spans
+            .iter()
+            .find(|(span, fields)| {
+                span == name && fields.get("operation").map(String::as_str) == Some(operation)
+            })
+            .map(|(_, fields)| fields)
+    }
+
+    /// Whether an event `message` was emitted with `field` set to
+    /// something containing `value`.
+    fn has_event(&self, message: &str, field: &str, value: &str) -> bool {
+        self.events.iter().any(|fields| {
+            fields.get("message").map(String::as_str) == Some(message)
+                && fields.get(field).is_some_and(|v| v.contains(value))
+        })
+    }
+}
+
+struct Capture(Arc<Mutex<Captured>>);
+
+struct Text<'a>(&'a mut Fields);
+
+impl Visit for Text<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+impl Subscriber for Capture {
+    fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut captured = self.0.lock().unwrap();
+        let mut fields = Fields::new();
+        span.record(&mut Text(&mut fields));
+        captured
+            .spans
+            .push((span.metadata().name().to_string(), fields));
+        Id::from_u64(captured.spans.len() as u64)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut captured = self.0.lock().unwrap();
+        let index = span.into_u64() as usize - 1;
+        values.record(&mut Text(&mut captured.spans[index].1));
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &tracing::Event<'_>) {
+        let mut fields = Fields::new();
+        event.record(&mut Text(&mut fields));
+        self.0.lock().unwrap().events.push(fields);
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[tokio::test]
+async fn test_tracing() {
+    let captured = Arc::new(Mutex::new(Captured::default()));
+    let _default = tracing::subscriber::set_default(Capture(captured.clone()));
+
+    let bus = common::TestBus::new();
+    let mock_conn = bus.connect();
+    let mock = MockPlayer::register("vlc", &mock_conn).await.unwrap();
+    let conn = bus.connect();
+    let player = Player::try_new("vlc", &conn).await.unwrap();
+
+    player.pause().await.unwrap();
+    player.get_property::<bool>("Nope").await.unwrap_err();
+    {
+        let captured = captured.lock().unwrap();
+        let pause = captured.span("pris.call", "Pause").unwrap();
+        assert_eq!(pause["player"], "vlc");
+        assert!(pause.contains_key("duration_ms"));
+        assert!(!pause.contains_key("error_kind"));
+        let nope = captured.span("pris.call", "Nope").unwrap();
+        assert_eq!(nope["error_kind"], "unknown_property");
+        assert!(captured.has_event("call failed", "error_kind", "unknown_property"));
+    }
+
+    let manager = EventManager::new(&conn);
+    let (sender, mut received) = tokio::sync::mpsc::unbounded_channel();
+    let guard = manager
+        .add_callback(EventType::Seeked, move |_| sender.send(()).is_ok())
+        .await
+        .unwrap();
+    mock.emit_seeked(Duration::from_secs(1)).unwrap();
+    tokio::time::timeout(Duration::from_secs(5), received.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    drop(guard);
+
+    let captured = captured.lock().unwrap();
+    assert!(captured.has_event("match added", "rule", "member='Seeked'"));
+    assert!(captured.has_event("dispatching callback", "member", "Seeked"));
+    assert!(captured.has_event("match removed", "rule", "member='Seeked'"));
+}
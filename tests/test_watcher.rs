@@ -0,0 +1,604 @@
+mod common;
+
+use dbus::{
+    arg::{RefArg, Variant},
+    channel::{MatchingReceiver, Sender},
+    message::MatchRule,
+};
+use futures::StreamExt;
+use pris::{
+    testing::MockPlayer, CachedPlayer, EventManager, HealthEvent, MilestonePolicy, PlaybackClock,
+    PlaybackStatus, Player, PlayerHealth, PlayerStateWatcher, PositionEstimate, PositionTicks,
+    ProgressMilestone, ProgressMilestones,
+};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+fn secs(secs: f64) -> Duration {
+    Duration::from_secs_f64(secs)
+}
+
+/// Compares durations to the millisecond, to absorb float rounding.
+fn assert_near(actual: Duration, expected: Duration) {
+    assert!(
+        actual.abs_diff(expected) < Duration::from_millis(1),
+        "{:?} != {:?}",
+        actual,
+        expected
+    );
+}
+
+#[test]
+fn test_clock_playing_and_paused() {
+    let start = Instant::now();
+    let at = |s: f64| start + secs(s);
+    let mut clock = PlaybackClock::new(secs(10.0), PlaybackStatus::Playing, 1.0, start);
+    assert_near(clock.position_at(at(5.0)), secs(15.0));
+
+    clock.set_status(PlaybackStatus::Paused, at(5.0));
+    assert_near(clock.position_at(at(60.0)), secs(15.0));
+
+    clock.set_status(PlaybackStatus::Playing, at(60.0));
+    assert_near(clock.position_at(at(62.0)), secs(17.0));
+    // Time before the last change doesn't move the position back
+    assert_near(clock.position_at(at(1.0)), secs(15.0));
+
+    clock.set_status(PlaybackStatus::Stopped, at(70.0));
+    assert_near(clock.position_at(at(80.0)), Duration::ZERO);
+}
+
+#[test]
+fn test_clock_rate_changes() {
+    let start = Instant::now();
+    let at = |s: f64| start + secs(s);
+    let mut clock = PlaybackClock::new(Duration::ZERO, PlaybackStatus::Playing, 2.0, start);
+    assert_near(clock.position_at(at(3.0)), secs(6.0));
+
+    // Time played at the old rate is kept
+    clock.set_rate(0.5, at(3.0));
+    assert_near(clock.position_at(at(7.0)), secs(8.0));
+
+    clock.set_rate(-1.0, at(7.0));
+    assert_near(clock.position_at(at(9.0)), secs(6.0));
+    assert_near(clock.position_at(at(100.0)), Duration::ZERO);
+
+    // Rate changes while paused only apply once playing again
+    clock.set_status(PlaybackStatus::Paused, at(100.0));
+    clock.set_rate(1.0, at(110.0));
+    clock.set_status(PlaybackStatus::Playing, at(120.0));
+    assert_near(clock.position_at(at(125.0)), secs(5.0));
+}
+
+#[test]
+fn test_clock_seeks_and_length() {
+    let start = Instant::now();
+    let at = |s: f64| start + secs(s);
+    let mut clock = PlaybackClock::new(secs(1.0), PlaybackStatus::Playing, 1.0, start);
+    clock.set_length(Some(secs(30.0)));
+
+    clock.set_position(secs(25.0), at(2.0));
+    assert_near(clock.position_at(at(3.0)), secs(26.0));
+    assert_near(clock.position_at(at(60.0)), secs(30.0));
+
+    // Seeking while paused moves the position without resuming
+    clock.set_status(PlaybackStatus::Paused, at(4.0));
+    clock.set_position(secs(5.0), at(5.0));
+    assert_near(clock.position_at(at(50.0)), secs(5.0));
+    assert_eq!(clock.status(), PlaybackStatus::Paused);
+}
+
+#[test]
+fn test_estimate_rates() {
+    let start = Instant::now();
+    let at = |s: f64| start + secs(s);
+    let estimate = PositionEstimate::new(secs(10.0), start, 1.5, PlaybackStatus::Playing);
+    assert_near(estimate.at(at(2.0)), secs(13.0));
+    // Instants before the capture don't move the position back
+    assert_near(estimate.at(start - secs(5.0)), secs(10.0));
+
+    let slow = PositionEstimate {
+        rate: 0.25,
+        ..estimate
+    };
+    assert_near(slow.at(at(8.0)), secs(12.0));
+
+    let rewinding = PositionEstimate {
+        rate: -2.0,
+        ..estimate
+    };
+    assert_near(rewinding.at(at(3.0)), secs(4.0));
+    assert_near(rewinding.at(at(30.0)), Duration::ZERO);
+
+    let bounded = estimate.with_length(Some(secs(20.0)));
+    assert_near(bounded.at(at(4.0)), secs(16.0));
+    assert_near(bounded.at(at(60.0)), secs(20.0));
+}
+
+#[test]
+fn test_estimate_statuses() {
+    let start = Instant::now();
+    let at = |s: f64| start + secs(s);
+    let paused = PositionEstimate::new(secs(10.0), start, 2.0, PlaybackStatus::Paused);
+    assert_near(paused.at(at(30.0)), secs(10.0));
+    let stopped = PositionEstimate {
+        status: PlaybackStatus::Stopped,
+        ..paused
+    };
+    assert_near(stopped.at(at(30.0)), secs(10.0));
+
+    // A clock's snapshot follows it through status changes
+    let mut clock = PlaybackClock::new(secs(10.0), PlaybackStatus::Playing, 2.0, start);
+    clock.set_status(PlaybackStatus::Paused, at(5.0));
+    let estimate = clock.estimate();
+    assert_eq!(estimate.status, PlaybackStatus::Paused);
+    assert_near(estimate.at(at(50.0)), secs(20.0));
+
+    clock.set_status(PlaybackStatus::Playing, at(50.0));
+    assert_near(clock.estimate().at(at(51.0)), secs(22.0));
+    // Earlier snapshots are unaffected
+    assert_near(estimate.at(at(51.0)), secs(20.0));
+
+    clock.set_status(PlaybackStatus::Stopped, at(60.0));
+    assert_near(clock.estimate().at(at(70.0)), Duration::ZERO);
+}
+
+async fn update(watcher: &mut PlayerStateWatcher<'_>) -> Option<Vec<String>> {
+    tokio::time::timeout(Duration::from_secs(5), watcher.update())
+        .await
+        .unwrap()
+}
+
+async fn next_tick(ticks: &mut PositionTicks<'_>) -> Option<Duration> {
+    tokio::time::timeout(Duration::from_secs(5), ticks.next())
+        .await
+        .unwrap()
+}
+
+async fn next_milestone(milestones: &mut ProgressMilestones<'_>) -> Option<ProgressMilestone> {
+    tokio::time::timeout(Duration::from_secs(5), milestones.next())
+        .await
+        .unwrap()
+}
+
+fn track(id: &str, length: i64) -> dbus::arg::PropMap {
+    common::props(vec![
+        (
+            "mpris:trackid",
+            common::var(dbus::Path::from(id.to_string())),
+        ),
+        ("mpris:length", common::var(length)),
+    ])
+}
+
+#[tokio::test]
+async fn test_watcher() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let served = common::serve_properties(
+        &emitter,
+        common::props(vec![
+            ("PlaybackStatus", common::var("Paused".to_string())),
+            ("Volume", common::var(0.5f64)),
+            ("Rate", common::var(1.0f64)),
+            ("Position", common::var(10_000_000i64)),
+            ("Metadata", common::var(track("/track/1", 60_000_000))),
+        ]),
+    );
+    let manager = EventManager::new(&conn);
+    let player = Player::try_new("test", &conn).await.unwrap();
+    let mut watcher = PlayerStateWatcher::new(&manager, &player).await.unwrap();
+
+    assert_eq!(watcher.status(), Some(PlaybackStatus::Paused));
+    assert_eq!(watcher.volume(), Some(0.5));
+    assert_eq!(watcher.position(), secs(10.0));
+    assert!(watcher.metadata().unwrap().contains_key("mpris:trackid"));
+
+    let notified = Arc::new(Mutex::new(Vec::new()));
+    {
+        let notified = notified.clone();
+        watcher.on_change(move |_, changed| notified.lock().unwrap().push(changed.to_vec()));
+    }
+
+    common::emit(
+        &emitter,
+        common::properties_changed(
+            ":1.1",
+            common::PLAYER_INTERFACE,
+            common::props(vec![("Volume", common::var(0.8f64))]),
+            vec![],
+        ),
+    );
+    assert_eq!(update(&mut watcher).await, Some(vec!["Volume".to_string()]));
+    assert_eq!(watcher.volume(), Some(0.8));
+
+    common::emit(&emitter, common::seeked(":1.1", 30_000_000i64));
+    assert_eq!(
+        update(&mut watcher).await,
+        Some(vec!["Position".to_string()])
+    );
+    assert_eq!(watcher.position(), secs(30.0));
+
+    // A new track restarts the clock, and invalidated values are fetched
+    served.lock().unwrap().insert(
+        "Metadata".to_string(),
+        common::var(track("/track/2", 90_000_000)),
+    );
+    common::emit(
+        &emitter,
+        common::properties_changed(
+            ":1.1",
+            common::PLAYER_INTERFACE,
+            common::props(vec![("PlaybackStatus", common::var("Playing".to_string()))]),
+            vec!["Metadata"],
+        ),
+    );
+    assert_eq!(
+        update(&mut watcher).await,
+        Some(vec!["PlaybackStatus".to_string(), "Metadata".to_string()])
+    );
+    assert_eq!(watcher.status(), Some(PlaybackStatus::Playing));
+    assert!(watcher.position() < secs(5.0));
+    let trackid = watcher.metadata().unwrap()["mpris:trackid"]
+        .0
+        .as_str()
+        .map(str::to_string);
+    assert_eq!(trackid.as_deref(), Some("/track/2"));
+
+    assert_eq!(notified.lock().unwrap().len(), 3);
+
+    emitter
+        .release_name("org.mpris.MediaPlayer2.test")
+        .await
+        .unwrap();
+    assert_eq!(update(&mut watcher).await, None);
+    assert!(watcher.is_gone());
+}
+
+#[tokio::test]
+async fn test_watch_channel() {
+    let bus = common::TestBus::new();
+    let mock_conn = bus.connect();
+    let mock = MockPlayer::register("vlc", &mock_conn).await.unwrap();
+    let conn = bus.connect();
+    let manager = EventManager::new(&conn);
+    let player = Player::try_new("vlc", &conn).await.unwrap();
+    let watcher = PlayerStateWatcher::new(&manager, &player).await.unwrap();
+    let (mut states, feed) = watcher.watch_channel();
+    assert_eq!(
+        states.borrow().playback_status,
+        Some(PlaybackStatus::Stopped)
+    );
+
+    let watching = async move {
+        mock.set_property("PlaybackStatus", "Playing".to_string());
+        mock.emit_properties_changed(&["PlaybackStatus"]).unwrap();
+        let changed = tokio::time::timeout(Duration::from_secs(5), states.changed());
+        changed.await.unwrap().unwrap();
+        assert_eq!(
+            states.borrow().playback_status,
+            Some(PlaybackStatus::Playing)
+        );
+
+        // The player leaving sends an empty state, then closes the channel
+        drop(mock);
+        let changed = tokio::time::timeout(Duration::from_secs(5), states.changed());
+        changed.await.unwrap().unwrap();
+        assert_eq!(states.borrow().player, "vlc");
+        assert_eq!(states.borrow().playback_status, None);
+        assert!(states.changed().await.is_err());
+    };
+    tokio::time::timeout(
+        Duration::from_secs(10),
+        futures::future::join(feed, watching),
+    )
+    .await
+    .unwrap();
+
+    // Dropping every receiver stops the feed
+    let mock = MockPlayer::register("mpv", &mock_conn).await.unwrap();
+    let player = Player::try_new("mpv", &conn).await.unwrap();
+    let watcher = PlayerStateWatcher::new(&manager, &player).await.unwrap();
+    let (states, feed) = watcher.watch_channel();
+    let copy = states.clone();
+    drop(states);
+    drop(copy);
+    tokio::time::timeout(Duration::from_secs(5), feed)
+        .await
+        .unwrap();
+    drop(mock);
+}
+
+#[tokio::test]
+async fn test_position_ticks() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let served = common::serve_properties(
+        &emitter,
+        common::props(vec![
+            ("PlaybackStatus", common::var("Paused".to_string())),
+            ("Rate", common::var(1.0f64)),
+            ("Position", common::var(5_000_000i64)),
+        ]),
+    );
+    let manager = EventManager::new(&conn);
+    let player = Player::try_new("test", &conn).await.unwrap();
+    let mut ticks = manager
+        .position_ticks(&player, Duration::from_secs(60))
+        .await
+        .unwrap();
+    assert_eq!(next_tick(&mut ticks).await, Some(secs(5.0)));
+
+    common::emit(&emitter, common::seeked(":1.1", 20_000_000i64));
+    assert_eq!(next_tick(&mut ticks).await, Some(secs(20.0)));
+
+    // A rate change makes the position be fetched again
+    served
+        .lock()
+        .unwrap()
+        .insert("Position".to_string(), common::var(40_000_000i64));
+    common::emit(
+        &emitter,
+        common::properties_changed(
+            ":1.1",
+            common::PLAYER_INTERFACE,
+            common::props(vec![("Rate", common::var(2.0f64))]),
+            vec![],
+        ),
+    );
+    assert_eq!(next_tick(&mut ticks).await, Some(secs(20.0)));
+    assert_eq!(next_tick(&mut ticks).await, Some(secs(40.0)));
+
+    emitter
+        .release_name("org.mpris.MediaPlayer2.test")
+        .await
+        .unwrap();
+    assert_eq!(next_tick(&mut ticks).await, None);
+}
+
+#[tokio::test]
+async fn test_progress_milestones() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let _served = common::serve_properties(
+        &emitter,
+        common::props(vec![
+            ("PlaybackStatus", common::var("Playing".to_string())),
+            ("Metadata", common::var(track("/track/1", 60_000_000))),
+        ]),
+    );
+    let manager = EventManager::new(&conn);
+    let player = Player::try_new("test", &conn).await.unwrap();
+    // Without a threshold to play through, every track is reached at once
+    let policy = MilestonePolicy {
+        fraction: None,
+        duration: Some(Duration::ZERO),
+    };
+    let mut milestones = manager.progress_milestones(&player, policy).await.unwrap();
+    let first = next_milestone(&mut milestones).await.unwrap();
+    assert_eq!(first.player, "test");
+    assert_eq!(first.length, Some(secs(60.0)));
+
+    common::emit(
+        &emitter,
+        common::properties_changed(
+            ":1.1",
+            common::PLAYER_INTERFACE,
+            common::props(vec![(
+                "Metadata",
+                common::var(track("/track/2", 90_000_000)),
+            )]),
+            vec![],
+        ),
+    );
+    let second = next_milestone(&mut milestones).await.unwrap();
+    assert_eq!(second.length, Some(secs(90.0)));
+
+    emitter
+        .release_name("org.mpris.MediaPlayer2.test")
+        .await
+        .unwrap();
+    assert!(next_milestone(&mut milestones).await.is_none());
+}
+
+#[tokio::test]
+async fn test_player_health() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    // The player hangs while this is set, leaving calls unanswered
+    let hung = Arc::new(AtomicBool::new(false));
+    {
+        let hung = hung.clone();
+        emitter.start_receive(
+            MatchRule::new_method_call(),
+            Box::new(move |msg, conn| {
+                if !hung.load(Ordering::SeqCst) {
+                    let _ = conn.send(msg.method_return().append1(Variant("Playing")));
+                }
+                true
+            }),
+        );
+    }
+    let player = Player::try_new("test", &conn).await.unwrap();
+    let mut health = PlayerHealth::monitor(&player, Duration::from_millis(50), 2);
+    let next = tokio::time::timeout(Duration::from_millis(300), health.next());
+    assert!(next.await.is_err(), "a responsive player was reported");
+    assert!(health.is_healthy());
+
+    hung.store(true, Ordering::SeqCst);
+    let next = tokio::time::timeout(Duration::from_secs(5), health.next());
+    assert_eq!(
+        next.await.unwrap(),
+        Some(HealthEvent::Unhealthy {
+            player: "test".to_string(),
+            failures: 2,
+        })
+    );
+    assert!(!health.is_healthy());
+
+    hung.store(false, Ordering::SeqCst);
+    let next = tokio::time::timeout(Duration::from_secs(5), health.next());
+    assert_eq!(
+        next.await.unwrap(),
+        Some(HealthEvent::Recovered {
+            player: "test".to_string(),
+        })
+    );
+    assert!(health.is_healthy());
+}
+
+#[tokio::test]
+async fn test_cached_player() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    let served = common::serve_properties(
+        &emitter,
+        common::props(vec![
+            ("Volume", common::var(0.5f64)),
+            ("Shuffle", common::var(false)),
+            ("Position", common::var(10_000_000i64)),
+        ]),
+    );
+    let manager = EventManager::new(&conn);
+    let player = Player::try_new("test", &conn).await.unwrap();
+    let cached = &CachedPlayer::new(&manager, &player).await.unwrap();
+    let set = |name: &str, value: Variant<Box<dyn RefArg>>| {
+        served.lock().unwrap().insert(name.to_string(), value);
+    };
+
+    // Values read once are served from the cache, even if stale
+    assert_eq!(cached.get_property::<f64>("Volume").await.unwrap(), 0.5);
+    set("Volume", common::var(0.6f64));
+    assert_eq!(cached.get_property::<f64>("Volume").await.unwrap(), 0.5);
+    assert!(matches!(
+        cached.get_property::<String>("Volume").await,
+        Err(pris::Error::TypeMismatch { .. })
+    ));
+
+    // Until a signal reports them changed or invalidated
+    common::emit(
+        &emitter,
+        common::properties_changed(
+            ":1.1",
+            common::PLAYER_INTERFACE,
+            common::props(vec![("Volume", common::var(0.8f64))]),
+            vec![],
+        ),
+    );
+    assert!(
+        common::eventually(
+            || async move { cached.get_property::<f64>("Volume").await.unwrap() == 0.8 }
+        )
+        .await
+    );
+    assert!(!cached.get_property::<bool>("Shuffle").await.unwrap());
+    set("Shuffle", common::var(true));
+    common::emit(
+        &emitter,
+        common::properties_changed(
+            ":1.1",
+            common::PLAYER_INTERFACE,
+            common::props(vec![]),
+            vec!["Shuffle"],
+        ),
+    );
+    assert!(
+        common::eventually(|| async move { cached.get_property::<bool>("Shuffle").await.unwrap() })
+            .await
+    );
+
+    // Position always comes from the player
+    assert_eq!(
+        cached.get_property::<i64>("Position").await.unwrap(),
+        10_000_000
+    );
+    set("Position", common::var(20_000_000i64));
+    assert_eq!(
+        cached.get_property::<i64>("Position").await.unwrap(),
+        20_000_000
+    );
+
+    // A forced refresh bypasses the cache and refills it
+    set("Volume", common::var(0.3f64));
+    assert_eq!(cached.get_property::<f64>("Volume").await.unwrap(), 0.8);
+    assert_eq!(cached.refresh::<f64>("Volume").await.unwrap(), 0.3);
+    set("Volume", common::var(0.4f64));
+    assert_eq!(cached.get_property::<f64>("Volume").await.unwrap(), 0.3);
+    cached.clear();
+    assert_eq!(cached.get_property::<f64>("Volume").await.unwrap(), 0.4);
+
+    // Once the name changes hands, nothing is cached
+    assert!(cached.is_tracking());
+    emitter
+        .release_name("org.mpris.MediaPlayer2.test")
+        .await
+        .unwrap();
+    assert!(common::eventually(|| async move { !cached.is_tracking() }).await);
+}
+
+#[tokio::test]
+async fn test_guarded_commands() {
+    let bus = common::TestBus::new();
+    let conn = bus.connect();
+    let emitter = bus.connect_as("test").await;
+    common::serve_player(
+        &emitter,
+        common::props(vec![
+            ("CanControl", common::var(true)),
+            ("CanGoNext", common::var(true)),
+            ("CanGoPrevious", common::var(false)),
+        ]),
+        |msg, _| match msg.member().as_deref() {
+            Some("Next") => Some(msg.method_return()),
+            _ => None,
+        },
+    );
+    let manager = EventManager::new(&conn);
+    let player = Player::try_new("test", &conn).await.unwrap();
+    let cached = &CachedPlayer::new(&manager, &player).await.unwrap();
+    let calls = common::calls_from(&bus, &conn.unique_name()).await;
+
+    // The capabilities are read once, then served from the cache
+    let first = cached.try_next().await.unwrap();
+    assert!(first.sent && !first.cached);
+    calls.lock().unwrap().clear();
+    let second = cached.try_next().await.unwrap();
+    assert!(second.sent && second.cached);
+    assert_eq!(*calls.lock().unwrap(), vec!["Next"]);
+
+    // A missing capability holds the command back
+    let previous = cached.try_previous().await.unwrap();
+    assert!(!previous.sent);
+    assert_eq!(previous.capability, "CanGoPrevious");
+
+    // And a signalled change is picked up without asking the player
+    common::emit(
+        &emitter,
+        common::properties_changed(
+            ":1.1",
+            common::PLAYER_INTERFACE,
+            common::props(vec![("CanGoNext", common::var(false))]),
+            vec![],
+        ),
+    );
+    assert!(
+        common::eventually(|| async move {
+            let guarded = cached.try_next().await.unwrap();
+            !guarded.sent && guarded.cached
+        })
+        .await
+    );
+
+    // Without a cache, the capabilities are always read from the player
+    let uncached = player.try_previous().await.unwrap();
+    assert!(!uncached.sent && !uncached.cached);
+}
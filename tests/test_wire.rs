@@ -0,0 +1,129 @@
+#![cfg(feature = "serde")]
+mod common;
+
+use dbus::{arg::cast, Path};
+use pris::{
+    ChangedProperties, Event, LifecycleEvent, PlaybackStatus, PropertiesChangedEvent, SeekedEvent,
+};
+use std::time::Duration;
+
+fn round_trip(event: &Event) -> Event {
+    let line = serde_json::to_string(event).unwrap();
+    serde_json::from_str(&line).unwrap()
+}
+
+#[test]
+fn test_wire_format() {
+    let seeked = Event::Seeked(SeekedEvent {
+        player: "vlc".to_string(),
+        position: Duration::from_secs(30),
+    });
+    assert_eq!(
+        serde_json::to_value(&seeked).unwrap(),
+        serde_json::json!({"version": 1, "type": "Seeked", "player": "vlc", "position": 30000000})
+    );
+    match round_trip(&seeked) {
+        Event::Seeked(event) => assert_eq!(event.position, Duration::from_secs(30)),
+        other => panic!("expected a Seeked event, got {:?}", other),
+    }
+
+    let appeared = Event::PlayerLifecycle(LifecycleEvent::Appeared {
+        name: "mpv".to_string(),
+    });
+    assert_eq!(
+        serde_json::to_value(&appeared).unwrap(),
+        serde_json::json!({"version": 1, "type": "PlayerLifecycle", "change": "Appeared", "name": "mpv"})
+    );
+    match round_trip(&appeared) {
+        Event::PlayerLifecycle(event) => assert_eq!(
+            event,
+            LifecycleEvent::Appeared {
+                name: "mpv".to_string()
+            }
+        ),
+        other => panic!("expected a PlayerLifecycle event, got {:?}", other),
+    }
+
+    let metadata = common::props(vec![
+        (
+            "mpris:trackid",
+            common::var(Path::from("/org/videolan/vlc/3")),
+        ),
+        ("mpris:length", common::var(360_000_000i64)),
+        ("xesam:artist", common::var(vec!["Aphex Twin".to_string()])),
+        ("xesam:title", common::var("Flim".to_string())),
+    ]);
+    let changed = Event::PropertiesChanged(PropertiesChangedEvent {
+        player: "vlc".to_string(),
+        properties: ChangedProperties {
+            interface: common::PLAYER_INTERFACE.to_string(),
+            playback_status: Some(PlaybackStatus::Playing),
+            metadata: Some(metadata),
+            other: common::props(vec![("TrackCount", common::var(12u32))]),
+            invalidated: vec!["Volume".to_string()],
+            ..ChangedProperties::default()
+        },
+    });
+    let value = serde_json::to_value(&changed).unwrap();
+    assert_eq!(
+        value["changed"]["PlaybackStatus"],
+        serde_json::json!(["s", "Playing"])
+    );
+    assert_eq!(
+        value["changed"]["Metadata"][1]["mpris:trackid"],
+        serde_json::json!(["o", "/org/videolan/vlc/3"])
+    );
+
+    // Values come back with their D-Bus types
+    let properties = match round_trip(&changed) {
+        Event::PropertiesChanged(event) => event.properties,
+        other => panic!("expected a PropertiesChanged event, got {:?}", other),
+    };
+    assert_eq!(properties.playback_status, Some(PlaybackStatus::Playing));
+    assert_eq!(properties.invalidated, vec!["Volume".to_string()]);
+    let metadata = properties.metadata.unwrap();
+    assert_eq!(
+        pris::prop_cast::<Path>(&metadata, "mpris:trackid").map(ToString::to_string),
+        Some("/org/videolan/vlc/3".to_string())
+    );
+    assert_eq!(
+        pris::prop_cast::<i64>(&metadata, "mpris:length"),
+        Some(&360_000_000)
+    );
+    assert_eq!(
+        pris::prop_cast::<Vec<String>>(&metadata, "xesam:artist"),
+        Some(&vec!["Aphex Twin".to_string()])
+    );
+    assert_eq!(cast::<u32>(&*properties.other["TrackCount"].0), Some(&12));
+}
+
+#[test]
+fn test_wire_versions() {
+    // Fields added later are ignored
+    let line = r#"{"version": 1, "type": "Seeked", "player": "vlc", "position": 5, "extra": true}"#;
+    assert!(serde_json::from_str::<Event>(line).is_ok());
+
+    let line = r#"{"version": 2, "type": "Seeked", "player": "vlc", "position": 5}"#;
+    let error = serde_json::from_str::<Event>(line).unwrap_err();
+    assert!(error.to_string().contains("version 2"));
+
+    let line = r#"{"version": 1, "type": "PropertiesChanged", "player": "vlc",
+        "interface": "org.mpris.MediaPlayer2.Player", "changed": {"Volume": ["s", 0.5]}}"#;
+    assert!(serde_json::from_str::<Event>(line).is_err());
+}
+
+#[test]
+fn test_selection_memory_round_trip() {
+    let memory = pris::SelectionMemory {
+        bus_name: Some("org.mpris.MediaPlayer2.mpd".to_string()),
+        identity: Some("Music Player Daemon".to_string()),
+    };
+    let stored = serde_json::to_string(&memory).unwrap();
+    assert_eq!(
+        serde_json::from_str::<pris::SelectionMemory>(&stored).unwrap(),
+        memory
+    );
+    let empty: pris::SelectionMemory =
+        serde_json::from_str(r#"{"bus_name": null, "identity": null}"#).unwrap();
+    assert_eq!(empty, pris::SelectionMemory::new());
+}
@@ -0,0 +1,83 @@
+#![cfg(feature = "zbus")]
+
+mod common;
+
+use pris::zbus::{self as backend, Player};
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_zbus_player() {
+    let bus = common::TestBus::new();
+    let vlc = bus.connect_as("vlc").await;
+    let served = common::serve_player(
+        &vlc,
+        common::props(vec![
+            ("PlaybackStatus", common::var("Playing".to_string())),
+            ("CanPause", common::var(true)),
+            ("Volume", common::var(0.5f64)),
+            ("Position", common::var(10_000_000i64)),
+            ("Metadata", common::var(common::captured_metadata())),
+        ]),
+        |msg, properties| match msg.member().as_deref() {
+            Some("Pause") => Some(msg.method_return()),
+            Some("Set") => {
+                let (_, name, value): (String, String, _) = msg.read3().unwrap();
+                properties.insert(name, value);
+                Some(msg.method_return())
+            }
+            _ => None,
+        },
+    );
+    let mpv = bus.connect_as("mpv").await;
+    common::serve_error(&mpv, "org.freedesktop.DBus.Error.NoReply");
+
+    let conn = zbus::ConnectionBuilder::address(bus.address.as_str())
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    let mut names: Vec<String> = backend::get_all_players(&conn)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|player| player.name)
+        .collect();
+    names.sort();
+    assert_eq!(names, vec!["mpv", "vlc"]);
+    assert!(matches!(
+        Player::try_new("nope", &conn).await,
+        Err(pris::Error::UnknownPlayer { requested, .. }) if requested == "nope"
+    ));
+
+    // Values come out as they do on the dbus backend
+    let player = Player::try_new("vlc", &conn).await.unwrap();
+    let metadata = player.get_metadata().await.unwrap();
+    let expected = common::captured_metadata();
+    assert_eq!(
+        pris::prop_str(&metadata, "xesam:title"),
+        pris::prop_str(&expected, "xesam:title")
+    );
+    let state = player.get_state().await.unwrap();
+    assert_eq!(state.volume, Some(0.5));
+    assert_eq!(state.position, Some(Duration::from_secs(10)));
+    assert_eq!(
+        player.get_position().await.unwrap(),
+        Duration::from_secs(10)
+    );
+
+    // Properties are written and clamped, and commands fall back
+    assert_eq!(player.set_volume(1.5).await.unwrap(), 1.0);
+    assert_eq!(served.lock().unwrap()["Volume"].0.as_f64(), Some(1.0));
+    assert!(matches!(
+        player.get_property::<String>("Volume").await,
+        Err(pris::Error::TypeMismatch { .. })
+    ));
+    player.play_pause().await.unwrap();
+
+    // And errors are classified the same way
+    let player = Player::try_new("mpv", &conn).await.unwrap();
+    assert!(matches!(
+        player.next().await,
+        Err(pris::Error::Timeout { player, .. }) if player.as_deref() == Some("mpv")
+    ));
+}